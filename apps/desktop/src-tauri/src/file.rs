@@ -1,13 +1,14 @@
-use sd_core::Node;
+use sd_core::{Node, OpenWith, OpenWithError};
 use sd_prisma::prisma::{file_path, location};
 
 use std::{
 	collections::{BTreeSet, HashMap, HashSet},
 	hash::{Hash, Hasher},
-	path::PathBuf,
+	path::{Path, PathBuf},
 	sync::Arc,
 };
 
+use async_trait::async_trait;
 use futures::future::join_all;
 use serde::Serialize;
 use specta::Type;
@@ -371,25 +372,160 @@ pub async fn open_ephemeral_file_with(paths_and_urls: Vec<PathAndUrl>) -> Result
 	Ok(())
 }
 
-fn inner_reveal_paths(paths: impl Iterator<Item = PathBuf>) {
-	for path in paths {
+/// Lets the core list and launch applications for files, using the same platform APIs as the
+/// commands above.
+pub struct DesktopOpenWith;
+
+#[async_trait]
+impl OpenWith for DesktopOpenWith {
+	async fn applications(
+		&self,
+		path: &Path,
+	) -> Result<Vec<sd_core::OpenWithApplication>, OpenWithError> {
+		get_file_path_open_apps_set(path.to_path_buf())
+			.await
+			.map(|apps| {
+				apps.into_iter()
+					.map(|app| sd_core::OpenWithApplication {
+						icon_hint: Some(app.url.clone()),
+						handler_id: app.url,
+						name: app.name,
+					})
+					.collect()
+			})
+			.ok_or_else(|| {
+				OpenWithError::Failed(
+					path.to_path_buf(),
+					"failed to list applications".to_string(),
+				)
+			})
+	}
+
+	async fn open_with(&self, path: &Path, handler_id: &str) -> Result<(), OpenWithError> {
+		if !self
+			.applications(path)
+			.await?
+			.iter()
+			.any(|app| app.handler_id == handler_id)
+		{
+			return Err(OpenWithError::HandlerNotFound(handler_id.to_string()));
+		}
+
+		let path = path.to_path_buf();
+		let url = handler_id.to_string();
+
+		#[cfg(target_os = "macos")]
+		let res = {
+			let Some(path_str) = path.to_str().map(str::to_string) else {
+				return Err(OpenWithError::Failed(
+					path,
+					"path contains non-UTF8 characters".to_string(),
+				));
+			};
+
+			spawn_blocking(move || sd_desktop_macos::open_file_paths_with(&[path_str], &url))
+				.await
+				.map_err(|e| e.to_string())
+		};
+
 		#[cfg(target_os = "linux")]
-		if sd_desktop_linux::is_appimage() {
-			// This is a workaround for the app, when package inside an AppImage, crashing when using opener::reveal.
-			if let Err(e) = sd_desktop_linux::open_file_path(if path.is_file() {
-				path.parent().unwrap_or(&path)
-			} else {
-				&path
-			}) {
-				error!("Failed to open logs dir: {e:#?}");
+		let res = spawn_blocking({
+			let path = path.clone();
+			move || sd_desktop_linux::open_files_path_with(&[path], &url)
+		})
+		.await
+		.map_err(|e| e.to_string())
+		.and_then(|res| res.map_err(|e| e.to_string()));
+
+		#[cfg(target_os = "windows")]
+		let res = spawn_blocking({
+			let path = path.clone();
+			move || sd_desktop_windows::open_file_path_with(path, &url)
+		})
+		.await
+		.map_err(|e| e.to_string())
+		.and_then(|res| res.map_err(|e| e.to_string()));
+
+		res.map_err(|e| {
+			error!(
+				"Failed to open '{}' with '{handler_id}': {e}",
+				path.display()
+			);
+			OpenWithError::Failed(path, e)
+		})
+	}
+
+	async fn open_default(&self, path: &Path) -> Result<(), OpenWithError> {
+		let res = spawn_blocking({
+			let path = path.to_path_buf();
+			move || {
+				#[cfg(target_os = "linux")]
+				{
+					sd_desktop_linux::open_file_path(path).map_err(|e| e.to_string())
+				}
+
+				#[cfg(not(target_os = "linux"))]
+				{
+					opener::open(path).map_err(|e| match e {
+						opener::OpenError::Io(e)
+							if e.kind() == std::io::ErrorKind::PermissionDenied =>
+						{
+							None
+						}
+						e => Some(e.to_string()),
+					})
+				}
 			}
-		} else if let Err(e) = opener::reveal(path) {
-			error!("Failed to open logs dir: {e:#?}");
-		}
+		})
+		.await
+		.map_err(|e| OpenWithError::Failed(path.to_path_buf(), e.to_string()))?;
+
+		#[cfg(target_os = "linux")]
+		return res.map_err(|e| OpenWithError::Failed(path.to_path_buf(), e));
 
 		#[cfg(not(target_os = "linux"))]
-		if let Err(e) = opener::reveal(path) {
-			error!("Failed to open logs dir: {e:#?}");
+		res.map_err(|e| match e {
+			Some(e) => OpenWithError::Failed(path.to_path_buf(), e),
+			None => OpenWithError::Sandboxed(path.to_path_buf()),
+		})
+	}
+
+	async fn reveal(&self, path: &Path) -> Result<(), OpenWithError> {
+		let path = path.to_path_buf();
+
+		spawn_blocking({
+			let path = path.clone();
+			move || reveal_path(&path)
+		})
+		.await
+		.map_err(|e| e.to_string())
+		.and_then(|res| res)
+		.map_err(|e| {
+			error!("Failed to reveal '{}': {e}", path.display());
+			OpenWithError::Failed(path, e)
+		})
+	}
+}
+
+fn reveal_path(path: &Path) -> Result<(), String> {
+	#[cfg(target_os = "linux")]
+	if sd_desktop_linux::is_appimage() {
+		// This is a workaround for the app, when package inside an AppImage, crashing when using opener::reveal.
+		return sd_desktop_linux::open_file_path(if path.is_file() {
+			path.parent().unwrap_or(path)
+		} else {
+			path
+		})
+		.map_err(|e| e.to_string());
+	}
+
+	opener::reveal(path).map_err(|e| e.to_string())
+}
+
+fn inner_reveal_paths(paths: impl Iterator<Item = PathBuf>) {
+	for path in paths {
+		if let Err(e) = reveal_path(&path) {
+			error!("Failed to reveal '{}': {e}", path.display());
 		}
 	}
 }
@@ -182,7 +182,14 @@ async fn main() -> tauri::Result<()> {
 	let (_guard, result) = match Node::init_logger(&data_dir) {
 		Ok(guard) => (
 			Some(guard),
-			Node::new(data_dir, sd_core::Env::new(CLIENT_ID)).await,
+			Node::new(
+				data_dir,
+				sd_core::Env {
+					open_with: Some(Arc::new(file::DesktopOpenWith)),
+					..sd_core::Env::new(CLIENT_ID)
+				},
+			)
+			.await,
 		),
 		Err(err) => (None, Err(NodeError::Logger(err))),
 	};
@@ -20,7 +20,7 @@ use serde::Deserialize;
 use tauri::{async_runtime::block_on, plugin::TauriPlugin, RunEvent, Runtime};
 use thiserror::Error;
 use tokio::{net::TcpListener, task::block_in_place};
-use tracing::info;
+use tracing::{error, info};
 
 /// Inject `window.__SD_ERROR__` so the frontend can render core startup errors.
 /// It's assumed the error happened prior or during settings up the core and rspc.
@@ -106,7 +106,13 @@ pub async fn sd_server_plugin<R: Runtime>(
 		.on_event(move |_app, e| {
 			if let RunEvent::Exit { .. } = e {
 				block_in_place(|| {
-					block_on(node.shutdown());
+					let report = block_on(node.shutdown());
+					if !report.is_clean() {
+						error!(
+							"Subsystems failed to shut down in time: {:?}",
+							report.abandoned
+						);
+					}
 					block_on(tx.send(())).ok();
 				});
 			}
@@ -49,7 +49,7 @@ export const platform = {
 	platform: 'tauri',
 	getThumbnailUrlByThumbKey: (keyParts) =>
 		constructServerUrl(
-			`/thumbnail/${keyParts.map((i) => encodeURIComponent(i)).join('/')}.webp`
+			`/thumbnail/${keyParts.map((i) => encodeURIComponent(i)).join('/')}`
 		),
 	getFileUrl: (libraryId, locationLocalId, filePathId) =>
 		constructServerUrl(`/file/${libraryId}/${locationLocalId}/${filePathId}`),
@@ -16,7 +16,7 @@ import { tw } from '../../lib/tailwind';
 export const getThumbnailUrlByThumbKey = (thumbKey: string[]) =>
 	`${DocumentDirectoryPath}/thumbnails/${thumbKey
 		.map((i) => encodeURIComponent(i))
-		.join('/')}.webp`;
+		.join('/')}`;
 
 const FileThumbWrapper = ({ children, size = 1 }: PropsWithChildren<{ size: number }>) => (
 	<View style={[tw`items-center justify-center`, { width: 80 * size, height: 80 * size }]}>
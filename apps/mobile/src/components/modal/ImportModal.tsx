@@ -2,7 +2,7 @@ import { forwardRef, useCallback } from 'react';
 import { Alert, Platform, Text, View } from 'react-native';
 import DocumentPicker from 'react-native-document-picker';
 import RNFS from 'react-native-fs';
-import { useLibraryMutation } from '@sd/client';
+import { extractLocationCreateError, useLibraryMutation } from '@sd/client';
 import { Modal, ModalRef } from '~/components/layout/Modal';
 import { Button } from '~/components/primitive/Button';
 import useForwardedRef from '~/hooks/useForwardedRef';
@@ -19,7 +19,7 @@ const ImportModal = forwardRef<ModalRef, unknown>((_, ref) => {
 
 	const createLocation = useLibraryMutation('locations.create', {
 		onError: (error, variables) => {
-			switch (error.message) {
+			switch (extractLocationCreateError(error)?.code ?? error.message) {
 				case 'NEED_RELINK':
 					if (!variables.dry_run) relinkLocation.mutate(variables.path);
 					break;
@@ -2,7 +2,8 @@ use std::{env, net::SocketAddr, path::Path};
 
 use axum::routing::get;
 use sd_core::{custom_uri, Node};
-use tracing::info;
+use tokio::sync::oneshot;
+use tracing::{error, info};
 
 mod utils;
 
@@ -44,8 +45,10 @@ async fn main() {
 				std::env::var("SD_API_URL")
 					.unwrap_or_else(|_| "https://app.spacedrive.com".to_string()),
 			),
-			client_id: std::env::var("SD_CLIENT_ID")
-				.unwrap_or_else(|_| "04701823-a498-406e-aef9-22081c1dae34".to_string()),
+			..sd_core::Env::new(
+				&std::env::var("SD_CLIENT_ID")
+					.unwrap_or_else(|_| "04701823-a498-406e-aef9-22081c1dae34".to_string()),
+			)
 		},
 	)
 	.await
@@ -55,7 +58,15 @@ async fn main() {
 			panic!("{}", e.to_string())
 		}
 	};
-	let signal = utils::axum_shutdown_signal(node.clone());
+	let (shutdown_tx, shutdown_rx) = oneshot::channel();
+	let signal = {
+		let node = node.clone();
+		async move {
+			shutdown_tx
+				.send(utils::axum_shutdown_signal(node).await)
+				.ok();
+		}
+	};
 
 	let app = axum::Router::new()
 		.route("/health", get(|| async { "OK" }))
@@ -144,4 +155,13 @@ async fn main() {
 		.with_graceful_shutdown(signal)
 		.await
 		.expect("Error with HTTP server!");
+
+	if let Ok(Err(report)) = shutdown_rx.await {
+		error!(
+			"Subsystems failed to shut down in time: {:?}, forcing exit",
+			report.abandoned
+		);
+		// The abandoned subsystems could keep the runtime from ever shutting down
+		std::process::exit(1);
+	}
 }
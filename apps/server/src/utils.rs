@@ -1,10 +1,13 @@
 use std::sync::Arc;
 
-use sd_core::Node;
+use sd_core::{Node, ShutdownReport};
 use tokio::signal;
+use tracing::info;
 
 /// `shutdown_signal` will inform axum to gracefully shutdown when the process is asked to shutdown.
-pub async fn axum_shutdown_signal(node: Arc<Node>) {
+///
+/// Returns the node's shutdown report as an error if some of it's subsystems had to be abandoned.
+pub async fn axum_shutdown_signal(node: Arc<Node>) -> Result<(), ShutdownReport> {
 	let ctrl_c = async {
 		signal::ctrl_c()
 			.await
@@ -27,6 +30,11 @@ pub async fn axum_shutdown_signal(node: Arc<Node>) {
 		() = terminate => {},
 	}
 
-	println!("signal received, starting graceful shutdown");
-	node.shutdown().await;
+	info!("signal received, starting graceful shutdown");
+	let report = node.shutdown().await;
+	if report.is_clean() {
+		Ok(())
+	} else {
+		Err(report)
+	}
 }
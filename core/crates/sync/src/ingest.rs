@@ -1,4 +1,4 @@
-use std::{ops::Deref, sync::Arc};
+use std::{collections::HashSet, ops::Deref, sync::Arc};
 
 use sd_prisma::{
 	prisma::{crdt_operation, instance, PrismaClient, SortOrder},
@@ -7,6 +7,7 @@ use sd_prisma::{
 use sd_sync::CRDTOperation;
 use serde_json::to_vec;
 use tokio::sync::{mpsc, Mutex};
+use tracing::debug;
 use uhlc::{Timestamp, NTP64};
 use uuid::Uuid;
 
@@ -20,7 +21,7 @@ use crate::{
 /// Stuff that can be handled outside the actor
 pub enum Request {
 	Messages { timestamps: Vec<(Uuid, NTP64)> },
-	Ingested,
+	Ingested { models: HashSet<String> },
 	FinishedIngesting,
 }
 
@@ -70,9 +71,18 @@ impl Actor {
 				State::Ingesting(wait!(self.io.event_rx, Event::Messages(event) => event))
 			}
 			State::Ingesting(event) => {
+				let mut models = HashSet::new();
+
 				for op in event.messages {
-					let fut = self.receive_crdt_operation(op);
-					fut.await;
+					let model = op.model.clone();
+					if self.receive_crdt_operation(op).await {
+						models.insert(model);
+					}
+				}
+
+				// We report once per batch so the frontend isn't flooded when ingesting a large amount of operations
+				if !models.is_empty() {
+					self.io.send(Request::Ingested { models }).await.ok();
 				}
 
 				match event.has_more {
@@ -117,7 +127,8 @@ impl Actor {
 	}
 
 	// where the magic happens
-	async fn receive_crdt_operation(&mut self, op: CRDTOperation) {
+	// returns `true` if the operation was applied
+	async fn receive_crdt_operation(&mut self, op: CRDTOperation) -> bool {
 		// first, we update the HLC's timestamp with the incoming one.
 		// this involves a drift check + sets the last time of the clock
 		self.clock
@@ -131,16 +142,27 @@ impl Actor {
 		let op_instance = op.instance;
 		let op_timestamp = op.timestamp;
 
-		if !self.is_operation_old(&op).await {
-			// actually go and apply the operation in the db
-			self.apply_op(op).await.ok();
-
-			// update the stored timestamp for this instance - will be derived from the crdt operations table on restart
-			self.timestamps.write().await.insert(
-				op_instance,
-				NTP64::max(timestamp.unwrap_or_default(), op_timestamp),
-			);
+		if self.is_operation_old(&op).await {
+			return false;
 		}
+
+		// actually go and apply the operation in the db, which fails for records we don't have,
+		// like the ones in locations the sending instance excluded from sync
+		let applied = match self.apply_op(op).await {
+			Ok(()) => true,
+			Err(e) => {
+				debug!("Skipped an operation which couldn't be applied: {e}");
+				false
+			}
+		};
+
+		// update the stored timestamp for this instance - will be derived from the crdt operations table on restart
+		self.timestamps.write().await.insert(
+			op_instance,
+			NTP64::max(timestamp.unwrap_or_default(), op_timestamp),
+		);
+
+		applied
 	}
 
 	async fn apply_op(&mut self, op: CRDTOperation) -> prisma_client_rust::Result<()> {
@@ -160,8 +182,6 @@ impl Actor {
 			})
 			.await?;
 
-		self.io.req_tx.send(Request::Ingested).await.ok();
-
 		Ok(())
 	}
 
@@ -9,7 +9,7 @@ use sd_prisma::prisma::{crdt_operation, instance, PrismaClient};
 use sd_sync::CRDTOperation;
 
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	sync::{atomic::AtomicBool, Arc},
 };
 
@@ -19,7 +19,8 @@ pub use uhlc::NTP64;
 
 #[derive(Clone)]
 pub enum SyncMessage {
-	Ingested,
+	/// Operations from another instance were applied. Holds the names of the models which were affected.
+	Ingested(HashSet<String>),
 	Created,
 }
 
@@ -1,12 +1,12 @@
 use crate::{crdt_op_db, db_operation::*, ingest, SharedState, SyncMessage, NTP64};
 
 use sd_prisma::prisma::{cloud_crdt_operation, crdt_operation, instance, PrismaClient, SortOrder};
-use sd_sync::{CRDTOperation, OperationFactory};
+use sd_sync::{CRDTOperation, CRDTOperationData, OperationFactory};
 use sd_utils::uuid_to_bytes;
 
 use std::{
 	cmp::Ordering,
-	collections::HashMap,
+	collections::{BTreeMap, HashMap},
 	ops::Deref,
 	sync::{
 		atomic::{self, AtomicBool},
@@ -14,6 +14,7 @@ use std::{
 	},
 };
 
+use serde_json::Value;
 use tokio::sync::{broadcast, RwLock};
 use uhlc::{HLCBuilder, HLC};
 use uuid::Uuid;
@@ -171,6 +172,100 @@ impl Manager {
 			.collect())
 	}
 
+	/// The operations of a single instance which came after `timestamp`, oldest first.
+	pub async fn get_instance_ops(
+		&self,
+		instance_uuid: Uuid,
+		timestamp: NTP64,
+		count: u32,
+	) -> prisma_client_rust::Result<Vec<CRDTOperation>> {
+		Ok(self
+			.db
+			.crdt_operation()
+			.find_many(vec![
+				crdt_operation::instance::is(vec![instance::pub_id::equals(uuid_to_bytes(
+					instance_uuid,
+				))]),
+				crdt_operation::timestamp::gt(timestamp.as_u64() as i64),
+			])
+			.take(i64::from(count))
+			.order_by(crdt_operation::timestamp::order(SortOrder::Asc))
+			.include(crdt_include::include())
+			.exec()
+			.await?
+			.into_iter()
+			.map(|o| o.into_operation())
+			.collect())
+	}
+
+	/// Every operation on the given records of `model`, from any instance, oldest first.
+	pub async fn get_record_ops(
+		&self,
+		model: &str,
+		record_ids: &[Value],
+	) -> prisma_client_rust::Result<Vec<CRDTOperation>> {
+		Ok(self
+			.db
+			.crdt_operation()
+			.find_many(vec![
+				crdt_operation::model::equals(model.to_string()),
+				crdt_operation::record_id::in_vec(
+					record_ids
+						.iter()
+						.map(|record_id| serde_json::to_vec(record_id).unwrap())
+						.collect(),
+				),
+			])
+			.order_by(crdt_operation::timestamp::order(SortOrder::Asc))
+			.include(crdt_include::include())
+			.exec()
+			.await?
+			.into_iter()
+			.map(|o| o.into_operation())
+			.collect())
+	}
+
+	/// Writes the current state of records as new operations from this instance, so it comes after
+	/// everything that was already sent. That's one operation per field, and the create if there
+	/// was one, with the latest values in `ops`, which must be the records' history, oldest first.
+	///
+	/// Older values aren't written again, so they can't overwrite newer ones elsewhere. Records
+	/// which were deleted are left out.
+	pub async fn snapshot_records(
+		&self,
+		ops: Vec<CRDTOperation>,
+	) -> prisma_client_rust::Result<usize> {
+		let snapshot = snapshot(ops);
+		if snapshot.is_empty() {
+			return Ok(0);
+		}
+
+		let count = snapshot.len();
+
+		self.db
+			._batch(
+				snapshot
+					.into_iter()
+					.map(|(model, record_id, data)| {
+						crdt_op_db(&CRDTOperation {
+							instance: self.instance,
+							timestamp: *self.clock.new_timestamp().get_time(),
+							id: Uuid::new_v4(),
+							model,
+							record_id,
+							data,
+						})
+						.to_query(&self.db)
+					})
+					.collect::<Vec<_>>(),
+			)
+			.await?;
+
+		self.tx.send(SyncMessage::Created).ok();
+
+		Ok(count)
+	}
+
 	pub async fn get_cloud_ops(
 		&self,
 		args: GetOpsArgs,
@@ -241,6 +336,63 @@ impl OperationFactory for Manager {
 	}
 }
 
+#[derive(Default)]
+struct RecordSnapshot {
+	created: bool,
+	deleted: bool,
+	fields: BTreeMap<String, Value>,
+}
+
+/// The latest state of every record in `ops`, in the order they first appear in, as the data of
+/// the operations writing it.
+fn snapshot(ops: Vec<CRDTOperation>) -> Vec<(String, Value, CRDTOperationData)> {
+	let mut records = Vec::<((String, Value), RecordSnapshot)>::new();
+	// `Value` isn't `Hash`, so records are looked up by their serialized id
+	let mut indexes = HashMap::new();
+
+	for op in ops {
+		let i = *indexes
+			.entry((op.model.clone(), op.record_id.to_string()))
+			.or_insert_with(|| {
+				records.push(((op.model, op.record_id), RecordSnapshot::default()));
+				records.len() - 1
+			});
+		let record = &mut records[i].1;
+
+		match op.data {
+			CRDTOperationData::Create => {
+				record.created = true;
+				record.deleted = false;
+			}
+			CRDTOperationData::Update { field, value } => {
+				record.fields.insert(field, value);
+			}
+			CRDTOperationData::Delete => {
+				record.deleted = true;
+				record.fields.clear();
+			}
+		}
+	}
+
+	records
+		.into_iter()
+		.filter(|(_, record)| !record.deleted)
+		.flat_map(|((model, record_id), record)| {
+			record
+				.created
+				.then_some(CRDTOperationData::Create)
+				.into_iter()
+				.chain(
+					record
+						.fields
+						.into_iter()
+						.map(|(field, value)| CRDTOperationData::Update { field, value }),
+				)
+				.map(move |data| (model.clone(), record_id.clone(), data))
+		})
+		.collect()
+}
+
 impl Deref for Manager {
 	type Target = SharedState;
 
@@ -248,3 +400,91 @@ impl Deref for Manager {
 		&self.shared
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serde_json::json;
+
+	fn op(instance: Uuid, timestamp: u64, record: &str, data: CRDTOperationData) -> CRDTOperation {
+		CRDTOperation {
+			instance,
+			timestamp: NTP64(timestamp),
+			id: Uuid::new_v4(),
+			model: "location".to_string(),
+			record_id: json!({ "pub_id": record }),
+			data,
+		}
+	}
+
+	fn update(field: &str, value: Value) -> CRDTOperationData {
+		CRDTOperationData::Update {
+			field: field.to_string(),
+			value,
+		}
+	}
+
+	#[test]
+	fn test_snapshot_keeps_latest_values() {
+		let (ours, theirs) = (Uuid::new_v4(), Uuid::new_v4());
+
+		let snapshot = snapshot(vec![
+			op(ours, 1, "a", CRDTOperationData::Create),
+			op(ours, 2, "a", update("name", json!("Photos"))),
+			op(ours, 3, "b", CRDTOperationData::Create),
+			op(theirs, 4, "a", update("name", json!("Holidays"))),
+			op(ours, 5, "a", update("path", json!("/photos"))),
+			op(theirs, 6, "b", CRDTOperationData::Delete),
+			op(ours, 7, "c", update("name", json!("Documents"))),
+		]);
+
+		assert_eq!(
+			snapshot,
+			vec![
+				(
+					"location".to_string(),
+					json!({ "pub_id": "a" }),
+					CRDTOperationData::Create
+				),
+				(
+					"location".to_string(),
+					json!({ "pub_id": "a" }),
+					update("name", json!("Holidays"))
+				),
+				(
+					"location".to_string(),
+					json!({ "pub_id": "a" }),
+					update("path", json!("/photos"))
+				),
+				// Only updated through sync, so there's no create to send either
+				(
+					"location".to_string(),
+					json!({ "pub_id": "c" }),
+					update("name", json!("Documents"))
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn test_snapshot_after_recreate() {
+		let instance = Uuid::new_v4();
+
+		let snapshot = snapshot(vec![
+			op(instance, 1, "a", CRDTOperationData::Create),
+			op(instance, 2, "a", update("name", json!("Photos"))),
+			op(instance, 3, "a", CRDTOperationData::Delete),
+			op(instance, 4, "a", CRDTOperationData::Create),
+			op(instance, 5, "a", update("path", json!("/photos"))),
+		]);
+
+		assert_eq!(
+			snapshot
+				.into_iter()
+				.map(|(_, _, data)| data)
+				.collect::<Vec<_>>(),
+			vec![CRDTOperationData::Create, update("path", json!("/photos"))]
+		);
+	}
+}
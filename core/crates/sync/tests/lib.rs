@@ -153,8 +153,8 @@ async fn bruh() -> Result<(), Box<dyn std::error::Error>> {
 							.await
 							.unwrap();
 					}
-					ingest::Request::Ingested => {
-						instance2.sync.tx.send(SyncMessage::Ingested).ok();
+					ingest::Request::Ingested { models } => {
+						instance2.sync.tx.send(SyncMessage::Ingested(models)).ok();
 					}
 					_ => todo!(),
 				}
@@ -197,7 +197,9 @@ async fn bruh() -> Result<(), Box<dyn std::error::Error>> {
 		})
 		.await?;
 
-	assert!(matches!(sync_rx2.recv().await?, SyncMessage::Ingested));
+	assert!(
+		matches!(sync_rx2.recv().await?, SyncMessage::Ingested(models) if models.contains(prisma::location::NAME))
+	);
 
 	let out = instance2
 		.sync
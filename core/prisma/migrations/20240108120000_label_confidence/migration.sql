@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "label_on_object" ADD COLUMN "confidence" REAL;
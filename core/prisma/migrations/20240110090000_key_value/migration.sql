@@ -0,0 +1,6 @@
+-- CreateTable
+CREATE TABLE "key_value" (
+    "key" TEXT NOT NULL PRIMARY KEY,
+    "value" TEXT NOT NULL,
+    "date_modified" DATETIME
+);
@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "notification" ADD COLUMN "created_at" DATETIME;
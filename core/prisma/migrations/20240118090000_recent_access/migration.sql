@@ -0,0 +1,9 @@
+-- CreateTable
+CREATE TABLE "recent_access" (
+    "object_id" INTEGER NOT NULL PRIMARY KEY,
+    "accessed_at" DATETIME NOT NULL,
+    CONSTRAINT "recent_access_object_id_fkey" FOREIGN KEY ("object_id") REFERENCES "object" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE INDEX "recent_access_accessed_at_idx" ON "recent_access"("accessed_at");
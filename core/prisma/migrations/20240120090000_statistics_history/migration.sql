@@ -0,0 +1,11 @@
+-- CreateTable
+CREATE TABLE "statistics_history" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "date_captured" DATETIME NOT NULL,
+    "total_bytes" TEXT NOT NULL,
+    "file_count" INTEGER NOT NULL,
+    "object_count" INTEGER NOT NULL
+);
+
+-- CreateIndex
+CREATE INDEX "statistics_history_date_captured_idx" ON "statistics_history"("date_captured");
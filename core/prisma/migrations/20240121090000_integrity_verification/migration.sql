@@ -0,0 +1,15 @@
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "integrity_verified_at" DATETIME;
+
+-- CreateTable
+CREATE TABLE "integrity_mismatch" (
+    "file_path_id" INTEGER NOT NULL PRIMARY KEY,
+    "location_id" INTEGER NOT NULL,
+    "expected_cas_id" TEXT NOT NULL,
+    "actual_cas_id" TEXT,
+    "date_detected" DATETIME NOT NULL,
+    CONSTRAINT "integrity_mismatch_file_path_id_fkey" FOREIGN KEY ("file_path_id") REFERENCES "file_path" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE INDEX "integrity_mismatch_location_id_idx" ON "integrity_mismatch"("location_id");
@@ -0,0 +1,8 @@
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "case_insensitive" BOOLEAN;
+
+-- AlterTable
+ALTER TABLE "file_path" ADD COLUMN "name_normalized" TEXT;
+
+-- CreateIndex
+CREATE INDEX "file_path_location_id_materialized_path_name_normalized_idx" ON "file_path"("location_id", "materialized_path", "name_normalized");
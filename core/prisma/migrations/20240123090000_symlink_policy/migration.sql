@@ -0,0 +1,5 @@
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "symlink_policy" INTEGER;
+
+-- AlterTable
+ALTER TABLE "file_path" ADD COLUMN "symlink_target" TEXT;
@@ -0,0 +1,15 @@
+-- CreateTable
+CREATE TABLE "cloud_send_queue" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "start_time" BIGINT NOT NULL,
+    "end_time" BIGINT NOT NULL,
+    "ops_count" INTEGER NOT NULL,
+    "contents" BLOB NOT NULL,
+    "date_created" DATETIME NOT NULL,
+    "date_sent" DATETIME,
+    "instance_id" INTEGER NOT NULL,
+    CONSTRAINT "cloud_send_queue_instance_id_fkey" FOREIGN KEY ("instance_id") REFERENCES "instance" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE INDEX "cloud_send_queue_instance_id_date_sent_idx" ON "cloud_send_queue"("instance_id", "date_sent");
@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "tag" ADD COLUMN "sort_order" INTEGER;
@@ -0,0 +1,13 @@
+-- CreateTable
+CREATE TABLE "cloud_pending_operation" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "resource" TEXT NOT NULL,
+    "operation" BLOB NOT NULL,
+    "attempts" INTEGER NOT NULL DEFAULT 0,
+    "last_error" TEXT,
+    "next_attempt_at" DATETIME NOT NULL,
+    "queued_at" DATETIME NOT NULL
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "cloud_pending_operation_resource_key" ON "cloud_pending_operation"("resource");
@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "instance" ADD COLUMN "node_remote_identity" BLOB;
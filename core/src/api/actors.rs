@@ -0,0 +1,23 @@
+use rspc::alpha::AlphaRouter;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{utils::library, Ctx, R};
+
+pub(crate) fn mount() -> AlphaRouter<Ctx> {
+	R.router().procedure("events", {
+		R.with2(library()).subscription(|(_, library), _: ()| {
+			let mut rx = library.actors.subscribe_events();
+
+			async_stream::stream! {
+				loop {
+					match rx.recv().await {
+						Ok(event) => yield event,
+						// The current state can always be fetched again with `library.actors`
+						Err(RecvError::Lagged(_)) => continue,
+						Err(RecvError::Closed) => break,
+					}
+				}
+			}
+		})
+	})
+}
@@ -4,7 +4,7 @@ use crate::{
 	Node,
 };
 
-use sd_utils::error::FileIOError;
+use sd_utils::{db, error::FileIOError};
 
 use std::{
 	cmp,
@@ -16,6 +16,7 @@ use std::{
 use flate2::{bufread::GzDecoder, write::GzEncoder, Compression};
 use futures::executor::block_on;
 use futures_concurrency::future::TryJoin;
+use prisma_client_rust::{raw, PrismaValue, QueryError};
 use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::{Serialize, Serializer};
 use specta::Type;
@@ -178,6 +179,9 @@ enum BackupError {
 	#[error("Library already exists, please remove it and try again!")]
 	LibraryAlreadyExists,
 
+	#[error("database error: {0}")]
+	Database(#[from] QueryError),
+
 	#[error(transparent)]
 	FileIO(#[from] FileIOError),
 }
@@ -253,10 +257,25 @@ async fn do_backup(id: Uuid, node: &Node, library: &Library) -> Result<PathBuf,
 		))
 	})?;
 
-	let library_db_path = node
-		.libraries
-		.libraries_dir
-		.join(format!("{}.db", library.id));
+	// The database file alone misses whatever is still in its WAL file, so a consistent copy of it
+	// is made to be backed up instead
+	let temp_dir = tempdir().map_err(|e| {
+		FileIOError::from((
+			"/tmp",
+			e,
+			"Failed to get a temporary directory to do a backup",
+		))
+	})?;
+	let library_db_path = temp_dir.path().join("library.db");
+
+	library
+		.db
+		._execute_raw(raw!(
+			"VACUUM INTO {}",
+			PrismaValue::String(library_db_path.to_string_lossy().to_string())
+		))
+		.exec()
+		.await?;
 
 	tar.append_file(
 		"library.db",
@@ -363,6 +382,22 @@ async fn restore_backup(node: &Arc<Node>, path: impl AsRef<Path>) -> Result<Head
 		.libraries_dir
 		.join(format!("{}.db", header.library_id));
 
+	// Stale WAL files would otherwise be applied on top of the restored database
+	for wal_path in db::wal_files(&db_restored_path) {
+		match fs::remove_file(&wal_path).await {
+			Ok(()) => {}
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => {
+				return Err(FileIOError::from((
+					wal_path,
+					e,
+					"Failed to remove stale database files to restore backup",
+				))
+				.into())
+			}
+		}
+	}
+
 	fs::copy(db_path, &db_restored_path).await.map_err(|e| {
 		FileIOError::from((
 			&db_restored_path,
@@ -1,9 +1,15 @@
-use crate::{api::libraries::LibraryConfigWrapped, invalidate_query, library::LibraryName};
-
+use crate::{
+	api::{libraries::LibraryConfigWrapped, CoreEvent},
+	invalidate_query,
+	library::LibraryName,
+	node::{BusEvent, EventCategory, EventFilter},
+};
+
+use futures::StreamExt;
 use reqwest::Response;
 use rspc::alpha::AlphaRouter;
-use serde::de::DeserializeOwned;
-
+use serde::{de::DeserializeOwned, Deserialize};
+use specta::Type;
 use uuid::Uuid;
 
 use super::{utils::library, Ctx, R};
@@ -22,6 +28,61 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.merge("library.", library::mount())
 		.merge("locations.", locations::mount())
+		.procedure("syncStatus", {
+			R.with2(library()).query(|(_, library), _: ()| async move {
+				Ok(crate::cloud::sync::queue::status(&library.db).await?)
+			})
+		})
+		.procedure("resetSync", {
+			#[derive(Type, Deserialize)]
+			pub struct ResetSyncArgs {
+				/// Must be set, as pulling everything again can mean a large download.
+				pub confirm: bool,
+				/// Start syncing again right away, pulling everything from the cloud.
+				pub pull: bool,
+			}
+
+			R.with2(library())
+				.mutation(|(_, library), args: ResetSyncArgs| async move {
+					if !args.confirm {
+						return Err(rspc::Error::new(
+							rspc::ErrorCode::BadRequest,
+							"Resetting cloud sync must be confirmed".to_string(),
+						));
+					}
+
+					crate::cloud::sync::reset(&library, args.pull).await?;
+
+					Ok(())
+				})
+		})
+		.procedure("resetSyncProgress", {
+			R.with2(library())
+				.subscription(|(node, library), _: ()| async move {
+					let mut events = node.subscribe(
+						EventFilter::categories([EventCategory::CloudSync]).library(library.id),
+					);
+
+					async_stream::stream! {
+						while let Some(event) = events.next().await {
+							if let BusEvent::Event(CoreEvent::CloudSyncReset(event)) = event {
+								yield event.stage;
+							}
+						}
+					}
+				})
+		})
+		.procedure("pendingOperations", {
+			R.with2(library()).query(|(_, library), _: ()| async move {
+				Ok(crate::cloud::pending::list(&library.db).await?)
+			})
+		})
+		.procedure("flushNow", {
+			R.with2(library())
+				.mutation(|(node, library), _: ()| async move {
+					Ok(crate::cloud::pending::flush(&node, &library, true).await?)
+				})
+		})
 		.procedure("getApiOrigin", {
 			R.query(|node, _: ()| async move { Ok(node.env.api_url.lock().await.to_string()) })
 		})
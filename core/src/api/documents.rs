@@ -0,0 +1,106 @@
+use crate::object::media::thumbnail::{
+	find_indexed_page_thumbnail_format, generate_pdf_page_thumbnail, get_indexed_page_thumb_key,
+	get_indexed_page_thumbnail_path,
+};
+
+use sd_file_ext::extensions::DocumentExtension;
+use sd_prisma::prisma::file_path;
+
+use std::str::FromStr;
+
+use rspc::{alpha::AlphaRouter, ErrorCode};
+use serde::Deserialize;
+use specta::Type;
+
+use super::{
+	files::{existing_file_path, pdf_page_count},
+	utils::{library, ApiError},
+	Ctx, R,
+};
+
+pub(crate) fn mount() -> AlphaRouter<Ctx> {
+	R.router().procedure("pageThumbnail", {
+		#[derive(Type, Deserialize)]
+		pub struct DocumentPageThumbnailArgs {
+			pub file_path_id: file_path::id::Type,
+			/// Numbered from 1, like the pages of the document
+			pub page: u32,
+		}
+
+		R.with2(library()).query(
+			|(node, library),
+			 DocumentPageThumbnailArgs { file_path_id, page }: DocumentPageThumbnailArgs| async move {
+				let file_path = library
+					.db
+					.file_path()
+					.find_unique(file_path::id::equals(file_path_id))
+					.select(file_path::select!({ cas_id extension }))
+					.exec()
+					.await?
+					.ok_or_else(|| {
+						ApiError::NotFound(format!("File path not found: <id='{file_path_id}'>"))
+					})?;
+
+				if !matches!(
+					file_path
+						.extension
+						.as_deref()
+						.map(DocumentExtension::from_str),
+					Some(Ok(DocumentExtension::Pdf))
+				) {
+					return Err(ApiError::Validation(
+						"Only PDF documents have page thumbnails".to_string(),
+					)
+					.into());
+				}
+
+				// Page thumbnails are cached by the document's cas_id like its own thumbnail
+				let Some(cas_id) = file_path.cas_id else {
+					return Err(ApiError::Conflict(
+						"The document hasn't been identified yet".to_string(),
+					)
+					.into());
+				};
+
+				let format = if let Some(format) =
+					find_indexed_page_thumbnail_format(&node, &cas_id, page, library.id).await?
+				{
+					format
+				} else {
+					let format = node.thumbnailer.format();
+					let thumbnail_path =
+						get_indexed_page_thumbnail_path(&node, &cas_id, page, library.id, format);
+
+					let path = existing_file_path(&library, file_path_id).await?;
+					let page_count = pdf_page_count(&path).await?;
+
+					let Some(page_index) = page
+						.checked_sub(1)
+						.and_then(|page_index| u16::try_from(page_index).ok())
+						.filter(|page_index| *page_index < page_count)
+					else {
+						return Err(ApiError::Validation(format!(
+							"Page {page} doesn't exist, the document has {page_count} pages"
+						))
+						.into());
+					};
+
+					generate_pdf_page_thumbnail(&path, page_index, &thumbnail_path, format)
+						.await
+						.map_err(|e| {
+							rspc::Error::new(
+								ErrorCode::InternalServerError,
+								format!("Failed to generate the thumbnail of page {page}: {e}"),
+							)
+						})?;
+
+					format
+				};
+
+				Ok(get_indexed_page_thumb_key(
+					&cas_id, page, library.id, format,
+				))
+			},
+		)
+	})
+}
@@ -1,7 +1,11 @@
 use crate::{
-	api::utils::library,
+	api::utils::{library, ApiError},
 	invalidate_query,
 	library::Library,
+	location::{
+		indexer::rules::{seed::no_os_protected, IndexerRule, RuleKind, RulePerKind},
+		non_indexed::NonIndexedPathItem,
+	},
 	object::{
 		fs::{error::FileSystemJobsError, find_available_filename_for_duplicate},
 		media::media_data_extractor::{
@@ -15,7 +19,13 @@ use sd_file_path_helper::IsolatedFilePathData;
 use sd_media_metadata::MediaMetadata;
 use sd_utils::error::FileIOError;
 
-use std::{ffi::OsStr, path::PathBuf, str::FromStr};
+use std::{
+	collections::VecDeque,
+	ffi::OsStr,
+	path::{Component, Path, PathBuf},
+	slice,
+	str::FromStr,
+};
 
 use async_recursion::async_recursion;
 use futures_concurrency::future::TryJoin;
@@ -34,6 +44,10 @@ use super::{
 
 const UNTITLED_FOLDER_STR: &str = "Untitled Folder";
 
+/// Windows' `MAX_PATH`, longer paths fail with most APIs unless they have the `\\?\` prefix.
+#[cfg(target_os = "windows")]
+const WINDOWS_MAX_PATH_LEN: usize = 260;
+
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("getMediaData", {
@@ -76,13 +90,23 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				 CreateEphemeralFolderArgs { mut path, name }: CreateEphemeralFolderArgs| async move {
 					path.push(name.as_deref().unwrap_or(UNTITLED_FOLDER_STR));
 
-					create_directory(path, &library).await
+					check_not_protected(&path).await?;
+					check_new_path(&path)?;
+
+					let name = create_directory(path.clone(), &library).await?;
+					path.set_file_name(name);
+
+					NonIndexedPathItem::from_path(path)
+						.await
+						.map_err(Into::into)
 				},
 			)
 		})
 		.procedure("deleteFiles", {
 			R.with2(library())
 				.mutation(|(_, library), paths: Vec<PathBuf>| async move {
+					check_all_deletable(&paths).await?;
+
 					paths
 						.into_iter()
 						.map(|path| async move {
@@ -110,16 +134,28 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Ok(())
 				})
 		})
+		.procedure("moveToTrash", {
+			R.with2(library())
+				.mutation(|(_, library), paths: Vec<PathBuf>| async move {
+					check_all_deletable(&paths).await?;
+
+					move_to_trash(paths).await?;
+
+					invalidate_query!(library, "search.ephemeralPaths");
+
+					Ok(())
+				})
+		})
 		.procedure("copyFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: EphemeralFileSystemOps| async move {
-					args.copy(&library).await
+					items_from_paths(args.copy(&library).await?).await
 				})
 		})
 		.procedure("cutFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: EphemeralFileSystemOps| async move {
-					args.cut(&library).await
+					items_from_paths(args.cut(&library).await?).await
 				})
 		})
 		.procedure("renameFile", {
@@ -150,7 +186,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			impl EphemeralRenameFileArgs {
 				pub async fn rename_one(
 					EphemeralRenameOne { from_path, to }: EphemeralRenameOne,
-				) -> Result<(), rspc::Error> {
+				) -> Result<PathBuf, rspc::Error> {
 					let Some(old_name) = from_path.file_name() else {
 						return Err(rspc::Error::new(
 							ErrorCode::BadRequest,
@@ -159,9 +195,11 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					};
 
 					if old_name == OsStr::new(&to) {
-						return Ok(());
+						return Ok(from_path);
 					}
 
+					check_not_protected(&from_path).await?;
+
 					let (new_file_name, new_extension) =
 						IsolatedFilePathData::separate_name_and_extension_from_str(&to).map_err(
 							|e| rspc::Error::with_cause(ErrorCode::BadRequest, e.to_string(), e),
@@ -187,6 +225,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						new_file_name
 					});
 
+					check_new_path(&new_file_full_path)?;
+
 					match fs::metadata(&new_file_full_path).await {
 						Ok(_) => Err(rspc::Error::new(
 							ErrorCode::Conflict,
@@ -202,12 +242,13 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 								));
 							}
 
-							fs::rename(&from_path, new_file_full_path)
+							fs::rename(&from_path, &new_file_full_path)
 								.await
 								.map_err(|e| {
 									FileIOError::from((from_path, e, "Failed to rename file"))
-										.into()
-								})
+								})?;
+
+							Ok(new_file_full_path)
 						}
 					}
 				}
@@ -218,7 +259,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						ref to_pattern,
 						from_paths,
 					}: EphemeralRenameMany,
-				) -> Result<(), rspc::Error> {
+				) -> Result<Vec<PathBuf>, rspc::Error> {
 					let from_regex = &Regex::new(&from_pattern.pattern).map_err(|e| {
 						rspc::Error::with_cause(
 							rspc::ErrorCode::BadRequest,
@@ -227,6 +268,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						)
 					})?;
 
+					check_all_not_protected(&from_paths).await?;
+
 					from_paths
 						.into_iter()
 						.map(|old_path| async move {
@@ -267,6 +310,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 
 							let new_path = parent.join(replaced_full_name.as_ref());
 
+							check_new_path(&new_path)?;
+
 							fs::rename(&old_path, &new_path).await.map_err(|e| {
 								error!(
 									"Failed to rename file from: '{}' to: '{}'; Error: {e:#?}",
@@ -275,22 +320,22 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 								);
 								let e = FileIOError::from((old_path, e, "Failed to rename file"));
 								rspc::Error::with_cause(ErrorCode::Conflict, e.to_string(), e)
-							})
+							})?;
+
+							Ok(new_path)
 						})
 						.collect::<Vec<_>>()
 						.try_join()
-						.await?;
-
-					Ok(())
+						.await
 				}
 			}
 
 			R.with2(library()).mutation(
 				|(_, library), EphemeralRenameFileArgs { kind }: EphemeralRenameFileArgs| async move {
 					let res = match kind {
-						EphemeralRenameKind::One(one) => {
-							EphemeralRenameFileArgs::rename_one(one).await
-						}
+						EphemeralRenameKind::One(one) => EphemeralRenameFileArgs::rename_one(one)
+							.await
+							.map(|path| vec![path]),
 						EphemeralRenameKind::Many(many) => {
 							EphemeralRenameFileArgs::rename_many(many).await
 						}
@@ -300,7 +345,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						invalidate_query!(library, "search.ephemeralPaths");
 					}
 
-					res
+					items_from_paths(res?).await
 				},
 			)
 		})
@@ -359,11 +404,15 @@ impl EphemeralFileSystemOps {
 		self.check_sources()?;
 		self.check_target_directory().await?;
 
+		check_all_not_protected(&self.sources).await?;
+		check_not_protected(&self.target_dir).await?;
+
 		Ok(())
 	}
 
+	/// Returns the paths of the copies of the sources.
 	#[async_recursion]
-	async fn copy(self, library: &Library) -> Result<(), rspc::Error> {
+	async fn copy(self, library: &Library) -> Result<Vec<PathBuf>, rspc::Error> {
 		self.check().await?;
 
 		let EphemeralFileSystemOps {
@@ -398,7 +447,7 @@ impl EphemeralFileSystemOps {
 			.into_iter()
 			.partition::<Vec<_>, _>(|(_, _, is_dir)| *is_dir);
 
-		files_to_copy
+		let mut copied = files_to_copy
 			.into_iter()
 			.map(|(source, mut target, _)| async move {
 				match fs::metadata(&target).await {
@@ -407,7 +456,7 @@ impl EphemeralFileSystemOps {
 						// Everything is awesome!
 					}
 					Err(e) => {
-						return Err(FileSystemJobsError::FileIO(FileIOError::from((
+						return Err(rspc::Error::from(FileIOError::from((
 							target,
 							e,
 							"Failed to get target file metadata",
@@ -415,20 +464,24 @@ impl EphemeralFileSystemOps {
 					}
 				}
 
-				fs::copy(&source, target).await.map_err(|e| {
+				check_new_path(&target)?;
+
+				fs::copy(&source, &target).await.map_err(|e| {
 					FileSystemJobsError::FileIO(FileIOError::from((
 						source,
 						e,
 						"Failed to copy file",
 					)))
-				})
+				})?;
+
+				Ok(target)
 			})
 			.collect::<Vec<_>>()
 			.try_join()
 			.await?;
 
 		if !directories_to_create.is_empty() {
-			directories_to_create
+			let copied_directories = directories_to_create
 				.into_iter()
 				.map(|(source, mut target, _)| async move {
 					match fs::metadata(&target).await {
@@ -445,6 +498,8 @@ impl EphemeralFileSystemOps {
 						}
 					}
 
+					check_new_path(&target)?;
+
 					fs::create_dir_all(&target).await.map_err(|e| {
 						FileIOError::from((&target, e, "Failed to create directory"))
 					})?;
@@ -467,25 +522,28 @@ impl EphemeralFileSystemOps {
 					if !more_files.is_empty() {
 						Self {
 							sources: more_files,
-							target_dir: target,
+							target_dir: target.clone(),
 						}
 						.copy(library)
-						.await
-					} else {
-						Ok(())
+						.await?;
 					}
+
+					Ok::<_, rspc::Error>(target)
 				})
 				.collect::<Vec<_>>()
 				.try_join()
 				.await?;
+
+			copied.extend(copied_directories);
 		}
 
 		invalidate_query!(library, "search.ephemeralPaths");
 
-		Ok(())
+		Ok(copied)
 	}
 
-	async fn cut(self, library: &Library) -> Result<(), rspc::Error> {
+	/// Returns the paths the sources were moved to.
+	async fn cut(self, library: &Library) -> Result<Vec<PathBuf>, rspc::Error> {
 		self.check().await?;
 
 		let EphemeralFileSystemOps {
@@ -493,7 +551,7 @@ impl EphemeralFileSystemOps {
 			target_dir,
 		} = self;
 
-		sources
+		let moved = sources
 			.into_iter()
 			.filter_map(|source| {
 				if let Some(name) = source.file_name() {
@@ -507,29 +565,34 @@ impl EphemeralFileSystemOps {
 			.map(|(source, target)| async move {
 				match fs::metadata(&target).await {
 					Ok(_) => {
-						return Err(FileSystemJobsError::WouldOverwrite(
-							target.into_boxed_path(),
-						));
+						return Err(
+							FileSystemJobsError::WouldOverwrite(target.into_boxed_path()).into(),
+						);
 					}
 					Err(e) if e.kind() == io::ErrorKind::NotFound => {
 						// Everything is awesome!
 					}
 					Err(e) => {
-						return Err(FileSystemJobsError::FileIO(FileIOError::from((
+						return Err(FileIOError::from((
 							source,
 							e,
 							"Failed to get target file metadata",
-						))));
+						))
+						.into());
 					}
 				}
 
-				fs::rename(&source, target).await.map_err(|e| {
+				check_new_path(&target)?;
+
+				fs::rename(&source, &target).await.map_err(|e| {
 					FileSystemJobsError::FileIO(FileIOError::from((
 						source,
 						e,
 						"Failed to move file",
 					)))
-				})
+				})?;
+
+				Ok::<_, rspc::Error>(target)
 			})
 			.collect::<Vec<_>>()
 			.try_join()
@@ -537,6 +600,295 @@ impl EphemeralFileSystemOps {
 
 		invalidate_query!(library, "search.ephemeralPaths");
 
-		Ok(())
+		Ok(moved)
+	}
+}
+
+#[cfg(not(any(target_os = "ios", target_os = "android")))]
+async fn move_to_trash(paths: Vec<PathBuf>) -> Result<(), rspc::Error> {
+	tokio::task::spawn_blocking(move || trash::delete_all(paths))
+		.await
+		.map_err(|e| {
+			rspc::Error::with_cause(
+				ErrorCode::InternalServerError,
+				"Failed to join the move to trash task".to_string(),
+				e,
+			)
+		})?
+		.map_err(|e| {
+			rspc::Error::with_cause(
+				ErrorCode::InternalServerError,
+				"Failed to move files to trash".to_string(),
+				e,
+			)
+		})
+}
+
+/// The `trash` crate doesn't support mobile, which doesn't have a trash to move files to anyway.
+#[cfg(any(target_os = "ios", target_os = "android"))]
+async fn move_to_trash(_: Vec<PathBuf>) -> Result<(), rspc::Error> {
+	Err(ApiError::Unsupported(
+		"Moving files to the trash isn't supported on this platform".to_string(),
+	)
+	.into())
+}
+
+/// Rejects paths hidden from the ephemeral explorer by the `no_os_protected` rule, or inside one
+/// of them, so system files can't be changed through it either.
+async fn check_not_protected(path: impl AsRef<Path>) -> Result<(), rspc::Error> {
+	let rule = IndexerRule::from(no_os_protected());
+
+	check_ancestors_not_protected(&rule, &normalise_path(path.as_ref()).await?).await
+}
+
+async fn check_all_not_protected(paths: &[PathBuf]) -> Result<(), rspc::Error> {
+	paths
+		.iter()
+		.map(check_not_protected)
+		.collect::<Vec<_>>()
+		.try_join()
+		.await
+		.map(|_| ())
+}
+
+/// Like [`check_not_protected`], but also rejects directories containing protected paths, as
+/// deleting them deletes everything inside too, like deleting the root of a drive would.
+async fn check_deletable(path: impl AsRef<Path>) -> Result<(), rspc::Error> {
+	let rule = IndexerRule::from(no_os_protected());
+	let path = normalise_path(path.as_ref()).await?;
+
+	check_ancestors_not_protected(&rule, &path).await?;
+	check_descendants_not_protected(&rule, &path).await
+}
+
+async fn check_all_deletable(paths: &[PathBuf]) -> Result<(), rspc::Error> {
+	paths
+		.iter()
+		.map(check_deletable)
+		.collect::<Vec<_>>()
+		.try_join()
+		.await
+		.map(|_| ())
+}
+
+/// Resolves `.`, `..` and symlinks in the path's ancestors, so the protected globs can't be
+/// sidestepped. A symlink at the path itself is kept, as that's what we rename or delete.
+async fn normalise_path(path: &Path) -> Result<PathBuf, rspc::Error> {
+	let (parent, name) = match (path.parent(), path.components().next_back()) {
+		(Some(parent), Some(Component::Normal(name))) => (parent, Some(name)),
+		_ => (path, None),
+	};
+	let parent = if parent.as_os_str().is_empty() {
+		Path::new(".")
+	} else {
+		parent
+	};
+
+	let parent = fs::canonicalize(parent)
+		.await
+		.map_err(|e| FileIOError::from((parent, e, "Failed to resolve path")))?;
+
+	Ok(without_verbatim_prefix(match name {
+		Some(name) => parent.join(name),
+		None => parent,
+	}))
+}
+
+/// `canonicalize` returns `\\?\C:\...` paths on Windows, which the protected globs don't match.
+#[cfg(target_os = "windows")]
+fn without_verbatim_prefix(path: PathBuf) -> PathBuf {
+	let stripped = path
+		.to_str()
+		.and_then(|path| path.strip_prefix(r"\\?\"))
+		.filter(|path| !path.starts_with("UNC"))
+		.map(PathBuf::from);
+
+	stripped.unwrap_or(path)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn without_verbatim_prefix(path: PathBuf) -> PathBuf {
+	path
+}
+
+async fn check_ancestors_not_protected(rule: &IndexerRule, path: &Path) -> Result<(), rspc::Error> {
+	for ancestor in path.ancestors() {
+		if is_protected(rule, ancestor).await? {
+			return Err(protected_error(ancestor));
+		}
+	}
+
+	Ok(())
+}
+
+/// Only walks as deep as the protected paths at fixed places go, like `C:/Windows` or
+/// `/Users/*/Library`, as walking whole directories would take too long.
+async fn check_descendants_not_protected(
+	rule: &IndexerRule,
+	path: &Path,
+) -> Result<(), rspc::Error> {
+	// Not following symlinks, as deleting them doesn't delete what they point to
+	if !fs::symlink_metadata(path)
+		.await
+		.map_or(false, |metadata| metadata.is_dir())
+	{
+		return Ok(());
+	}
+
+	let max_depth = protected_depth(rule);
+
+	let mut to_check = VecDeque::from([(path.to_path_buf(), path_depth(path))]);
+	while let Some((dir, depth)) = to_check.pop_front() {
+		if depth >= max_depth {
+			continue;
+		}
+
+		let Ok(mut entries) = fs::read_dir(&dir).await else {
+			continue;
+		};
+
+		while let Ok(Some(entry)) = entries.next_entry().await {
+			if !entry
+				.file_type()
+				.await
+				.map_or(false, |file_type| file_type.is_dir())
+			{
+				continue;
+			}
+
+			let child = entry.path();
+			if is_protected(rule, &child).await? {
+				return Err(protected_error(&child));
+			}
+
+			to_check.push_back((child, depth + 1));
+		}
+	}
+
+	Ok(())
+}
+
+async fn is_protected(rule: &IndexerRule, path: &Path) -> Result<bool, rspc::Error> {
+	Ok(IndexerRule::apply_all(slice::from_ref(rule), path)
+		.await?
+		.get(&RuleKind::RejectFilesByGlob)
+		.map_or(false, |results| results.iter().any(|accepted| !accepted)))
+}
+
+/// How many components the deepest protected path has, not counting the ones matched at any depth.
+fn protected_depth(rule: &IndexerRule) -> usize {
+	rule.rules
+		.iter()
+		.flat_map(|per_kind| match per_kind {
+			RulePerKind::RejectFilesByGlob(globs, _) => globs.as_slice(),
+			_ => &[],
+		})
+		.filter(|glob| !glob.glob().starts_with("**"))
+		.map(|glob| {
+			glob.glob()
+				.split('/')
+				.filter(|component| !component.is_empty())
+				.count()
+		})
+		.max()
+		.unwrap_or_default()
+}
+
+fn path_depth(path: &Path) -> usize {
+	path.components()
+		.filter(|component| matches!(component, Component::Prefix(_) | Component::Normal(_)))
+		.count()
+}
+
+fn protected_error(path: &Path) -> rspc::Error {
+	rspc::Error::new(
+		ErrorCode::Forbidden,
+		format!("'{}' is protected by the operating system", path.display()),
+	)
+}
+
+/// Validates a path we're about to create, as the errors we'd get from the OS for a bad name
+/// aren't very helpful.
+fn check_new_path(path: &Path) -> Result<(), rspc::Error> {
+	let Some(name) = path.file_name().and_then(OsStr::to_str) else {
+		return Err(rspc::Error::new(
+			ErrorCode::BadRequest,
+			"Invalid file name".to_string(),
+		));
+	};
+
+	// On Windows this also rejects reserved names like `CON` or `NUL.txt`
+	if !IsolatedFilePathData::accept_file_name(name) {
+		return Err(rspc::Error::new(
+			ErrorCode::BadRequest,
+			"Invalid file name".to_string(),
+		));
+	}
+
+	#[cfg(target_os = "windows")]
+	{
+		// Windows silently strips these, so `file.` would end up as `file`
+		if name.ends_with(['.', ' ']) {
+			return Err(rspc::Error::new(
+				ErrorCode::BadRequest,
+				"File names can't end with a dot or a space on Windows".to_string(),
+			));
+		}
+
+		let path_str = path.as_os_str().to_string_lossy();
+		if !path_str.starts_with(r"\\?\") && path_str.encode_utf16().count() >= WINDOWS_MAX_PATH_LEN
+		{
+			return Err(rspc::Error::new(
+				ErrorCode::BadRequest,
+				format!("Path is longer than Windows' limit of {WINDOWS_MAX_PATH_LEN} characters"),
+			));
+		}
+	}
+
+	Ok(())
+}
+
+async fn items_from_paths(paths: Vec<PathBuf>) -> Result<Vec<NonIndexedPathItem>, rspc::Error> {
+	paths
+		.into_iter()
+		.map(NonIndexedPathItem::from_path)
+		.collect::<Vec<_>>()
+		.try_join()
+		.await
+		.map_err(Into::into)
+}
+
+#[cfg(all(test, target_family = "unix"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_protected_paths() {
+		let dir = tempdir().unwrap();
+		let photos = dir.path().join("photos");
+		fs::create_dir(&photos).await.unwrap();
+
+		check_not_protected(&photos).await.unwrap();
+		check_not_protected(photos.join("new")).await.unwrap();
+		check_deletable(&photos).await.unwrap();
+
+		// Inside protected directories, however they're spelled
+		assert!(check_not_protected("/dev/null").await.is_err());
+		assert!(check_not_protected("/tmp/../dev/null").await.is_err());
+		fs::symlink("/dev", dir.path().join("devices"))
+			.await
+			.unwrap();
+		assert!(check_not_protected(dir.path().join("devices/null"))
+			.await
+			.is_err());
+		// The link itself isn't protected though
+		check_deletable(dir.path().join("devices")).await.unwrap();
+
+		// Directories containing protected ones
+		assert!(check_deletable("/").await.is_err());
+		assert!(check_deletable("/tmp/..").await.is_err());
 	}
 }
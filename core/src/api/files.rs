@@ -1,47 +1,123 @@
 use crate::{
-	api::{locations::object_with_file_paths, utils::library},
+	api::{
+		locations::{indexed_thumbnail, object_with_file_paths, ExplorerItem},
+		objects::{favorite_items, ObjectUpdateArgs},
+		search::media_by_date::{location_buckets, LocationMediaDateArgs},
+		utils::{library, ApiError},
+	},
 	invalidate_query,
 	job::Job,
 	library::Library,
-	location::{get_location_path_from_location_id, LocationError},
+	location::{find_location, get_location_path_from_location_id, non_indexed, LocationError},
 	object::{
 		fs::{
-			copy::FileCopierJobInit, cut::FileCutterJobInit, delete::FileDeleterJobInit,
-			erase::FileEraserJobInit, error::FileSystemJobsError,
-			find_available_filename_for_duplicate,
+			copy::FileCopierJobInit, cut::FileCutterJobInit, decrypt::FileDecryptorJobInit,
+			delete::FileDeleterJobInit, encrypt::FileEncryptorJobInit, erase::FileEraserJobInit,
+			error::FileSystemJobsError, find_available_filename_for_duplicate,
+			rename::rename_file_path, text_preview::text_preview,
+		},
+		media::{
+			media_data_extractor::{self, can_extract_media_data_for_image},
+			media_data_image_from_prisma_data,
+			thumbnail::{
+				generate_custom_thumbnail, get_indexed_custom_thumb_key,
+				get_indexed_custom_thumbnail_path, get_indexed_thumb_key,
+				remove_indexed_custom_thumbnail, ThumbnailerError,
+			},
 		},
-		media::media_data_image_from_prisma_data,
+		recent,
+		validation::integrity_job::IntegrityVerifierJobInit,
 	},
+	preferences::LibraryPreferences,
+	util::MaybeUndefined,
+	Node, OpenWith, OpenWithError,
 };
 
-use sd_cache::{CacheNode, Model, NormalisedResult, Reference};
-use sd_file_ext::kind::ObjectKind;
+use sd_cache::{CacheNode, Model, Normalise, NormalisedResult, NormalisedResults, Reference};
+use sd_file_ext::{
+	extensions::{DocumentExtension, ImageExtension},
+	kind::ObjectKind,
+};
 use sd_file_path_helper::{
 	file_path_to_isolate, file_path_to_isolate_with_id, FilePathError, IsolatedFilePathData,
 };
 use sd_images::ConvertableExtension;
-use sd_media_metadata::MediaMetadata;
-use sd_prisma::prisma::{file_path, location, object};
+use sd_media_metadata::{DocumentMetadata, MediaMetadata};
+use sd_prisma::prisma::{
+	file_path, integrity_mismatch, location, object, recent_access, SortOrder,
+};
 use sd_utils::{db::maybe_missing, error::FileIOError};
 
 use std::{
+	collections::{HashMap, HashSet},
 	ffi::OsString,
-	path::{Path, PathBuf},
-	sync::Arc,
+	path::{Component, Path, PathBuf},
+	str::FromStr,
+	sync::{Arc, Mutex, PoisonError},
+	time::Duration,
 };
 
 use chrono::{DateTime, FixedOffset, Utc};
 use futures::future::join_all;
+use mini_moka::sync::Cache;
+use once_cell::sync::Lazy;
 use regex::Regex;
 use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tokio::{fs, io, task::spawn_blocking};
 use tracing::{error, warn};
+use uuid::Uuid;
 
-use super::{Ctx, R};
+use super::{CoreEvent, Ctx, R};
 
 const UNTITLED_FOLDER_STR: &str = "Untitled Folder";
+const DEFAULT_RECENTS_LIMIT: i64 = 50;
+
+type MediaDataKey = (Uuid, object::id::Type);
+
+const MEDIA_DATA_UNAVAILABLE_CAPACITY: u64 = 4096;
+const MEDIA_DATA_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Objects which `files.getMediaData` has queued extraction for and which are still running.
+static MEDIA_DATA_EXTRACTIONS: Lazy<Mutex<HashSet<MediaDataKey>>> = Lazy::new(Default::default);
+
+/// Objects which turned out to not have any media data, so we don't re-queue extraction for them.
+static MEDIA_DATA_UNAVAILABLE: Lazy<Cache<MediaDataKey, ()>> =
+	Lazy::new(|| Cache::new(MEDIA_DATA_UNAVAILABLE_CAPACITY));
+
+/// The errors of failed extractions, which are only retried once they expire so the file isn't
+/// read again on every request.
+static MEDIA_DATA_FAILED: Lazy<Cache<MediaDataKey, String>> = Lazy::new(|| {
+	Cache::builder()
+		.max_capacity(MEDIA_DATA_UNAVAILABLE_CAPACITY)
+		.time_to_live(MEDIA_DATA_RETRY_AFTER)
+		.build()
+});
+
+#[derive(Type, Serialize)]
+#[serde(tag = "status", content = "data")]
+pub enum MediaDataState {
+	Ready(MediaMetadata),
+	/// Extraction has been queued and `files.getMediaData` will be invalidated once it's done.
+	Pending,
+	/// The object doesn't have any media data we can extract.
+	Unavailable,
+	/// Extraction failed, it's tried again when requested after a while.
+	Failed {
+		error: String,
+	},
+}
+
+integrity_mismatch::include!(integrity_mismatch_with_file_path { file_path });
+
+#[derive(Type, Serialize)]
+pub struct IntegrityReport {
+	/// When all the files of the location were last verified, a verification of only some objects
+	/// doesn't count.
+	pub verified_at: Option<DateTime<FixedOffset>>,
+	pub mismatches: Vec<integrity_mismatch_with_file_path::Data>,
+}
 
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
@@ -121,26 +197,45 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 		.procedure("getMediaData", {
 			R.with2(library())
 				.query(|(_, library), args: object::id::Type| async move {
-					library
+					let obj = library
 						.db
 						.object()
 						.find_unique(object::id::equals(args))
 						.select(object::select!({ id kind media_data }))
 						.exec()
 						.await?
-						.and_then(|obj| {
-							Some(match obj.kind {
-								Some(v) if v == ObjectKind::Image as i32 => {
-									MediaMetadata::Image(Box::new(
-										media_data_image_from_prisma_data(obj.media_data?).ok()?,
-									))
-								}
-								_ => return None, // TODO(brxken128): audio and video
-							})
-						})
 						.ok_or_else(|| {
 							rspc::Error::new(ErrorCode::NotFound, "Object not found".to_string())
-						})
+						})?;
+
+					if obj.kind == Some(ObjectKind::Document as i32) {
+						return document_media_data(&library, obj.id).await;
+					}
+
+					// TODO(brxken128): audio and video
+					if obj.kind != Some(ObjectKind::Image as i32) {
+						return Ok(MediaDataState::Unavailable);
+					}
+
+					let Some(media_data) = obj.media_data else {
+						return queue_media_data_extraction(&library, obj.id).await;
+					};
+
+					let mut image = media_data_image_from_prisma_data(media_data).map_err(|e| {
+						rspc::Error::new(
+							ErrorCode::InternalServerError,
+							format!("Failed to read media data: {e}"),
+						)
+					})?;
+
+					if !LibraryPreferences::read(&library.db)
+						.await?
+						.expose_location_metadata()
+					{
+						image.location = None;
+					}
+
+					Ok(MediaDataState::Ready(MediaMetadata::Image(Box::new(image))))
 				})
 		})
 		.procedure("getPath", {
@@ -168,6 +263,100 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.map(|str| str.to_string()))
 				})
 		})
+		.procedure("textPreview", {
+			#[derive(Type, Deserialize)]
+			pub struct TextPreviewArgs {
+				pub file_path_id: file_path::id::Type,
+				/// Capped by the server, so asking for more than it allows returns less.
+				pub max_bytes: u32,
+			}
+
+			R.with2(library()).query(
+				|(_, library),
+				 TextPreviewArgs {
+				     file_path_id,
+				     max_bytes,
+				 }: TextPreviewArgs| async move {
+					text_preview(&library, file_path_id, max_bytes)
+						.await
+						.map_err(Into::into)
+				},
+			)
+		})
+		.procedure("inspect", {
+			#[derive(Type, Deserialize)]
+			pub struct FileInspectArgs {
+				pub path: PathBuf,
+			}
+
+			R.query(
+				|node, FileInspectArgs { path }: FileInspectArgs| async move {
+					non_indexed::inspect(&path, &node).await.map_err(Into::into)
+				},
+			)
+		})
+		.procedure("getOpenWithApplications", {
+			R.with2(library())
+				.query(|(node, library), path: PathBuf| async move {
+					check_path_can_be_opened(&node, &library, &path).await?;
+
+					Ok(open_with_provider(&node)?.applications(&path).await?)
+				})
+		})
+		.procedure("openWith", {
+			#[derive(Type, Deserialize)]
+			pub struct OpenWithArgs {
+				pub path: PathBuf,
+				pub handler_id: String,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library), OpenWithArgs { path, handler_id }: OpenWithArgs| async move {
+					check_path_can_be_opened(&node, &library, &path).await?;
+
+					Ok(open_with_provider(&node)?
+						.open_with(&path, &handler_id)
+						.await?)
+				},
+			)
+		})
+		.procedure("openDefault", {
+			R.with2(library())
+				.mutation(|(node, library), path: PathBuf| async move {
+					check_path_can_be_opened(&node, &library, &path).await?;
+
+					Ok(open_with_provider(&node)?.open_default(&path).await?)
+				})
+		})
+		.procedure("openInDefaultApp", {
+			#[derive(Type, Deserialize)]
+			pub struct OpenInDefaultAppArgs {
+				pub file_path_id: file_path::id::Type,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library), OpenInDefaultAppArgs { file_path_id }: OpenInDefaultAppArgs| async move {
+					let path = existing_file_path(&library, file_path_id).await?;
+
+					Ok(open_with_provider(&node)?.open_default(&path).await?)
+				},
+			)
+		})
+		.procedure("revealInFileManager", {
+			#[derive(Type, Deserialize)]
+			pub struct RevealInFileManagerArgs {
+				pub file_path_id: file_path::id::Type,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library),
+				 RevealInFileManagerArgs { file_path_id }: RevealInFileManagerArgs| async move {
+					let path = existing_file_path(&library, file_path_id).await?;
+
+					Ok(open_with_provider(&node)?.reveal(&path).await?)
+				},
+			)
+		})
 		.procedure("setNote", {
 			#[derive(Type, Deserialize)]
 			pub struct SetNoteArgs {
@@ -177,20 +366,16 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 
 			R.with2(library())
 				.mutation(|(_, library), args: SetNoteArgs| async move {
-					library
-						.db
-						.object()
-						.update(
-							object::id::equals(args.id),
-							vec![object::note::set(args.note)],
-						)
-						.exec()
-						.await?;
-
-					invalidate_query!(library, "search.paths");
-					invalidate_query!(library, "search.objects");
-
-					Ok(())
+					ObjectUpdateArgs {
+						id: args.id,
+						note: args
+							.note
+							.map_or(MaybeUndefined::Null, MaybeUndefined::Value),
+						favorite: None,
+						hidden: None,
+					}
+					.apply(&library)
+					.await
 				})
 		})
 		.procedure("setFavorite", {
@@ -202,21 +387,86 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 
 			R.with2(library())
 				.mutation(|(_, library), args: SetFavoriteArgs| async move {
-					library
-						.db
-						.object()
-						.update(
-							object::id::equals(args.id),
-							vec![object::favorite::set(Some(args.favorite))],
-						)
-						.exec()
+					ObjectUpdateArgs {
+						id: args.id,
+						note: MaybeUndefined::Undefined,
+						favorite: Some(args.favorite),
+						hidden: None,
+					}
+					.apply(&library)
+					.await
+				})
+		})
+		.procedure("setCustomThumbnail", {
+			#[derive(Type, Deserialize)]
+			pub struct SetCustomThumbnailArgs {
+				pub file_path_id: file_path::id::Type,
+				/// The image to use as the thumbnail, in any format we can decode
+				pub image_bytes: Vec<u8>,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library),
+				 SetCustomThumbnailArgs {
+				     file_path_id,
+				     image_bytes,
+				 }: SetCustomThumbnailArgs| async move {
+					let cas_id = identified_cas_id(&library, file_path_id).await?;
+
+					let format = node.thumbnailer.format();
+					generate_custom_thumbnail(
+						image_bytes,
+						get_indexed_custom_thumbnail_path(&node, &cas_id, library.id, format),
+						format,
+					)
+					.await
+					.map_err(|e| match e {
+						ThumbnailerError::CustomThumbnail(e) => rspc::Error::from(
+							ApiError::Validation(format!("The image couldn't be read: {e}")),
+						),
+						e => rspc::Error::with_cause(
+							ErrorCode::InternalServerError,
+							"Failed to set the custom thumbnail".to_string(),
+							e,
+						),
+					})?;
+
+					// So a custom thumbnail set before in another format doesn't shadow this one
+					remove_indexed_custom_thumbnail(&node, &cas_id, library.id, Some(format))
 						.await?;
 
+					node.emit(CoreEvent::NewThumbnail {
+						thumb_key: get_indexed_custom_thumb_key(&cas_id, library.id, format),
+					});
+
 					invalidate_query!(library, "search.paths");
 					invalidate_query!(library, "search.objects");
 
 					Ok(())
-				})
+				},
+			)
+		})
+		.procedure("clearCustomThumbnail", {
+			R.with2(library()).mutation(
+				|(node, library), file_path_id: file_path::id::Type| async move {
+					let cas_id = identified_cas_id(&library, file_path_id).await?;
+
+					remove_indexed_custom_thumbnail(&node, &cas_id, library.id, None).await?;
+
+					node.emit(CoreEvent::NewThumbnail {
+						thumb_key: get_indexed_thumb_key(
+							&cas_id,
+							library.id,
+							node.thumbnailer.format(),
+						),
+					});
+
+					invalidate_query!(library, "search.paths");
+					invalidate_query!(library, "search.objects");
+
+					Ok(())
+				},
+			)
 		})
 		.procedure("createFolder", {
 			#[derive(Type, Deserialize)]
@@ -255,14 +505,17 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.db
 						.object()
 						.update_many(
-							vec![object::id::in_vec(ids)],
+							vec![object::id::in_vec(ids.clone())],
 							vec![object::date_accessed::set(Some(Utc::now().into()))],
 						)
 						.exec()
 						.await?;
 
+					recent::record_access(&library.db, ids).await?;
+
 					invalidate_query!(library, "search.paths");
 					invalidate_query!(library, "search.objects");
+					invalidate_query!(library, "files.recents");
 					Ok(())
 				})
 		})
@@ -273,29 +526,193 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.db
 						.object()
 						.update_many(
-							vec![object::id::in_vec(object_ids)],
+							vec![object::id::in_vec(object_ids.clone())],
 							vec![object::date_accessed::set(None)],
 						)
 						.exec()
 						.await?;
 
+					recent::remove_access(&library.db, object_ids).await?;
+
 					invalidate_query!(library, "search.objects");
 					invalidate_query!(library, "search.paths");
+					invalidate_query!(library, "files.recents");
 					Ok(())
 				})
 		})
-		// .procedure("encryptFiles", {
-		// 	R.with2(library())
-		// 		.mutation(|(node, library), args: FileEncryptorJobInit| async move {
-		// 			Job::new(args).spawn(&node, &library).await.map_err(Into::into)
-		// 		})
-		// })
-		// .procedure("decryptFiles", {
-		// 	R.with2(library())
-		// 		.mutation(|(node, library), args: FileDecryptorJobInit| async move {
-		// 			Job::new(args).spawn(&node, &library).await.map_err(Into::into)
-		// 		})
-		// })
+		.procedure("recordAccess", {
+			R.with2(library()).mutation(
+				|(_, library), object_ids: Vec<object::id::Type>| async move {
+					recent::record_access(&library.db, object_ids).await?;
+
+					invalidate_query!(library, "files.recents");
+					Ok(())
+				},
+			)
+		})
+		.procedure("recents", {
+			#[derive(Type, Deserialize)]
+			pub struct RecentsArgs {
+				#[specta(optional)]
+				pub limit: Option<u32>,
+			}
+
+			R.with2(library())
+				.query(|(node, library), RecentsArgs { limit }| async move {
+					let Library { db, .. } = library.as_ref();
+
+					let limit = limit.map_or(DEFAULT_RECENTS_LIMIT, i64::from);
+
+					let object_ids = db
+						.recent_access()
+						.find_many(vec![])
+						.order_by(recent_access::accessed_at::order(SortOrder::Desc))
+						.take(limit.min(recent::MAX_RECENT_ACCESSES))
+						.select(recent_access::select!({ object_id }))
+						.exec()
+						.await?
+						.into_iter()
+						.map(|access| access.object_id)
+						.collect::<Vec<_>>();
+
+					let mut objects = db
+						.object()
+						.find_many(vec![object::id::in_vec(object_ids.clone())])
+						.include(object_with_file_paths::include())
+						.exec()
+						.await?
+						.into_iter()
+						.map(|object| (object.id, object))
+						.collect::<HashMap<_, _>>();
+
+					let mut items = Vec::with_capacity(objects.len());
+
+					// Keeps the most recently accessed first
+					for object in object_ids.into_iter().filter_map(|id| objects.remove(&id)) {
+						let cas_id = object
+							.file_paths
+							.iter()
+							.find_map(|file_path| file_path.cas_id.as_deref());
+
+						let (thumbnail, thumbnail_status) =
+							indexed_thumbnail(&node, library.id, cas_id)
+								.await
+								.map_err(LocationError::from)?;
+
+						items.push(ExplorerItem::Object {
+							thumbnail,
+							thumbnail_status,
+							item: object,
+						});
+					}
+
+					let (nodes, items) = items.normalise(|item| item.cache_key());
+
+					Ok(NormalisedResults { items, nodes })
+				})
+		})
+		.procedure("favorites", {
+			R.with2(library())
+				.query(|(node, library), _: ()| async move {
+					let (nodes, items) = favorite_items(&node, &library)
+						.await?
+						.normalise(|item| item.cache_key());
+
+					Ok(NormalisedResults { items, nodes })
+				})
+		})
+		.procedure("groupedByDate", {
+			R.with2(library())
+				.query(|(node, library), args: LocationMediaDateArgs| async move {
+					Ok(
+						location_buckets(&library.db, library.id, node.thumbnailer.format(), args)
+							.await?,
+					)
+				})
+		})
+		.procedure("verifyIntegrity", {
+			#[derive(Type, Deserialize)]
+			pub struct VerifyIntegrityArgs {
+				pub location_id: location::id::Type,
+				/// Only verify the files of these objects, instead of the whole location.
+				#[serde(default)]
+				pub object_ids: Option<Vec<object::id::Type>>,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library),
+				 VerifyIntegrityArgs {
+				     location_id,
+				     object_ids,
+				 }: VerifyIntegrityArgs| async move {
+					let Some(location) = find_location(&library, location_id).exec().await? else {
+						return Err(LocationError::IdNotFound(location_id).into());
+					};
+
+					Job::new(IntegrityVerifierJobInit {
+						location,
+						object_ids,
+					})
+					.spawn(&node, &library)
+					.await
+					.map_err(Into::into)
+				},
+			)
+		})
+		.procedure("integrityReport", {
+			R.with2(library())
+				.query(|(_, library), location_id: location::id::Type| async move {
+					let Some(location) = find_location(&library, location_id).exec().await? else {
+						return Err(LocationError::IdNotFound(location_id).into());
+					};
+
+					let mismatches = library
+						.db
+						.integrity_mismatch()
+						.find_many(vec![integrity_mismatch::location_id::equals(location_id)])
+						.order_by(integrity_mismatch::date_detected::order(SortOrder::Desc))
+						.include(integrity_mismatch_with_file_path::include())
+						.exec()
+						.await?;
+
+					Ok(IntegrityReport {
+						verified_at: location.integrity_verified_at,
+						mismatches,
+					})
+				})
+		})
+		.procedure("encrypt", {
+			R.with2(library())
+				.mutation(|(node, library), args: FileEncryptorJobInit| async move {
+					if args.password.is_none() {
+						return Err(rspc::Error::new(
+							ErrorCode::BadRequest,
+							"A password is required to encrypt files".to_string(),
+						));
+					}
+
+					Job::new(args)
+						.spawn(&node, &library)
+						.await
+						.map_err(Into::into)
+				})
+		})
+		.procedure("decrypt", {
+			R.with2(library())
+				.mutation(|(node, library), args: FileDecryptorJobInit| async move {
+					if args.password.is_none() {
+						return Err(rspc::Error::new(
+							ErrorCode::BadRequest,
+							"A password is required to decrypt files".to_string(),
+						));
+					}
+
+					Job::new(args)
+						.spawn(&node, &library)
+						.await
+						.map_err(Into::into)
+				})
+		})
 		.procedure("deleteFiles", {
 			R.with2(library())
 				.mutation(|(node, library), args: FileDeleterJobInit| async move {
@@ -531,6 +948,20 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.map_err(Into::into)
 				})
 		})
+		.procedure("rename", {
+			#[derive(Type, Deserialize)]
+			pub struct RenameArgs {
+				pub file_path_id: file_path::id::Type,
+				pub new_name: String,
+			}
+
+			R.with2(library())
+				.mutation(|(node, library), args: RenameArgs| async move {
+					rename_file_path(&node, &library, args.file_path_id, &args.new_name)
+						.await
+						.map_err(Into::into)
+				})
+		})
 		.procedure("renameFile", {
 			#[derive(Type, Deserialize)]
 			pub struct RenameOne {
@@ -736,6 +1167,288 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 		})
 }
 
+async fn queue_media_data_extraction(
+	library: &Arc<Library>,
+	object_id: object::id::Type,
+) -> Result<MediaDataState, rspc::Error> {
+	let key = (library.id, object_id);
+
+	if MEDIA_DATA_UNAVAILABLE.contains_key(&key) {
+		return Ok(MediaDataState::Unavailable);
+	}
+
+	if let Some(error) = MEDIA_DATA_FAILED.get(&key) {
+		return Ok(MediaDataState::Failed { error });
+	}
+
+	if !MEDIA_DATA_EXTRACTIONS
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.insert(key)
+	{
+		return Ok(MediaDataState::Pending);
+	}
+
+	let set_finished = move |unavailable: bool| {
+		if unavailable {
+			MEDIA_DATA_UNAVAILABLE.insert(key, ());
+		}
+
+		MEDIA_DATA_EXTRACTIONS
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&key);
+	};
+
+	let path = match media_data_source_path(library, object_id).await {
+		Ok(Some(path)) => path,
+		Ok(None) => {
+			set_finished(true);
+			return Ok(MediaDataState::Unavailable);
+		}
+		Err(e) => {
+			set_finished(false);
+			return Err(e);
+		}
+	};
+
+	let library = Arc::clone(library);
+	tokio::spawn(async move {
+		match media_data_extractor::process_single(&path, object_id, &library.db).await {
+			Ok(has_media_data) => {
+				set_finished(!has_media_data);
+				invalidate_query!(library, "files.getMediaData");
+			}
+			Err(e) => {
+				error!(
+					"Failed to extract media data for object <id='{object_id}'> at \"{}\": {e:#?}",
+					path.display()
+				);
+
+				// Remembered before invalidating, otherwise the refetch would retry it in a loop
+				MEDIA_DATA_FAILED.insert(key, e.to_string());
+				set_finished(false);
+				invalidate_query!(library, "files.getMediaData");
+			}
+		}
+	});
+
+	Ok(MediaDataState::Pending)
+}
+
+/// Find a file of the object which we're able to extract media data from.
+async fn media_data_source_path(
+	library: &Library,
+	object_id: object::id::Type,
+) -> Result<Option<PathBuf>, rspc::Error> {
+	let Some(file_path) = library
+		.db
+		.file_path()
+		.find_first(vec![
+			file_path::object_id::equals(Some(object_id)),
+			file_path::is_dir::equals(Some(false)),
+		])
+		.select(file_path_to_isolate::select())
+		.exec()
+		.await?
+	else {
+		return Ok(None);
+	};
+
+	if !file_path
+		.extension
+		.as_deref()
+		.and_then(|extension| ImageExtension::from_str(extension).ok())
+		.is_some_and(|extension| can_extract_media_data_for_image(&extension))
+	{
+		return Ok(None);
+	}
+
+	let isolated_path =
+		IsolatedFilePathData::try_from(file_path).map_err(LocationError::MissingField)?;
+	let location_path =
+		get_location_path_from_location_id(&library.db, isolated_path.location_id()).await?;
+
+	Ok(Some(location_path.join(&isolated_path)))
+}
+
+/// Documents' metadata is read from the file each time, as it's only their page count for now.
+async fn document_media_data(
+	library: &Library,
+	object_id: object::id::Type,
+) -> Result<MediaDataState, rspc::Error> {
+	let file_paths = library
+		.db
+		.file_path()
+		.find_many(vec![
+			file_path::object_id::equals(Some(object_id)),
+			file_path::is_dir::equals(Some(false)),
+		])
+		.select(file_path::select!({ id extension }))
+		.exec()
+		.await?;
+
+	let Some(file_path) = file_paths.into_iter().find(|file_path| {
+		matches!(
+			file_path
+				.extension
+				.as_deref()
+				.map(DocumentExtension::from_str),
+			Some(Ok(DocumentExtension::Pdf))
+		)
+	}) else {
+		return Ok(MediaDataState::Unavailable);
+	};
+
+	let path = existing_file_path(library, file_path.id).await?;
+
+	Ok(MediaDataState::Ready(MediaMetadata::Document(Box::new(
+		DocumentMetadata {
+			page_count: pdf_page_count(&path).await?.into(),
+		},
+	))))
+}
+
+pub(super) async fn pdf_page_count(path: &Path) -> Result<u16, rspc::Error> {
+	let path = path.to_path_buf();
+
+	spawn_blocking(move || sd_images::pdf_page_count(&path).map_err(|e| e.to_string()))
+		.await
+		.map_err(|e| e.to_string())
+		.and_then(|res| res)
+		.map_err(|e| {
+			rspc::Error::new(
+				ErrorCode::InternalServerError,
+				format!("Failed to read the document: {e}"),
+			)
+		})
+}
+
+/// The cas_id of a file path, which its thumbnails are stored by
+async fn identified_cas_id(
+	library: &Library,
+	file_path_id: file_path::id::Type,
+) -> Result<String, rspc::Error> {
+	let file_path = library
+		.db
+		.file_path()
+		.find_unique(file_path::id::equals(file_path_id))
+		.select(file_path::select!({ cas_id }))
+		.exec()
+		.await?
+		.ok_or_else(|| ApiError::NotFound(format!("File path not found: <id='{file_path_id}'>")))?;
+
+	file_path
+		.cas_id
+		.ok_or_else(|| ApiError::Conflict("The file hasn't been identified yet".to_string()).into())
+}
+
+/// The absolute path of a file path, which must still exist on disk.
+///
+/// The path is built from the location and materialized path components instead of strings, so
+/// spaces and non-ASCII names are passed to the OS untouched.
+pub(super) async fn existing_file_path(
+	library: &Library,
+	file_path_id: file_path::id::Type,
+) -> Result<PathBuf, rspc::Error> {
+	let Some(file_path) = library
+		.db
+		.file_path()
+		.find_unique(file_path::id::equals(file_path_id))
+		.select(file_path_to_isolate::select())
+		.exec()
+		.await?
+	else {
+		return Err(
+			ApiError::NotFound(format!("File path not found: <id='{file_path_id}'>")).into(),
+		);
+	};
+
+	let isolated_path =
+		IsolatedFilePathData::try_from(file_path).map_err(LocationError::MissingField)?;
+	let location_path =
+		get_location_path_from_location_id(&library.db, isolated_path.location_id()).await?;
+	let path = location_path.join(&isolated_path);
+
+	match fs::metadata(&path).await {
+		Ok(_) => Ok(path),
+		// The location may be offline or the file moved since it was last indexed
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {
+			Err(ApiError::NotFound(format!("File no longer exists: '{}'", path.display())).into())
+		}
+		Err(e) => Err(FileIOError::from((&path, e)).into()),
+	}
+}
+
+fn open_with_provider(node: &Node) -> Result<&dyn OpenWith, OpenWithError> {
+	node.env
+		.open_with
+		.as_deref()
+		.ok_or(OpenWithError::NotSupported)
+}
+
+/// The core only opens files in online locations of the library or in directories the user browsed
+/// to, so the API can't be used to run arbitrary files.
+async fn check_path_can_be_opened(
+	node: &Node,
+	library: &Library,
+	path: &Path,
+) -> Result<(), rspc::Error> {
+	if !path.is_absolute()
+		|| path
+			.components()
+			.any(|component| matches!(component, Component::ParentDir))
+	{
+		return Err(rspc::Error::new(
+			ErrorCode::BadRequest,
+			"Path must be absolute and normalized".to_string(),
+		));
+	}
+
+	if path
+		.parent()
+		.is_some_and(|parent| node.ephemeral_paths.contains_key(parent))
+	{
+		return Ok(());
+	}
+
+	let locations = library
+		.db
+		.location()
+		.find_many(vec![location::instance_id::equals(Some(
+			library.config().await.instance_id,
+		))])
+		.select(location::select!({ pub_id path }))
+		.exec()
+		.await?;
+
+	for location in locations {
+		let Some(location_path) = location.path else {
+			continue;
+		};
+
+		if path.starts_with(&location_path)
+			&& node
+				.locations
+				.is_online(&Uuid::from_slice(&location.pub_id).map_err(|e| {
+					rspc::Error::with_cause(
+						ErrorCode::InternalServerError,
+						"Location has an invalid pub_id".to_string(),
+						e,
+					)
+				})?)
+				.await
+		{
+			return Ok(());
+		}
+	}
+
+	Err(rspc::Error::new(
+		ErrorCode::Forbidden,
+		"Path is not in an online location or a browsed directory".to_string(),
+	))
+}
+
 pub(super) async fn create_directory(
 	mut target_path: PathBuf,
 	library: &Library,
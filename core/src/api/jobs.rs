@@ -1,11 +1,13 @@
 use crate::{
 	invalidate_query,
-	job::{job_without_data, Job, JobReport, JobStatus, Jobs},
+	job::{job_without_data, Job, JobPriority, JobReport, JobStatus, Jobs},
 	location::{find_location, LocationError},
+	node::{BusEvent, EventCategory, EventFilter},
 	object::{
 		file_identifier::file_identifier_job::FileIdentifierJobInit, media::MediaProcessorJobInit,
 		validation::validator_job::ObjectValidatorJobInit,
 	},
+	preferences::LibraryPreferences,
 };
 
 use sd_prisma::prisma::{job, location, SortOrder};
@@ -17,6 +19,7 @@ use std::{
 };
 
 use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use prisma_client_rust::or;
 use rspc::alpha::AlphaRouter;
 use serde::{Deserialize, Serialize};
@@ -35,17 +38,18 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			// - the client replaces its local copy of the JobReport using the index provided by the reports procedure
 			// - this should be used with the ephemeral sync engine
 			R.with2(library())
-				.subscription(|(node, _), _: ()| async move {
-					let mut event_bus_rx = node.event_bus.0.subscribe();
+				.subscription(|(node, library), _: ()| async move {
+					let mut events = node.subscribe(
+						EventFilter::categories([EventCategory::Jobs]).library(library.id),
+					);
 					// debounce per-job
 					let mut intervals = BTreeMap::<Uuid, Instant>::new();
 
 					async_stream::stream! {
-						loop {
-							let progress_event = loop {
-								if let Ok(CoreEvent::JobProgress(progress_event)) = event_bus_rx.recv().await {
-									break progress_event;
-								}
+						// Dropped progress events are fine, the next one has the latest progress
+						while let Some(event) = events.next().await {
+							let BusEvent::Event(CoreEvent::JobProgress(progress_event)) = event else {
+								continue;
 							};
 
 							let instant = intervals.entry(progress_event.id).or_insert_with(
@@ -223,6 +227,23 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					ret
 				})
 		})
+		.procedure("setPriority", {
+			#[derive(Type, Deserialize)]
+			pub struct SetJobPriorityArgs {
+				pub id: Uuid,
+				pub priority: JobPriority,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library), SetJobPriorityArgs { id, priority }: SetJobPriorityArgs| async move {
+					let ret = Jobs::reprioritize(&node.jobs, &library, id, priority)
+						.await
+						.map_err(Into::into);
+					invalidate_query!(library, "jobs.reports");
+					ret
+				},
+			)
+		})
 		.procedure("generateThumbsForLocation", {
 			#[derive(Type, Deserialize)]
 			pub struct GenerateThumbsForLocationArgs {
@@ -248,6 +269,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						sub_path: Some(path),
 						regenerate_thumbnails: regenerate,
 						regenerate_labels: false,
+						labels_min_confidence: None,
 					})
 					.spawn(&node, &library)
 					.await
@@ -280,6 +302,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						sub_path: Some(path),
 						regenerate_thumbnails: false,
 						regenerate_labels: regenerate,
+						labels_min_confidence: None,
 					})
 					.spawn(&node, &library)
 					.await
@@ -325,6 +348,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Job::new(FileIdentifierJobInit {
 						location,
 						sub_path: Some(args.path),
+						full_hash: LibraryPreferences::read(&library.db).await?.full_hash(),
 					})
 					.spawn(&node, &library)
 					.await
@@ -334,15 +358,15 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 		})
 		.procedure("newThumbnail", {
 			R.with2(library())
-				.subscription(|(node, _), _: ()| async move {
-					// TODO: Only return event for the library that was subscribed to
+				.subscription(|(node, library), _: ()| async move {
+					let mut events = node.subscribe(
+						EventFilter::categories([EventCategory::Thumbnails]).library(library.id),
+					);
 
-					let mut event_bus_rx = node.event_bus.0.subscribe();
 					async_stream::stream! {
-						while let Ok(event) = event_bus_rx.recv().await {
-							match event {
-								CoreEvent::NewThumbnail { thumb_key } => yield thumb_key,
-								_ => {}
+						while let Some(event) = events.next().await {
+							if let BusEvent::Event(CoreEvent::NewThumbnail { thumb_key }) = event {
+								yield thumb_key;
 							}
 						}
 					}
@@ -1,15 +1,28 @@
-use crate::{invalidate_query, library::Library, object::media::thumbnail::get_indexed_thumb_key};
+use crate::{
+	invalidate_query,
+	job::Job,
+	library::Library,
+	location::{find_location, LocationError},
+	object::media::{thumbnail::get_indexed_thumb_key, MediaProcessorJobInit},
+	Node,
+};
 
-use sd_prisma::prisma::{label, label_on_object, object, SortOrder};
+use sd_prisma::prisma::{label, label_on_object, location, object, SortOrder};
 
 use std::collections::BTreeMap;
 
 use rspc::alpha::AlphaRouter;
+use serde::Deserialize;
+use specta::Type;
 
-use super::{locations::ExplorerItem, utils::library, Ctx, R};
+use super::{
+	locations::ExplorerItem,
+	utils::{library, ApiError},
+	Ctx, R,
+};
 
-label::include!((take: i64) => label_with_objects {
-	label_objects(vec![]).take(take): select {
+label::include!((take: i64, filter: Vec<label_on_object::WhereParam>) => label_with_objects {
+	label_objects(filter).take(take): select {
 		object: select {
 			id
 			file_paths(vec![]).take(1)
@@ -25,14 +38,42 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			})
 		})
 		.procedure("listWithThumbnails", {
-			R.with2(library())
-				.query(|(_, library), cursor: label::name::Type| async move {
+			#[derive(Type, Deserialize)]
+			pub struct ListWithThumbnailsArgs {
+				pub cursor: label::name::Type,
+				/// Exclude labels which were only assigned with a lower confidence than this.
+				#[serde(default)]
+				#[specta(optional)]
+				pub min_confidence: Option<f64>,
+			}
+
+			R.with2(library()).query(
+				|(node, library),
+				 ListWithThumbnailsArgs {
+				     cursor,
+				     min_confidence,
+				 }: ListWithThumbnailsArgs| async move {
+					let thumbnail_format = node.thumbnailer.format();
+
+					let confident = || {
+						min_confidence
+							.map(|min_confidence| {
+								vec![label_on_object::confidence::gte(min_confidence)]
+							})
+							.unwrap_or_default()
+					};
+
+					let mut params = vec![label::name::gt(cursor)];
+					if min_confidence.is_some() {
+						params.push(label::label_objects::some(confident()));
+					}
+
 					Ok(library
 						.db
 						.label()
-						.find_many(vec![label::name::gt(cursor)])
+						.find_many(params)
 						.order_by(label::name::order(SortOrder::Asc))
-						.include(label_with_objects::include(4))
+						.include(label_with_objects::include(4, confident()))
 						.exec()
 						.await?
 						.into_iter()
@@ -47,15 +88,15 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 									label_object.object.file_paths.into_iter().next()
 								})
 								.filter_map(|file_path_data| {
-									file_path_data
-										.cas_id
-										.as_ref()
-										.map(|cas_id| get_indexed_thumb_key(cas_id, library.id))
+									file_path_data.cas_id.as_ref().map(|cas_id| {
+										get_indexed_thumb_key(cas_id, library.id, thumbnail_format)
+									})
 								}) // Filter out None values and transform each element to Vec<Vec<String>>
 								.collect::<Vec<_>>(), // Collect into Vec<Vec<Vec<String>>>
 						})
 						.collect::<Vec<_>>())
-				})
+				},
+			)
 		})
 		.procedure("count", {
 			R.with2(library()).query(|(_, library), _: ()| async move {
@@ -88,6 +129,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 							id
 							label_objects(vec![label_on_object::object_id::in_vec(object_ids.clone())]): select {
 								date_created
+								confidence
 								object: select {
 									id
 								}
@@ -129,4 +171,167 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Ok(())
 				}),
 		)
+		.procedure("removeFromObject", {
+			#[derive(Type, Deserialize)]
+			pub struct RemoveLabelFromObjectArgs {
+				pub object_id: object::id::Type,
+				pub label_id: label::id::Type,
+			}
+
+			R.with2(library()).mutation(
+				|(_, library),
+				 RemoveLabelFromObjectArgs {
+				     object_id,
+				     label_id,
+				 }: RemoveLabelFromObjectArgs| async move {
+					library
+						.db
+						.label_on_object()
+						.delete_many(vec![
+							label_on_object::object_id::equals(object_id),
+							label_on_object::label_id::equals(label_id),
+						])
+						.exec()
+						.await?;
+
+					invalidate_labels(&library);
+
+					Ok(())
+				},
+			)
+		})
+		.procedure("reprocessObject", {
+			R.with2(library())
+				.mutation(|(node, library), object_id: object::id::Type| async move {
+					ensure_ai_supported()?;
+
+					reprocess_object(&node, &library, object_id).await
+				})
+		})
+		.procedure("reprocessLocation", {
+			#[derive(Type, Deserialize)]
+			pub struct ReprocessLocationLabelsArgs {
+				pub location_id: location::id::Type,
+				/// Labels which aren't detected again with at least this confidence are removed.
+				pub min_confidence: f64,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library),
+				 ReprocessLocationLabelsArgs {
+				     location_id,
+				     min_confidence,
+				 }: ReprocessLocationLabelsArgs| async move {
+					ensure_ai_supported()?;
+
+					let Some(location) = find_location(&library, location_id).exec().await? else {
+						return Err(LocationError::IdNotFound(location_id).into());
+					};
+
+					Job::new(MediaProcessorJobInit {
+						location,
+						sub_path: None,
+						regenerate_thumbnails: false,
+						regenerate_labels: true,
+						labels_min_confidence: Some(min_confidence),
+					})
+					.spawn(&node, &library)
+					.await
+					.map_err(Into::into)
+				},
+			)
+		})
+}
+
+fn ensure_ai_supported() -> Result<(), rspc::Error> {
+	if cfg!(feature = "ai") {
+		Ok(())
+	} else {
+		Err(ApiError::Unsupported("AI feature is not available".to_string()).into())
+	}
+}
+
+fn invalidate_labels(library: &Library) {
+	invalidate_query!(library, "labels.list");
+	invalidate_query!(library, "labels.listWithThumbnails");
+	invalidate_query!(library, "labels.getForObject");
+	invalidate_query!(library, "labels.getWithObjects");
+}
+
+/// Replace the labels of the object with freshly detected ones, this runs in the background.
+#[cfg(feature = "ai")]
+async fn reprocess_object(
+	node: &Node,
+	library: &std::sync::Arc<Library>,
+	object_id: object::id::Type,
+) -> Result<(), rspc::Error> {
+	use sd_file_path_helper::file_path_for_media_processor;
+	use sd_prisma::prisma::file_path;
+	use sd_utils::db::maybe_missing;
+
+	use std::{path::PathBuf, sync::Arc};
+
+	use futures::StreamExt;
+	use tracing::error;
+
+	let Library { db, .. } = library.as_ref();
+
+	let Some(file_path) = db
+		.file_path()
+		.find_first(vec![
+			file_path::object_id::equals(Some(object_id)),
+			file_path::cas_id::not(None),
+		])
+		.select(file_path_for_media_processor::select())
+		.exec()
+		.await?
+	else {
+		return Err(ApiError::NotFound("Object has no file to label".to_string()).into());
+	};
+
+	let location = db
+		.location()
+		.find_first(vec![location::file_paths::some(vec![
+			file_path::id::equals(file_path.id),
+		])])
+		.select(location::select!({ id path }))
+		.exec()
+		.await?
+		.ok_or_else(|| ApiError::NotFound("Location not found".to_string()))?;
+
+	let location_path = maybe_missing(location.path, "location.path")
+		.map(PathBuf::from)
+		.map_err(LocationError::from)?;
+
+	db.label_on_object()
+		.delete_many(vec![label_on_object::object_id::equals(object_id)])
+		.exec()
+		.await?;
+
+	let mut labels_rx = node
+		.image_labeller
+		.new_batch(location.id, location_path, vec![file_path], Arc::clone(db))
+		.await;
+
+	let library = Arc::clone(library);
+	tokio::spawn(async move {
+		while let Some(output) = labels_rx.next().await {
+			if let Err(e) = output.result {
+				error!("Failed to relabel object <id='{object_id}'>: {e:#?}");
+			}
+		}
+
+		invalidate_labels(&library);
+	});
+
+	Ok(())
+}
+
+#[cfg(not(feature = "ai"))]
+async fn reprocess_object(
+	_: &Node,
+	_: &std::sync::Arc<Library>,
+	_: object::id::Type,
+) -> Result<(), rspc::Error> {
+	ensure_ai_supported()
 }
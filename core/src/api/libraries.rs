@@ -1,16 +1,21 @@
 use crate::{
 	invalidate_query,
-	library::{update_library_statistics, Library, LibraryConfig, LibraryName},
+	job::Job,
+	library::{
+		refresh_library_statistics, KeyValueNamespace, Libraries, Library, LibraryConfig,
+		LibraryManagerEvent, LibraryName,
+	},
 	location::{scan_location, LocationCreateArgs},
+	object::{consolidator::ObjectConsolidatorJobInit, orphan_remover::remove_orphan_objects},
 	util::MaybeUndefined,
 	Node,
 };
 
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use sd_cache::{Model, Normalise, NormalisedResult, NormalisedResults};
 use sd_file_ext::kind::ObjectKind;
 use sd_p2p::spacetunnel::RemoteIdentity;
-use sd_prisma::prisma::{indexer_rule, object, statistics};
+use sd_prisma::prisma::{indexer_rule, statistics, statistics_history, PrismaClient, SortOrder};
 use tokio_stream::wrappers::IntervalStream;
 
 use std::{
@@ -22,10 +27,12 @@ use std::{
 };
 
 use async_channel as chan;
+use chrono::{DateTime, Utc};
 use directories::UserDirs;
 use futures_concurrency::{future::Join, stream::Merge};
 use once_cell::sync::Lazy;
-use rspc::{alpha::AlphaRouter, ErrorCode};
+use prisma_client_rust::{QueryError, Raw};
+use rspc::alpha::AlphaRouter;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use strum::IntoEnumIterator;
@@ -37,7 +44,10 @@ use tokio::{
 use tracing::{debug, error};
 use uuid::Uuid;
 
-use super::{utils::library, Ctx, R};
+use super::{
+	utils::{library, rate_limited_library, ApiError, RateLimit},
+	Ctx, R,
+};
 
 const ONE_MINUTE: Duration = Duration::from_secs(60);
 const TWO_MINUTES: Duration = Duration::from_secs(60 * 2);
@@ -99,11 +109,15 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			})
 		})
 		.procedure("statistics", {
+			const LIMIT: RateLimit = RateLimit::new("library.statistics", Duration::from_secs(1));
+
 			#[derive(Serialize, Deserialize, Type)]
 			pub struct StatisticsResponse {
 				statistics: Option<statistics::Data>,
+				/// When the statistics were last refreshed, whether a client asked for it or not.
+				last_refreshed_at: Option<DateTime<Utc>>,
 			}
-			R.with2(library())
+			R.with2(rate_limited_library(LIMIT))
 				.query(|(node, library), _: ()| async move {
 					let statistics = library
 						.db
@@ -112,55 +126,68 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.exec()
 						.await?;
 
-					match STATISTICS_UPDATERS.lock().await.entry(library.id) {
-						Entry::Occupied(entry) => {
-							if entry.get().send(Instant::now()).await.is_err() {
-								error!("Failed to send statistics update request");
-							}
-						}
-						Entry::Vacant(entry) => {
-							let (tx, rx) = chan::bounded(1);
-							entry.insert(tx);
-
-							spawn(update_statistics_loop(node, library, rx));
-						}
-					}
+					request_statistics_update(&node, &library).await;
 
-					Ok(StatisticsResponse { statistics })
+					Ok(StatisticsResponse {
+						last_refreshed_at: statistics
+							.as_ref()
+							.map(|statistics| statistics.date_captured.into()),
+						statistics,
+					})
 				})
 		})
-		.procedure("kindStatistics", {
-			#[derive(Serialize, Deserialize, Type, Default)]
-			pub struct KindStatistic {
-				kind: i32,
-				name: String,
-				count: i32,
-				total_bytes: String,
-			}
-			#[derive(Serialize, Deserialize, Type, Default)]
-			pub struct KindStatistics {
-				statistics: Vec<KindStatistic>,
+		.procedure("statisticsHistory", {
+			#[derive(Deserialize, Type)]
+			pub struct StatisticsHistoryArgs {
+				/// Only points captured after this date, or the whole history if `null`.
+				since: Option<DateTime<Utc>>,
 			}
-			R.with2(library()).query(|(_, library), _: ()| async move {
-				let mut statistics: Vec<KindStatistic> = vec![];
-				for kind in ObjectKind::iter() {
-					let count = library
+
+			R.with2(library()).query(
+				|(_, library), StatisticsHistoryArgs { since }: StatisticsHistoryArgs| async move {
+					Ok(library
 						.db
-						.object()
-						.count(vec![object::kind::equals(Some(kind as i32))])
+						.statistics_history()
+						.find_many(
+							since
+								.map(|since| statistics_history::date_captured::gt(since.into()))
+								.into_iter()
+								.collect(),
+						)
+						.order_by(statistics_history::date_captured::order(SortOrder::Asc))
 						.exec()
-						.await?;
+						.await?)
+				},
+			)
+		})
+		.procedure("kindStatistics", {
+			R.with2(library()).query(|(_, library), _: ()| async move {
+				Ok(KindStatistics {
+					statistics: kind_statistics(&library.db).await?,
+				})
+			})
+		})
+		.procedure("consolidateObjects", {
+			R.with2(library())
+				.mutation(|(node, library), _: ()| async move {
+					Job::new(ObjectConsolidatorJobInit { location_id: None })
+						.spawn(&node, &library)
+						.await
+						.map_err(Into::into)
+				})
+		})
+		.procedure("cleanOrphans", {
+			R.with2(library())
+				.mutation(|(_, library), _: ()| async move {
+					let cleanup = remove_orphan_objects(&library).await?;
 
-					statistics.push(KindStatistic {
-						kind: kind as i32,
-						name: kind.to_string(),
-						count: count as i32,
-						total_bytes: "0".to_string(),
-					});
-				}
+					if cleanup.removed > 0 {
+						invalidate_query!(library, "search.objects");
+						invalidate_query!(library, "library.statistics");
+					}
 
-				Ok(KindStatistics { statistics })
-			})
+					Ok(cleanup)
+				})
 		})
 		.procedure("create", {
 			#[derive(Deserialize, Type, Default)]
@@ -200,10 +227,9 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				}
 
 				let Some(default_locations_paths) = UserDirs::new() else {
-					return Err(rspc::Error::new(
-						ErrorCode::NotFound,
-						"Didn't find any system locations".to_string(),
-					));
+					return Err(
+						ApiError::NotFound("Didn't find any system locations".to_string()).into(),
+					);
 				};
 
 				let default_rules_ids = library
@@ -214,9 +240,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					.exec()
 					.await
 					.map_err(|e| {
-						rspc::Error::with_cause(
-							ErrorCode::InternalServerError,
-							"Failed to get default indexer rules for default locations".to_string(),
+						ApiError::internal(
+							"Failed to get default indexer rules for default locations",
 							e,
 						)
 					})?
@@ -246,6 +271,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 								path,
 								dry_run: false,
 								indexer_rules_ids,
+								interactive: false,
+								force: false,
 							}
 							.create(&node, &library)
 							.await
@@ -254,7 +281,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 								return Ok(());
 							};
 
-							scan_location(&node, &library, location)
+							scan_location(&node, &library, location, false)
 								.await
 								.map_err(rspc::Error::from)
 						}))
@@ -268,11 +295,9 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				.into_iter()
 				.map(|spawn_res| {
 					spawn_res
-						.map_err(|_| {
-							rspc::Error::new(
-								ErrorCode::InternalServerError,
-								"A task to create a default location failed".to_string(),
-							)
+						.map_err(|e| {
+							ApiError::internal("A task to create a default location failed", e)
+								.into()
 						})
 						.and_then(identity)
 				})
@@ -326,6 +351,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			pub struct EditLibraryArgs {
 				pub id: Uuid,
 				pub name: Option<LibraryName>,
+				#[serde(default)]
+				#[specta(optional)]
 				pub description: MaybeUndefined<String>,
 			}
 
@@ -383,8 +410,196 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Ok(())
 				}),
 		)
+		.merge("kv.", mount_kv_routes())
+}
+
+#[derive(Type, Deserialize)]
+pub struct KeyValueArgs {
+	pub namespace: String,
+	pub key: String,
 }
 
+fn mount_kv_routes() -> AlphaRouter<Ctx> {
+	R.router()
+		.procedure("get", {
+			R.with2(library()).query(
+				|(_, library), KeyValueArgs { namespace, key }: KeyValueArgs| async move {
+					Ok(KeyValueNamespace::new(&library.db, &namespace)?
+						.get(&key)
+						.await?)
+				},
+			)
+		})
+		.procedure("list", {
+			R.with2(library())
+				.query(|(_, library), namespace: String| async move {
+					Ok(KeyValueNamespace::new(&library.db, &namespace)?
+						.list()
+						.await?)
+				})
+		})
+		.procedure("set", {
+			#[derive(Type, Deserialize)]
+			pub struct SetKeyValueArgs {
+				pub namespace: String,
+				pub key: String,
+				pub value: serde_json::Value,
+			}
+
+			R.with2(library()).mutation(
+				|(_, library),
+				 SetKeyValueArgs {
+				     namespace,
+				     key,
+				     value,
+				 }: SetKeyValueArgs| async move {
+					KeyValueNamespace::new(&library.db, &namespace)?
+						.set(&key, &value)
+						.await?;
+
+					invalidate_query!(library, "library.kv.get");
+					invalidate_query!(library, "library.kv.list");
+
+					Ok(())
+				},
+			)
+		})
+		.procedure("delete", {
+			R.with2(library()).mutation(
+				|(_, library), KeyValueArgs { namespace, key }: KeyValueArgs| async move {
+					KeyValueNamespace::new(&library.db, &namespace)?
+						.delete(&key)
+						.await?;
+
+					invalidate_query!(library, "library.kv.get");
+					invalidate_query!(library, "library.kv.list");
+
+					Ok(())
+				},
+			)
+		})
+}
+
+#[derive(Serialize, Deserialize, Type, Default)]
+pub struct KindStatistic {
+	kind: i32,
+	name: String,
+	count: i32,
+	total_bytes: String,
+}
+
+#[derive(Serialize, Deserialize, Type, Default)]
+pub struct KindStatistics {
+	statistics: Vec<KindStatistic>,
+}
+
+/// SQLite can't read integers out of blobs, so the big endian `size_in_bytes_bytes` is decoded
+/// from its hex representation, one nibble at a time. A `NULL` size decodes to 0.
+fn size_in_bytes_sql(column: &str) -> String {
+	(0..16)
+		.map(|nibble| {
+			format!(
+				"((instr('0123456789ABCDEF', substr(hex({column}), {}, 1)) - 1) << {})",
+				nibble + 1,
+				(15 - nibble) * 4
+			)
+		})
+		.collect::<Vec<_>>()
+		.join(" | ")
+}
+
+/// Counts the objects of every kind and sums the sizes of their file paths in a single query.
+/// Kinds without any objects are still reported, with zeroes.
+async fn kind_statistics(db: &PrismaClient) -> Result<Vec<KindStatistic>, QueryError> {
+	#[derive(Deserialize)]
+	struct KindRow {
+		kind: Option<i32>,
+		count: i64,
+		total_bytes: i64,
+	}
+
+	let rows = db
+		._query_raw::<KindRow>(Raw::new(
+			&format!(
+				"SELECT
+					o.kind AS kind,
+					COUNT(DISTINCT o.id) AS count,
+					COALESCE(SUM({}), 0) AS total_bytes
+				FROM object o
+				LEFT JOIN file_path fp ON fp.object_id = o.id
+				WHERE o.kind IS NOT NULL
+				GROUP BY o.kind",
+				size_in_bytes_sql("fp.size_in_bytes_bytes")
+			),
+			vec![],
+		))
+		.exec()
+		.await?;
+
+	let mut per_kind = rows
+		.into_iter()
+		.filter_map(|row| Some((row.kind?, (row.count, row.total_bytes))))
+		.collect::<HashMap<_, _>>();
+
+	Ok(ObjectKind::iter()
+		.map(|kind| {
+			let (count, total_bytes) = per_kind.remove(&(kind as i32)).unwrap_or_default();
+
+			KindStatistic {
+				kind: kind as i32,
+				name: kind.to_string(),
+				count: count as i32,
+				total_bytes: (total_bytes.max(0) as u64).to_string(),
+			}
+		})
+		.collect())
+}
+
+/// Asks the library's statistics updater for fresh statistics, starting it if it isn't running.
+async fn request_statistics_update(node: &Arc<Node>, library: &Arc<Library>) {
+	let mut updaters = STATISTICS_UPDATERS.lock().await;
+
+	if let Some(tx) = updaters.get(&library.id) {
+		match tx.try_send(Instant::now()) {
+			// A full channel means the updater still has a request to handle
+			Ok(()) | Err(chan::TrySendError::Full(_)) => return,
+			Err(chan::TrySendError::Closed(_)) => {}
+		}
+	}
+
+	let (tx, rx) = chan::bounded(1);
+	updaters.insert(library.id, tx);
+
+	spawn(update_statistics_loop(
+		Arc::clone(node),
+		Arc::clone(library),
+		rx,
+	));
+}
+
+/// Stops the statistics updaters of deleted libraries, so they don't keep the library alive.
+pub(crate) fn start_statistics_updaters_cleanup(libraries: &Libraries) {
+	let rx = libraries.rx.clone();
+
+	spawn(async move {
+		let subscribe_res = rx
+			.subscribe(|event| async move {
+				if let LibraryManagerEvent::Delete(library) = event {
+					if let Some(tx) = STATISTICS_UPDATERS.lock().await.remove(&library.id) {
+						tx.close();
+					}
+				}
+			})
+			.await;
+
+		if subscribe_res.is_err() {
+			error!("Statistics updaters cleanup has crashed...");
+		}
+	});
+}
+
+/// Updates the library statistics every minute while a client has requested them in the last
+/// five minutes. It stops once the library is gone, its channel is closed or no more requests arrive.
 async fn update_statistics_loop(
 	node: Arc<Node>,
 	library: Arc<Library>,
@@ -397,31 +612,158 @@ async fn update_statistics_loop(
 	enum Message {
 		Tick,
 		Requested(Instant),
+		Closed,
 	}
 
-	let mut msg_stream = pin!((
-		IntervalStream::new(tick).map(|_| Message::Tick),
-		last_requested_rx.map(Message::Requested)
-	)
-		.merge());
-
-	while let Some(msg) = msg_stream.next().await {
-		match msg {
-			Message::Tick => {
-				if last_received_at.elapsed() < FIVE_MINUTES {
-					if let Err(e) = update_library_statistics(&node, &library).await {
-						error!("Failed to update library statistics: {e:#?}");
-					} else {
-						invalidate_query!(&library, "library.statistics");
+	{
+		let mut msg_stream = pin!((
+			IntervalStream::new(tick).map(|_| Message::Tick),
+			last_requested_rx
+				.map(Message::Requested)
+				.chain(stream::once(async { Message::Closed }))
+		)
+			.merge());
+
+		while let Some(msg) = msg_stream.next().await {
+			match msg {
+				Message::Tick => {
+					if last_received_at.elapsed() >= FIVE_MINUTES {
+						debug!(
+							"No statistics requested for library <id='{}'> lately, stopping its updater",
+							library.id
+						);
+						break;
+					}
+
+					if node.libraries.get_library(&library.id).await.is_none() {
+						break;
+					}
+
+					// Skipped if the background refresh just did it
+					match refresh_library_statistics(&node, &library, chrono::Duration::seconds(30))
+						.await
+					{
+						Ok(Some(_)) => {
+							invalidate_query!(&library, "library.statistics");
+							invalidate_query!(&library, "library.statisticsHistory");
+						}
+						Ok(None) => {}
+						Err(e) => error!("Failed to update library statistics: {e:#?}"),
 					}
 				}
-			}
-			Message::Requested(instant) => {
-				if instant - last_received_at > TWO_MINUTES {
-					debug!("Updating last received at");
-					last_received_at = instant;
+				Message::Requested(instant) => {
+					if instant - last_received_at > TWO_MINUTES {
+						debug!("Updating last received at");
+						last_received_at = instant;
+					}
 				}
+				Message::Closed => break,
 			}
 		}
 	}
+
+	// Our receiver was dropped with the stream, so a closed sender is ours and not a newer updater's
+	if let Entry::Occupied(entry) = STATISTICS_UPDATERS.lock().await.entry(library.id) {
+		if entry.get().is_closed() {
+			entry.remove();
+		}
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_library;
+
+	use sd_prisma::prisma::{file_path, object};
+	use sd_utils::{db::load_and_migrate, uuid_to_bytes};
+
+	use tempfile::tempdir;
+	use tokio::time::timeout;
+
+	async fn seed_object(db: &PrismaClient, kind: ObjectKind, sizes: &[Option<u64>]) {
+		let object = db
+			.object()
+			.create(
+				uuid_to_bytes(Uuid::new_v4()),
+				vec![object::kind::set(Some(kind as i32))],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		for size in sizes {
+			db.file_path()
+				.create(
+					uuid_to_bytes(Uuid::new_v4()),
+					vec![
+						file_path::size_in_bytes_bytes::set(
+							size.map(|size| size.to_be_bytes().to_vec()),
+						),
+						file_path::object::connect(object::id::equals(object.id)),
+					],
+				)
+				.exec()
+				.await
+				.unwrap();
+		}
+	}
+
+	#[tokio::test]
+	async fn test_kind_statistics() {
+		let dir = tempdir().unwrap();
+		let db = load_and_migrate(&format!("file:{}", dir.path().join("library.db").display()))
+			.await
+			.unwrap();
+
+		seed_object(&db, ObjectKind::Image, &[Some(1024)]).await;
+		seed_object(&db, ObjectKind::Image, &[Some(2048), Some(2048)]).await;
+		seed_object(&db, ObjectKind::Video, &[Some(5 * 1024 * 1024 * 1024)]).await;
+		seed_object(&db, ObjectKind::Document, &[None]).await;
+		seed_object(&db, ObjectKind::Document, &[]).await;
+
+		let statistics = kind_statistics(&db).await.unwrap();
+		assert_eq!(statistics.len(), ObjectKind::iter().count());
+
+		let get = |kind: ObjectKind| {
+			let statistic = statistics
+				.iter()
+				.find(|statistic| statistic.kind == kind as i32)
+				.unwrap();
+
+			(statistic.count, statistic.total_bytes.as_str())
+		};
+
+		assert_eq!(get(ObjectKind::Image), (2, "5120"));
+		assert_eq!(get(ObjectKind::Video), (1, "5368709120"));
+		assert_eq!(get(ObjectKind::Document), (2, "0"));
+		assert_eq!(get(ObjectKind::Audio), (0, "0"));
+	}
+
+	#[tokio::test]
+	async fn test_statistics_updater_stops_on_library_delete() {
+		let (_data_dir, node, library) = test_library("test").await;
+
+		let (tx, rx) = chan::bounded(1);
+		STATISTICS_UPDATERS.lock().await.insert(library.id, tx);
+		let updater = spawn(update_statistics_loop(
+			Arc::clone(&node),
+			Arc::clone(&library),
+			rx,
+		));
+		let strong_count_with_updater = Arc::strong_count(&library);
+
+		node.libraries.delete(&library.id).await.unwrap();
+
+		timeout(Duration::from_secs(10), updater)
+			.await
+			.expect("statistics updater didn't stop after the library was deleted")
+			.unwrap();
+
+		// Both the library manager and the updater must have released the library
+		assert!(Arc::strong_count(&library) <= strong_count_with_updater - 2);
+		assert!(!STATISTICS_UPDATERS.lock().await.contains_key(&library.id));
+	}
 }
@@ -1,34 +1,62 @@
 use crate::{
+	cloud::sync::selective,
 	invalidate_query,
-	job::StatefulJob,
+	job::{Job, JobPriority, StatefulJob},
+	library::LibraryId,
 	location::{
-		delete_location, find_location,
-		indexer::{rules::IndexerRuleCreateArgs, IndexerJobInit},
-		light_scan_location, location_with_indexer_rules,
+		delete_location, find_location, index_path,
+		indexer::{
+			self,
+			rules::{IndexerRuleCreateArgs, IndexerRuleUpdateArgs},
+			HiddenPrunerJobInit, IndexerJobInit,
+		},
+		interactive_scan_location, light_scan_location, location_with_indexer_rules,
 		non_indexed::NonIndexedPathItem,
-		relink_location, scan_location, scan_location_sub_path, LocationCreateArgs, LocationError,
-		LocationUpdateArgs,
+		rebase_location, relink_location, scan_location, scan_location_jobs,
+		scan_location_sub_path, set_location_favorite, LocationCreateArgs, LocationCreateManyArgs,
+		LocationCreatePreview, LocationError, LocationUpdateArgs, ShallowScanSummary,
+		RESCAN_CHECK_INTERVAL,
+	},
+	object::{
+		consolidator::ObjectConsolidatorJobInit,
+		file_identifier::file_identifier_job::FileIdentifierJobInit,
+		media::thumbnail::{
+			find_indexed_custom_thumbnail_format, get_indexed_custom_thumb_key,
+			get_indexed_thumb_key, ThumbnailStatus,
+		},
+		orphan_remover::OrphanRemoverJobInit,
 	},
-	object::file_identifier::file_identifier_job::FileIdentifierJobInit,
 	p2p::PeerMetadata,
 	util::AbortOnDrop,
+	volume::{get_volume_of, Volume},
+	Node,
 };
 
 use sd_cache::{CacheNode, Model, Normalise, NormalisedResult, NormalisedResults, Reference};
+use sd_p2p::spacetunnel::RemoteIdentity;
 use sd_prisma::prisma::{
 	file_path, indexer_rule, indexer_rules_in_location, location, object, SortOrder,
 };
+use sd_utils::error::FileIOError;
 
-use std::path::{Path, PathBuf};
+use std::{
+	path::{Path, PathBuf},
+	time::Duration,
+};
 
 use chrono::{DateTime, FixedOffset, Utc};
 use directories::UserDirs;
-use rspc::{self, alpha::AlphaRouter, ErrorCode};
+use rspc::{self, alpha::AlphaRouter};
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use tokio::fs;
 use tracing::{debug, error};
 
-use super::{labels::label_with_objects, utils::library, Ctx, R};
+use super::{
+	labels::label_with_objects,
+	utils::{library, rate_limited_library, ApiError, RateLimit},
+	Ctx, R,
+};
 
 // it includes the shard hex formatted as ([["f02", "cab34a76fbf3469f"]])
 // Will be None if no thumbnail exists
@@ -39,10 +67,12 @@ pub type ThumbnailKey = Vec<String>;
 pub enum ExplorerItem {
 	Path {
 		thumbnail: Option<ThumbnailKey>,
+		thumbnail_status: ThumbnailStatus,
 		item: file_path_with_object::Data,
 	},
 	Object {
 		thumbnail: Option<ThumbnailKey>,
+		thumbnail_status: ThumbnailStatus,
 		item: object_with_file_paths::Data,
 	},
 	Location {
@@ -50,9 +80,11 @@ pub enum ExplorerItem {
 	},
 	NonIndexedPath {
 		thumbnail: Option<ThumbnailKey>,
+		thumbnail_status: ThumbnailStatus,
 		item: NonIndexedPathItem,
 	},
 	SpacedropPeer {
+		identity: RemoteIdentity,
 		item: PeerMetadata,
 	},
 	Label {
@@ -61,6 +93,34 @@ pub enum ExplorerItem {
 	},
 }
 
+/// The thumbnail key and status of an indexed item, the key is only set if the thumbnail is
+/// generated or on its way, so the frontend can pick it up once it's ready.
+pub(crate) async fn indexed_thumbnail(
+	node: &Node,
+	library_id: LibraryId,
+	cas_id: Option<&str>,
+) -> Result<(Option<ThumbnailKey>, ThumbnailStatus), FileIOError> {
+	let Some(cas_id) = cas_id else {
+		return Ok((None, ThumbnailStatus::None));
+	};
+
+	// A thumbnail set by the user takes the place of the generated one
+	if let Some(format) = find_indexed_custom_thumbnail_format(node, cas_id, library_id).await? {
+		return Ok((
+			Some(get_indexed_custom_thumb_key(cas_id, library_id, format)),
+			ThumbnailStatus::Ready,
+		));
+	}
+
+	let status = node.thumbnailer.indexed_status(cas_id, library_id).await?;
+
+	Ok((
+		(status != ThumbnailStatus::None)
+			.then(|| get_indexed_thumb_key(cas_id, library_id, node.thumbnailer.format())),
+		status,
+	))
+}
+
 // TODO: Really this shouldn't be a `Model` but it's easy for now.
 // In the future we should store the inner data of the variant on behalf of it's existing model so it works cross queries.
 impl Model for ExplorerItem {
@@ -70,7 +130,10 @@ impl Model for ExplorerItem {
 }
 
 impl ExplorerItem {
-	pub fn id(&self) -> String {
+	/// The id of the item in the normalised cache, also used to key it in the frontend.
+	///
+	/// It's stable across renames, and unique across every kind of item, as it starts with the kind.
+	pub fn cache_key(&self) -> String {
 		let ty = match self {
 			ExplorerItem::Path { .. } => "FilePath",
 			ExplorerItem::Object { .. } => "Object",
@@ -84,8 +147,8 @@ impl ExplorerItem {
 			ExplorerItem::Object { item, .. } => format!("{ty}:{}", item.id),
 			ExplorerItem::Location { item, .. } => format!("{ty}:{}", item.id),
 			ExplorerItem::NonIndexedPath { item, .. } => format!("{ty}:{}", item.path),
-			ExplorerItem::SpacedropPeer { item, .. } => format!("{ty}:{}", item.name), // TODO: Use a proper primary key
-			ExplorerItem::Label { item, .. } => format!("{ty}:{}", item.name),
+			ExplorerItem::SpacedropPeer { identity, .. } => format!("{ty}:{identity}"),
+			ExplorerItem::Label { item, .. } => format!("{ty}:{}", item.id),
 		}
 	}
 }
@@ -243,7 +306,15 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				pub generate_preview_media: Option<bool>,
 				pub sync_preview_media: Option<bool>,
 				pub hidden: Option<bool>,
+				pub favorite: Option<bool>,
 				pub date_created: Option<DateTime<FixedOffset>>,
+				pub scanned_at: Option<DateTime<FixedOffset>>,
+				pub rescan_interval: Option<i32>,
+				pub integrity_verified_at: Option<DateTime<FixedOffset>>,
+				pub case_insensitive: Option<bool>,
+				pub symlink_policy: Option<i32>,
+				pub index_hidden: Option<bool>,
+				pub sync_enabled: Option<bool>,
 				pub instance_id: Option<i32>,
 				pub indexer_rules: Vec<Reference<indexer_rule::Data>>,
 			}
@@ -271,7 +342,15 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						generate_preview_media: value.generate_preview_media,
 						sync_preview_media: value.sync_preview_media,
 						hidden: value.hidden,
+						favorite: value.favorite,
 						date_created: value.date_created,
+						scanned_at: value.scanned_at,
+						rescan_interval: value.rescan_interval,
+						integrity_verified_at: value.integrity_verified_at,
+						case_insensitive: value.case_insensitive,
+						symlink_policy: value.symlink_policy,
+						index_hidden: value.index_hidden,
+						sync_enabled: value.sync_enabled,
 						instance_id: value.instance_id,
 						indexer_rules: value
 							.indexer_rules
@@ -309,19 +388,139 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						}))
 				})
 		})
+		.procedure("volumeInfo", {
+			#[derive(Serialize, Type)]
+			#[serde(tag = "status")]
+			pub enum LocationVolumeInfo {
+				Available(Volume),
+				/// The location's path can't be reached, like when it's on a removable drive
+				/// which isn't plugged in.
+				Unavailable,
+			}
+
+			R.with2(library())
+				.query(|(_, library), location_id: location::id::Type| async move {
+					let location = find_location(&library, location_id)
+						.select(location::select!({ path }))
+						.exec()
+						.await?
+						.ok_or(LocationError::IdNotFound(location_id))?;
+
+					let Some(path) = location.path else {
+						return Ok(LocationVolumeInfo::Unavailable);
+					};
+
+					if fs::metadata(&path).await.is_err() {
+						return Ok(LocationVolumeInfo::Unavailable);
+					}
+
+					Ok(get_volume_of(&path).await.map_or(
+						LocationVolumeInfo::Unavailable,
+						LocationVolumeInfo::Available,
+					))
+				})
+		})
 		.procedure("create", {
+			#[derive(Serialize, Type)]
+			#[serde(tag = "type")]
+			pub enum LocationCreateResult {
+				Created {
+					id: location::id::Type,
+					/// Only for interactive creations
+					shallow_scan: Option<ShallowScanSummary>,
+				},
+				DryRun(LocationCreatePreview),
+			}
+
 			R.with2(library())
 				.mutation(|(node, library), args: LocationCreateArgs| async move {
-					if let Some(location) = args.create(&node, &library).await? {
-						let id = Some(location.id);
-						scan_location(&node, &library, location).await?;
-						invalidate_query!(library, "locations.list");
-						Ok(id)
-					} else {
-						Ok(None)
+					if args.dry_run {
+						return Ok(LocationCreateResult::DryRun(
+							args.preview(&node, &library)
+								.await
+								.map_err(LocationError::into_create_rspc_error)?,
+						));
 					}
+
+					let interactive = args.interactive;
+
+					let location = args
+						.create(&node, &library)
+						.await
+						.map_err(LocationError::into_create_rspc_error)?
+						.ok_or_else(|| {
+							rspc::Error::new(
+								rspc::ErrorCode::InternalServerError,
+								"Location wasn't created".to_string(),
+							)
+						})?;
+
+					let id = location.id;
+					let shallow_scan = if interactive {
+						interactive_scan_location(&node, &library, location).await?
+					} else {
+						scan_location(&node, &library, location, false).await?;
+						None
+					};
+
+					invalidate_query!(library, "locations.list");
+
+					Ok(LocationCreateResult::Created { id, shallow_scan })
 				})
 		})
+		.procedure("createMany", {
+			#[derive(Serialize, Type)]
+			#[serde(tag = "type")]
+			pub enum LocationCreateManyResult {
+				Created { id: location::id::Type },
+				Failed { error: String },
+			}
+
+			#[derive(Serialize, Type)]
+			pub struct LocationCreateManyItem {
+				path: PathBuf,
+				result: LocationCreateManyResult,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library), args: LocationCreateManyArgs| async move {
+					let mut items = vec![];
+					let mut created_any = false;
+
+					for (path, res) in args.create(&node, &library).await {
+						let result = match res {
+							Ok(Some(location)) => {
+								created_any = true;
+
+								let id = location.id;
+								// The location is there already, so a failed scan can be retried
+								if let Err(e) =
+									scan_location(&node, &library, location, false).await
+								{
+									error!("Failed to scan new location <id='{id}'>: {e:#?}");
+								}
+
+								LocationCreateManyResult::Created { id }
+							}
+							Ok(None) => LocationCreateManyResult::Failed {
+								error: "Location wasn't created".to_string(),
+							},
+							Err(e) => LocationCreateManyResult::Failed {
+								error: e.to_string(),
+							},
+						};
+
+						items.push(LocationCreateManyItem { path, result });
+					}
+
+					if created_any {
+						invalidate_query!(library, "locations.list");
+					}
+
+					Ok(items)
+				},
+			)
+		})
 		.procedure("update", {
 			R.with2(library())
 				.mutation(|(node, library), args: LocationUpdateArgs| async move {
@@ -330,11 +529,87 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					ret
 				})
 		})
+		.procedure("setFavorite", {
+			#[derive(Type, Deserialize)]
+			pub struct SetLocationFavoriteArgs {
+				pub location_id: location::id::Type,
+				pub favorite: bool,
+			}
+
+			R.with2(library()).mutation(
+				|(_, library),
+				 SetLocationFavoriteArgs {
+				     location_id,
+				     favorite,
+				 }: SetLocationFavoriteArgs| async move {
+					set_location_favorite(&library, location_id, favorite).await?;
+
+					invalidate_query!(library, "locations.list");
+					invalidate_query!(library, "locations.get");
+					invalidate_query!(library, "locations.getWithRules");
+
+					Ok(())
+				},
+			)
+		})
+		.procedure("setSyncEnabled", {
+			#[derive(Type, Deserialize)]
+			pub struct SetLocationSyncEnabledArgs {
+				pub location_id: location::id::Type,
+				pub enabled: bool,
+			}
+
+			R.with2(library()).mutation(
+				|(_, library),
+				 SetLocationSyncEnabledArgs {
+				     location_id,
+				     enabled,
+				 }: SetLocationSyncEnabledArgs| async move {
+					let location = find_location(&library, location_id)
+						.select(location::select!({ pub_id sync_enabled }))
+						.exec()
+						.await?
+						.ok_or(LocationError::IdNotFound(location_id))?;
+
+					// Local only, as it's about what this instance sends
+					library
+						.db
+						.location()
+						.update(
+							location::id::equals(location_id),
+							vec![location::sync_enabled::set(Some(enabled))],
+						)
+						.exec()
+						.await?;
+
+					// What was done to it while it was excluded has never been sent
+					if enabled && location.sync_enabled == Some(false) {
+						selective::backfill_location(&library, location_id, location.pub_id)
+							.await?;
+					}
+
+					invalidate_query!(library, "locations.list");
+					invalidate_query!(library, "locations.get");
+					invalidate_query!(library, "locations.getWithRules");
+
+					Ok(())
+				},
+			)
+		})
 		.procedure("delete", {
 			R.with2(library()).mutation(
 				|(node, library), location_id: location::id::Type| async move {
 					delete_location(&node, &library, location_id).await?;
 					invalidate_query!(library, "locations.list");
+
+					// The objects of the location's files are left without any
+					if let Err(e) = Job::new(OrphanRemoverJobInit {})
+						.spawn(&node, &library)
+						.await
+					{
+						error!("Failed to remove orphaned objects after deleting location: {e:#?}");
+					}
+
 					Ok(())
 				},
 			)
@@ -347,12 +622,34 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.map_err(Into::into)
 				})
 		})
+		.procedure("rebase", {
+			#[derive(Type, Deserialize)]
+			pub struct LocationRebaseArgs {
+				pub location_id: location::id::Type,
+				pub old_prefix: PathBuf,
+				pub new_prefix: PathBuf,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library),
+				 LocationRebaseArgs {
+				     location_id,
+				     old_prefix,
+				     new_prefix,
+				 }: LocationRebaseArgs| async move {
+					rebase_location(&node, &library, location_id, old_prefix, new_prefix)
+						.await
+						.map(|_| ())
+						.map_err(Into::into)
+				},
+			)
+		})
 		.procedure("addLibrary", {
 			R.with2(library())
 				.mutation(|(node, library), args: LocationCreateArgs| async move {
 					if let Some(location) = args.add_library(&node, &library).await? {
 						let id = location.id;
-						scan_location(&node, &library, location).await?;
+						scan_location(&node, &library, location, false).await?;
 						invalidate_query!(library, "locations.list");
 						Ok(Some(id))
 					} else {
@@ -361,17 +658,25 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				})
 		})
 		.procedure("fullRescan", {
+			const LIMIT: RateLimit =
+				RateLimit::new("locations.fullRescan", Duration::from_secs(30));
+
 			#[derive(Type, Deserialize)]
 			pub struct FullRescanArgs {
 				pub location_id: location::id::Type,
 				pub reidentify_objects: bool,
+				/// Don't walk into directories which weren't modified since the last scan. It's quicker,
+				/// but files added deeper inside them are missed until the next full scan.
+				#[serde(default)]
+				pub skip_unchanged: bool,
 			}
 
-			R.with2(library()).mutation(
+			R.with2(rate_limited_library(LIMIT)).mutation(
 				|(node, library),
 				 FullRescanArgs {
 				     location_id,
 				     reidentify_objects,
+				     skip_unchanged,
 				 }| async move {
 					if reidentify_objects {
 						let count = library
@@ -392,22 +697,96 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 							.await?;
 
 						debug!("Disconnected {count} file paths from objects");
-
-						// library.orphan_remover.invoke().await;
 					}
 
+					let location = find_location(&library, location_id)
+						.include(location_with_indexer_rules::include())
+						.exec()
+						.await?
+						.ok_or(LocationError::IdNotFound(location_id))?;
+
 					// rescan location
-					scan_location(
-						&node,
-						&library,
-						find_location(&library, location_id)
-							.include(location_with_indexer_rules::include())
-							.exec()
+					let Some(jobs) =
+						scan_location_jobs(&library, location, skip_unchanged, JobPriority::Normal)
 							.await?
-							.ok_or(LocationError::IdNotFound(location_id))?,
-					)
-					.await
-					.map_err(Into::into)
+					else {
+						return Ok(());
+					};
+
+					// Reidentified files can end up sharing objects with other ones, and their old
+					// objects are left without files
+					let jobs = if reidentify_objects {
+						jobs.queue_next(ObjectConsolidatorJobInit {
+							location_id: Some(location_id),
+						})
+					} else {
+						// Files deleted since the last scan can leave their objects behind
+						jobs.queue_next(OrphanRemoverJobInit {})
+					};
+
+					jobs.spawn(&node, &library).await.map_err(Into::into)
+				},
+			)
+		})
+		.procedure("setRescanSchedule", {
+			#[derive(Type, Deserialize)]
+			pub struct SetRescanScheduleArgs {
+				pub location_id: location::id::Type,
+				/// Seconds between automatic rescans, `null` disables them.
+				pub interval_secs: Option<u32>,
+			}
+
+			R.with2(library()).mutation(
+				|(_, library),
+				 SetRescanScheduleArgs {
+				     location_id,
+				     interval_secs,
+				 }: SetRescanScheduleArgs| async move {
+					let rescan_interval = interval_secs
+						.map(|secs| {
+							Some(secs)
+								.filter(|secs| u64::from(*secs) >= RESCAN_CHECK_INTERVAL.as_secs())
+								.and_then(|secs| i32::try_from(secs).ok())
+								.ok_or_else(|| {
+									ApiError::Validation(format!(
+										"Rescan interval must be between {} and {} seconds",
+										RESCAN_CHECK_INTERVAL.as_secs(),
+										i32::MAX
+									))
+								})
+						})
+						.transpose()?;
+
+					// The schedule is local to this node, like `scanned_at`, so it isn't synced
+					let updated = library
+						.db
+						.location()
+						.update_many(
+							vec![location::id::equals(location_id)],
+							vec![location::rescan_interval::set(rescan_interval)],
+						)
+						.exec()
+						.await?;
+
+					if updated == 0 {
+						return Err(LocationError::IdNotFound(location_id).into());
+					}
+
+					invalidate_query!(library, "locations.list");
+					invalidate_query!(library, "locations.get");
+					invalidate_query!(library, "locations.getWithRules");
+
+					Ok(())
+				},
+			)
+		})
+		.procedure("pruneHidden", {
+			R.with2(library()).mutation(
+				|(node, library), location_id: location::id::Type| async move {
+					Job::new(HiddenPrunerJobInit { location_id })
+						.spawn(&node, &library)
+						.await
+						.map_err(Into::into)
 				},
 			)
 		})
@@ -462,10 +841,10 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						})
 						.await
 					{
-						return Err(rspc::Error::new(
-							ErrorCode::Conflict,
+						return Err(ApiError::Conflict(
 							"We're still indexing this location, pleases wait a bit...".to_string(),
-						));
+						)
+						.into());
 					}
 
 					let location = find_location(&library, location_id)
@@ -485,6 +864,31 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				},
 			)
 		})
+		.procedure("indexPath", {
+			#[derive(Clone, Serialize, Deserialize, Type, Debug)]
+			pub struct IndexPathArgs {
+				pub location_id: location::id::Type,
+				/// Absolute, or relative to the location.
+				pub path: PathBuf,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library), IndexPathArgs { location_id, path }: IndexPathArgs| async move {
+					index_path(
+						&node,
+						&library,
+						find_location(&library, location_id)
+							.include(location_with_indexer_rules::include())
+							.exec()
+							.await?
+							.ok_or(LocationError::IdNotFound(location_id))?,
+						path,
+					)
+					.await
+					.map_err(Into::into)
+				},
+			)
+		})
 		.procedure(
 			"online",
 			R.subscription(|node, _: ()| async move {
@@ -503,12 +907,9 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 		)
 		.procedure("systemLocations", {
 			R.query(|_, _: ()| async move {
-				UserDirs::new().map(SystemLocations::from).ok_or_else(|| {
-					rspc::Error::new(
-						ErrorCode::NotFound,
-						"Didn't find any system locations".to_string(),
-					)
-				})
+				Ok(UserDirs::new().map(SystemLocations::from).ok_or_else(|| {
+					ApiError::NotFound("Didn't find any system locations".to_string())
+				})?)
 			})
 		})
 		.merge("indexer_rules.", mount_indexer_rule_routes())
@@ -519,11 +920,28 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 		.procedure("create", {
 			R.with2(library())
 				.mutation(|(_, library), args: IndexerRuleCreateArgs| async move {
-					if args.create(&library).await?.is_some() {
+					let indexer_rule = args.create(&library).await?;
+
+					if indexer_rule.is_some() {
 						invalidate_query!(library, "locations.indexer_rules.list");
 					}
 
-					Ok(())
+					Ok(indexer_rule)
+				})
+		})
+		.procedure("update", {
+			R.with2(library())
+				.mutation(|(_, library), args: IndexerRuleUpdateArgs| async move {
+					let indexer_rule = args.update(&library).await?;
+
+					invalidate_query!(library, "locations.indexer_rules.list");
+					invalidate_query!(library, "locations.indexer_rules.get");
+
+					if !indexer_rule.rescan_location_ids.is_empty() {
+						invalidate_query!(library, "locations.getWithRules");
+					}
+
+					Ok(indexer_rule)
 				})
 		})
 		.procedure("delete", {
@@ -538,16 +956,16 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 						.await?
 					{
 						if indexer_rule.default.unwrap_or_default() {
-							return Err(rspc::Error::new(
-								ErrorCode::Forbidden,
-								format!("Indexer rule <id={indexer_rule_id}> can't be deleted"),
-							));
+							return Err(ApiError::Forbidden(format!(
+								"Indexer rule <id={indexer_rule_id}> can't be deleted"
+							))
+							.into());
 						}
 					} else {
-						return Err(rspc::Error::new(
-							ErrorCode::NotFound,
-							format!("Indexer rule <id={indexer_rule_id}> not found"),
-						));
+						return Err(ApiError::NotFound(format!(
+							"Indexer rule <id={indexer_rule_id}> not found"
+						))
+						.into());
 					}
 
 					library
@@ -572,19 +990,19 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 		.procedure("get", {
 			R.with2(library())
 				.query(|(_, library), indexer_rule_id: i32| async move {
-					library
+					let indexer_rule = library
 						.db
 						.indexer_rule()
 						.find_unique(indexer_rule::id::equals(indexer_rule_id))
 						.exec()
 						.await?
 						.ok_or_else(|| {
-							rspc::Error::new(
-								ErrorCode::NotFound,
-								format!("Indexer rule <id={indexer_rule_id}> not found"),
-							)
-						})
-						.map(|i| NormalisedResult::from(i, |i| i.id.to_string()))
+							ApiError::NotFound(format!(
+								"Indexer rule <id={indexer_rule_id}> not found"
+							))
+						})?;
+
+					Ok(NormalisedResult::from(indexer_rule, |i| i.id.to_string()))
 				})
 		})
 		.procedure("list", {
@@ -596,6 +1014,20 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 				Ok(NormalisedResults { items, nodes })
 			})
 		})
+		.procedure("resetDefaults", {
+			R.with2(library())
+				.mutation(|(_, library), _: ()| async move {
+					indexer::rules::seed::new_or_existing_library(&library)
+						.await
+						.map_err(|e| {
+							ApiError::internal("Failed to reset default indexer rules", e)
+						})?;
+
+					invalidate_query!(library, "locations.indexer_rules.list");
+
+					Ok(())
+				})
+		})
 		// list indexer rules for location, returning the indexer rule
 		.procedure("listForLocation", {
 			R.with2(library())
@@ -615,3 +1047,197 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 				})
 		})
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sd_p2p::spacetunnel::Identity;
+
+	use std::collections::HashSet;
+
+	const ADVERSARIAL_NAME: &str = "Location:5";
+
+	fn items() -> Vec<ExplorerItem> {
+		vec![
+			ExplorerItem::Path {
+				thumbnail: None,
+				thumbnail_status: ThumbnailStatus::None,
+				item: file_path_with_object::Data {
+					id: 5,
+					pub_id: vec![],
+					is_dir: Some(false),
+					cas_id: None,
+					integrity_checksum: None,
+					location_id: Some(5),
+					materialized_path: Some("/".to_string()),
+					name: Some(ADVERSARIAL_NAME.to_string()),
+					extension: Some(String::new()),
+					hidden: None,
+					name_normalized: None,
+					symlink_target: None,
+					size_in_bytes: None,
+					size_in_bytes_bytes: None,
+					inode: None,
+					object_id: None,
+					key_id: None,
+					date_created: None,
+					date_modified: None,
+					date_indexed: None,
+					object: None,
+				},
+			},
+			ExplorerItem::Object {
+				thumbnail: None,
+				thumbnail_status: ThumbnailStatus::None,
+				item: object_with_file_paths::Data {
+					id: 5,
+					pub_id: vec![],
+					kind: None,
+					key_id: None,
+					hidden: None,
+					favorite: None,
+					important: None,
+					note: Some(ADVERSARIAL_NAME.to_string()),
+					date_created: None,
+					date_accessed: None,
+					file_paths: vec![],
+				},
+			},
+			ExplorerItem::Location {
+				item: location::Data {
+					id: 5,
+					pub_id: vec![],
+					name: Some(ADVERSARIAL_NAME.to_string()),
+					path: None,
+					total_capacity: None,
+					available_capacity: None,
+					size_in_bytes: None,
+					is_archived: None,
+					generate_preview_media: None,
+					sync_preview_media: None,
+					hidden: None,
+					favorite: None,
+					date_created: None,
+					scanned_at: None,
+					rescan_interval: None,
+					integrity_verified_at: None,
+					case_insensitive: None,
+					symlink_policy: None,
+					index_hidden: None,
+					sync_enabled: None,
+					instance_id: None,
+					file_paths: None,
+					indexer_rules: None,
+					instance: None,
+				},
+			},
+			ExplorerItem::NonIndexedPath {
+				thumbnail: None,
+				thumbnail_status: ThumbnailStatus::None,
+				item: NonIndexedPathItem {
+					path: ADVERSARIAL_NAME.to_string(),
+					name: ADVERSARIAL_NAME.to_string(),
+					extension: String::new(),
+					kind: 0,
+					is_dir: false,
+					date_created: Utc::now(),
+					date_modified: Utc::now(),
+					size_in_bytes_bytes: vec![0; 8],
+					hidden: false,
+				},
+			},
+			ExplorerItem::SpacedropPeer {
+				identity: Identity::new().to_remote_identity(),
+				item: PeerMetadata {
+					name: ADVERSARIAL_NAME.to_string(),
+					operating_system: None,
+					device_model: None,
+					version: None,
+					encrypted_spacedrop: false,
+				},
+			},
+			ExplorerItem::Label {
+				thumbnails: vec![],
+				item: label_with_objects::Data {
+					id: 5,
+					pub_id: vec![],
+					name: ADVERSARIAL_NAME.to_string(),
+					date_created: Utc::now().into(),
+					date_modified: Utc::now().into(),
+					label_objects: vec![],
+				},
+			},
+		]
+	}
+
+	#[test]
+	fn test_cache_keys_dont_collide() {
+		let items = items();
+		let keys = items
+			.iter()
+			.map(ExplorerItem::cache_key)
+			.collect::<HashSet<_>>();
+
+		assert_eq!(keys.len(), items.len(), "{keys:#?}");
+	}
+
+	#[test]
+	fn test_cache_keys_ignore_names() {
+		for mut item in items() {
+			let key = item.cache_key();
+
+			match &mut item {
+				ExplorerItem::Path { item, .. } => item.name = Some("renamed".to_string()),
+				ExplorerItem::Object { item, .. } => item.note = Some("renamed".to_string()),
+				ExplorerItem::Location { item } => item.name = Some("renamed".to_string()),
+				// The path is the identity of a non indexed path
+				ExplorerItem::NonIndexedPath { item, .. } => item.name = "renamed".to_string(),
+				ExplorerItem::SpacedropPeer { item, .. } => item.name = "renamed".to_string(),
+				ExplorerItem::Label { item, .. } => item.name = "renamed".to_string(),
+			}
+
+			assert_eq!(item.cache_key(), key);
+		}
+	}
+
+	#[test]
+	fn test_peers_with_the_same_name() {
+		let peer = |identity: RemoteIdentity| ExplorerItem::SpacedropPeer {
+			identity,
+			item: PeerMetadata {
+				name: "MacBook Pro".to_string(),
+				operating_system: None,
+				device_model: None,
+				version: None,
+				encrypted_spacedrop: false,
+			},
+		};
+
+		assert_ne!(
+			peer(Identity::new().to_remote_identity()).cache_key(),
+			peer(Identity::new().to_remote_identity()).cache_key()
+		);
+	}
+
+	#[test]
+	fn test_thumbnail_status_is_serialized() {
+		for item in items() {
+			let value = serde_json::to_value(&item).unwrap();
+
+			match item {
+				ExplorerItem::Path { .. }
+				| ExplorerItem::Object { .. }
+				| ExplorerItem::NonIndexedPath { .. } => {
+					assert_eq!(value["thumbnail_status"], "None", "{value:#?}")
+				}
+				_ => assert!(value.get("thumbnail_status").is_none(), "{value:#?}"),
+			}
+		}
+
+		assert_eq!(
+			serde_json::to_value(ThumbnailStatus::Queued).unwrap(),
+			"Queued"
+		);
+	}
+}
@@ -1,15 +1,19 @@
 use crate::{
+	cloud::sync::CloudSyncResetEvent,
 	invalidate_query,
 	job::JobProgressEvent,
+	location::LocationCreateError,
 	node::{
 		config::{NodeConfig, NodePreferences},
-		get_hardware_model_name, HardwareModel,
+		get_hardware_model_name,
+		readiness::Readiness,
+		HardwareModel,
 	},
 	Node,
 };
 
 use sd_cache::patch_typedef;
-use sd_p2p::P2PStatus;
+use sd_p2p::{DiscoveryMode, P2PStatus, RelayConfig};
 use std::sync::{atomic::Ordering, Arc};
 
 use itertools::Itertools;
@@ -18,25 +22,29 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 use uuid::Uuid;
 
+mod actors;
 mod auth;
 mod backups;
 mod cloud;
 // mod categories;
+mod documents;
 mod ephemeral_files;
 mod files;
 mod jobs;
 mod keys;
 mod labels;
-mod libraries;
+pub(crate) mod libraries;
 pub mod locations;
 mod models;
 mod nodes;
 pub mod notifications;
+mod objects;
 mod p2p;
 mod preferences;
 pub(crate) mod search;
-mod sync;
+pub(crate) mod sync;
 mod tags;
+mod thumbnails;
 pub mod utils;
 pub mod volumes;
 mod web_api;
@@ -55,6 +63,9 @@ pub enum CoreEvent {
 	NewThumbnail { thumb_key: Vec<String> },
 	JobProgress(JobProgressEvent),
 	InvalidateOperation(InvalidateOperationEvent),
+	ReadinessChanged(Readiness),
+	ModelDownloadProgress(models::ModelDownloadProgress),
+	CloudSyncReset(CloudSyncResetEvent),
 }
 
 /// All of the feature flags provided by the core itself. The frontend has it's own set of feature flags!
@@ -95,6 +106,9 @@ pub struct SanitisedNodeConfig {
 	pub name: String,
 	pub p2p_enabled: bool,
 	pub p2p_port: Option<u16>,
+	pub p2p_relays: Vec<RelayConfig>,
+	pub p2p_enable_hole_punching: bool,
+	pub p2p_discovery: DiscoveryMode,
 	pub features: Vec<BackendFeature>,
 	pub preferences: NodePreferences,
 	pub image_labeler_version: Option<String>,
@@ -107,6 +121,9 @@ impl From<NodeConfig> for SanitisedNodeConfig {
 			name: value.name,
 			p2p_enabled: value.p2p.enabled,
 			p2p_port: value.p2p.port,
+			p2p_relays: value.p2p.relays,
+			p2p_enable_hole_punching: value.p2p.enable_hole_punching,
+			p2p_discovery: value.p2p.discovery,
 			features: value.features,
 			preferences: value.preferences,
 			image_labeler_version: value.image_labeler_version,
@@ -201,6 +218,7 @@ pub(crate) fn mount() -> Arc<Router> {
 			})
 		})
 		.merge("api.", web_api::mount())
+		.merge("actors.", actors::mount())
 		.merge("auth.", auth::mount())
 		.merge("cloud.", cloud::mount())
 		.merge("search.", search::mount())
@@ -213,6 +231,8 @@ pub(crate) fn mount() -> Arc<Router> {
 		.merge("locations.", locations::mount())
 		.merge("ephemeralFiles.", ephemeral_files::mount())
 		.merge("files.", files::mount())
+		.merge("documents.", documents::mount())
+		.merge("objects.", objects::mount())
 		.merge("jobs.", jobs::mount())
 		.merge("p2p.", p2p::mount())
 		.merge("models.", models::mount())
@@ -221,6 +241,7 @@ pub(crate) fn mount() -> Arc<Router> {
 		.merge("preferences.", preferences::mount())
 		.merge("notifications.", notifications::mount())
 		.merge("backups.", backups::mount())
+		.merge("thumbnails.", thumbnails::mount())
 		.merge("invalidation.", utils::mount_invalidate())
 		.sd_patch_types_dangerously(|type_map| {
 			patch_typedef(type_map);
@@ -233,6 +254,11 @@ pub(crate) fn mount() -> Arc<Router> {
 				<sd_prisma::prisma::object::Data as specta::NamedType>::SID,
 				def,
 			);
+
+			// Only sent as the message of `locations.create` errors, so no procedure references it
+			let def =
+				<LocationCreateError as specta::NamedType>::definition_named_data_type(type_map);
+			type_map.insert(<LocationCreateError as specta::NamedType>::SID, def);
 		})
 		.build(
 			#[allow(clippy::let_and_return)]
@@ -251,6 +277,8 @@ pub(crate) fn mount() -> Arc<Router> {
 		.arced();
 
 	InvalidRequests::validate(r.clone()); // This validates all invalidation calls.
+	#[cfg(debug_assertions)]
+	sync::validate_ingest_invalidations(&r);
 
 	r
 }
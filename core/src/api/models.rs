@@ -1,23 +1,210 @@
-use rspc::alpha::AlphaRouter;
+use crate::node::{BusEvent, EventCategory, EventFilter};
 
-use super::{Ctx, R};
+use futures::StreamExt;
+use rspc::{alpha::AlphaRouter, ErrorCode};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::{CoreEvent, Ctx, R};
+
+/// An image labeler model which can be selected.
+#[derive(Serialize, Type, Debug)]
+pub struct ImageLabelerModel {
+	pub version: String,
+	/// Whether this is the model the image labeler is configured to use.
+	pub active: bool,
+	pub downloaded: bool,
+	/// Size in bytes of the model on disk, as a string because it may not fit in a JS number.
+	pub size: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Type, Debug, Clone)]
+pub struct ModelDownloadProgress {
+	pub version: String,
+	/// `None` when the server didn't tell us the size of the model.
+	pub percent: Option<u8>,
+}
 
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
-	R.router().procedure("image_detection.list", {
-		R.query(
-			|_, _: ()| -> std::result::Result<Vec<&'static str>, rspc::Error> {
+	R.router()
+		.procedure("image_detection.list", {
+			R.query(
+				|_, _: ()| -> std::result::Result<Vec<&'static str>, rspc::Error> {
+					#[cfg(not(feature = "ai"))]
+					return Err(rspc::Error::new(
+						rspc::ErrorCode::MethodNotSupported,
+						"AI feature is not available".to_string(),
+					));
+
+					#[cfg(feature = "ai")]
+					{
+						use sd_ai::image_labeler::{Model, YoloV8};
+						Ok(YoloV8::versions())
+					}
+				},
+			)
+		})
+		.procedure("list", {
+			R.query(|_node, _: ()| async move {
 				#[cfg(not(feature = "ai"))]
-				return Err(rspc::Error::new(
-					rspc::ErrorCode::MethodNotSupported,
+				return Err::<Vec<ImageLabelerModel>, _>(rspc::Error::new(
+					ErrorCode::MethodNotSupported,
 					"AI feature is not available".to_string(),
 				));
 
 				#[cfg(feature = "ai")]
 				{
 					use sd_ai::image_labeler::{Model, YoloV8};
-					Ok(YoloV8::versions())
+
+					let active_version = _node.config.get().await.image_labeler_version;
+
+					let mut versions = YoloV8::versions();
+					versions.sort_unstable();
+
+					let mut models = Vec::with_capacity(versions.len());
+					for version in versions {
+						let model = YoloV8::model(Some(version)).map_err(|e| {
+							rspc::Error::with_cause(
+								ErrorCode::InternalServerError,
+								"Failed to create image labeler model".to_string(),
+								e,
+							)
+						})?;
+						let size = _node
+							.image_labeller
+							.downloaded_model_size(model.as_ref())
+							.await;
+
+						models.push(ImageLabelerModel {
+							version: version.to_string(),
+							active: active_version.as_deref() == Some(version),
+							downloaded: size.is_some(),
+							size: size.map(|size| size.to_string()),
+						});
+					}
+
+					Ok(models)
+				}
+			})
+		})
+		.procedure("set", {
+			R.mutation(|_node, _version: String| async move {
+				#[cfg(not(feature = "ai"))]
+				return Err::<(), _>(rspc::Error::new(
+					ErrorCode::MethodNotSupported,
+					"AI feature is not available".to_string(),
+				));
+
+				#[cfg(feature = "ai")]
+				{
+					let model =
+						sd_ai::image_labeler::YoloV8::model(Some(&_version)).map_err(|e| {
+							rspc::Error::with_cause(
+								ErrorCode::BadRequest,
+								"Unknown image labeler model".to_string(),
+								e,
+							)
+						})?;
+
+					tokio::spawn(change_image_labeler_model(_node, model));
+
+					Ok(())
 				}
-			},
-		)
-	})
+			})
+		})
+		.procedure("downloadProgress", {
+			R.subscription(|node, _: ()| async move {
+				let mut events = node.subscribe(EventFilter::categories([EventCategory::Models]));
+
+				async_stream::stream! {
+					while let Some(event) = events.next().await {
+						if let BusEvent::Event(CoreEvent::ModelDownloadProgress(progress)) = event {
+							yield progress;
+						}
+					}
+				}
+			})
+		})
+}
+
+/// Download the model, swap the image labeler over to it and persist it as the node's model.
+///
+/// If anything fails the image labeler keeps using it's current model.
+#[cfg(feature = "ai")]
+pub(crate) async fn change_image_labeler_model(
+	node: Ctx,
+	model: Box<dyn sd_ai::image_labeler::Model>,
+) {
+	use super::notifications::{NotificationData, NotificationKind};
+
+	use crate::invalidate_query;
+
+	use std::sync::{Mutex, PoisonError};
+
+	use tracing::error;
+
+	let version = model.version().to_string();
+
+	let last_percent = Mutex::new(None);
+	let download_res = node
+		.image_labeller
+		.download_model(model.as_ref(), |downloaded, total| {
+			let percent = total
+				.filter(|total| *total > 0)
+				.map(|total| (downloaded.min(total) * 100 / total) as u8);
+
+			// Only emitting when the percentage changes, as chunks are small
+			let mut last_percent = last_percent.lock().unwrap_or_else(PoisonError::into_inner);
+			if *last_percent != Some(percent) {
+				*last_percent = Some(percent);
+				node.emit(CoreEvent::ModelDownloadProgress(ModelDownloadProgress {
+					version: version.clone(),
+					percent,
+				}));
+			}
+		})
+		.await;
+
+	let res = match download_res {
+		Ok(_) => {
+			invalidate_query!(node; node, "models.list");
+			node.image_labeller
+				.change_model(model)
+				.await
+				.map_err(|e| e.to_string())
+		}
+		Err(e) => Err(e.to_string()),
+	};
+
+	let notification = match res {
+		Ok(()) => {
+			if let Err(e) = node
+				.config
+				.write(|config| config.image_labeler_version = Some(version.clone()))
+				.await
+			{
+				error!("Failed to persist image labeler model: {e:#?}");
+			}
+
+			invalidate_query!(node; node, "nodeState");
+			invalidate_query!(node; node, "models.list");
+
+			NotificationData {
+				title: String::from("Model download completed"),
+				content: format!("Sucessfuly loaded model: {version}"),
+				kind: NotificationKind::Success,
+			}
+		}
+		Err(e) => {
+			error!("Failed to change image labeler model to '{version}': {e}");
+
+			NotificationData {
+				title: String::from("Failed to change image detection model"),
+				content: format!("Error: {e}"),
+				kind: NotificationKind::Error,
+			}
+		}
+	};
+
+	node.emit_notification(notification, None).await;
 }
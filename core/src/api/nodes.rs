@@ -1,10 +1,18 @@
-use crate::{invalidate_query, util::MaybeUndefined};
+use crate::{
+	invalidate_query,
+	node::{config::CryptoDefaults, LogFilterError},
+	object::media::thumbnail::ThumbnailFormat,
+	util::MaybeUndefined,
+};
 
+use sd_crypto::primitives::LATEST_FILE_HEADER;
 use sd_prisma::prisma::{instance, location};
 
+use chrono::{DateTime, Utc};
 use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::Deserialize;
 use specta::Type;
+use tokio_stream::wrappers::WatchStream;
 use tracing::error;
 use uuid::Uuid;
 
@@ -16,6 +24,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			#[derive(Deserialize, Type)]
 			pub struct ChangeNodeNameArgs {
 				pub name: Option<String>,
+				#[serde(default)]
+				#[specta(optional)]
 				pub p2p_port: MaybeUndefined<u16>,
 				pub p2p_enabled: Option<bool>,
 				pub image_labeler_version: Option<String>,
@@ -50,6 +60,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 
 						#[cfg(feature = "ai")]
 						if let Some(version) = args.image_labeler_version {
+							// The config is only updated once the new model is loaded
 							if config
 								.image_labeler_version
 								.as_ref()
@@ -64,9 +75,6 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 									);
 									})
 									.ok();
-								if new_model.is_some() {
-									config.image_labeler_version = Some(version);
-								}
 							}
 						}
 					})
@@ -90,31 +98,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				invalidate_query!(node; node, "nodeState");
 
 				#[cfg(feature = "ai")]
-				{
-					use super::notifications::{NotificationData, NotificationKind};
-
-					if let Some(model) = new_model {
-						let version = model.version().to_string();
-						tokio::spawn(async move {
-							let notification = if let Err(e) =
-								node.image_labeller.change_model(model).await
-							{
-								NotificationData {
-									title: String::from("Failed to change image detection model"),
-									content: format!("Error: {e}"),
-									kind: NotificationKind::Error,
-								}
-							} else {
-								NotificationData {
-									title: String::from("Model download completed"),
-									content: format!("Sucessfuly loaded model: {version}"),
-									kind: NotificationKind::Success,
-								}
-							};
-
-							node.emit_notification(notification, None).await;
-						});
-					}
+				if let Some(model) = new_model {
+					tokio::spawn(super::models::change_image_labeler_model(node, model));
 				}
 
 				Ok(())
@@ -157,11 +142,16 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			#[derive(Deserialize, Type)]
 			pub struct UpdateThumbnailerPreferences {
 				pub background_processing_percentage: u8, // 0-100
+				/// Left as it is when not set, the thumbnails already generated keep their format.
+				#[serde(default)]
+				#[specta(optional)]
+				pub thumbnail_format: Option<ThumbnailFormat>,
 			}
 			R.mutation(
 				|node,
 				 UpdateThumbnailerPreferences {
 				     background_processing_percentage,
+				     thumbnail_format,
 				 }: UpdateThumbnailerPreferences| async move {
 					node.config
 						.update_preferences(|preferences| {
@@ -170,6 +160,12 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 								.set_background_processing_percentage(
 									background_processing_percentage,
 								);
+
+							if let Some(thumbnail_format) = thumbnail_format {
+								preferences
+									.thumbnailer
+									.set_thumbnail_format(thumbnail_format);
+							}
 						})
 						.await
 						.map_err(|e| {
@@ -183,4 +179,155 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				},
 			)
 		})
+		.procedure("updateIntegrityPreferences", {
+			#[derive(Deserialize, Type)]
+			pub struct UpdateIntegrityPreferences {
+				pub max_throughput_mb_per_sec: u32, // 0 means unlimited
+			}
+			R.mutation(
+				|node,
+				 UpdateIntegrityPreferences {
+				     max_throughput_mb_per_sec,
+				 }: UpdateIntegrityPreferences| async move {
+					node.config
+						.update_preferences(|preferences| {
+							preferences
+								.integrity
+								.set_max_throughput_mb_per_sec(max_throughput_mb_per_sec);
+						})
+						.await
+						.map_err(|e| {
+							error!("failed to update integrity preferences: {e:#?}");
+							rspc::Error::with_cause(
+								ErrorCode::InternalServerError,
+								"Failed to update integrity preferences".to_string(),
+								e,
+							)
+						})
+				},
+			)
+		})
+		.procedure("updateStatisticsPreferences", {
+			#[derive(Deserialize, Type)]
+			pub struct UpdateStatisticsPreferences {
+				pub refresh_interval_mins: u32, // 0 disables the background refresh
+			}
+			R.mutation(
+				|node,
+				 UpdateStatisticsPreferences {
+				     refresh_interval_mins,
+				 }: UpdateStatisticsPreferences| async move {
+					node.config
+						.update_preferences(|preferences| {
+							preferences
+								.statistics
+								.set_refresh_interval_mins(refresh_interval_mins);
+						})
+						.await
+						.map_err(|e| {
+							error!("failed to update statistics preferences: {e:#?}");
+							rspc::Error::with_cause(
+								ErrorCode::InternalServerError,
+								"Failed to update statistics preferences".to_string(),
+								e,
+							)
+						})
+				},
+			)
+		})
+		.procedure("updateNetworkPreferences", {
+			#[derive(Deserialize, Type)]
+			pub struct UpdateNetworkPreferences {
+				pub metered_connection: bool,
+			}
+			R.mutation(
+				|node,
+				 UpdateNetworkPreferences { metered_connection }: UpdateNetworkPreferences| async move {
+					node.config
+						.update_preferences(|preferences| {
+							preferences
+								.network
+								.set_metered_connection(metered_connection);
+						})
+						.await
+						.map_err(|e| {
+							error!("failed to update network preferences: {e:#?}");
+							rspc::Error::with_cause(
+								ErrorCode::InternalServerError,
+								"Failed to update network preferences".to_string(),
+								e,
+							)
+						})
+				},
+			)
+		})
+		.procedure("networkUsage", {
+			#[derive(Deserialize, Type)]
+			pub struct NetworkUsageArgs {
+				/// The first UTC day included.
+				pub from: DateTime<Utc>,
+				/// The last UTC day included.
+				pub to: DateTime<Utc>,
+			}
+			R.query(
+				|node, NetworkUsageArgs { from, to }: NetworkUsageArgs| async move {
+					Ok(node.network_usage.usage(from, to))
+				},
+			)
+		})
+		.procedure("eventBusMetrics", {
+			R.query(|node, _: ()| async move { Ok(node.event_bus.metrics()) })
+		})
+		.procedure("readiness", {
+			// Starts with the current state, so late subscribers don't miss what happened during startup
+			R.subscription(
+				|node, _: ()| async move { WatchStream::new(node.env.readiness.subscribe()) },
+			)
+		})
+		.procedure("setLogLevel", {
+			#[derive(Type, Deserialize)]
+			pub struct SetLogLevelArgs {
+				/// Same syntax as `RUST_LOG`, like `info,sd_core=trace`.
+				pub directive: String,
+			}
+
+			R.mutation(
+				|node, SetLogLevelArgs { directive }: SetLogLevelArgs| async move {
+					node.log_filter
+						.as_ref()
+						.ok_or(LogFilterError::NotInitialized)?
+						.set(directive)
+						.map_err(Into::into)
+				},
+			)
+		})
+		.procedure("cryptoDefaults", {
+			R.query(|node, _: ()| async move { Ok(node.config.get().await.crypto_defaults) })
+		})
+		.procedure("updateCryptoDefaults", {
+			R.mutation(|node, args: CryptoDefaults| async move {
+				if !LATEST_FILE_HEADER.supports(args.algorithm) {
+					return Err(rspc::Error::new(
+						ErrorCode::BadRequest,
+						format!("{} isn't supported for new encrypted files", args.algorithm),
+					));
+				}
+
+				node.config
+					.write(|config| config.crypto_defaults = args)
+					.await
+					.map_err(|e| {
+						error!("failed to update crypto defaults: {e:#?}");
+						rspc::Error::with_cause(
+							ErrorCode::InternalServerError,
+							"Failed to update crypto defaults".to_string(),
+							e,
+						)
+					})?;
+
+				invalidate_query!(node; node, "nodes.cryptoDefaults");
+
+				Ok(())
+			})
+		})
 }
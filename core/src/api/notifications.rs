@@ -1,9 +1,18 @@
-use sd_prisma::prisma::notification;
+use sd_prisma::prisma::{notification, SortOrder};
+
+use crate::{
+	api::{Ctx, R},
+	invalidate_query,
+	library::Library,
+	Node,
+};
+
+use std::{cmp::Reverse, collections::HashMap};
 
-use crate::api::{Ctx, R};
 use async_stream::stream;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 use futures::future::join_all;
+use prisma_client_rust::{and, or};
 use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -17,6 +26,9 @@ pub struct Notification {
 	pub data: NotificationData,
 	pub read: bool,
 	pub expires: Option<DateTime<Utc>>,
+	/// Notifications stored before this was added are treated as the oldest ones.
+	#[serde(default)]
+	pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type, PartialEq, Eq)]
@@ -43,54 +55,313 @@ pub struct NotificationData {
 	pub kind: NotificationKind,
 }
 
+const MAX_LIMIT: u8 = 100;
+
+#[derive(Serialize, Type, Debug)]
+pub struct NotificationsPage {
+	pub items: Vec<Notification>,
+	/// Pass it back to get the next page, `None` once there are no more notifications.
+	pub cursor: Option<NotificationsCursor>,
+}
+
+/// The last notification of a page, which is still valid after that notification is dismissed.
+#[derive(Serialize, Deserialize, Type, Debug, Clone)]
+pub struct NotificationsCursor {
+	pub created_at: DateTime<Utc>,
+	pub id: NotificationId,
+}
+
+impl NotificationsCursor {
+	/// Most recent first. Ids only break ties, so pages are stable.
+	fn sort_key(&self) -> impl Ord {
+		let id = match self.id {
+			NotificationId::Node(id) => (None, id),
+			NotificationId::Library(library_id, id) => (Some(library_id), id),
+		};
+
+		(Reverse(self.created_at), Reverse(id))
+	}
+}
+
+impl NotificationsCursor {
+	/// Matches the notifications of a library which come after this cursor.
+	fn library_filter(&self, library_id: Uuid) -> notification::WhereParam {
+		let created_at = DateTime::<FixedOffset>::from(self.created_at);
+
+		// Notifications without a date are treated as the oldest ones
+		let (older, same_date) = if self.created_at == DateTime::<Utc>::default() {
+			(
+				notification::created_at::lt(created_at),
+				or![
+					notification::created_at::equals(None),
+					notification::created_at::equals(Some(created_at))
+				],
+			)
+		} else {
+			(
+				or![
+					notification::created_at::lt(created_at),
+					notification::created_at::equals(None)
+				],
+				notification::created_at::equals(Some(created_at)),
+			)
+		};
+
+		// On the same date, node notifications and the ones of libraries with a lower id come later
+		match self.id {
+			NotificationId::Library(cursor_library_id, id) if cursor_library_id == library_id => {
+				or![older, and![same_date, notification::id::lt(id as i32)]]
+			}
+			NotificationId::Library(cursor_library_id, _) if library_id < cursor_library_id => {
+				or![older, same_date]
+			}
+			_ => older,
+		}
+	}
+}
+
+impl From<&Notification> for NotificationsCursor {
+	fn from(notification: &Notification) -> Self {
+		Self {
+			created_at: notification.created_at,
+			id: notification.id.clone(),
+		}
+	}
+}
+
+/// Most recent first, at most `take` of them.
+async fn library_notifications(
+	library: &Library,
+	filter: Vec<notification::WhereParam>,
+	take: Option<i64>,
+) -> Result<Vec<Notification>, rspc::Error> {
+	let mut query = library
+		.db
+		.notification()
+		.find_many(filter)
+		.order_by(notification::created_at::order(SortOrder::Desc))
+		.order_by(notification::id::order(SortOrder::Desc));
+	if let Some(take) = take {
+		query = query.take(take);
+	}
+
+	query
+		.exec()
+		.await
+		.map_err(|err| {
+			rspc::Error::new(
+				ErrorCode::InternalServerError,
+				format!(
+					"Failed to get notifications for library '{}': {}",
+					library.id, err
+				),
+			)
+		})?
+		.into_iter()
+		.map(|n| {
+			Ok(Notification {
+				id: NotificationId::Library(library.id, n.id as u32),
+				data: rmp_serde::from_slice(&n.data).map_err(|err| {
+					rspc::Error::new(
+						ErrorCode::InternalServerError,
+						format!(
+							"Failed to get notifications for library '{}': {}",
+							library.id, err
+						),
+					)
+				})?,
+				read: n.read,
+				expires: n.expires_at.map(Into::into),
+				created_at: n.created_at.map(Into::into).unwrap_or_default(),
+			})
+		})
+		.collect()
+}
+
+/// The notifications of the node and of every library.
+async fn all_notifications(node: &Node) -> Result<Vec<Notification>, rspc::Error> {
+	let mut notifications = node.config.get().await.notifications;
+	for lib_notifications in join_all(
+		node.libraries
+			.get_all()
+			.await
+			.into_iter()
+			.map(|library| async move { library_notifications(&library, vec![], None).await }),
+	)
+	.await
+	{
+		notifications.extend(lib_notifications?);
+	}
+
+	Ok(notifications)
+}
+
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("get", {
-			R.query(|node, _: ()| async move {
-				let mut notifications = node.config.get().await.notifications;
-				for lib_notifications in join_all(node.libraries.get_all().await.into_iter().map(
-					|library| async move {
-						library
+			R.query(|node, _: ()| async move { all_notifications(&node).await })
+		})
+		.procedure("list", {
+			#[derive(Deserialize, Type, Debug)]
+			pub struct NotificationsListArgs {
+				#[serde(default)]
+				pub unread_only: bool,
+				pub limit: u8,
+				/// The `cursor` of the previous page.
+				#[specta(optional)]
+				pub cursor: Option<NotificationsCursor>,
+			}
+
+			R.query(
+				|node,
+				 NotificationsListArgs {
+				     unread_only,
+				     limit,
+				     cursor,
+				 }| async move {
+					let limit = limit.min(MAX_LIMIT) as usize;
+
+					let mut items = node.config.get().await.notifications;
+
+					// Each library only loads one page past the cursor, which are merged with the node ones
+					for lib_notifications in
+						join_all(node.libraries.get_all().await.into_iter().map(|library| {
+							let mut filter = vec![];
+							if unread_only {
+								filter.push(notification::read::equals(false));
+							}
+							if let Some(cursor) = &cursor {
+								filter.push(cursor.library_filter(library.id));
+							}
+
+							async move {
+								library_notifications(&library, filter, Some(limit as i64 + 1))
+									.await
+							}
+						}))
+						.await
+					{
+						items.extend(lib_notifications?);
+					}
+
+					let cursor_key = cursor.as_ref().map(NotificationsCursor::sort_key);
+					items.retain(|n| {
+						!(unread_only && n.read)
+							&& cursor_key.as_ref().map_or(true, |cursor_key| {
+								NotificationsCursor::from(n).sort_key() > *cursor_key
+							})
+					});
+					items.sort_by_cached_key(|n| NotificationsCursor::from(n).sort_key());
+					items.truncate(limit + 1);
+
+					let cursor = (items.len() > limit)
+						.then(|| {
+							items.truncate(limit);
+							items.last().map(NotificationsCursor::from)
+						})
+						.flatten();
+
+					Ok(NotificationsPage { items, cursor })
+				},
+			)
+		})
+		.procedure("markRead", {
+			#[derive(Deserialize, Type, Debug)]
+			pub struct NotificationsMarkReadArgs {
+				pub ids: Vec<NotificationId>,
+			}
+
+			R.mutation(
+				|node, NotificationsMarkReadArgs { ids }: NotificationsMarkReadArgs| async move {
+					let mut node_ids = vec![];
+					let mut library_ids = HashMap::<_, Vec<_>>::new();
+					for id in ids {
+						match id {
+							NotificationId::Node(id) => node_ids.push(id),
+							NotificationId::Library(library_id, id) => {
+								library_ids.entry(library_id).or_default().push(id as i32)
+							}
+						}
+					}
+
+					if !node_ids.is_empty() {
+						node.config
+							.write(|cfg| {
+								for n in &mut cfg.notifications {
+									if matches!(n.id, NotificationId::Node(id) if node_ids.contains(&id))
+									{
+										n.read = true;
+									}
+								}
+							})
+							.await
+							.map_err(|err| {
+								rspc::Error::new(ErrorCode::InternalServerError, err.to_string())
+							})?;
+					}
+
+					for (library_id, ids) in library_ids {
+						node.libraries
+							.get_library(&library_id)
+							.await
+							.ok_or_else(|| {
+								rspc::Error::new(ErrorCode::NotFound, "Library not found".into())
+							})?
 							.db
 							.notification()
-							.find_many(vec![])
+							.update_many(
+								vec![notification::id::in_vec(ids)],
+								vec![notification::read::set(true)],
+							)
 							.exec()
-							.await
-							.map_err(|err| {
-								rspc::Error::new(
-									ErrorCode::InternalServerError,
-									format!(
-										"Failed to get notifications for library '{}': {}",
-										library.id, err
-									),
+							.await?;
+					}
+
+					invalidate_query!(node; node, "notifications.get");
+					invalidate_query!(node; node, "notifications.list");
+
+					Ok(())
+				},
+			)
+		})
+		.procedure("markAllRead", {
+			R.mutation(|node, _: ()| async move {
+				node.config
+					.write(|cfg| {
+						for n in &mut cfg.notifications {
+							n.read = true;
+						}
+					})
+					.await
+					.map_err(|err| {
+						rspc::Error::new(ErrorCode::InternalServerError, err.to_string())
+					})?;
+
+				join_all(
+					node.libraries
+						.get_all()
+						.await
+						.into_iter()
+						.map(|library| async move {
+							library
+								.db
+								.notification()
+								.update_many(
+									vec![notification::read::equals(false)],
+									vec![notification::read::set(true)],
 								)
-							})?
-							.into_iter()
-							.map(|n| {
-								Ok(Notification {
-									id: NotificationId::Library(library.id, n.id as u32),
-									data: rmp_serde::from_slice(&n.data).map_err(|err| {
-										rspc::Error::new(
-											ErrorCode::InternalServerError,
-											format!(
-												"Failed to get notifications for library '{}': {}",
-												library.id, err
-											),
-										)
-									})?,
-									read: false,
-									expires: n.expires_at.map(Into::into),
-								})
-							})
-							.collect::<Result<Vec<Notification>, rspc::Error>>()
-					},
-				))
+								.exec()
+								.await
+						}),
+				)
 				.await
-				{
-					notifications.extend(lib_notifications?);
-				}
+				.into_iter()
+				.collect::<Result<Vec<_>, _>>()?;
 
-				Ok(notifications)
+				invalidate_query!(node; node, "notifications.get");
+				invalidate_query!(node; node, "notifications.list");
+
+				Ok(())
 			})
 		})
 		.procedure("dismiss", {
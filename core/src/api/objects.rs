@@ -0,0 +1,315 @@
+use crate::{
+	invalidate_query, library::Library, location::LocationError, util::MaybeUndefined, Node,
+};
+
+use sd_prisma::{
+	prisma::{object, SortOrder},
+	prisma_sync,
+};
+use sd_sync::OperationFactory;
+
+use rspc::{alpha::AlphaRouter, ErrorCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use specta::Type;
+
+use super::{
+	locations::{indexed_thumbnail, object_with_file_paths, ExplorerItem},
+	utils::library,
+	Ctx, R,
+};
+
+/// The longest note an object can have, in characters.
+pub const MAX_NOTE_LENGTH: usize = 10_000;
+
+#[derive(Type, Deserialize)]
+pub struct ObjectUpdateArgs {
+	pub id: object::id::Type,
+	#[serde(default)]
+	#[specta(optional)]
+	pub note: MaybeUndefined<String>,
+	pub favorite: Option<bool>,
+	pub hidden: Option<bool>,
+}
+
+impl ObjectUpdateArgs {
+	fn validate(&self) -> Result<(), rspc::Error> {
+		if let MaybeUndefined::Value(note) = &self.note {
+			if note.chars().count() > MAX_NOTE_LENGTH {
+				return Err(rspc::Error::new(
+					ErrorCode::BadRequest,
+					format!("Notes can't be longer than {MAX_NOTE_LENGTH} characters"),
+				));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// The fields to update as `(sync field, sync value, db param)`.
+	fn into_params(self) -> Vec<(&'static str, Value, object::SetParam)> {
+		[
+			Option::<Option<String>>::from(self.note)
+				.map(|note| (object::note::NAME, json!(note), object::note::set(note))),
+			self.favorite.map(|favorite| {
+				(
+					object::favorite::NAME,
+					json!(favorite),
+					object::favorite::set(Some(favorite)),
+				)
+			}),
+			self.hidden.map(|hidden| {
+				(
+					object::hidden::NAME,
+					json!(hidden),
+					object::hidden::set(Some(hidden)),
+				)
+			}),
+		]
+		.into_iter()
+		.flatten()
+		.collect()
+	}
+
+	/// Updates the object, syncing the changes so concurrent edits from other instances are
+	/// settled by the last one written.
+	pub(super) async fn apply(self, library: &Library) -> Result<(), rspc::Error> {
+		let Library { db, sync, .. } = library;
+
+		self.validate()?;
+
+		let id = self.id;
+		let favorite_changed = self.favorite.is_some();
+		let params = self.into_params();
+		if params.is_empty() {
+			return Ok(());
+		}
+
+		let object = db
+			.object()
+			.find_unique(object::id::equals(id))
+			.select(object::select!({ pub_id }))
+			.exec()
+			.await?
+			.ok_or_else(|| rspc::Error::new(ErrorCode::NotFound, "Object not found".into()))?;
+
+		let (sync_params, db_params): (Vec<_>, Vec<_>) = params
+			.into_iter()
+			.map(|(field, value, param)| ((field, value), param))
+			.unzip();
+
+		sync.write_ops(
+			db,
+			(
+				sync_params
+					.into_iter()
+					.map(|(field, value)| {
+						sync.shared_update(
+							prisma_sync::object::SyncId {
+								pub_id: object.pub_id.clone(),
+							},
+							field,
+							value,
+						)
+					})
+					.collect(),
+				db.object().update(object::id::equals(id), db_params),
+			),
+		)
+		.await?;
+
+		invalidate_query!(library, "search.objects");
+		invalidate_query!(library, "search.paths");
+		invalidate_query!(library, "files.get");
+		if favorite_changed {
+			invalidate_query!(library, "objects.favorites");
+			invalidate_query!(library, "files.favorites");
+		}
+
+		Ok(())
+	}
+}
+
+/// The favorite objects, the most recently accessed first.
+pub(super) async fn favorite_items(
+	node: &Node,
+	library: &Library,
+) -> Result<Vec<ExplorerItem>, rspc::Error> {
+	let objects = library
+		.db
+		.object()
+		.find_many(vec![object::favorite::equals(Some(true))])
+		.order_by(object::date_accessed::order(SortOrder::Desc))
+		.include(object_with_file_paths::include())
+		.exec()
+		.await?;
+
+	let mut items = Vec::with_capacity(objects.len());
+
+	for object in objects {
+		let cas_id = object
+			.file_paths
+			.iter()
+			.find_map(|file_path| file_path.cas_id.as_deref());
+
+		let (thumbnail, thumbnail_status) = indexed_thumbnail(node, library.id, cas_id)
+			.await
+			.map_err(LocationError::from)?;
+
+		items.push(ExplorerItem::Object {
+			thumbnail,
+			thumbnail_status,
+			item: object,
+		});
+	}
+
+	Ok(items)
+}
+
+pub(crate) fn mount() -> AlphaRouter<Ctx> {
+	R.router()
+		.procedure("update", {
+			R.with2(library())
+				.mutation(|(_, library), args: ObjectUpdateArgs| async move {
+					args.apply(&library).await
+				})
+		})
+		.procedure("setFavorite", {
+			#[derive(Type, Deserialize)]
+			pub struct SetObjectFavoriteArgs {
+				pub object_id: object::id::Type,
+				pub favorite: bool,
+			}
+
+			R.with2(library()).mutation(
+				|(_, library),
+				 SetObjectFavoriteArgs {
+				     object_id,
+				     favorite,
+				 }: SetObjectFavoriteArgs| async move {
+					ObjectUpdateArgs {
+						id: object_id,
+						note: MaybeUndefined::Undefined,
+						favorite: Some(favorite),
+						hidden: None,
+					}
+					.apply(&library)
+					.await
+				},
+			)
+		})
+		.procedure("setNote", {
+			#[derive(Type, Deserialize)]
+			pub struct SetObjectNoteArgs {
+				pub object_id: object::id::Type,
+				pub note: Option<String>,
+			}
+
+			R.with2(library()).mutation(
+				|(_, library), SetObjectNoteArgs { object_id, note }: SetObjectNoteArgs| async move {
+					ObjectUpdateArgs {
+						id: object_id,
+						note: note.map_or(MaybeUndefined::Null, MaybeUndefined::Value),
+						favorite: None,
+						hidden: None,
+					}
+					.apply(&library)
+					.await
+				},
+			)
+		})
+		.procedure("favorites", {
+			R.with2(library())
+				.query(
+					|(node, library), _: ()| async move { favorite_items(&node, &library).await },
+				)
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::cloud::sync::CompressedCRDTOperations;
+
+	use sd_sync::{CRDTOperation, CRDTOperationData, NTP64};
+
+	use uuid::Uuid;
+
+	#[test]
+	fn test_note_edit_round_trips_through_compression() {
+		let args = ObjectUpdateArgs {
+			id: 1,
+			note: MaybeUndefined::Value("Hello World".into()),
+			favorite: None,
+			hidden: None,
+		};
+
+		let instance = Uuid::new_v4();
+		let record_id = json!(prisma_sync::object::SyncId {
+			pub_id: Uuid::new_v4().as_bytes().to_vec(),
+		});
+
+		let ops = args
+			.into_params()
+			.into_iter()
+			.enumerate()
+			.map(|(i, (field, value, _))| CRDTOperation {
+				instance,
+				timestamp: NTP64(i as u64),
+				id: Uuid::new_v4(),
+				model: object::NAME.to_string(),
+				record_id: record_id.clone(),
+				data: CRDTOperationData::Update {
+					field: field.to_string(),
+					value,
+				},
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(ops.len(), 1);
+
+		let compressed = serde_json::to_vec(&CompressedCRDTOperations::new(ops.clone())).unwrap();
+		let decompressed = serde_json::from_slice::<CompressedCRDTOperations>(&compressed)
+			.unwrap()
+			.into_ops();
+
+		assert_eq!(ops, decompressed);
+		assert_eq!(
+			decompressed[0].data,
+			CRDTOperationData::Update {
+				field: object::note::NAME.to_string(),
+				value: json!("Hello World"),
+			}
+		);
+	}
+
+	#[test]
+	fn test_note_length_is_capped() {
+		let args = |note: String| ObjectUpdateArgs {
+			id: 1,
+			note: MaybeUndefined::Value(note),
+			favorite: None,
+			hidden: None,
+		};
+
+		assert!(args("é".repeat(MAX_NOTE_LENGTH)).validate().is_ok());
+		assert!(args("é".repeat(MAX_NOTE_LENGTH + 1)).validate().is_err());
+	}
+
+	#[test]
+	fn test_undefined_note_is_not_updated() {
+		let args = serde_json::from_value::<ObjectUpdateArgs>(json!({
+			"id": 1,
+			"favorite": true,
+			"hidden": null
+		}))
+		.unwrap();
+
+		let fields = args
+			.into_params()
+			.into_iter()
+			.map(|(field, value, _)| (field, value))
+			.collect::<Vec<_>>();
+		assert_eq!(fields, vec![(object::favorite::NAME, json!(true))]);
+	}
+}
@@ -1,14 +1,19 @@
-use crate::p2p::{operations, P2PEvent};
+use crate::{
+	invalidate_query,
+	p2p::{operations, P2PEvent},
+};
 
-use sd_p2p::spacetunnel::RemoteIdentity;
+use sd_p2p::{spacetunnel::RemoteIdentity, DiscoveryMode, RelayConfig};
+use sd_prisma::prisma::location;
 
 use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::Deserialize;
 use specta::Type;
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::atomic::Ordering};
+use tracing::error;
 use uuid::Uuid;
 
-use super::{Ctx, R};
+use super::{utils::library, Ctx, R};
 
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
@@ -54,6 +59,85 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				Ok(serde_json::to_value(node.p2p.state()).unwrap())
 			})
 		})
+		.procedure("connections", {
+			R.query(|node, _: ()| async move { Ok(node.p2p.manager.connections()) })
+		})
+		.procedure("updateConfig", {
+			#[derive(Type, Deserialize)]
+			pub struct UpdateConfigArgs {
+				relays: Option<Vec<RelayConfig>>,
+				enable_hole_punching: Option<bool>,
+			}
+
+			R.mutation(|node, args: UpdateConfigArgs| async move {
+				node.config
+					.write(|config| {
+						if let Some(relays) = args.relays {
+							config.p2p.relays = relays;
+						}
+
+						if let Some(enable_hole_punching) = args.enable_hole_punching {
+							config.p2p.enable_hole_punching = enable_hole_punching;
+						}
+					})
+					.await
+					.map_err(|err| {
+						error!("Failed to write config: {}", err);
+						rspc::Error::new(
+							ErrorCode::InternalServerError,
+							"error updating config".into(),
+						)
+					})?;
+
+				// The manager applies this without needing a restart, except for hole punching
+				node.p2p
+					.manager
+					.update_config(node.config.get().await.p2p.clone())
+					.await;
+
+				invalidate_query!(node; node, "nodeState");
+
+				Ok(())
+			})
+		})
+		.procedure("discoveryMode", {
+			R.query(|node, _: ()| async move { Ok(node.config.get().await.p2p.discovery) })
+		})
+		.procedure("setDiscoveryMode", {
+			R.mutation(|node, mode: DiscoveryMode| async move {
+				if node.config.get().await.p2p.discovery == mode {
+					return Ok(());
+				}
+
+				node.config
+					.write(|config| config.p2p.discovery = mode)
+					.await
+					.map_err(|err| {
+						error!("Failed to write config: {}", err);
+						rspc::Error::new(
+							ErrorCode::InternalServerError,
+							"error updating config".into(),
+						)
+					})?;
+
+				// Starts or stops mDNS and the relay listeners without restarting the manager
+				node.p2p
+					.manager
+					.update_config(node.config.get().await.p2p.clone())
+					.await;
+
+				node.p2p
+					.events
+					.0
+					.send(P2PEvent::DiscoveryModeChanged { mode })
+					.ok();
+
+				invalidate_query!(node; node, "p2p.discoveryMode");
+				invalidate_query!(node; node, "nodeState");
+
+				Ok(())
+			})
+		})
 		.procedure("spacedrop", {
 			#[derive(Type, Deserialize)]
 			pub struct SpacedropArgs {
@@ -76,15 +160,89 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				})
 			})
 		})
+		.procedure("requestFile", {
+			#[derive(Type, Deserialize)]
+			pub struct RequestFileArgs {
+				identity: RemoteIdentity,
+				/// The `pub_id` of the `file_path` on the remote instance.
+				file_path_id: Uuid,
+				/// Where to save the file locally.
+				path: String,
+			}
+
+			R.with2(library())
+				.mutation(|(node, library), args: RequestFileArgs| async move {
+					if !node.files_over_p2p_flag.load(Ordering::Relaxed) {
+						return Err(rspc::Error::new(
+							ErrorCode::Forbidden,
+							"Files over P2P is disabled".into(),
+						));
+					}
+
+					if node.network_usage.is_metered() {
+						return Err(rspc::Error::new(
+							ErrorCode::Forbidden,
+							"Files over P2P are paused on a metered connection".into(),
+						));
+					}
+
+					operations::pull_file(
+						node.p2p.clone(),
+						library,
+						args.identity,
+						args.file_path_id,
+						PathBuf::from(args.path),
+					)
+					.await
+					.map_err(|_err| {
+						rspc::Error::new(
+							ErrorCode::InternalServerError,
+							"Failed to request file".into(),
+						)
+					})
+				})
+		})
 		.procedure("acceptSpacedrop", {
-			R.mutation(|node, (id, path): (Uuid, Option<String>)| async move {
-				match path {
-					Some(path) => node.p2p.accept_spacedrop(id, path).await,
-					None => node.p2p.reject_spacedrop(id).await,
-				};
+			#[derive(Type, Deserialize)]
+			pub struct SpacedropLocationTarget {
+				library_id: Uuid,
+				location_id: location::id::Type,
+				sub_path: Option<String>,
+			}
 
-				Ok(())
-			})
+			R.mutation(
+				|node,
+				 (id, path, target): (Uuid, Option<String>, Option<SpacedropLocationTarget>)| async move {
+					match (path, target) {
+						(_, Some(target)) => {
+							let library = node
+								.libraries
+								.get_library(&target.library_id)
+								.await
+								.ok_or_else(|| {
+									rspc::Error::new(
+										ErrorCode::NotFound,
+										"Library not found".into(),
+									)
+								})?;
+
+							node.p2p
+								.accept_spacedrop_into_location(
+									&node,
+									id,
+									library,
+									target.location_id,
+									target.sub_path,
+								)
+								.await?
+						}
+						(Some(path), None) => node.p2p.accept_spacedrop(id, path).await,
+						(None, None) => node.p2p.reject_spacedrop(id).await,
+					};
+
+					Ok(())
+				},
+			)
 		})
 		.procedure("cancelSpacedrop", {
 			R.mutation(|node, id: Uuid| async move {
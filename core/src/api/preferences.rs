@@ -1,15 +1,24 @@
-use crate::preferences::LibraryPreferences;
+use crate::{
+	api::utils::InvalidateOperationEvent,
+	invalidate_query,
+	node::{EventCategory, EventFilter},
+	preferences::LibraryPreferences,
+};
 
+use futures::StreamExt;
 use rspc::alpha::AlphaRouter;
+use tracing::error;
 
-use super::{utils::library, Ctx, R};
+use super::{utils::library, CoreEvent, Ctx, R};
 
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("update", {
 			R.with2(library())
 				.mutation(|(_, library), args: LibraryPreferences| async move {
-					args.write(&library.db).await?;
+					args.write(&library).await?;
+
+					invalidate_query!(library, "preferences.get");
 
 					Ok(())
 				})
@@ -19,4 +28,35 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				Ok(LibraryPreferences::read(&library.db).await?)
 			})
 		})
+		.procedure("watch", {
+			R.with2(library()).subscription(|(node, library), _: ()| {
+				let mut events = node.subscribe_resyncing(
+					EventFilter::categories([EventCategory::Invalidation]).library(library.id),
+				);
+
+				async_stream::stream! {
+					// Preferences are invalidated both when they're updated here and when they're
+					// synced from another instance
+					'watch: loop {
+						match LibraryPreferences::read(&library.db).await {
+							Ok(preferences) => yield preferences,
+							Err(e) => error!("Failed to read library preferences: {e:#?}"),
+						}
+
+						loop {
+							match events.next().await {
+								Some(CoreEvent::InvalidateOperation(
+									InvalidateOperationEvent::Single(event),
+								)) if event.key == "preferences.get" => break,
+								Some(CoreEvent::InvalidateOperation(InvalidateOperationEvent::All)) => {
+									break
+								}
+								Some(_) => {}
+								None => break 'watch,
+							}
+						}
+					}
+				}
+			})
+		})
 }
@@ -1,10 +1,10 @@
 use crate::location::LocationError;
 
-use sd_file_path_helper::{check_file_path_exists, IsolatedFilePathData};
+use sd_file_path_helper::{check_file_path_exists, normalize_name, IsolatedFilePathData};
 use sd_prisma::prisma::{self, file_path};
 
 use chrono::{DateTime, FixedOffset, Utc};
-use prisma_client_rust::{OrderByQuery, PaginatedQuery, WhereQuery};
+use prisma_client_rust::{operator::or, OrderByQuery, PaginatedQuery, WhereQuery};
 use rspc::ErrorCode;
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -120,12 +120,33 @@ impl FilePathFilterArgs {
 					})
 					.unwrap_or_default()
 			}
-			Self::Name(v) => v
-				.into_param(name::contains, name::starts_with, name::ends_with, |s| {
+			Self::Name(v) => {
+				// Also matched against the normalized names, so "café" finds the files named with
+				// either of its unicode encodings, whatever the case used in case-insensitive locations
+				let normalized_param = match &v {
+					TextMatch::Contains(s) => {
+						Some((name_normalized::contains as fn(_) -> _, s.clone()))
+					}
+					TextMatch::StartsWith(s) => {
+						Some((name_normalized::starts_with as fn(_) -> _, s.clone()))
+					}
+					TextMatch::EndsWith(_) | TextMatch::Equals(_) => None,
+				};
+
+				v.into_param(name::contains, name::starts_with, name::ends_with, |s| {
 					name::equals(Some(s))
 				})
+				.map(|param| match normalized_param {
+					Some((normalized_fn, s)) => or(vec![
+						param,
+						normalized_fn(normalize_name(&s, false)),
+						normalized_fn(normalize_name(&s, true)),
+					]),
+					None => param,
+				})
 				.map(|v| vec![v])
-				.unwrap_or_default(),
+				.unwrap_or_default()
+			}
 			Self::Extension(v) => v
 				.into_param(extension::in_vec, extension::not_in_vec)
 				.map(|v| vec![v])
@@ -293,3 +314,200 @@ impl OrderAndPagination {
 		}
 	}
 }
+
+/// Stable orderings for [`FilePathPageCursor`] pagination.
+///
+/// Every ordering is tie-broken by `id`, so each row has a unique position and pages don't skip or
+/// repeat rows when other rows are inserted between requests.
+#[derive(Serialize, Deserialize, Type, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase", tag = "field", content = "value")]
+pub enum FilePathPageOrder {
+	Id(SortOrder),
+	Name(SortOrder),
+}
+
+impl Default for FilePathPageOrder {
+	fn default() -> Self {
+		Self::Id(SortOrder::Asc)
+	}
+}
+
+/// The last row of the previous page, which the next page starts after.
+pub struct FilePathPageCursor {
+	pub id: file_path::id::Type,
+	pub name: Option<String>,
+}
+
+impl FilePathPageOrder {
+	pub fn apply(self, query: &mut file_path::FindManyQuery, cursor: Option<FilePathPageCursor>) {
+		let order = match self {
+			Self::Id(order) => order,
+			Self::Name(order) => order,
+		};
+
+		let id_after = |id| match order {
+			SortOrder::Asc => prisma::file_path::id::gt(id),
+			SortOrder::Desc => prisma::file_path::id::lt(id),
+		};
+
+		if let Some(FilePathPageCursor { id, name }) = cursor {
+			query.add_where(match self {
+				Self::Id(_) => id_after(id),
+				// SQLite sorts `NULL`s first, so nameless rows come before named ones when ascending
+				// and after them when descending
+				Self::Name(_) => match (name, order) {
+					(Some(name), SortOrder::Asc) => prisma_client_rust::or![
+						prisma::file_path::name::gt(name.clone()),
+						prisma_client_rust::and![
+							prisma::file_path::name::equals(Some(name)),
+							id_after(id)
+						]
+					],
+					(Some(name), SortOrder::Desc) => prisma_client_rust::or![
+						prisma::file_path::name::lt(name.clone()),
+						prisma_client_rust::and![
+							prisma::file_path::name::equals(Some(name)),
+							id_after(id)
+						],
+						prisma::file_path::name::equals(None)
+					],
+					(None, SortOrder::Asc) => prisma_client_rust::or![
+						prisma::file_path::name::not(None),
+						prisma_client_rust::and![
+							prisma::file_path::name::equals(None),
+							id_after(id)
+						]
+					],
+					(None, SortOrder::Desc) => prisma_client_rust::and![
+						prisma::file_path::name::equals(None),
+						id_after(id)
+					],
+				},
+			});
+		}
+
+		if let Self::Name(order) = self {
+			query.add_order_by(prisma::file_path::name::order(order.into()));
+		}
+
+		query.add_order_by(prisma::file_path::id::order(order.into()));
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use sd_prisma::prisma::PrismaClient;
+	use sd_utils::{db::load_and_migrate, uuid_to_bytes};
+
+	use tempfile::tempdir;
+	use uuid::Uuid;
+
+	async fn create_file_path(db: &PrismaClient, name: Option<&str>) -> file_path::id::Type {
+		db.file_path()
+			.create(
+				uuid_to_bytes(Uuid::new_v4()),
+				vec![file_path::name::set(name.map(str::to_string))],
+			)
+			.exec()
+			.await
+			.unwrap()
+			.id
+	}
+
+	async fn page(
+		db: &PrismaClient,
+		order: FilePathPageOrder,
+		cursor: Option<file_path::id::Type>,
+	) -> Vec<file_path::id::Type> {
+		let cursor = match cursor {
+			Some(id) => Some(FilePathPageCursor {
+				id,
+				name: db
+					.file_path()
+					.find_unique(file_path::id::equals(id))
+					.exec()
+					.await
+					.unwrap()
+					.unwrap()
+					.name,
+			}),
+			None => None,
+		};
+
+		let mut query = db.file_path().find_many(vec![]);
+		order.apply(&mut query, cursor);
+
+		query
+			.take(2)
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|file_path| file_path.id)
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn test_page_order_is_stable_across_inserts() {
+		let dir = tempdir().unwrap();
+		let db = load_and_migrate(&format!("file:{}", dir.path().join("library.db").display()))
+			.await
+			.unwrap();
+
+		let b = create_file_path(&db, Some("b")).await;
+		let a1 = create_file_path(&db, Some("a")).await;
+		let none = create_file_path(&db, None).await;
+		let a2 = create_file_path(&db, Some("a")).await;
+		let c = create_file_path(&db, Some("c")).await;
+
+		for (order, expected) in [
+			(
+				FilePathPageOrder::Id(SortOrder::Asc),
+				vec![b, a1, none, a2, c],
+			),
+			(
+				FilePathPageOrder::Id(SortOrder::Desc),
+				vec![c, a2, none, a1, b],
+			),
+			(
+				FilePathPageOrder::Name(SortOrder::Asc),
+				vec![none, a1, a2, b, c],
+			),
+			(
+				FilePathPageOrder::Name(SortOrder::Desc),
+				vec![c, b, a2, a1, none],
+			),
+		] {
+			let mut seen = Vec::new();
+			let mut cursor = None;
+
+			loop {
+				let ids = page(&db, order, cursor).await;
+				if ids.is_empty() {
+					break;
+				}
+
+				cursor = ids.last().copied();
+				seen.extend(ids);
+			}
+
+			assert_eq!(seen, expected, "{order:?}");
+		}
+
+		// Rows inserted before the cursor don't shift the following pages
+		let first = page(&db, FilePathPageOrder::Name(SortOrder::Asc), None).await;
+		create_file_path(&db, Some("0")).await;
+		assert_eq!(
+			page(
+				&db,
+				FilePathPageOrder::Name(SortOrder::Asc),
+				first.last().copied()
+			)
+			.await,
+			vec![a2, b]
+		);
+	}
+}
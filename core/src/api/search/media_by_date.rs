@@ -0,0 +1,366 @@
+//! Grouping of media objects by the date they were captured, for the photos view.
+//!
+//! The capture date is the `media_data` epoch time when we have it, falling back to the earliest
+//! `file_path.date_created` of the object. Buckets are computed in UTC.
+//!
+//! [`location_buckets`] groups the media of a single location in the node's timezone instead, and
+//! also dates media from their file names.
+
+use crate::{
+	api::locations::ExplorerItem,
+	object::media::{
+		capture_time,
+		thumbnail::{get_indexed_thumb_key, ThumbnailFormat},
+	},
+};
+
+use sd_cache::{CacheNode, Reference};
+use sd_file_ext::kind::ObjectKind;
+use sd_prisma::prisma::{file_path, location, object, PrismaClient};
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use prisma_client_rust::{PrismaValue, QueryError, Raw};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use uuid::Uuid;
+
+/// How many thumbnails are returned with each bucket as a preview.
+const SAMPLE_THUMBNAILS: u8 = 4;
+
+#[derive(Deserialize, Type, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum DateGranularity {
+	Day,
+	Month,
+	Year,
+}
+
+impl DateGranularity {
+	/// The `strftime` format of the bucket key.
+	const fn format(self) -> &'static str {
+		match self {
+			Self::Day => "%Y-%m-%d",
+			Self::Month => "%Y-%m",
+			Self::Year => "%Y",
+		}
+	}
+}
+
+#[derive(Deserialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationMediaDateArgs {
+	pub location_id: location::id::Type,
+	pub granularity: DateGranularity,
+}
+
+#[derive(Deserialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaDateFilter {
+	/// The `ObjectKind`s to include, defaults to images and videos.
+	#[serde(default)]
+	#[specta(optional)]
+	pub kinds: Vec<i32>,
+	pub granularity: DateGranularity,
+	#[specta(optional)]
+	pub from: Option<DateTime<Utc>>,
+	#[specta(optional)]
+	pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct MediaDateBucket {
+	/// The bucket key, formatted as `YYYY-MM-DD`, `YYYY-MM` or `YYYY` depending on the granularity.
+	pub date: String,
+	pub count: u32,
+	pub sample_thumb_keys: Vec<Vec<String>>,
+}
+
+/// Position in a bucket to continue paginating from.
+#[derive(Serialize, Deserialize, Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaDateCursor {
+	pub captured_at: DateTime<Utc>,
+	pub id: object::id::Type,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct MediaDateBucketItems {
+	pub cursor: Option<MediaDateCursor>,
+	pub items: Vec<Reference<ExplorerItem>>,
+	pub nodes: Vec<CacheNode>,
+}
+
+impl MediaDateFilter {
+	/// Builds the `captured` CTE, returning it alongside the values for it's placeholders.
+	fn captured_cte(&self) -> (String, Vec<PrismaValue>) {
+		let kinds = if self.kinds.is_empty() {
+			vec![ObjectKind::Image as i32, ObjectKind::Video as i32]
+		} else {
+			self.kinds.clone()
+		};
+
+		let mut conditions = vec!["ts IS NOT NULL".to_string()];
+		let mut values = vec![];
+		if let Some(from) = self.from {
+			conditions.push("ts >= {}".to_string());
+			values.push(PrismaValue::BigInt(from.timestamp()));
+		}
+		if let Some(to) = self.to {
+			conditions.push("ts < {}".to_string());
+			values.push(PrismaValue::BigInt(to.timestamp()));
+		}
+
+		// Prisma stores `DateTime`s as milliseconds, but rows created by SQLite defaults are text.
+		// The kinds are formatted into the query as PCR doesn't support IN with Vec for SQLite.
+		(
+			format!(
+				"WITH captured AS (
+					SELECT * FROM (
+						SELECT
+							o.id AS id,
+							COALESCE(
+								m.epoch_time,
+								(
+									SELECT MIN(
+										CASE typeof(fp.date_created)
+											WHEN 'integer' THEN fp.date_created / 1000
+											ELSE CAST(strftime('%s', fp.date_created) AS INTEGER)
+										END
+									)
+									FROM file_path fp
+									WHERE fp.object_id = o.id
+								)
+							) AS ts
+						FROM object o
+						LEFT JOIN media_data m ON m.object_id = o.id
+						WHERE o.kind IN ({})
+					)
+					WHERE {}
+				)",
+				kinds
+					.iter()
+					.map(ToString::to_string)
+					.collect::<Vec<_>>()
+					.join(","),
+				conditions.join(" AND ")
+			),
+			values,
+		)
+	}
+
+	pub async fn buckets(
+		&self,
+		db: &PrismaClient,
+		library_id: Uuid,
+		thumbnail_format: ThumbnailFormat,
+	) -> Result<Vec<MediaDateBucket>, QueryError> {
+		#[derive(Deserialize)]
+		struct BucketRow {
+			date: String,
+			count: i64,
+			sample_cas_ids: Option<String>,
+		}
+
+		let (cte, values) = self.captured_cte();
+		let format = self.granularity.format();
+
+		let rows = db
+			._query_raw::<BucketRow>(Raw::new(
+				&format!(
+					"{cte}
+					SELECT
+						bucket AS date,
+						COUNT(*) AS count,
+						GROUP_CONCAT(CASE WHEN position <= {SAMPLE_THUMBNAILS} THEN cas_id END) AS sample_cas_ids
+					FROM (
+						SELECT
+							strftime('{format}', ts, 'unixepoch') AS bucket,
+							(
+								SELECT cas_id FROM file_path
+								WHERE object_id = captured.id AND cas_id IS NOT NULL
+								LIMIT 1
+							) AS cas_id,
+							ROW_NUMBER() OVER (
+								PARTITION BY strftime('{format}', ts, 'unixepoch')
+								ORDER BY ts DESC, id DESC
+							) AS position
+						FROM captured
+					)
+					GROUP BY bucket
+					ORDER BY bucket DESC"
+				),
+				values,
+			))
+			.exec()
+			.await?;
+
+		Ok(rows
+			.into_iter()
+			.map(|row| MediaDateBucket {
+				date: row.date,
+				count: row.count as u32,
+				sample_thumb_keys: row
+					.sample_cas_ids
+					.map(|cas_ids| {
+						cas_ids
+							.split(',')
+							.map(|cas_id| {
+								get_indexed_thumb_key(cas_id, library_id, thumbnail_format)
+							})
+							.collect()
+					})
+					.unwrap_or_default(),
+			})
+			.collect())
+	}
+
+	/// The ids of the objects in the bucket, most recently captured first.
+	pub async fn bucket_object_ids(
+		&self,
+		db: &PrismaClient,
+		date: String,
+		take: u8,
+		cursor: Option<MediaDateCursor>,
+	) -> Result<Vec<(object::id::Type, MediaDateCursor)>, QueryError> {
+		#[derive(Deserialize)]
+		struct ObjectRow {
+			id: object::id::Type,
+			ts: i64,
+		}
+
+		let (cte, mut values) = self.captured_cte();
+		let format = self.granularity.format();
+
+		values.push(PrismaValue::String(date));
+		let cursor_condition = if let Some(cursor) = cursor {
+			let captured_at = cursor.captured_at.timestamp();
+			values.extend([
+				PrismaValue::BigInt(captured_at),
+				PrismaValue::BigInt(captured_at),
+				PrismaValue::Int(cursor.id as i64),
+			]);
+
+			"AND (ts < {} OR (ts = {} AND id < {}))"
+		} else {
+			""
+		};
+		values.push(PrismaValue::Int(take as i64));
+
+		let rows = db
+			._query_raw::<ObjectRow>(Raw::new(
+				&format!(
+					"{cte}
+					SELECT id, ts FROM captured
+					WHERE strftime('{format}', ts, 'unixepoch') = {{}} {cursor_condition}
+					ORDER BY ts DESC, id DESC
+					LIMIT {{}}"
+				),
+				values,
+			))
+			.exec()
+			.await?;
+
+		Ok(rows
+			.into_iter()
+			.filter_map(|row| {
+				Some((
+					row.id,
+					MediaDateCursor {
+						captured_at: DateTime::from_timestamp(row.ts, 0)?,
+						id: row.id,
+					},
+				))
+			})
+			.collect())
+	}
+}
+
+/// Groups the images and videos of a location by the date they were captured, in the node's
+/// timezone.
+///
+/// Media without a date in their media data are dated from their file name when it has one, like
+/// `IMG_20230415_093012.jpg`, before falling back to when their file was created.
+pub async fn location_buckets(
+	db: &PrismaClient,
+	library_id: Uuid,
+	thumbnail_format: ThumbnailFormat,
+	LocationMediaDateArgs {
+		location_id,
+		granularity,
+	}: LocationMediaDateArgs,
+) -> Result<Vec<MediaDateBucket>, QueryError> {
+	let file_paths = db
+		.file_path()
+		.find_many(vec![
+			file_path::location_id::equals(Some(location_id)),
+			file_path::object::is(vec![object::kind::in_vec(vec![
+				ObjectKind::Image as i32,
+				ObjectKind::Video as i32,
+			])]),
+		])
+		.select(file_path::select!({
+			name
+			cas_id
+			date_created
+			object: select { id media_data: select { media_date } }
+		}))
+		.exec()
+		.await?;
+
+	// An object can have many file_paths in the location, but it's only counted once
+	let mut objects = HashMap::<object::id::Type, (Option<NaiveDateTime>, Option<String>)>::new();
+	for file_path in file_paths {
+		let Some(object) = file_path.object else {
+			continue;
+		};
+
+		let (_, cas_id) = objects.entry(object.id).or_insert_with(|| {
+			(
+				capture_time(
+					object
+						.media_data
+						.and_then(|media_data| media_data.media_date),
+					file_path.name.as_deref().unwrap_or_default(),
+					file_path.date_created,
+				),
+				None,
+			)
+		});
+
+		if cas_id.is_none() {
+			*cas_id = file_path.cas_id;
+		}
+	}
+
+	let mut captured = objects
+		.into_iter()
+		.filter_map(|(id, (captured_at, cas_id))| Some((captured_at?, id, cas_id)))
+		.collect::<Vec<_>>();
+	captured.sort_unstable_by(|a, b| b.cmp(a));
+
+	// Sorted from the most recently captured, so the objects of each bucket are next to each other
+	let mut buckets = Vec::<MediaDateBucket>::new();
+	for (captured_at, _, cas_id) in captured {
+		let date = captured_at.format(granularity.format()).to_string();
+		let sample_thumb_key =
+			cas_id.map(|cas_id| get_indexed_thumb_key(&cas_id, library_id, thumbnail_format));
+
+		match buckets.last_mut() {
+			Some(bucket) if bucket.date == date => {
+				bucket.count += 1;
+				if bucket.sample_thumb_keys.len() < SAMPLE_THUMBNAILS as usize {
+					bucket.sample_thumb_keys.extend(sample_thumb_key);
+				}
+			}
+			_ => buckets.push(MediaDateBucket {
+				date,
+				count: 1,
+				sample_thumb_keys: sample_thumb_key.into_iter().collect(),
+			}),
+		}
+	}
+
+	Ok(buckets)
+}
@@ -1,18 +1,19 @@
 use crate::{
 	api::{
-		locations::{file_path_with_object, object_with_file_paths, ExplorerItem},
-		utils::library,
+		locations::{
+			file_path_with_object, indexed_thumbnail, object_with_file_paths, ExplorerItem,
+		},
+		utils::{library, ApiError},
 	},
 	library::Library,
 	location::{non_indexed, LocationError},
-	object::media::thumbnail::get_indexed_thumb_key,
 	util::{unsafe_streamed_query, BatchedStream},
 };
 
-use sd_cache::{CacheNode, Model, Normalise, Reference};
+use sd_cache::{CacheNode, Model, Normalise, Normaliser, Reference};
 use sd_prisma::prisma::{self, PrismaClient};
 
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use async_stream::stream;
 use futures::StreamExt;
@@ -22,6 +23,7 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 
 pub mod file_path;
+pub mod media_by_date;
 pub mod media_data;
 pub mod object;
 pub mod saved;
@@ -29,6 +31,8 @@ mod utils;
 
 pub use self::{file_path::*, object::*, utils::*};
 
+use self::media_by_date::{MediaDateBucketItems, MediaDateCursor, MediaDateFilter};
+
 use super::{Ctx, R};
 
 const MAX_TAKE: u8 = 100;
@@ -170,7 +174,7 @@ pub fn mount() -> AlphaRouter<Ctx> {
 								}
 							}
 
-							let (nodes, entries) = entries.normalise(|item: &ExplorerItem| item.id());
+							let (nodes, entries) = entries.normalise(|item: &ExplorerItem| item.cache_key());
 
 							yield EphemeralPathsResultItem {
 								entries,
@@ -245,26 +249,19 @@ pub fn mount() -> AlphaRouter<Ctx> {
 					let mut items = Vec::with_capacity(file_paths.len());
 
 					for file_path in file_paths {
-						let thumbnail_exists_locally = if let Some(cas_id) = &file_path.cas_id {
-							library
-								.thumbnail_exists(&node, cas_id)
+						let (thumbnail, thumbnail_status) =
+							indexed_thumbnail(&node, library.id, file_path.cas_id.as_deref())
 								.await
-								.map_err(LocationError::from)?
-						} else {
-							false
-						};
+								.map_err(LocationError::from)?;
 
 						items.push(ExplorerItem::Path {
-							thumbnail: file_path
-								.cas_id
-								.as_ref()
-								.filter(|_| thumbnail_exists_locally)
-								.map(|i| get_indexed_thumb_key(i, library.id)),
+							thumbnail,
+							thumbnail_status,
 							item: file_path,
 						})
 					}
 
-					let (nodes, items) = items.normalise(|item| item.id());
+					let (nodes, items) = items.normalise(|item| item.cache_key());
 
 					Ok(SearchData {
 						items,
@@ -274,6 +271,118 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				},
 			)
 		})
+		.procedure("pathsPage", {
+			#[derive(Deserialize, Type, Debug)]
+			#[serde(rename_all = "camelCase")]
+			struct FilePathPageArgs {
+				#[specta(optional)]
+				take: Option<u8>,
+				/// The `id` of the last item of the previous page
+				#[specta(optional)]
+				cursor: Option<prisma::file_path::id::Type>,
+				#[serde(default)]
+				order: FilePathPageOrder,
+				#[serde(default)]
+				filters: Vec<SearchFilterArgs>,
+			}
+
+			#[derive(Serialize, Type, Debug)]
+			#[serde(rename_all = "camelCase")]
+			struct FilePathPage {
+				items: Vec<Reference<ExplorerItem>>,
+				nodes: Vec<CacheNode>,
+				/// `None` once the last page has been returned
+				next_cursor: Option<prisma::file_path::id::Type>,
+			}
+
+			R.with2(library()).query(
+				|(node, library),
+				 FilePathPageArgs {
+				     take,
+				     cursor,
+				     order,
+				     filters,
+				 }| async move {
+					let Library { db, .. } = library.as_ref();
+
+					let take = take.unwrap_or(MAX_TAKE).min(MAX_TAKE) as usize;
+
+					let cursor = match cursor {
+						Some(id) => {
+							let Some(file_path) = db
+								.file_path()
+								.find_unique(prisma::file_path::id::equals(id))
+								.select(prisma::file_path::select!({ name }))
+								.exec()
+								.await?
+							else {
+								return Err(ApiError::NotFound(format!(
+									"Cursor file path not found: <id='{id}'>"
+								))
+								.into());
+							};
+
+							Some(FilePathPageCursor {
+								id,
+								name: file_path.name,
+							})
+						}
+						None => None,
+					};
+
+					let mut query = db.file_path().find_many({
+						let mut params = Vec::new();
+
+						for filter in filters {
+							params.extend(filter.into_file_path_params(db).await?);
+						}
+
+						params
+					});
+
+					order.apply(&mut query, cursor);
+
+					// Fetching one more item than requested tells us if there's a next page
+					let mut file_paths = query
+						.take(take as i64 + 1)
+						.include(file_path_with_object::include())
+						.exec()
+						.await?;
+
+					let has_more = file_paths.len() > take;
+					file_paths.truncate(take);
+
+					let next_cursor = has_more
+						.then(|| file_paths.last().map(|file_path| file_path.id))
+						.flatten();
+
+					let mut normaliser = Normaliser::with_capacity(file_paths.len());
+
+					for file_path in file_paths {
+						let (thumbnail, thumbnail_status) =
+							indexed_thumbnail(&node, library.id, file_path.cas_id.as_deref())
+								.await
+								.map_err(LocationError::from)?;
+
+						let item = ExplorerItem::Path {
+							thumbnail,
+							thumbnail_status,
+							item: file_path,
+						};
+
+						normaliser.push(item.cache_key(), item);
+					}
+
+					let (nodes, items) = normaliser.finish();
+
+					Ok(FilePathPage {
+						items,
+						nodes,
+						next_cursor,
+					})
+				},
+			)
+		})
 		.procedure("pathsCount", {
 			#[derive(Deserialize, Type, Debug)]
 			#[serde(rename_all = "camelCase")]
@@ -364,27 +473,25 @@ pub fn mount() -> AlphaRouter<Ctx> {
 							.map(|fp| fp.cas_id.as_ref())
 							.find_map(|c| c);
 
-						let thumbnail_exists_locally = if let Some(cas_id) = cas_id {
-							library.thumbnail_exists(&node, cas_id).await.map_err(|e| {
-								rspc::Error::with_cause(
-									ErrorCode::InternalServerError,
-									"Failed to check that thumbnail exists".to_string(),
-									e,
-								)
-							})?
-						} else {
-							false
-						};
+						let (thumbnail, thumbnail_status) =
+							indexed_thumbnail(&node, library.id, cas_id.map(String::as_str))
+								.await
+								.map_err(|e| {
+									rspc::Error::with_cause(
+										ErrorCode::InternalServerError,
+										"Failed to check that thumbnail exists".to_string(),
+										e,
+									)
+								})?;
 
 						items.push(ExplorerItem::Object {
-							thumbnail: cas_id
-								.filter(|_| thumbnail_exists_locally)
-								.map(|cas_id| get_indexed_thumb_key(cas_id, library.id)),
+							thumbnail,
+							thumbnail_status,
 							item: object,
 						});
 					}
 
-					let (nodes, items) = items.normalise(|item| item.id());
+					let (nodes, items) = items.normalise(|item| item.cache_key());
 
 					Ok(SearchData {
 						nodes,
@@ -394,6 +501,127 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				},
 			)
 		})
+		.procedure("explorerItemsByIds", {
+			#[derive(Deserialize, Type, Debug, Clone, PartialEq, Eq, Hash)]
+			enum ExplorerItemId {
+				FilePath(prisma::file_path::id::Type),
+				Object(prisma::object::id::Type),
+				NonIndexed(PathBuf),
+			}
+
+			#[derive(Serialize, Type, Debug)]
+			struct ExplorerItemsByIds {
+				/// In the same order as the requested ids, `None` for the ones which weren't found
+				items: Vec<Option<Reference<ExplorerItem>>>,
+				nodes: Vec<CacheNode>,
+			}
+
+			R.with2(library())
+				.query(|(node, library), ids: Vec<ExplorerItemId>| async move {
+					let Library { db, .. } = library.as_ref();
+
+					let (file_path_ids, object_ids) = ids.iter().fold(
+						(vec![], vec![]),
+						|(mut file_path_ids, mut object_ids), id| {
+							match id {
+								ExplorerItemId::FilePath(id) => file_path_ids.push(*id),
+								ExplorerItemId::Object(id) => object_ids.push(*id),
+								ExplorerItemId::NonIndexed(_) => {}
+							}
+
+							(file_path_ids, object_ids)
+						},
+					);
+
+					let (file_paths, objects) = db
+						._batch((
+							db.file_path()
+								.find_many(vec![prisma::file_path::id::in_vec(file_path_ids)])
+								.include(file_path_with_object::include()),
+							db.object()
+								.find_many(vec![prisma::object::id::in_vec(object_ids)])
+								.include(object_with_file_paths::include()),
+						))
+						.await?;
+
+					let mut file_paths = file_paths
+						.into_iter()
+						.map(|file_path| (file_path.id, file_path))
+						.collect::<HashMap<_, _>>();
+					let mut objects = objects
+						.into_iter()
+						.map(|object| (object.id, object))
+						.collect::<HashMap<_, _>>();
+
+					// The same item can be requested more than once, but is only sent once
+					let mut keys = HashMap::<ExplorerItemId, String>::with_capacity(ids.len());
+					let mut items = Vec::with_capacity(ids.len());
+					let mut nodes = Vec::with_capacity(ids.len());
+
+					for id in ids {
+						if let Some(key) = keys.get(&id) {
+							items.push(Some(Reference::new(key.clone())));
+							continue;
+						}
+
+						let item = match &id {
+							ExplorerItemId::FilePath(file_path_id) => {
+								match file_paths.remove(file_path_id) {
+									Some(file_path) => {
+										let (thumbnail, thumbnail_status) = indexed_thumbnail(
+											&node,
+											library.id,
+											file_path.cas_id.as_deref(),
+										)
+										.await
+										.map_err(LocationError::from)?;
+
+										Some(ExplorerItem::Path {
+											thumbnail,
+											thumbnail_status,
+											item: file_path,
+										})
+									}
+									None => None,
+								}
+							}
+							ExplorerItemId::Object(object_id) => match objects.remove(object_id) {
+								Some(object) => {
+									let (thumbnail, thumbnail_status) = indexed_thumbnail(
+										&node,
+										library.id,
+										object
+											.file_paths
+											.iter()
+											.find_map(|file_path| file_path.cas_id.as_deref()),
+									)
+									.await
+									.map_err(LocationError::from)?;
+
+									Some(ExplorerItem::Object {
+										thumbnail,
+										thumbnail_status,
+										item: object,
+									})
+								}
+								None => None,
+							},
+							ExplorerItemId::NonIndexed(path) => {
+								non_indexed::explorer_item(path, &node).await?
+							}
+						};
+
+						items.push(item.map(|item| {
+							let key = item.cache_key();
+							nodes.push(CacheNode::new(key.clone(), item));
+							keys.insert(id, key.clone());
+							Reference::new(key)
+						}));
+					}
+
+					Ok(ExplorerItemsByIds { items, nodes })
+				})
+		})
 		.procedure("objectsCount", {
 			#[derive(Deserialize, Type, Debug)]
 			#[serde(rename_all = "camelCase")]
@@ -422,5 +650,90 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						.await? as u32)
 				})
 		})
+		.procedure("mediaByDate", {
+			R.with2(library())
+				.query(|(node, library), filter: MediaDateFilter| async move {
+					Ok(filter
+						.buckets(&library.db, library.id, node.thumbnailer.format())
+						.await?)
+				})
+		})
+		.procedure("mediaByDateBucket", {
+			#[derive(Deserialize, Type, Debug)]
+			#[serde(rename_all = "camelCase")]
+			struct MediaByDateBucketArgs {
+				filter: MediaDateFilter,
+				/// The `date` of the bucket, as returned by `search.mediaByDate`.
+				date: String,
+				take: u8,
+				#[specta(optional)]
+				cursor: Option<MediaDateCursor>,
+			}
+
+			R.with2(library()).query(
+				|(node, library),
+				 MediaByDateBucketArgs {
+				     filter,
+				     date,
+				     take,
+				     cursor,
+				 }| async move {
+					let Library { db, .. } = library.as_ref();
+
+					let take = take.min(MAX_TAKE);
+
+					let ids = filter.bucket_object_ids(db, date, take, cursor).await?;
+
+					let cursor = (ids.len() == take as usize)
+						.then(|| ids.last().map(|(_, cursor)| cursor.clone()))
+						.flatten();
+
+					let positions = ids
+						.iter()
+						.enumerate()
+						.map(|(position, (id, _))| (*id, position))
+						.collect::<HashMap<_, _>>();
+
+					let mut objects = db
+						.object()
+						.find_many(vec![prisma::object::id::in_vec(
+							ids.into_iter().map(|(id, _)| id).collect(),
+						)])
+						.include(object_with_file_paths::include())
+						.exec()
+						.await?;
+					objects.sort_by_key(|object| positions.get(&object.id).copied());
+
+					let mut items = Vec::with_capacity(objects.len());
+
+					for object in objects {
+						let (thumbnail, thumbnail_status) = indexed_thumbnail(
+							&node,
+							library.id,
+							object
+								.file_paths
+								.iter()
+								.find_map(|file_path| file_path.cas_id.as_deref()),
+						)
+						.await
+						.map_err(LocationError::from)?;
+
+						items.push(ExplorerItem::Object {
+							thumbnail,
+							thumbnail_status,
+							item: object,
+						});
+					}
+
+					let (nodes, items) = items.normalise(|item| item.cache_key());
+
+					Ok(MediaDateBucketItems {
+						cursor,
+						items,
+						nodes,
+					})
+				},
+			)
+		})
 		.merge("saved.", saved::mount())
 }
@@ -1,8 +1,136 @@
+use crate::library::Library;
+
 use sd_core_sync::GetOpsArgs;
+use sd_prisma::prisma;
+
+use std::collections::{BTreeSet, HashSet};
 
 use rspc::alpha::AlphaRouter;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use super::{
+	utils::{library, InvalidateOperationEvent},
+	CoreEvent, Ctx, R,
+};
+
+/// The queries which depend on each synced model, so ingesting operations from other instances
+/// only makes the frontend refetch what could have changed.
+const INGEST_INVALIDATIONS: &[(&str, &[&str])] = &[
+	(
+		prisma::location::NAME,
+		&[
+			"locations.list",
+			"locations.get",
+			"locations.getWithRules",
+			"nodes.listLocations",
+			"library.statistics",
+		],
+	),
+	(
+		prisma::file_path::NAME,
+		&[
+			"search.paths",
+			"search.pathsCount",
+			"search.pathsPage",
+			"search.objects",
+			"files.get",
+			"library.statistics",
+			"library.kindStatistics",
+		],
+	),
+	(
+		prisma::object::NAME,
+		&[
+			"search.objects",
+			"search.objectsCount",
+			"search.paths",
+			"search.pathsPage",
+			"files.get",
+			"files.recents",
+			"library.kindStatistics",
+		],
+	),
+	(
+		prisma::tag::NAME,
+		&[
+			"tags.list",
+			"tags.get",
+			"tags.getForObject",
+			"tags.getWithObjects",
+		],
+	),
+	(
+		prisma::tag_on_object::NAME,
+		&[
+			"tags.getForObject",
+			"tags.getWithObjects",
+			"search.objects",
+			"search.paths",
+			"search.pathsPage",
+		],
+	),
+	(prisma::preference::NAME, &["preferences.get"]),
+];
+
+/// The queries to invalidate after ingesting operations for `models`, or `None` if one of them
+/// isn't known and everything has to be invalidated.
+fn ingest_invalidations<'a>(
+	models: impl IntoIterator<Item = &'a str>,
+) -> Option<BTreeSet<&'static str>> {
+	let mut keys = BTreeSet::new();
+
+	for model in models {
+		let Some((_, model_keys)) = INGEST_INVALIDATIONS.iter().find(|(name, _)| *name == model)
+		else {
+			warn!("Ingested operations for unknown model '{model}'");
+			return None;
+		};
+
+		keys.extend(model_keys.iter().copied());
+	}
+
+	Some(keys)
+}
+
+/// Invalidates the queries affected by operations ingested from other instances for `models`,
+/// falling back to [`InvalidateOperationEvent::all`] for models missing from the mapping.
+pub(crate) fn invalidate_ingested(library: &Library, models: &HashSet<String>) {
+	let Some(keys) = ingest_invalidations(models.iter().map(String::as_str)) else {
+		library.emit(CoreEvent::InvalidateOperation(
+			InvalidateOperationEvent::all(),
+		));
+		return;
+	};
 
-use super::{utils::library, Ctx, R};
+	debug!(
+		"Ingested operations for {} models, invalidating {} queries",
+		models.len(),
+		keys.len()
+	);
+
+	for key in keys {
+		library.emit(CoreEvent::InvalidateOperation(
+			InvalidateOperationEvent::dangerously_create(key, Value::Null, None),
+		));
+	}
+}
+
+/// Ensures every query in [`INGEST_INVALIDATIONS`] exists on the router, as the keys can't be
+/// checked by [`invalidate_query!`](crate::invalidate_query).
+#[cfg(debug_assertions)]
+#[allow(clippy::panic)]
+pub(crate) fn validate_ingest_invalidations(r: &super::Router) {
+	let queries = r.queries();
+
+	for (model, keys) in INGEST_INVALIDATIONS {
+		for key in *keys {
+			if queries.get(*key).is_none() {
+				panic!("Ingested '{model}' operations invalidate query '{key}' which was not found in the router");
+			}
+		}
+	}
+}
 
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
@@ -33,3 +161,24 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			})
 		})
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_tag_ingest_does_not_invalidate_locations() {
+		let keys = ingest_invalidations([prisma::tag::NAME, prisma::tag_on_object::NAME]).unwrap();
+
+		assert!(keys.contains("tags.list"));
+		assert!(keys.contains("tags.getForObject"));
+		assert!(!keys.contains("locations.list"));
+		assert!(!keys.contains("library.statistics"));
+	}
+
+	#[test]
+	fn test_unknown_model_invalidates_everything() {
+		assert!(ingest_invalidations([prisma::tag::NAME, "not_a_model"]).is_none());
+	}
+}
@@ -1,4 +1,8 @@
-use crate::{invalidate_query, library::Library, object::tag::TagCreateArgs};
+use crate::{
+	invalidate_query,
+	library::Library,
+	object::tag::{reorder_tags, seed, sort_tags, TagCreateArgs},
+};
 
 use sd_cache::{CacheNode, Normalise, NormalisedResult, NormalisedResults, Reference};
 use sd_file_ext::kind::ObjectKind;
@@ -25,7 +29,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("list", {
 			R.with2(library()).query(|(_, library), _: ()| async move {
-				let tags = library.db.tag().find_many(vec![]).exec().await?;
+				let mut tags = library.db.tag().find_many(vec![]).exec().await?;
+				sort_tags(&mut tags);
 
 				let (nodes, items) = tags.normalise(|i| i.id.to_string());
 
@@ -113,6 +118,16 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Ok(created_tag)
 				})
 		})
+		.procedure("resetDefaults", {
+			R.with2(library())
+				.mutation(|(_, library), _: ()| async move {
+					if seed::new_or_existing_library(&library).await? > 0 {
+						invalidate_query!(library, "tags.list");
+					}
+
+					Ok(())
+				})
+		})
 		.procedure("assign", {
 			#[derive(Debug, Type, Deserialize)]
 			#[specta(inline)]
@@ -354,6 +369,21 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Ok(())
 				})
 		})
+		.procedure("reorder", {
+			#[derive(Type, Deserialize)]
+			pub struct TagReorderArgs {
+				pub ordered_ids: Vec<i32>,
+			}
+
+			R.with2(library())
+				.mutation(|(_, library), args: TagReorderArgs| async move {
+					reorder_tags(&library, args.ordered_ids).await?;
+
+					invalidate_query!(library, "tags.list");
+
+					Ok(())
+				})
+		})
 		.procedure(
 			"delete",
 			R.with2(library())
@@ -0,0 +1,110 @@
+use crate::{
+	job::Job,
+	location::{find_location, LocationError},
+	object::media::{media_processor::prewarm_thumbnails, MediaProcessorJobInit},
+};
+
+use sd_prisma::prisma::location;
+
+use std::path::PathBuf;
+
+use rspc::{alpha::AlphaRouter, ErrorCode};
+use serde::Deserialize;
+use specta::Type;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{utils::library, Ctx, R};
+
+pub(crate) fn mount() -> AlphaRouter<Ctx> {
+	R.router()
+		.procedure("prewarm", {
+			#[derive(Type, Deserialize)]
+			pub struct ThumbnailsPrewarmArgs {
+				pub location_id: location::id::Type,
+				pub sub_path: PathBuf,
+			}
+
+			R.with2(library()).mutation(
+				|(node, library),
+				 ThumbnailsPrewarmArgs {
+				     location_id,
+				     sub_path,
+				 }: ThumbnailsPrewarmArgs| async move {
+					let Some(location) = find_location(&library, location_id).exec().await? else {
+						return Err(LocationError::IdNotFound(location_id).into());
+					};
+
+					prewarm_thumbnails(&location, sub_path, &library, &node)
+						.await
+						.map_err(Into::into)
+				},
+			)
+		})
+		.procedure("regenerateAll", {
+			// Converges the existing thumbnails to the preferred format, returning for how many
+			// locations they're being regenerated
+			R.with2(library())
+				.mutation(|(node, library), _: ()| async move {
+					let locations = library
+						.db
+						.location()
+						.find_many(vec![location::instance_id::equals(Some(
+							library.config().await.instance_id,
+						))])
+						.exec()
+						.await?;
+
+					let mut regenerating = 0u32;
+
+					for location in locations {
+						let location_id = location.id;
+
+						if let Err(e) = Job::new(MediaProcessorJobInit {
+							location,
+							sub_path: None,
+							regenerate_thumbnails: true,
+							regenerate_labels: false,
+							labels_min_confidence: None,
+						})
+						.spawn(&node, &library)
+						.await
+						{
+							warn!(
+								"Failed to regenerate thumbnails of location <id='{location_id}'>: {e:#?}"
+							);
+							continue;
+						}
+
+						regenerating += 1;
+					}
+
+					Ok(regenerating)
+				})
+		})
+		.procedure("prewarmProgress", {
+			R.subscription(|node, batch_id: Uuid| async move {
+				let mut progress_rx = node
+					.thumbnailer
+					.prewarm_batch_progress(&batch_id)
+					.ok_or_else(|| {
+						rspc::Error::new(
+							ErrorCode::NotFound,
+							format!("thumbnails batch not found: <id='{batch_id}'>"),
+						)
+					})?;
+
+				Ok(async_stream::stream! {
+					loop {
+						let progress = *progress_rx.borrow_and_update();
+
+						yield progress;
+
+						if progress.is_done() || progress_rx.changed().await.is_err() {
+							break;
+						}
+					}
+				})
+			})
+		})
+}
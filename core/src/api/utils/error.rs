@@ -0,0 +1,101 @@
+use std::{error::Error, time::Duration};
+
+use rspc::ErrorCode;
+use thiserror::Error;
+
+/// Errors the procedures raise themselves, instead of building [`rspc::Error`]s from strings.
+///
+/// Each kind maps to its own [`ErrorCode`], so clients can branch on the code instead of parsing
+/// the message.
+#[derive(Debug, Error)]
+pub enum ApiError {
+	/// The requested resource doesn't exist.
+	#[error("{0}")]
+	NotFound(String),
+	/// The request conflicts with the current state, like work still running on the resource.
+	#[error("{0}")]
+	Conflict(String),
+	/// The request needs an authenticated user.
+	#[error("{0}")]
+	Unauthorized(String),
+	/// The resource exists but can't be changed this way.
+	#[error("{0}")]
+	Forbidden(String),
+	/// The request's arguments are invalid.
+	#[error("{0}")]
+	Validation(String),
+	/// The procedure isn't available in this build or on this platform.
+	#[error("{0}")]
+	Unsupported(String),
+	/// The procedure was called too often, rspc has no code for this so it's reported as a
+	/// conflict with the message saying when to retry.
+	#[error("{message}")]
+	RateLimited {
+		message: String,
+		retry_after: Duration,
+	},
+	#[error("{message}")]
+	Internal {
+		message: String,
+		#[source]
+		source: Box<dyn Error + Send + Sync>,
+	},
+}
+
+impl ApiError {
+	pub fn internal(
+		message: impl Into<String>,
+		source: impl Error + Send + Sync + 'static,
+	) -> Self {
+		Self::Internal {
+			message: message.into(),
+			source: Box::new(source),
+		}
+	}
+
+	pub fn code(&self) -> ErrorCode {
+		match self {
+			Self::NotFound(_) => ErrorCode::NotFound,
+			Self::Conflict(_) => ErrorCode::Conflict,
+			Self::Unauthorized(_) => ErrorCode::Unauthorized,
+			Self::Forbidden(_) => ErrorCode::Forbidden,
+			Self::Validation(_) => ErrorCode::BadRequest,
+			Self::Unsupported(_) => ErrorCode::MethodNotSupported,
+			Self::RateLimited { .. } => ErrorCode::Conflict,
+			Self::Internal { .. } => ErrorCode::InternalServerError,
+		}
+	}
+}
+
+impl From<ApiError> for rspc::Error {
+	fn from(err: ApiError) -> Self {
+		rspc::Error::with_cause(err.code(), err.to_string(), err)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::io;
+
+	#[test]
+	fn test_api_error_codes_and_messages() {
+		let err = ApiError::Conflict("Location is being indexed".to_string());
+		assert_eq!(err.code(), ErrorCode::Conflict);
+		assert_eq!(err.to_string(), "Location is being indexed");
+
+		assert_eq!(
+			ApiError::Validation(String::new()).code(),
+			ErrorCode::BadRequest
+		);
+
+		let err = ApiError::internal(
+			"Failed to read rules",
+			io::Error::new(io::ErrorKind::Other, "disk on fire"),
+		);
+		assert_eq!(err.code(), ErrorCode::InternalServerError);
+		assert_eq!(err.to_string(), "Failed to read rules");
+		assert!(err.source().is_some());
+	}
+}
@@ -1,9 +1,13 @@
-use crate::api::{CoreEvent, Ctx, Router, R};
+use crate::{
+	api::{CoreEvent, Ctx, Router, R},
+	node::{EventCategory, EventFilter},
+};
 
 use async_stream::stream;
+use futures::StreamExt;
 use rspc::alpha::AlphaRouter;
 use serde::Serialize;
-use serde_hashkey::to_key;
+use serde_hashkey::{to_key, Key};
 use serde_json::Value;
 use specta::{DataType, Type};
 use std::{
@@ -16,6 +20,7 @@ use std::{
 };
 use tokio::sync::broadcast;
 use tracing::{debug, warn};
+use uuid::Uuid;
 
 #[cfg(debug_assertions)]
 use std::sync::Mutex;
@@ -32,6 +37,21 @@ pub struct SingleInvalidateOperationEvent {
 	pub key: &'static str,
 	arg: Value,
 	result: Option<Value>,
+	/// Set when the event is emitted by a library, so subscribers can filter by library
+	#[serde(skip)]
+	library_id: Option<Uuid>,
+}
+
+impl SingleInvalidateOperationEvent {
+	pub fn library_id(&self) -> Option<Uuid> {
+		self.library_id
+	}
+
+	/// Invalidations of the same query and arguments in the same library share this key, so they
+	/// can be coalesced by the [`EventBus`](crate::node::EventBus).
+	pub(crate) fn coalescing_key(&self) -> Result<Key, serde_hashkey::Error> {
+		to_key(&(self.library_id, self.key, &self.arg))
+	}
 }
 
 #[derive(Debug, Clone, Serialize, Type)]
@@ -45,7 +65,22 @@ pub enum InvalidateOperationEvent {
 impl InvalidateOperationEvent {
 	/// If you are using this function, your doing it wrong.
 	pub fn dangerously_create(key: &'static str, arg: Value, result: Option<Value>) -> Self {
-		Self::Single(SingleInvalidateOperationEvent { key, arg, result })
+		Self::Single(SingleInvalidateOperationEvent {
+			key,
+			arg,
+			result,
+			library_id: None,
+		})
+	}
+
+	pub(crate) fn with_library_id(self, library_id: Uuid) -> Self {
+		match self {
+			Self::Single(event) => Self::Single(SingleInvalidateOperationEvent {
+				library_id: Some(library_id),
+				..event
+			}),
+			Self::All => Self::All,
+		}
 	}
 
 	pub fn all() -> Self {
@@ -185,9 +220,9 @@ macro_rules! invalidate_query {
 		::tracing::trace!(target: "sd_core::invalidate-query", "invalidate_query!(\"{}\") at {}", $key, concat!(file!(), ":", line!()));
 
 		// The error are ignored here because they aren't mission critical. If they fail the UI might be outdated for a bit.
-		ctx.event_bus.0.send($crate::api::CoreEvent::InvalidateOperation(
+		ctx.event_bus.send($crate::api::CoreEvent::InvalidateOperation(
 			$crate::api::utils::InvalidateOperationEvent::dangerously_create($key, serde_json::Value::Null, None)
-		)).ok();
+		));
 	}};
 	($ctx:expr, $key:literal: $arg_ty:ty, $arg:expr $(,)?) => {{
 		let _: $arg_ty = $arg; // Assert the type the user provided is correct
@@ -307,16 +342,22 @@ pub(crate) fn mount_invalidate() -> AlphaRouter<Ctx> {
 			// Their is only ever one of these management threads per Node but we spawn it like this so we can steal the event bus from the rspc context.
 			// Batching is important because when refetching data on the frontend rspc can fetch all invalidated queries in a single round trip.
 			if !manager_thread_active.swap(true, Ordering::Relaxed) {
-				let mut event_bus_rx = ctx.event_bus.0.subscribe();
+				// Lost invalidations become a full invalidation, so clients never keep stale data
+				let mut events =
+					ctx.subscribe_resyncing(EventFilter::categories([EventCategory::Invalidation]));
 				let tx = tx.clone();
 				let manager_thread_active = manager_thread_active.clone();
 
 				tokio::spawn(async move {
 					loop {
-						let Ok(CoreEvent::InvalidateOperation(first_event)) =
-							event_bus_rx.recv().await
-						else {
-							continue;
+						let first_event = match events.next().await {
+							Some(CoreEvent::InvalidateOperation(event)) => event,
+							Some(_) => continue,
+							None => {
+								warn!("Shutting down invalidation manager thread due to the core event bus being dropped!");
+								manager_thread_active.store(false, Ordering::Relaxed);
+								break;
+							}
 						};
 
 						let mut buf =
@@ -346,14 +387,16 @@ pub(crate) fn mount_invalidate() -> AlphaRouter<Ctx> {
 								_ = tokio::time::sleep_until(batch_time) => {
 									break;
 								}
-								event = event_bus_rx.recv() => {
-									let Ok(event) = event else {
-										warn!("Shutting down invalidation manager thread due to the core event bus being dropped!");
-										break;
+								event = events.next() => {
+									let op = match event {
+										Some(CoreEvent::InvalidateOperation(op)) => op,
+										Some(_) => continue,
+										None => {
+											warn!("Shutting down invalidation manager thread due to the core event bus being dropped!");
+											break;
+										}
 									};
 
-									let CoreEvent::InvalidateOperation(op) = event else { continue; };
-
 									match (&op, &mut buf) {
 										(InvalidateOperationEvent::All, Some(_)) => buf = None,
 										(InvalidateOperationEvent::Single(SingleInvalidateOperationEvent { key, arg, .. }), Some(buf)) => {
@@ -13,6 +13,8 @@ use uuid::Uuid;
 
 use crate::{api::Ctx, library::Library};
 
+use super::RateLimit;
+
 /// Can wrap a query argument to require it to contain a `library_id` and provide helpers for working with libraries.
 #[derive(Clone, Serialize, Deserialize, Type)]
 pub(crate) struct LibraryArgs<T> {
@@ -33,18 +35,35 @@ impl MwArgMapper for LibraryArgsLike {
 }
 
 pub(crate) fn library() -> impl MwV3<Ctx, NewCtx = (Ctx, Arc<Library>)> {
-	MwArgMapperMiddleware::<LibraryArgsLike>::new().mount(|mw, ctx: Ctx, library_id| async move {
-		let library = ctx
-			.libraries
-			.get_library(&library_id)
-			.await
-			.ok_or_else(|| {
-				rspc::Error::new(
-					ErrorCode::BadRequest,
-					"You must specify a valid library to use this operation.".to_string(),
-				)
-			})?;
-
-		Ok(mw.next((ctx, library)))
-	})
+	library_middleware(None)
+}
+
+/// Like [`library`], but rejects calls made more often than the limit allows for the library.
+pub(crate) fn rate_limited_library(
+	limit: RateLimit,
+) -> impl MwV3<Ctx, NewCtx = (Ctx, Arc<Library>)> {
+	library_middleware(Some(limit))
+}
+
+fn library_middleware(limit: Option<RateLimit>) -> impl MwV3<Ctx, NewCtx = (Ctx, Arc<Library>)> {
+	MwArgMapperMiddleware::<LibraryArgsLike>::new().mount(
+		move |mw, ctx: Ctx, library_id| async move {
+			let library = ctx
+				.libraries
+				.get_library(&library_id)
+				.await
+				.ok_or_else(|| {
+					rspc::Error::new(
+						ErrorCode::BadRequest,
+						"You must specify a valid library to use this operation.".to_string(),
+					)
+				})?;
+
+			if let Some(limit) = limit {
+				ctx.rate_limiter.check(limit, library.id)?;
+			}
+
+			Ok(mw.next((ctx, library)))
+		},
+	)
 }
@@ -2,11 +2,15 @@ use std::path::Path;
 
 use tokio::{fs, io};
 
+mod error;
 mod invalidate;
 mod library;
+mod rate_limit;
 
+pub use error::*;
 pub use invalidate::*;
 pub(crate) use library::*;
+pub(crate) use rate_limit::*;
 
 /// Returns the size of the file or directory
 pub async fn get_size(path: impl AsRef<Path>) -> Result<u64, io::Error> {
@@ -0,0 +1,179 @@
+use std::{
+	collections::HashMap,
+	sync::{Mutex, PoisonError},
+	time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+use super::ApiError;
+
+/// How often a procedure can be called for the same library, declared next to the procedure and
+/// applied with [`rate_limited_library`](super::rate_limited_library).
+///
+/// In debug builds the limits can be overridden with the `SD_RATE_LIMITS` environment variable,
+/// as comma separated `key=milliseconds` pairs like `locations.fullRescan=0`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RateLimit {
+	key: &'static str,
+	min_interval: Duration,
+}
+
+impl RateLimit {
+	pub const fn new(key: &'static str, min_interval: Duration) -> Self {
+		Self { key, min_interval }
+	}
+}
+
+/// Remembers when rate limited procedures were last called, per library.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+	last_calls: Mutex<HashMap<(&'static str, Uuid), Instant>>,
+	#[cfg(debug_assertions)]
+	overrides: HashMap<String, Duration>,
+}
+
+impl RateLimiter {
+	pub fn new() -> Self {
+		Self {
+			last_calls: Mutex::default(),
+			#[cfg(debug_assertions)]
+			overrides: std::env::var("SD_RATE_LIMITS")
+				.map(|overrides| parse_overrides(&overrides))
+				.unwrap_or_default(),
+		}
+	}
+
+	/// Records a call to the procedure for the library, or errors with how long to wait if the
+	/// last one was too recent.
+	pub(crate) fn check(&self, limit: RateLimit, library_id: Uuid) -> Result<(), ApiError> {
+		self.check_at(limit, library_id, Instant::now())
+	}
+
+	fn check_at(&self, limit: RateLimit, library_id: Uuid, now: Instant) -> Result<(), ApiError> {
+		#[cfg(debug_assertions)]
+		let min_interval = self
+			.overrides
+			.get(limit.key)
+			.copied()
+			.unwrap_or(limit.min_interval);
+		#[cfg(not(debug_assertions))]
+		let min_interval = limit.min_interval;
+
+		let mut last_calls = self
+			.last_calls
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+
+		if let Some(last_call) = last_calls.get(&(limit.key, library_id)) {
+			let elapsed = now.saturating_duration_since(*last_call);
+
+			if elapsed < min_interval {
+				let retry_after = min_interval - elapsed;
+
+				return Err(ApiError::RateLimited {
+					message: format!(
+						"Too many calls to '{}', retry in {}ms",
+						limit.key,
+						retry_after.as_millis()
+					),
+					retry_after,
+				});
+			}
+		}
+
+		last_calls.insert((limit.key, library_id), now);
+
+		Ok(())
+	}
+}
+
+#[cfg(debug_assertions)]
+fn parse_overrides(overrides: &str) -> HashMap<String, Duration> {
+	overrides
+		.split(',')
+		.filter(|pair| !pair.trim().is_empty())
+		.filter_map(|pair| {
+			let parsed = pair.split_once('=').and_then(|(key, millis)| {
+				millis
+					.trim()
+					.parse()
+					.ok()
+					.map(|millis| (key.trim().to_string(), Duration::from_millis(millis)))
+			});
+
+			if parsed.is_none() {
+				tracing::warn!("Ignoring invalid rate limit override '{pair}'");
+			}
+
+			parsed
+		})
+		.collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::panic)]
+mod tests {
+	use super::*;
+
+	const LIMIT: RateLimit = RateLimit::new("locations.fullRescan", Duration::from_secs(30));
+
+	#[test]
+	fn test_rate_limit_is_per_library() {
+		let limiter = RateLimiter::default();
+		let (busy_library, other_library) = (Uuid::new_v4(), Uuid::new_v4());
+		let now = Instant::now();
+
+		assert!(limiter.check_at(LIMIT, busy_library, now).is_ok());
+		for i in 1..100 {
+			assert!(matches!(
+				limiter.check_at(LIMIT, busy_library, now + Duration::from_millis(i)),
+				Err(ApiError::RateLimited { .. })
+			));
+		}
+
+		// The other library isn't affected by the first one being hammered
+		assert!(limiter
+			.check_at(LIMIT, other_library, now + Duration::from_secs(1))
+			.is_ok());
+
+		// Neither are other procedures of the same library
+		assert!(limiter
+			.check_at(
+				RateLimit::new("library.statistics", Duration::from_secs(1)),
+				busy_library,
+				now
+			)
+			.is_ok());
+	}
+
+	#[test]
+	fn test_rate_limit_retry_after() {
+		let limiter = RateLimiter::default();
+		let library_id = Uuid::new_v4();
+		let now = Instant::now();
+
+		assert!(limiter.check_at(LIMIT, library_id, now).is_ok());
+
+		match limiter.check_at(LIMIT, library_id, now + Duration::from_secs(10)) {
+			Err(ApiError::RateLimited { retry_after, .. }) => {
+				assert_eq!(retry_after, Duration::from_secs(20))
+			}
+			res => panic!("expected a rate limit error, got {res:?}"),
+		}
+
+		assert!(limiter
+			.check_at(LIMIT, library_id, now + Duration::from_secs(30))
+			.is_ok());
+	}
+
+	#[test]
+	#[cfg(debug_assertions)]
+	fn test_parse_overrides() {
+		let overrides = parse_overrides("locations.fullRescan=0, library.statistics = 250,nope");
+
+		assert_eq!(overrides.len(), 2);
+		assert_eq!(overrides["locations.fullRescan"], Duration::ZERO);
+		assert_eq!(overrides["library.statistics"], Duration::from_millis(250));
+	}
+}
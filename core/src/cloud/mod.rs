@@ -1 +1,2 @@
+pub mod pending;
 pub mod sync;
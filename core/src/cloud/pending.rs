@@ -0,0 +1,362 @@
+use crate::{invalidate_query, library::Library, Node};
+
+use sd_prisma::prisma::{cloud_pending_operation, PrismaClient, SortOrder};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use thiserror::Error;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// The longest to wait before trying to send a failed operation again.
+const MAX_BACKOFF_MINUTES: i64 = 60;
+
+#[derive(Debug, Error)]
+pub enum PendingOperationError {
+	#[error("database error: {0}")]
+	Database(#[from] prisma_client_rust::QueryError),
+	#[error("failed to (de)serialize pending cloud operation: {0}")]
+	Json(#[from] serde_json::Error),
+}
+
+impl From<PendingOperationError> for rspc::Error {
+	fn from(e: PendingOperationError) -> Self {
+		Self::with_cause(rspc::ErrorCode::InternalServerError, e.to_string(), e)
+	}
+}
+
+/// A change to the library on the cloud, which ends up the same however many times it's sent.
+#[derive(Serialize, Deserialize, Type, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum CloudOperation {
+	UpdateLibrary {
+		name: String,
+	},
+	UpdateInstance {
+		instance_uuid: Uuid,
+		node_id: Uuid,
+		node_name: String,
+		node_platform: u8,
+	},
+}
+
+impl CloudOperation {
+	/// What the operation changes, a newer operation on it supersedes the queued one.
+	fn resource(&self) -> String {
+		match self {
+			Self::UpdateLibrary { .. } => "library".to_string(),
+			Self::UpdateInstance { instance_uuid, .. } => format!("instance/{instance_uuid}"),
+		}
+	}
+
+	async fn send(&self, node: &Node, library: &Library) -> Result<(), sd_cloud_api::Error> {
+		match self {
+			Self::UpdateLibrary { name } => {
+				sd_cloud_api::library::update(
+					node.cloud_api_config().await,
+					library.id,
+					Some(name.clone()),
+				)
+				.await
+			}
+			Self::UpdateInstance {
+				instance_uuid,
+				node_id,
+				node_name,
+				node_platform,
+			} => {
+				sd_cloud_api::library::update_instance(
+					node.cloud_api_config().await,
+					library.id,
+					*instance_uuid,
+					Some(*node_id),
+					Some(node_name.clone()),
+					Some(*node_platform),
+				)
+				.await
+			}
+		}
+	}
+}
+
+/// An operation waiting to be sent to the cloud.
+#[derive(Serialize, Type, Debug)]
+pub struct PendingCloudOperation {
+	pub operation: CloudOperation,
+	pub attempts: u32,
+	pub last_error: Option<String>,
+	pub next_attempt_at: DateTime<Utc>,
+	pub queued_at: DateTime<Utc>,
+}
+
+/// Twice as long after each failed attempt, starting at a minute.
+fn backoff(attempts: i32) -> Duration {
+	Duration::minutes((1i64 << (attempts.clamp(1, 7) - 1)).min(MAX_BACKOFF_MINUTES))
+}
+
+/// Sends an operation to the cloud, queuing it to be sent again later if that fails. Returns
+/// whether it was sent right away.
+///
+/// Failures aren't told apart, as being offline or signed out both pass, and sending these
+/// operations again is harmless.
+pub async fn send(
+	node: &Node,
+	library: &Library,
+	operation: CloudOperation,
+) -> Result<bool, PendingOperationError> {
+	let Err(e) = operation.send(node, library).await else {
+		// The queued one is outdated, sending it later would undo this one
+		let removed = library
+			.db
+			.cloud_pending_operation()
+			.delete_many(vec![cloud_pending_operation::resource::equals(
+				operation.resource(),
+			)])
+			.exec()
+			.await?;
+
+		if removed > 0 {
+			invalidate_query!(library, "cloud.pendingOperations");
+		}
+
+		return Ok(true);
+	};
+
+	warn!("Failed to send {operation:?} to the cloud, queuing it to try again later: {e}");
+
+	enqueue(library, &operation, Some(e.to_string())).await?;
+
+	Ok(false)
+}
+
+/// Queues an operation to be sent on the next [`flush`], replacing the one queued for the same
+/// resource. `error` is set when it was just tried, so it waits before being tried again.
+pub async fn enqueue(
+	library: &Library,
+	operation: &CloudOperation,
+	error: Option<String>,
+) -> Result<(), PendingOperationError> {
+	let now = Utc::now();
+	let resource = operation.resource();
+	let operation = serde_json::to_vec(operation)?;
+
+	let (attempts, next_attempt_at) = if error.is_some() {
+		(1, now + backoff(1))
+	} else {
+		(0, now)
+	};
+
+	library
+		.db
+		.cloud_pending_operation()
+		.upsert(
+			cloud_pending_operation::resource::equals(resource.clone()),
+			cloud_pending_operation::create(
+				resource,
+				operation.clone(),
+				next_attempt_at.into(),
+				now.into(),
+				vec![
+					cloud_pending_operation::attempts::set(attempts),
+					cloud_pending_operation::last_error::set(error.clone()),
+				],
+			),
+			vec![
+				cloud_pending_operation::operation::set(operation),
+				cloud_pending_operation::attempts::set(attempts),
+				cloud_pending_operation::last_error::set(error),
+				cloud_pending_operation::next_attempt_at::set(next_attempt_at.into()),
+				cloud_pending_operation::queued_at::set(now.into()),
+			],
+		)
+		.exec()
+		.await?;
+
+	invalidate_query!(library, "cloud.pendingOperations");
+
+	Ok(())
+}
+
+/// Sends the queued operations which are due, oldest first, or all of them when `force` is set,
+/// like when the cloud was just reached. Returns how many were sent.
+///
+/// Stops at the first one which fails, as the others would most likely fail too.
+pub async fn flush(
+	node: &Node,
+	library: &Library,
+	force: bool,
+) -> Result<u32, PendingOperationError> {
+	let Library { db, .. } = library;
+
+	let now = Utc::now();
+
+	let queued = db
+		.cloud_pending_operation()
+		.find_many(if force {
+			vec![]
+		} else {
+			vec![cloud_pending_operation::next_attempt_at::lte(now.into())]
+		})
+		.order_by(cloud_pending_operation::id::order(SortOrder::Asc))
+		.exec()
+		.await?;
+
+	if queued.is_empty() {
+		return Ok(0);
+	}
+
+	let mut sent = 0;
+
+	for pending in queued {
+		// Unless it was replaced by a newer one while being sent
+		let still_queued = vec![
+			cloud_pending_operation::id::equals(pending.id),
+			cloud_pending_operation::queued_at::equals(pending.queued_at),
+		];
+
+		let operation = match serde_json::from_slice::<CloudOperation>(&pending.operation) {
+			Ok(operation) => operation,
+			Err(e) => {
+				warn!(
+					"Dropping pending cloud operation for '{}' which can't be read: {e}",
+					pending.resource
+				);
+				db.cloud_pending_operation()
+					.delete_many(still_queued)
+					.exec()
+					.await?;
+				continue;
+			}
+		};
+
+		if let Err(e) = operation.send(node, library).await {
+			let attempts = pending.attempts + 1;
+
+			debug!(
+				"Failed to send pending cloud operation for '{}', attempt {attempts}: {e}",
+				pending.resource
+			);
+
+			db.cloud_pending_operation()
+				.update_many(
+					still_queued,
+					vec![
+						cloud_pending_operation::attempts::set(attempts),
+						cloud_pending_operation::last_error::set(Some(e.to_string())),
+						cloud_pending_operation::next_attempt_at::set(
+							(now + backoff(attempts)).into(),
+						),
+					],
+				)
+				.exec()
+				.await?;
+
+			break;
+		}
+
+		db.cloud_pending_operation()
+			.delete_many(still_queued)
+			.exec()
+			.await?;
+
+		sent += 1;
+	}
+
+	if sent > 0 {
+		debug!("Sent {sent} pending operations to the cloud");
+	}
+
+	invalidate_query!(library, "cloud.pendingOperations");
+
+	Ok(sent)
+}
+
+pub async fn list(db: &PrismaClient) -> Result<Vec<PendingCloudOperation>, PendingOperationError> {
+	Ok(db
+		.cloud_pending_operation()
+		.find_many(vec![])
+		.order_by(cloud_pending_operation::id::order(SortOrder::Asc))
+		.exec()
+		.await?
+		.into_iter()
+		.filter_map(|pending| {
+			Some(PendingCloudOperation {
+				operation: serde_json::from_slice(&pending.operation)
+					.map_err(|e| {
+						warn!(
+							"Pending cloud operation for '{}' can't be read: {e}",
+							pending.resource
+						)
+					})
+					.ok()?,
+				attempts: pending.attempts as u32,
+				last_error: pending.last_error,
+				next_attempt_at: pending.next_attempt_at.into(),
+				queued_at: pending.queued_at.into(),
+			})
+		})
+		.collect())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_library;
+
+	#[test]
+	fn test_backoff() {
+		assert_eq!(backoff(0), Duration::minutes(1));
+		assert_eq!(backoff(1), Duration::minutes(1));
+		assert_eq!(backoff(2), Duration::minutes(2));
+		assert_eq!(backoff(4), Duration::minutes(8));
+		assert_eq!(backoff(7), Duration::minutes(MAX_BACKOFF_MINUTES));
+		assert_eq!(backoff(100), Duration::minutes(MAX_BACKOFF_MINUTES));
+	}
+
+	#[tokio::test]
+	async fn test_enqueue_supersedes_queued_operation() {
+		let (_data_dir, _node, library) = test_library("Pending").await;
+
+		let instance = CloudOperation::UpdateInstance {
+			instance_uuid: library.instance_uuid,
+			node_id: Uuid::new_v4(),
+			node_name: "Laptop".to_string(),
+			node_platform: 0,
+		};
+
+		enqueue(
+			&library,
+			&CloudOperation::UpdateLibrary {
+				name: "Photos".to_string(),
+			},
+			Some("offline".to_string()),
+		)
+		.await
+		.unwrap();
+		enqueue(&library, &instance, None).await.unwrap();
+		enqueue(
+			&library,
+			&CloudOperation::UpdateLibrary {
+				name: "Holidays".to_string(),
+			},
+			None,
+		)
+		.await
+		.unwrap();
+
+		let pending = list(&library.db).await.unwrap();
+		assert_eq!(pending.len(), 2);
+		assert_eq!(
+			pending[0].operation,
+			CloudOperation::UpdateLibrary {
+				name: "Holidays".to_string()
+			}
+		);
+		assert_eq!(pending[0].attempts, 0);
+		assert_eq!(pending[0].last_error, None);
+		assert_eq!(pending[1].operation, instance);
+	}
+}
@@ -0,0 +1,313 @@
+//! Chunked uploads of message collections which are too large to be sent in a single request.
+//!
+//! The payload is uploaded in chunks, and the message collection only contains a [`ChunkManifest`]
+//! describing them. Receivers download the chunks and verify them against the manifest.
+//!
+//! The upload id is derived from the payload, so retrying the upload of the same operations skips
+//! the chunks the server has already acknowledged.
+
+use sd_cloud_api::{library::message_collections, RequestConfigProvider};
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Payloads larger than this are uploaded in chunks of this size.
+pub const CHUNK_SIZE: usize = 512 * 1024;
+
+/// The prefix of a message collection containing a chunk manifest instead of the payload.
+const PAYLOAD_CHUNKED_V1: &str = "sd-chunked-v1:";
+
+const CHUNK_UPLOAD_ATTEMPTS: u32 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkManifest {
+	pub upload_id: Uuid,
+	pub size: u64,
+	/// BLAKE3 hash of the whole payload
+	pub checksum: String,
+	pub chunks: Vec<ChunkInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkInfo {
+	pub index: u32,
+	pub size: u64,
+	/// BLAKE3 hash of the chunk
+	pub checksum: String,
+}
+
+#[derive(Debug, Error)]
+pub enum ChunkedUploadError {
+	#[error("cloud api request failed: {0}")]
+	Api(#[from] sd_cloud_api::Error),
+	#[error("failed to decode chunk: {0}")]
+	Base64(#[from] base64::DecodeError),
+	#[error("failed to (de)serialize chunk manifest: {0}")]
+	Json(#[from] serde_json::Error),
+	#[error("chunk {0} doesn't match the manifest")]
+	ChunkMismatch(u32),
+	#[error("reassembled payload doesn't match the manifest")]
+	PayloadMismatch,
+}
+
+fn checksum(bytes: &[u8]) -> String {
+	blake3::hash(bytes).to_hex().to_string()
+}
+
+impl ChunkManifest {
+	/// Whether the payload is too large to be uploaded in a single request.
+	pub fn is_required(payload: &[u8]) -> bool {
+		payload.len() > CHUNK_SIZE
+	}
+
+	pub fn new(instance_uuid: Uuid, payload: &[u8]) -> Self {
+		let payload_checksum = checksum(payload);
+
+		let mut hasher = blake3::Hasher::new();
+		hasher.update(instance_uuid.as_bytes());
+		hasher.update(payload_checksum.as_bytes());
+		let mut upload_id = [0u8; 16];
+		upload_id.copy_from_slice(&hasher.finalize().as_bytes()[..16]);
+
+		Self {
+			upload_id: Uuid::from_bytes(upload_id),
+			size: payload.len() as u64,
+			checksum: payload_checksum,
+			chunks: payload
+				.chunks(CHUNK_SIZE)
+				.enumerate()
+				.map(|(index, chunk)| ChunkInfo {
+					index: index as u32,
+					size: chunk.len() as u64,
+					checksum: checksum(chunk),
+				})
+				.collect(),
+		}
+	}
+
+	/// Serialize into the contents of the message collection, in place of the payload.
+	pub fn to_payload(&self) -> Result<Value, ChunkedUploadError> {
+		Ok(Value::String(format!(
+			"{PAYLOAD_CHUNKED_V1}{}",
+			serde_json::to_string(self)?
+		)))
+	}
+
+	/// The manifest in the contents of a message collection, if it was uploaded in chunks.
+	pub fn from_payload(contents: &[u8]) -> Option<Result<Self, ChunkedUploadError>> {
+		let Ok(Value::String(payload)) = serde_json::from_slice::<Value>(contents) else {
+			return None;
+		};
+
+		payload
+			.strip_prefix(PAYLOAD_CHUNKED_V1)
+			.map(|manifest| serde_json::from_str(manifest).map_err(Into::into))
+	}
+
+	fn verify_chunk(&self, index: u32, chunk: &[u8]) -> Result<(), ChunkedUploadError> {
+		match self.chunks.get(index as usize) {
+			Some(info) if info.size == chunk.len() as u64 && info.checksum == checksum(chunk) => {
+				Ok(())
+			}
+			_ => Err(ChunkedUploadError::ChunkMismatch(index)),
+		}
+	}
+
+	/// Reassemble the payload from its chunks, in order, verifying each of them against the manifest.
+	pub fn reassemble(
+		&self,
+		chunks: impl IntoIterator<Item = Vec<u8>>,
+	) -> Result<Vec<u8>, ChunkedUploadError> {
+		let mut payload = Vec::with_capacity(self.size as usize);
+		let mut count = 0;
+
+		for (index, chunk) in chunks.into_iter().enumerate() {
+			self.verify_chunk(index as u32, &chunk)?;
+			payload.extend_from_slice(&chunk);
+			count += 1;
+		}
+
+		if count != self.chunks.len()
+			|| payload.len() as u64 != self.size
+			|| checksum(&payload) != self.checksum
+		{
+			return Err(ChunkedUploadError::PayloadMismatch);
+		}
+
+		Ok(payload)
+	}
+}
+
+/// Upload the chunks of the payload which the server hasn't acknowledged yet.
+pub async fn upload(
+	cloud_api_config_provider: &Arc<impl RequestConfigProvider>,
+	library_id: Uuid,
+	manifest: &ChunkManifest,
+	payload: &[u8],
+) -> Result<(), ChunkedUploadError> {
+	let acknowledged = message_collections::upload_status(
+		cloud_api_config_provider.get_request_config().await,
+		library_id,
+		manifest.upload_id,
+	)
+	.await?
+	.acknowledged
+	.into_iter()
+	.collect::<HashSet<_>>();
+
+	debug!(
+		"Uploading {} of {} chunks for upload {}",
+		manifest.chunks.len().saturating_sub(acknowledged.len()),
+		manifest.chunks.len(),
+		manifest.upload_id
+	);
+
+	for (info, chunk) in manifest.chunks.iter().zip(payload.chunks(CHUNK_SIZE)) {
+		if acknowledged.contains(&info.index) {
+			continue;
+		}
+
+		let mut attempt = 1;
+		loop {
+			match message_collections::upload_chunk(
+				cloud_api_config_provider.get_request_config().await,
+				library_id,
+				manifest.upload_id,
+				message_collections::upload_chunk::Input {
+					index: info.index,
+					checksum: info.checksum.clone(),
+					contents: BASE64_STANDARD.encode(chunk),
+				},
+			)
+			.await
+			{
+				Ok(()) => break,
+				Err(e) if attempt < CHUNK_UPLOAD_ATTEMPTS => {
+					warn!(
+						"Failed to upload chunk {} of upload {}, retrying: {e}",
+						info.index, manifest.upload_id
+					);
+					sleep(Duration::from_secs(u64::from(attempt))).await;
+					attempt += 1;
+				}
+				Err(e) => return Err(e.into()),
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Download the chunks of the payload and reassemble it.
+pub async fn download(
+	cloud_api_config_provider: &Arc<impl RequestConfigProvider>,
+	library_id: Uuid,
+	manifest: &ChunkManifest,
+) -> Result<Vec<u8>, ChunkedUploadError> {
+	let mut chunks = Vec::with_capacity(manifest.chunks.len());
+
+	for info in &manifest.chunks {
+		let chunk = BASE64_STANDARD.decode(
+			message_collections::get_chunk(
+				cloud_api_config_provider.get_request_config().await,
+				library_id,
+				manifest.upload_id,
+				info.index,
+			)
+			.await?
+			.contents,
+		)?;
+
+		manifest.verify_chunk(info.index, &chunk)?;
+		chunks.push(chunk);
+	}
+
+	manifest.reassemble(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn payload() -> Vec<u8> {
+		(0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect()
+	}
+
+	#[test]
+	fn test_reassemble() {
+		let payload = payload();
+		let manifest = ChunkManifest::new(Uuid::new_v4(), &payload);
+
+		assert!(ChunkManifest::is_required(&payload));
+		assert_eq!(manifest.chunks.len(), 3);
+		assert_eq!(
+			manifest
+				.reassemble(payload.chunks(CHUNK_SIZE).map(<[u8]>::to_vec))
+				.unwrap(),
+			payload
+		);
+	}
+
+	#[test]
+	fn test_corrupted_or_missing_chunk() {
+		let payload = payload();
+		let manifest = ChunkManifest::new(Uuid::new_v4(), &payload);
+
+		let mut chunks = payload
+			.chunks(CHUNK_SIZE)
+			.map(<[u8]>::to_vec)
+			.collect::<Vec<_>>();
+		chunks[1][0] ^= 0xFF;
+		assert!(matches!(
+			manifest.reassemble(chunks.clone()),
+			Err(ChunkedUploadError::ChunkMismatch(1))
+		));
+
+		chunks[1][0] ^= 0xFF;
+		chunks.pop();
+		assert!(matches!(
+			manifest.reassemble(chunks),
+			Err(ChunkedUploadError::PayloadMismatch)
+		));
+	}
+
+	#[test]
+	fn test_upload_id_is_stable() {
+		let payload = payload();
+		let instance_uuid = Uuid::new_v4();
+
+		// retries of the same payload have to resume the same upload
+		assert_eq!(
+			ChunkManifest::new(instance_uuid, &payload).upload_id,
+			ChunkManifest::new(instance_uuid, &payload).upload_id
+		);
+		assert_ne!(
+			ChunkManifest::new(instance_uuid, &payload).upload_id,
+			ChunkManifest::new(Uuid::new_v4(), &payload).upload_id
+		);
+	}
+
+	#[test]
+	fn test_manifest_payload_round_trip() {
+		let manifest = ChunkManifest::new(Uuid::new_v4(), &payload());
+		let contents = serde_json::to_vec(&manifest.to_payload().unwrap()).unwrap();
+
+		assert_eq!(
+			ChunkManifest::from_payload(&contents).unwrap().unwrap(),
+			manifest
+		);
+		assert!(ChunkManifest::from_payload(
+			&serde_json::to_vec(&Value::String("sd-gzip-v1:AAAA".to_string())).unwrap()
+		)
+		.is_none());
+	}
+}
@@ -1,11 +1,17 @@
 use super::err_return;
 
+use sd_actors::ActorActivity;
+
 use std::sync::Arc;
 
 use tokio::sync::Notify;
 use tracing::info;
 
-pub async fn run_actor(sync: Arc<sd_core_sync::Manager>, notify: Arc<Notify>) {
+pub async fn run_actor(
+	sync: Arc<sd_core_sync::Manager>,
+	notify: Arc<Notify>,
+	activity: ActorActivity,
+) {
 	loop {
 		{
 			let mut rx = sync.ingest.req_rx.lock().await;
@@ -25,7 +31,10 @@ pub async fn run_actor(sync: Arc<sd_core_sync::Manager>, notify: Arc<Notify>) {
 					let timestamps = match req {
 						Request::FinishedIngesting => break,
 						Request::Messages { timestamps } => timestamps,
-						_ => continue,
+						Request::Ingested { models } => {
+							sync.tx.send(SyncMessage::Ingested(models)).ok();
+							continue;
+						}
 					};
 
 					let ops = err_return!(
@@ -37,6 +46,7 @@ pub async fn run_actor(sync: Arc<sd_core_sync::Manager>, notify: Arc<Notify>) {
 					);
 
 					info!("Got {} cloud ops to ingest", ops.len());
+					activity.bump();
 
 					err_return!(
 						sync.ingest
@@ -1,16 +1,32 @@
+use base64::prelude::*;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sd_actors::{Backoff, RestartPolicy};
 use sd_sync::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::{atomic, Arc};
+use specta::Type;
+use std::{
+	io::{Read, Write},
+	sync::{atomic, Arc},
+};
+use thiserror::Error;
 use tokio::sync::Notify;
+use tracing::info;
 use uuid::Uuid;
 
-use crate::{library::Library, Node};
+use crate::{api::CoreEvent, invalidate_query, library::Library, Node};
 
+pub mod chunked;
 pub mod ingest;
+pub mod queue;
 pub mod receive;
+pub mod selective;
 pub mod send;
 
+const SENDER_ACTOR: &str = "Cloud Sync Sender";
+const RECEIVER_ACTOR: &str = "Cloud Sync Receiver";
+const INGEST_ACTOR: &str = "Cloud Sync Ingest";
+
 pub async fn declare_actors(library: &Arc<Library>, node: &Arc<Node>) {
 	let ingest_notify = Arc::new(Notify::new());
 	let actors = &library.actors;
@@ -19,26 +35,35 @@ pub async fn declare_actors(library: &Arc<Library>, node: &Arc<Node>) {
 
 	actors
 		.declare(
-			"Cloud Sync Sender",
+			SENDER_ACTOR,
 			{
 				let library = library.clone();
 				let node = node.clone();
 
-				move || send::run_actor(library.id, library.sync.clone(), node.clone())
+				move |activity| {
+					send::run_actor(
+						library.clone(),
+						node.clone(),
+						node.network_usage.clone(),
+						activity,
+					)
+				}
 			},
+			// It only returns once the library is gone
+			RestartPolicy::OnFailure(Backoff::default()),
 			autorun,
 		)
 		.await;
 
 	actors
 		.declare(
-			"Cloud Sync Receiver",
+			RECEIVER_ACTOR,
 			{
 				let library = library.clone();
 				let node = node.clone();
 				let ingest_notify = ingest_notify.clone();
 
-				move || {
+				move |activity| {
 					receive::run_actor(
 						library.clone(),
 						node.libraries.clone(),
@@ -47,26 +72,122 @@ pub async fn declare_actors(library: &Arc<Library>, node: &Arc<Node>) {
 						library.instance_uuid,
 						library.sync.clone(),
 						node.clone(),
+						node.network_usage.clone(),
 						ingest_notify,
+						activity,
 					)
 				}
 			},
+			// It gives up on errors, which leaves sync broken until it's running again
+			RestartPolicy::Always(Backoff::default()),
 			autorun,
 		)
 		.await;
 
 	actors
 		.declare(
-			"Cloud Sync Ingest",
+			INGEST_ACTOR,
 			{
 				let library = library.clone();
-				move || ingest::run_actor(library.sync.clone(), ingest_notify)
+				move |activity| ingest::run_actor(library.sync.clone(), ingest_notify, activity)
 			},
+			RestartPolicy::Always(Backoff::default()),
 			autorun,
 		)
 		.await;
 }
 
+/// How far along [`reset`] is, sent as [`CoreEvent::CloudSyncReset`].
+#[derive(Serialize, Type, Debug, Clone)]
+#[serde(tag = "stage")]
+pub enum CloudSyncResetStage {
+	StoppingActors,
+	ClearingState,
+	/// The actors are running again and everything is being downloaded from the cloud. It's the
+	/// last stage when pulling, as the download is then up to the actors.
+	Pulling,
+	Done,
+	Failed {
+		error: String,
+	},
+}
+
+#[derive(Serialize, Type, Debug, Clone)]
+pub struct CloudSyncResetEvent {
+	pub library_id: Uuid,
+	pub stage: CloudSyncResetStage,
+}
+
+/// Stops the cloud sync actors, cancelling whatever they were doing, and forgets how far sync
+/// got with every other instance along with the operations waiting to be ingested or sent.
+///
+/// With `pull` the actors are started again, downloading every operation from the cloud, which
+/// can be a lot. Operations we already have are skipped when ingesting them. Otherwise the actors
+/// stay stopped until they're started again.
+pub async fn reset(
+	library: &Arc<Library>,
+	pull: bool,
+) -> Result<(), prisma_client_rust::QueryError> {
+	let emit = |stage| {
+		library.emit(CoreEvent::CloudSyncReset(CloudSyncResetEvent {
+			library_id: library.id,
+			stage,
+		}))
+	};
+
+	emit(CloudSyncResetStage::StoppingActors);
+
+	for name in [SENDER_ACTOR, RECEIVER_ACTOR, INGEST_ACTOR] {
+		library.actors.stop(name).await;
+	}
+
+	emit(CloudSyncResetStage::ClearingState);
+
+	if let Err(e) = clear_state(library).await {
+		emit(CloudSyncResetStage::Failed {
+			error: e.to_string(),
+		});
+
+		return Err(e);
+	}
+
+	info!("Reset cloud sync state of library '{}'", library.id);
+
+	invalidate_query!(library, "cloud.syncStatus");
+
+	if pull {
+		for name in [INGEST_ACTOR, RECEIVER_ACTOR, SENDER_ACTOR] {
+			library.actors.start(name).await;
+		}
+
+		emit(CloudSyncResetStage::Pulling);
+	} else {
+		emit(CloudSyncResetStage::Done);
+	}
+
+	Ok(())
+}
+
+async fn clear_state(library: &Library) -> Result<(), prisma_client_rust::QueryError> {
+	let Library { db, sync, .. } = library;
+
+	db._batch((
+		db.cloud_crdt_operation().delete_many(vec![]),
+		db.cloud_send_queue().delete_many(vec![]),
+	))
+	.await?;
+
+	// Our own operations are never ingested, so only the other instances start over
+	sync.timestamps
+		.write()
+		.await
+		.iter_mut()
+		.filter(|(instance_uuid, _)| **instance_uuid != library.instance_uuid)
+		.for_each(|(_, timestamp)| *timestamp = NTP64(0));
+
+	Ok(())
+}
+
 macro_rules! err_break {
 	($e:expr) => {
 		match $e {
@@ -98,13 +219,28 @@ pub type CompressedCRDTOperationsForModel = Vec<(Value, Vec<CompressedCRDTOperat
 #[derive(Serialize, Deserialize)]
 pub struct CompressedCRDTOperations(Vec<(Uuid, Vec<(String, CompressedCRDTOperationsForModel)>)>);
 
+/// How many operations [`CompressedCRDTOperations::new_with_progress`] goes through between
+/// each report.
+const PROGRESS_INTERVAL: usize = 10_000;
+
 impl CompressedCRDTOperations {
 	pub fn new(ops: Vec<CRDTOperation>) -> Self {
+		Self::new_with_progress(ops, |_, _| {})
+	}
+
+	/// Same as [`Self::new`], calling `progress` with how many of the operations were compressed
+	/// and how many there are in total, every [`PROGRESS_INTERVAL`] operations and once done.
+	pub fn new_with_progress(
+		ops: Vec<CRDTOperation>,
+		mut progress: impl FnMut(usize, usize),
+	) -> Self {
+		let total = ops.len();
 		let mut compressed = vec![];
 
 		let mut ops_iter = ops.into_iter();
 
 		let Some(first) = ops_iter.next() else {
+			progress(0, 0);
 			return Self(vec![]);
 		};
 
@@ -117,7 +253,11 @@ impl CompressedCRDTOperations {
 		let mut record_id = first.record_id.clone();
 		let mut record = vec![first.into()];
 
-		for op in ops_iter {
+		for (processed, op) in (1..).zip(ops_iter) {
+			if processed % PROGRESS_INTERVAL == 0 {
+				progress(processed, total);
+			}
+
 			if instance_id != op.instance {
 				model.push((
 					std::mem::replace(&mut record_id, op.record_id.clone()),
@@ -154,6 +294,8 @@ impl CompressedCRDTOperations {
 		instance.push((model_str, model));
 		compressed.push((instance_id, instance));
 
+		progress(total, total);
+
 		Self(compressed)
 	}
 
@@ -179,6 +321,66 @@ impl CompressedCRDTOperations {
 
 		ops
 	}
+
+	/// Serialize into the value uploaded to the cloud.
+	///
+	/// The JSON is gzipped and base64 encoded into a string prefixed with [`PAYLOAD_GZIP_V1`] so it can be told apart from the uncompressed payloads sent by older clients.
+	pub fn to_payload(&self) -> Result<Value, PayloadError> {
+		let json = serde_json::to_vec(self)?;
+
+		let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+		encoder.write_all(&json)?;
+		let compressed = encoder.finish()?;
+
+		tracing::debug!(
+			"Compressed cloud sync payload from {} to {} bytes ({:.1}%)",
+			json.len(),
+			compressed.len(),
+			(compressed.len() as f64 / json.len() as f64) * 100.0
+		);
+
+		Ok(Value::String(format!(
+			"{PAYLOAD_GZIP_V1}{}",
+			BASE64_STANDARD.encode(compressed)
+		)))
+	}
+
+	/// Deserialize the contents of a message collection downloaded from the cloud.
+	/// This accepts both compressed payloads and the uncompressed payloads sent by older clients.
+	pub fn from_payload(contents: &[u8]) -> Result<Self, PayloadError> {
+		let value = serde_json::from_slice::<Value>(contents)?;
+
+		let Value::String(payload) = value else {
+			return Ok(serde_json::from_value(value)?);
+		};
+
+		let Some(compressed) = payload.strip_prefix(PAYLOAD_GZIP_V1) else {
+			return Err(PayloadError::UnknownFormat(
+				payload.chars().take(PAYLOAD_GZIP_V1.len()).collect(),
+			));
+		};
+
+		let mut json = Vec::new();
+		GzDecoder::new(BASE64_STANDARD.decode(compressed)?.as_slice()).read_to_end(&mut json)?;
+
+		Ok(serde_json::from_slice(&json)?)
+	}
+}
+
+/// The prefix of a gzip compressed cloud sync payload.
+/// The version should be bumped if the format of the payload ever changes.
+const PAYLOAD_GZIP_V1: &str = "sd-gzip-v1:";
+
+#[derive(Debug, Error)]
+pub enum PayloadError {
+	#[error("failed to (de)serialize cloud sync payload: {0}")]
+	Json(#[from] serde_json::Error),
+	#[error("failed to (de)compress cloud sync payload: {0}")]
+	Io(#[from] std::io::Error),
+	#[error("failed to decode cloud sync payload: {0}")]
+	Base64(#[from] base64::DecodeError),
+	#[error("unknown cloud sync payload format '{0}'")]
+	UnknownFormat(String),
 }
 
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone)]
@@ -197,3 +399,158 @@ impl From<CRDTOperation> for CompressedCRDTOperation {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use futures::StreamExt;
+	use serde_json::json;
+
+	fn ops() -> Vec<CRDTOperation> {
+		let instance = Uuid::new_v4();
+
+		(0..100)
+			.map(|i| CRDTOperation {
+				instance,
+				timestamp: NTP64(i),
+				id: Uuid::new_v4(),
+				model: "object".to_string(),
+				record_id: json!({ "pub_id": [i % 10] }),
+				data: CRDTOperationData::Update {
+					field: "note".to_string(),
+					value: json!("Lorem ipsum dolor sit amet"),
+				},
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_compressed_payload_round_trip() {
+		let ops = ops();
+
+		let payload = CompressedCRDTOperations::new(ops.clone())
+			.to_payload()
+			.unwrap();
+		let contents = serde_json::to_vec(&payload).unwrap();
+
+		assert!(contents.len() < serde_json::to_vec(&ops).unwrap().len());
+		assert_eq!(
+			CompressedCRDTOperations::from_payload(&contents)
+				.unwrap()
+				.into_ops(),
+			ops
+		);
+	}
+
+	#[test]
+	fn test_progress_is_reported_without_changing_the_output() {
+		let ops = std::iter::repeat_with(ops)
+			.flatten()
+			.take(PROGRESS_INTERVAL * 2 + 1)
+			.collect::<Vec<_>>();
+		let total = ops.len();
+
+		let mut reports = vec![];
+		let compressed =
+			CompressedCRDTOperations::new_with_progress(ops.clone(), |processed, total| {
+				reports.push((processed, total))
+			});
+
+		assert_eq!(
+			reports,
+			vec![
+				(PROGRESS_INTERVAL, total),
+				(PROGRESS_INTERVAL * 2, total),
+				(total, total)
+			]
+		);
+		assert_eq!(
+			serde_json::to_vec(&compressed).unwrap(),
+			serde_json::to_vec(&CompressedCRDTOperations::new(ops)).unwrap()
+		);
+	}
+
+	#[tokio::test]
+	async fn test_reset_forgets_other_instances_progress() {
+		use crate::util::test_utils::test_library;
+
+		use sd_prisma::prisma::instance;
+		use sd_utils::uuid_to_bytes;
+
+		let (_data_dir, node, library) = test_library("Sync").await;
+
+		let other_instance = Uuid::new_v4();
+		{
+			let mut timestamps = library.sync.timestamps.write().await;
+			timestamps.insert(library.instance_uuid, NTP64(7));
+			timestamps.insert(other_instance, NTP64(5));
+		}
+
+		library
+			.db
+			.cloud_send_queue()
+			.create(
+				0,
+				7,
+				1,
+				vec![],
+				chrono::Utc::now().into(),
+				instance::pub_id::equals(uuid_to_bytes(library.instance_uuid)),
+				vec![],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		let mut events = node.subscribe(
+			crate::node::EventFilter::categories([crate::node::EventCategory::CloudSync])
+				.library(library.id),
+		);
+
+		reset(&library, false).await.unwrap();
+
+		let timestamps = library.sync.timestamps.read().await.clone();
+		assert_eq!(timestamps[&library.instance_uuid], NTP64(7));
+		assert_eq!(timestamps[&other_instance], NTP64(0));
+		assert_eq!(
+			library
+				.db
+				.cloud_send_queue()
+				.count(vec![])
+				.exec()
+				.await
+				.unwrap(),
+			0
+		);
+
+		let mut stages = vec![];
+		while let Ok(Some(crate::node::BusEvent::Event(CoreEvent::CloudSyncReset(event)))) =
+			tokio::time::timeout(std::time::Duration::from_millis(100), events.next()).await
+		{
+			stages.push(event.stage);
+		}
+		assert!(matches!(
+			stages.as_slice(),
+			[
+				CloudSyncResetStage::StoppingActors,
+				CloudSyncResetStage::ClearingState,
+				CloudSyncResetStage::Done
+			]
+		));
+	}
+
+	#[test]
+	fn test_uncompressed_payload_is_still_accepted() {
+		let ops = ops();
+
+		let contents = serde_json::to_vec(&CompressedCRDTOperations::new(ops.clone())).unwrap();
+
+		assert_eq!(
+			CompressedCRDTOperations::from_payload(&contents)
+				.unwrap()
+				.into_ops(),
+			ops
+		);
+	}
+}
@@ -0,0 +1,329 @@
+use crate::{
+	invalidate_query,
+	library::Library,
+	node::{NetworkUsage, NetworkUsageCategory},
+};
+
+use super::{
+	chunked::{self, ChunkManifest, ChunkedUploadError},
+	selective::ExcludedLocations,
+	CompressedCRDTOperations, PayloadError,
+};
+
+use sd_cloud_api::RequestConfigProvider;
+use sd_core_sync::NTP64;
+use sd_prisma::prisma::{cloud_send_queue, instance, PrismaClient, SortOrder};
+use sd_utils::uuid_to_bytes;
+
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use specta::Type;
+use thiserror::Error;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// How many operations go in each queued batch, which is also how many are sent at once.
+const BATCH_SIZE: u32 = 1000;
+
+#[derive(Debug, Error)]
+pub enum QueueError {
+	#[error("database error: {0}")]
+	Database(#[from] prisma_client_rust::QueryError),
+	#[error("cloud api request failed: {0}")]
+	Api(#[from] sd_cloud_api::Error),
+	#[error(transparent)]
+	Payload(#[from] PayloadError),
+	#[error(transparent)]
+	ChunkedUpload(#[from] ChunkedUploadError),
+	#[error("failed to (de)serialize queued operations: {0}")]
+	Json(#[from] serde_json::Error),
+}
+
+/// The local operations which haven't been sent to the cloud yet.
+#[derive(Serialize, Type, Debug, Default)]
+pub struct CloudSyncStatus {
+	pub queued_operations: u32,
+	pub queued_batches: u32,
+	pub oldest_queued_at: Option<DateTime<Utc>>,
+}
+
+pub async fn status(db: &PrismaClient) -> Result<CloudSyncStatus, prisma_client_rust::QueryError> {
+	let batches = db
+		.cloud_send_queue()
+		.find_many(vec![cloud_send_queue::date_sent::equals(None)])
+		.order_by(cloud_send_queue::id::order(SortOrder::Asc))
+		.select(cloud_send_queue::select!({ ops_count date_created }))
+		.exec()
+		.await?;
+
+	Ok(CloudSyncStatus {
+		queued_operations: batches.iter().map(|batch| batch.ops_count as u32).sum(),
+		queued_batches: batches.len() as u32,
+		oldest_queued_at: batches.first().map(|batch| batch.date_created.into()),
+	})
+}
+
+/// Queues the operations of each instance which came after the last queued ones, or after
+/// `cloud_timestamps` when the cloud already has more of them.
+///
+/// Instances which were never queued are skipped until we know the cloud's timestamp for them.
+///
+/// Operations on locations excluded from sync are left out, see [`ExcludedLocations`].
+pub async fn enqueue(
+	library: &Library,
+	cloud_timestamps: &HashMap<Uuid, NTP64>,
+) -> Result<usize, QueueError> {
+	let Library { db, sync, .. } = library;
+
+	let instances = sync
+		.timestamps
+		.read()
+		.await
+		.keys()
+		.copied()
+		.collect::<Vec<_>>();
+
+	let queued_until = db
+		._batch(
+			instances
+				.iter()
+				.map(|instance_uuid| {
+					db.cloud_send_queue()
+						.find_first(vec![cloud_send_queue::instance::is(vec![
+							instance::pub_id::equals(uuid_to_bytes(*instance_uuid)),
+						])])
+						.order_by(cloud_send_queue::end_time::order(SortOrder::Desc))
+						.select(cloud_send_queue::select!({ end_time }))
+				})
+				.collect::<Vec<_>>(),
+		)
+		.await?;
+
+	let excluded_locations = ExcludedLocations::load(db).await?;
+
+	let mut queued_count = 0;
+
+	for (instance_uuid, queued_until) in instances.into_iter().zip(queued_until) {
+		let Some(mut from) = Ord::max(
+			queued_until.map(|batch| NTP64(batch.end_time as u64)),
+			cloud_timestamps.get(&instance_uuid).copied(),
+		) else {
+			continue;
+		};
+
+		loop {
+			let ops = sync
+				.get_instance_ops(instance_uuid, from, BATCH_SIZE)
+				.await?;
+
+			let (Some(first), Some(last)) = (ops.first(), ops.last()) else {
+				break;
+			};
+
+			let (start_time, end_time) = (first.timestamp, last.timestamp);
+
+			let ops = excluded_locations.retain_included(db, ops).await?;
+			let ops_count = ops.len();
+
+			db.cloud_send_queue()
+				.create(
+					start_time.as_u64() as i64,
+					end_time.as_u64() as i64,
+					ops_count as i32,
+					if ops.is_empty() {
+						vec![]
+					} else {
+						serde_json::to_vec(&CompressedCRDTOperations::new(ops).to_payload()?)?
+					},
+					Utc::now().into(),
+					instance::pub_id::equals(uuid_to_bytes(instance_uuid)),
+					// Nothing is left to send, but the next batch still has to start after it
+					if ops_count == 0 {
+						vec![cloud_send_queue::date_sent::set(Some(Utc::now().into()))]
+					} else {
+						vec![]
+					},
+				)
+				.exec()
+				.await?;
+
+			queued_count += ops_count;
+			from = end_time;
+		}
+	}
+
+	if queued_count > 0 {
+		debug!("Queued {queued_count} operations to be sent to the cloud");
+		invalidate_query!(library, "cloud.syncStatus");
+	}
+
+	Ok(queued_count)
+}
+
+/// Sends the queued batches to the cloud, oldest first, queuing what's new beforehand.
+pub async fn drain(
+	library: &Library,
+	cloud_api_config_provider: &Arc<impl RequestConfigProvider>,
+	network_usage: &NetworkUsage,
+) -> Result<(), QueueError> {
+	use sd_cloud_api::library::message_collections::{do_add, request_add};
+
+	let Library { db, sync, .. } = library;
+
+	loop {
+		let instances = sync
+			.timestamps
+			.read()
+			.await
+			.keys()
+			.copied()
+			.collect::<Vec<_>>();
+
+		// obtains a lock on the timestamp collections for the instances we have
+		let req_adds = request_add(
+			cloud_api_config_provider.get_request_config().await,
+			library.id,
+			instances,
+		)
+		.await?;
+
+		// instances the cloud never got anything from don't have a timestamp yet
+		let cloud_timestamps = req_adds
+			.iter()
+			.map(|req_add| {
+				(
+					req_add.instance_uuid,
+					NTP64(
+						req_add
+							.from_time
+							.as_deref()
+							.and_then(|from_time| from_time.parse().ok())
+							.unwrap_or_default(),
+					),
+				)
+			})
+			.collect::<HashMap<_, _>>();
+
+		enqueue(library, &cloud_timestamps).await?;
+
+		let mut inputs = vec![];
+		let mut inline_bytes = 0;
+		let mut sent_ids = vec![];
+		let mut skipped_ids = vec![];
+
+		for req_add in req_adds {
+			let Some(batch) = db
+				.cloud_send_queue()
+				.find_first(vec![
+					cloud_send_queue::instance::is(vec![instance::pub_id::equals(uuid_to_bytes(
+						req_add.instance_uuid,
+					))]),
+					cloud_send_queue::date_sent::equals(None),
+				])
+				.order_by(cloud_send_queue::id::order(SortOrder::Asc))
+				.exec()
+				.await?
+			else {
+				continue;
+			};
+
+			// The cloud got it before we could tell it was sent
+			if NTP64(batch.end_time as u64)
+				<= cloud_timestamps
+					.get(&req_add.instance_uuid)
+					.copied()
+					.unwrap_or_default()
+			{
+				skipped_ids.push(batch.id);
+				continue;
+			}
+
+			let mut contents = serde_json::from_slice::<Value>(&batch.contents)?;
+
+			// receivers get the contents as JSON, so that's what has to fit in a request
+			if ChunkManifest::is_required(&batch.contents) {
+				let manifest = ChunkManifest::new(req_add.instance_uuid, &batch.contents);
+
+				chunked::upload(
+					cloud_api_config_provider,
+					library.id,
+					&manifest,
+					&batch.contents,
+				)
+				.await?;
+				network_usage
+					.record_sent(NetworkUsageCategory::CloudSync, batch.contents.len() as u64);
+
+				contents = manifest.to_payload()?;
+			} else {
+				inline_bytes += batch.contents.len() as u64;
+			}
+
+			inputs.push(do_add::Input {
+				uuid: req_add.instance_uuid,
+				key: req_add.key,
+				start_time: batch.start_time.to_string(),
+				end_time: batch.end_time.to_string(),
+				contents,
+			});
+			sent_ids.push(batch.id);
+		}
+
+		if inputs.is_empty() && skipped_ids.is_empty() {
+			return Ok(());
+		}
+
+		if !inputs.is_empty() {
+			// uses lock we acquired earlier to send the operations to the cloud
+			do_add(
+				cloud_api_config_provider.get_request_config().await,
+				library.id,
+				inputs,
+			)
+			.await?;
+			network_usage.record_sent(NetworkUsageCategory::CloudSync, inline_bytes);
+
+			info!("Sent {} queued batches to the cloud", sent_ids.len());
+		}
+
+		mark_sent(db, sent_ids.into_iter().chain(skipped_ids).collect()).await?;
+
+		invalidate_query!(library, "cloud.syncStatus");
+	}
+}
+
+/// Only the batches just sent are kept, with their contents dropped, as they're just there for
+/// [`enqueue`] to know where each instance's next batch starts.
+async fn mark_sent(
+	db: &PrismaClient,
+	ids: Vec<cloud_send_queue::id::Type>,
+) -> Result<(), prisma_client_rust::QueryError> {
+	let batches = db
+		.cloud_send_queue()
+		.find_many(vec![cloud_send_queue::id::in_vec(ids.clone())])
+		.select(cloud_send_queue::select!({ instance_id }))
+		.exec()
+		.await?;
+
+	db._batch((
+		db.cloud_send_queue().delete_many(vec![
+			cloud_send_queue::instance_id::in_vec(
+				batches.into_iter().map(|batch| batch.instance_id).collect(),
+			),
+			cloud_send_queue::date_sent::not(None),
+		]),
+		db.cloud_send_queue().update_many(
+			vec![cloud_send_queue::id::in_vec(ids)],
+			vec![
+				cloud_send_queue::date_sent::set(Some(Utc::now().into())),
+				cloud_send_queue::contents::set(vec![]),
+			],
+		),
+	))
+	.await?;
+
+	Ok(())
+}
@@ -1,6 +1,13 @@
-use crate::library::{Libraries, Library};
+use crate::{
+	library::{Libraries, Library},
+	node::{NetworkUsage, NetworkUsageCategory},
+};
 
-use super::{err_break, err_return, CompressedCRDTOperations};
+use super::{
+	chunked::{self, ChunkManifest},
+	err_break, err_return, CompressedCRDTOperations,
+};
+use sd_actors::ActorActivity;
 use sd_cloud_api::RequestConfigProvider;
 use sd_core_sync::NTP64;
 use sd_p2p::spacetunnel::{IdentityOrRemoteIdentity, RemoteIdentity};
@@ -29,7 +36,9 @@ pub async fn run_actor(
 	instance_uuid: Uuid,
 	sync: Arc<sd_core_sync::Manager>,
 	cloud_api_config_provider: Arc<impl RequestConfigProvider>,
+	network_usage: Arc<NetworkUsage>,
 	ingest_notify: Arc<Notify>,
+	activity: ActorActivity,
 ) {
 	loop {
 		loop {
@@ -155,12 +164,38 @@ pub async fn run_actor(
 					e.insert(NTP64(0));
 				}
 
-				let compressed_operations: CompressedCRDTOperations =
-					err_break!(serde_json::from_slice(err_break!(
-						&BASE64_STANDARD.decode(collection.contents)
-					)));
+				network_usage.record_received(
+					NetworkUsageCategory::CloudSync,
+					collection.contents.len() as u64,
+				);
+				let contents = err_break!(BASE64_STANDARD.decode(collection.contents));
+
+				// large collections only contain the manifest of the chunks they were uploaded in
+				let contents = match ChunkManifest::from_payload(&contents) {
+					Some(manifest) => {
+						let contents = err_break!(
+							chunked::download(
+								&cloud_api_config_provider,
+								library_id,
+								&err_break!(manifest)
+							)
+							.await
+						);
+						network_usage.record_received(
+							NetworkUsageCategory::CloudSync,
+							contents.len() as u64,
+						);
+
+						contents
+					}
+					None => contents,
+				};
+
+				let compressed_operations =
+					err_break!(CompressedCRDTOperations::from_payload(&contents));
 
 				err_break!(write_cloud_ops_to_db(compressed_operations.into_ops(), &db).await);
+				activity.bump();
 
 				let collection_timestamp =
 					NTP64(collection.end_time.parse().expect("unable to parse time"));
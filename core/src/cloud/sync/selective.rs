@@ -0,0 +1,285 @@
+//! Locations with `sync_enabled` turned off keep their records to themselves, so the operations
+//! on them and on their file paths are left out of what's sent to the cloud.
+
+use crate::library::Library;
+
+use sd_prisma::{
+	prisma::{file_path, location, PrismaClient},
+	prisma_sync,
+};
+use sd_sync::CRDTOperation;
+
+use std::collections::HashSet;
+
+use serde_json::json;
+use tracing::debug;
+
+/// How many records have their state written again at once.
+const BACKFILL_BATCH_SIZE: usize = 1000;
+
+/// The locations whose records aren't sent to the cloud.
+#[derive(Debug, Default)]
+pub struct ExcludedLocations {
+	ids: Vec<location::id::Type>,
+	pub_ids: HashSet<Vec<u8>>,
+}
+
+impl ExcludedLocations {
+	pub async fn load(db: &PrismaClient) -> Result<Self, prisma_client_rust::QueryError> {
+		let locations = db
+			.location()
+			.find_many(vec![location::sync_enabled::equals(Some(false))])
+			.select(location::select!({ id pub_id }))
+			.exec()
+			.await?;
+
+		Ok(Self {
+			ids: locations.iter().map(|location| location.id).collect(),
+			pub_ids: locations
+				.into_iter()
+				.map(|location| location.pub_id)
+				.collect(),
+		})
+	}
+
+	/// Leaves out the operations on the excluded locations and on the file paths inside them.
+	///
+	/// Operations on file paths which don't exist anymore are kept, as there's no telling where
+	/// they were.
+	pub async fn retain_included(
+		&self,
+		db: &PrismaClient,
+		mut ops: Vec<CRDTOperation>,
+	) -> Result<Vec<CRDTOperation>, prisma_client_rust::QueryError> {
+		if self.ids.is_empty() {
+			return Ok(ops);
+		}
+
+		let file_path_pub_ids = ops
+			.iter()
+			.filter(|op| op.model == file_path::NAME)
+			.filter_map(|op| {
+				serde_json::from_value::<prisma_sync::file_path::SyncId>(op.record_id.clone()).ok()
+			})
+			.map(|sync_id| sync_id.pub_id)
+			.collect::<HashSet<_>>();
+
+		let excluded_file_paths = if file_path_pub_ids.is_empty() {
+			HashSet::new()
+		} else {
+			db.file_path()
+				.find_many(vec![
+					file_path::pub_id::in_vec(file_path_pub_ids.into_iter().collect()),
+					file_path::location::is(vec![location::id::in_vec(self.ids.clone())]),
+				])
+				.select(file_path::select!({ pub_id }))
+				.exec()
+				.await?
+				.into_iter()
+				.map(|file_path| file_path.pub_id)
+				.collect::<HashSet<_>>()
+		};
+
+		ops.retain(|op| {
+			let excluded = match op.model.as_str() {
+				file_path::NAME => &excluded_file_paths,
+				location::NAME => &self.pub_ids,
+				_ => return true,
+			};
+
+			!op.record_id
+				.get("pub_id")
+				.and_then(|pub_id| serde_json::from_value::<Vec<u8>>(pub_id.clone()).ok())
+				.is_some_and(|pub_id| excluded.contains(&pub_id))
+		});
+
+		Ok(ops)
+	}
+}
+
+/// Writes the current state of a location and its file paths as new operations, as the ones left
+/// out while it was excluded are behind what was already sent to the cloud.
+///
+/// Only the current state is written again, instead of reissuing every past operation, so other
+/// instances don't get values which were already overwritten or operations from other instances
+/// attributed to this one.
+pub async fn backfill_location(
+	library: &Library,
+	location_id: location::id::Type,
+	location_pub_id: Vec<u8>,
+) -> Result<usize, prisma_client_rust::QueryError> {
+	let Library { db, sync, .. } = library;
+
+	let mut written = sync
+		.snapshot_records(
+			sync.get_record_ops(
+				location::NAME,
+				&[json!(prisma_sync::location::SyncId {
+					pub_id: location_pub_id,
+				})],
+			)
+			.await?,
+		)
+		.await?;
+
+	let file_path_pub_ids = db
+		.file_path()
+		.find_many(vec![file_path::location_id::equals(Some(location_id))])
+		.select(file_path::select!({ pub_id }))
+		.exec()
+		.await?;
+
+	for chunk in file_path_pub_ids.chunks(BACKFILL_BATCH_SIZE) {
+		let record_ids = chunk
+			.iter()
+			.map(|file_path| {
+				json!(prisma_sync::file_path::SyncId {
+					pub_id: file_path.pub_id.clone(),
+				})
+			})
+			.collect::<Vec<_>>();
+
+		written += sync
+			.snapshot_records(sync.get_record_ops(file_path::NAME, &record_ids).await?)
+			.await?;
+	}
+
+	debug!("Wrote {written} operations of <location_id={location_id}> to be synced");
+
+	Ok(written)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::{
+		location::{set_location_favorite, LocationCreateArgs},
+		util::test_utils::test_library,
+	};
+
+	use sd_sync::{CRDTOperationData, NTP64};
+
+	use tempfile::tempdir;
+
+	async fn create_location(
+		node: &crate::Node,
+		library: &std::sync::Arc<Library>,
+		path: &std::path::Path,
+	) -> location::Data {
+		let location = LocationCreateArgs {
+			path: path.to_path_buf(),
+			dry_run: false,
+			indexer_rules_ids: vec![],
+			interactive: false,
+			force: false,
+		}
+		.create(node, library)
+		.await
+		.unwrap()
+		.unwrap();
+
+		library
+			.db
+			.location()
+			.find_unique(location::id::equals(location.id))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn test_excluded_location_ops_are_left_out() {
+		let (_data_dir, node, library) = test_library("Selective").await;
+		let root = tempdir().unwrap();
+		let location = create_location(&node, &library, root.path()).await;
+		set_location_favorite(&library, location.id, true)
+			.await
+			.unwrap();
+
+		let ops = library
+			.sync
+			.get_instance_ops(library.instance_uuid, NTP64(0), 1000)
+			.await
+			.unwrap();
+		assert!(ops.iter().any(|op| op.model == location::NAME));
+
+		library
+			.db
+			.location()
+			.update(
+				location::id::equals(location.id),
+				vec![location::sync_enabled::set(Some(false))],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		let included = ExcludedLocations::load(&library.db)
+			.await
+			.unwrap()
+			.retain_included(&library.db, ops.clone())
+			.await
+			.unwrap();
+		assert_eq!(
+			included,
+			ops.into_iter()
+				.filter(|op| op.model != location::NAME)
+				.collect::<Vec<_>>()
+		);
+	}
+
+	#[tokio::test]
+	async fn test_backfill_location_writes_current_state() {
+		let (_data_dir, node, library) = test_library("Selective").await;
+		let root = tempdir().unwrap();
+		let location = create_location(&node, &library, root.path()).await;
+		set_location_favorite(&library, location.id, true)
+			.await
+			.unwrap();
+		set_location_favorite(&library, location.id, false)
+			.await
+			.unwrap();
+
+		let last_timestamp = library
+			.sync
+			.get_instance_ops(library.instance_uuid, NTP64(0), 1000)
+			.await
+			.unwrap()
+			.last()
+			.unwrap()
+			.timestamp;
+
+		let written = backfill_location(&library, location.id, location.pub_id)
+			.await
+			.unwrap();
+
+		let ops = library
+			.sync
+			.get_instance_ops(library.instance_uuid, last_timestamp, 1000)
+			.await
+			.unwrap();
+		assert_eq!(ops.len(), written);
+
+		let location_ops = ops
+			.iter()
+			.filter(|op| op.model == location::NAME)
+			.map(|op| &op.data)
+			.collect::<Vec<_>>();
+		assert_eq!(location_ops[0], &CRDTOperationData::Create);
+
+		// Once per field, with the latest value only
+		let favorites = location_ops
+			.iter()
+			.filter_map(|data| match data {
+				CRDTOperationData::Update { field, value } if field == location::favorite::NAME => {
+					Some(value.clone())
+				}
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(favorites, vec![json!(false)]);
+	}
+}
@@ -1,99 +1,50 @@
-use super::CompressedCRDTOperations;
+use crate::{library::Library, node::NetworkUsage};
 
+use super::queue;
+
+use sd_actors::ActorActivity;
 use sd_cloud_api::RequestConfigProvider;
-use sd_core_sync::{GetOpsArgs, SyncMessage, NTP64};
-use uuid::Uuid;
+use sd_core_sync::SyncMessage;
 
 use std::{sync::Arc, time::Duration};
 
-use tokio::time::sleep;
+use tokio::{
+	sync::broadcast::error::RecvError,
+	time::{sleep, sleep_until, timeout, Instant},
+};
+use tracing::{error, info};
 
-use super::err_break;
+/// How long the cloud api's origin has to answer before we consider ourselves offline.
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(5);
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
 
 pub async fn run_actor(
-	library_id: Uuid,
-	sync: Arc<sd_core_sync::Manager>,
+	library: Arc<Library>,
 	cloud_api_config_provider: Arc<impl RequestConfigProvider>,
+	network_usage: Arc<NetworkUsage>,
+	activity: ActorActivity,
 ) {
-	loop {
-		loop {
-			// all available instances will have a default timestamp from create_instance
-			let instances = sync
-				.timestamps
-				.read()
-				.await
-				.keys()
-				.cloned()
-				.collect::<Vec<_>>();
-
-			// obtains a lock on the timestamp collections for the instances we have
-			let req_adds = err_break!(
-				sd_cloud_api::library::message_collections::request_add(
-					cloud_api_config_provider.get_request_config().await,
-					library_id,
-					instances,
-				)
-				.await
-			);
-
-			let mut instances = vec![];
-
-			use sd_cloud_api::library::message_collections::do_add;
-
-			// gets new operations for each instance to send to cloud
-			for req_add in req_adds {
-				let ops = err_break!(
-					sync.get_ops(GetOpsArgs {
-						count: 1000,
-						clocks: vec![(
-							req_add.instance_uuid,
-							NTP64(
-								req_add
-									.from_time
-									.unwrap_or_else(|| "0".to_string())
-									.parse()
-									.expect("couldn't parse ntp64 value"),
-							),
-						)],
-					})
-					.await
-				);
-
-				if ops.is_empty() {
-					continue;
-				}
-
-				let start_time = ops[0].timestamp.0.to_string();
-				let end_time = ops[ops.len() - 1].timestamp.0.to_string();
-
-				instances.push(do_add::Input {
-					uuid: req_add.instance_uuid,
-					key: req_add.key,
-					start_time,
-					end_time,
-					contents: serde_json::to_value(CompressedCRDTOperations::new(ops))
-						.expect("CompressedCRDTOperation should serialize!"),
-				})
-			}
+	let mut retry_interval = MIN_RETRY_INTERVAL;
 
-			if instances.is_empty() {
-				break;
-			}
+	loop {
+		let sent = if !network_usage.is_metered() && is_reachable(&cloud_api_config_provider).await
+		{
+			activity.bump();
 
-			// uses lock we acquired earlier to send the operations to the cloud
-			err_break!(
-				do_add(
-					cloud_api_config_provider.get_request_config().await,
-					library_id,
-					instances,
-				)
+			queue::drain(&library, &cloud_api_config_provider, &network_usage)
 				.await
-			);
-		}
+				.map_err(|e| error!("Failed to send operations to the cloud: {e}"))
+				.is_ok()
+		} else {
+			false
+		};
 
-		{
-			// recreate subscription each time so that existing messages are dropped
-			let mut rx = sync.subscribe();
+		// recreate subscription each time so that existing messages are dropped
+		let mut rx = library.sync.subscribe();
+
+		if sent {
+			retry_interval = MIN_RETRY_INTERVAL;
 
 			// wait until Created message comes in
 			loop {
@@ -101,8 +52,62 @@ pub async fn run_actor(
 					break;
 				};
 			}
+
+			sleep(Duration::from_millis(1000)).await;
+			continue;
 		}
 
-		sleep(Duration::from_millis(1000)).await;
+		// Offline, metered, or the cloud failed us, so what's created meanwhile is queued until it
+		// can be sent again
+		let mut created = true;
+
+		loop {
+			let retry_at = Instant::now() + retry_interval;
+
+			loop {
+				tokio::select! {
+					msg = rx.recv() => match msg {
+						Ok(SyncMessage::Created) | Err(RecvError::Lagged(_)) => created = true,
+						Ok(_) => {}
+						Err(RecvError::Closed) => return,
+					},
+					_ = sleep_until(retry_at) => break,
+				}
+			}
+
+			if created {
+				created = false;
+				activity.bump();
+
+				if let Err(e) = queue::enqueue(&library, &Default::default()).await {
+					error!("Failed to queue operations for the cloud: {e}");
+				}
+			}
+
+			retry_interval = (retry_interval * 2).min(MAX_RETRY_INTERVAL);
+
+			if network_usage.is_metered() {
+				continue;
+			}
+
+			if is_reachable(&cloud_api_config_provider).await {
+				info!("Cloud is reachable, sending queued operations");
+				break;
+			}
+		}
 	}
 }
+
+/// Any answer from the cloud api's origin means we're online, even if it's an error.
+async fn is_reachable(cloud_api_config_provider: &Arc<impl RequestConfigProvider>) -> bool {
+	let config = cloud_api_config_provider.get_request_config().await;
+
+	matches!(
+		timeout(
+			REACHABILITY_TIMEOUT,
+			config.client.head(&config.api_url).send()
+		)
+		.await,
+		Ok(Ok(_))
+	)
+}
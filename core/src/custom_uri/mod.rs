@@ -1,7 +1,8 @@
 use crate::{
 	api::{utils::InvalidateOperationEvent, CoreEvent},
 	library::Library,
-	object::media::thumbnail::WEBP_EXTENSION,
+	node::{EventCategory, EventFilter, NetworkUsageCategory},
+	object::media::thumbnail::ThumbnailFormat,
 	p2p::operations,
 	util::InfallibleResponse,
 	Node,
@@ -21,6 +22,7 @@ use std::{
 	ffi::OsStr,
 	fmt::Debug,
 	fs::Metadata,
+	iter,
 	path::{Path, PathBuf},
 	str::FromStr,
 	sync::{atomic::Ordering, Arc},
@@ -36,6 +38,7 @@ use axum::{
 	Router,
 };
 use bytes::Bytes;
+use futures::StreamExt;
 use mini_moka::sync::Cache;
 use tokio::{
 	fs::{self, File},
@@ -162,28 +165,40 @@ pub fn router(node: Arc<Node>) -> Router<()> {
 					let path = thumbnail_path.join(path);
 
 					// Prevent directory traversal attacks (Eg. requesting `../../../etc/passwd`)
-					// For now we only support `webp` thumbnails.
-					(path.starts_with(&thumbnail_path)
-						&& path.extension() == Some(WEBP_EXTENSION.as_ref()))
-					.then_some(())
-					.ok_or_else(|| not_found(()))?;
+					let requested_format = path
+						.starts_with(&thumbnail_path)
+						.then(|| path.extension()?.to_str())
+						.flatten()
+						.and_then(ThumbnailFormat::from_extension)
+						.ok_or_else(|| not_found(()))?;
 
-					let file = File::open(&path).await.map_err(|err| {
-						InfallibleResponse::builder()
-							.status(if err.kind() == io::ErrorKind::NotFound {
-								StatusCode::NOT_FOUND
-							} else {
-								StatusCode::INTERNAL_SERVER_ERROR
-							})
-							.body(body::boxed(Full::from("")))
-					})?;
+					// Thumbnails generated before the preferred format changed stay in the old one
+					let mut file = None;
+					for format in iter::once(requested_format).chain(
+						ThumbnailFormat::ALL
+							.into_iter()
+							.filter(|format| *format != requested_format),
+					) {
+						match File::open(path.with_extension(format.extension())).await {
+							Ok(found) => {
+								file = Some((found, format));
+								break;
+							}
+							Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+							Err(err) => return Err(internal_server_error(err)),
+						}
+					}
+
+					let (file, format) = file.ok_or_else(|| not_found(()))?;
 					let metadata = file.metadata().await;
 					serve_file(
 						file,
 						metadata,
 						request.into_parts().0,
-						InfallibleResponse::builder()
-							.header("Content-Type", HeaderValue::from_static("image/webp")),
+						InfallibleResponse::builder().header(
+							"Content-Type",
+							HeaderValue::from_static(format.content_type()),
+						),
 					)
 					.await
 				},
@@ -238,7 +253,9 @@ pub fn router(node: Arc<Node>) -> Router<()> {
 							serve_file(file, Ok(metadata), request.into_parts().0, resp).await
 						}
 						ServeFrom::Remote(identity) => {
-							if !state.node.files_over_p2p_flag.load(Ordering::Relaxed) {
+							if !state.node.files_over_p2p_flag.load(Ordering::Relaxed)
+								|| state.node.network_usage.is_metered()
+							{
 								return Ok(not_found(()));
 							}
 
@@ -253,6 +270,10 @@ pub fn router(node: Arc<Node>) -> Router<()> {
 												"Error connecting to {identity}: {err:?}"
 											))
 										})?;
+									let stream = state
+										.node
+										.network_usage
+										.count(stream, NetworkUsageCategory::FilesOverP2P);
 
 									let (tx, mut rx) =
 										tokio::sync::mpsc::channel::<io::Result<Bytes>>(150);
@@ -264,6 +285,7 @@ pub fn router(node: Arc<Node>) -> Router<()> {
 											file_path_pub_id,
 											Range::Full,
 											MpscToAsyncWrite::new(PollSender::new(tx)),
+											|_| {},
 										)
 										.await
 										else {
@@ -324,15 +346,19 @@ pub fn router(node: Arc<Node>) -> Router<()> {
 				},
 			),
 		)
-		.route_layer(middleware::from_fn(cors_middleware))
+		.route_layer(middleware::from_fn_with_state(
+			node.clone(),
+			cors_middleware,
+		))
 		.with_state({
 			let file_metadata_cache = Arc::new(Cache::new(150));
 
 			tokio::spawn({
 				let file_metadata_cache = file_metadata_cache.clone();
-				let mut tx = node.event_bus.0.subscribe();
+				let mut events = node
+					.subscribe_resyncing(EventFilter::categories([EventCategory::Invalidation]));
 				async move {
-					while let Ok(event) = tx.recv().await {
+					while let Some(event) = events.next().await {
 						if let CoreEvent::InvalidateOperation(e) = event {
 							match e {
 								InvalidateOperationEvent::Single(event) => {
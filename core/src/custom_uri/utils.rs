@@ -1,10 +1,11 @@
-use crate::util::InfallibleResponse;
+use crate::{util::InfallibleResponse, Node};
 
-use std::{fmt::Debug, panic::Location};
+use std::{fmt::Debug, panic::Location, sync::Arc};
 
 use axum::{
 	body::{self, BoxBody},
-	http::{self, HeaderValue, Method, Request, Response, StatusCode},
+	extract::State,
+	http::{self, header, HeaderValue, Method, Request, Response, StatusCode},
 	middleware::Next,
 };
 use http_body::Full;
@@ -19,6 +20,15 @@ pub(crate) fn bad_request(err: impl Debug) -> http::Response<BoxBody> {
 		.body(body::boxed(Full::from("")))
 }
 
+#[track_caller]
+pub(crate) fn forbidden(err: impl Debug) -> http::Response<BoxBody> {
+	debug!("403: Forbidden at {}: {err:?}", Location::caller());
+
+	InfallibleResponse::builder()
+		.status(StatusCode::FORBIDDEN)
+		.body(body::boxed(Full::from("")))
+}
+
 #[track_caller]
 pub(crate) fn not_found(err: impl Debug) -> http::Response<BoxBody> {
 	debug!("404: Not Found at {}: {err:?}", Location::caller());
@@ -49,16 +59,37 @@ pub(crate) fn not_implemented(err: impl Debug) -> http::Response<BoxBody> {
 		.body(body::boxed(Full::from("")))
 }
 
-pub(crate) async fn cors_middleware<B>(req: Request<B>, next: Next<B>) -> Response<BoxBody> {
-	if req.method() == Method::OPTIONS {
-		return Response::builder()
-			.header("Access-Control-Allow-Methods", "GET, HEAD, POST, OPTIONS")
-			.header("Access-Control-Allow-Origin", "*")
-			.header("Access-Control-Allow-Headers", "*")
-			.header("Access-Control-Max-Age", "86400")
-			.status(StatusCode::OK)
-			.body(body::boxed(Full::from("")))
-			.expect("Invalid static response!");
+/// Only pages from the origins in [`NodeConfig::allowed_origins`](crate::node::config::NodeConfig::allowed_origins)
+/// can fetch from us. Requests without an `Origin` header aren't cross-origin fetches from a browser,
+/// like images on a page, so they're let through.
+pub(crate) async fn cors_middleware<B>(
+	State(node): State<Arc<Node>>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response<BoxBody> {
+	let origin = req.headers().get(header::ORIGIN).cloned();
+
+	if let Some(origin) = &origin {
+		let allowed = match origin.to_str() {
+			Ok(origin) => node.config.get().await.allows_origin(origin),
+			Err(_) => false,
+		};
+
+		if !allowed {
+			return forbidden(format!("origin {origin:?} isn't allowed"));
+		}
+
+		if req.method() == Method::OPTIONS {
+			return Response::builder()
+				.header("Access-Control-Allow-Methods", "GET, HEAD, POST, OPTIONS")
+				.header("Access-Control-Allow-Origin", origin)
+				.header("Access-Control-Allow-Headers", "*")
+				.header("Access-Control-Max-Age", "86400")
+				.header("Vary", "Origin")
+				.status(StatusCode::OK)
+				.body(body::boxed(Full::from("")))
+				.expect("Invalid static response!");
+		}
 	}
 
 	let mut response = next.run(req).await;
@@ -66,12 +97,17 @@ pub(crate) async fn cors_middleware<B>(req: Request<B>, next: Next<B>) -> Respon
 	{
 		let headers = response.headers_mut();
 
-		headers.insert("Access-Control-Allow-Origin", HeaderValue::from_static("*"));
+		if let Some(origin) = origin {
+			headers.insert("Access-Control-Allow-Origin", origin);
+
+			headers.insert(
+				"Access-Control-Allow-Headers",
+				HeaderValue::from_static("*"),
+			);
+		}
 
-		headers.insert(
-			"Access-Control-Allow-Headers",
-			HeaderValue::from_static("*"),
-		);
+		// The response depends on the origin, so caches mustn't serve it to others
+		headers.insert("Vary", HeaderValue::from_static("Origin"));
 
 		// https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Connection
 		headers.insert("Connection", HeaderValue::from_static("Keep-Alive"));
@@ -1,8 +1,18 @@
+use crate::node::{open_with::OpenWith, readiness::ReadinessTracker, DEFAULT_EVENT_BUS_CAPACITY};
+
+use std::sync::Arc;
+
 use tokio::sync::Mutex;
 
 pub struct Env {
 	pub api_url: Mutex<String>,
 	pub client_id: String,
+	/// Subscribe to this before calling `Node::new` to observe the core starting up.
+	pub readiness: ReadinessTracker,
+	/// Lets the core open files with the OS' applications, `None` on hosts which can't.
+	pub open_with: Option<Arc<dyn OpenWith>>,
+	/// How many events of each category a subscriber can fall behind, see `nodes.eventBusMetrics`.
+	pub event_bus_capacity: usize,
 }
 
 impl Env {
@@ -10,6 +20,9 @@ impl Env {
 		Self {
 			api_url: Mutex::new("https://app.spacedrive.com".to_string()),
 			client_id: client_id.to_string(),
+			readiness: ReadinessTracker::default(),
+			open_with: None,
+			event_bus_capacity: DEFAULT_EVENT_BUS_CAPACITY,
 		}
 	}
 }
@@ -36,12 +36,18 @@ pub enum JobError {
 		"Tried to resume a job that doesn't have saved state data: job <name='{1}', uuid='{0}'>"
 	)]
 	MissingJobDataState(Uuid, String),
+	#[error(
+		"tried to resume a job that can't be resumed after a restart: job <name='{1}', uuid='{0}'>"
+	)]
+	NotResumable(Uuid, String),
 	#[error("missing report field: job <uuid='{id}', name='{name}'>")]
 	MissingReport { id: Uuid, name: String },
 	#[error("missing some job data: '{value}'")]
 	MissingData { value: String },
 	#[error("invalid job status integer: {0}")]
 	InvalidJobStatusInt(i32),
+	#[error("invalid job priority integer: {0}")]
+	InvalidJobPriorityInt(i32),
 	#[error(transparent)]
 	FileIO(#[from] FileIOError),
 	#[error("Location error: {0}")]
@@ -91,6 +97,9 @@ pub enum JobManagerError {
 	#[error("job not found: {0}")]
 	NotFound(Uuid),
 
+	#[error("job isn't queued: {0}")]
+	NotQueued(Uuid),
+
 	#[error("missing-field: {0}")]
 	MissingField(#[from] MissingFieldError),
 }
@@ -113,6 +122,11 @@ impl From<JobManagerError> for rspc::Error {
 				"Job not found".to_string(),
 				value,
 			),
+			JobManagerError::NotQueued(_) => Self::with_cause(
+				rspc::ErrorCode::BadRequest,
+				"Only queued jobs can be reprioritized".to_string(),
+				value,
+			),
 			JobManagerError::MissingField(_) => Self::with_cause(
 				rspc::ErrorCode::InternalServerError,
 				"Missing field".to_string(),
@@ -1,15 +1,19 @@
 use crate::{
 	job::{worker::Worker, DynJob, Job, JobError},
 	library::Library,
-	location::indexer::indexer_job::IndexerJobInit,
+	location::indexer::{hidden_pruner::HiddenPrunerJobInit, indexer_job::IndexerJobInit},
 	object::{
+		consolidator::ObjectConsolidatorJobInit,
 		file_identifier::file_identifier_job::FileIdentifierJobInit,
 		fs::{
-			copy::FileCopierJobInit, cut::FileCutterJobInit, delete::FileDeleterJobInit,
-			erase::FileEraserJobInit,
+			copy::FileCopierJobInit, cut::FileCutterJobInit, decrypt::FileDecryptorJobInit,
+			delete::FileDeleterJobInit, encrypt::FileEncryptorJobInit, erase::FileEraserJobInit,
 		},
 		media::media_processor::MediaProcessorJobInit,
-		validation::validator_job::ObjectValidatorJobInit,
+		orphan_remover::OrphanRemoverJobInit,
+		validation::{
+			integrity_job::IntegrityVerifierJobInit, validator_job::ObjectValidatorJobInit,
+		},
 	},
 	Node,
 };
@@ -17,6 +21,7 @@ use crate::{
 use sd_prisma::prisma::job;
 
 use std::{
+	cmp::Reverse,
 	collections::{HashMap, HashSet, VecDeque},
 	sync::Arc,
 };
@@ -27,7 +32,7 @@ use tokio::sync::{mpsc, oneshot, RwLock};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use super::{JobIdentity, JobManagerError, JobReport, JobStatus, StatefulJob};
+use super::{JobIdentity, JobManagerError, JobPriority, JobReport, JobStatus, StatefulJob};
 
 const MAX_WORKERS: usize = 5;
 
@@ -191,7 +196,16 @@ impl Jobs {
 		let job = if next_job.is_some() {
 			next_job
 		} else {
-			self.job_queue.write().await.pop_front()
+			let mut job_queue = self.job_queue.write().await;
+			// `max_by_key` returns the last maximum, so we iterate in reverse to keep queue order
+			// between jobs with the same priority
+			job_queue
+				.iter()
+				.enumerate()
+				.rev()
+				.max_by_key(|(_, job)| queued_priority(job.as_ref()))
+				.map(|(idx, _)| idx)
+				.and_then(|idx| job_queue.remove(idx))
 		};
 
 		if let Some(job) = job {
@@ -262,6 +276,55 @@ impl Jobs {
 		}
 	}
 
+	/// Change the priority of a queued job, so it's run before or after the other queued jobs.
+	///
+	/// Running jobs can't be reprioritized, they keep running.
+	pub async fn reprioritize(
+		&self,
+		library: &Library,
+		job_id: Uuid,
+		priority: JobPriority,
+	) -> Result<(), JobManagerError> {
+		if let Some(report) = self
+			.job_queue
+			.write()
+			.await
+			.iter_mut()
+			.find(|job| job.id() == job_id)
+			.and_then(|job| job.report_mut().as_mut())
+		{
+			debug!("Reprioritizing job: {report} to {priority:?}");
+
+			library
+				.db
+				.job()
+				.update(
+					job::id::equals(job_id.as_bytes().to_vec()),
+					vec![job::priority::set(Some(priority as i32))],
+				)
+				.exec()
+				.await?;
+
+			report.priority = priority;
+
+			return Ok(());
+		}
+
+		// The queue lock must be released by now, as `dispatch` locks the workers before the queue
+		let is_running = self
+			.running_workers
+			.read()
+			.await
+			.values()
+			.any(|worker| worker.report().id == job_id);
+
+		Err(if is_running {
+			JobManagerError::NotQueued(job_id)
+		} else {
+			JobManagerError::NotFound(job_id)
+		})
+	}
+
 	/// This is called at startup to resume all paused jobs or jobs that were running
 	/// when the core was shut down.
 	/// - It will resume jobs that contain data and cancel jobs that do not.
@@ -278,18 +341,20 @@ impl Jobs {
 			job::status::equals(Some(JobStatus::Queued as i32)),
 		])];
 
-		let all_jobs = library
+		let mut all_jobs = library
 			.db
 			.job()
 			.find_many(find_condition)
 			.exec()
 			.await?
 			.into_iter()
-			.map(JobReport::try_from);
+			.map(JobReport::try_from)
+			.collect::<Result<Vec<_>, _>>()?;
 
-		for job in all_jobs {
-			let job = job?;
+		// Dispatching higher priority jobs first, so they get the free workers
+		all_jobs.sort_by_key(|job| Reverse(job.priority));
 
+		for job in all_jobs {
 			match initialize_resumable_job(job.clone(), None) {
 				Ok(resumable_job) => {
 					info!("Resuming job: {} with uuid {}", job.name, job.id);
@@ -380,6 +445,12 @@ mod macros {
         }};
     }
 }
+fn queued_priority(job: &dyn DynJob) -> JobPriority {
+	job.report()
+		.as_ref()
+		.map_or_else(JobPriority::default, |report| report.priority)
+}
+
 /// This function is used to initialize a  DynJob from a job report.
 fn initialize_resumable_job(
 	job_report: JobReport,
@@ -398,12 +469,18 @@ fn initialize_resumable_job(
 		jobs = [
 			MediaProcessorJobInit,
 			IndexerJobInit,
+			HiddenPrunerJobInit,
 			FileIdentifierJobInit,
 			ObjectValidatorJobInit,
+			IntegrityVerifierJobInit,
+			ObjectConsolidatorJobInit,
+			OrphanRemoverJobInit,
 			FileCutterJobInit,
 			FileCopierJobInit,
 			FileDeleterJobInit,
 			FileEraserJobInit,
+			FileEncryptorJobInit,
+			FileDecryptorJobInit,
 		]
 	)
 }
@@ -93,6 +93,8 @@ pub trait StatefulJob:
 	const NAME: &'static str;
 	const IS_BACKGROUND: bool = false;
 	const IS_BATCHED: bool = false;
+	/// Jobs which need data that isn't persisted with their state can't be resumed after a restart.
+	const IS_RESUMABLE: bool = true;
 
 	/// initialize the steps for the job
 	async fn init(
@@ -195,6 +197,11 @@ impl<SJob: StatefulJob> JobBuilder<SJob> {
 		self.report_builder = self.report_builder.with_metadata(metadata);
 		self
 	}
+
+	pub fn with_priority(mut self, priority: JobPriority) -> Self {
+		self.report_builder = self.report_builder.with_priority(priority);
+		self
+	}
 }
 
 pub struct Job<SJob: StatefulJob> {
@@ -223,6 +230,9 @@ impl<SJob: StatefulJob> Job<SJob> {
 				child_job_builder =
 					child_job_builder.with_action(format!("{parent_action}-{next_job_order}"));
 			}
+
+			// The rest of the chain is as urgent as the job starting it
+			child_job_builder = child_job_builder.with_priority(parent_report.priority);
 		}
 
 		self.next_jobs.push_back(child_job_builder.build());
@@ -235,6 +245,10 @@ impl<SJob: StatefulJob> Job<SJob> {
 		mut report: JobReport,
 		next_jobs: Option<VecDeque<Box<dyn DynJob>>>,
 	) -> Result<Box<dyn DynJob>, JobError> {
+		if !SJob::IS_RESUMABLE {
+			return Err(JobError::NotResumable(report.id, report.name));
+		}
+
 		let state = rmp_serde::from_slice::<JobState<SJob>>(
 			&report
 				.data
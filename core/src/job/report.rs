@@ -29,6 +29,7 @@ job::select!(job_without_data {
 	name
 	action
 	status
+	priority
 	parent_id
 	errors_text
 	metadata
@@ -37,9 +38,21 @@ job::select!(job_without_data {
 	date_completed
 	task_count
 	completed_task_count
+	phases
 	date_estimated_completion
 });
 
+/// A named stage of a job, like walking a location before saving what was found, with its own
+/// progress so each stage can be told apart from the others.
+#[derive(Debug, Serialize, Deserialize, Type, Clone, PartialEq, Eq)]
+pub struct JobPhase {
+	pub name: String,
+	pub task_count: i32,
+	pub completed_task_count: i32,
+	pub started_at: DateTime<Utc>,
+	pub completed_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Type, Clone)]
 pub struct JobReport {
 	pub id: Uuid,
@@ -59,8 +72,11 @@ pub struct JobReport {
 	pub parent_id: Option<Uuid>,
 
 	pub status: JobStatus,
+	pub priority: JobPriority,
 	pub task_count: i32,
 	pub completed_task_count: i32,
+	/// Every phase the job went through so far, in order, the last one being the current phase.
+	pub phases: Vec<JobPhase>,
 
 	pub phase: String,
 	pub message: String,
@@ -82,6 +98,8 @@ impl TryFrom<job::Data> for JobReport {
 	type Error = MissingFieldError;
 
 	fn try_from(data: job::Data) -> Result<Self, Self::Error> {
+		let phases = phases_from_db(data.phases);
+
 		Ok(Self {
 			id: Uuid::from_slice(&data.id).expect("corrupted database"),
 			name: maybe_missing(data.name, "job.name")?,
@@ -105,9 +123,14 @@ impl TryFrom<job::Data> for JobReport {
 				.map(|id| Uuid::from_slice(&id).expect("corrupted database")),
 			status: JobStatus::try_from(maybe_missing(data.status, "job.status")?)
 				.expect("corrupted database"),
+			priority: data
+				.priority
+				.and_then(|priority| JobPriority::try_from(priority).ok())
+				.unwrap_or_default(),
 			task_count: data.task_count.unwrap_or(0),
 			completed_task_count: data.completed_task_count.unwrap_or(0),
-			phase: String::new(),
+			phase: current_phase_name(&phases),
+			phases,
 			message: String::new(),
 			estimated_completion: data
 				.date_estimated_completion
@@ -123,6 +146,8 @@ impl TryFrom<job_without_data::Data> for JobReport {
 	type Error = MissingFieldError;
 
 	fn try_from(data: job_without_data::Data) -> Result<Self, Self::Error> {
+		let phases = phases_from_db(data.phases);
+
 		Ok(Self {
 			id: Uuid::from_slice(&data.id).expect("corrupted database"),
 			name: maybe_missing(data.name, "job.name")?,
@@ -146,10 +171,14 @@ impl TryFrom<job_without_data::Data> for JobReport {
 				.map(|id| Uuid::from_slice(&id).expect("corrupted database")),
 			status: JobStatus::try_from(maybe_missing(data.status, "job.status")?)
 				.expect("corrupted database"),
+			priority: data
+				.priority
+				.and_then(|priority| JobPriority::try_from(priority).ok())
+				.unwrap_or_default(),
 			task_count: data.task_count.unwrap_or(0),
 			completed_task_count: data.completed_task_count.unwrap_or(0),
-
-			phase: String::new(),
+			phase: current_phase_name(&phases),
+			phases,
 			message: String::new(),
 			estimated_completion: data
 				.date_estimated_completion
@@ -158,6 +187,26 @@ impl TryFrom<job_without_data::Data> for JobReport {
 	}
 }
 
+fn phases_from_db(phases: Option<Vec<u8>>) -> Vec<JobPhase> {
+	phases
+		.map(|phases| {
+			serde_json::from_slice(&phases).unwrap_or_else(|e| {
+				error!("Failed to deserialize job phases: {}", e);
+				vec![]
+			})
+		})
+		.unwrap_or_default()
+}
+
+/// A resumed job carries on with the phase it was paused in, if it hadn't finished it yet.
+fn current_phase_name(phases: &[JobPhase]) -> String {
+	phases
+		.last()
+		.filter(|phase| phase.completed_at.is_none())
+		.map(|phase| phase.name.clone())
+		.unwrap_or_default()
+}
+
 impl JobReport {
 	pub fn new(uuid: Uuid, name: String) -> Self {
 		Self {
@@ -168,12 +217,14 @@ impl JobReport {
 			started_at: None,
 			completed_at: None,
 			status: JobStatus::Queued,
+			priority: JobPriority::default(),
 			errors_text: vec![],
 			task_count: 0,
 			data: None,
 			metadata: None,
 			parent_id: None,
 			completed_task_count: 0,
+			phases: vec![],
 			phase: String::new(),
 			message: String::new(),
 			estimated_completion: Utc::now(),
@@ -200,6 +251,40 @@ impl JobReport {
 		(action_name, Some(group_key))
 	}
 
+	/// Completes the current phase and starts the named one, unless the job is already in it, as
+	/// happens when a resumed job announces the phase it was paused in again.
+	pub fn start_phase(&mut self, name: String) {
+		if self.phase == name {
+			return;
+		}
+
+		let now = Utc::now();
+		self.complete_phase(now);
+
+		self.phases.push(JobPhase {
+			name: name.clone(),
+			task_count: 0,
+			completed_task_count: 0,
+			started_at: now,
+			completed_at: None,
+		});
+		self.phase = name;
+	}
+
+	/// Completes the current phase, if there is one running.
+	pub fn complete_phase(&mut self, completed_at: DateTime<Utc>) {
+		if let Some(phase) = self.current_phase_mut() {
+			phase.completed_at = Some(completed_at);
+		}
+		self.phase.clear();
+	}
+
+	pub fn current_phase_mut(&mut self) -> Option<&mut JobPhase> {
+		self.phases
+			.last_mut()
+			.filter(|phase| phase.completed_at.is_none())
+	}
+
 	pub async fn create(&mut self, library: &Library) -> Result<(), JobError> {
 		let now = Utc::now();
 
@@ -215,6 +300,7 @@ impl JobReport {
 						job::data::set(self.data.clone()),
 						job::date_created::set(Some(now.into())),
 						job::status::set(Some(self.status as i32)),
+						job::priority::set(Some(self.priority as i32)),
 						job::date_started::set(self.started_at.map(|d| d.into())),
 						job::task_count::set(Some(1)),
 						job::completed_task_count::set(Some(0)),
@@ -248,6 +334,7 @@ impl JobReport {
 					job::metadata::set(serde_json::to_vec(&self.metadata).ok()),
 					job::task_count::set(Some(self.task_count)),
 					job::completed_task_count::set(Some(self.completed_task_count)),
+					job::phases::set(serde_json::to_vec(&self.phases).ok()),
 					job::date_started::set(self.started_at.map(Into::into)),
 					job::date_completed::set(self.completed_at.map(Into::into)),
 				],
@@ -300,12 +387,41 @@ impl TryFrom<i32> for JobStatus {
 	}
 }
 
+/// Queued jobs with a higher priority are run first, jobs with the same priority run in the
+/// order they were queued.
+#[repr(i32)]
+#[derive(
+	Debug, Default, Clone, Copy, Serialize, Deserialize, Type, Eq, PartialEq, Ord, PartialOrd,
+)]
+pub enum JobPriority {
+	Low = 0,
+	#[default]
+	Normal = 1,
+	High = 2,
+}
+
+impl TryFrom<i32> for JobPriority {
+	type Error = JobError;
+
+	fn try_from(value: i32) -> Result<Self, Self::Error> {
+		let p = match value {
+			0 => Self::Low,
+			1 => Self::Normal,
+			2 => Self::High,
+			_ => return Err(JobError::InvalidJobPriorityInt(value)),
+		};
+
+		Ok(p)
+	}
+}
+
 pub struct JobReportBuilder {
 	pub id: Uuid,
 	pub name: String,
 	pub action: Option<String>,
 	pub metadata: Option<serde_json::Value>,
 	pub parent_id: Option<Uuid>,
+	pub priority: JobPriority,
 }
 
 impl JobReportBuilder {
@@ -318,12 +434,14 @@ impl JobReportBuilder {
 			started_at: None,
 			completed_at: None,
 			status: JobStatus::Queued,
+			priority: self.priority,
 			errors_text: vec![],
 			task_count: 0,
 			data: None,
 			metadata: self.metadata,
 			parent_id: self.parent_id,
 			completed_task_count: 0,
+			phases: vec![],
 			phase: String::new(),
 			message: String::new(),
 			estimated_completion: Utc::now(),
@@ -337,6 +455,7 @@ impl JobReportBuilder {
 			action: None,
 			metadata: None,
 			parent_id: None,
+			priority: JobPriority::default(),
 		}
 	}
 
@@ -354,4 +473,42 @@ impl JobReportBuilder {
 		self.parent_id = Some(parent_id);
 		self
 	}
+
+	pub fn with_priority(mut self, priority: JobPriority) -> Self {
+		self.priority = priority;
+		self
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_phases() {
+		let mut report = JobReport::new(Uuid::new_v4(), "media_processor".to_string());
+
+		report.start_phase("media_data".to_string());
+		report.start_phase("thumbnails".to_string());
+		report.current_phase_mut().unwrap().task_count = 10;
+
+		// Announced again after resuming, it must carry on instead of starting over
+		report.start_phase("thumbnails".to_string());
+
+		assert_eq!(report.phase, "thumbnails");
+		assert_eq!(report.phases.len(), 2);
+		assert!(report.phases[0].completed_at.is_some());
+		assert_eq!(report.phases[1].task_count, 10);
+
+		// The state read back on a cold resume
+		let phases = phases_from_db(serde_json::to_vec(&report.phases).ok());
+		assert_eq!(phases, report.phases);
+		assert_eq!(current_phase_name(&phases), "thumbnails");
+
+		report.complete_phase(Utc::now());
+		assert!(report.phase.is_empty());
+		assert!(report.current_phase_mut().is_none());
+		assert_eq!(current_phase_name(&report.phases), "");
+	}
 }
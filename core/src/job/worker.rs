@@ -41,6 +41,7 @@ pub struct JobProgressEvent {
 	pub library_id: Uuid,
 	pub task_count: i32,
 	pub completed_task_count: i32,
+	/// Name of the phase the job is in, empty if it doesn't report phases.
 	pub phase: String,
 	pub message: String,
 	pub estimated_completion: DateTime<Utc>,
@@ -280,9 +281,15 @@ impl Worker {
 			match update {
 				JobReportUpdate::TaskCount(task_count) => {
 					report.task_count = task_count as i32;
+					if let Some(phase) = report.current_phase_mut() {
+						phase.task_count = task_count as i32;
+					}
 				}
 				JobReportUpdate::CompletedTaskCount(completed_task_count) => {
 					report.completed_task_count = completed_task_count as i32;
+					if let Some(phase) = report.current_phase_mut() {
+						phase.completed_task_count = completed_task_count as i32;
+					}
 				}
 
 				JobReportUpdate::Message(message) => {
@@ -295,7 +302,7 @@ impl Worker {
 						report.id,
 						report.phase
 					);
-					report.phase = phase;
+					report.start_phase(phase);
 				}
 			}
 		}
@@ -322,6 +329,8 @@ impl Worker {
 				old.completed_task_count = report.completed_task_count;
 				old.estimated_completion = report.estimated_completion;
 				old.message = report.message.clone();
+				old.phase = report.phase.clone();
+				old.phases = report.phases.clone();
 			});
 			*last_report_watch_update = Instant::now();
 		}
@@ -525,7 +534,9 @@ impl Worker {
 					(Some(current_metadata), None) => Some(current_metadata),
 					_ => None,
 				};
-				report.completed_at = Some(Utc::now());
+				let now = Utc::now();
+				report.complete_phase(now);
+				report.completed_at = Some(now);
 				if let Err(e) = report.update(library).await {
 					error!("failed to update job report: {:#?}", e);
 				}
@@ -558,7 +569,9 @@ impl Worker {
 					(Some(current_metadata), None) => Some(current_metadata),
 					_ => None,
 				};
-				report.completed_at = Some(Utc::now());
+				let now = Utc::now();
+				report.complete_phase(now);
+				report.completed_at = Some(now);
 				if let Err(e) = report.update(library).await {
 					error!("failed to update job report: {:#?}", e);
 				}
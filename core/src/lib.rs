@@ -1,8 +1,12 @@
 #![warn(clippy::unwrap_used, clippy::panic)]
 
 use crate::{
-	api::{CoreEvent, Router},
+	api::{utils::RateLimiter, CoreEvent, Router},
 	location::LocationManagerError,
+	node::{
+		readiness::{Readiness, Subsystem, SubsystemStatus},
+		BusEvent, EventBus, EventFilter, LogFilter, NetworkUsage, ShutdownReport,
+	},
 	object::media::thumbnail::actor::Thumbnailer,
 };
 
@@ -11,6 +15,7 @@ use sd_ai::image_labeler::{DownloadModelError, ImageLabeler, YoloV8};
 
 use api::notifications::{Notification, NotificationData, NotificationId};
 use chrono::{DateTime, Utc};
+use mini_moka::sync::Cache;
 use node::config;
 use notifications::Notifications;
 use reqwest::{RequestBuilder, Response};
@@ -19,16 +24,18 @@ use std::{
 	fmt,
 	path::{Path, PathBuf},
 	sync::{atomic::AtomicBool, Arc},
+	time::Duration,
 };
 
+use futures::Stream;
 use thiserror::Error;
-use tokio::{fs, sync::broadcast};
+use tokio::fs;
 use tracing::{error, info, warn};
 use tracing_appender::{
 	non_blocking::{NonBlocking, WorkerGuard},
 	rolling::{RollingFileAppender, Rotation},
 };
-use tracing_subscriber::{filter::FromEnvError, prelude::*, EnvFilter};
+use tracing_subscriber::{filter::FromEnvError, prelude::*, reload, EnvFilter};
 
 pub mod api;
 mod cloud;
@@ -47,6 +54,8 @@ pub mod util;
 pub(crate) mod volume;
 
 pub use env::Env;
+pub use node::open_with::{OpenWith, OpenWithApplication, OpenWithError};
+pub use node::ShutdownReport;
 
 pub(crate) use sd_core_sync as sync;
 
@@ -59,13 +68,20 @@ pub struct Node {
 	pub jobs: Arc<job::Jobs>,
 	pub locations: location::Locations,
 	pub p2p: Arc<p2p::P2PManager>,
-	pub event_bus: (broadcast::Sender<CoreEvent>, broadcast::Receiver<CoreEvent>),
+	pub event_bus: EventBus,
 	pub notifications: Notifications,
 	pub thumbnailer: Thumbnailer,
+	/// Bytes sent and received by cloud sync and P2P, per day.
+	pub network_usage: Arc<NetworkUsage>,
 	pub files_over_p2p_flag: Arc<AtomicBool>,
 	pub cloud_sync_flag: Arc<AtomicBool>,
 	pub env: Arc<env::Env>,
 	pub http: reqwest::Client,
+	/// Directories browsed outside of locations, files in them can be opened through the core.
+	pub(crate) ephemeral_paths: Cache<PathBuf, ()>,
+	pub(crate) rate_limiter: RateLimiter,
+	/// `None` unless the logger was set up by [`Node::init_logger`].
+	pub(crate) log_filter: Option<LogFilter>,
 	#[cfg(feature = "ai")]
 	pub image_labeller: ImageLabeler,
 }
@@ -95,9 +111,14 @@ impl Node {
 		// This error is ignored because it's throwing on mobile despite the folder existing.
 		let _ = fs::create_dir_all(&data_dir).await;
 
-		let event_bus = broadcast::channel(1024);
-		let config = config::Manager::new(data_dir.to_path_buf())
-			.await
+		let event_bus = EventBus::with_capacity(env.event_bus_capacity);
+		let readiness = &env.readiness;
+		let config = readiness
+			.track(
+				Subsystem::Config,
+				config::Manager::new(data_dir.to_path_buf()).await,
+				&event_bus,
+			)
 			.map_err(NodeError::FailedToInitializeConfig)?;
 
 		if let Some(url) = config.get().await.sd_api_origin {
@@ -112,28 +133,46 @@ impl Node {
 
 		let (locations, locations_actor) = location::Locations::new();
 		let (jobs, jobs_actor) = job::Jobs::new();
-		let libraries = library::Libraries::new(data_dir.join("libraries")).await?;
+		let libraries = readiness.track_failure(
+			Subsystem::Libraries,
+			library::Libraries::new(data_dir.join("libraries")).await,
+			&event_bus,
+		)?;
+
+		let network_usage = NetworkUsage::load(data_dir, config.preferences_watcher()).await;
+
+		let (p2p, p2p_actor) = readiness.track(
+			Subsystem::P2P,
+			p2p::P2PManager::new(config.clone(), libraries.clone(), network_usage.clone()).await,
+			&event_bus,
+		)?;
+
+		let thumbnailer = Thumbnailer::new(
+			data_dir,
+			libraries.clone(),
+			event_bus.clone(),
+			config.preferences_watcher(),
+		)
+		.await;
+		readiness.set(Subsystem::Thumbnailer, SubsystemStatus::Ready, &event_bus);
 
-		let (p2p, p2p_actor) = p2p::P2PManager::new(config.clone(), libraries.clone()).await?;
 		let node = Arc::new(Node {
 			data_dir: data_dir.to_path_buf(),
 			jobs,
 			locations,
 			notifications: notifications::Notifications::new(),
 			p2p,
-			thumbnailer: Thumbnailer::new(
-				data_dir,
-				libraries.clone(),
-				event_bus.0.clone(),
-				config.preferences_watcher(),
-			)
-			.await,
+			thumbnailer,
+			network_usage,
 			config,
 			event_bus,
 			libraries,
 			files_over_p2p_flag: Arc::new(AtomicBool::new(false)),
 			cloud_sync_flag: Arc::new(AtomicBool::new(false)),
 			http: reqwest::Client::new(),
+			ephemeral_paths: Cache::new(1024),
+			rate_limiter: RateLimiter::new(),
+			log_filter: LogFilter::get(),
 			env,
 			#[cfg(feature = "ai")]
 			image_labeller: ImageLabeler::new(YoloV8::model(image_labeler_version)?, data_dir)
@@ -154,9 +193,14 @@ impl Node {
 
 		// Be REALLY careful about ordering here or you'll get unreliable deadlock's!
 		locations_actor.start(node.clone());
-		node.libraries.init(&node).await?;
+		node.env.readiness.track(
+			Subsystem::Libraries,
+			node.libraries.init(&node).await,
+			&node.event_bus,
+		)?;
 		jobs_actor.start(node.clone());
 		p2p_actor.start(node.clone());
+		api::libraries::start_statistics_updaters_cleanup(&node.libraries);
 
 		let router = api::mount();
 
@@ -164,6 +208,22 @@ impl Node {
 		Ok((node, router))
 	}
 
+	/// Moves the data directory to `new_dir`, see [`node::migrate_data_dir`].
+	///
+	/// This takes the paths instead of a running [`Node`] as the directory can only be moved while
+	/// the core isn't using it. Call it before [`Node::new`] or after [`Node::shutdown`].
+	pub async fn migrate_data_dir(
+		data_dir: impl AsRef<Path>,
+		new_dir: impl AsRef<Path>,
+	) -> Result<(), node::DataDirMigrationError> {
+		node::migrate_data_dir(data_dir, new_dir).await
+	}
+
+	/// Which subsystems of the core are up.
+	pub fn readiness(&self) -> Readiness {
+		self.env.readiness.get()
+	}
+
 	pub fn init_logger(data_dir: impl AsRef<Path>) -> Result<WorkerGuard, FromEnvError> {
 		let (logfile, guard) = NonBlocking::new(
 			RollingFileAppender::builder()
@@ -188,21 +248,24 @@ impl Node {
 			);
 		}
 
+		// Shared by both outputs, so it can be changed for both at runtime
+		let (filter, handle) = reload::Layer::new(EnvFilter::from_default_env());
+		LogFilter::register(handle, std::env::var("RUST_LOG").unwrap_or_default());
+
 		tracing_subscriber::registry()
+			.with(filter)
 			.with(
 				tracing_subscriber::fmt::layer()
 					.with_file(true)
 					.with_line_number(true)
 					.with_ansi(false)
-					.with_writer(logfile)
-					.with_filter(EnvFilter::from_default_env()),
+					.with_writer(logfile),
 			)
 			.with(
 				tracing_subscriber::fmt::layer()
 					.with_file(true)
 					.with_line_number(true)
-					.with_writer(std::io::stdout)
-					.with_filter(EnvFilter::from_default_env()),
+					.with_writer(std::io::stdout),
 			)
 			.init();
 
@@ -221,20 +284,65 @@ impl Node {
 		Ok(guard)
 	}
 
-	pub async fn shutdown(&self) {
+	/// Shut down each subsystem in turn. Subsystems which take longer than
+	/// [`NodeConfig::shutdown_timeout_secs`](config::NodeConfig::shutdown_timeout_secs) are
+	/// abandoned so a stuck one can't hang the process, the report tells which.
+	pub async fn shutdown(&self) -> ShutdownReport {
 		info!("Spacedrive shutting down...");
-		self.thumbnailer.shutdown().await;
-		self.jobs.shutdown().await;
-		self.p2p.shutdown().await;
+
+		let limit = Duration::from_secs(
+			self.config
+				.get()
+				.await
+				.shutdown_timeout_secs
+				.unwrap_or(config::DEFAULT_SHUTDOWN_TIMEOUT_SECS)
+				.into(),
+		);
+
+		let mut report = ShutdownReport::default();
+		report
+			.shutdown("thumbnailer", limit, self.thumbnailer.shutdown())
+			.await;
+		report.shutdown("jobs", limit, self.jobs.shutdown()).await;
+		report.shutdown("p2p", limit, self.p2p.shutdown()).await;
+		report
+			.shutdown("network_usage", limit, self.network_usage.shutdown())
+			.await;
 		#[cfg(feature = "ai")]
-		self.image_labeller.shutdown().await;
-		info!("Spacedrive Core shutdown successful!");
+		report
+			.shutdown("image_labeller", limit, self.image_labeller.shutdown())
+			.await;
+
+		if report.is_clean() {
+			info!("Spacedrive Core shutdown successful!");
+		} else {
+			warn!(
+				"Spacedrive Core shutdown abandoned subsystems: {:?}",
+				report.abandoned
+			);
+		}
+
+		report
 	}
 
 	pub(crate) fn emit(&self, event: CoreEvent) {
-		if let Err(e) = self.event_bus.0.send(event) {
-			warn!("Error sending event to event bus: {e:?}");
-		}
+		self.event_bus.send(event);
+	}
+
+	/// Subscribes to the events matching the filter, see [`EventBus::subscribe`].
+	pub fn subscribe(
+		&self,
+		filter: EventFilter,
+	) -> impl Stream<Item = BusEvent> + Unpin + Send + 'static {
+		self.event_bus.subscribe(filter)
+	}
+
+	/// Subscribes to the events matching the filter, see [`EventBus::subscribe_resyncing`].
+	pub fn subscribe_resyncing(
+		&self,
+		filter: EventFilter,
+	) -> impl Stream<Item = CoreEvent> + Unpin + Send + 'static {
+		self.event_bus.subscribe_resyncing(filter)
 	}
 
 	pub async fn emit_notification(&self, data: NotificationData, expires: Option<DateTime<Utc>>) {
@@ -243,6 +351,7 @@ impl Node {
 			data,
 			read: false,
 			expires,
+			created_at: Utc::now(),
 		};
 
 		match self
@@ -1,4 +1,5 @@
 use crate::{
+	location::normalization,
 	node::{config::NodeConfig, Platform},
 	util::version_manager::{Kind, ManagedVersion, VersionManager, VersionManagerError},
 };
@@ -63,10 +64,11 @@ pub enum LibraryConfigVersion {
 	V7 = 7,
 	V8 = 8,
 	V9 = 9,
+	V10 = 10,
 }
 
 impl ManagedVersion<LibraryConfigVersion> for LibraryConfig {
-	const LATEST_VERSION: LibraryConfigVersion = LibraryConfigVersion::V9;
+	const LATEST_VERSION: LibraryConfigVersion = LibraryConfigVersion::V10;
 
 	const KIND: Kind = Kind::Json("version");
 
@@ -388,6 +390,10 @@ impl LibraryConfig {
 						.await?;
 					}
 
+					(LibraryConfigVersion::V9, LibraryConfigVersion::V10) => {
+						normalization::backfill_normalized_names(db).await?;
+					}
+
 					_ => {
 						error!("Library config version is not handled: {:?}", current);
 						return Err(VersionManagerError::UnexpectedMigration {
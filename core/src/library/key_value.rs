@@ -0,0 +1,162 @@
+use sd_prisma::prisma::{key_value, PrismaClient};
+
+use chrono::Utc;
+use prisma_client_rust::QueryError;
+use rspc::ErrorCode;
+use serde::Serialize;
+use serde_json::Value;
+use specta::Type;
+use thiserror::Error;
+
+/// Values bigger than this, once encoded, are rejected. This is meant for small settings, not files.
+pub const KEY_VALUE_MAX_SIZE: usize = 64 * 1024;
+
+const NAMESPACE_MAX_LEN: usize = 64;
+const NAMESPACE_SEPARATOR: char = ':';
+
+#[derive(Error, Debug)]
+pub enum KeyValueError {
+	#[error("invalid namespace '{0}', it must be 1 to {NAMESPACE_MAX_LEN} ASCII letters, digits, '-' or '_'")]
+	InvalidNamespace(String),
+	#[error("key can't be empty")]
+	EmptyKey,
+	#[error("value is {0} bytes, the limit is {KEY_VALUE_MAX_SIZE} bytes")]
+	ValueTooBig(usize),
+	#[error("stored value for key '{0}' isn't valid JSON: {1}")]
+	CorruptedValue(String, serde_json::Error),
+	#[error("database error: {0}")]
+	Database(#[from] QueryError),
+}
+
+impl From<KeyValueError> for rspc::Error {
+	fn from(err: KeyValueError) -> Self {
+		match err {
+			KeyValueError::InvalidNamespace(_)
+			| KeyValueError::EmptyKey
+			| KeyValueError::ValueTooBig(_) => {
+				rspc::Error::with_cause(ErrorCode::BadRequest, err.to_string(), err)
+			}
+			_ => rspc::Error::with_cause(ErrorCode::InternalServerError, err.to_string(), err),
+		}
+	}
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct KeyValueEntry {
+	pub key: String,
+	pub value: Value,
+}
+
+/// The key-values of a single feature in a library's key-value store.
+///
+/// Every feature must use it's own namespace, which is prefixed to the keys in the database so
+/// features can't overwrite each other's values. The values are not synced to other instances.
+pub struct KeyValueNamespace<'db> {
+	db: &'db PrismaClient,
+	prefix: String,
+}
+
+impl<'db> KeyValueNamespace<'db> {
+	pub fn new(db: &'db PrismaClient, namespace: &str) -> Result<Self, KeyValueError> {
+		if namespace.is_empty()
+			|| namespace.len() > NAMESPACE_MAX_LEN
+			|| !namespace
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+		{
+			return Err(KeyValueError::InvalidNamespace(namespace.to_string()));
+		}
+
+		Ok(Self {
+			db,
+			prefix: format!("{namespace}{NAMESPACE_SEPARATOR}"),
+		})
+	}
+
+	fn full_key(&self, key: &str) -> Result<String, KeyValueError> {
+		if key.is_empty() {
+			return Err(KeyValueError::EmptyKey);
+		}
+
+		Ok(format!("{}{key}", self.prefix))
+	}
+
+	pub async fn get(&self, key: &str) -> Result<Option<Value>, KeyValueError> {
+		self.db
+			.key_value()
+			.find_unique(key_value::key::equals(self.full_key(key)?))
+			.select(key_value::select!({ value }))
+			.exec()
+			.await?
+			.map(|data| {
+				serde_json::from_str(&data.value)
+					.map_err(|e| KeyValueError::CorruptedValue(key.to_string(), e))
+			})
+			.transpose()
+	}
+
+	pub async fn set(&self, key: &str, value: &Value) -> Result<(), KeyValueError> {
+		let full_key = self.full_key(key)?;
+
+		let value = value.to_string();
+		if value.len() > KEY_VALUE_MAX_SIZE {
+			return Err(KeyValueError::ValueTooBig(value.len()));
+		}
+
+		let date_modified = Utc::now().into();
+
+		self.db
+			.key_value()
+			.upsert(
+				key_value::key::equals(full_key.clone()),
+				key_value::create(
+					full_key,
+					value.clone(),
+					vec![key_value::date_modified::set(Some(date_modified))],
+				),
+				vec![
+					key_value::value::set(value),
+					key_value::date_modified::set(Some(date_modified)),
+				],
+			)
+			.exec()
+			.await?;
+
+		Ok(())
+	}
+
+	pub async fn delete(&self, key: &str) -> Result<(), KeyValueError> {
+		self.db
+			.key_value()
+			.delete_many(vec![key_value::key::equals(self.full_key(key)?)])
+			.exec()
+			.await?;
+
+		Ok(())
+	}
+
+	pub async fn list(&self) -> Result<Vec<KeyValueEntry>, KeyValueError> {
+		self.db
+			.key_value()
+			.find_many(vec![key_value::key::starts_with(self.prefix.clone())])
+			.select(key_value::select!({ key value }))
+			.exec()
+			.await?
+			.into_iter()
+			// `_` is a wildcard for `LIKE`, so other namespaces can slip through
+			.filter_map(|data| {
+				data.key
+					.strip_prefix(&self.prefix)
+					.map(|key| (key.to_string(), data.value))
+			})
+			.map(|(key, value)| {
+				serde_json::from_str(&value)
+					.map(|value| KeyValueEntry {
+						key: key.clone(),
+						value,
+					})
+					.map_err(|e| KeyValueError::CorruptedValue(key, e))
+			})
+			.collect()
+	}
+}
@@ -1,8 +1,17 @@
-use crate::{api::CoreEvent, object::media::thumbnail::get_indexed_thumbnail_path, sync, Node};
+use crate::{
+	api::{
+		notifications::{Notification, NotificationData, NotificationId},
+		CoreEvent,
+	},
+	node::EventBus,
+	notifications::Notifications,
+	object::media::thumbnail::find_indexed_thumbnail_format,
+	sync, Node,
+};
 
 use sd_file_path_helper::{file_path_to_full_path, IsolatedFilePathData};
 use sd_p2p::spacetunnel::Identity;
-use sd_prisma::prisma::{file_path, location, PrismaClient};
+use sd_prisma::prisma::{file_path, location, notification, PrismaClient};
 use sd_utils::{db::maybe_missing, error::FileIOError};
 
 use std::{
@@ -12,8 +21,9 @@ use std::{
 	sync::Arc,
 };
 
-use tokio::{fs, io, sync::broadcast, sync::RwLock};
-use tracing::warn;
+use chrono::{DateTime, Utc};
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tracing::{error, warn};
 use uuid::Uuid;
 
 use super::{LibraryConfig, LibraryManagerError};
@@ -39,7 +49,6 @@ pub struct Library {
 	// pub key_manager: Arc<KeyManager>,
 	/// p2p identity
 	pub identity: Arc<Identity>,
-	// pub orphan_remover: OrphanRemoverActor,
 	// The UUID which matches `config.instance_id`'s primary key.
 	pub instance_uuid: Uuid,
 
@@ -48,9 +57,13 @@ pub struct Library {
 
 	// Look, I think this shouldn't be here but our current invalidation system needs it.
 	// TODO(@Oscar): Get rid of this with the new invalidation system.
-	event_bus_tx: broadcast::Sender<CoreEvent>,
+	event_bus: EventBus,
+	notifications: Notifications,
 
 	pub actors: Arc<sd_actors::Actors>,
+
+	/// Held while the statistics are refreshed, so they are never refreshed twice at once.
+	pub(crate) statistics_refresh: Mutex<()>,
 }
 
 impl Debug for Library {
@@ -81,15 +94,16 @@ impl Library {
 			id,
 			config: RwLock::new(config),
 			sync,
-			db: db.clone(),
+			db,
 			// key_manager,
 			identity,
-			// orphan_remover: OrphanRemoverActor::spawn(db),
 			instance_uuid,
 			do_cloud_sync,
 			env: node.env.clone(),
-			event_bus_tx: node.event_bus.0.clone(),
+			event_bus: node.event_bus.clone(),
+			notifications: node.notifications.clone(),
 			actors: Default::default(),
+			statistics_refresh: Mutex::new(()),
 		})
 	}
 
@@ -111,19 +125,64 @@ impl Library {
 
 	// TODO: Remove this once we replace the old invalidation system
 	pub(crate) fn emit(&self, event: CoreEvent) {
-		if let Err(e) = self.event_bus_tx.send(event) {
-			warn!("Error sending event to event bus: {e:?}");
+		self.event_bus.send(match event {
+			CoreEvent::InvalidateOperation(event) => {
+				CoreEvent::InvalidateOperation(event.with_library_id(self.id))
+			}
+			event => event,
+		});
+	}
+
+	/// Stores the notification in the library's database, so it moves with the library.
+	pub async fn emit_notification(&self, data: NotificationData, expires: Option<DateTime<Utc>>) {
+		let created_at = Utc::now();
+
+		let encoded_data = match rmp_serde::to_vec_named(&data) {
+			Ok(encoded_data) => encoded_data,
+			Err(err) => {
+				error!(
+					"Error encoding notification for library '{}': {:?}",
+					self.id, err
+				);
+				return;
+			}
+		};
+
+		match self
+			.db
+			.notification()
+			.create(
+				encoded_data,
+				vec![
+					notification::expires_at::set(expires.map(Into::into)),
+					notification::created_at::set(Some(created_at.into())),
+				],
+			)
+			.exec()
+			.await
+		{
+			Ok(notification) => {
+				self.notifications._internal_send(Notification {
+					id: NotificationId::Library(self.id, notification.id as u32),
+					data,
+					read: false,
+					expires,
+					created_at,
+				});
+			}
+			Err(err) => {
+				error!(
+					"Error saving notification to library '{}': {:?}",
+					self.id, err
+				);
+			}
 		}
 	}
 
 	pub async fn thumbnail_exists(&self, node: &Node, cas_id: &str) -> Result<bool, FileIOError> {
-		let thumb_path = get_indexed_thumbnail_path(node, cas_id, self.id);
-
-		match fs::metadata(&thumb_path).await {
-			Ok(_) => Ok(true),
-			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
-			Err(e) => Err(FileIOError::from((thumb_path, e))),
-		}
+		Ok(find_indexed_thumbnail_format(node, cas_id, self.id)
+			.await?
+			.is_some())
 	}
 
 	/// Returns the full path of a file
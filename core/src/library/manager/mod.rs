@@ -1,9 +1,11 @@
 use crate::{
-	api::{utils::InvalidateOperationEvent, CoreEvent},
+	api::{sync::invalidate_ingested, utils::InvalidateOperationEvent, CoreEvent},
+	cloud::pending::{self, CloudOperation},
 	invalidate_query,
 	location::{
 		indexer,
 		metadata::{LocationMetadataError, SpacedriveLocationMetadataFile},
+		relink_if_moved,
 	},
 	node::Platform,
 	object::tag,
@@ -35,12 +37,12 @@ use futures_concurrency::future::{Join, TryJoin};
 use tokio::{
 	fs, io,
 	sync::{broadcast, RwLock},
-	time::sleep,
+	time::{sleep, sleep_until, Instant},
 };
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use super::{Library, LibraryConfig, LibraryName};
+use super::{refresh_statistics_in_background, Library, LibraryConfig, LibraryName};
 
 mod error;
 
@@ -221,7 +223,7 @@ impl Libraries {
 		debug!("Loaded library '{id:?}'");
 
 		if should_seed {
-			tag::seed::new_library(&library).await?;
+			tag::seed::new_or_existing_library(&library).await?;
 			indexer::rules::seed::new_or_existing_library(&library).await?;
 			debug!("Seeded library '{id:?}'");
 		}
@@ -256,6 +258,9 @@ impl Libraries {
 				.ok_or(LibraryManagerError::LibraryNotFound)?,
 		);
 
+		let renamed = name.is_some();
+		let unlinked = matches!(cloud_id, MaybeUndefined::Null);
+
 		library
 			.update_config(
 				|config| {
@@ -263,23 +268,43 @@ impl Libraries {
 					if let Some(name) = name {
 						config.name = name;
 					}
-					match description {
-						MaybeUndefined::Undefined => {}
-						MaybeUndefined::Null => config.description = None,
-						MaybeUndefined::Value(description) => {
-							config.description = Some(description)
-						}
-					}
-					match cloud_id {
-						MaybeUndefined::Undefined => {}
-						MaybeUndefined::Null => config.cloud_id = None,
-						MaybeUndefined::Value(cloud_id) => config.cloud_id = Some(cloud_id),
-					}
+					description.apply_to(&mut config.description);
+					cloud_id.apply_to(&mut config.cloud_id);
 				},
 				self.libraries_dir.join(format!("{id}.sdlibrary")),
 			)
 			.await?;
 
+		let config = library.config().await;
+
+		if unlinked {
+			// Nothing is left on the cloud for them to change
+			if let Err(e) = library
+				.db
+				.cloud_pending_operation()
+				.delete_many(vec![])
+				.exec()
+				.await
+			{
+				error!("Failed to clear pending cloud operations of unlinked library: {e:#?}");
+			}
+		} else if renamed && config.cloud_id.is_some() {
+			// Queued first, so the rename isn't lost if the cloud can't be reached right now
+			if let Err(e) = pending::enqueue(
+				&library,
+				&CloudOperation::UpdateLibrary {
+					name: config.name.into(),
+				},
+				None,
+			)
+			.await
+			{
+				error!("Failed to queue library rename for cloud: {e:#?}");
+			}
+
+			library.do_cloud_sync();
+		}
+
 		self.tx
 			.emit(LibraryManagerEvent::Edit(Arc::clone(&library)))
 			.await;
@@ -341,7 +366,7 @@ impl Libraries {
 			async {
 				fs::remove_file(&db_path)
 					.await
-					.map_err(|e| LibraryManagerError::FileIO(FileIOError::from((db_path, e))))
+					.map_err(|e| LibraryManagerError::FileIO(FileIOError::from((&db_path, e))))
 			},
 			async {
 				fs::remove_file(&sd_lib_path)
@@ -352,6 +377,20 @@ impl Libraries {
 			.try_join()
 			.await?;
 
+		// The database may have been left with WAL files which would be picked up by a library
+		// later restored under this same id
+		for wal_path in db::wal_files(&db_path) {
+			match fs::remove_file(&wal_path).await {
+				Ok(()) => {}
+				Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+				Err(e) => {
+					return Err(LibraryManagerError::FileIO(FileIOError::from((
+						wal_path, e,
+					))))
+				}
+			}
+		}
+
 		// We only remove here after files deletion
 		let library = libraries_write_guard
 			.remove(id)
@@ -387,19 +426,18 @@ impl Libraries {
 		let db_path = db_path.as_ref();
 		let config_path = config_path.as_ref();
 
-		let db_url = format!(
-			"file:{}?socket_timeout=15&connection_limit=1",
-			db_path.as_os_str().to_str().ok_or_else(|| {
+		let node_config = node.config.get().await;
+
+		let db_url =
+			node_config.db_url(db_path.as_os_str().to_str().ok_or_else(|| {
 				LibraryManagerError::NonUtf8Path(NonUtf8PathError(db_path.into()))
-			})?
-		);
+			})?);
 		let db = Arc::new(db::load_and_migrate(&db_url).await?);
 
 		if let Some(create) = create {
 			create.to_query(&db).exec().await?;
 		}
 
-		let node_config = node.config.get().await;
 		let config = LibraryConfig::load(config_path, &node_config, &db).await?;
 
 		let instances = db.instance().find_many(vec![]).exec().await?;
@@ -423,9 +461,11 @@ impl Libraries {
 		let instance_id = Uuid::from_slice(&instance.pub_id)?;
 		let curr_platform = Platform::current() as i32;
 		let instance_node_id = Uuid::from_slice(&instance.node_id)?;
+		let node_remote_identity = node.p2p.manager.identity().get_bytes().to_vec();
 		if instance_node_id != node_config.id
 			|| instance.node_platform != curr_platform
 			|| instance.node_name != node_config.name
+			|| instance.node_remote_identity.as_ref() != Some(&node_remote_identity)
 		{
 			info!(
 				"Detected that the library '{}' has changed node from '{}' to '{}'. Reconciling node data...",
@@ -439,6 +479,7 @@ impl Libraries {
 						instance::node_id::set(node_config.id.as_bytes().to_vec()),
 						instance::node_platform::set(curr_platform),
 						instance::node_name::set(node_config.name),
+						instance::node_remote_identity::set(Some(node_remote_identity)),
 					],
 				)
 				.exec()
@@ -518,6 +559,11 @@ impl Libraries {
 			.exec()
 			.await?
 		{
+			// A renamed location would just be offline, so we look for it before watching it
+			if let Err(e) = relink_if_moved(&location, &library).await {
+				error!("Failed to look for moved location on startup: {e:#?}");
+			}
+
 			if let Err(e) = node.locations.add(location.id, library.clone()).await {
 				error!("Failed to watch location on startup: {e}");
 			};
@@ -527,6 +573,11 @@ impl Libraries {
 			error!("Failed to resume jobs for library. {:#?}", e);
 		}
 
+		tokio::spawn(refresh_statistics_in_background(
+			node.clone(),
+			Arc::downgrade(&library),
+		));
+
 		tokio::spawn({
 			let this = self.clone();
 			let node = node.clone();
@@ -536,10 +587,14 @@ impl Libraries {
 					debug!("Syncing library with cloud!");
 
 					if let Some(_) = library.config().await.cloud_id {
-						if let Ok(lib) =
+						let lib =
 							sd_cloud_api::library::get(node.cloud_api_config().await, library.id)
-								.await
-						{
+								.await;
+
+						// Reaching the cloud means what failed to be sent before can go through now
+						let reached_cloud = lib.is_ok();
+
+						if let Ok(lib) = lib {
 							match lib {
 								Some(lib) => {
 									if let Some(this_instance) = lib
@@ -556,32 +611,34 @@ impl Libraries {
 										if should_update {
 											warn!("Library instance on cloud is outdated. Updating...");
 
-											if let Err(err) =
-												sd_cloud_api::library::update_instance(
-													node.cloud_api_config().await,
-													library.id,
-													this_instance.uuid,
-													Some(node_config.id),
-													Some(node_config.name),
-													Some(Platform::current() as u8),
-												)
-												.await
+											if let Err(err) = pending::send(
+												&node,
+												&library,
+												CloudOperation::UpdateInstance {
+													instance_uuid: this_instance.uuid,
+													node_id: node_config.id,
+													node_name: node_config.name,
+													node_platform: Platform::current() as u8,
+												},
+											)
+											.await
 											{
 												error!(
-													"Failed to updating instance '{}' on cloud: {:#?}",
+													"Failed to update instance '{}' on cloud: {:#?}",
 													this_instance.uuid, err
 												);
 											}
 										}
 									}
 
-									if &lib.name != &*library.config().await.name {
+									let name: String = library.config().await.name.into();
+									if lib.name != name {
 										warn!("Library name on cloud is outdated. Updating...");
 
-										if let Err(err) = sd_cloud_api::library::update(
-											node.cloud_api_config().await,
-											library.id,
-											Some(lib.name),
+										if let Err(err) = pending::send(
+											&node,
+											&library,
+											CloudOperation::UpdateLibrary { name },
 										)
 										.await
 										{
@@ -628,6 +685,10 @@ impl Libraries {
 								}
 							}
 						}
+
+						if let Err(err) = pending::flush(&node, &library, reached_cloud).await {
+							error!("Failed to send pending operations to cloud: {:#?}", err);
+						}
 					}
 
 					tokio::select! {
@@ -650,6 +711,9 @@ impl Libraries {
 	}
 }
 
+/// How long to wait for more ingest events before invalidating the affected queries
+const INGEST_INVALIDATION_WINDOW: Duration = Duration::from_millis(250);
+
 async fn sync_rx_actor(
 	library: Arc<Library>,
 	node: Arc<Node>,
@@ -661,10 +725,34 @@ async fn sync_rx_actor(
 		};
 
 		match msg {
-			// TODO: Any sync event invalidates the entire React Query cache this is a hacky workaround until the new invalidation system.
-			SyncMessage::Ingested => node.emit(CoreEvent::InvalidateOperation(
-				InvalidateOperationEvent::all(),
-			)),
+			SyncMessage::Ingested(mut models) => {
+				// Bursts of ingest events are coalesced so a large ingest doesn't cause the frontend to refetch constantly
+				let deadline = Instant::now() + INGEST_INVALIDATION_WINDOW;
+				let mut lagged = false;
+
+				loop {
+					tokio::select! {
+						_ = sleep_until(deadline) => break,
+						msg = sync_rx.recv() => match msg {
+							Ok(SyncMessage::Ingested(more)) => models.extend(more),
+							Ok(SyncMessage::Created) => {
+								p2p::sync::originator(library.id, &library.sync, &node.p2p).await
+							}
+							Err(broadcast::error::RecvError::Lagged(_)) => lagged = true,
+							Err(broadcast::error::RecvError::Closed) => break,
+						}
+					}
+				}
+
+				// If we missed events we can't know what changed so we have to invalidate everything
+				if lagged {
+					node.emit(CoreEvent::InvalidateOperation(
+						InvalidateOperationEvent::all(),
+					));
+				} else {
+					invalidate_ingested(&library, &models);
+				}
+			}
 			SyncMessage::Created => {
 				p2p::sync::originator(library.id, &library.sync, &node.p2p).await
 			}
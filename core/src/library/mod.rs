@@ -1,4 +1,5 @@
 mod config;
+mod key_value;
 #[allow(clippy::module_inception)]
 mod library;
 mod manager;
@@ -6,6 +7,7 @@ mod name;
 mod statistics;
 
 pub use config::*;
+pub use key_value::*;
 pub use library::*;
 pub use manager::*;
 pub use name::*;
@@ -1,12 +1,60 @@
-use crate::{api::utils::get_size, library::Library, volume::get_volumes, Node};
+use crate::{api::utils::get_size, invalidate_query, library::Library, volume::get_volumes, Node};
 
-use sd_prisma::prisma::statistics;
+use sd_prisma::prisma::{
+	file_path, location, object, statistics, statistics_history, PrismaClient,
+};
 
-use chrono::Utc;
-use tracing::info;
+use std::{
+	collections::HashMap,
+	sync::{Arc, Weak},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use prisma_client_rust::QueryError;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info};
 
 use super::LibraryManagerError;
 
+/// History points younger than this many days are kept one per hour, older ones one per day.
+const HOURLY_HISTORY_DAYS: i64 = 7;
+/// History points older than this many days are removed.
+const HISTORY_RETENTION_DAYS: i64 = 365 * 2;
+/// How often the background refresh checks whether the statistics are due.
+const BACKGROUND_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Statistics are captured a bit after each check, so without some leeway they would only be
+/// due one check late.
+const REFRESH_LEEWAY_SECS: i64 = 30;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Type)]
+pub struct StatisticsPreferences {
+	refresh_interval_mins: u32, // 0 disables the background refresh
+}
+
+impl Default for StatisticsPreferences {
+	fn default() -> Self {
+		Self {
+			refresh_interval_mins: 15,
+		}
+	}
+}
+
+impl StatisticsPreferences {
+	/// How often statistics are refreshed in the background, `None` if they aren't.
+	pub fn refresh_interval(&self) -> Option<Duration> {
+		(self.refresh_interval_mins != 0)
+			.then(|| Duration::minutes(i64::from(self.refresh_interval_mins)))
+	}
+
+	pub fn set_refresh_interval_mins(&mut self, refresh_interval_mins: u32) -> &mut Self {
+		self.refresh_interval_mins = refresh_interval_mins;
+
+		self
+	}
+}
+
 pub async fn update_library_statistics(
 	node: &Node,
 	library: &Library,
@@ -35,11 +83,13 @@ pub async fn update_library_statistics(
 		.await
 		.unwrap_or(0);
 
+	let total_object_count = library.db.object().count(vec![]).exec().await?;
+
 	use statistics::*;
 	let params = vec![
 		id::set(1), // Each library is a database so only one of these ever exists
 		date_captured::set(Utc::now().into()),
-		total_object_count::set(0),
+		total_object_count::set(i32::try_from(total_object_count).unwrap_or(i32::MAX)),
 		library_db_size::set(library_db_size.to_string()),
 		total_bytes_used::set(total_bytes_used.to_string()),
 		total_bytes_capacity::set(total_capacity.to_string()),
@@ -62,5 +112,238 @@ pub async fn update_library_statistics(
 
 	info!("Updated library statistics: {:?}", stats);
 
+	record_statistics_history(&library.db, Utc::now()).await?;
+
 	Ok(stats)
 }
+
+/// Updates the statistics unless they were captured less than `min_age` ago, returning `None` if
+/// they were. Both the background refresh and the one clients ask for go through here, so they
+/// don't run at the same time nor right after one another.
+pub async fn refresh_library_statistics(
+	node: &Node,
+	library: &Library,
+	min_age: Duration,
+) -> Result<Option<statistics::Data>, LibraryManagerError> {
+	let _guard = library.statistics_refresh.lock().await;
+
+	let last_captured = library
+		.db
+		.statistics()
+		.find_unique(statistics::id::equals(1))
+		.select(statistics::select!({ date_captured }))
+		.exec()
+		.await?;
+
+	if let Some(last_captured) = last_captured {
+		if Utc::now() - DateTime::<Utc>::from(last_captured.date_captured) < min_age {
+			return Ok(None);
+		}
+	}
+
+	update_library_statistics(node, library).await.map(Some)
+}
+
+/// Refreshes the library's statistics on the interval set in the node's preferences, whether or
+/// not a client is looking at them, so nodes without a UI don't report stale statistics.
+/// It stops once the library is gone.
+pub(crate) async fn refresh_statistics_in_background(node: Arc<Node>, library: Weak<Library>) {
+	let preferences_rx = node.config.preferences_watcher();
+
+	let mut tick = interval(BACKGROUND_CHECK_INTERVAL);
+	tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	loop {
+		tick.tick().await;
+
+		let Some(library) = library.upgrade() else {
+			break;
+		};
+
+		if node.libraries.get_library(&library.id).await.is_none() {
+			break;
+		}
+
+		// Read on every check, so a new interval applies right away
+		let refresh_interval = preferences_rx.borrow().statistics.refresh_interval();
+		let Some(refresh_interval) = refresh_interval else {
+			continue;
+		};
+
+		match refresh_library_statistics(
+			&node,
+			&library,
+			refresh_interval - Duration::seconds(REFRESH_LEEWAY_SECS),
+		)
+		.await
+		{
+			Ok(Some(_)) => {
+				invalidate_query!(library, "library.statistics");
+				invalidate_query!(library, "library.statisticsHistory");
+			}
+			Ok(None) => {}
+			Err(e) => error!("Failed to refresh library statistics in the background: {e:#?}"),
+		}
+	}
+}
+
+/// Appends the library's current size to its statistics history and downsamples the older points.
+async fn record_statistics_history(
+	db: &PrismaClient,
+	now: DateTime<Utc>,
+) -> Result<(), QueryError> {
+	let total_bytes = db
+		.location()
+		.find_many(vec![])
+		.select(location::select!({ size_in_bytes }))
+		.exec()
+		.await?
+		.into_iter()
+		.filter_map(|location| {
+			location
+				.size_in_bytes
+				.and_then(|size| size.try_into().ok())
+				.map(u64::from_be_bytes)
+		})
+		.sum::<u64>();
+
+	let (file_count, object_count) = (
+		db.file_path()
+			.count(vec![file_path::is_dir::equals(Some(false))])
+			.exec()
+			.await?,
+		db.object().count(vec![]).exec().await?,
+	);
+
+	db.statistics_history()
+		.create(
+			now.into(),
+			total_bytes.to_string(),
+			i32::try_from(file_count).unwrap_or(i32::MAX),
+			i32::try_from(object_count).unwrap_or(i32::MAX),
+			vec![],
+		)
+		.exec()
+		.await?;
+
+	let points = db
+		.statistics_history()
+		.find_many(vec![])
+		.select(statistics_history::select!({ id date_captured }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|point| (point.id, DateTime::<Utc>::from(point.date_captured)))
+		.collect::<Vec<_>>();
+
+	let to_prune = points_to_prune(&points, now);
+	if !to_prune.is_empty() {
+		db.statistics_history()
+			.delete_many(vec![statistics_history::id::in_vec(to_prune)])
+			.exec()
+			.await?;
+	}
+
+	Ok(())
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum HistoryBucket {
+	Hour(i64),
+	Day(i64),
+}
+
+/// Only the latest point of each hour is kept for recent history and of each day for older
+/// history, so the table stays small however often the statistics are updated.
+fn points_to_prune(
+	points: &[(statistics_history::id::Type, DateTime<Utc>)],
+	now: DateTime<Utc>,
+) -> Vec<statistics_history::id::Type> {
+	let mut to_prune = Vec::new();
+	let mut latest_per_bucket = HashMap::<_, (statistics_history::id::Type, DateTime<Utc>)>::new();
+
+	for &(id, date_captured) in points {
+		let age = now - date_captured;
+		let timestamp = date_captured.timestamp();
+
+		let bucket = if age > Duration::days(HISTORY_RETENTION_DAYS) {
+			to_prune.push(id);
+			continue;
+		} else if age > Duration::days(HOURLY_HISTORY_DAYS) {
+			HistoryBucket::Day(timestamp.div_euclid(60 * 60 * 24))
+		} else {
+			HistoryBucket::Hour(timestamp.div_euclid(60 * 60))
+		};
+
+		match latest_per_bucket.get_mut(&bucket) {
+			Some(latest) if latest.1 >= date_captured => to_prune.push(id),
+			Some(latest) => to_prune.push(std::mem::replace(latest, (id, date_captured)).0),
+			None => {
+				latest_per_bucket.insert(bucket, (id, date_captured));
+			}
+		}
+	}
+
+	to_prune
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_library;
+
+	#[tokio::test]
+	async fn test_refresh_skips_fresh_statistics() {
+		let (_data_dir, node, library) = test_library("Statistics").await;
+
+		let refreshed = refresh_library_statistics(&node, &library, Duration::zero())
+			.await
+			.unwrap()
+			.unwrap();
+
+		// Just refreshed, by the background refresh or by a client
+		assert!(
+			refresh_library_statistics(&node, &library, Duration::hours(1))
+				.await
+				.unwrap()
+				.is_none()
+		);
+
+		let refreshed_again = refresh_library_statistics(&node, &library, Duration::zero())
+			.await
+			.unwrap()
+			.unwrap();
+		assert!(refreshed_again.date_captured >= refreshed.date_captured);
+	}
+
+	#[test]
+	fn test_statistics_history_downsampling() {
+		let now = DateTime::parse_from_rfc3339("2024-01-20T12:30:00Z")
+			.map(DateTime::<Utc>::from)
+			.expect("valid date");
+
+		let points = [
+			// Every minute of the current hour, only the latest is kept
+			(1, now - Duration::minutes(20)),
+			(2, now - Duration::minutes(10)),
+			(3, now),
+			// Two points in an hour of the last week
+			(4, now - Duration::days(2) - Duration::minutes(5)),
+			(5, now - Duration::days(2)),
+			// Two points on the same old day, kept daily
+			(6, now - Duration::days(30) - Duration::hours(3)),
+			(7, now - Duration::days(30) - Duration::hours(1)),
+			// A point in another old day
+			(8, now - Duration::days(31)),
+			// Past the retention
+			(9, now - Duration::days(365 * 3)),
+		];
+
+		let mut to_prune = points_to_prune(&points, now);
+		to_prune.sort_unstable();
+
+		assert_eq!(to_prune, vec![1, 2, 4, 6, 9]);
+	}
+}
@@ -1,3 +1,5 @@
+use crate::job::JobError;
+
 use sd_file_path_helper::FilePathError;
 use sd_prisma::prisma::location;
 use sd_utils::{
@@ -5,13 +7,18 @@ use sd_utils::{
 	error::{FileIOError, NonUtf8PathError},
 };
 
-use std::path::Path;
+use std::{
+	io,
+	path::{Path, PathBuf},
+};
 
 use rspc::{self, ErrorCode};
+use serde::Serialize;
+use specta::Type;
 use thiserror::Error;
 use uuid::Uuid;
 
-use super::{manager::LocationManagerError, metadata::LocationMetadataError};
+use super::{manager::LocationManagerError, metadata::LocationMetadataError, OtherLibrary};
 
 /// Error type for location related errors
 #[derive(Error, Debug)]
@@ -40,15 +47,44 @@ pub enum LocationError {
 	},
 	#[error(
 		"this location belongs to another library, must update .spacedrive file <path='{}'>",
-		.0.display()
+		.path.display()
 	)]
-	AddLibraryToMetadata(Box<Path>),
+	AddLibraryToMetadata {
+		path: Box<Path>,
+		/// One of the libraries it belongs to, if it's loaded on this node.
+		other_library: Option<OtherLibrary>,
+	},
 	#[error("location metadata file not found <path='{}'>", .0.display())]
 	MetadataNotFound(Box<Path>),
 	#[error("location already exists in database <path='{}'>", .0.display())]
 	LocationAlreadyExists(Box<Path>),
-	#[error("nested location currently not supported <path='{}'>", .0.display())]
-	NestedLocation(Box<Path>),
+	#[error(
+		"location overlaps another location <path='{}', location_id='{location_id}'>",
+		.path.display(),
+	)]
+	Overlapping {
+		path: Box<Path>,
+		/// The location containing the path, or inside it.
+		location_id: location::id::Type,
+	},
+	#[error(
+		"location overlaps another one being added with it <path='{}', other='{}'>",
+		.path.display(),
+		.other.display(),
+	)]
+	OverlappingInBatch { path: Box<Path>, other: Box<Path> },
+	#[error("location is offline <id='{0}'>")]
+	Offline(location::id::Type),
+	#[error("location directory is read only <path='{}'>", .0.display())]
+	ReadOnly(Box<Path>),
+	#[error("no permission to access the location directory <path='{}'>", .0.display())]
+	PermissionDenied(Box<Path>),
+	#[error("location path doesn't start with prefix <path='{}', prefix='{}'>", .path.display(), .prefix.display())]
+	PrefixMismatch { path: Box<Path>, prefix: Box<Path> },
+	#[error("path is outside of the location <path='{}'>", .0.display())]
+	PathOutsideLocation(Box<Path>),
+	#[error("path is rejected by the location's indexer rules <path='{}'>", .0.display())]
+	RejectedByIndexerRules(Box<Path>),
 	#[error(transparent)]
 	NonUtf8Path(#[from] NonUtf8PathError),
 
@@ -75,6 +111,120 @@ pub enum LocationError {
 	MissingPath(location::id::Type),
 	#[error("missing-field: {0}")]
 	MissingField(#[from] MissingFieldError),
+	#[error("failed to index path: {0}")]
+	Indexing(Box<JobError>),
+}
+
+impl LocationError {
+	/// Access errors on the location's directory, told apart from other IO errors as they're up to
+	/// the user to fix.
+	pub(super) fn access_denied(path: impl AsRef<Path>, e: &io::Error) -> Option<Self> {
+		if e.kind() == io::ErrorKind::PermissionDenied {
+			Some(Self::PermissionDenied(path.as_ref().into()))
+		} else if is_read_only_filesystem(e) {
+			Some(Self::ReadOnly(path.as_ref().into()))
+		} else {
+			None
+		}
+	}
+
+	/// Errors on the `.spacedrive` file of the location at `path`, with access errors told apart
+	/// like in [`Self::access_denied`].
+	pub(super) fn from_metadata(path: impl AsRef<Path>, e: LocationMetadataError) -> Self {
+		let denied = match &e {
+			LocationMetadataError::Read(e, _) | LocationMetadataError::Write(e, _) => {
+				Self::access_denied(path, e)
+			}
+			_ => None,
+		};
+
+		denied.unwrap_or_else(|| e.into())
+	}
+
+	/// The typed error of the failures creating a location commonly has, if it's one of them.
+	pub fn create_error(&self) -> Option<LocationCreateError> {
+		match self {
+			Self::PathNotFound(path) => Some(LocationCreateError::PathNotFound {
+				path: path.to_path_buf(),
+			}),
+			Self::Overlapping { path, location_id } => Some(LocationCreateError::Overlapping {
+				path: path.to_path_buf(),
+				location_id: *location_id,
+			}),
+			Self::AddLibraryToMetadata {
+				path,
+				other_library,
+			} => Some(LocationCreateError::AddLibrary {
+				path: path.to_path_buf(),
+				other_library: other_library.clone(),
+			}),
+			Self::PermissionDenied(path) => Some(LocationCreateError::PermissionDenied {
+				path: path.to_path_buf(),
+			}),
+			Self::ReadOnly(path) => Some(LocationCreateError::ReadOnly {
+				path: path.to_path_buf(),
+			}),
+			_ => None,
+		}
+	}
+
+	/// For `locations.create`, whose errors have the JSON of a [`LocationCreateError`] as their
+	/// message when they're one of them, as rspc errors can't carry data.
+	pub fn into_create_rspc_error(self) -> rspc::Error {
+		let Some(create_error) = self.create_error() else {
+			return self.into();
+		};
+
+		let code = match create_error {
+			LocationCreateError::PathNotFound { .. } => ErrorCode::NotFound,
+			LocationCreateError::Overlapping { .. } | LocationCreateError::ReadOnly { .. } => {
+				ErrorCode::BadRequest
+			}
+			LocationCreateError::AddLibrary { .. } => ErrorCode::Conflict,
+			LocationCreateError::PermissionDenied { .. } => ErrorCode::Forbidden,
+		};
+
+		match serde_json::to_string(&create_error) {
+			Ok(message) => rspc::Error::with_cause(code, message, self),
+			Err(_) => self.into(),
+		}
+	}
+}
+
+/// `io::ErrorKind::ReadOnlyFilesystem` isn't stable yet.
+fn is_read_only_filesystem(e: &io::Error) -> bool {
+	#[cfg(unix)]
+	const READ_ONLY_FILESYSTEM: i32 = 30; // EROFS
+	#[cfg(windows)]
+	const READ_ONLY_FILESYSTEM: i32 = 19; // ERROR_WRITE_PROTECT
+
+	e.raw_os_error() == Some(READ_ONLY_FILESYSTEM)
+}
+
+/// The failures creating a location commonly has, so the frontend can tell them apart.
+#[derive(Serialize, Type, Debug)]
+#[serde(tag = "code", content = "data", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LocationCreateError {
+	PathNotFound {
+		path: PathBuf,
+	},
+	/// The path is inside another location, or has one inside it, it can be forced.
+	Overlapping {
+		path: PathBuf,
+		location_id: location::id::Type,
+	},
+	/// The location's `.spacedrive` file belongs to another library, it can be added to it too.
+	AddLibrary {
+		path: PathBuf,
+		other_library: Option<OtherLibrary>,
+	},
+	PermissionDenied {
+		path: PathBuf,
+	},
+	/// The `.spacedrive` file can't be written, as the directory or its volume is read only.
+	ReadOnly {
+		path: PathBuf,
+	},
 }
 
 impl From<LocationError> for rspc::Error {
@@ -91,16 +241,24 @@ impl From<LocationError> for rspc::Error {
 			}
 
 			// User's fault errors
-			NotDirectory(_) | NestedLocation(_) | LocationAlreadyExists(_) => {
-				Self::with_cause(ErrorCode::BadRequest, err.to_string(), err)
-			}
+			NotDirectory(_)
+			| Overlapping { .. }
+			| OverlappingInBatch { .. }
+			| LocationAlreadyExists(_)
+			| Offline(_)
+			| ReadOnly(_)
+			| PrefixMismatch { .. }
+			| PathOutsideLocation(_)
+			| RejectedByIndexerRules(_) => Self::with_cause(ErrorCode::BadRequest, err.to_string(), err),
+
+			PermissionDenied(_) => Self::with_cause(ErrorCode::Forbidden, err.to_string(), err),
 
 			// Custom error message is used to differenciate these errors in the frontend
 			// TODO: A better solution would be for rspc to support sending custom data alongside errors
 			NeedRelink { .. } => {
 				Self::with_cause(ErrorCode::Conflict, "NEED_RELINK".to_owned(), err)
 			}
-			AddLibraryToMetadata(_) => {
+			AddLibraryToMetadata { .. } => {
 				Self::with_cause(ErrorCode::Conflict, "ADD_LIBRARY".to_owned(), err)
 			}
 
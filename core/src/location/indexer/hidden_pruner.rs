@@ -0,0 +1,172 @@
+use crate::{
+	invalidate_query,
+	job::{
+		CurrentStep, JobError, JobInitOutput, JobResult, JobRunMetadata, JobStepOutput,
+		StatefulJob, WorkerContext,
+	},
+	library::Library,
+	object::orphan_remover::remove_orphan_objects,
+};
+
+use sd_file_path_helper::{file_path_to_isolate_with_id, IsolatedFilePathData};
+use sd_prisma::prisma::{file_path, location, SortOrder};
+
+use std::hash::{Hash, Hasher};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use super::IndexerError;
+
+const BATCH_SIZE: usize = 100;
+
+/// Removes the hidden file paths of a location which no longer indexes them, the same ones the
+/// `No Hidden` rule skips, along with everything inside hidden directories.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HiddenPrunerJobInit {
+	pub location_id: location::id::Type,
+}
+
+impl Hash for HiddenPrunerJobInit {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.location_id.hash(state);
+	}
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct HiddenPrunerJobRunMetadata {
+	removed_count: u64,
+}
+
+impl JobRunMetadata for HiddenPrunerJobRunMetadata {
+	fn update(&mut self, new_data: Self) {
+		self.removed_count += new_data.removed_count;
+	}
+}
+
+#[async_trait::async_trait]
+impl StatefulJob for HiddenPrunerJobInit {
+	type Data = ();
+	type Step = Vec<file_path_to_isolate_with_id::Data>;
+	type RunMetadata = HiddenPrunerJobRunMetadata;
+
+	const NAME: &'static str = "hidden_pruner";
+
+	fn target_location(&self) -> location::id::Type {
+		self.location_id
+	}
+
+	async fn init(
+		&self,
+		ctx: &WorkerContext,
+		data: &mut Option<Self::Data>,
+	) -> Result<JobInitOutput<Self::RunMetadata, Self::Step>, JobError> {
+		let init = self;
+		let Library { db, .. } = &*ctx.library;
+
+		let hidden_file_paths = db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(Some(init.location_id)),
+				file_path::name::starts_with(".".to_string()),
+			])
+			.order_by(file_path::id::order(SortOrder::Asc))
+			.select(file_path_to_isolate_with_id::select())
+			.exec()
+			.await
+			.map_err(IndexerError::from)?;
+
+		let steps = hidden_file_paths
+			.into_iter()
+			.chunks(BATCH_SIZE)
+			.into_iter()
+			.map(|chunk| chunk.collect::<Vec<_>>())
+			.collect::<Vec<_>>();
+
+		// Must fill in the data, otherwise the job will not run
+		*data = Some(());
+
+		Ok(steps.into())
+	}
+
+	async fn execute_step(
+		&self,
+		ctx: &WorkerContext,
+		CurrentStep {
+			step: file_paths, ..
+		}: CurrentStep<'_, Self::Step>,
+		_: &Self::Data,
+		_: &Self::RunMetadata,
+	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
+		let init = self;
+		let Library { db, .. } = &*ctx.library;
+
+		let children_materialized_paths = file_paths
+			.iter()
+			.filter(|file_path| file_path.is_dir.unwrap_or_default())
+			.map(IsolatedFilePathData::try_from)
+			.filter_map_ok(|iso_file_path| iso_file_path.materialized_path_for_children())
+			.collect::<Result<Vec<_>, _>>()?;
+
+		// A hidden directory can be inside another one from the same step, which is fine as
+		// deleting what was already deleted doesn't do anything
+		let (removed_count, children_removed_counts) = db
+			._batch((
+				db.file_path().delete_many(vec![file_path::id::in_vec(
+					file_paths.iter().map(|file_path| file_path.id).collect(),
+				)]),
+				children_materialized_paths
+					.into_iter()
+					.map(|materialized_path| {
+						db.file_path().delete_many(vec![
+							file_path::location_id::equals(Some(init.location_id)),
+							file_path::materialized_path::starts_with(materialized_path),
+						])
+					})
+					.collect::<Vec<_>>(),
+			))
+			.await
+			.map_err(IndexerError::from)?;
+
+		Ok(HiddenPrunerJobRunMetadata {
+			removed_count: (removed_count + children_removed_counts.into_iter().sum::<i64>())
+				as u64,
+		}
+		.into())
+	}
+
+	async fn finalize(
+		&self,
+		ctx: &WorkerContext,
+		_: &Option<Self::Data>,
+		run_metadata: &Self::RunMetadata,
+	) -> JobResult {
+		let init = self;
+
+		// Objects whose only files were hidden aren't needed anymore
+		let orphans_removed = remove_orphan_objects(&ctx.library)
+			.await
+			.map_err(IndexerError::from)?
+			.removed;
+
+		info!(
+			"finalizing hidden pruner job: {} hidden file paths removed from <location_id={}>, \
+			{orphans_removed} orphaned objects removed",
+			run_metadata.removed_count, init.location_id
+		);
+
+		if run_metadata.removed_count > 0 {
+			invalidate_query!(ctx.library, "search.paths");
+			invalidate_query!(ctx.library, "search.objects");
+			invalidate_query!(ctx.library, "library.statistics");
+		}
+
+		Ok(Some(json!({
+			"init": init,
+			"run_metadata": run_metadata,
+			"orphans_removed": orphans_removed,
+		})))
+	}
+}
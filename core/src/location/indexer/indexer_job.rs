@@ -5,7 +5,10 @@ use crate::{
 		JobStepOutput, StatefulJob, WorkerContext,
 	},
 	library::Library,
-	location::{location_with_indexer_rules, update_location_size},
+	location::{
+		location_with_indexer_rules, normalization::location_is_case_insensitive,
+		symlink::SymlinkResolver, update_location_size,
+	},
 	to_remove_db_fetcher_fn,
 };
 
@@ -28,6 +31,7 @@ use std::{
 	time::Duration,
 };
 
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
 use prisma_client_rust::operator::or;
 use serde::{Deserialize, Serialize};
@@ -37,7 +41,7 @@ use tracing::{debug, info, warn};
 
 use super::{
 	execute_indexer_save_step, execute_indexer_update_step, iso_file_path_factory,
-	remove_non_existing_file_paths, reverse_update_directories_sizes,
+	location_indexer_rules, remove_non_existing_file_paths, reverse_update_directories_sizes,
 	rules::IndexerRule,
 	walk::{keep_walking, walk, ToWalkEntry, WalkResult},
 	IndexerError, IndexerJobSaveStep, IndexerJobUpdateStep,
@@ -53,6 +57,9 @@ const BATCH_SIZE: usize = 1000;
 pub struct IndexerJobInit {
 	pub location: location_with_indexer_rules::Data,
 	pub sub_path: Option<PathBuf>,
+	/// Skip walking into directories which weren't modified since the location's last full scan.
+	#[serde(default)]
+	pub skip_unchanged: bool,
 }
 
 impl Hash for IndexerJobInit {
@@ -61,6 +68,7 @@ impl Hash for IndexerJobInit {
 		if let Some(ref sub_path) = self.sub_path {
 			sub_path.hash(state);
 		}
+		self.skip_unchanged.hash(state);
 	}
 }
 /// `IndexerJobData` contains the state of the indexer job, which includes a `location_path` that
@@ -71,6 +79,10 @@ pub struct IndexerJobData {
 	location_path: PathBuf,
 	indexed_path: PathBuf,
 	indexer_rules: Vec<IndexerRule>,
+	skip_unchanged_since: Option<DateTime<Utc>>,
+	scan_started_at: DateTime<Utc>,
+	#[serde(default)]
+	case_insensitive: bool,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
@@ -106,6 +118,7 @@ impl JobRunMetadata for IndexerJobRunMetadata {
 
 #[derive(Clone)]
 pub enum ScanProgress {
+	Phase(&'static str),
 	ChunkCount(usize),
 	SavedChunks(usize),
 	UpdatedChunks(usize),
@@ -118,6 +131,7 @@ impl IndexerJobData {
 			progress
 				.into_iter()
 				.map(|p| match p {
+					ScanProgress::Phase(phase) => JobReportUpdate::Phase(phase.to_string()),
 					ScanProgress::ChunkCount(c) => JobReportUpdate::TaskCount(c),
 					ScanProgress::SavedChunks(p) | ScanProgress::UpdatedChunks(p) => {
 						JobReportUpdate::CompletedTaskCount(p)
@@ -165,13 +179,7 @@ impl StatefulJob for IndexerJobInit {
 
 		let db = Arc::clone(&ctx.library.db);
 
-		let indexer_rules = init
-			.location
-			.indexer_rules
-			.iter()
-			.map(|rule| IndexerRule::try_from(&rule.indexer_rule))
-			.collect::<Result<Vec<_>, _>>()
-			.map_err(IndexerError::from)?;
+		let indexer_rules = location_indexer_rules(&init.location).map_err(IndexerError::from)?;
 
 		let to_walk_path = match &init.sub_path {
 			Some(sub_path) if sub_path != Path::new("") => {
@@ -196,6 +204,24 @@ impl StatefulJob for IndexerJobInit {
 			_ => location_path.to_path_buf(),
 		};
 
+		let scan_started_at = Utc::now();
+		let skip_unchanged_since = init
+			.skip_unchanged
+			.then_some(init.location.scanned_at)
+			.flatten()
+			.map(Into::into);
+
+		let case_insensitive = location_is_case_insensitive(
+			&db,
+			location_id,
+			Some(location_path),
+			init.location.case_insensitive,
+		)
+		.await
+		.map_err(IndexerError::from)?;
+
+		IndexerJobData::on_scan_progress(ctx, vec![ScanProgress::Phase("walking")]);
+
 		let scan_start = Instant::now();
 		let WalkResult {
 			walked,
@@ -212,6 +238,9 @@ impl StatefulJob for IndexerJobInit {
 			to_remove_db_fetcher_fn!(location_id, &db),
 			iso_file_path_factory(location_id, location_path),
 			50_000,
+			skip_unchanged_since,
+			case_insensitive,
+			&SymlinkResolver::new(init.location.symlink_policy, location_path),
 		)
 		.await?;
 		let scan_read_time = scan_start.elapsed();
@@ -284,6 +313,8 @@ impl StatefulJob for IndexerJobInit {
 		IndexerJobData::on_scan_progress(
 			ctx,
 			vec![
+				// Deeper directories are walked along with the saving, as more steps
+				ScanProgress::Phase("saving"),
 				ScanProgress::ChunkCount(*to_save_chunks + *to_update_chunks),
 				ScanProgress::Message(format!(
 					"Starting saving {total_new_paths} files or directories, \
@@ -297,6 +328,9 @@ impl StatefulJob for IndexerJobInit {
 			location_path: location_path.to_path_buf(),
 			indexed_path: to_walk_path,
 			indexer_rules,
+			skip_unchanged_since,
+			scan_started_at,
+			case_insensitive,
 		});
 
 		Ok((
@@ -398,6 +432,9 @@ impl StatefulJob for IndexerJobInit {
 					file_paths_db_fetcher_fn!(&db),
 					to_remove_db_fetcher_fn!(location_id, &db),
 					iso_file_path_factory(location_id, location_path),
+					data.skip_unchanged_since,
+					data.case_insensitive,
+					&SymlinkResolver::new(init.location.symlink_policy, location_path),
 				)
 				.await?;
 
@@ -530,6 +567,23 @@ impl StatefulJob for IndexerJobInit {
 			}
 		}
 
+		if let Some(data) = data {
+			// Only a scan of the whole location tells us that nothing changed before it started
+			if data.indexed_path == data.location_path {
+				// Local only, as it's about this instance's filesystem
+				ctx.library
+					.db
+					.location()
+					.update(
+						location::id::equals(init.location.id),
+						vec![location::scanned_at::set(Some(data.scan_started_at.into()))],
+					)
+					.exec()
+					.await
+					.map_err(IndexerError::from)?;
+			}
+		}
+
 		Ok(Some(json!({"init: ": init, "run_metadata": run_metadata})))
 	}
 }
@@ -24,14 +24,16 @@ use tracing::{trace, warn};
 
 use super::location_with_indexer_rules;
 
+pub mod hidden_pruner;
 pub mod indexer_job;
 pub mod rules;
 mod shallow;
 mod walk;
 
-use rules::IndexerRuleError;
+use rules::{seed::no_hidden, IndexerRule, IndexerRuleError};
 use walk::WalkedEntry;
 
+pub use hidden_pruner::HiddenPrunerJobInit;
 pub use indexer_job::IndexerJobInit;
 pub use shallow::*;
 
@@ -83,6 +85,19 @@ impl From<IndexerError> for rspc::Error {
 	}
 }
 
+/// The location's indexer rules, along with the system `No Hidden` rule when it doesn't index
+/// hidden files.
+pub fn location_indexer_rules(
+	location: &location_with_indexer_rules::Data,
+) -> Result<Vec<IndexerRule>, IndexerRuleError> {
+	location
+		.indexer_rules
+		.iter()
+		.map(|rule| IndexerRule::try_from(&rule.indexer_rule))
+		.chain((!location.index_hidden.unwrap_or(true)).then(|| Ok(IndexerRule::from(no_hidden()))))
+		.collect()
+}
+
 async fn execute_indexer_save_step(
 	location: &location_with_indexer_rules::Data,
 	save_step: &IndexerJobSaveStep,
@@ -106,7 +121,7 @@ async fn execute_indexer_save_step(
 
 			let pub_id = sd_utils::uuid_to_bytes(entry.pub_id);
 
-			let (sync_params, db_params): (Vec<_>, Vec<_>) = [
+			let (sync_params, mut db_params): (Vec<_>, Vec<_>) = [
 				(
 					(
 						location::NAME,
@@ -159,6 +174,11 @@ async fn execute_indexer_save_step(
 			.into_iter()
 			.unzip();
 
+			// Not synced, as it depends on the filesystem of each instance's location
+			db_params.push(name_normalized::set(entry.name_normalized.clone()));
+			// Neither is the target, which is a path on this instance
+			db_params.push(symlink_target::set(entry.symlink_target.clone()));
+
 			(
 				sync.shared_create(
 					prisma_sync::file_path::SyncId {
@@ -194,7 +214,12 @@ async fn execute_indexer_update_step(
 		.to_update
 		.iter()
 		.map(|entry| async move {
-			let IsolatedFilePathDataParts { is_dir, .. } = &entry.iso_file_path.to_parts();
+			let IsolatedFilePathDataParts {
+				is_dir,
+				name,
+				extension,
+				..
+			} = &entry.iso_file_path.to_parts();
 
 			let pub_id = sd_utils::uuid_to_bytes(entry.pub_id);
 
@@ -209,7 +234,7 @@ async fn execute_indexer_update_step(
 
 			use file_path::*;
 
-			let (sync_params, db_params): (Vec<_>, Vec<_>) = [
+			let (sync_params, mut db_params): (Vec<_>, Vec<_>) = [
 				// As this file was updated while Spacedrive was offline, we mark the object_id and cas_id as null
 				// So this file_path will be updated at file identifier job
 				(
@@ -224,6 +249,16 @@ async fn execute_indexer_update_step(
 					(is_dir::NAME, json!(*is_dir)),
 					Some(is_dir::set(Some(*is_dir))),
 				),
+				// Can differ from the stored ones when the file was renamed to a name the
+				// filesystem considers the same, like only changing its case
+				(
+					(name::NAME, json!(name)),
+					Some(name::set(Some(name.to_string()))),
+				),
+				(
+					(extension::NAME, json!(extension)),
+					Some(extension::set(Some(extension.to_string()))),
+				),
 				(
 					(
 						size_in_bytes_bytes::NAME,
@@ -256,6 +291,9 @@ async fn execute_indexer_update_step(
 			})
 			.unzip();
 
+			db_params.push(name_normalized::set(entry.name_normalized.clone()));
+			db_params.push(symlink_target::set(entry.symlink_target.clone()));
+
 			Ok::<_, IndexerError>((
 				sync_params
 					.into_iter()
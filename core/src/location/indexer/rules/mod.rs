@@ -1,6 +1,6 @@
 use crate::library::Library;
 
-use sd_prisma::prisma::indexer_rule;
+use sd_prisma::prisma::{indexer_rule, indexer_rules_in_location, location};
 use sd_utils::{
 	db::{maybe_missing, MissingFieldError},
 	error::{FileIOError, NonUtf8PathError},
@@ -35,6 +35,10 @@ pub enum IndexerRuleError {
 	Glob(#[from] globset::Error),
 	#[error(transparent)]
 	NonUtf8Path(#[from] NonUtf8PathError),
+	#[error("indexer rule <id={0}> not found")]
+	IdNotFound(indexer_rule::id::Type),
+	#[error("indexer rule <id={0}> is a default one, it can't be edited")]
+	DefaultRule(indexer_rule::id::Type),
 
 	// Internal Errors
 	#[error("indexer rule parameters encode error: {0}")]
@@ -59,6 +63,12 @@ impl From<IndexerRuleError> for rspc::Error {
 			| IndexerRuleError::NonUtf8Path(_) => {
 				rspc::Error::with_cause(ErrorCode::BadRequest, err.to_string(), err)
 			}
+			IndexerRuleError::IdNotFound(_) => {
+				rspc::Error::with_cause(ErrorCode::NotFound, err.to_string(), err)
+			}
+			IndexerRuleError::DefaultRule(_) => {
+				rspc::Error::with_cause(ErrorCode::Forbidden, err.to_string(), err)
+			}
 
 			_ => rspc::Error::with_cause(ErrorCode::InternalServerError, err.to_string(), err),
 		}
@@ -66,25 +76,19 @@ impl From<IndexerRuleError> for rspc::Error {
 }
 
 /// `IndexerRuleCreateArgs` is the argument received from the client using rspc to create a new indexer rule.
-/// Note that `rules` field is a vector of tuples of `RuleKind` and `parameters`.
-///
-/// In case of  `RuleKind::AcceptFilesByGlob` or `RuleKind::RejectFilesByGlob`, it will be a
-/// vector of strings containing a glob patterns.
-///
-/// In case of `RuleKind::AcceptIfChildrenDirectoriesArePresent` or `RuleKind::RejectIfChildrenDirectoriesArePresent` the
-/// `parameters` field must be a vector of strings containing the names of the directories.
+/// Each of its `rules` is one of the kinds of rules with its parameters, see [`RuleParameters`].
 #[derive(Type, Deserialize)]
 pub struct IndexerRuleCreateArgs {
 	pub name: String,
 	pub dry_run: bool,
-	pub rules: Vec<(RuleKind, Vec<String>)>,
+	pub rules: Vec<RuleParameters>,
 }
 
 impl IndexerRuleCreateArgs {
 	pub async fn create(
 		self,
 		library: &Library,
-	) -> Result<Option<indexer_rule::Data>, IndexerRuleError> {
+	) -> Result<Option<SavedIndexerRule>, IndexerRuleError> {
 		debug!(
 			"{} a new indexer rule (name = {}, params = {:?})",
 			if self.dry_run {
@@ -96,30 +100,7 @@ impl IndexerRuleCreateArgs {
 			self.rules
 		);
 
-		let rules_data = rmp_serde::to_vec_named(
-			&self
-				.rules
-				.into_iter()
-				.map(|(kind, parameters)| match kind {
-					RuleKind::AcceptFilesByGlob => {
-						RulePerKind::new_accept_files_by_globs_str(parameters)
-					}
-					RuleKind::RejectFilesByGlob => {
-						RulePerKind::new_reject_files_by_globs_str(parameters)
-					}
-					RuleKind::AcceptIfChildrenDirectoriesArePresent => {
-						Ok(RulePerKind::AcceptIfChildrenDirectoriesArePresent(
-							parameters.into_iter().collect(),
-						))
-					}
-					RuleKind::RejectIfChildrenDirectoriesArePresent => {
-						Ok(RulePerKind::RejectIfChildrenDirectoriesArePresent(
-							parameters.into_iter().collect(),
-						))
-					}
-				})
-				.collect::<Result<Vec<_>, _>>()?,
-		)?;
+		let rules_data = encode_rules(self.rules)?;
 
 		if self.dry_run {
 			return Ok(None);
@@ -129,22 +110,258 @@ impl IndexerRuleCreateArgs {
 
 		use indexer_rule::*;
 
-		Ok(Some(
-			library
-				.db
-				.indexer_rule()
-				.create(
-					sd_utils::uuid_to_bytes(generate_pub_id()),
-					vec![
-						name::set(Some(self.name)),
-						rules_per_kind::set(Some(rules_data)),
-						date_created::set(Some(date_created.into())),
-						date_modified::set(Some(date_created.into())),
-					],
-				)
+		library
+			.db
+			.indexer_rule()
+			.create(
+				sd_utils::uuid_to_bytes(generate_pub_id()),
+				vec![
+					name::set(Some(self.name)),
+					rules_per_kind::set(Some(rules_data)),
+					date_created::set(Some(date_created.into())),
+					date_modified::set(Some(date_created.into())),
+				],
+			)
+			.exec()
+			.await
+			.map_err(Into::into)
+			.and_then(|data| SavedIndexerRule::new(&data, vec![]))
+			.map(Some)
+	}
+}
+
+/// `IndexerRuleUpdateArgs` is the argument received from the client using rspc to edit an indexer
+/// rule, the fields left out are kept as they are.
+///
+/// Default rules can't be edited, `locations.indexer_rules.resetDefaults` would undo it.
+#[derive(Type, Deserialize)]
+pub struct IndexerRuleUpdateArgs {
+	pub id: indexer_rule::id::Type,
+	pub name: Option<String>,
+	/// Replaces all of the rule's rules.
+	pub rules: Option<Vec<RuleParameters>>,
+}
+
+impl IndexerRuleUpdateArgs {
+	/// Changing the rules of an indexer rule changes what its locations index, so they're marked
+	/// to be fully rescanned next time, instead of skipping the directories which didn't change.
+	pub async fn update(self, library: &Library) -> Result<SavedIndexerRule, IndexerRuleError> {
+		let db = &library.db;
+
+		let indexer_rule = db
+			.indexer_rule()
+			.find_unique(indexer_rule::id::equals(self.id))
+			.exec()
+			.await?
+			.ok_or(IndexerRuleError::IdNotFound(self.id))?;
+
+		if indexer_rule.default.unwrap_or_default() {
+			return Err(IndexerRuleError::DefaultRule(self.id));
+		}
+
+		let rules_data = self.rules.map(encode_rules).transpose()?;
+		let rules_changed = rules_data.as_ref().map_or(false, |data| {
+			indexer_rule.rules_per_kind.as_ref() != Some(data)
+		});
+
+		debug!(
+			"Updating indexer rule <id='{}'> (name = {:?}, rules changed = {rules_changed})",
+			self.id, self.name
+		);
+
+		let indexer_rule = db
+			.indexer_rule()
+			.update(
+				indexer_rule::id::equals(self.id),
+				[
+					self.name.map(|name| indexer_rule::name::set(Some(name))),
+					rules_data.map(|data| indexer_rule::rules_per_kind::set(Some(data))),
+					Some(indexer_rule::date_modified::set(Some(Utc::now().into()))),
+				]
+				.into_iter()
+				.flatten()
+				.collect(),
+			)
+			.exec()
+			.await?;
+
+		let rescan_location_ids = if rules_changed {
+			let location_ids = db
+				.indexer_rules_in_location()
+				.find_many(vec![indexer_rules_in_location::indexer_rule_id::equals(
+					self.id,
+				)])
+				.select(indexer_rules_in_location::select!({ location_id }))
 				.exec()
-				.await?,
-		))
+				.await?
+				.into_iter()
+				.map(|rule_in_location| rule_in_location.location_id)
+				.collect::<Vec<_>>();
+
+			if !location_ids.is_empty() {
+				db.location()
+					.update_many(
+						vec![location::id::in_vec(location_ids.clone())],
+						vec![location::scanned_at::set(None)],
+					)
+					.exec()
+					.await?;
+			}
+
+			location_ids
+		} else {
+			vec![]
+		};
+
+		SavedIndexerRule::new(&indexer_rule, rescan_location_ids)
+	}
+}
+
+/// The parameters of each kind of rule.
+/// In case of doubt about globs, consult <https://docs.rs/globset/latest/globset/#syntax>
+#[derive(Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum RuleParameters {
+	AcceptFilesByGlob {
+		globs: Vec<String>,
+	},
+	RejectFilesByGlob {
+		globs: Vec<String>,
+	},
+	/// The names of the child directories.
+	AcceptIfChildrenDirectoriesArePresent {
+		children: Vec<String>,
+	},
+	/// The names of the child directories.
+	RejectIfChildrenDirectoriesArePresent {
+		children: Vec<String>,
+	},
+	AcceptIfAllOfRejectIfAnyOf {
+		accept: Vec<String>,
+		reject: Vec<String>,
+	},
+}
+
+impl RuleParameters {
+	pub fn kind(&self) -> RuleKind {
+		match self {
+			Self::AcceptFilesByGlob { .. } => RuleKind::AcceptFilesByGlob,
+			Self::RejectFilesByGlob { .. } => RuleKind::RejectFilesByGlob,
+			Self::AcceptIfChildrenDirectoriesArePresent { .. } => {
+				RuleKind::AcceptIfChildrenDirectoriesArePresent
+			}
+			Self::RejectIfChildrenDirectoriesArePresent { .. } => {
+				RuleKind::RejectIfChildrenDirectoriesArePresent
+			}
+			Self::AcceptIfAllOfRejectIfAnyOf { .. } => RuleKind::AcceptIfAllOfRejectIfAnyOf,
+		}
+	}
+}
+
+impl TryFrom<RuleParameters> for RulePerKind {
+	type Error = IndexerRuleError;
+
+	fn try_from(parameters: RuleParameters) -> Result<Self, Self::Error> {
+		match parameters {
+			RuleParameters::AcceptFilesByGlob { globs } => {
+				Self::new_accept_files_by_globs_str(globs)
+			}
+			RuleParameters::RejectFilesByGlob { globs } => {
+				Self::new_reject_files_by_globs_str(globs)
+			}
+			RuleParameters::AcceptIfChildrenDirectoriesArePresent { children } => Ok(
+				Self::AcceptIfChildrenDirectoriesArePresent(children.into_iter().collect()),
+			),
+			RuleParameters::RejectIfChildrenDirectoriesArePresent { children } => Ok(
+				Self::RejectIfChildrenDirectoriesArePresent(children.into_iter().collect()),
+			),
+			RuleParameters::AcceptIfAllOfRejectIfAnyOf { accept, reject } => {
+				Self::new_accept_if_all_of_reject_if_any_of_globs_str(accept, reject)
+			}
+		}
+	}
+}
+
+impl From<&RulePerKind> for RuleParameters {
+	fn from(rule: &RulePerKind) -> Self {
+		fn globs_str(globs: &[Glob]) -> Vec<String> {
+			globs.iter().map(|glob| glob.glob().to_string()).collect()
+		}
+
+		fn sorted(children: &HashSet<String>) -> Vec<String> {
+			let mut children = children.iter().cloned().collect::<Vec<_>>();
+			children.sort();
+			children
+		}
+
+		match rule {
+			RulePerKind::AcceptFilesByGlob(globs, _) => Self::AcceptFilesByGlob {
+				globs: globs_str(globs),
+			},
+			RulePerKind::RejectFilesByGlob(globs, _) => Self::RejectFilesByGlob {
+				globs: globs_str(globs),
+			},
+			RulePerKind::AcceptIfChildrenDirectoriesArePresent(children) => {
+				Self::AcceptIfChildrenDirectoriesArePresent {
+					children: sorted(children),
+				}
+			}
+			RulePerKind::RejectIfChildrenDirectoriesArePresent(children) => {
+				Self::RejectIfChildrenDirectoriesArePresent {
+					children: sorted(children),
+				}
+			}
+			RulePerKind::AcceptIfAllOfRejectIfAnyOf { accept, reject, .. } => {
+				Self::AcceptIfAllOfRejectIfAnyOf {
+					accept: globs_str(accept),
+					reject: globs_str(reject),
+				}
+			}
+		}
+	}
+}
+
+/// Validates the rules and serializes them to be stored, in the order they're evaluated.
+fn encode_rules(mut rules: Vec<RuleParameters>) -> Result<Vec<u8>, IndexerRuleError> {
+	rules.sort_by_key(|rule| rule.kind().evaluation_position());
+
+	rmp_serde::to_vec_named(
+		&rules
+			.into_iter()
+			.map(RulePerKind::try_from)
+			.collect::<Result<Vec<_>, _>>()?,
+	)
+	.map_err(Into::into)
+}
+
+/// An indexer rule once it's been created or updated.
+#[derive(Type, Serialize, Debug)]
+pub struct SavedIndexerRule {
+	pub id: indexer_rule::id::Type,
+	pub name: String,
+	/// In the order they're evaluated, see [`RuleKind::EVALUATION_ORDER`].
+	pub rules: Vec<RuleParameters>,
+	/// The locations using this rule which will be fully rescanned, as its rules changed.
+	pub rescan_location_ids: Vec<location::id::Type>,
+}
+
+impl SavedIndexerRule {
+	fn new(
+		data: &indexer_rule::Data,
+		rescan_location_ids: Vec<location::id::Type>,
+	) -> Result<Self, IndexerRuleError> {
+		let indexer_rule = IndexerRule::try_from(data)?;
+
+		Ok(Self {
+			id: data.id,
+			name: indexer_rule.name,
+			rules: indexer_rule
+				.rules
+				.iter()
+				.map(RuleParameters::from)
+				.collect(),
+			rescan_location_ids,
+		})
 	}
 }
 
@@ -156,12 +373,37 @@ pub enum RuleKind {
 	RejectFilesByGlob = 1,
 	AcceptIfChildrenDirectoriesArePresent = 2,
 	RejectIfChildrenDirectoriesArePresent = 3,
+	/// Accepts paths matching all of its accept globs, unless they match any of its reject globs,
+	/// so the reject globs always win.
+	///
+	/// A path is only indexed if every rule of this kind accepts it, regardless of the rules
+	/// order. Like `AcceptFilesByGlob`, directories not accepted are still walked into, use
+	/// `RejectFilesByGlob` to skip a directory and everything in it.
+	AcceptIfAllOfRejectIfAnyOf = 4,
 }
 
 impl RuleKind {
+	/// The order the indexer checks a path against each kind of rule, stopping at the first one
+	/// which rejects it: first the rejects, then the accepts. The ones by children directories
+	/// only apply to directories.
+	///
+	/// The rules of each indexer rule are stored in this order, the order of the indexer rules of
+	/// a location doesn't matter.
+	pub const EVALUATION_ORDER: [Self; Self::variant_count()] = [
+		Self::RejectFilesByGlob,
+		Self::RejectIfChildrenDirectoriesArePresent,
+		Self::AcceptIfChildrenDirectoriesArePresent,
+		Self::AcceptFilesByGlob,
+		Self::AcceptIfAllOfRejectIfAnyOf,
+	];
+
 	pub const fn variant_count() -> usize {
 		// TODO: Use https://doc.rust-lang.org/std/mem/fn.variant_count.html if it ever gets stabilized
-		4
+		5
+	}
+
+	fn evaluation_position(self) -> Option<usize> {
+		Self::EVALUATION_ORDER.iter().position(|kind| *kind == self)
 	}
 }
 
@@ -181,6 +423,12 @@ pub enum RulePerKind {
 	RejectFilesByGlob(Vec<Glob>, GlobSet),
 	AcceptIfChildrenDirectoriesArePresent(HashSet<String>),
 	RejectIfChildrenDirectoriesArePresent(HashSet<String>),
+	AcceptIfAllOfRejectIfAnyOf {
+		accept: Vec<Glob>,
+		accept_glob_set: GlobSet,
+		reject: Vec<Glob>,
+		reject_glob_set: GlobSet,
+	},
 }
 
 impl RulePerKind {
@@ -188,22 +436,22 @@ impl RulePerKind {
 		globs_str: impl IntoIterator<Item = impl AsRef<str>>,
 		kind_fn: impl Fn(Vec<Glob>, GlobSet) -> Self,
 	) -> Result<Self, IndexerRuleError> {
-		globs_str
-			.into_iter()
-			.map(|s| s.as_ref().parse::<Glob>())
-			.collect::<Result<Vec<_>, _>>()
-			.and_then(|globs| {
-				globs
-					.iter()
-					.cloned()
-					.fold(&mut GlobSetBuilder::new(), |builder, glob| {
-						builder.add(glob)
-					})
-					.build()
-					.map(move |glob_set| kind_fn(globs, glob_set))
-					.map_err(Into::into)
-			})
-			.map_err(Into::into)
+		globs_and_glob_set(globs_str).map(|(globs, glob_set)| kind_fn(globs, glob_set))
+	}
+
+	pub fn new_accept_if_all_of_reject_if_any_of_globs_str(
+		accept_globs_str: impl IntoIterator<Item = impl AsRef<str>>,
+		reject_globs_str: impl IntoIterator<Item = impl AsRef<str>>,
+	) -> Result<Self, IndexerRuleError> {
+		let (accept, accept_glob_set) = globs_and_glob_set(accept_globs_str)?;
+		let (reject, reject_glob_set) = globs_and_glob_set(reject_globs_str)?;
+
+		Ok(Self::AcceptIfAllOfRejectIfAnyOf {
+			accept,
+			accept_glob_set,
+			reject,
+			reject_glob_set,
+		})
 	}
 
 	pub fn new_accept_files_by_globs_str(
@@ -219,6 +467,28 @@ impl RulePerKind {
 	}
 }
 
+fn globs_and_glob_set(
+	globs_str: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<(Vec<Glob>, GlobSet), IndexerRuleError> {
+	let globs = globs_str
+		.into_iter()
+		.map(|s| s.as_ref().parse::<Glob>())
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let glob_set = build_glob_set(&globs)?;
+
+	Ok((globs, glob_set))
+}
+
+fn build_glob_set(globs: &[Glob]) -> Result<GlobSet, globset::Error> {
+	globs
+		.iter()
+		.fold(&mut GlobSetBuilder::new(), |builder, glob| {
+			builder.add(glob.to_owned())
+		})
+		.build()
+}
+
 /// We're implementing `Serialize` by hand as `GlobSet`s aren't serializable, so we ignore them on
 /// serialization
 impl Serialize for RulePerKind {
@@ -245,6 +515,16 @@ impl Serialize for RulePerKind {
 					"RejectIfChildrenDirectoriesArePresent",
 					children,
 				),
+			RulePerKind::AcceptIfAllOfRejectIfAnyOf {
+				ref accept,
+				ref reject,
+				..
+			} => serializer.serialize_newtype_variant(
+				"ParametersPerKind",
+				4,
+				"AcceptIfAllOfRejectIfAnyOf",
+				&(accept, reject),
+			),
 		}
 	}
 }
@@ -259,6 +539,7 @@ impl<'de> Deserialize<'de> for RulePerKind {
 			"RejectFilesByGlob",
 			"AcceptIfChildrenDirectoriesArePresent",
 			"RejectIfChildrenDirectoriesArePresent",
+			"AcceptIfAllOfRejectIfAnyOf",
 		];
 
 		enum Fields {
@@ -266,6 +547,7 @@ impl<'de> Deserialize<'de> for RulePerKind {
 			RejectFilesByGlob,
 			AcceptIfChildrenDirectoriesArePresent,
 			RejectIfChildrenDirectoriesArePresent,
+			AcceptIfAllOfRejectIfAnyOf,
 		}
 
 		struct FieldsVisitor;
@@ -278,7 +560,8 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					"`AcceptFilesByGlob` \
 				or `RejectFilesByGlob` \
 				or `AcceptIfChildrenDirectoriesArePresent` \
-				or `RejectIfChildrenDirectoriesArePresent`",
+				or `RejectIfChildrenDirectoriesArePresent` \
+				or `AcceptIfAllOfRejectIfAnyOf`",
 				)
 			}
 
@@ -291,9 +574,10 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					1 => Ok(Fields::RejectFilesByGlob),
 					2 => Ok(Fields::AcceptIfChildrenDirectoriesArePresent),
 					3 => Ok(Fields::RejectIfChildrenDirectoriesArePresent),
+					4 => Ok(Fields::AcceptIfAllOfRejectIfAnyOf),
 					_ => Err(de::Error::invalid_value(
 						de::Unexpected::Unsigned(value),
-						&"variant index 0 <= i < 3",
+						&"variant index 0 <= i < 5",
 					)),
 				}
 			}
@@ -310,6 +594,7 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					"RejectIfChildrenDirectoriesArePresent" => {
 						Ok(Fields::RejectIfChildrenDirectoriesArePresent)
 					}
+					"AcceptIfAllOfRejectIfAnyOf" => Ok(Fields::AcceptIfAllOfRejectIfAnyOf),
 					_ => Err(de::Error::unknown_variant(value, VARIANTS)),
 				}
 			}
@@ -326,6 +611,7 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					b"RejectIfChildrenDirectoriesArePresent" => {
 						Ok(Fields::RejectIfChildrenDirectoriesArePresent)
 					}
+					b"AcceptIfAllOfRejectIfAnyOf" => Ok(Fields::AcceptIfAllOfRejectIfAnyOf),
 					_ => Err(de::Error::unknown_variant(
 						&String::from_utf8_lossy(bytes),
 						VARIANTS,
@@ -411,6 +697,24 @@ impl<'de> Deserialize<'de> for RulePerKind {
 						reject_if_children_directories_are_present,
 					)
 					.map(Self::Value::RejectIfChildrenDirectoriesArePresent),
+					(Fields::AcceptIfAllOfRejectIfAnyOf, accept_if_all_of_reject_if_any_of) => {
+						de::VariantAccess::newtype_variant::<(Vec<Glob>, Vec<Glob>)>(
+							accept_if_all_of_reject_if_any_of,
+						)
+						.and_then(|(accept, reject)| {
+							let accept_glob_set =
+								build_glob_set(&accept).map_err(PPK::Error::custom)?;
+							let reject_glob_set =
+								build_glob_set(&reject).map_err(PPK::Error::custom)?;
+
+							Ok(Self::Value::AcceptIfAllOfRejectIfAnyOf {
+								accept,
+								accept_glob_set,
+								reject,
+								reject_glob_set,
+							})
+						})
+					}
 				})
 			}
 		}
@@ -448,6 +752,20 @@ impl RulePerKind {
 				RuleKind::RejectFilesByGlob,
 				reject_by_glob(source, reject_glob_set),
 			)),
+			RulePerKind::AcceptIfAllOfRejectIfAnyOf {
+				accept,
+				accept_glob_set,
+				reject_glob_set,
+				..
+			} => Ok((
+				RuleKind::AcceptIfAllOfRejectIfAnyOf,
+				accept_if_all_of_reject_if_any_of(
+					source,
+					accept.len(),
+					accept_glob_set,
+					reject_glob_set,
+				),
+			)),
 		}
 	}
 }
@@ -470,6 +788,12 @@ impl IndexerRule {
 		try_join_all(self.rules.iter().map(|rule| rule.apply(source.as_ref()))).await
 	}
 
+	/// The results of every rule for the path, grouped by kind, where `true` always means the
+	/// path passed the rule, even for the reject kinds.
+	///
+	/// How the results of a kind are combined is up to the caller: a path is rejected if any of
+	/// the reject kinds results is `false`, and accepted if any of the accept kinds results is
+	/// `true`, except for `RuleKind::AcceptIfAllOfRejectIfAnyOf` where all of them must be `true`.
 	pub async fn apply_all(
 		rules: &[IndexerRule],
 		source: impl AsRef<Path>,
@@ -522,6 +846,19 @@ fn reject_by_glob(source: impl AsRef<Path>, reject_glob_set: &GlobSet) -> bool {
 	!accept_by_glob(source.as_ref(), reject_glob_set)
 }
 
+/// The reject globs are checked first, so a path matching any of them is rejected even if it
+/// matches all the accept globs.
+fn accept_if_all_of_reject_if_any_of(
+	source: impl AsRef<Path>,
+	accept_globs_count: usize,
+	accept_glob_set: &GlobSet,
+	reject_glob_set: &GlobSet,
+) -> bool {
+	let source = source.as_ref();
+
+	!reject_glob_set.is_match(source) && accept_glob_set.matches(source).len() == accept_globs_count
+}
+
 async fn accept_dir_for_its_children(
 	source: impl AsRef<Path>,
 	children: &HashSet<String>,
@@ -795,6 +1132,61 @@ mod tests {
 		assert!(check_rule(&rule, not_project).await);
 	}
 
+	#[tokio::test]
+	async fn test_accept_if_all_of_reject_if_any_of() {
+		let photo = Path::new("/test/photos/photo1.png");
+		let other_photo = Path::new("/test/documents/photo2.png");
+		let edited_photo = Path::new("/test/photos/edited/photo3.png");
+		let text = Path::new("/test/photos/file.txt");
+
+		let photos = || {
+			IndexerRule::new(
+				"photos but the edited ones".to_string(),
+				false,
+				vec![
+					RulePerKind::new_accept_if_all_of_reject_if_any_of_globs_str(
+						["**/photos/**", "*.png"],
+						["**/edited/**"],
+					)
+					.unwrap(),
+				],
+			)
+		};
+		let pngs = || {
+			IndexerRule::new(
+				"pngs".to_string(),
+				false,
+				vec![
+					RulePerKind::new_accept_if_all_of_reject_if_any_of_globs_str(
+						["*.png"],
+						[] as [&str; 0],
+					)
+					.unwrap(),
+				],
+			)
+		};
+
+		let rule = photos();
+		assert!(check_rule(&rule, photo).await);
+		assert!(!check_rule(&rule, other_photo).await);
+		assert!(!check_rule(&rule, text).await);
+		// Reject wins
+		assert!(!check_rule(&rule, edited_photo).await);
+
+		// The order of the rules doesn't matter, every one of them must accept the path
+		for rules in [[pngs(), photos()], [photos(), pngs()]] {
+			let results = IndexerRule::apply_all(&rules, edited_photo).await.unwrap();
+			assert!(results[&RuleKind::AcceptIfAllOfRejectIfAnyOf]
+				.iter()
+				.any(|accept| !accept));
+
+			let results = IndexerRule::apply_all(&rules, photo).await.unwrap();
+			assert!(results[&RuleKind::AcceptIfAllOfRejectIfAnyOf]
+				.iter()
+				.all(|accept| *accept));
+		}
+	}
+
 	impl PartialEq for RulePerKind {
 		fn eq(&self, other: &Self) -> bool {
 			match (self, other) {
@@ -814,6 +1206,18 @@ mod tests {
 					RulePerKind::RejectIfChildrenDirectoriesArePresent(self_childrens),
 					RulePerKind::RejectIfChildrenDirectoriesArePresent(other_childrens),
 				) => self_childrens == other_childrens,
+				(
+					RulePerKind::AcceptIfAllOfRejectIfAnyOf {
+						accept: self_accept,
+						reject: self_reject,
+						..
+					},
+					RulePerKind::AcceptIfAllOfRejectIfAnyOf {
+						accept: other_accept,
+						reject: other_reject,
+						..
+					},
+				) => self_accept == other_accept && self_reject == other_reject,
 				_ => false,
 			}
 		}
@@ -834,17 +1238,67 @@ mod tests {
 
 	impl Eq for IndexerRule {}
 
+	#[test]
+	fn test_rules_are_stored_in_evaluation_order() {
+		let rules = vec![
+			RuleParameters::AcceptIfAllOfRejectIfAnyOf {
+				accept: vec!["**/photos/**".to_string()],
+				reject: vec!["**/edited/**".to_string()],
+			},
+			RuleParameters::AcceptFilesByGlob {
+				globs: vec!["*.png".to_string()],
+			},
+			RuleParameters::RejectIfChildrenDirectoriesArePresent {
+				children: vec![".git".to_string(), "node_modules".to_string()],
+			},
+			RuleParameters::RejectFilesByGlob {
+				globs: vec!["**/.*".to_string()],
+			},
+		];
+
+		let stored =
+			rmp_serde::from_slice::<Vec<RulePerKind>>(&encode_rules(rules.clone()).unwrap())
+				.unwrap()
+				.iter()
+				.map(RuleParameters::from)
+				.collect::<Vec<_>>();
+
+		assert_eq!(
+			stored,
+			vec![
+				rules[3].clone(),
+				rules[2].clone(),
+				rules[1].clone(),
+				rules[0].clone()
+			]
+		);
+
+		assert!(matches!(
+			encode_rules(vec![RuleParameters::RejectFilesByGlob {
+				globs: vec!["[".to_string()]
+			}]),
+			Err(IndexerRuleError::Glob(_))
+		));
+	}
+
 	#[test]
 	fn serde_smoke_test() {
 		let actual = IndexerRule::new(
 			"No Hidden".to_string(),
 			true,
-			vec![RulePerKind::RejectFilesByGlob(
-				vec![Glob::new("**/.*").unwrap()],
-				Glob::new("**/.*")
-					.and_then(|glob| GlobSetBuilder::new().add(glob).build())
-					.unwrap(),
-			)],
+			vec![
+				RulePerKind::RejectFilesByGlob(
+					vec![Glob::new("**/.*").unwrap()],
+					Glob::new("**/.*")
+						.and_then(|glob| GlobSetBuilder::new().add(glob).build())
+						.unwrap(),
+				),
+				RulePerKind::new_accept_if_all_of_reject_if_any_of_globs_str(
+					["**/photos/**", "*.png"],
+					["**/edited/**"],
+				)
+				.unwrap(),
+			],
 		);
 
 		let expected =
@@ -36,14 +36,22 @@ impl From<SystemIndexerRule> for IndexerRule {
 	}
 }
 
-/// Seeds system indexer rules into a new or existing library,
-pub async fn new_or_existing_library(library: &Library) -> Result<(), SeederError> {
+/// The system indexer rules with their fixed pub_ids, which the V0 to V1 library config migration
+/// also assigned to existing rules by name.
+fn system_indexer_rules() -> Vec<(Uuid, SystemIndexerRule)> {
 	// DO NOT REORDER THIS ARRAY!
-	for (i, rule) in [no_os_protected(), no_hidden(), no_git(), only_images()]
+	[no_os_protected(), no_hidden(), no_git(), only_images()]
 		.into_iter()
 		.enumerate()
-	{
-		let pub_id = sd_utils::uuid_to_bytes(Uuid::from_u128(i as u128));
+		.map(|(i, rule)| (Uuid::from_u128(i as u128), rule))
+		.collect()
+}
+
+/// Seeds system indexer rules into a new or existing library, re-creating the ones which were
+/// deleted and resetting the others to their defaults.
+pub async fn new_or_existing_library(library: &Library) -> Result<(), SeederError> {
+	for (pub_id, rule) in system_indexer_rules() {
+		let pub_id = sd_utils::uuid_to_bytes(pub_id);
 		let rules = rmp_serde::to_vec_named(&rule.rules).map_err(IndexerRuleError::from)?;
 
 		use indexer_rule::*;
@@ -207,3 +215,26 @@ fn only_images() -> SystemIndexerRule {
 		.expect("this is hardcoded and should always work")],
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_system_indexer_rules_match_migration() {
+		// The V0 to V1 library config migration gave existing rules these pub_ids by name, so the
+		// seed must keep using the same ones or it would duplicate them
+		assert_eq!(
+			system_indexer_rules()
+				.into_iter()
+				.map(|(pub_id, rule)| (pub_id, rule.name))
+				.collect::<Vec<_>>(),
+			vec![
+				(Uuid::from_u128(0), "No OS protected"),
+				(Uuid::from_u128(1), "No Hidden"),
+				(Uuid::from_u128(2), "No Git"),
+				(Uuid::from_u128(3), "Only Images"),
+			]
+		);
+	}
+}
@@ -6,7 +6,10 @@ use crate::{
 		indexer::{
 			execute_indexer_update_step, reverse_update_directories_sizes, IndexerJobUpdateStep,
 		},
-		scan_location_sub_path, update_location_size,
+		normalization::location_is_case_insensitive,
+		scan_location_sub_path,
+		symlink::SymlinkResolver,
+		update_location_size,
 	},
 	to_remove_db_fetcher_fn, Node,
 };
@@ -28,31 +31,30 @@ use itertools::Itertools;
 use tracing::{debug, error};
 
 use super::{
-	execute_indexer_save_step, iso_file_path_factory, location_with_indexer_rules,
-	remove_non_existing_file_paths, rules::IndexerRule, walk::walk_single_dir, IndexerError,
-	IndexerJobSaveStep,
+	execute_indexer_save_step, iso_file_path_factory, location_indexer_rules,
+	location_with_indexer_rules, remove_non_existing_file_paths,
+	walk::{walk_single_dir, walk_single_file},
+	IndexerError, IndexerJobSaveStep,
 };
 
 /// BATCH_SIZE is the number of files to index at each step, writing the chunk of files metadata in the database.
 const BATCH_SIZE: usize = 1000;
 
+/// Indexes the entries directly inside `sub_path`, returning how many of them were created or
+/// updated. New directories found are scanned by their own jobs if `scan_new_directories` is set.
 pub async fn shallow(
 	location: &location_with_indexer_rules::Data,
 	sub_path: &PathBuf,
 	node: &Arc<Node>,
 	library: &Arc<Library>,
-) -> Result<(), JobError> {
+	scan_new_directories: bool,
+) -> Result<usize, JobError> {
 	let location_id = location.id;
 	let location_path = maybe_missing(&location.path, "location.path").map(Path::new)?;
 
 	let db = library.db.clone();
 
-	let indexer_rules = location
-		.indexer_rules
-		.iter()
-		.map(|rule| IndexerRule::try_from(&rule.indexer_rule))
-		.collect::<Result<Vec<_>, _>>()
-		.map_err(IndexerError::from)?;
+	let indexer_rules = location_indexer_rules(location).map_err(IndexerError::from)?;
 
 	let (add_root, to_walk_path) = if sub_path != Path::new("") && sub_path != Path::new("/") {
 		let full_path = ensure_sub_path_is_in_location(&location_path, &sub_path)
@@ -75,6 +77,15 @@ pub async fn shallow(
 		(false, location_path.to_path_buf())
 	};
 
+	let case_insensitive = location_is_case_insensitive(
+		&db,
+		location_id,
+		Some(location_path),
+		location.case_insensitive,
+	)
+	.await
+	.map_err(IndexerError::from)?;
+
 	let (walked, to_update, to_remove, errors, _s) = {
 		walk_single_dir(
 			&to_walk_path,
@@ -84,6 +95,8 @@ pub async fn shallow(
 			to_remove_db_fetcher_fn!(location_id, &db),
 			iso_file_path_factory(location_id, location_path),
 			add_root,
+			case_insensitive,
+			&SymlinkResolver::new(location.symlink_policy, location_path),
 		)
 		.await?
 	};
@@ -117,14 +130,16 @@ pub async fn shallow(
 			let walked = chunk.collect::<Vec<_>>();
 			to_create_count += walked.len();
 
-			walked
-				.iter()
-				.filter_map(|walked_entry| {
-					walked_entry.iso_file_path.materialized_path_for_children()
-				})
-				.for_each(|new_dir| {
-					new_directories_to_scan.insert(new_dir);
-				});
+			if scan_new_directories {
+				walked
+					.iter()
+					.filter_map(|walked_entry| {
+						walked_entry.iso_file_path.materialized_path_for_children()
+					})
+					.for_each(|new_dir| {
+						new_directories_to_scan.insert(new_dir);
+					});
+			}
 
 			IndexerJobSaveStep {
 				chunk_idx: i,
@@ -192,5 +207,95 @@ pub async fn shallow(
 
 	// library.orphan_remover.invoke().await;
 
-	Ok(())
+	Ok(to_create_count + to_update_count)
+}
+
+/// Indexes a single file without reading the rest of its directory, returning how many entries
+/// were created or updated, counting the ancestors which weren't indexed yet. Returns `None` if
+/// the location's indexer rules reject the file.
+pub async fn shallow_file(
+	location: &location_with_indexer_rules::Data,
+	sub_path: &Path,
+	library: &Arc<Library>,
+) -> Result<Option<usize>, JobError> {
+	let location_id = location.id;
+	let location_path = maybe_missing(&location.path, "location.path").map(Path::new)?;
+
+	let db = library.db.clone();
+
+	let indexer_rules = location_indexer_rules(location).map_err(IndexerError::from)?;
+
+	let full_path = ensure_sub_path_is_in_location(&location_path, &sub_path)
+		.await
+		.map_err(IndexerError::from)?;
+
+	let case_insensitive = location_is_case_insensitive(
+		&db,
+		location_id,
+		Some(location_path),
+		location.case_insensitive,
+	)
+	.await
+	.map_err(IndexerError::from)?;
+
+	let Some((walked, to_update)) = walk_single_file(
+		location_path,
+		&full_path,
+		&indexer_rules,
+		file_paths_db_fetcher_fn!(&db),
+		iso_file_path_factory(location_id, location_path),
+		case_insensitive,
+		&SymlinkResolver::new(location.symlink_policy, location_path),
+	)
+	.await?
+	else {
+		return Ok(None);
+	};
+
+	let (to_create_count, to_update_count) = (walked.len(), to_update.len());
+
+	if to_create_count > 0 {
+		execute_indexer_save_step(
+			location,
+			&IndexerJobSaveStep {
+				chunk_idx: 0,
+				walked,
+			},
+			library,
+		)
+		.await?;
+	}
+
+	if to_update_count > 0 {
+		execute_indexer_update_step(
+			&IndexerJobUpdateStep {
+				chunk_idx: 0,
+				to_update,
+			},
+			library,
+		)
+		.await?;
+	}
+
+	debug!(
+		"Single file indexer for {}: To create: {to_create_count}; To update: {to_update_count};",
+		full_path.display()
+	);
+
+	if to_create_count > 0 || to_update_count > 0 {
+		if let Some(parent) = full_path.parent().filter(|&parent| parent != location_path) {
+			reverse_update_directories_sizes(parent, location_id, location_path, library)
+				.await
+				.map_err(IndexerError::from)?;
+		}
+
+		update_location_size(location.id, library)
+			.await
+			.map_err(IndexerError::from)?;
+
+		invalidate_query!(library, "search.paths");
+		invalidate_query!(library, "search.objects");
+	}
+
+	Ok(Some(to_create_count + to_update_count))
 }
@@ -1,3 +1,5 @@
+use crate::location::symlink::{DirId, ResolvedSymlink, SymlinkResolver};
+
 use sd_file_path_helper::{
 	file_path_pub_and_cas_ids, file_path_walker, FilePathMetadata, IsolatedFilePathData,
 };
@@ -11,7 +13,7 @@ use std::{
 	path::{Path, PathBuf},
 };
 
-use chrono::{DateTime, Duration, FixedOffset};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tracing::trace;
@@ -34,6 +36,12 @@ pub struct WalkedEntry {
 	pub maybe_object_id: file_path::object_id::Type,
 	pub iso_file_path: IsolatedFilePathData<'static>,
 	pub metadata: FilePathMetadata,
+	/// See [`IsolatedFilePathData::normalized_name`], missing on steps of jobs paused before it
+	#[serde(default)]
+	pub name_normalized: Option<String>,
+	/// Set for symlinks indexed as links, see [`SymlinkPolicy`](crate::location::SymlinkPolicy)
+	#[serde(default)]
+	pub symlink_target: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,12 +49,16 @@ pub struct ToWalkEntry {
 	path: PathBuf,
 	parent_dir_accepted_by_its_children: Option<bool>,
 	maybe_parent: Option<PathBuf>,
+	/// The symlinked directories followed to get here, see [`SymlinkResolver::resolve`]
+	#[serde(default)]
+	followed_links: Vec<DirId>,
 }
 
 #[derive(Debug)]
 struct WalkingEntry {
 	iso_file_path: IsolatedFilePathData<'static>,
 	maybe_metadata: Option<FilePathMetadata>,
+	symlink_target: Option<String>,
 }
 
 impl From<WalkingEntry> for WalkedEntry {
@@ -54,6 +66,7 @@ impl From<WalkingEntry> for WalkedEntry {
 		let WalkingEntry {
 			iso_file_path,
 			maybe_metadata,
+			symlink_target,
 		} = walking_entry;
 
 		Self {
@@ -62,6 +75,8 @@ impl From<WalkingEntry> for WalkedEntry {
 			iso_file_path,
 			metadata: maybe_metadata
 				.expect("we always use Some in `the inner_walk_single_dir` function"),
+			name_normalized: None,
+			symlink_target,
 		}
 	}
 }
@@ -73,6 +88,7 @@ impl From<(Uuid, file_path::object_id::Type, WalkingEntry)> for WalkedEntry {
 		let WalkingEntry {
 			iso_file_path,
 			maybe_metadata,
+			symlink_target,
 		} = walking_entry;
 
 		Self {
@@ -81,10 +97,19 @@ impl From<(Uuid, file_path::object_id::Type, WalkingEntry)> for WalkedEntry {
 			iso_file_path,
 			metadata: maybe_metadata
 				.expect("we always use Some in `the inner_walk_single_dir` function"),
+			name_normalized: None,
+			symlink_target,
 		}
 	}
 }
 
+impl WalkedEntry {
+	fn with_normalized_name(mut self, case_insensitive: bool) -> Self {
+		self.name_normalized = Some(self.iso_file_path.normalized_name(case_insensitive));
+		self
+	}
+}
+
 impl PartialEq for WalkingEntry {
 	fn eq(&self, other: &Self) -> bool {
 		self.iso_file_path == other.iso_file_path
@@ -127,6 +152,9 @@ pub(super) async fn walk<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 	) -> ToRemoveDbFetcherFut,
 	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
 	limit: u64,
+	skip_unchanged_since: Option<DateTime<Utc>>,
+	case_insensitive: bool,
+	symlinks: &SymlinkResolver,
 ) -> Result<
 	WalkResult<
 		impl Iterator<Item = WalkedEntry>,
@@ -147,6 +175,7 @@ where
 		path: root.to_path_buf(),
 		parent_dir_accepted_by_its_children: None,
 		maybe_parent: None,
+		followed_links: vec![],
 	});
 	let mut indexed_paths = HashSet::with_capacity(WALKER_PATHS_BUFFER_INITIAL_CAPACITY);
 	let mut errors = vec![];
@@ -160,8 +189,12 @@ where
 			&entry,
 			indexer_rules,
 			&mut update_notifier,
+			&file_paths_db_fetcher,
 			&to_remove_db_fetcher,
 			&iso_file_path_factory,
+			skip_unchanged_since,
+			case_insensitive,
+			symlinks,
 			WorkingTable {
 				indexed_paths: &mut indexed_paths,
 				paths_buffer: &mut paths_buffer,
@@ -185,7 +218,8 @@ where
 		}
 	}
 
-	let (walked, to_update) = filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?;
+	let (walked, to_update) =
+		filter_existing_paths(indexed_paths, file_paths_db_fetcher, case_insensitive).await?;
 
 	Ok(WalkResult {
 		walked,
@@ -207,6 +241,9 @@ pub(super) async fn keep_walking<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 		Vec<file_path::WhereParam>,
 	) -> ToRemoveDbFetcherFut,
 	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
+	skip_unchanged_since: Option<DateTime<Utc>>,
+	case_insensitive: bool,
+	symlinks: &SymlinkResolver,
 ) -> Result<
 	WalkResult<
 		impl Iterator<Item = WalkedEntry>,
@@ -230,8 +267,12 @@ where
 		to_walk_entry,
 		indexer_rules,
 		&mut update_notifier,
+		&file_paths_db_fetcher,
 		&to_remove_db_fetcher,
 		&iso_file_path_factory,
+		skip_unchanged_since,
+		case_insensitive,
+		symlinks,
 		WorkingTable {
 			indexed_paths: &mut indexed_paths,
 			paths_buffer: &mut paths_buffer,
@@ -241,7 +282,8 @@ where
 	)
 	.await;
 
-	let (walked, to_update) = filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?;
+	let (walked, to_update) =
+		filter_existing_paths(indexed_paths, file_paths_db_fetcher, case_insensitive).await?;
 
 	Ok(WalkResult {
 		walked,
@@ -273,6 +315,8 @@ pub(super) async fn walk_single_dir<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 	) -> ToRemoveDbFetcherFut,
 	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
 	add_root: bool,
+	case_insensitive: bool,
+	symlinks: &SymlinkResolver,
 ) -> Result<
 	(
 		impl Iterator<Item = WalkedEntry>,
@@ -300,6 +344,7 @@ where
 		indexed_paths.insert(WalkingEntry {
 			iso_file_path: iso_file_path_factory(root, true)?,
 			maybe_metadata: Some(FilePathMetadata::from_path(&root, &metadata).await?),
+			symlink_target: None,
 		});
 	}
 
@@ -312,11 +357,16 @@ where
 			path: root.to_path_buf(),
 			parent_dir_accepted_by_its_children: None,
 			maybe_parent: None,
+			followed_links: vec![],
 		},
 		indexer_rules,
 		&mut update_notifier,
+		&file_paths_db_fetcher,
 		&to_remove_db_fetcher,
 		&iso_file_path_factory,
+		None,
+		case_insensitive,
+		symlinks,
 		WorkingTable {
 			indexed_paths: &mut indexed_paths,
 			paths_buffer: &mut paths_buffer,
@@ -326,14 +376,139 @@ where
 	)
 	.await;
 
-	let (walked, to_update) = filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?;
+	let (walked, to_update) =
+		filter_existing_paths(indexed_paths, file_paths_db_fetcher, case_insensitive).await?;
 
 	Ok((walked, to_update, to_remove, errors, root_size))
 }
 
+/// Checks a single file against the rules, without reading its siblings, returning the entries to
+/// create and to update for it. Its ancestors up to `root` which aren't indexed yet are created
+/// along with it.
+///
+/// Returns `None` if the rules reject the file or its symlink is skipped.
+pub(super) async fn walk_single_file<FilePathDBFetcherFut>(
+	root: impl AsRef<Path>,
+	path: impl AsRef<Path>,
+	indexer_rules: &[IndexerRule],
+	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
+	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
+	case_insensitive: bool,
+	symlinks: &SymlinkResolver,
+) -> Result<Option<(Vec<WalkedEntry>, Vec<WalkedEntry>)>, IndexerError>
+where
+	FilePathDBFetcherFut: Future<Output = Result<Vec<file_path_walker::Data>, IndexerError>>,
+{
+	let (root, path) = (root.as_ref(), path.as_ref());
+
+	let rules_per_kind = IndexerRule::apply_all(indexer_rules, path).await?;
+
+	let rejected = [
+		(RuleKind::RejectFilesByGlob, false),
+		(RuleKind::AcceptFilesByGlob, true),
+		(RuleKind::AcceptIfAllOfRejectIfAnyOf, false),
+	]
+	.into_iter()
+	.any(|(kind, rejected_unless_any_accepts)| {
+		rules_per_kind.get(&kind).map_or(false, |results| {
+			if rejected_unless_any_accepts {
+				results.iter().all(|accept| !accept)
+			} else {
+				results.iter().any(|accept| !accept)
+			}
+		})
+	});
+
+	if rejected {
+		trace!("Path {} rejected by the indexer rules", path.display());
+		return Ok(None);
+	}
+
+	let metadata = fs::symlink_metadata(path)
+		.await
+		.map_err(|e| FileIOError::from((path, e)))?;
+
+	let symlink_target = if metadata.is_symlink() {
+		match symlinks
+			.resolve(path, &[])
+			.await
+			.map_err(|e| FileIOError::from((path, e)))?
+		{
+			ResolvedSymlink::Link { target } => Some(target.to_string_lossy().to_string()),
+			// A followed link is a directory, which isn't a single file to index
+			ResolvedSymlink::Skipped | ResolvedSymlink::Followed { .. } => return Ok(None),
+		}
+	} else {
+		None
+	};
+
+	let mut file_metadata = FilePathMetadata::from_path(path, &metadata).await?;
+
+	// Links are indexed as empty files, their target isn't ours to read
+	if symlink_target.is_some() {
+		file_metadata.size_in_bytes = 0;
+	}
+
+	let iso_file_path = iso_file_path_factory(path, false)?;
+
+	let mut indexed_paths = HashSet::from([WalkingEntry {
+		iso_file_path: iso_file_path.clone(),
+		maybe_metadata: Some(file_metadata),
+		symlink_target,
+	}]);
+
+	for ancestor in path
+		.ancestors()
+		.skip(1)
+		.take_while(|&ancestor| ancestor != root)
+	{
+		let metadata = fs::metadata(ancestor)
+			.await
+			.map_err(|e| FileIOError::from((ancestor, e)))?;
+
+		indexed_paths.insert(WalkingEntry {
+			iso_file_path: iso_file_path_factory(ancestor, true)?,
+			maybe_metadata: Some(FilePathMetadata::from_path(ancestor, &metadata).await?),
+			symlink_target: None,
+		});
+	}
+
+	let (walked, to_update) =
+		filter_existing_paths(indexed_paths, file_paths_db_fetcher, case_insensitive).await?;
+
+	Ok(Some((
+		walked.collect(),
+		// Ancestors already indexed are left for a scan of their own
+		to_update
+			.filter(|entry| entry.iso_file_path == iso_file_path)
+			.collect(),
+	)))
+}
+
+/// The params to find the `file_path` of an entry, which can be stored with a name the filesystem
+/// considers the same, like when it was renamed only changing its case.
+fn existing_file_path_params(
+	iso_file_path: &IsolatedFilePathData<'_>,
+	case_insensitive: bool,
+) -> [file_path::WhereParam; 2] {
+	[
+		iso_file_path.into(),
+		file_path::WhereParam::And(vec![
+			file_path::location_id::equals(Some(iso_file_path.location_id())),
+			file_path::materialized_path::equals(Some(
+				iso_file_path.to_parts().materialized_path.to_string(),
+			)),
+			file_path::name_normalized::equals(Some(
+				iso_file_path.normalized_name(case_insensitive),
+			)),
+		]),
+	]
+}
+
 async fn filter_existing_paths<F>(
 	indexed_paths: HashSet<WalkingEntry>,
 	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> F,
+	case_insensitive: bool,
 ) -> Result<
 	(
 		impl Iterator<Item = WalkedEntry>,
@@ -348,8 +523,7 @@ where
 		file_paths_db_fetcher(
 			indexed_paths
 				.iter()
-				.map(|entry| &entry.iso_file_path)
-				.map(Into::into)
+				.flat_map(|entry| existing_file_path_params(&entry.iso_file_path, case_insensitive))
 				.collect(),
 		)
 		.await
@@ -365,6 +539,43 @@ where
 			})
 			.collect::<HashMap<_, _>>();
 
+		let normalized_paths_already_in_db = isolated_paths_already_in_db
+			.keys()
+			.map(|iso_file_path| {
+				(
+					(
+						iso_file_path.to_parts().materialized_path.to_string(),
+						iso_file_path.normalized_name(case_insensitive),
+					),
+					iso_file_path,
+				)
+			})
+			.collect::<HashMap<_, _>>();
+
+		// Entries whose `file_path` is stored with another name the filesystem considers the same,
+		// unless that name was also walked, as then they're different files
+		let renamed_paths = indexed_paths
+			.iter()
+			.filter(|entry| !isolated_paths_already_in_db.contains_key(&entry.iso_file_path))
+			.filter_map(|entry| {
+				normalized_paths_already_in_db
+					.get(&(
+						entry.iso_file_path.to_parts().materialized_path.to_string(),
+						entry.iso_file_path.normalized_name(case_insensitive),
+					))
+					.filter(|&&iso_file_path_in_db| {
+						!indexed_paths.contains(&WalkingEntry {
+							iso_file_path: iso_file_path_in_db.clone(),
+							maybe_metadata: None,
+							symlink_target: None,
+						})
+					})
+					.map(|&iso_file_path_in_db| {
+						(entry.iso_file_path.clone(), iso_file_path_in_db.clone())
+					})
+			})
+			.collect::<HashMap<_, _>>();
+
 		let mut to_update = vec![];
 
 		let to_create = indexed_paths
@@ -411,14 +622,30 @@ where
 						}
 					}
 
+					None
+				} else if let Some(file_path) = renamed_paths
+					.get(&entry.iso_file_path)
+					.and_then(|iso_file_path_in_db| isolated_paths_already_in_db.get(iso_file_path_in_db))
+				{
+					// Always updated, so it's stored with its current name
+					to_update.push(
+						(sd_utils::from_bytes_to_uuid(&file_path.pub_id), file_path.object_id, entry).into(),
+					);
+
 					None
 				} else {
 					Some(entry.into())
 				}
 			})
+			.map(|entry: WalkedEntry| entry.with_normalized_name(case_insensitive))
 			.collect::<Vec<_>>();
 
-		(to_create.into_iter(), to_update.into_iter())
+		(
+			to_create.into_iter(),
+			to_update
+				.into_iter()
+				.map(move |entry: WalkedEntry| entry.with_normalized_name(case_insensitive)),
+		)
 	})
 }
 
@@ -429,20 +656,26 @@ struct WorkingTable<'a> {
 	errors: &'a mut Vec<IndexerError>,
 }
 
-async fn inner_walk_single_dir<ToRemoveDbFetcherFut>(
+#[allow(clippy::too_many_arguments)]
+async fn inner_walk_single_dir<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 	root: impl AsRef<Path>,
 	ToWalkEntry {
 		path,
 		parent_dir_accepted_by_its_children,
+		followed_links,
 		..
 	}: &ToWalkEntry,
 	indexer_rules: &[IndexerRule],
 	update_notifier: &mut impl FnMut(&Path, usize),
+	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
 	to_remove_db_fetcher: impl Fn(
 		IsolatedFilePathData<'static>,
 		Vec<file_path::WhereParam>,
 	) -> ToRemoveDbFetcherFut,
 	iso_file_path_factory: &impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
+	skip_unchanged_since: Option<DateTime<Utc>>,
+	case_insensitive: bool,
+	symlinks: &SymlinkResolver,
 	WorkingTable {
 		indexed_paths,
 		paths_buffer,
@@ -451,6 +684,7 @@ async fn inner_walk_single_dir<ToRemoveDbFetcherFut>(
 	}: WorkingTable<'_>,
 ) -> (u64, Vec<file_path_pub_and_cas_ids::Data>)
 where
+	FilePathDBFetcherFut: Future<Output = Result<Vec<file_path_walker::Data>, IndexerError>>,
 	ToRemoveDbFetcherFut:
 		Future<Output = Result<Vec<file_path_pub_and_cas_ids::Data>, IndexerError>>,
 {
@@ -473,6 +707,9 @@ where
 
 	let mut found_paths_counts = 0;
 
+	// Directories not modified since the last scan, which may not need to be walked again
+	let mut maybe_unchanged_dirs = vec![];
+
 	// Marking with a loop label here in case of rejection or errors, to continue with next entry
 	'entries: loop {
 		let entry = match read_dir.next_entry().await {
@@ -516,6 +753,7 @@ where
 			continue 'entries;
 		};
 
+		// The kinds are checked in `RuleKind::EVALUATION_ORDER`
 		if rules_per_kind
 			.get(&RuleKind::RejectFilesByGlob)
 			.map_or(false, |reject_results| {
@@ -536,10 +774,23 @@ where
 			continue 'entries;
 		};
 
-		// TODO: Hard ignoring symlinks for now, but this should be configurable
-		if metadata.is_symlink() {
-			continue 'entries;
-		}
+		let (metadata, symlink_target, followed_link) = if metadata.is_symlink() {
+			match symlinks
+				.resolve(&current_path, followed_links)
+				.await
+				.map_err(|e| errors.push(FileIOError::from((&current_path, e)).into()))
+			{
+				Ok(ResolvedSymlink::Skipped) | Err(()) => continue 'entries,
+				Ok(ResolvedSymlink::Link { target }) => {
+					(metadata, Some(target.to_string_lossy().to_string()), None)
+				}
+				Ok(ResolvedSymlink::Followed { metadata, dir_id }) => {
+					(metadata, None, Some(dir_id))
+				}
+			}
+		} else {
+			(metadata, None, None)
+		};
 
 		let is_dir = metadata.is_dir();
 
@@ -577,11 +828,23 @@ where
 
 			// Then we mark this directory the be walked in too
 			if let Some(ref mut to_walk) = maybe_to_walk {
-				to_walk.push_back(ToWalkEntry {
+				let to_walk_entry = ToWalkEntry {
 					path: current_path.clone(),
 					parent_dir_accepted_by_its_children: accept_by_children_dir,
 					maybe_parent: Some(path.clone()),
-				});
+					followed_links: followed_links
+						.iter()
+						.copied()
+						.chain(followed_link)
+						.collect(),
+				};
+
+				match (skip_unchanged_since, metadata.modified()) {
+					(Some(since), Ok(modified)) if DateTime::<Utc>::from(modified) < since => {
+						maybe_unchanged_dirs.push((to_walk_entry, DateTime::<Utc>::from(modified)));
+					}
+					_ => to_walk.push_back(to_walk_entry),
+				}
 			}
 		}
 
@@ -597,6 +860,18 @@ where
 			continue 'entries;
 		}
 
+		if rules_per_kind
+			.get(&RuleKind::AcceptIfAllOfRejectIfAnyOf)
+			.map_or(false, |accept_rules| {
+				accept_rules.iter().any(|accept| !accept)
+			}) {
+			trace!(
+				"Path {} rejected because it didn't passed in all AcceptIfAllOfRejectIfAnyOf rules",
+				current_path.display()
+			);
+			continue 'entries;
+		}
+
 		if accept_by_children_dir.unwrap_or(true) {
 			let Ok(iso_file_path) =
 				iso_file_path_factory(&current_path, is_dir).map_err(|e| errors.push(e))
@@ -604,16 +879,22 @@ where
 				continue 'entries;
 			};
 
-			let Ok(metadata) = FilePathMetadata::from_path(&current_path, &metadata)
+			let Ok(mut metadata) = FilePathMetadata::from_path(&current_path, &metadata)
 				.await
 				.map_err(|e| errors.push(e.into()))
 			else {
 				continue;
 			};
 
+			// Links are indexed as empty files, their target isn't ours to read
+			if symlink_target.is_some() {
+				metadata.size_in_bytes = 0;
+			}
+
 			paths_buffer.insert(WalkingEntry {
 				iso_file_path,
 				maybe_metadata: Some(metadata),
+				symlink_target,
 			});
 
 			// If the ancestors directories wasn't indexed before, now we do
@@ -632,6 +913,7 @@ where
 				let mut ancestor_iso_walking_entry = WalkingEntry {
 					iso_file_path,
 					maybe_metadata: None,
+					symlink_target: None,
 				};
 				trace!("Indexing ancestor {}", ancestor.display());
 				if !indexed_paths.contains(&ancestor_iso_walking_entry) {
@@ -662,6 +944,23 @@ where
 		}
 	}
 
+	let mut to_walk_entry_size = 0;
+
+	if let Some(ref mut to_walk) = maybe_to_walk {
+		if !maybe_unchanged_dirs.is_empty() {
+			let (changed_dirs, unchanged_dirs_size) = filter_unchanged_dirs(
+				maybe_unchanged_dirs,
+				&file_paths_db_fetcher,
+				iso_file_path_factory,
+				errors,
+			)
+			.await;
+
+			to_walk.extend(changed_dirs);
+			to_walk_entry_size += unchanged_dirs_size;
+		}
+	}
+
 	// We continue the function even if we fail to fetch `file_path`s to remove,
 	// the DB will have old `file_path`s but at least this is better than
 	// don't adding the newly indexed paths
@@ -669,8 +968,7 @@ where
 		iso_file_path_to_walk,
 		paths_buffer
 			.iter()
-			.map(|entry| &entry.iso_file_path)
-			.map(Into::into)
+			.flat_map(|entry| existing_file_path_params(&entry.iso_file_path, case_insensitive))
 			.collect(),
 	)
 	.await
@@ -679,8 +977,6 @@ where
 		vec![]
 	});
 
-	let mut to_walk_entry_size = 0;
-
 	// Just merging the `found_paths` with `indexed_paths` here in the end to avoid possibly
 	// multiple rehashes during function execution
 	indexed_paths.extend(paths_buffer.drain().map(|walking_entry| {
@@ -693,11 +989,86 @@ where
 	(to_walk_entry_size, to_remove)
 }
 
+/// A directory's modification date only changes when entries are added, removed or renamed in it.
+/// So a directory modified before the last scan, whose `file_path` has the same modification date,
+/// had the same entries back then and we skip walking into it again.
+///
+/// This misses changes deeper in the skipped subtree, which is why a forced rescan walks everything.
+///
+/// Returns the directories which must still be walked, and the sum of the sizes stored for the
+/// skipped ones, as they still count towards their parent's size.
+async fn filter_unchanged_dirs<FilePathDBFetcherFut>(
+	maybe_unchanged_dirs: Vec<(ToWalkEntry, DateTime<Utc>)>,
+	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
+	iso_file_path_factory: &impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
+	errors: &mut Vec<IndexerError>,
+) -> (Vec<ToWalkEntry>, u64)
+where
+	FilePathDBFetcherFut: Future<Output = Result<Vec<file_path_walker::Data>, IndexerError>>,
+{
+	let iso_file_paths = maybe_unchanged_dirs
+		.iter()
+		.map(|(entry, _)| iso_file_path_factory(&entry.path, true).ok())
+		.collect::<Vec<_>>();
+
+	let file_paths_in_db =
+		file_paths_db_fetcher(iso_file_paths.iter().flatten().map(Into::into).collect())
+			.await
+			.unwrap_or_else(|e| {
+				errors.push(e);
+				vec![]
+			})
+			.into_iter()
+			.flat_map(|file_path| {
+				IsolatedFilePathData::try_from(file_path.clone())
+					.map(|iso_file_path| (iso_file_path, file_path))
+			})
+			.collect::<HashMap<_, _>>();
+
+	let mut unchanged_dirs_size = 0;
+
+	let changed_dirs = maybe_unchanged_dirs
+		.into_iter()
+		.zip(iso_file_paths)
+		.filter_map(|((entry, modified_at), maybe_iso_file_path)| {
+			let Some(file_path) =
+				maybe_iso_file_path.and_then(|iso_file_path| file_paths_in_db.get(&iso_file_path))
+			else {
+				return Some(entry);
+			};
+
+			match &file_path.date_modified {
+				// Datetimes stored in DB loses a bit of precision, so we need to check against a delta
+				Some(date_modified)
+					if DateTime::<FixedOffset>::from(modified_at) - *date_modified
+						<= Duration::milliseconds(1) =>
+				{
+					trace!("Skipping unchanged directory {}", entry.path.display());
+
+					unchanged_dirs_size += file_path
+						.size_in_bytes_bytes
+						.as_ref()
+						.and_then(|bytes| bytes.get(..8))
+						.and_then(|bytes| bytes.try_into().ok())
+						.map(u64::from_be_bytes)
+						.unwrap_or_default();
+
+					None
+				}
+				_ => Some(entry),
+			}
+		})
+		.collect();
+
+	(changed_dirs, unchanged_dirs_size)
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used, clippy::panic)]
 mod tests {
 	use super::super::rules::RulePerKind;
 	use super::*;
+	use crate::location::symlink::SymlinkPolicy;
 	use chrono::Utc;
 	use globset::{Glob, GlobSetBuilder};
 	use tempfile::{tempdir, TempDir};
@@ -794,28 +1165,28 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/text.txt"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/text.txt"), false), metadata, name_normalized: None, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -830,6 +1201,9 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			None,
+			false,
+			&SymlinkResolver::new(SymlinkPolicy::Skip, root_path),
 		)
 		.await
 		.unwrap();
@@ -865,10 +1239,10 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata, name_normalized: None, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -895,6 +1269,9 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			None,
+			false,
+			&SymlinkResolver::new(SymlinkPolicy::Skip, root_path),
 		)
 		.await
 		.unwrap();
@@ -930,23 +1307,23 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata, name_normalized: None, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -969,6 +1346,9 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			None,
+			false,
+			&SymlinkResolver::new(SymlinkPolicy::Skip, root_path),
 		)
 		.await
 		.unwrap();
@@ -1004,17 +1384,17 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata, name_normalized: None, symlink_target: None },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata, name_normalized: None, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -1061,6 +1441,9 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			None,
+			false,
+			&SymlinkResolver::new(SymlinkPolicy::Skip, root_path),
 		)
 		.await
 		.unwrap();
@@ -1075,4 +1458,239 @@ mod tests {
 			panic!("difference: {:#?}", expected.difference(&actual));
 		}
 	}
+
+	#[tokio::test]
+	async fn test_skip_unchanged_dirs() {
+		let root = prepare_location().await;
+		let root_path = root.path();
+
+		let since = Utc::now();
+
+		let photos_modified_at = DateTime::<Utc>::from(
+			fs::metadata(root_path.join("photos"))
+				.await
+				.unwrap()
+				.modified()
+				.unwrap(),
+		);
+
+		// As if `photos` was indexed on the last scan, and nothing else was
+		let photos_in_db = file_path_walker::Data {
+			pub_id: Uuid::new_v4().as_bytes().to_vec(),
+			location_id: Some(0),
+			object_id: None,
+			materialized_path: Some("/".to_string()),
+			is_dir: Some(true),
+			name: Some("photos".to_string()),
+			extension: Some(String::new()),
+			date_modified: Some(photos_modified_at.into()),
+			inode: None,
+			size_in_bytes_bytes: Some(42u64.to_be_bytes().to_vec()),
+			hidden: Some(false),
+		};
+
+		let walk_result = walk(
+			root_path.to_path_buf(),
+			&[],
+			|_, _| {},
+			|_| {
+				let photos_in_db = photos_in_db.clone();
+				async move { Ok(vec![photos_in_db]) }
+			},
+			|_, _| async { Ok(vec![]) },
+			|path, is_dir| {
+				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+			},
+			420,
+			Some(since),
+			false,
+			&SymlinkResolver::new(SymlinkPolicy::Skip, root_path),
+		)
+		.await
+		.unwrap();
+
+		if !walk_result.errors.is_empty() {
+			panic!("errors: {:#?}", walk_result.errors);
+		}
+
+		let walked = walk_result
+			.walked
+			.map(|entry| entry.iso_file_path)
+			.collect::<Vec<_>>();
+
+		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
+
+		// Directories which aren't in the db are still walked, even if they are older than the last scan
+		assert!(walked.contains(&f(root_path.join("rust_project/src/main.rs"), false)));
+		assert!(!walked.contains(&f(root_path.join("photos/photo1.png"), false)));
+		assert!(!walk_result
+			.paths_and_sizes
+			.contains_key(&root_path.join("photos")));
+	}
+
+	#[tokio::test]
+	async fn test_new_file_under_unchanged_dir() {
+		let root = tempdir().unwrap();
+		let root_path = root.path();
+
+		fs::create_dir_all(root_path.join("a/b")).await.unwrap();
+
+		let a_modified_at = DateTime::<Utc>::from(
+			fs::metadata(root_path.join("a"))
+				.await
+				.unwrap()
+				.modified()
+				.unwrap(),
+		);
+
+		let since = Utc::now();
+
+		// Only touches the modification date of `a/b`, not of `a`
+		fs::write(root_path.join("a/b/new.txt"), "new")
+			.await
+			.unwrap();
+
+		let a_in_db = file_path_walker::Data {
+			pub_id: Uuid::new_v4().as_bytes().to_vec(),
+			location_id: Some(0),
+			object_id: None,
+			materialized_path: Some("/".to_string()),
+			is_dir: Some(true),
+			name: Some("a".to_string()),
+			extension: Some(String::new()),
+			date_modified: Some(a_modified_at.into()),
+			inode: None,
+			size_in_bytes_bytes: Some(0u64.to_be_bytes().to_vec()),
+			hidden: Some(false),
+		};
+
+		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
+
+		// A regular scan walks everything, only a quick rescan misses the new file
+		for (skip_unchanged_since, expect_new_file) in [(None, true), (Some(since), false)] {
+			let walk_result = walk(
+				root_path.to_path_buf(),
+				&[],
+				|_, _| {},
+				|_| {
+					let a_in_db = a_in_db.clone();
+					async move { Ok(vec![a_in_db]) }
+				},
+				|_, _| async { Ok(vec![]) },
+				|path, is_dir| {
+					IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+				},
+				420,
+				skip_unchanged_since,
+				false,
+				&SymlinkResolver::new(SymlinkPolicy::Skip, root_path),
+			)
+			.await
+			.unwrap();
+
+			if !walk_result.errors.is_empty() {
+				panic!("errors: {:#?}", walk_result.errors);
+			}
+
+			let walked = walk_result
+				.walked
+				.map(|entry| entry.iso_file_path)
+				.collect::<Vec<_>>();
+
+			assert_eq!(
+				walked.contains(&f(root_path.join("a/b/new.txt"), false)),
+				expect_new_file
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_walk_single_file() {
+		let root = prepare_location().await;
+		let root_path = root.path();
+
+		let only_photos_rule = &[IndexerRule::new(
+			"only photos".to_string(),
+			false,
+			vec![RulePerKind::AcceptFilesByGlob(
+				vec![],
+				GlobSetBuilder::new()
+					.add(Glob::new("{*.png,*.jpg,*.jpeg}").unwrap())
+					.build()
+					.unwrap(),
+			)],
+		)];
+
+		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
+		let symlinks = SymlinkResolver::new(SymlinkPolicy::Skip, root_path);
+
+		let walk_file = |path: PathBuf, photos_in_db: Option<file_path_walker::Data>| {
+			let symlinks = &symlinks;
+			async move {
+				walk_single_file(
+					root_path,
+					path,
+					only_photos_rule,
+					|_| {
+						let photos_in_db = photos_in_db.clone();
+						async move { Ok(photos_in_db.into_iter().collect()) }
+					},
+					|path, is_dir| {
+						IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+					},
+					false,
+					symlinks,
+				)
+				.await
+				.unwrap()
+			}
+		};
+
+		assert!(walk_file(root_path.join("photos/text.txt"), None)
+			.await
+			.is_none());
+
+		// The directory isn't indexed yet, so it comes along, but not the other photos in it
+		let (walked, to_update) = walk_file(root_path.join("photos/photo1.png"), None)
+			.await
+			.unwrap();
+		assert!(to_update.is_empty());
+		assert_eq!(
+			walked
+				.into_iter()
+				.map(|entry| entry.iso_file_path)
+				.collect::<HashSet<_>>(),
+			HashSet::from([
+				f(root_path.join("photos"), true),
+				f(root_path.join("photos/photo1.png"), false),
+			])
+		);
+
+		let photos_in_db = file_path_walker::Data {
+			pub_id: Uuid::new_v4().as_bytes().to_vec(),
+			location_id: Some(0),
+			object_id: None,
+			materialized_path: Some("/".to_string()),
+			is_dir: Some(true),
+			name: Some("photos".to_string()),
+			extension: Some(String::new()),
+			date_modified: None,
+			inode: None,
+			size_in_bytes_bytes: None,
+			hidden: Some(false),
+		};
+
+		let (walked, to_update) =
+			walk_file(root_path.join("photos/photo2.jpg"), Some(photos_in_db))
+				.await
+				.unwrap();
+		assert!(to_update.is_empty());
+		assert_eq!(
+			walked
+				.into_iter()
+				.map(|entry| entry.iso_file_path)
+				.collect::<Vec<_>>(),
+			vec![f(root_path.join("photos/photo2.jpg"), false)]
+		);
+	}
 }
@@ -32,6 +32,10 @@ mod watcher;
 #[cfg(feature = "location-watcher")]
 mod helpers;
 
+mod rescan;
+
+pub use rescan::RESCAN_CHECK_INTERVAL;
+
 #[derive(Clone, Copy, Debug)]
 #[allow(dead_code)]
 enum ManagementMessageAction {
@@ -178,6 +182,8 @@ impl LocationManagerActor {
 			}
 		});
 
+		tokio::spawn(rescan::run_rescan_scheduler(node.clone()));
+
 		#[cfg(feature = "location-watcher")]
 		tokio::spawn(Locations::run_locations_checker(
 			self.location_management_rx,
@@ -0,0 +1,154 @@
+use crate::{
+	job::StatefulJob,
+	library::{Library, LibraryId},
+	location::{indexer::IndexerJobInit, location_with_indexer_rules, scan_location},
+	Node,
+};
+
+use sd_prisma::prisma::location;
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use tokio::{fs, time::interval};
+use tracing::{debug, error};
+
+/// How often the scheduler looks for locations which are due for a rescan, which is also the
+/// shortest rescan interval a location can have.
+pub const RESCAN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+type LocationAndLibraryKey = (location::id::Type, LibraryId);
+
+/// Periodically rescans the locations which have a `rescan_interval`.
+///
+/// When a location is due is computed from `location.scanned_at`, so the schedule carries over
+/// restarts. Rescans skip unchanged directories like any other non forced scan.
+pub(super) async fn run_rescan_scheduler(node: Arc<Node>) {
+	// `scanned_at` is only updated once a scan finishes, so we also remember when we last queued
+	// one to not queue it again while it's waiting for other jobs or if it keeps failing
+	let mut last_queued = HashMap::<LocationAndLibraryKey, DateTime<Utc>>::new();
+	let mut check_interval = interval(RESCAN_CHECK_INTERVAL);
+
+	loop {
+		check_interval.tick().await;
+
+		for library in node.libraries.get_all().await {
+			if let Err(e) = rescan_due_locations(&node, &library, &mut last_queued).await {
+				error!(
+					"Failed to check locations due for a rescan in library <id='{}'>: {e:#?}",
+					library.id
+				);
+			}
+		}
+	}
+}
+
+async fn rescan_due_locations(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	last_queued: &mut HashMap<LocationAndLibraryKey, DateTime<Utc>>,
+) -> Result<(), prisma_client_rust::QueryError> {
+	let now = Utc::now();
+
+	// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
+	let locations = library
+		.db
+		.location()
+		.find_many(vec![
+			location::rescan_interval::not(None),
+			location::instance_id::equals(Some(library.config().await.instance_id)),
+		])
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?;
+
+	for location in locations {
+		let key = (location.id, library.id);
+
+		let Some(interval) = location
+			.rescan_interval
+			.and_then(|secs| u64::try_from(secs).ok())
+			.map(Duration::from_secs)
+		else {
+			continue;
+		};
+
+		let last_run = location
+			.scanned_at
+			.map(Into::into)
+			.max(last_queued.get(&key).copied());
+
+		if !is_rescan_due(last_run, interval, now) {
+			continue;
+		}
+
+		// Offline locations are picked up once they're back, as their last scan stays old
+		let Some(path) = &location.path else {
+			continue;
+		};
+		if fs::metadata(path).await.is_err() {
+			continue;
+		}
+
+		if node
+			.jobs
+			.has_job_running(|job_identity| {
+				job_identity.target_location == location.id
+					&& job_identity.name == <IndexerJobInit as StatefulJob>::NAME
+			})
+			.await
+		{
+			continue;
+		}
+
+		debug!(
+			"Rescanning location <id='{}'> of library <id='{}'>",
+			location.id, library.id
+		);
+
+		last_queued.insert(key, now);
+
+		if let Err(e) = scan_location(node, library, location, false).await {
+			error!("Failed to queue scheduled rescan: {e:#?}");
+		}
+	}
+
+	Ok(())
+}
+
+fn is_rescan_due(last_run: Option<DateTime<Utc>>, interval: Duration, now: DateTime<Utc>) -> bool {
+	let Some(last_run) = last_run else {
+		// Never scanned
+		return true;
+	};
+
+	chrono::Duration::from_std(interval)
+		.ok()
+		.and_then(|interval| last_run.checked_add_signed(interval))
+		.is_some_and(|due_at| due_at <= now)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_is_rescan_due() {
+		let now = Utc::now();
+		let hour = Duration::from_secs(60 * 60);
+
+		assert!(is_rescan_due(None, hour, now));
+		assert!(is_rescan_due(
+			Some(now - chrono::Duration::hours(2)),
+			hour,
+			now
+		));
+		assert!(!is_rescan_due(
+			Some(now - chrono::Duration::minutes(30)),
+			hour,
+			now
+		));
+		// Too big to ever be due
+		assert!(!is_rescan_due(Some(now), Duration::MAX, now));
+	}
+}
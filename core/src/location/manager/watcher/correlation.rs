@@ -0,0 +1,517 @@
+//! Editors usually save files atomically, writing a temporary file and renaming it over the
+//! original, or removing the original and creating it again. Some backends report that as the
+//! original being removed and another file being created, and removing its `file_path` right away
+//! would lose the object linked to it, with its tags and everything else.
+//!
+//! So removals are held for a short while, and a file showing up meanwhile with the same inode or
+//! contents takes over the removed `file_path`, as if it was renamed or just modified.
+//!
+//! Events also come in bursts, like when checking out a git branch, so when too many files settle
+//! at once we scan their closest common directory instead of updating them one by one.
+
+use crate::{
+	invalidate_query,
+	library::Library,
+	location::{
+		find_location, location_with_indexer_rules, manager::LocationManagerError,
+		scan_location_sub_path,
+	},
+	object::cas::generate_cas_id,
+	Node,
+};
+
+use sd_file_path_helper::loose_find_existing_file_path_params;
+use sd_prisma::prisma::{file_path, location};
+use sd_utils::{
+	db::{inode_from_db, maybe_missing},
+	error::FileIOError,
+};
+
+#[cfg(target_family = "unix")]
+use sd_file_path_helper::get_inode;
+
+#[cfg(target_family = "windows")]
+use sd_file_path_helper::get_inode_from_path;
+
+use std::{
+	collections::HashMap,
+	fs::Metadata,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+
+use tokio::{fs, io::ErrorKind, time::Instant};
+use tracing::{debug, trace};
+
+use super::{
+	utils::{extract_location_path, is_symlink, remove, rename, update_file, update_symlink},
+	INode,
+};
+
+/// How long a removed file can take to show up again to keep its `file_path`
+const REMOVAL_WINDOW: Duration = Duration::from_millis(500);
+
+/// How many files settling at once are handled by a single scan instead
+const BURST_SCAN_THRESHOLD: usize = 500;
+
+#[derive(Debug)]
+struct PendingRemoval {
+	removed_at: Instant,
+	inode: Option<INode>,
+	cas_id: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct EventCorrelator {
+	pending_removals: HashMap<PathBuf, PendingRemoval>,
+}
+
+impl EventCorrelator {
+	/// Holds the removal of a file for a while, in case it comes back at the same or another path.
+	pub(super) async fn remove(
+		&mut self,
+		location_id: location::id::Type,
+		path: PathBuf,
+		library: &Library,
+	) -> Result<(), LocationManagerError> {
+		let location_path = extract_location_path(location_id, library).await?;
+
+		let Some(file_path) = library
+			.db
+			.file_path()
+			.find_first(loose_find_existing_file_path_params(
+				location_id,
+				&location_path,
+				&path,
+			)?)
+			.select(file_path::select!({ is_dir inode cas_id }))
+			.exec()
+			.await?
+		else {
+			// Never indexed, like most temporary files
+			return Ok(());
+		};
+
+		// Directories can't be matched by their contents, and their children are reported on their own
+		if maybe_missing(file_path.is_dir, "file_path.is_dir")? {
+			return remove(location_id, &path, library).await;
+		}
+
+		self.hold(
+			path,
+			file_path
+				.inode
+				.as_deref()
+				.map(|inode| inode_from_db(&inode[0..8])),
+			file_path.cas_id,
+			Instant::now(),
+		);
+
+		Ok(())
+	}
+
+	/// Updates the files whose events settled, each taking over the pending removal it turns out
+	/// to be, or scans them all at once when there are too many.
+	pub(super) async fn update_files(
+		&mut self,
+		location_id: location::id::Type,
+		paths: Vec<PathBuf>,
+		node: &Arc<Node>,
+		library: &Arc<Library>,
+	) -> Result<(), LocationManagerError> {
+		if paths.len() >= BURST_SCAN_THRESHOLD {
+			return self.scan_burst(location_id, &paths, node, library).await;
+		}
+
+		for path in paths {
+			self.update_file(location_id, &path, node, library).await?;
+		}
+
+		Ok(())
+	}
+
+	async fn update_file(
+		&mut self,
+		location_id: location::id::Type,
+		path: &Path,
+		node: &Arc<Node>,
+		library: &Arc<Library>,
+	) -> Result<(), LocationManagerError> {
+		if is_symlink(path).await {
+			return update_symlink(location_id, path, node, library).await;
+		}
+
+		let metadata = match fs::metadata(path).await {
+			Ok(metadata) => metadata,
+			// It was just a temporary file
+			Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+			Err(e) => return Err(FileIOError::from((path, e)).into()),
+		};
+
+		// Directories are handled when they're created
+		if !metadata.is_file() {
+			return Ok(());
+		}
+
+		if let Some(old_path) = self.take_removed(path, &metadata).await {
+			if old_path != path {
+				trace!(
+					"Got a rename instead of remove/create: {} -> {}",
+					old_path.display(),
+					path.display()
+				);
+
+				rename(location_id, path, &old_path, metadata, node, library).await?;
+			}
+		}
+
+		update_file(location_id, path, node, library).await
+	}
+
+	/// Removes the files which didn't show up again in time, or scans them all at once when
+	/// there are too many.
+	pub(super) async fn evict(
+		&mut self,
+		location_id: location::id::Type,
+		node: &Arc<Node>,
+		library: &Arc<Library>,
+		to_recalculate_size: &mut HashMap<PathBuf, Instant>,
+	) -> Result<(), LocationManagerError> {
+		let expired = self.take_expired(Instant::now());
+		if expired.is_empty() {
+			return Ok(());
+		}
+
+		for path in &expired {
+			if let Some(parent) = path.parent() {
+				if parent != Path::new("") {
+					to_recalculate_size.insert(parent.to_path_buf(), Instant::now());
+				}
+			}
+		}
+
+		if expired.len() >= BURST_SCAN_THRESHOLD {
+			return self.scan_burst(location_id, &expired, node, library).await;
+		}
+
+		for path in expired {
+			match fs::metadata(&path).await {
+				// It came back without any event of its own, so it was just modified
+				Ok(_) => update_file(location_id, &path, node, library).await?,
+				Err(e) if e.kind() == ErrorKind::NotFound => {
+					remove(location_id, &path, library).await?;
+					trace!("Removed file_path due timeout: {}", path.display());
+				}
+				Err(e) => return Err(FileIOError::from((&path, e)).into()),
+			}
+		}
+
+		invalidate_query!(library, "search.paths");
+
+		Ok(())
+	}
+
+	fn hold(&mut self, path: PathBuf, inode: Option<INode>, cas_id: Option<String>, at: Instant) {
+		self.pending_removals.insert(
+			path,
+			PendingRemoval {
+				removed_at: at,
+				inode,
+				cas_id,
+			},
+		);
+	}
+
+	fn take_expired(&mut self, now: Instant) -> Vec<PathBuf> {
+		let expired = self
+			.pending_removals
+			.iter()
+			.filter(|(_, removal)| {
+				now.saturating_duration_since(removal.removed_at) > REMOVAL_WINDOW
+			})
+			.map(|(path, _)| path.clone())
+			.collect::<Vec<_>>();
+
+		for path in &expired {
+			self.pending_removals.remove(path);
+		}
+
+		expired
+	}
+
+	/// Takes the pending removal which the file at `path` turns out to be, returning its path.
+	async fn take_removed(&mut self, path: &Path, metadata: &Metadata) -> Option<PathBuf> {
+		if self.pending_removals.remove(path).is_some() {
+			return Some(path.to_path_buf());
+		}
+
+		if self.pending_removals.is_empty() {
+			return None;
+		}
+
+		#[cfg(target_family = "unix")]
+		let inode = Some(get_inode(metadata));
+
+		#[cfg(target_family = "windows")]
+		let inode = get_inode_from_path(path).await.ok();
+
+		let mut removed =
+			inode.and_then(|inode| self.find_removed(path, |removal| removal.inode == Some(inode)));
+
+		// Every empty file has the same cas_id, so they can't be told apart
+		if removed.is_none()
+			&& metadata.len() > 0
+			&& self
+				.pending_removals
+				.values()
+				.any(|removal| removal.cas_id.is_some())
+		{
+			if let Ok(cas_id) = generate_cas_id(path, metadata.len()).await {
+				removed = self.find_removed(path, |removal| {
+					removal.cas_id.as_deref() == Some(cas_id.as_str())
+				});
+			}
+		}
+
+		if let Some(old_path) = &removed {
+			self.pending_removals.remove(old_path);
+		}
+
+		removed
+	}
+
+	/// Prefers a removal from the same directory, as copies of a file can be removed together.
+	fn find_removed(
+		&self,
+		path: &Path,
+		matches: impl Fn(&PendingRemoval) -> bool,
+	) -> Option<PathBuf> {
+		let mut candidates = self
+			.pending_removals
+			.iter()
+			.filter(|(_, removal)| matches(removal))
+			.map(|(old_path, _)| old_path);
+
+		let first = candidates.next()?;
+
+		if first.parent() == path.parent() {
+			return Some(first.clone());
+		}
+
+		Some(
+			candidates
+				.find(|old_path| old_path.parent() == path.parent())
+				.unwrap_or(first)
+				.clone(),
+		)
+	}
+
+	async fn scan_burst(
+		&mut self,
+		location_id: location::id::Type,
+		paths: &[PathBuf],
+		node: &Arc<Node>,
+		library: &Arc<Library>,
+	) -> Result<(), LocationManagerError> {
+		let location = find_location(library, location_id)
+			.include(location_with_indexer_rules::include())
+			.exec()
+			.await?
+			.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+		let location_path = PathBuf::from(maybe_missing(&location.path, "location.path")?);
+
+		let mut sub_path = common_ancestor(paths)
+			.filter(|ancestor| ancestor.starts_with(&location_path))
+			.unwrap_or_else(|| location_path.clone());
+
+		// The burst could have removed the directory itself
+		while sub_path != location_path
+			&& !fs::metadata(&sub_path)
+				.await
+				.is_ok_and(|metadata| metadata.is_dir())
+		{
+			sub_path.pop();
+		}
+
+		debug!(
+			"Scanning {} instead of handling {} files one by one",
+			sub_path.display(),
+			paths.len()
+		);
+
+		// The scan removes them as well, if they didn't show up again
+		self.pending_removals
+			.retain(|path, _| !path.starts_with(&sub_path));
+
+		scan_location_sub_path(
+			node,
+			library,
+			location,
+			if sub_path == location_path {
+				PathBuf::new()
+			} else {
+				sub_path
+			},
+		)
+		.await
+		.map_err(Into::into)
+	}
+}
+
+fn common_ancestor(paths: &[PathBuf]) -> Option<PathBuf> {
+	let mut parents = paths.iter().filter_map(|path| path.parent());
+	let mut ancestor = parents.next()?.to_path_buf();
+
+	for parent in parents {
+		while !parent.starts_with(&ancestor) {
+			if !ancestor.pop() {
+				return None;
+			}
+		}
+	}
+
+	Some(ancestor)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use sd_file_path_helper::get_inode_from_path;
+
+	use tempfile::tempdir;
+
+	async fn hold_file(correlator: &mut EventCorrelator, path: &Path) {
+		let metadata = fs::metadata(path).await.unwrap();
+
+		correlator.hold(
+			path.to_path_buf(),
+			Some(get_inode_from_path(path).await.unwrap()),
+			Some(generate_cas_id(path, metadata.len()).await.unwrap()),
+			Instant::now(),
+		);
+	}
+
+	#[tokio::test]
+	async fn test_temporary_file_renamed_over_original() {
+		let dir = tempdir().unwrap();
+		let original = dir.path().join("report.txt");
+		let temporary = dir.path().join(".report.txt.swp");
+
+		fs::write(&original, "first draft").await.unwrap();
+
+		let mut correlator = EventCorrelator::default();
+		hold_file(&mut correlator, &original).await;
+
+		fs::write(&temporary, "second draft").await.unwrap();
+		fs::rename(&temporary, &original).await.unwrap();
+
+		// Another inode and other contents, but it is the same file to the user
+		let metadata = fs::metadata(&original).await.unwrap();
+		assert_eq!(
+			correlator.take_removed(&original, &metadata).await,
+			Some(original.clone())
+		);
+		assert!(correlator.pending_removals.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_removed_file_created_again_elsewhere() {
+		let dir = tempdir().unwrap();
+		let original = dir.path().join("report.txt");
+		let moved = dir.path().join("report (final).txt");
+		let copied = dir.path().join("report (copy).txt");
+
+		fs::write(&original, "final draft").await.unwrap();
+
+		let mut correlator = EventCorrelator::default();
+		hold_file(&mut correlator, &original).await;
+
+		// Moved, so it keeps its inode
+		fs::rename(&original, &moved).await.unwrap();
+		let metadata = fs::metadata(&moved).await.unwrap();
+		assert_eq!(
+			correlator.take_removed(&moved, &metadata).await,
+			Some(original.clone())
+		);
+
+		// Written again, so only its contents are the same
+		hold_file(&mut correlator, &moved).await;
+		fs::remove_file(&moved).await.unwrap();
+		fs::write(&copied, "final draft").await.unwrap();
+		let metadata = fs::metadata(&copied).await.unwrap();
+		assert_eq!(
+			correlator.take_removed(&copied, &metadata).await,
+			Some(moved.clone())
+		);
+
+		// Unrelated files don't take over anything
+		hold_file(&mut correlator, &copied).await;
+		fs::write(&original, "another report").await.unwrap();
+		let metadata = fs::metadata(&original).await.unwrap();
+		assert_eq!(correlator.take_removed(&original, &metadata).await, None);
+		assert_eq!(correlator.pending_removals.len(), 1);
+	}
+
+	#[test]
+	fn test_find_removed_prefers_same_directory() {
+		let mut correlator = EventCorrelator::default();
+		let now = Instant::now();
+
+		correlator.hold(
+			PathBuf::from("/backup/copy.txt"),
+			Some(1),
+			Some("copy".to_string()),
+			now,
+		);
+		correlator.hold(
+			PathBuf::from("/docs/copy.txt"),
+			Some(2),
+			Some("copy".to_string()),
+			now,
+		);
+
+		assert_eq!(
+			correlator.find_removed(Path::new("/docs/copy (1).txt"), |removal| {
+				removal.cas_id.as_deref() == Some("copy")
+			}),
+			Some(PathBuf::from("/docs/copy.txt"))
+		);
+	}
+
+	#[test]
+	fn test_take_expired() {
+		let mut correlator = EventCorrelator::default();
+		let now = Instant::now();
+
+		correlator.hold(PathBuf::from("/a.txt"), Some(1), None, now);
+		correlator.hold(PathBuf::from("/b.txt"), Some(2), None, now + REMOVAL_WINDOW);
+
+		assert!(correlator.take_expired(now + REMOVAL_WINDOW).is_empty());
+		assert_eq!(
+			correlator.take_expired(now + REMOVAL_WINDOW * 3 / 2),
+			vec![PathBuf::from("/a.txt")]
+		);
+		assert_eq!(correlator.pending_removals.len(), 1);
+	}
+
+	#[test]
+	fn test_common_ancestor() {
+		assert_eq!(
+			common_ancestor(&[
+				PathBuf::from("/repo/src/a.rs"),
+				PathBuf::from("/repo/src/nested/b.rs"),
+				PathBuf::from("/repo/docs/c.md"),
+			]),
+			Some(PathBuf::from("/repo"))
+		);
+		assert_eq!(
+			common_ancestor(&[PathBuf::from("/repo/src/a.rs")]),
+			Some(PathBuf::from("/repo/src"))
+		);
+		assert_eq!(common_ancestor(&[]), None);
+	}
+}
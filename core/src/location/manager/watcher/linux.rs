@@ -5,6 +5,8 @@
 //! other. If we have dangling Rename From events, we have to remove them after some time.
 //! Aside from that, when a directory is moved to our watched location from the outside, we receive
 //! a Create Dir event, this one is actually ok at least.
+//! Removals and files settling down go through the [`EventCorrelator`], so a file saved by
+//! renaming a temporary file over it, or by removing and creating it again, keeps its `file_path`.
 
 use crate::{invalidate_query, library::Library, location::manager::LocationManagerError, Node};
 
@@ -26,7 +28,8 @@ use tokio::{fs, time::Instant};
 use tracing::{error, trace};
 
 use super::{
-	utils::{create_dir, recalculate_directories_size, remove, rename, update_file},
+	correlation::EventCorrelator,
+	utils::{create_dir, recalculate_directories_size, rename},
 	EventHandler, HUNDRED_MILLIS, ONE_SECOND,
 };
 
@@ -42,6 +45,7 @@ pub(super) struct LinuxEventHandler<'lib> {
 	reincident_to_update_files: HashMap<PathBuf, Instant>,
 	to_recalculate_size: HashMap<PathBuf, Instant>,
 	path_and_instant_buffer: Vec<(PathBuf, Instant)>,
+	correlator: EventCorrelator,
 }
 
 #[async_trait]
@@ -62,6 +66,7 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 			reincident_to_update_files: HashMap::new(),
 			to_recalculate_size: HashMap::new(),
 			path_and_instant_buffer: Vec::new(),
+			correlator: EventCorrelator::default(),
 		}
 	}
 
@@ -131,6 +136,7 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 					fs::metadata(to_path)
 						.await
 						.map_err(|e| FileIOError::from((to_path, e)))?,
+					self.node,
 					self.library,
 				)
 				.await?;
@@ -146,7 +152,9 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 					}
 				}
 
-				remove(self.location_id, &path, self.library).await?;
+				self.correlator
+					.remove(self.location_id, path, self.library)
+					.await?;
 			}
 			other_event_kind => {
 				trace!("Other Linux event that we don't handle for now: {other_event_kind:#?}");
@@ -169,6 +177,19 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 			self.recently_renamed_from
 				.retain(|_, instant| instant.elapsed() < HUNDRED_MILLIS);
 
+			if let Err(e) = self
+				.correlator
+				.evict(
+					self.location_id,
+					self.node,
+					self.library,
+					&mut self.to_recalculate_size,
+				)
+				.await
+			{
+				error!("Failed to remove file_path: {e:#?}");
+			}
+
 			if !self.to_recalculate_size.is_empty() {
 				if let Err(e) = recalculate_directories_size(
 					&mut self.to_recalculate_size,
@@ -190,7 +211,7 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 impl LinuxEventHandler<'_> {
 	async fn handle_to_update_eviction(&mut self) -> Result<(), LocationManagerError> {
 		self.path_and_instant_buffer.clear();
-		let mut should_invalidate = false;
+		let mut to_update = Vec::new();
 
 		for (path, created_at) in self.files_to_update.drain() {
 			if created_at.elapsed() < HUNDRED_MILLIS * 5 {
@@ -203,8 +224,7 @@ impl LinuxEventHandler<'_> {
 					}
 				}
 				self.reincident_to_update_files.remove(&path);
-				update_file(self.location_id, &path, self.node, self.library).await?;
-				should_invalidate = true;
+				to_update.push(path);
 			}
 		}
 
@@ -227,24 +247,26 @@ impl LinuxEventHandler<'_> {
 					}
 				}
 				self.files_to_update.remove(&path);
-				update_file(self.location_id, &path, self.node, self.library).await?;
-				should_invalidate = true;
+				to_update.push(path);
 			}
 		}
 
-		if should_invalidate {
-			invalidate_query!(self.library, "search.paths");
-		}
-
 		self.reincident_to_update_files
 			.extend(self.path_and_instant_buffer.drain(..));
 
+		if !to_update.is_empty() {
+			self.correlator
+				.update_files(self.location_id, to_update, self.node, self.library)
+				.await?;
+
+			invalidate_query!(self.library, "search.paths");
+		}
+
 		Ok(())
 	}
 
 	async fn handle_rename_from_eviction(&mut self) -> Result<(), LocationManagerError> {
 		self.path_and_instant_buffer.clear();
-		let mut should_invalidate = false;
 
 		for (path, instant) in self.rename_from.drain() {
 			if instant.elapsed() > HUNDRED_MILLIS {
@@ -254,18 +276,15 @@ impl LinuxEventHandler<'_> {
 							.insert(parent.to_path_buf(), Instant::now());
 					}
 				}
-				remove(self.location_id, &path, self.library).await?;
-				should_invalidate = true;
-				trace!("Removed file_path due timeout: {}", path.display());
+				trace!("Moved out of the location: {}", path.display());
+				self.correlator
+					.remove(self.location_id, path, self.library)
+					.await?;
 			} else {
 				self.path_and_instant_buffer.push((path, instant));
 			}
 		}
 
-		if should_invalidate {
-			invalidate_query!(self.library, "search.paths");
-		}
-
 		for (path, instant) in self.path_and_instant_buffer.drain(..) {
 			self.rename_from.insert(path, instant);
 		}
@@ -8,6 +8,8 @@
 //! way we have to handle like a file deletion, and the same applies for when a file is moved to our
 //! current location from anywhere else, we just receive the new path rename event, which means a
 //! creation.
+//! Removals and files settling down go through the [`EventCorrelator`], so a file saved by
+//! renaming a temporary file over it, or by removing and creating it again, keeps its `file_path`.
 
 use crate::{invalidate_query, library::Library, location::manager::LocationManagerError, Node};
 
@@ -30,9 +32,10 @@ use tokio::{fs, io, time::Instant};
 use tracing::{error, trace, warn};
 
 use super::{
+	correlation::EventCorrelator,
 	utils::{
-		create_dir, create_file, extract_inode_from_path, extract_location_path,
-		recalculate_directories_size, remove, rename, update_file,
+		create_dir, extract_inode_from_path, extract_location_path, find_renamed_by_normalization,
+		recalculate_directories_size, rename,
 	},
 	EventHandler, INode, InstantAndPath, HUNDRED_MILLIS, ONE_SECOND,
 };
@@ -51,6 +54,7 @@ pub(super) struct MacOsEventHandler<'lib> {
 	paths_map_buffer: Vec<(INode, InstantAndPath)>,
 	to_recalculate_size: HashMap<PathBuf, Instant>,
 	path_and_instant_buffer: Vec<(PathBuf, Instant)>,
+	correlator: EventCorrelator,
 }
 
 #[async_trait]
@@ -76,6 +80,7 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 			paths_map_buffer: Vec::new(),
 			to_recalculate_size: HashMap::new(),
 			path_and_instant_buffer: Vec::new(),
+			correlator: EventCorrelator::default(),
 		}
 	}
 
@@ -149,7 +154,9 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 							.insert(parent.to_path_buf(), Instant::now());
 					}
 				}
-				remove(self.location_id, &path, self.library).await?;
+				self.correlator
+					.remove(self.location_id, path, self.library)
+					.await?;
 			}
 			other_event_kind => {
 				trace!("Other MacOS event that we don't handle for now: {other_event_kind:#?}");
@@ -174,6 +181,19 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 				error!("Failed to remove file_path: {e:#?}");
 			}
 
+			if let Err(e) = self
+				.correlator
+				.evict(
+					self.location_id,
+					self.node,
+					self.library,
+					&mut self.to_recalculate_size,
+				)
+				.await
+			{
+				error!("Failed to remove file_path: {e:#?}");
+			}
+
 			if !self.to_recalculate_size.is_empty() {
 				if let Err(e) = recalculate_directories_size(
 					&mut self.to_recalculate_size,
@@ -195,7 +215,7 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 impl MacOsEventHandler<'_> {
 	async fn handle_to_update_eviction(&mut self) -> Result<(), LocationManagerError> {
 		self.path_and_instant_buffer.clear();
-		let mut should_invalidate = false;
+		let mut to_update = Vec::new();
 
 		for (path, created_at) in self.files_to_update.drain() {
 			if created_at.elapsed() < HUNDRED_MILLIS * 5 {
@@ -208,8 +228,7 @@ impl MacOsEventHandler<'_> {
 					}
 				}
 				self.reincident_to_update_files.remove(&path);
-				update_file(self.location_id, &path, self.node, self.library).await?;
-				should_invalidate = true;
+				to_update.push(path);
 			}
 		}
 
@@ -232,18 +251,21 @@ impl MacOsEventHandler<'_> {
 					}
 				}
 				self.files_to_update.remove(&path);
-				update_file(self.location_id, &path, self.node, self.library).await?;
-				should_invalidate = true;
+				to_update.push(path);
 			}
 		}
 
-		if should_invalidate {
-			invalidate_query!(self.library, "search.paths");
-		}
-
 		self.reincident_to_update_files
 			.extend(self.path_and_instant_buffer.drain(..));
 
+		if !to_update.is_empty() {
+			self.correlator
+				.update_files(self.location_id, to_update, self.node, self.library)
+				.await?;
+
+			invalidate_query!(self.library, "search.paths");
+		}
+
 		Ok(())
 	}
 
@@ -251,6 +273,7 @@ impl MacOsEventHandler<'_> {
 		// Just to make sure that our buffer is clean
 		self.paths_map_buffer.clear();
 		let mut should_invalidate = false;
+		let mut to_create = Vec::new();
 
 		for (inode, (instant, path)) in self.new_paths_map.drain() {
 			if instant.elapsed() > HUNDRED_MILLIS {
@@ -271,11 +294,10 @@ impl MacOsEventHandler<'_> {
 									.insert(parent.to_path_buf(), Instant::now());
 							}
 						}
-						create_file(self.location_id, &path, &metadata, self.node, self.library)
-							.await?;
+						trace!("Creating file_path due timeout: {}", path.display());
+						to_create.push(path);
 					}
 
-					trace!("Created file_path due timeout: {}", path.display());
 					should_invalidate = true;
 				}
 			} else {
@@ -283,19 +305,24 @@ impl MacOsEventHandler<'_> {
 			}
 		}
 
+		self.new_paths_map.extend(self.paths_map_buffer.drain(..));
+
+		if !to_create.is_empty() {
+			self.correlator
+				.update_files(self.location_id, to_create, self.node, self.library)
+				.await?;
+		}
+
 		if should_invalidate {
 			invalidate_query!(self.library, "search.paths");
 		}
 
-		self.new_paths_map.extend(self.paths_map_buffer.drain(..));
-
 		Ok(())
 	}
 
 	async fn handle_rename_remove_eviction(&mut self) -> Result<(), LocationManagerError> {
 		// Just to make sure that our buffer is clean
 		self.paths_map_buffer.clear();
-		let mut should_invalidate = false;
 
 		for (inode, (instant, path)) in self.old_paths_map.drain() {
 			if instant.elapsed() > HUNDRED_MILLIS {
@@ -305,18 +332,15 @@ impl MacOsEventHandler<'_> {
 							.insert(parent.to_path_buf(), Instant::now());
 					}
 				}
-				remove(self.location_id, &path, self.library).await?;
-				trace!("Removed file_path due timeout: {}", path.display());
-				should_invalidate = true;
+				trace!("Moved out of the location: {}", path.display());
+				self.correlator
+					.remove(self.location_id, path, self.library)
+					.await?;
 			} else {
 				self.paths_map_buffer.push((inode, (instant, path)));
 			}
 		}
 
-		if should_invalidate {
-			invalidate_query!(self.library, "search.paths");
-		}
-
 		self.old_paths_map.extend(self.paths_map_buffer.drain(..));
 
 		Ok(())
@@ -331,6 +355,33 @@ impl MacOsEventHandler<'_> {
 				// File or directory exists, so this can be a "new path" to an actual rename/move or a creation
 				trace!("Path exists: {}", path.display());
 
+				if let Some(old_path) = find_renamed_by_normalization(
+					self.location_id,
+					&path,
+					meta.is_dir(),
+					self.library,
+				)
+				.await?
+				{
+					trace!(
+						"Got a rename keeping the same file: {} -> {}",
+						old_path.display(),
+						path.display()
+					);
+
+					// Only its case or normalization changed, so the old path still "exists" and
+					// would never be paired with this one by its inode
+					return rename(
+						self.location_id,
+						&path,
+						&old_path,
+						meta,
+						self.node,
+						self.library,
+					)
+					.await;
+				}
+
 				let inode = get_inode(&meta);
 				let location_path = extract_location_path(self.location_id, self.library).await?;
 
@@ -353,14 +404,26 @@ impl MacOsEventHandler<'_> {
 						);
 
 						// We found a new path for this old path, so we can rename it
-						rename(self.location_id, &path, &old_path, meta, self.library).await?;
+						rename(
+							self.location_id,
+							&path,
+							&old_path,
+							meta,
+							self.node,
+							self.library,
+						)
+						.await?;
 					} else {
 						trace!("No match for new path yet: {}", path.display());
 						self.new_paths_map.insert(inode, (Instant::now(), path));
 					}
+				} else if meta.is_file() {
+					// Something was renamed over it, like a temporary file when editors save it
+					trace!("Path replaced by a rename: {}", path.display());
+					self.files_to_update.insert(path, Instant::now());
 				} else {
 					warn!(
-						"Received rename event for a file that already exists in the database: {}",
+						"Received rename event for a directory that already exists in the database: {}",
 						path.display()
 					);
 				}
@@ -396,6 +459,7 @@ impl MacOsEventHandler<'_> {
 						fs::metadata(&new_path)
 							.await
 							.map_err(|e| FileIOError::from((&new_path, e)))?,
+						self.node,
 						self.library,
 					)
 					.await?;
@@ -28,6 +28,7 @@ mod linux;
 mod macos;
 mod windows;
 
+mod correlation;
 mod utils;
 
 use utils::check_event;
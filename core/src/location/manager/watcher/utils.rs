@@ -3,15 +3,21 @@ use crate::{
 	library::Library,
 	location::{
 		create_file_path, delete_directory, find_location,
-		indexer::reverse_update_directories_sizes, location_with_indexer_rules,
-		manager::LocationManagerError, scan_location_sub_path, update_location_size,
+		indexer::{
+			reverse_update_directories_sizes,
+			rules::{seed::no_hidden, IndexerRule},
+		},
+		location_with_indexer_rules,
+		manager::LocationManagerError,
+		normalization::location_is_case_insensitive,
+		scan_location_sub_path, update_location_size, SymlinkPolicy,
 	},
 	object::{
 		file_identifier::FileMetadata,
 		media::{
 			media_data_extractor::{can_extract_media_data_for_image, extract_media_data},
 			media_data_image_to_query_params,
-			thumbnail::get_indexed_thumbnail_path,
+			thumbnail::remove_indexed_thumbnail,
 		},
 		validation::hash::file_checksum,
 	},
@@ -22,8 +28,8 @@ use sd_file_ext::{extensions::ImageExtension, kind::ObjectKind};
 use sd_file_path_helper::{
 	check_file_path_exists, file_path_with_object, filter_existing_file_path_params,
 	isolated_file_path_data::extract_normalized_materialized_path_str,
-	loose_find_existing_file_path_params, path_is_hidden, FilePathError, FilePathMetadata,
-	IsolatedFilePathData, MetadataExt,
+	loose_find_existing_file_path_params, normalize_name, path_is_hidden, FilePathError,
+	FilePathMetadata, IsolatedFilePathData, MetadataExt,
 };
 use sd_prisma::{
 	prisma::{file_path, location, media_data, object},
@@ -83,13 +89,21 @@ pub(super) async fn create_dir(
 	node: &Arc<Node>,
 	library: &Arc<Library>,
 ) -> Result<(), LocationManagerError> {
+	let path = path.as_ref();
+
+	if is_symlink(path).await {
+		return update_symlink(location_id, path, node, library).await;
+	}
+
 	let location = find_location(library, location_id)
 		.include(location_with_indexer_rules::include())
 		.exec()
 		.await?
 		.ok_or(LocationManagerError::MissingLocation(location_id))?;
 
-	let path = path.as_ref();
+	if skips_hidden(location.index_hidden, path).await {
+		return Ok(());
+	}
 
 	let location_path = maybe_missing(&location.path, "location.path")?;
 
@@ -135,24 +149,6 @@ pub(super) async fn create_dir(
 	Ok(())
 }
 
-pub(super) async fn create_file(
-	location_id: location::id::Type,
-	path: impl AsRef<Path>,
-	metadata: &Metadata,
-	node: &Arc<Node>,
-	library: &Arc<Library>,
-) -> Result<(), LocationManagerError> {
-	inner_create_file(
-		location_id,
-		extract_location_path(location_id, library).await?,
-		path,
-		metadata,
-		node,
-		library,
-	)
-	.await
-}
-
 async fn inner_create_file(
 	location_id: location::id::Type,
 	location_path: impl AsRef<Path>,
@@ -169,6 +165,18 @@ async fn inner_create_file(
 	let path = path.as_ref();
 	let location_path = location_path.as_ref();
 
+	let index_hidden = db
+		.location()
+		.find_unique(location::id::equals(location_id))
+		.select(location::select!({ index_hidden }))
+		.exec()
+		.await?
+		.and_then(|location| location.index_hidden);
+
+	if skips_hidden(index_hidden, path).await {
+		return Ok(());
+	}
+
 	trace!(
 		"Location: <root_path ='{}'> creating file: {}",
 		location_path.display(),
@@ -237,6 +245,7 @@ async fn inner_create_file(
 		cas_id,
 		kind,
 		fs_metadata,
+		..
 	} = FileMetadata::new(&location_path, &iso_file_path).await?;
 
 	debug!("Creating path: {}", iso_file_path);
@@ -312,7 +321,10 @@ async fn inner_create_file(
 	)
 	.await?;
 
-	if !extension.is_empty() && matches!(kind, ObjectKind::Image | ObjectKind::Video) {
+	if !extension.is_empty()
+		&& matches!(kind, ObjectKind::Image | ObjectKind::Video)
+		&& generates_preview_media(location_id, library).await?
+	{
 		// Running in a detached task as thumbnail generation can take a while and we don't want to block the watcher
 
 		if let Some(cas_id) = cas_id {
@@ -370,6 +382,66 @@ async fn inner_create_file(
 	Ok(())
 }
 
+/// Whether the path is hidden in a location which doesn't index hidden files, like the indexer
+/// skips them with the `No Hidden` rule.
+async fn skips_hidden(index_hidden: Option<bool>, path: impl AsRef<Path>) -> bool {
+	!index_hidden.unwrap_or(true)
+		&& IndexerRule::from(no_hidden())
+			.apply(path)
+			.await
+			.is_ok_and(|results| results.into_iter().any(|(_, passed)| !passed))
+}
+
+pub(super) async fn is_symlink(path: impl AsRef<Path>) -> bool {
+	fs::symlink_metadata(path)
+		.await
+		.is_ok_and(|metadata| metadata.is_symlink())
+}
+
+/// Symlinks are left to the indexer, which resolves them under the location's [`SymlinkPolicy`],
+/// by scanning the directory they showed up in.
+pub(super) async fn update_symlink(
+	location_id: location::id::Type,
+	path: impl AsRef<Path>,
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+) -> Result<(), LocationManagerError> {
+	let location = find_location(library, location_id)
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?
+		.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+	if SymlinkPolicy::from(location.symlink_policy) == SymlinkPolicy::Skip {
+		return Ok(());
+	}
+
+	let location_path = Path::new(maybe_missing(&location.path, "location.path")?);
+
+	let Some(parent) = path.as_ref().parent() else {
+		return Ok(());
+	};
+
+	trace!(
+		"Location: <root_path ='{}'> scanning for symlink: {}",
+		location_path.display(),
+		path.as_ref().display()
+	);
+
+	scan_location_sub_path(
+		node,
+		library,
+		location,
+		if parent == location_path {
+			PathBuf::new()
+		} else {
+			parent.to_path_buf()
+		},
+	)
+	.await
+	.map_err(Into::into)
+}
+
 pub(super) async fn update_file(
 	location_id: location::id::Type,
 	full_path: impl AsRef<Path>,
@@ -444,6 +516,7 @@ async fn inner_update_file(
 		cas_id,
 		fs_metadata,
 		kind,
+		..
 	} = FileMetadata::new(&location_path, &iso_file_path).await?;
 
 	let inode = if let Some(inode) = maybe_new_inode {
@@ -633,7 +706,13 @@ async fn inner_update_file(
 
 			if let Some(old_cas_id) = &file_path.cas_id {
 				// if this file had a thumbnail previously, we update it to match the new content
-				if library.thumbnail_exists(node, old_cas_id).await? {
+				if library.thumbnail_exists(node, old_cas_id).await?
+					&& generates_preview_media(
+						maybe_missing(file_path.location_id, "file_path.location_id")?,
+						library,
+					)
+					.await?
+				{
 					if let Some(ext) = file_path.extension.clone() {
 						// Running in a detached task as thumbnail generation can take a while and we don't want to block the watcher
 						if let Some(cas_id) = cas_id {
@@ -657,13 +736,11 @@ async fn inner_update_file(
 								// so we overwrote our previous thumbnail, so we can't remove it
 								if !was_overwritten {
 									// remove the old thumbnail as we're generating a new one
-									let thumb_path =
-										get_indexed_thumbnail_path(&node, &old_cas_id, library_id);
-									if let Err(e) = fs::remove_file(&thumb_path).await {
-										error!(
-											"Failed to remove old thumbnail: {:#?}",
-											FileIOError::from((thumb_path, e))
-										);
+									if let Err(e) =
+										remove_indexed_thumbnail(&node, &old_cas_id, library_id)
+											.await
+									{
+										error!("Failed to remove old thumbnail: {e:#?}");
 									}
 								}
 							});
@@ -736,12 +813,50 @@ pub(super) async fn rename(
 	new_path: impl AsRef<Path>,
 	old_path: impl AsRef<Path>,
 	new_path_metadata: Metadata,
-	library: &Library,
+	node: &Arc<Node>,
+	library: &Arc<Library>,
 ) -> Result<(), LocationManagerError> {
 	let location_path = extract_location_path(location_id, library).await?;
 	let old_path = old_path.as_ref();
 	let new_path = new_path.as_ref();
-	let Library { db, .. } = library;
+	let Library { db, .. } = &**library;
+
+	let old_file_path = db
+		.file_path()
+		.find_first(loose_find_existing_file_path_params(
+			location_id,
+			&location_path,
+			old_path,
+		)?)
+		.exec()
+		.await?;
+
+	// Renaming over an existing file, like editors saving through a temporary file, keeps the
+	// replaced file_path and its object, only updating it with the new contents
+	if new_path_metadata.is_file() {
+		if let Some(replaced) = db
+			.file_path()
+			.find_first(loose_find_existing_file_path_params(
+				location_id,
+				&location_path,
+				new_path,
+			)?)
+			.exec()
+			.await?
+		{
+			// Unless it's the same file_path, when only the case of its name changed
+			if old_file_path
+				.as_ref()
+				.map_or(true, |old| old.pub_id != replaced.pub_id)
+			{
+				if let Some(old) = &old_file_path {
+					remove_by_file_path(location_id, old_path, old, library).await?;
+				}
+
+				return update_file(location_id, new_path, node, library).await;
+			}
+		}
+	}
 
 	let old_path_materialized_str =
 		extract_normalized_materialized_path_str(location_id, &location_path, old_path)?;
@@ -763,16 +878,7 @@ pub(super) async fn rename(
 		});
 	}
 
-	if let Some(file_path) = db
-		.file_path()
-		.find_first(loose_find_existing_file_path_params(
-			location_id,
-			&location_path,
-			old_path,
-		)?)
-		.exec()
-		.await?
-	{
+	if let Some(file_path) = old_file_path {
 		let is_dir = maybe_missing(file_path.is_dir, "file_path.is_dir")?;
 
 		let new = IsolatedFilePathData::new(location_id, &location_path, new_path, is_dir)?;
@@ -806,6 +912,7 @@ pub(super) async fn rename(
 		}
 
 		let is_hidden = path_is_hidden(new_path, &new_path_metadata);
+		let case_insensitive = is_case_insensitive(location_id, library).await?;
 
 		library
 			.db
@@ -820,6 +927,7 @@ pub(super) async fn rename(
 						DateTime::<Utc>::from(new_path_metadata.modified_or_now()).into(),
 					)),
 					file_path::hidden::set(Some(is_hidden)),
+					file_path::name_normalized::set(Some(new.normalized_name(case_insensitive))),
 				],
 			)
 			.exec()
@@ -832,6 +940,87 @@ pub(super) async fn rename(
 	Ok(())
 }
 
+/// Finds the path a file was known by before being renamed to `path`, when the filesystem
+/// considers both names the same, like when only their case or unicode normalization changed.
+///
+/// Such renames can be reported as the new path existing without any event for the old one.
+pub(super) async fn find_renamed_by_normalization(
+	location_id: location::id::Type,
+	path: impl AsRef<Path>,
+	is_dir: bool,
+	library: &Library,
+) -> Result<Option<PathBuf>, LocationManagerError> {
+	let path = path.as_ref();
+	let location_path = extract_location_path(location_id, library).await?;
+	let case_insensitive = is_case_insensitive(location_id, library).await?;
+
+	let iso_file_path = IsolatedFilePathData::new(location_id, &location_path, path, is_dir)?;
+	let parts = iso_file_path.to_parts();
+	let full_name = iso_file_path.full_name();
+
+	let Some(file_path) = library
+		.db
+		.file_path()
+		.find_first(vec![
+			file_path::location_id::equals(Some(location_id)),
+			file_path::materialized_path::equals(Some(parts.materialized_path.to_string())),
+			file_path::name_normalized::equals(Some(normalize_name(&full_name, case_insensitive))),
+		])
+		.exec()
+		.await?
+	else {
+		return Ok(None);
+	};
+
+	let old_path = location_path.join(IsolatedFilePathData::try_from(&file_path)?);
+
+	// Same name, so it isn't a rename, but just the file changing
+	if old_path.file_name() == path.file_name() {
+		return Ok(None);
+	}
+
+	// The old name must be gone, otherwise these are two different files
+	let Some(parent) = path.parent() else {
+		return Ok(None);
+	};
+
+	let mut read_dir = fs::read_dir(parent)
+		.await
+		.map_err(|e| FileIOError::from((parent, e)))?;
+
+	while let Some(entry) = read_dir
+		.next_entry()
+		.await
+		.map_err(|e| FileIOError::from((parent, e)))?
+	{
+		if Some(entry.file_name().as_os_str()) == old_path.file_name() {
+			return Ok(None);
+		}
+	}
+
+	Ok(Some(old_path))
+}
+
+async fn is_case_insensitive(
+	location_id: location::id::Type,
+	library: &Library,
+) -> Result<bool, LocationManagerError> {
+	let location = find_location(library, location_id)
+		.select(location::select!({ path case_insensitive }))
+		.exec()
+		.await?
+		.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+	location_is_case_insensitive(
+		&library.db,
+		location_id,
+		location.path.as_ref(),
+		location.case_insensitive,
+	)
+	.await
+	.map_err(Into::into)
+}
+
 pub(super) async fn remove(
 	location_id: location::id::Type,
 	full_path: impl AsRef<Path>,
@@ -959,6 +1148,19 @@ pub(super) async fn extract_location_path(
 		)
 }
 
+/// Whether thumbnails should be generated for files in the location, see `location.generate_preview_media`.
+async fn generates_preview_media(
+	location_id: location::id::Type,
+	library: &Library,
+) -> Result<bool, LocationManagerError> {
+	Ok(find_location(library, location_id)
+		.select(location::select!({ generate_preview_media }))
+		.exec()
+		.await?
+		.and_then(|location| location.generate_preview_media)
+		.unwrap_or(true))
+}
+
 pub(super) async fn recalculate_directories_size(
 	candidates: &mut HashMap<PathBuf, Instant>,
 	buffer: &mut Vec<(PathBuf, Instant)>,
@@ -5,7 +5,8 @@
 //! directory, we receive a remove event and then a create event, so to avoid having to actually
 //! remove and create the `file_path` in the database, we have to wait some time after receiving
 //! a remove event to see if a create event is emitted. If it is, we just update the `file_path`
-//! in the database. If not, we remove the file from the database.
+//! in the database. If not, we hand it to the [`EventCorrelator`], which also matches it against
+//! files created with the same contents, like when editors save through a temporary file.
 
 use crate::{invalidate_query, library::Library, location::manager::LocationManagerError, Node};
 
@@ -28,10 +29,8 @@ use tokio::{fs, time::Instant};
 use tracing::{error, trace};
 
 use super::{
-	utils::{
-		create_dir, extract_inode_from_path, recalculate_directories_size, remove, rename,
-		update_file,
-	},
+	correlation::EventCorrelator,
+	utils::{create_dir, extract_inode_from_path, recalculate_directories_size, rename},
 	EventHandler, INode, InstantAndPath, HUNDRED_MILLIS, ONE_SECOND,
 };
 
@@ -50,6 +49,7 @@ pub(super) struct WindowsEventHandler<'lib> {
 	reincident_to_update_files: HashMap<PathBuf, Instant>,
 	to_recalculate_size: HashMap<PathBuf, Instant>,
 	path_and_instant_buffer: Vec<(PathBuf, Instant)>,
+	correlator: EventCorrelator,
 }
 
 #[async_trait]
@@ -75,6 +75,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 			reincident_to_update_files: HashMap::new(),
 			to_recalculate_size: HashMap::new(),
 			path_and_instant_buffer: Vec::new(),
+			correlator: EventCorrelator::default(),
 		}
 	}
 
@@ -121,6 +122,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 						fs::metadata(&paths[0])
 							.await
 							.map_err(|e| FileIOError::from((&paths[0], e)))?,
+						self.node,
 						self.library,
 					)
 					.await?;
@@ -176,6 +178,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 						fs::metadata(&new_path)
 							.await
 							.map_err(|e| FileIOError::from((&new_path, e)))?,
+						self.node,
 						self.library,
 					)
 					.await?;
@@ -197,6 +200,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 						fs::metadata(&path)
 							.await
 							.map_err(|e| FileIOError::from((&path, e)))?,
+						self.node,
 						self.library,
 					)
 					.await?;
@@ -245,6 +249,19 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 				error!("Failed to remove file_path: {e:#?}");
 			}
 
+			if let Err(e) = self
+				.correlator
+				.evict(
+					self.location_id,
+					self.node,
+					self.library,
+					&mut self.to_recalculate_size,
+				)
+				.await
+			{
+				error!("Failed to remove file_path: {e:#?}");
+			}
+
 			if !self.to_recalculate_size.is_empty() {
 				if let Err(e) = recalculate_directories_size(
 					&mut self.to_recalculate_size,
@@ -266,22 +283,20 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 impl WindowsEventHandler<'_> {
 	async fn handle_to_update_eviction(&mut self) -> Result<(), LocationManagerError> {
 		self.path_and_instant_buffer.clear();
-		let mut should_invalidate = false;
+		let mut to_update = Vec::new();
 
 		for (path, created_at) in self.files_to_update.drain() {
 			if created_at.elapsed() < HUNDRED_MILLIS * 5 {
 				self.path_and_instant_buffer.push((path, created_at));
 			} else {
+				if let Some(parent) = path.parent() {
+					if parent != Path::new("") {
+						self.to_recalculate_size
+							.insert(parent.to_path_buf(), Instant::now());
+					}
+				}
 				self.reincident_to_update_files.remove(&path);
-				handle_update(
-					self.location_id,
-					&path,
-					self.node,
-					&mut self.to_recalculate_size,
-					self.library,
-				)
-				.await?;
-				should_invalidate = true;
+				to_update.push(path);
 			}
 		}
 
@@ -297,32 +312,33 @@ impl WindowsEventHandler<'_> {
 			if created_at.elapsed() < ONE_SECOND * 10 {
 				self.path_and_instant_buffer.push((path, created_at));
 			} else {
+				if let Some(parent) = path.parent() {
+					if parent != Path::new("") {
+						self.to_recalculate_size
+							.insert(parent.to_path_buf(), Instant::now());
+					}
+				}
 				self.files_to_update.remove(&path);
-				handle_update(
-					self.location_id,
-					&path,
-					self.node,
-					&mut self.to_recalculate_size,
-					self.library,
-				)
-				.await?;
-				should_invalidate = true;
+				to_update.push(path);
 			}
 		}
 
-		if should_invalidate {
-			invalidate_query!(self.library, "search.paths");
-		}
-
 		self.reincident_to_update_files
 			.extend(self.path_and_instant_buffer.drain(..));
 
+		if !to_update.is_empty() {
+			self.correlator
+				.update_files(self.location_id, to_update, self.node, self.library)
+				.await?;
+
+			invalidate_query!(self.library, "search.paths");
+		}
+
 		Ok(())
 	}
 
 	async fn handle_removes_eviction(&mut self) -> Result<(), LocationManagerError> {
 		self.files_to_remove_buffer.clear();
-		let mut should_invalidate = false;
 
 		for (inode, (instant, path)) in self.files_to_remove.drain() {
 			if instant.elapsed() > HUNDRED_MILLIS {
@@ -332,16 +348,13 @@ impl WindowsEventHandler<'_> {
 							.insert(parent.to_path_buf(), Instant::now());
 					}
 				}
-				remove(self.location_id, &path, self.library).await?;
-				should_invalidate = true;
-				trace!("Removed file_path due timeout: {}", path.display());
+				self.correlator
+					.remove(self.location_id, path, self.library)
+					.await?;
 			} else {
 				self.files_to_remove_buffer.push((inode, (instant, path)));
 			}
 		}
-		if should_invalidate {
-			invalidate_query!(self.library, "search.paths");
-		}
 
 		for (key, value) in self.files_to_remove_buffer.drain(..) {
 			self.files_to_remove.insert(key, value);
@@ -350,25 +363,3 @@ impl WindowsEventHandler<'_> {
 		Ok(())
 	}
 }
-
-async fn handle_update<'lib>(
-	location_id: location::id::Type,
-	path: &PathBuf,
-	node: &'lib Arc<Node>,
-	to_recalculate_size: &mut HashMap<PathBuf, Instant>,
-	library: &'lib Arc<Library>,
-) -> Result<(), LocationManagerError> {
-	let metadata = fs::metadata(&path)
-		.await
-		.map_err(|e| FileIOError::from((&path, e)))?;
-	if metadata.is_file() {
-		if let Some(parent) = path.parent() {
-			if parent != Path::new("") {
-				to_recalculate_size.insert(parent.to_path_buf(), Instant::now());
-			}
-		}
-		update_file(location_id, path, node, library).await?;
-	}
-
-	Ok(())
-}
@@ -1,4 +1,9 @@
-use crate::library::LibraryId;
+use crate::{
+	library::LibraryId,
+	util::version_manager::{Kind, ManagedVersion, VersionManagerError},
+};
+
+use sd_file_path_helper::{get_filesystem_id, FilePathError, FilesystemId};
 
 use std::{
 	collections::{HashMap, HashSet},
@@ -6,15 +11,18 @@ use std::{
 };
 
 use chrono::{DateTime, Utc};
+use int_enum::IntEnum;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 use thiserror::Error;
 use tokio::{fs, io};
-use tracing::error;
 use uuid::Uuid;
 
 use super::LocationPubId;
 
 static SPACEDRIVE_LOCATION_METADATA_FILE: &str = ".spacedrive";
+const VERSION_FIELD: &str = "version";
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 struct LocationMetadata {
@@ -25,11 +33,34 @@ struct LocationMetadata {
 	updated_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 struct SpacedriveLocationMetadata {
 	libraries: HashMap<LibraryId, LocationMetadata>,
+	/// The filesystem id of the location's directory, used to find it again if it's renamed.
+	/// Metadata files written before it existed don't have it.
+	#[serde(default)]
+	root_id: Option<FilesystemId>,
 	created_at: DateTime<Utc>,
 	updated_at: DateTime<Utc>,
+	version: SpacedriveLocationMetadataVersion,
+}
+
+#[derive(
+	IntEnum, Debug, Clone, Copy, Eq, PartialEq, strum::Display, Serialize_repr, Deserialize_repr,
+)]
+#[repr(u64)]
+pub enum SpacedriveLocationMetadataVersion {
+	/// Files written before the version field existed.
+	V0 = 0,
+	V1 = 1,
+}
+
+impl ManagedVersion<SpacedriveLocationMetadataVersion> for SpacedriveLocationMetadata {
+	const LATEST_VERSION: SpacedriveLocationMetadataVersion = SpacedriveLocationMetadataVersion::V1;
+
+	const KIND: Kind = Kind::Json(VERSION_FIELD);
+
+	type MigrationError = LocationMetadataError;
 }
 
 pub struct SpacedriveLocationMetadataFile {
@@ -41,47 +72,50 @@ impl SpacedriveLocationMetadataFile {
 	pub async fn try_load(
 		location_path: impl AsRef<Path>,
 	) -> Result<Option<Self>, LocationMetadataError> {
-		let metadata_file_name = location_path
-			.as_ref()
-			.join(SPACEDRIVE_LOCATION_METADATA_FILE);
-
-		match fs::read(&metadata_file_name).await {
-			Ok(data) => Ok(Some(Self {
-				metadata: match serde_json::from_slice(&data) {
-					Ok(data) => data,
-					Err(e) => {
-						#[cfg(debug_assertions)]
-						{
-							error!(
-								"Failed to deserialize corrupted metadata file, \
-								we will remove it and create a new one; File: {}; Error: {e}",
-								metadata_file_name.display()
-							);
-							fs::remove_file(&metadata_file_name).await.map_err(|e| {
-								LocationMetadataError::Delete(
-									e,
-									location_path.as_ref().to_path_buf(),
-								)
-							})?;
-
-							return Ok(None);
-						}
-
-						#[cfg(not(debug_assertions))]
-						return Err(LocationMetadataError::Deserialize(
-							e,
-							location_path.as_ref().to_path_buf(),
-						));
-					}
-				},
-				path: metadata_file_name,
-			})),
-			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
-			Err(e) => Err(LocationMetadataError::Read(
-				e,
-				location_path.as_ref().to_path_buf(),
-			)),
+		let location_path = location_path.as_ref();
+		let metadata_file_name = location_path.join(SPACEDRIVE_LOCATION_METADATA_FILE);
+
+		let data = match fs::read(&metadata_file_name).await {
+			Ok(data) => data,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(LocationMetadataError::Read(e, location_path.to_path_buf())),
+		};
+
+		let mut fields = serde_json::from_slice::<Map<String, Value>>(&data)
+			.map_err(|e| LocationMetadataError::Deserialize(e, location_path.to_path_buf()))?;
+
+		let version = match fields.get(VERSION_FIELD).map(Value::as_u64) {
+			// Written before the version field existed
+			None => SpacedriveLocationMetadataVersion::V0,
+			Some(Some(version)) => {
+				SpacedriveLocationMetadataVersion::from_int(version).map_err(|_| {
+					LocationMetadataError::NewerVersion(version, metadata_file_name.clone())
+				})?
+			}
+			// Not a version at all, so deserializing fails on it below
+			Some(None) => SpacedriveLocationMetadata::LATEST_VERSION,
+		};
+
+		// Migrated in memory only, as loading mustn't write anything, the file is brought up to
+		// date the next time it's written
+		match version {
+			// Only the version field is new, `root_id` already has a default
+			SpacedriveLocationMetadataVersion::V0 => {
+				fields.insert(
+					VERSION_FIELD.to_string(),
+					json!(SpacedriveLocationMetadataVersion::V1.int_value()),
+				);
+			}
+			SpacedriveLocationMetadataVersion::V1 => {}
 		}
+
+		let metadata = serde_json::from_value(Value::Object(fields))
+			.map_err(|e| LocationMetadataError::Deserialize(e, location_path.to_path_buf()))?;
+
+		Ok(Some(Self {
+			path: metadata_file_name,
+			metadata,
+		}))
 	}
 
 	pub async fn create_and_save(
@@ -107,8 +141,10 @@ impl SpacedriveLocationMetadataFile {
 				)]
 				.into_iter()
 				.collect(),
+				root_id: Some(get_filesystem_id(&location_path).await?),
 				created_at: Utc::now(),
 				updated_at: Utc::now(),
+				version: SpacedriveLocationMetadata::LATEST_VERSION,
 			},
 		}
 		.write_metadata()
@@ -133,6 +169,7 @@ impl SpacedriveLocationMetadataFile {
 
 		location_metadata.path = new_path;
 		location_metadata.updated_at = Utc::now();
+		self.metadata.root_id = Some(get_filesystem_id(&location_path).await?);
 		self.path = location_path
 			.as_ref()
 			.join(SPACEDRIVE_LOCATION_METADATA_FILE);
@@ -175,6 +212,10 @@ impl SpacedriveLocationMetadataFile {
 			},
 		);
 
+		if self.metadata.root_id.is_none() {
+			self.metadata.root_id = Some(get_filesystem_id(&location_path).await?);
+		}
+
 		self.metadata.updated_at = Utc::now();
 		self.write_metadata().await
 	}
@@ -190,6 +231,10 @@ impl SpacedriveLocationMetadataFile {
 			.map(|l| l.path.as_path())
 	}
 
+	pub fn root_id(&self) -> Option<FilesystemId> {
+		self.metadata.root_id
+	}
+
 	pub fn is_empty(&self) -> bool {
 		self.metadata.libraries.is_empty()
 	}
@@ -198,6 +243,13 @@ impl SpacedriveLocationMetadataFile {
 		&mut self,
 		library_id: LibraryId,
 	) -> Result<(), LocationMetadataError> {
+		// Other libraries may have written to the file since it was loaded, their entries must be kept
+		if let Some(location_path) = self.path.parent() {
+			if let Ok(Some(Self { metadata, .. })) = Self::try_load(location_path).await {
+				self.metadata = metadata;
+			}
+		}
+
 		self.metadata
 			.libraries
 			.remove(&library_id)
@@ -214,16 +266,25 @@ impl SpacedriveLocationMetadataFile {
 		}
 	}
 
-	pub async fn clean_stale_libraries(
-		&mut self,
-		existing_libraries_ids: &HashSet<LibraryId>,
-	) -> Result<(), LocationMetadataError> {
+	pub fn library_ids(&self) -> impl Iterator<Item = LibraryId> + '_ {
+		self.metadata.libraries.keys().copied()
+	}
+
+	/// Like [`Self::clean_stale_libraries`], without touching the file.
+	pub fn forget_stale_libraries(&mut self, existing_libraries_ids: &HashSet<LibraryId>) -> bool {
 		let previous_libraries_count = self.metadata.libraries.len();
 		self.metadata
 			.libraries
 			.retain(|library_id, _| existing_libraries_ids.contains(library_id));
 
-		if self.metadata.libraries.len() != previous_libraries_count {
+		self.metadata.libraries.len() != previous_libraries_count
+	}
+
+	pub async fn clean_stale_libraries(
+		&mut self,
+		existing_libraries_ids: &HashSet<LibraryId>,
+	) -> Result<(), LocationMetadataError> {
+		if self.forget_stale_libraries(existing_libraries_ids) {
 			self.metadata.updated_at = Utc::now();
 
 			if !self.metadata.libraries.is_empty() {
@@ -273,4 +334,199 @@ pub enum LocationMetadataError {
 	Deserialize(serde_json::Error, PathBuf),
 	#[error("Failed to relink, as the new location path is the same as the old path: {0}")]
 	RelinkSamePath(PathBuf),
+	#[error("Failed to get the filesystem id of the location: {0}")]
+	FilesystemId(#[from] FilePathError),
+	#[error("Location metadata file (path: {1:?}) was written by a newer version: {0}")]
+	NewerVersion(u64, PathBuf),
+	#[error(transparent)]
+	VersionManager(#[from] VersionManagerError<SpacedriveLocationMetadataVersion>),
+}
+
+impl LocationMetadataError {
+	/// Whether the file is there but can't be made sense of, as it's corrupted.
+	///
+	/// Files written by a newer version aren't, as they're only unknown to this one.
+	pub fn is_unreadable(&self) -> bool {
+		matches!(self, Self::Deserialize(..))
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_remove_library_keeps_other_libraries() {
+		let location = tempdir().unwrap();
+		let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+		let (second_pub_id, third_pub_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			first,
+			Uuid::new_v4(),
+			location.path(),
+			"first".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let mut metadata = SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.unwrap()
+			.unwrap();
+		metadata
+			.add_library(second, second_pub_id, location.path(), "second".to_string())
+			.await
+			.unwrap();
+
+		// Added by someone else after `metadata` was loaded
+		SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.unwrap()
+			.unwrap()
+			.add_library(third, third_pub_id, location.path(), "third".to_string())
+			.await
+			.unwrap();
+
+		metadata.remove_library(first).await.unwrap();
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.unwrap()
+			.unwrap();
+		assert!(!metadata.has_library(first));
+		assert_eq!(metadata.location_pub_id(second).unwrap(), second_pub_id);
+		assert_eq!(metadata.location_pub_id(third).unwrap(), third_pub_id);
+		assert_eq!(metadata.metadata.libraries[&second].name, "second");
+		assert_eq!(metadata.metadata.libraries[&third].name, "third");
+	}
+
+	#[tokio::test]
+	async fn test_remove_last_library_deletes_file() {
+		let location = tempdir().unwrap();
+		let library_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			Uuid::new_v4(),
+			location.path(),
+			"location".to_string(),
+		)
+		.await
+		.unwrap();
+
+		SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.unwrap()
+			.unwrap()
+			.remove_library(library_id)
+			.await
+			.unwrap();
+
+		assert!(SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.unwrap()
+			.is_none());
+	}
+
+	#[tokio::test]
+	async fn test_unversioned_file_is_migrated() {
+		let location = tempdir().unwrap();
+		let library_id = Uuid::new_v4();
+		let metadata_file = location.path().join(SPACEDRIVE_LOCATION_METADATA_FILE);
+
+		// As written before the version field and the root id existed
+		fs::write(
+			&metadata_file,
+			serde_json::to_vec(&json!({
+				"libraries": {
+					library_id.to_string(): {
+						"pub_id": Uuid::new_v4(),
+						"name": "old",
+						"path": location.path(),
+						"created_at": Utc::now(),
+						"updated_at": Utc::now(),
+					}
+				},
+				"created_at": Utc::now(),
+				"updated_at": Utc::now(),
+			}))
+			.unwrap(),
+		)
+		.await
+		.unwrap();
+
+		let mut metadata = SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.unwrap()
+			.unwrap();
+		assert!(metadata.has_library(library_id));
+		assert_eq!(
+			metadata.metadata.version,
+			SpacedriveLocationMetadata::LATEST_VERSION
+		);
+
+		let read_fields =
+			|bytes: Vec<u8>| serde_json::from_slice::<Map<String, Value>>(&bytes).unwrap();
+
+		// Only written once something changes
+		assert!(!read_fields(fs::read(&metadata_file).await.unwrap()).contains_key(VERSION_FIELD));
+
+		metadata
+			.update(library_id, "new".to_string())
+			.await
+			.unwrap();
+		assert_eq!(
+			read_fields(fs::read(&metadata_file).await.unwrap())[VERSION_FIELD],
+			json!(SpacedriveLocationMetadata::LATEST_VERSION.int_value())
+		);
+	}
+
+	#[tokio::test]
+	async fn test_unreadable_file() {
+		let location = tempdir().unwrap();
+		let metadata_file = location.path().join(SPACEDRIVE_LOCATION_METADATA_FILE);
+
+		fs::write(&metadata_file, b"{ not json").await.unwrap();
+		assert!(SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.err()
+			.unwrap()
+			.is_unreadable());
+
+		fs::write(
+			&metadata_file,
+			serde_json::to_vec(&json!({ VERSION_FIELD: "one" })).unwrap(),
+		)
+		.await
+		.unwrap();
+		assert!(SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.err()
+			.unwrap()
+			.is_unreadable());
+	}
+
+	#[tokio::test]
+	async fn test_newer_version_is_refused() {
+		let location = tempdir().unwrap();
+		let metadata_file = location.path().join(SPACEDRIVE_LOCATION_METADATA_FILE);
+		let data = serde_json::to_vec(&json!({ VERSION_FIELD: u64::MAX })).unwrap();
+
+		fs::write(&metadata_file, &data).await.unwrap();
+
+		let e = SpacedriveLocationMetadataFile::try_load(location.path())
+			.await
+			.err()
+			.unwrap();
+		assert!(matches!(
+			e,
+			LocationMetadataError::NewerVersion(u64::MAX, _)
+		));
+		assert!(!e.is_unreadable());
+		assert_eq!(fs::read(&metadata_file).await.unwrap(), data);
+	}
 }
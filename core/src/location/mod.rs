@@ -1,15 +1,20 @@
 use crate::{
+	api::notifications::{NotificationData, NotificationKind},
 	invalidate_query,
-	job::{JobBuilder, JobError, JobManagerError},
-	library::Library,
+	job::{Job, JobBuilder, JobError, JobManagerError, JobPriority},
+	library::{Library, LibraryId},
 	object::{
 		file_identifier::{self, file_identifier_job::FileIdentifierJobInit},
 		media::{media_processor, MediaProcessorJobInit},
 	},
+	preferences::LibraryPreferences,
+	volume::get_volumes,
 	Node,
 };
 
-use sd_file_path_helper::{filter_existing_file_path_params, IsolatedFilePathData};
+use sd_file_path_helper::{
+	filter_existing_file_path_params, get_filesystem_id, IsolatedFilePathData,
+};
 use sd_prisma::{
 	prisma::{file_path, indexer_rules_in_location, location, PrismaClient},
 	prisma_sync,
@@ -34,11 +39,11 @@ use chrono::Utc;
 use futures::future::TryFutureExt;
 use normpath::PathExt;
 use prisma_client_rust::{operator::and, or, QueryError};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use specta::Type;
 use tokio::{fs, io, time::Instant};
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 mod error;
@@ -46,11 +51,16 @@ pub mod indexer;
 mod manager;
 pub mod metadata;
 pub mod non_indexed;
+pub(crate) mod normalization;
+mod preview;
+pub mod symlink;
 
-pub use error::LocationError;
+pub use error::{LocationCreateError, LocationError};
 use indexer::IndexerJobInit;
-pub use manager::{LocationManagerError, Locations};
+pub use manager::{LocationManagerError, Locations, RESCAN_CHECK_INTERVAL};
 use metadata::SpacedriveLocationMetadataFile;
+pub use preview::{IndexerRuleImpact, LocationCreatePreview, OtherLibrary};
+pub use symlink::SymlinkPolicy;
 
 pub type LocationPubId = Uuid;
 
@@ -62,11 +72,23 @@ location::include!(location_with_indexer_rules {
 /// `LocationCreateArgs` is the argument received from the client using `rspc` to create a new location.
 /// It has the actual path and a vector of indexer rules ids, to create many-to-many relationships
 /// between the location and indexer rules.
+///
+/// When `interactive` is set, the top level of the location is scanned before returning, so it can
+/// be shown right away, and the full scan is queued ahead of other jobs.
+///
+/// A location inside another one of the library, or containing one, is rejected as its files would
+/// be indexed twice, unless `force` is set for overlaps that are meant to be.
 #[derive(Type, Deserialize)]
 pub struct LocationCreateArgs {
 	pub path: PathBuf,
 	pub dry_run: bool,
 	pub indexer_rules_ids: Vec<i32>,
+	#[serde(default)]
+	#[specta(optional)]
+	pub interactive: bool,
+	#[serde(default)]
+	#[specta(optional)]
+	pub force: bool,
 }
 
 impl LocationCreateArgs {
@@ -87,9 +109,13 @@ impl LocationCreateArgs {
 				return Err(LocationError::PathNotFound(self.path.into_boxed_path()))
 			}
 			Err(e) => {
-				return Err(LocationError::LocationPathFilesystemMetadataAccess(
-					FileIOError::from((self.path, e)),
-				));
+				return Err(
+					LocationError::access_denied(&self.path, &e).unwrap_or_else(|| {
+						LocationError::LocationPathFilesystemMetadataAccess(FileIOError::from((
+							&self.path, e,
+						)))
+					}),
+				);
 			}
 		};
 
@@ -97,18 +123,35 @@ impl LocationCreateArgs {
 			return Err(LocationError::NotDirectory(self.path.into_boxed_path()));
 		}
 
-		if let Some(mut metadata) = SpacedriveLocationMetadataFile::try_load(&self.path).await? {
-			metadata
-				.clean_stale_libraries(
-					&node
-						.libraries
-						.get_all()
-						.await
-						.into_iter()
-						.map(|library| library.id)
-						.collect(),
-				)
-				.await?;
+		// It has to be listed to be indexed
+		if let Err(e) = fs::read_dir(&self.path).await {
+			return Err(LocationError::access_denied(&self.path, &e)
+				.unwrap_or_else(|| FileIOError::from((&self.path, e)).into()));
+		}
+
+		// And the `.spacedrive` file has to be written in it
+		if path_metadata.permissions().readonly() {
+			return Err(LocationError::ReadOnly(self.path.into_boxed_path()));
+		}
+
+		if let Some(mut metadata) = load_or_heal_metadata(library, &self.path).await? {
+			let existing_libraries_ids = node
+				.libraries
+				.get_all()
+				.await
+				.into_iter()
+				.map(|library| library.id)
+				.collect();
+
+			// Dry runs must leave the metadata file as it is
+			if self.dry_run {
+				metadata.forget_stale_libraries(&existing_libraries_ids);
+			} else {
+				metadata
+					.clean_stale_libraries(&existing_libraries_ids)
+					.await
+					.map_err(|e| LocationError::from_metadata(&self.path, e))?;
+			}
 
 			if !metadata.is_empty() {
 				if let Some(old_path) = metadata.location_path(library.id) {
@@ -132,9 +175,21 @@ impl LocationCreateArgs {
 						});
 					}
 				} else {
-					return Err(LocationError::AddLibraryToMetadata(
-						self.path.into_boxed_path(),
-					));
+					let mut other_library = None;
+					for id in metadata.library_ids().collect::<Vec<_>>() {
+						if let Some(library) = node.libraries.get_library(&id).await {
+							other_library = Some(OtherLibrary {
+								id,
+								name: library.config().await.name.into(),
+							});
+							break;
+						}
+					}
+
+					return Err(LocationError::AddLibraryToMetadata {
+						path: self.path.into_boxed_path(),
+						other_library,
+					});
 				};
 			}
 		}
@@ -157,6 +212,7 @@ impl LocationCreateArgs {
 			&self.path,
 			&self.indexer_rules_ids,
 			self.dry_run,
+			self.force,
 		)
 		.await?;
 
@@ -168,7 +224,7 @@ impl LocationCreateArgs {
 				&self.path,
 				location.name,
 			)
-			.err_into::<LocationError>()
+			.map_err(|e| LocationError::from_metadata(&self.path, e))
 			.and_then(|()| async move {
 				node.locations
 					.add(location.data.id, library.clone())
@@ -198,17 +254,22 @@ impl LocationCreateArgs {
 			return Err(LocationError::MetadataNotFound(self.path.into_boxed_path()));
 		};
 
-		metadata
-			.clean_stale_libraries(
-				&node
-					.libraries
-					.get_all()
-					.await
-					.into_iter()
-					.map(|library| library.id)
-					.collect(),
-			)
-			.await?;
+		let existing_libraries_ids = node
+			.libraries
+			.get_all()
+			.await
+			.into_iter()
+			.map(|library| library.id)
+			.collect();
+
+		// Dry runs must leave the metadata file as it is
+		if self.dry_run {
+			metadata.forget_stale_libraries(&existing_libraries_ids);
+		} else {
+			metadata
+				.clean_stale_libraries(&existing_libraries_ids)
+				.await?;
+		}
 
 		if metadata.has_library(library.id) {
 			return Err(LocationError::NeedRelink {
@@ -239,6 +300,7 @@ impl LocationCreateArgs {
 			&self.path,
 			&self.indexer_rules_ids,
 			self.dry_run,
+			self.force,
 		)
 		.await?;
 
@@ -263,6 +325,67 @@ impl LocationCreateArgs {
 	}
 }
 
+/// `LocationCreateManyArgs` adds several locations at once, each one as with [`LocationCreateArgs`].
+#[derive(Type, Deserialize)]
+pub struct LocationCreateManyArgs {
+	pub args: Vec<LocationCreateArgs>,
+}
+
+impl LocationCreateManyArgs {
+	/// Creates each location in turn, so one that fails doesn't stop the rest.
+	///
+	/// A path repeated in the batch, or inside another path of it, is rejected so only the
+	/// outermost one is indexed, whatever their order.
+	pub async fn create(
+		self,
+		node: &Node,
+		library: &Arc<Library>,
+	) -> Vec<(
+		PathBuf,
+		Result<Option<location_with_indexer_rules::Data>, LocationError>,
+	)> {
+		let overlaps = batch_overlaps(self.args.iter().map(|args| args.path.as_path()));
+
+		let mut results = Vec::with_capacity(self.args.len());
+		for (args, overlap) in self.args.into_iter().zip(overlaps) {
+			let path = args.path.clone();
+
+			let res = match overlap {
+				Some(other) => Err(LocationError::OverlappingInBatch {
+					path: path.clone().into_boxed_path(),
+					other: other.into_boxed_path(),
+				}),
+				None => args.create(node, library).await,
+			};
+
+			results.push((path, res));
+		}
+
+		results
+	}
+}
+
+/// For each path, an earlier one it repeats or any other one it's inside of.
+fn batch_overlaps<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<Option<PathBuf>> {
+	let paths = paths.into_iter().collect::<Vec<_>>();
+
+	paths
+		.iter()
+		.enumerate()
+		.map(|(i, path)| {
+			paths[..i]
+				.iter()
+				.find(|other| *other == path)
+				.or_else(|| {
+					paths
+						.iter()
+						.find(|other| *other != path && path.starts_with(other))
+				})
+				.map(|other| other.to_path_buf())
+		})
+		.collect()
+}
+
 /// `LocationUpdateArgs` is the argument received from the client using `rspc` to update a location.
 /// It contains the id of the location to be updated, possible a name to change the current location's name
 /// and a vector of indexer rules ids to add or remove from the location.
@@ -278,6 +401,10 @@ pub struct LocationUpdateArgs {
 	hidden: Option<bool>,
 	indexer_rules_ids: Vec<i32>,
 	path: Option<String>,
+	#[serde(default)]
+	symlink_policy: Option<SymlinkPolicy>,
+	#[serde(default)]
+	index_hidden: Option<bool>,
 }
 
 impl LocationUpdateArgs {
@@ -325,6 +452,18 @@ impl LocationUpdateArgs {
 					location::path::set(Some(v)),
 				)
 			}),
+			self.symlink_policy.map(|v| {
+				(
+					(location::symlink_policy::NAME, json!(v as i32)),
+					location::symlink_policy::set(Some(v as i32)),
+				)
+			}),
+			self.index_hidden.map(|v| {
+				(
+					(location::index_hidden::NAME, json!(v)),
+					location::index_hidden::set(Some(v)),
+				)
+			}),
 		]
 		.into_iter()
 		.flatten()
@@ -369,6 +508,17 @@ impl LocationUpdateArgs {
 				node.locations.remove(self.id, library.clone()).await?;
 				node.locations.add(self.id, library.clone()).await?;
 			}
+
+			// Scans skip the directories which didn't change, whose hidden files were never indexed
+			if self.index_hidden == Some(true) && location.index_hidden == Some(false) {
+				db.location()
+					.update(
+						location::id::equals(self.id),
+						vec![location::scanned_at::set(None)],
+					)
+					.exec()
+					.await?;
+			}
 		}
 
 		let current_rules_ids = location
@@ -410,6 +560,38 @@ impl LocationUpdateArgs {
 	}
 }
 
+pub async fn set_location_favorite(
+	library: &Library,
+	location_id: location::id::Type,
+	favorite: bool,
+) -> Result<(), LocationError> {
+	let Library { sync, db, .. } = library;
+
+	let location = find_location(library, location_id)
+		.select(location::select!({ pub_id }))
+		.exec()
+		.await?
+		.ok_or(LocationError::IdNotFound(location_id))?;
+
+	sync.write_op(
+		db,
+		sync.shared_update(
+			prisma_sync::location::SyncId {
+				pub_id: location.pub_id,
+			},
+			location::favorite::NAME,
+			json!(favorite),
+		),
+		db.location().update(
+			location::id::equals(location_id),
+			vec![location::favorite::set(Some(favorite))],
+		),
+	)
+	.await?;
+
+	Ok(())
+}
+
 pub fn find_location(
 	library: &Library,
 	location_id: location::id::Type,
@@ -440,38 +622,66 @@ async fn link_location_and_indexer_rules(
 	Ok(())
 }
 
+/// Scan the whole location.
+///
+/// With `skip_unchanged`, directories which weren't modified since the last scan are not walked
+/// into again. Changes deeper inside them don't update their modification date, so those are only
+/// picked up by the location watcher or by a full scan.
 pub async fn scan_location(
 	node: &Arc<Node>,
 	library: &Arc<Library>,
 	location: location_with_indexer_rules::Data,
+	skip_unchanged: bool,
 ) -> Result<(), JobManagerError> {
+	let Some(jobs) =
+		scan_location_jobs(library, location, skip_unchanged, JobPriority::Normal).await?
+	else {
+		return Ok(());
+	};
+
+	jobs.spawn(node, library).await.map_err(Into::into)
+}
+
+/// The chain of jobs [`scan_location`] spawns, so more jobs can be queued after them.
+///
+/// Returns `None` for locations of other instances, as we can't scan them.
+pub(crate) async fn scan_location_jobs(
+	library: &Arc<Library>,
+	location: location_with_indexer_rules::Data,
+	skip_unchanged: bool,
+	priority: JobPriority,
+) -> Result<Option<Box<Job<IndexerJobInit>>>, JobManagerError> {
 	// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
 	if location.instance_id != Some(library.config().await.instance_id) {
-		return Ok(());
+		return Ok(None);
 	}
 
 	let location_base_data = location::Data::from(&location);
+	let full_hash = LibraryPreferences::read(&library.db).await?.full_hash();
 
-	JobBuilder::new(IndexerJobInit {
-		location,
-		sub_path: None,
-	})
-	.with_action("scan_location")
-	.with_metadata(json!({"location": location_base_data.clone()}))
-	.build()
-	.queue_next(FileIdentifierJobInit {
-		location: location_base_data.clone(),
-		sub_path: None,
-	})
-	.queue_next(MediaProcessorJobInit {
-		location: location_base_data,
-		sub_path: None,
-		regenerate_thumbnails: false,
-		regenerate_labels: false,
-	})
-	.spawn(node, library)
-	.await
-	.map_err(Into::into)
+	Ok(Some(
+		JobBuilder::new(IndexerJobInit {
+			location,
+			sub_path: None,
+			skip_unchanged,
+		})
+		.with_action("scan_location")
+		.with_metadata(json!({"location": location_base_data.clone()}))
+		.with_priority(priority)
+		.build()
+		.queue_next(FileIdentifierJobInit {
+			location: location_base_data.clone(),
+			sub_path: None,
+			full_hash,
+		})
+		.queue_next(MediaProcessorJobInit {
+			location: location_base_data,
+			sub_path: None,
+			regenerate_thumbnails: false,
+			regenerate_labels: false,
+			labels_min_confidence: None,
+		}),
+	))
 }
 
 pub async fn scan_location_sub_path(
@@ -488,10 +698,12 @@ pub async fn scan_location_sub_path(
 	}
 
 	let location_base_data = location::Data::from(&location);
+	let full_hash = LibraryPreferences::read(&library.db).await?.full_hash();
 
 	JobBuilder::new(IndexerJobInit {
 		location,
 		sub_path: Some(sub_path.clone()),
+		skip_unchanged: false,
 	})
 	.with_action("scan_location_sub_path")
 	.with_metadata(json!({
@@ -502,12 +714,14 @@ pub async fn scan_location_sub_path(
 	.queue_next(FileIdentifierJobInit {
 		location: location_base_data.clone(),
 		sub_path: Some(sub_path.clone()),
+		full_hash,
 	})
 	.queue_next(MediaProcessorJobInit {
 		location: location_base_data,
 		sub_path: Some(sub_path),
 		regenerate_thumbnails: false,
 		regenerate_labels: false,
+		labels_min_confidence: None,
 	})
 	.spawn(node, library)
 	.await
@@ -527,29 +741,139 @@ pub async fn light_scan_location(
 		return Ok(());
 	}
 
-	let location_base_data = location::Data::from(&location);
+	shallow_scan(&node, &library, &location, &sub_path, true).await?;
+
+	Ok(())
+}
+
+/// What the shallow pass of [`interactive_scan_location`] found.
+#[derive(Serialize, Type, Debug)]
+pub struct ShallowScanSummary {
+	/// Entries indexed at the top level of the location
+	pub entries: u32,
+}
+
+/// Scans the top level of a location right away, then queues the full scan ahead of other jobs.
+///
+/// The shallow pass applies the same indexer rules as the full scan, which picks up from what the
+/// shallow pass indexed. Returns `None` if the shallow pass didn't run or failed, as the full
+/// scan still takes care of the location in that case.
+pub async fn interactive_scan_location(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	location: location_with_indexer_rules::Data,
+) -> Result<Option<ShallowScanSummary>, JobManagerError> {
+	// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
+	if location.instance_id != Some(library.config().await.instance_id) {
+		return Ok(None);
+	}
+
+	let location_id = location.id;
+
+	let summary = shallow_scan(node, library, &location, &PathBuf::new(), false)
+		.await
+		.map(|entries| ShallowScanSummary {
+			entries: u32::try_from(entries).unwrap_or(u32::MAX),
+		})
+		.map_err(|e| error!("Shallow scan of location <id={location_id}> failed: {e:#?}"))
+		.ok();
+
+	if let Some(jobs) = scan_location_jobs(library, location, false, JobPriority::High).await? {
+		jobs.spawn(node, library).await?;
+	}
+
+	Ok(summary)
+}
 
-	indexer::shallow(&location, &sub_path, &node, &library).await?;
-	file_identifier::shallow(&location_base_data, &sub_path, &library).await?;
+async fn shallow_scan(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	location: &location_with_indexer_rules::Data,
+	sub_path: &PathBuf,
+	scan_new_directories: bool,
+) -> Result<usize, JobError> {
+	let location_base_data = location::Data::from(location);
+
+	let entries = indexer::shallow(location, sub_path, node, library, scan_new_directories).await?;
+	file_identifier::shallow(&location_base_data, sub_path, library).await?;
 	media_processor::shallow(
 		&location_base_data,
-		&sub_path,
-		&library,
+		sub_path,
+		library,
 		#[cfg(feature = "ai")]
 		false,
-		&node,
+		node,
 	)
 	.await?;
 
-	Ok(())
+	Ok(entries)
+}
+
+/// Indexes a single file, or the entries directly inside a directory, without walking anything
+/// else in the location, returning how many entries were created or updated. A file is also
+/// identified right away.
+///
+/// `path` can be absolute or relative to the location, but can't leave it.
+pub async fn index_path(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	location: location_with_indexer_rules::Data,
+	path: impl AsRef<Path>,
+) -> Result<u32, LocationError> {
+	let path = path.as_ref();
+
+	// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
+	if location.instance_id != Some(library.config().await.instance_id) {
+		return Ok(0);
+	}
+
+	let location_path = maybe_missing(&location.path, "location.path").map(Path::new)?;
+
+	let full_path = location_path.join(path);
+	if !full_path.starts_with(location_path)
+		|| full_path
+			.components()
+			.any(|component| matches!(component, Component::ParentDir))
+	{
+		return Err(LocationError::PathOutsideLocation(path.into()));
+	}
+
+	let sub_path = full_path
+		.strip_prefix(location_path)
+		.map_err(|_| LocationError::PathOutsideLocation(path.into()))?
+		.to_path_buf();
+
+	let metadata = fs::metadata(&full_path)
+		.await
+		.map_err(|e| FileIOError::from((&full_path, e)))?;
+
+	let entries = if metadata.is_dir() {
+		shallow_scan(node, library, &location, &sub_path, false)
+			.await
+			.map_err(|e| LocationError::Indexing(Box::new(e)))?
+	} else {
+		let entries = indexer::shallow_file(&location, &sub_path, library)
+			.await
+			.map_err(|e| LocationError::Indexing(Box::new(e)))?
+			.ok_or_else(|| LocationError::RejectedByIndexerRules(path.into()))?;
+
+		file_identifier::shallow_file(&location::Data::from(&location), &sub_path, library)
+			.await
+			.map_err(|e| LocationError::Indexing(Box::new(e)))?;
+
+		entries
+	};
+
+	Ok(u32::try_from(entries).unwrap_or(u32::MAX))
 }
 
 pub async fn relink_location(
-	Library { db, id, sync, .. }: &Library,
+	library: &Library,
 	location_path: impl AsRef<Path>,
 ) -> Result<i32, LocationError> {
+	let Library { db, id, sync, .. } = library;
 	let location_path = location_path.as_ref();
-	let mut metadata = SpacedriveLocationMetadataFile::try_load(&location_path)
+	let mut metadata = load_or_heal_metadata(library, location_path)
 		.await?
 		.ok_or_else(|| LocationError::MissingMetadataFile(location_path.into()))?;
 
@@ -590,6 +914,289 @@ pub async fn relink_location(
 	Ok(location_id.id)
 }
 
+/// Loads the metadata file of the location at `location_path`.
+///
+/// If it's there but corrupted, and this library has a location at that very path, it's written
+/// again from the database and the user is warned, instead of failing. A file written by a newer
+/// version is never overwritten, as it may hold what the other libraries using it need.
+async fn load_or_heal_metadata(
+	library: &Library,
+	location_path: &Path,
+) -> Result<Option<SpacedriveLocationMetadataFile>, LocationError> {
+	let e = match SpacedriveLocationMetadataFile::try_load(location_path).await {
+		Err(e) if e.is_unreadable() => e,
+		res => return res.map_err(|e| LocationError::from_metadata(location_path, e)),
+	};
+
+	let Some(path_str) = location_path.to_str() else {
+		return Err(e.into());
+	};
+
+	let Some(location) = library
+		.db
+		.location()
+		.find_first(vec![location::path::equals(Some(path_str.to_string()))])
+		.select(location::select!({ pub_id name }))
+		.exec()
+		.await?
+	else {
+		return Err(e.into());
+	};
+
+	let Ok(pub_id) = Uuid::from_slice(&location.pub_id) else {
+		return Err(e.into());
+	};
+
+	let name = location.name.unwrap_or_default();
+
+	warn!(
+		"Regenerating the unreadable metadata file of location at '{}' from the database: {e:#?}",
+		location_path.display()
+	);
+
+	SpacedriveLocationMetadataFile::create_and_save(
+		library.id,
+		pub_id,
+		location_path,
+		name.clone(),
+	)
+	.await
+	.map_err(|e| LocationError::from_metadata(location_path, e))?;
+
+	library
+		.emit_notification(
+			NotificationData {
+				title: "Location metadata repaired".to_string(),
+				content: format!(
+					"The metadata file of \"{name}\" couldn't be read, it was written again from \
+					this library. Other libraries using this location will need to add it again"
+				),
+				kind: NotificationKind::Warning,
+			},
+			None,
+		)
+		.await;
+
+	SpacedriveLocationMetadataFile::try_load(location_path)
+		.await
+		.map_err(|e| LocationError::from_metadata(location_path, e))
+}
+
+/// Looks for the directory of a location which isn't at `old_path` anymore, among the siblings of
+/// `old_path` and the roots of the volumes.
+///
+/// A candidate must have this location's metadata file and still be the very directory the
+/// location was created in, so copies or backups of the location are never picked.
+pub async fn find_moved_location(
+	library_id: LibraryId,
+	location_pub_id: Uuid,
+	old_path: impl AsRef<Path>,
+) -> Option<PathBuf> {
+	let old_path = old_path.as_ref();
+
+	let search_dirs = old_path
+		.parent()
+		.map(Path::to_path_buf)
+		.into_iter()
+		.chain(
+			get_volumes()
+				.await
+				.into_iter()
+				.flat_map(|volume| volume.mount_points),
+		)
+		.collect::<HashSet<_>>();
+
+	for search_dir in search_dirs {
+		let Ok(mut read_dir) = fs::read_dir(&search_dir).await else {
+			continue;
+		};
+
+		while let Ok(Some(entry)) = read_dir.next_entry().await {
+			let candidate = entry.path();
+			if candidate == old_path
+				|| !entry
+					.file_type()
+					.await
+					.map_or(false, |file_type| file_type.is_dir())
+			{
+				continue;
+			}
+
+			let Ok(Some(metadata)) = SpacedriveLocationMetadataFile::try_load(&candidate).await
+			else {
+				continue;
+			};
+
+			if metadata.location_pub_id(library_id).ok() != Some(location_pub_id) {
+				continue;
+			}
+
+			match (metadata.root_id(), get_filesystem_id(&candidate).await) {
+				(Some(root_id), Ok(candidate_id)) if root_id == candidate_id => {
+					return Some(candidate)
+				}
+				_ => {
+					warn!(
+						"Found location metadata at '{}' which isn't the location's original \
+						directory, not relinking to it",
+						candidate.display()
+					);
+				}
+			}
+		}
+	}
+
+	None
+}
+
+/// Relinks a location whose directory is gone from its path but was found with
+/// [`find_moved_location`], notifying the user about it.
+///
+/// Returns if the location was relinked.
+pub async fn relink_if_moved(
+	location: &location::Data,
+	library: &Library,
+) -> Result<bool, LocationError> {
+	let old_path = maybe_missing(&location.path, "location.path")?;
+
+	match fs::metadata(old_path).await {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+		_ => return Ok(false),
+	}
+
+	let Ok(pub_id) = Uuid::from_slice(&location.pub_id) else {
+		return Ok(false);
+	};
+
+	let Some(new_path) = find_moved_location(library.id, pub_id, old_path).await else {
+		return Ok(false);
+	};
+
+	relink_location(library, &new_path).await?;
+
+	info!(
+		"Relinked location <id='{}'> from '{old_path}' to '{}'",
+		location.id,
+		new_path.display()
+	);
+
+	library
+		.emit_notification(
+			NotificationData {
+				title: "Location relinked".to_string(),
+				content: format!(
+					"\"{}\" was moved from {old_path} to {}, it has been relinked",
+					location.name.as_deref().unwrap_or_default(),
+					new_path.display()
+				),
+				kind: NotificationKind::Info,
+			},
+			None,
+		)
+		.await;
+
+	Ok(true)
+}
+
+/// Point a location at a new root after it's been moved by a known prefix, eg. an external drive
+/// which is now mounted somewhere else.
+///
+/// File paths are stored relative to their location, so unlike a rescan only the location row and
+/// it's metadata file need updating.
+pub async fn rebase_location(
+	node: &Node,
+	library: &Arc<Library>,
+	location_id: location::id::Type,
+	old_prefix: impl AsRef<Path>,
+	new_prefix: impl AsRef<Path>,
+) -> Result<PathBuf, LocationError> {
+	let Library { db, sync, .. } = library.as_ref();
+	let old_prefix = old_prefix.as_ref();
+
+	let location = find_location(library, location_id)
+		.select(location::select!({ pub_id path }))
+		.exec()
+		.await?
+		.ok_or(LocationError::IdNotFound(location_id))?;
+
+	let old_path = PathBuf::from(maybe_missing(location.path, "location.path")?);
+	let new_path =
+		new_prefix
+			.as_ref()
+			.join(old_path.strip_prefix(old_prefix).map_err(|_| {
+				LocationError::PrefixMismatch {
+					path: old_path.clone().into_boxed_path(),
+					prefix: old_prefix.into(),
+				}
+			})?);
+
+	match fs::metadata(&new_path).await {
+		Ok(metadata) if metadata.is_dir() => {}
+		Ok(_) => return Err(LocationError::NotDirectory(new_path.into_boxed_path())),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {
+			return Err(LocationError::PathNotFound(new_path.into_boxed_path()))
+		}
+		Err(e) => {
+			return Err(LocationError::LocationPathFilesystemMetadataAccess(
+				FileIOError::from((new_path, e)),
+			))
+		}
+	}
+
+	let path = new_path
+		.to_str()
+		.map(str::to_string)
+		.ok_or_else(|| NonUtf8PathError(new_path.clone().into_boxed_path()))?;
+
+	if db
+		.location()
+		.count(vec![location::path::equals(Some(path.clone()))])
+		.exec()
+		.await? > 0
+	{
+		return Err(LocationError::LocationAlreadyExists(
+			new_path.into_boxed_path(),
+		));
+	}
+
+	if let Some(mut metadata) = SpacedriveLocationMetadataFile::try_load(&new_path).await? {
+		metadata.relink(library.id, &new_path).await?;
+	}
+
+	sync.write_op(
+		db,
+		sync.shared_update(
+			prisma_sync::location::SyncId {
+				pub_id: location.pub_id.clone(),
+			},
+			location::path::NAME,
+			json!(path),
+		),
+		db.location().update(
+			location::id::equals(location_id),
+			vec![location::path::set(Some(path))],
+		),
+	)
+	.await?;
+
+	// The watcher is still looking at the old path
+	node.locations
+		.remove(location_id, Arc::clone(library))
+		.await?;
+	node.locations.add(location_id, Arc::clone(library)).await?;
+
+	invalidate_query!(library, "locations.list");
+	invalidate_query!(library, "locations.get");
+
+	info!(
+		"Rebased location <id='{location_id}'> from \"{}\" to \"{}\"",
+		old_path.display(),
+		new_path.display()
+	);
+
+	Ok(new_path)
+}
+
 #[derive(Debug)]
 pub struct CreatedLocationResult {
 	pub name: String,
@@ -653,6 +1260,7 @@ async fn create_location(
 	location_path: impl AsRef<Path>,
 	indexer_rules_ids: &[i32],
 	dry_run: bool,
+	force: bool,
 ) -> Result<Option<CreatedLocationResult>, LocationError> {
 	let location_path = location_path.as_ref();
 	let (path, name) = normalize_path(location_path)
@@ -667,8 +1275,13 @@ async fn create_location(
 		return Err(LocationError::LocationAlreadyExists(location_path.into()));
 	}
 
-	if check_nested_location(&location_path, db).await? {
-		return Err(LocationError::NestedLocation(location_path.into()));
+	if !force {
+		if let Some(location_id) = overlapping_locations(location_path, db).await?.first() {
+			return Err(LocationError::Overlapping {
+				path: location_path.into(),
+				location_id: *location_id,
+			});
+		}
 	}
 
 	if dry_run {
@@ -875,7 +1488,15 @@ impl From<location_with_indexer_rules::Data> for location::Data {
 			generate_preview_media: data.generate_preview_media,
 			sync_preview_media: data.sync_preview_media,
 			hidden: data.hidden,
+			favorite: data.favorite,
 			date_created: data.date_created,
+			scanned_at: data.scanned_at,
+			rescan_interval: data.rescan_interval,
+			integrity_verified_at: data.integrity_verified_at,
+			case_insensitive: data.case_insensitive,
+			symlink_policy: data.symlink_policy,
+			index_hidden: data.index_hidden,
+			sync_enabled: data.sync_enabled,
 			file_paths: None,
 			indexer_rules: None,
 			instance: None,
@@ -898,7 +1519,15 @@ impl From<&location_with_indexer_rules::Data> for location::Data {
 			generate_preview_media: data.generate_preview_media,
 			sync_preview_media: data.sync_preview_media,
 			hidden: data.hidden,
+			favorite: data.favorite,
 			date_created: data.date_created,
+			scanned_at: data.scanned_at,
+			rescan_interval: data.rescan_interval,
+			integrity_verified_at: data.integrity_verified_at,
+			case_insensitive: data.case_insensitive,
+			symlink_policy: data.symlink_policy,
+			index_hidden: data.index_hidden,
+			sync_enabled: data.sync_enabled,
 			file_paths: None,
 			indexer_rules: None,
 			instance: None,
@@ -906,25 +1535,28 @@ impl From<&location_with_indexer_rules::Data> for location::Data {
 	}
 }
 
-async fn check_nested_location(
+/// The ids of the locations containing `location_path`, inside it or at the same path.
+pub(crate) async fn overlapping_locations(
 	location_path: impl AsRef<Path>,
 	db: &PrismaClient,
-) -> Result<bool, QueryError> {
+) -> Result<Vec<location::id::Type>, QueryError> {
 	let location_path = location_path.as_ref();
 
-	let (parents_count, potential_children) = db
+	let (parents, potential_children) = db
 		._batch((
-			db.location().count(vec![location::path::in_vec(
-				location_path
-					.ancestors()
-					.skip(1) // skip the actual location_path, we only want the parents
-					.map(|p| {
-						p.to_str()
-							.map(str::to_string)
-							.expect("Found non-UTF-8 path")
-					})
-					.collect(),
-			)]),
+			db.location()
+				.find_many(vec![location::path::in_vec(
+					location_path
+						.ancestors()
+						.skip(1) // skip the actual location_path, we only want the parents
+						.map(|p| {
+							p.to_str()
+								.map(str::to_string)
+								.expect("Found non-UTF-8 path")
+						})
+						.collect(),
+				)])
+				.select(location::select!({ id })),
 			db.location().find_many(vec![location::path::starts_with(
 				location_path
 					.to_str()
@@ -935,8 +1567,8 @@ async fn check_nested_location(
 		.await?;
 
 	let comps = location_path.components().collect::<Vec<_>>();
-	let is_a_child_location = potential_children.into_iter().any(|v| {
-		let Some(location_path) = v.path else {
+	let children = potential_children.into_iter().filter(|v| {
+		let Some(location_path) = &v.path else {
 			warn!(
 				"Missing location path on location <id='{}'> at check nested location",
 				v.id
@@ -959,7 +1591,11 @@ async fn check_nested_location(
 		true
 	});
 
-	Ok(parents_count > 0 || is_a_child_location)
+	Ok(parents
+		.into_iter()
+		.map(|parent| parent.id)
+		.chain(children.map(|child| child.id))
+		.collect())
 }
 
 pub async fn update_location_size(
@@ -1054,13 +1690,21 @@ pub async fn create_file_path(
 	let location = db
 		.location()
 		.find_unique(location::id::equals(location_id))
-		.select(location::select!({ id pub_id }))
+		.select(location::select!({ id pub_id path case_insensitive }))
 		.exec()
 		.await?
 		.ok_or(sd_file_path_helper::FilePathError::LocationNotFound(
 			location_id,
 		))?;
 
+	let case_insensitive = normalization::location_is_case_insensitive(
+		db,
+		location.id,
+		location.path.as_ref(),
+		location.case_insensitive,
+	)
+	.await?;
+
 	let params = {
 		use file_path::*;
 
@@ -1116,6 +1760,14 @@ pub async fn create_file_path(
 						date_modified::set(Some(metadata.modified_at.into())),
 						date_indexed::set(Some(indexed_at.into())),
 						hidden::set(Some(metadata.hidden)),
+						name_normalized::set(Some(sd_file_path_helper::normalize_name(
+							&if extension.is_empty() {
+								name.to_string()
+							} else {
+								format!("{name}.{extension}")
+							},
+							case_insensitive,
+						))),
 					]
 				}),
 			),
@@ -1124,3 +1776,307 @@ pub async fn create_file_path(
 
 	Ok(created_path)
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::{library::LibraryName, util::test_utils::test_library};
+
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_find_moved_location() {
+		let root = tempdir().unwrap();
+		let library_id = Uuid::new_v4();
+		let location_pub_id = Uuid::new_v4();
+
+		let old_path = root.path().join("photos");
+		fs::create_dir(&old_path).await.unwrap();
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			location_pub_id,
+			&old_path,
+			"photos".to_string(),
+		)
+		.await
+		.unwrap();
+
+		// A copy has the same metadata file but isn't the same directory
+		let copy_path = root.path().join("photos backup");
+		fs::create_dir(&copy_path).await.unwrap();
+		fs::copy(old_path.join(".spacedrive"), copy_path.join(".spacedrive"))
+			.await
+			.unwrap();
+
+		assert_eq!(
+			find_moved_location(library_id, location_pub_id, &old_path).await,
+			None
+		);
+
+		let new_path = root.path().join("pictures");
+		fs::rename(&old_path, &new_path).await.unwrap();
+
+		assert_eq!(
+			find_moved_location(library_id, location_pub_id, &old_path).await,
+			Some(new_path)
+		);
+		assert_eq!(
+			find_moved_location(Uuid::new_v4(), location_pub_id, &old_path).await,
+			None
+		);
+	}
+
+	fn create_args(path: impl AsRef<Path>) -> LocationCreateArgs {
+		LocationCreateArgs {
+			path: path.as_ref().to_path_buf(),
+			dry_run: false,
+			indexer_rules_ids: vec![],
+			interactive: false,
+			force: false,
+		}
+	}
+
+	#[tokio::test]
+	async fn test_create_error_path_not_found() {
+		let (_data_dir, node, library) = test_library("Not found").await;
+		let root = tempdir().unwrap();
+
+		let err = create_args(root.path().join("missing"))
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+
+		assert!(matches!(
+			err.create_error(),
+			Some(LocationCreateError::PathNotFound { path }) if path == root.path().join("missing")
+		));
+	}
+
+	#[tokio::test]
+	async fn test_create_error_overlapping() {
+		let (_data_dir, node, library) = test_library("Overlapping").await;
+		let root = tempdir().unwrap();
+		let parent = root.path().join("parent");
+		let child = parent.join("child");
+		fs::create_dir_all(&child).await.unwrap();
+
+		let parent_location = create_args(&parent)
+			.create(&node, &library)
+			.await
+			.unwrap()
+			.unwrap();
+
+		// Inside an existing location
+		let err = create_args(&child)
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+		assert!(matches!(
+			err.create_error(),
+			Some(LocationCreateError::Overlapping { path, location_id })
+				if path == child && location_id == parent_location.id
+		));
+
+		// Containing an existing location
+		let err = create_args(root.path())
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+		assert!(matches!(
+			err.create_error(),
+			Some(LocationCreateError::Overlapping { location_id, .. })
+				if location_id == parent_location.id
+		));
+
+		let child_location = LocationCreateArgs {
+			force: true,
+			..create_args(&child)
+		}
+		.create(&node, &library)
+		.await
+		.unwrap()
+		.unwrap();
+		assert_ne!(child_location.id, parent_location.id);
+	}
+
+	#[tokio::test]
+	async fn test_create_error_other_library() {
+		let (_data_dir, node, library) = test_library("Mine").await;
+		let other_library = node
+			.libraries
+			.create(LibraryName::new("Theirs").unwrap(), None, &node)
+			.await
+			.unwrap();
+		let root = tempdir().unwrap();
+
+		create_args(root.path())
+			.create(&node, &other_library)
+			.await
+			.unwrap()
+			.unwrap();
+
+		let err = create_args(root.path())
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+
+		assert!(matches!(
+			err.create_error(),
+			Some(LocationCreateError::AddLibrary {
+				other_library: Some(OtherLibrary { id, name }),
+				..
+			}) if id == other_library.id && name == "Theirs"
+		));
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_create_error_permission_denied() {
+		use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+		let (_data_dir, node, library) = test_library("Denied").await;
+		let root = tempdir().unwrap();
+		let path = root.path().join("private");
+		fs::create_dir(&path).await.unwrap();
+		fs::set_permissions(&path, Permissions::from_mode(0o000))
+			.await
+			.unwrap();
+
+		// Permissions aren't enforced for root
+		if fs::read_dir(&path).await.is_ok() {
+			return;
+		}
+
+		let err = create_args(&path)
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+
+		fs::set_permissions(&path, Permissions::from_mode(0o755))
+			.await
+			.unwrap();
+
+		assert!(matches!(
+			err.create_error(),
+			Some(LocationCreateError::PermissionDenied { path: denied }) if denied == path
+		));
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_create_error_read_only() {
+		use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+		let (_data_dir, node, library) = test_library("Read only").await;
+		let root = tempdir().unwrap();
+		let path = root.path().join("read only");
+		fs::create_dir(&path).await.unwrap();
+		fs::set_permissions(&path, Permissions::from_mode(0o555))
+			.await
+			.unwrap();
+
+		let err = create_args(&path)
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+
+		fs::set_permissions(&path, Permissions::from_mode(0o755))
+			.await
+			.unwrap();
+
+		assert!(matches!(
+			err.create_error(),
+			Some(LocationCreateError::ReadOnly { path: read_only }) if read_only == path
+		));
+		assert!(SpacedriveLocationMetadataFile::try_load(&path)
+			.await
+			.unwrap()
+			.is_none());
+	}
+
+	#[tokio::test]
+	async fn test_create_heals_unreadable_metadata() {
+		let (_data_dir, node, library) = test_library("Heal").await;
+		let root = tempdir().unwrap();
+
+		let location = create_args(root.path())
+			.create(&node, &library)
+			.await
+			.unwrap()
+			.unwrap();
+
+		fs::write(root.path().join(".spacedrive"), b"{ corrupted")
+			.await
+			.unwrap();
+
+		// Fails as the location exists, not on the metadata file
+		let err = create_args(root.path())
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+		assert!(matches!(err, LocationError::LocationAlreadyExists(_)));
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(root.path())
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(
+			metadata.location_pub_id(library.id).unwrap(),
+			Uuid::from_slice(&location.pub_id).unwrap()
+		);
+	}
+
+	#[tokio::test]
+	async fn test_create_keeps_newer_metadata() {
+		let (_data_dir, node, library) = test_library("Heal").await;
+		let root = tempdir().unwrap();
+
+		create_args(root.path())
+			.create(&node, &library)
+			.await
+			.unwrap()
+			.unwrap();
+
+		let newer = br#"{ "version": 1000 }"#;
+		fs::write(root.path().join(".spacedrive"), newer)
+			.await
+			.unwrap();
+
+		let err = create_args(root.path())
+			.create(&node, &library)
+			.await
+			.unwrap_err();
+		assert!(matches!(
+			err,
+			LocationError::LocationMetadata(metadata::LocationMetadataError::NewerVersion(1000, _))
+		));
+		assert_eq!(
+			fs::read(root.path().join(".spacedrive")).await.unwrap(),
+			newer
+		);
+	}
+
+	#[test]
+	fn test_batch_overlaps() {
+		let paths = [
+			Path::new("/home/user/photos/2023"),
+			Path::new("/home/user/photos"),
+			Path::new("/home/user/documents/"),
+			Path::new("/home/user/documents"),
+			Path::new("/home/user/photos-backup"),
+		];
+
+		assert_eq!(
+			batch_overlaps(paths),
+			vec![
+				Some(PathBuf::from("/home/user/photos")),
+				None,
+				None,
+				Some(PathBuf::from("/home/user/documents/")),
+				None,
+			]
+		);
+	}
+}
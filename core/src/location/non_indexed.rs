@@ -3,22 +3,36 @@ use crate::{
 	library::Library,
 	object::{
 		cas::generate_cas_id,
-		media::thumbnail::{get_ephemeral_thumb_key, BatchToProcess, GenerateThumbnailArgs},
+		media::{
+			media_data_extractor::{
+				can_extract_media_data_for_image, extract_media_data, MediaDataError,
+			},
+			thumbnail::{
+				get_ephemeral_thumb_key, BatchToProcess, GenerateThumbnailArgs, ThumbnailKind,
+				ThumbnailStatus,
+			},
+		},
 	},
 	Node,
 };
 
 use futures::Stream;
+use futures_concurrency::future::TryJoin;
 use itertools::Either;
-use sd_file_ext::{extensions::Extension, kind::ObjectKind};
+use sd_file_ext::{
+	extensions::{Extension, ImageExtension},
+	kind::ObjectKind,
+};
 use sd_file_path_helper::{path_is_hidden, MetadataExt};
+use sd_media_metadata::MediaMetadata;
 use sd_prisma::prisma::location;
 use sd_utils::{chain_optional_iter, error::FileIOError};
 
 use std::{
 	collections::HashMap,
 	io::ErrorKind,
-	path::{Path, PathBuf},
+	path::{Component, Path, PathBuf},
+	str::FromStr,
 	sync::Arc,
 };
 
@@ -26,8 +40,9 @@ use chrono::{DateTime, Utc};
 use rspc::ErrorCode;
 use serde::Serialize;
 use specta::Type;
+use strum::IntoEnumIterator;
 use thiserror::Error;
-use tokio::{io, sync::mpsc, task::JoinError};
+use tokio::{fs, io, sync::mpsc, task::JoinError};
 use tokio_stream::wrappers::ReceiverStream;
 use tracing::{error, span, warn, Level};
 
@@ -44,6 +59,12 @@ pub enum NonIndexedLocationError {
 	#[error("path not found: {}", .0.display())]
 	NotFound(PathBuf),
 
+	#[error(
+		"path can't be inspected, it must be normalized, inside a browsed directory and not protected by the OS: {}",
+		.0.display()
+	)]
+	NotInspectable(PathBuf),
+
 	#[error(transparent)]
 	FileIO(#[from] FileIOError),
 
@@ -69,6 +90,9 @@ impl From<NonIndexedLocationError> for rspc::Error {
 			NonIndexedLocationError::NotFound(_) => {
 				rspc::Error::with_cause(ErrorCode::NotFound, err.to_string(), err)
 			}
+			NonIndexedLocationError::NotInspectable(_) => {
+				rspc::Error::with_cause(ErrorCode::Forbidden, err.to_string(), err)
+			}
 			_ => rspc::Error::with_cause(ErrorCode::InternalServerError, err.to_string(), err),
 		}
 	}
@@ -97,6 +121,240 @@ pub struct NonIndexedPathItem {
 	pub hidden: bool,
 }
 
+impl NonIndexedPathItem {
+	/// Builds the item for a single path, used to return the result of a change made outside
+	/// of a location without walking the whole directory again.
+	pub async fn from_path(path: impl AsRef<Path>) -> Result<Self, NonIndexedLocationError> {
+		let path = path.as_ref();
+
+		let metadata = fs::metadata(path)
+			.await
+			.map_err(|e| NonIndexedLocationError::from((path, e)))?;
+
+		let (entry_path, name) =
+			normalize_path(path).map_err(|e| NonIndexedLocationError::from((path, e)))?;
+
+		let hidden = path_is_hidden(Path::new(&entry_path), &metadata);
+		let date_created = metadata.created_or_now().into();
+		let date_modified = metadata.modified_or_now().into();
+		let size_in_bytes_bytes = metadata.len().to_be_bytes().to_vec();
+
+		if metadata.is_dir() {
+			return Ok(Self {
+				path: entry_path,
+				name,
+				extension: String::new(),
+				kind: ObjectKind::Folder as i32,
+				is_dir: true,
+				date_created,
+				date_modified,
+				size_in_bytes_bytes,
+				hidden,
+			});
+		}
+
+		let name = path
+			.file_stem()
+			.and_then(|s| s.to_str().map(str::to_string))
+			.unwrap_or(name);
+
+		let extension = path
+			.extension()
+			.and_then(|s| s.to_str().map(str::to_string))
+			.unwrap_or_default();
+
+		let kind = Extension::resolve_conflicting(path, false)
+			.await
+			.map(Into::into)
+			.unwrap_or(ObjectKind::Unknown);
+
+		Ok(Self {
+			path: entry_path,
+			name,
+			extension,
+			kind: kind as i32,
+			is_dir: false,
+			date_created,
+			date_modified,
+			size_in_bytes_bytes,
+			hidden,
+		})
+	}
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct FileInspection {
+	pub item: NonIndexedPathItem,
+	pub cas_id: Option<String>,
+	pub media_data: Option<MediaMetadata>,
+}
+
+/// Computes the details [`walk`] would for a single path, plus its media data, without it
+/// having to be in a location.
+///
+/// Only paths directly inside a directory that was browsed with [`walk`] can be inspected, and
+/// symlinks must resolve inside that same directory.
+pub async fn inspect(path: &Path, node: &Node) -> Result<FileInspection, NonIndexedLocationError> {
+	let Some(parent) = path.parent().filter(|parent| {
+		path.is_absolute()
+			&& !path
+				.components()
+				.any(|component| matches!(component, Component::ParentDir))
+			&& node.ephemeral_paths.contains_key(*parent)
+	}) else {
+		return Err(NonIndexedLocationError::NotInspectable(path.into()));
+	};
+
+	let (canonical_path, canonical_parent) = (fs::canonicalize(path), fs::canonicalize(parent))
+		.try_join()
+		.await
+		.map_err(|e| NonIndexedLocationError::from((path, e)))?;
+
+	if !canonical_path.starts_with(&canonical_parent) {
+		return Err(NonIndexedLocationError::NotInspectable(path.into()));
+	}
+
+	let is_os_protected = IndexerRule::apply_all(&[IndexerRule::from(no_os_protected())], path)
+		.await
+		.map_or_else(
+			|e| {
+				error!(
+					"Failed to apply indexer rules to {}: {e:#?}",
+					path.display()
+				);
+				true
+			},
+			|rule_results| {
+				rule_results[&RuleKind::RejectFilesByGlob]
+					.iter()
+					.any(|reject| !reject)
+			},
+		);
+
+	if is_os_protected {
+		return Err(NonIndexedLocationError::NotInspectable(path.into()));
+	}
+
+	let item = NonIndexedPathItem::from_path(path).await?;
+	if item.is_dir {
+		return Ok(FileInspection {
+			item,
+			cas_id: None,
+			media_data: None,
+		});
+	}
+
+	let size = u64::from_be_bytes(
+		item.size_in_bytes_bytes
+			.as_slice()
+			.try_into()
+			.expect("size_in_bytes_bytes is always 8 bytes"),
+	);
+	let cas_id = generate_cas_id(path, size)
+		.await
+		.map_err(|e| NonIndexedLocationError::from((path, e)))?;
+
+	// TODO(fogodev): change this when we have media data for audio and videos
+	let media_data = match ImageExtension::from_str(&item.extension) {
+		Ok(image_extension) if can_extract_media_data_for_image(&image_extension) => {
+			match extract_media_data(path).await {
+				Ok(media_data) => Some(MediaMetadata::Image(Box::new(media_data))),
+				Err(MediaDataError::MediaData(sd_media_metadata::Error::NoExifDataOnPath(_))) => {
+					None
+				}
+				Err(e) => {
+					warn!(
+						"Failed to extract media data for {}: {e:#?}",
+						path.display()
+					);
+					None
+				}
+			}
+		}
+		_ => None,
+	};
+
+	Ok(FileInspection {
+		item,
+		cas_id: Some(cas_id),
+		media_data,
+	})
+}
+
+/// The item [`walk`] would return for a single path, which must be directly inside a directory
+/// that was browsed with it, or `None` if it isn't there anymore.
+///
+/// Thumbnails aren't generated here, so only the ones [`walk`] already generated are returned.
+pub async fn explorer_item(
+	path: &Path,
+	node: &Node,
+) -> Result<Option<ExplorerItem>, NonIndexedLocationError> {
+	if !path
+		.parent()
+		.is_some_and(|parent| node.ephemeral_paths.contains_key(parent))
+	{
+		return Err(NonIndexedLocationError::NotInspectable(path.into()));
+	}
+
+	let item = match NonIndexedPathItem::from_path(path).await {
+		Ok(item) => item,
+		Err(NonIndexedLocationError::NotFound(_)) => return Ok(None),
+		Err(e) => return Err(e),
+	};
+
+	let has_thumbnail = !item.is_dir
+		&& ObjectKind::iter()
+			.find(|kind| *kind as i32 == item.kind)
+			.is_some_and(can_generate_thumbnail);
+
+	let mut thumbnail = None;
+
+	if has_thumbnail {
+		let size = u64::from_be_bytes(
+			item.size_in_bytes_bytes
+				.as_slice()
+				.try_into()
+				.expect("size_in_bytes_bytes is always 8 bytes"),
+		);
+		let cas_id = generate_cas_id(path, size)
+			.await
+			.map_err(|e| NonIndexedLocationError::from((path, e)))?;
+
+		if let Ok(ThumbnailStatus::Ready) = node
+			.thumbnailer
+			.status(&cas_id, ThumbnailKind::Ephemeral)
+			.await
+		{
+			thumbnail = Some(get_ephemeral_thumb_key(&cas_id, node.thumbnailer.format()));
+		}
+	}
+
+	Ok(Some(ExplorerItem::NonIndexedPath {
+		thumbnail_status: if thumbnail.is_some() {
+			ThumbnailStatus::Ready
+		} else {
+			ThumbnailStatus::None
+		},
+		thumbnail,
+		item,
+	}))
+}
+
+fn can_generate_thumbnail(kind: ObjectKind) -> bool {
+	#[cfg(feature = "ffmpeg")]
+	{
+		matches!(
+			kind,
+			ObjectKind::Image | ObjectKind::Video | ObjectKind::Document
+		)
+	}
+
+	#[cfg(not(feature = "ffmpeg"))]
+	{
+		matches!(kind, ObjectKind::Image | ObjectKind::Document)
+	}
+}
+
 // #[instrument(name = "non_indexed::walk", skip(sort_fn))]
 pub async fn walk(
 	path: PathBuf,
@@ -110,6 +368,8 @@ pub async fn walk(
 > {
 	let mut entries = get_all_entries(path.clone()).await?;
 
+	node.ephemeral_paths.insert(path.clone(), ());
+
 	{
 		let span = span!(Level::INFO, "sort_fn");
 		let _enter = span.enter();
@@ -154,6 +414,17 @@ pub async fn walk(
 					{
 						continue;
 					}
+
+					// Directories must stay visible to be navigated into, like the indexer walks
+					// into directories these rules didn't accept
+					if !entry.metadata.is_dir()
+						&& rule_results
+							.get(&RuleKind::AcceptIfAllOfRejectIfAnyOf)
+							.map_or(false, |accept_results| {
+								accept_results.iter().any(|accept| !accept)
+							}) {
+						continue;
+					}
 				}
 				Err(e) => {
 					tx.send(Err(Either::Left(e.into()))).await?;
@@ -184,22 +455,7 @@ pub async fn walk(
 					.map(Into::into)
 					.unwrap_or(ObjectKind::Unknown);
 
-				let should_generate_thumbnail = {
-					#[cfg(feature = "ffmpeg")]
-					{
-						matches!(
-							kind,
-							ObjectKind::Image | ObjectKind::Video | ObjectKind::Document
-						)
-					}
-
-					#[cfg(not(feature = "ffmpeg"))]
-					{
-						matches!(kind, ObjectKind::Image | ObjectKind::Document)
-					}
-				};
-
-				let thumbnail_key = if should_generate_thumbnail {
+				let (thumbnail_key, thumbnail_status) = if can_generate_thumbnail(kind) {
 					if let Ok(cas_id) =
 						generate_cas_id(&path, entry.metadata.len())
 							.await
@@ -222,16 +478,30 @@ pub async fn walk(
 							));
 						}
 
-						Some(get_ephemeral_thumb_key(&cas_id))
+						// It's queued with the rest of this directory's batch if it isn't ready yet
+						let thumbnail_status = match node
+							.thumbnailer
+							.status(&cas_id, ThumbnailKind::Ephemeral)
+							.await
+						{
+							Ok(ThumbnailStatus::Ready) => ThumbnailStatus::Ready,
+							_ => ThumbnailStatus::Queued,
+						};
+
+						(
+							Some(get_ephemeral_thumb_key(&cas_id, node.thumbnailer.format())),
+							thumbnail_status,
+						)
 					} else {
-						None
+						(None, ThumbnailStatus::None)
 					}
 				} else {
-					None
+					(None, ThumbnailStatus::None)
 				};
 
 				tx.send(Ok(ExplorerItem::NonIndexedPath {
 					thumbnail: thumbnail_key,
+					thumbnail_status,
 					item: NonIndexedPathItem {
 						hidden: path_is_hidden(Path::new(&entry_path), &entry.metadata),
 						path: entry_path,
@@ -285,6 +555,7 @@ pub async fn walk(
 			} else {
 				tx.send(Ok(ExplorerItem::NonIndexedPath {
 					thumbnail: None,
+					thumbnail_status: ThumbnailStatus::None,
 					item: NonIndexedPathItem {
 						hidden: path_is_hidden(Path::new(&directory), &metadata),
 						path: directory,
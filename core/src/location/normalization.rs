@@ -0,0 +1,344 @@
+use sd_file_path_helper::{join_location_relative_path, normalize_name};
+use sd_prisma::prisma::{file_path, location, object, PrismaClient, SortOrder};
+
+use std::{collections::HashSet, fs::Metadata, path::Path};
+
+use prisma_client_rust::{raw, PrismaValue, QueryError};
+use serde::Deserialize;
+use tokio::fs;
+use tracing::{debug, warn};
+
+/// Used when we can't check a location's filesystem, as it's what these platforms default to
+const DEFAULT_CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+const BACKFILL_BATCH_SIZE: i64 = 1000;
+
+file_path::select!(file_path_to_merge {
+	id
+	name
+	extension
+	is_dir
+	object_id
+});
+
+/// Whether the location's filesystem considers names differing only by case the same, checking it
+/// the first time and storing it in `location.case_insensitive`.
+pub(crate) async fn location_is_case_insensitive(
+	db: &PrismaClient,
+	location_id: location::id::Type,
+	location_path: Option<impl AsRef<Path>>,
+	stored: Option<bool>,
+) -> Result<bool, QueryError> {
+	if let Some(case_insensitive) = stored {
+		return Ok(case_insensitive);
+	}
+
+	let Some(case_insensitive) = (match location_path {
+		Some(location_path) => detect_case_insensitive(location_path).await,
+		None => None,
+	}) else {
+		// Checked again next time, hopefully with the location online
+		return Ok(DEFAULT_CASE_INSENSITIVE);
+	};
+
+	// Local only, as other instances can have the same location in another filesystem
+	db.location()
+		.update(
+			location::id::equals(location_id),
+			vec![location::case_insensitive::set(Some(case_insensitive))],
+		)
+		.exec()
+		.await?;
+
+	Ok(case_insensitive)
+}
+
+/// Looks up the closest path component which has letters with its case swapped, which only finds
+/// the same file on a case-insensitive filesystem.
+///
+/// Returns `None` if the path can't be read or none of its components has letters.
+async fn detect_case_insensitive(path: impl AsRef<Path>) -> Option<bool> {
+	for ancestor in path.as_ref().ancestors() {
+		let Some(name) = ancestor.file_name().and_then(|name| name.to_str()) else {
+			continue;
+		};
+
+		let swapped = swap_case(name);
+		if swapped == name {
+			continue;
+		}
+
+		let metadata = fs::metadata(ancestor).await.ok()?;
+
+		return Some(
+			fs::metadata(ancestor.with_file_name(swapped))
+				.await
+				.is_ok_and(|swapped_metadata| is_same_file(&metadata, &swapped_metadata)),
+		);
+	}
+
+	None
+}
+
+fn swap_case(name: &str) -> String {
+	name.chars()
+		.flat_map(|c| {
+			if c.is_lowercase() {
+				c.to_uppercase().collect::<Vec<_>>()
+			} else {
+				c.to_lowercase().collect()
+			}
+		})
+		.collect()
+}
+
+#[cfg(unix)]
+fn is_same_file(metadata: &Metadata, other: &Metadata) -> bool {
+	use std::os::unix::fs::MetadataExt;
+
+	metadata.dev() == other.dev() && metadata.ino() == other.ino()
+}
+
+#[cfg(not(unix))]
+fn is_same_file(metadata: &Metadata, other: &Metadata) -> bool {
+	// The swapped name was found, and two files can't only differ by their case there
+	metadata.is_dir() == other.is_dir() && metadata.len() == other.len()
+}
+
+/// Fills `file_path.name_normalized` for libraries indexed before it existed, then merges the
+/// file_paths which ended up duplicated for names their filesystem considers the same.
+pub(crate) async fn backfill_normalized_names(db: &PrismaClient) -> Result<(), QueryError> {
+	let locations = db
+		.location()
+		.find_many(vec![])
+		.select(location::select!({ id path case_insensitive }))
+		.exec()
+		.await?;
+
+	for location in locations {
+		let case_insensitive = location_is_case_insensitive(
+			db,
+			location.id,
+			location.path.as_ref(),
+			location.case_insensitive,
+		)
+		.await?;
+
+		let mut cursor = None;
+
+		loop {
+			let mut query = db
+				.file_path()
+				.find_many(vec![file_path::location_id::equals(Some(location.id))])
+				.order_by(file_path::id::order(SortOrder::Asc))
+				.take(BACKFILL_BATCH_SIZE)
+				.select(file_path::select!({ id name extension }));
+
+			if let Some(cursor) = cursor {
+				query = query.cursor(file_path::id::equals(cursor)).skip(1);
+			}
+
+			let file_paths = query.exec().await?;
+
+			let Some(last) = file_paths.last() else {
+				break;
+			};
+			cursor = Some(last.id);
+
+			db._batch(
+				file_paths
+					.iter()
+					.map(|file_path| {
+						db.file_path().update(
+							file_path::id::equals(file_path.id),
+							vec![file_path::name_normalized::set(
+								file_path.name.as_deref().map(|name| {
+									normalize_full_name(
+										name,
+										file_path.extension.as_deref().unwrap_or_default(),
+										case_insensitive,
+									)
+								}),
+							)],
+						)
+					})
+					.collect::<Vec<_>>(),
+			)
+			.await?;
+		}
+
+		if let Some(path) = &location.path {
+			merge_duplicated_names(db, location.id, path).await?;
+		}
+	}
+
+	Ok(())
+}
+
+fn normalize_full_name(name: &str, extension: &str, case_insensitive: bool) -> String {
+	normalize_name(&full_name(name, extension), case_insensitive)
+}
+
+fn full_name(name: &str, extension: &str) -> String {
+	if extension.is_empty() {
+		name.to_string()
+	} else {
+		format!("{name}.{extension}")
+	}
+}
+
+/// Only merges the duplicated files whose directory can be read, keeping the one with the name
+/// found there. Directories are left for the indexer, as their children would have to be moved.
+async fn merge_duplicated_names(
+	db: &PrismaClient,
+	location_id: location::id::Type,
+	location_path: &str,
+) -> Result<(), QueryError> {
+	#[derive(Deserialize)]
+	struct DuplicatedName {
+		materialized_path: String,
+		name_normalized: String,
+	}
+
+	if fs::metadata(location_path).await.is_err() {
+		return Ok(());
+	}
+
+	let duplicates = db
+		._query_raw::<DuplicatedName>(raw!(
+			"SELECT materialized_path, name_normalized FROM file_path
+			WHERE
+				location_id = {}
+				AND materialized_path IS NOT NULL
+				AND name_normalized IS NOT NULL
+			GROUP BY materialized_path, name_normalized
+			HAVING COUNT(*) > 1",
+			PrismaValue::Int(location_id as i64)
+		))
+		.exec()
+		.await?;
+
+	for DuplicatedName {
+		materialized_path,
+		name_normalized,
+	} in duplicates
+	{
+		let file_paths = db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(Some(location_id)),
+				file_path::materialized_path::equals(Some(materialized_path.clone())),
+				file_path::name_normalized::equals(Some(name_normalized)),
+			])
+			.select(file_path_to_merge::select())
+			.exec()
+			.await?;
+
+		if file_paths
+			.iter()
+			.any(|file_path| file_path.is_dir.unwrap_or_default())
+		{
+			continue;
+		}
+
+		let Some(names_on_disk) = read_names(join_location_relative_path(
+			location_path,
+			&materialized_path,
+		))
+		.await
+		else {
+			continue;
+		};
+
+		let (on_disk, stale): (Vec<_>, Vec<_>) = file_paths.into_iter().partition(|file_path| {
+			names_on_disk.contains(&full_name(
+				file_path.name.as_deref().unwrap_or_default(),
+				file_path.extension.as_deref().unwrap_or_default(),
+			))
+		});
+
+		// Can't tell which one to keep otherwise
+		let [kept] = on_disk.as_slice() else {
+			warn!(
+				"Not merging the duplicated file_paths of <location_id={location_id}> \
+				at '{materialized_path}' as {} of them are on disk",
+				on_disk.len()
+			);
+			continue;
+		};
+
+		debug!(
+			"Merging {} duplicated file_paths into <file_path_id={}>",
+			stale.len(),
+			kept.id
+		);
+
+		// The kept file_path takes the object of a stale one, instead of being identified again
+		if kept.object_id.is_none() {
+			if let Some(object_id) = stale.iter().find_map(|file_path| file_path.object_id) {
+				db.file_path()
+					.update(
+						file_path::id::equals(kept.id),
+						vec![file_path::object::connect(object::id::equals(object_id))],
+					)
+					.exec()
+					.await?;
+			}
+		}
+
+		db.file_path()
+			.delete_many(vec![file_path::id::in_vec(
+				stale.into_iter().map(|file_path| file_path.id).collect(),
+			)])
+			.exec()
+			.await?;
+	}
+
+	Ok(())
+}
+
+async fn read_names(path: impl AsRef<Path>) -> Option<HashSet<String>> {
+	let mut read_dir = fs::read_dir(path).await.ok()?;
+	let mut names = HashSet::new();
+
+	while let Some(entry) = read_dir.next_entry().await.ok()? {
+		if let Ok(name) = entry.file_name().into_string() {
+			names.insert(name);
+		}
+	}
+
+	Some(names)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use tempfile::tempdir;
+
+	#[test]
+	fn test_swap_case() {
+		assert_eq!(swap_case("Photos 2023"), "pHOTOS 2023");
+		assert_eq!(swap_case("\u{c9}t\u{e9}"), "\u{e9}T\u{c9}");
+		assert_eq!(swap_case("2023"), "2023");
+	}
+
+	#[tokio::test]
+	async fn test_detect_case_insensitive() {
+		let dir = tempdir().unwrap();
+		let location = dir.path().join("Photos");
+		fs::create_dir(&location).await.unwrap();
+
+		let case_insensitive = fs::metadata(dir.path().join("PHOTOS")).await.is_ok();
+
+		assert_eq!(
+			detect_case_insensitive(&location).await,
+			Some(case_insensitive)
+		);
+		assert_eq!(
+			detect_case_insensitive(dir.path().join("Missing")).await,
+			None
+		);
+	}
+}
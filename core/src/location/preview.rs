@@ -0,0 +1,451 @@
+use crate::{library::Library, Node};
+
+use sd_prisma::prisma::{indexer_rule, location};
+use sd_utils::error::{FileIOError, NonUtf8PathError};
+
+use std::{
+	collections::{HashMap, VecDeque},
+	path::Path,
+	sync::Arc,
+};
+
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+use specta::Type;
+use tokio::{fs, io};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use super::{
+	indexer::rules::{IndexerRule, RuleKind},
+	metadata::SpacedriveLocationMetadataFile,
+	normalize_path, overlapping_locations, LocationCreateArgs, LocationError,
+};
+
+/// How many entries a preview walks through at most, so previewing huge directories stays quick.
+const SAMPLE_LIMIT: usize = 10_000;
+
+/// What creating a location would do, returned by dry runs instead of creating it.
+#[serde_as]
+#[derive(Serialize, Type, Debug)]
+pub struct LocationCreatePreview {
+	/// Entries which would be indexed, among the sampled ones.
+	pub files: u32,
+	pub directories: u32,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	pub total_size: u64,
+	/// Whether every entry was sampled, otherwise the counts only cover part of the location.
+	pub complete: bool,
+	/// In the same order as the rules were given.
+	pub rules: Vec<IndexerRuleImpact>,
+	/// Locations of this library containing the path or inside it.
+	pub overlapping_locations: Vec<location::id::Type>,
+	/// Libraries which already have the path as a location, from its `.spacedrive` file.
+	pub other_libraries: Vec<OtherLibrary>,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct IndexerRuleImpact {
+	pub indexer_rule_id: indexer_rule::id::Type,
+	pub name: String,
+	/// Sampled entries this rule would keep from being indexed on its own.
+	pub rejected: u32,
+}
+
+#[derive(Serialize, Type, Debug, Clone)]
+pub struct OtherLibrary {
+	pub id: Uuid,
+	pub name: String,
+}
+
+impl LocationCreateArgs {
+	/// Looks at what creating the location would do without writing anything, neither to the
+	/// database nor to the location's `.spacedrive` file.
+	///
+	/// Fails like [`Self::create`] when the location can't be created this way, for instance when
+	/// this library has it at another path and it needs to be relinked instead.
+	pub async fn preview(
+		&self,
+		node: &Node,
+		library: &Arc<Library>,
+	) -> Result<LocationCreatePreview, LocationError> {
+		let Library { db, .. } = &**library;
+
+		if self.path.to_str().is_none() {
+			return Err(LocationError::NonUtf8Path(NonUtf8PathError(
+				self.path.clone().into_boxed_path(),
+			)));
+		}
+
+		match fs::metadata(&self.path).await {
+			Ok(metadata) if metadata.is_dir() => {}
+			Ok(_) => {
+				return Err(LocationError::NotDirectory(
+					self.path.clone().into_boxed_path(),
+				))
+			}
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {
+				return Err(LocationError::PathNotFound(
+					self.path.clone().into_boxed_path(),
+				))
+			}
+			Err(e) => {
+				return Err(LocationError::LocationPathFilesystemMetadataAccess(
+					FileIOError::from((&self.path, e)),
+				))
+			}
+		}
+
+		let (path, _) = normalize_path(&self.path)
+			.map_err(|_| LocationError::DirectoryNotFound(self.path.clone().into_boxed_path()))?;
+
+		if db
+			.location()
+			.count(vec![location::path::equals(Some(path))])
+			.exec()
+			.await? > 0
+		{
+			return Err(LocationError::LocationAlreadyExists(
+				self.path.clone().into_boxed_path(),
+			));
+		}
+
+		let mut other_libraries = vec![];
+
+		if let Some(mut metadata) = SpacedriveLocationMetadataFile::try_load(&self.path).await? {
+			let libraries = node
+				.libraries
+				.get_all()
+				.await
+				.into_iter()
+				.map(|library| (library.id, library))
+				.collect::<HashMap<_, _>>();
+
+			metadata.forget_stale_libraries(&libraries.keys().copied().collect());
+
+			if let Some(old_path) = metadata.location_path(library.id) {
+				if old_path != self.path {
+					return Err(LocationError::NeedRelink {
+						old_path: old_path.into(),
+						new_path: self.path.clone().into_boxed_path(),
+					});
+				}
+			}
+
+			for id in metadata.library_ids().filter(|id| *id != library.id) {
+				if let Some(other_library) = libraries.get(&id) {
+					other_libraries.push(OtherLibrary {
+						id,
+						name: other_library.config().await.name.into(),
+					});
+				}
+			}
+		}
+
+		let rules = {
+			let mut rules = db
+				.indexer_rule()
+				.find_many(vec![indexer_rule::id::in_vec(
+					self.indexer_rules_ids.clone(),
+				)])
+				.exec()
+				.await?
+				.into_iter()
+				.filter_map(|rule| {
+					IndexerRule::try_from(&rule)
+						.map_err(|e| {
+							warn!("Skipping indexer rule <id='{}'> in dry run: {e}", rule.id)
+						})
+						.ok()
+				})
+				.collect::<Vec<_>>();
+
+			rules.sort_by_key(|rule| {
+				self.indexer_rules_ids
+					.iter()
+					.position(|id| Some(*id) == rule.id)
+			});
+
+			rules
+		};
+
+		let sample = sample_walk(&self.path, &rules, SAMPLE_LIMIT).await;
+
+		debug!(
+			"Dry run: sampled {} entries of '{}'",
+			sample.sampled,
+			self.path.display()
+		);
+
+		Ok(LocationCreatePreview {
+			files: sample.files,
+			directories: sample.directories,
+			total_size: sample.total_size,
+			complete: sample.complete,
+			rules: rules
+				.into_iter()
+				.zip(sample.rejected_per_rule)
+				.filter_map(|(rule, rejected)| {
+					rule.id.map(|indexer_rule_id| IndexerRuleImpact {
+						indexer_rule_id,
+						name: rule.name,
+						rejected,
+					})
+				})
+				.collect(),
+			overlapping_locations: overlapping_locations(&self.path, db).await?,
+			other_libraries,
+		})
+	}
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Sample {
+	sampled: usize,
+	files: u32,
+	directories: u32,
+	total_size: u64,
+	complete: bool,
+	rejected_per_rule: Vec<u32>,
+}
+
+/// Walks breadth first through up to `limit` entries, deciding which ones would be indexed the
+/// same way the indexer's walker does. Symlinks are counted but never followed.
+async fn sample_walk(root: impl AsRef<Path>, rules: &[IndexerRule], limit: usize) -> Sample {
+	let mut sample = Sample {
+		rejected_per_rule: vec![0; rules.len()],
+		..Default::default()
+	};
+
+	// Along with the state of `RuleKind::AcceptIfChildrenDirectoriesArePresent` inherited by
+	// what's inside each directory
+	let mut to_walk = VecDeque::from([(root.as_ref().to_path_buf(), None)]);
+
+	'walk: while let Some((dir, parent_accepted_by_its_children)) = to_walk.pop_front() {
+		let Ok(mut read_dir) = fs::read_dir(&dir).await else {
+			continue;
+		};
+
+		while let Ok(Some(entry)) = read_dir.next_entry().await {
+			if sample.sampled == limit {
+				break 'walk;
+			}
+			sample.sampled += 1;
+
+			let path = entry.path();
+
+			let Ok(metadata) = fs::symlink_metadata(&path).await else {
+				continue;
+			};
+			let is_dir = metadata.is_dir();
+
+			let mut results_per_kind = HashMap::<_, Vec<_>>::new();
+			for (rule, rejected) in rules.iter().zip(&mut sample.rejected_per_rule) {
+				let Ok(results) = rule.apply(&path).await else {
+					continue;
+				};
+
+				let results_of_rule =
+					results
+						.iter()
+						.fold(HashMap::<_, Vec<_>>::new(), |mut map, (kind, result)| {
+							map.entry(*kind).or_default().push(*result);
+							map
+						});
+				if !decide(&results_of_rule, is_dir, None).indexed {
+					*rejected += 1;
+				}
+
+				for (kind, result) in results {
+					results_per_kind.entry(kind).or_default().push(result);
+				}
+			}
+
+			let decision = decide(&results_per_kind, is_dir, parent_accepted_by_its_children);
+
+			if let Some(accepted_by_its_children) = decision.walk_into {
+				to_walk.push_back((path, accepted_by_its_children));
+			}
+
+			if decision.indexed {
+				if is_dir {
+					sample.directories += 1;
+				} else {
+					sample.files += 1;
+					sample.total_size += metadata.len();
+				}
+			}
+		}
+	}
+
+	sample.complete = to_walk.is_empty() && sample.sampled < limit;
+
+	sample
+}
+
+struct Decision {
+	indexed: bool,
+	/// `Some` for directories to walk into, with the state their children inherit.
+	walk_into: Option<Option<bool>>,
+}
+
+/// Mirrors how the indexer's walker combines the results of the rules for a path.
+fn decide(
+	results_per_kind: &HashMap<RuleKind, Vec<bool>>,
+	is_dir: bool,
+	parent_accepted_by_its_children: Option<bool>,
+) -> Decision {
+	let any_false = |kind| {
+		results_per_kind
+			.get(&kind)
+			.map_or(false, |results: &Vec<bool>| {
+				results.iter().any(|result| !result)
+			})
+	};
+
+	let skipped = Decision {
+		indexed: false,
+		walk_into: None,
+	};
+
+	if any_false(RuleKind::RejectFilesByGlob) {
+		return skipped;
+	}
+
+	let mut accepted_by_its_children = parent_accepted_by_its_children;
+
+	if is_dir {
+		if any_false(RuleKind::RejectIfChildrenDirectoriesArePresent) {
+			return skipped;
+		}
+
+		if let Some(results) =
+			results_per_kind.get(&RuleKind::AcceptIfChildrenDirectoriesArePresent)
+		{
+			if results.iter().any(|result| *result) {
+				accepted_by_its_children = Some(true);
+			}
+
+			if accepted_by_its_children.is_none() {
+				accepted_by_its_children = Some(false);
+			}
+		}
+	}
+
+	let walk_into = is_dir.then_some(accepted_by_its_children);
+
+	let indexed = !results_per_kind
+		.get(&RuleKind::AcceptFilesByGlob)
+		.map_or(false, |results| results.iter().all(|result| !result))
+		&& !any_false(RuleKind::AcceptIfAllOfRejectIfAnyOf)
+		&& accepted_by_its_children.unwrap_or(true);
+
+	Decision { indexed, walk_into }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::location::indexer::rules::RulePerKind;
+
+	use std::path::PathBuf;
+
+	use tempfile::{Builder, TempDir};
+
+	fn no_hidden() -> IndexerRule {
+		IndexerRule::new(
+			"No Hidden".to_string(),
+			false,
+			vec![RulePerKind::new_reject_files_by_globs_str(["**/.*"]).unwrap()],
+		)
+	}
+
+	fn no_git_repos() -> IndexerRule {
+		IndexerRule::new(
+			"No Git Repos".to_string(),
+			false,
+			vec![RulePerKind::RejectIfChildrenDirectoriesArePresent(
+				[".git".to_string()].into_iter().collect(),
+			)],
+		)
+	}
+
+	/// ```text
+	/// photos/
+	///   a.jpg (3 bytes), .hidden.jpg (5 bytes)
+	///   project/ (git repository)
+	///     .git/HEAD (7 bytes), main.rs (11 bytes)
+	///     vendor/ (nested git repository)
+	///       .git/HEAD (7 bytes), lib.rs (13 bytes)
+	///   .cache/
+	///     thumb.png (17 bytes)
+	/// ```
+	async fn fixture() -> (TempDir, PathBuf) {
+		// The default `.tmp` prefix would make everything inside look hidden
+		let dir = Builder::new().prefix("preview").tempdir().unwrap();
+		let root = dir.path().join("photos");
+
+		for (path, size) in [
+			("a.jpg", 3),
+			(".hidden.jpg", 5),
+			("project/.git/HEAD", 7),
+			("project/main.rs", 11),
+			("project/vendor/.git/HEAD", 7),
+			("project/vendor/lib.rs", 13),
+			(".cache/thumb.png", 17),
+		] {
+			let path = root.join(path);
+			fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+			fs::write(path, vec![0; size]).await.unwrap();
+		}
+
+		(dir, root)
+	}
+
+	#[tokio::test]
+	async fn test_sample_without_rules() {
+		let (_dir, root) = fixture().await;
+
+		let sample = sample_walk(&root, &[], SAMPLE_LIMIT).await;
+
+		assert_eq!(
+			sample,
+			Sample {
+				sampled: 12,
+				files: 7,
+				directories: 5,
+				total_size: 3 + 5 + 7 + 11 + 7 + 13 + 17,
+				complete: true,
+				rejected_per_rule: vec![],
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn test_sample_counts_rejections_per_rule() {
+		let (_dir, root) = fixture().await;
+
+		let sample = sample_walk(&root, &[no_hidden(), no_git_repos()], SAMPLE_LIMIT).await;
+
+		// Hidden entries are skipped along with what's inside them, and so is the repository
+		assert_eq!(sample.files, 1);
+		assert_eq!(sample.directories, 0);
+		assert_eq!(sample.total_size, 3);
+		assert!(sample.complete);
+		// `.hidden.jpg` and `.cache`, then `project` which is a repository
+		assert_eq!(sample.rejected_per_rule, vec![2, 1]);
+		assert_eq!(sample.sampled, 4);
+	}
+
+	#[tokio::test]
+	async fn test_sample_is_bounded() {
+		let (_dir, root) = fixture().await;
+
+		let sample = sample_walk(&root, &[], 3).await;
+
+		assert_eq!(sample.sampled, 3);
+		assert!(!sample.complete);
+	}
+}
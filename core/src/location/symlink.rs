@@ -0,0 +1,212 @@
+use std::{
+	fs::Metadata,
+	io,
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::fs;
+
+/// How many symlinked directories can be followed into one another, after which their links are
+/// just indexed as links, bounding how deep and how much the walker remembers through them.
+const MAX_FOLLOWED_LINKS: usize = 8;
+
+/// What the indexer and the watcher do with the symlinks in a location, see `location.symlink_policy`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Type, Eq, PartialEq)]
+pub enum SymlinkPolicy {
+	/// Symlinks aren't indexed at all.
+	#[default]
+	Skip = 0,
+	/// Symlinked directories outside of the location are indexed as if they were in it. Links to
+	/// files, to somewhere in the location or to one of their own ancestors, as well as broken
+	/// links, are indexed as links instead.
+	FollowSafe = 1,
+	/// Symlinks are indexed as empty files holding the path of their target, which isn't read.
+	IndexAsLink = 2,
+}
+
+impl From<Option<i32>> for SymlinkPolicy {
+	fn from(value: Option<i32>) -> Self {
+		match value {
+			Some(1) => Self::FollowSafe,
+			Some(2) => Self::IndexAsLink,
+			_ => Self::Skip,
+		}
+	}
+}
+
+/// Identifies a directory across the different paths it can be reached by.
+pub type DirId = (u64, u64);
+
+/// What a symlink found in a location is indexed as.
+#[derive(Debug)]
+pub enum ResolvedSymlink {
+	Skipped,
+	/// An empty file with the link's target, which may not exist.
+	Link {
+		target: PathBuf,
+	},
+	/// The directory it links to, to be walked into.
+	Followed {
+		metadata: Metadata,
+		dir_id: DirId,
+	},
+}
+
+/// Resolves the symlinks found in a location under its [`SymlinkPolicy`].
+#[derive(Debug, Clone)]
+pub struct SymlinkResolver {
+	policy: SymlinkPolicy,
+	location_path: PathBuf,
+}
+
+impl SymlinkResolver {
+	pub fn new(policy: impl Into<SymlinkPolicy>, location_path: impl Into<PathBuf>) -> Self {
+		Self {
+			policy: policy.into(),
+			location_path: location_path.into(),
+		}
+	}
+
+	/// `followed_links` are the directories already followed to get to the link, which can't be
+	/// followed again as that would be a cycle.
+	pub async fn resolve(
+		&self,
+		link_path: impl AsRef<Path>,
+		followed_links: &[DirId],
+	) -> io::Result<ResolvedSymlink> {
+		let link_path = link_path.as_ref();
+
+		let target = match self.policy {
+			SymlinkPolicy::Skip => return Ok(ResolvedSymlink::Skipped),
+			SymlinkPolicy::FollowSafe | SymlinkPolicy::IndexAsLink => {
+				fs::read_link(link_path).await?
+			}
+		};
+
+		if self.policy == SymlinkPolicy::IndexAsLink || followed_links.len() >= MAX_FOLLOWED_LINKS {
+			return Ok(ResolvedSymlink::Link { target });
+		}
+
+		// Broken links are still indexed, instead of failing the whole walk
+		let Ok(metadata) = fs::metadata(link_path).await else {
+			return Ok(ResolvedSymlink::Link { target });
+		};
+
+		if !metadata.is_dir() {
+			return Ok(ResolvedSymlink::Link { target });
+		}
+
+		let Some(dir_id) = dir_id(link_path, &metadata).await else {
+			return Ok(ResolvedSymlink::Link { target });
+		};
+
+		if followed_links.contains(&dir_id) {
+			return Ok(ResolvedSymlink::Link { target });
+		}
+
+		let canonical_target = fs::canonicalize(link_path).await?;
+
+		// Already indexed through its own path
+		if canonical_target.starts_with(fs::canonicalize(&self.location_path).await?) {
+			return Ok(ResolvedSymlink::Link { target });
+		}
+
+		// Linking to one of its ancestors, which would never end
+		if let Some(parent) = link_path.parent() {
+			if fs::canonicalize(parent)
+				.await?
+				.starts_with(&canonical_target)
+			{
+				return Ok(ResolvedSymlink::Link { target });
+			}
+		}
+
+		Ok(ResolvedSymlink::Followed { metadata, dir_id })
+	}
+}
+
+#[cfg(target_family = "unix")]
+async fn dir_id(_: &Path, metadata: &Metadata) -> Option<DirId> {
+	use std::os::unix::fs::MetadataExt;
+
+	Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(target_family = "windows")]
+async fn dir_id(path: &Path, _: &Metadata) -> Option<DirId> {
+	// The volume serial number isn't available yet, but paths followed out of a location rarely
+	// cross volumes more than once
+	sd_file_path_helper::get_inode_from_path(path)
+		.await
+		.ok()
+		.map(|inode| (0, inode))
+}
+
+#[cfg(all(test, target_family = "unix"))]
+#[allow(clippy::unwrap_used, clippy::panic)]
+mod tests {
+	use super::*;
+
+	use std::os::unix::fs::symlink;
+
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_resolve() {
+		let dir = tempdir().unwrap();
+		let location = dir.path().join("location");
+		let outside = dir.path().join("outside");
+		fs::create_dir_all(location.join("photos")).await.unwrap();
+		fs::create_dir_all(&outside).await.unwrap();
+
+		symlink(&outside, location.join("outside")).unwrap();
+		symlink(location.join("photos"), location.join("photos link")).unwrap();
+		symlink(&location, location.join("photos").join("loop")).unwrap();
+		symlink(location.join("missing"), location.join("broken")).unwrap();
+		// A cycle which only goes through the outside directory
+		symlink(&outside, outside.join("back")).unwrap();
+
+		let follow = SymlinkResolver::new(SymlinkPolicy::FollowSafe, &location);
+
+		let ResolvedSymlink::Followed { dir_id, .. } =
+			follow.resolve(location.join("outside"), &[]).await.unwrap()
+		else {
+			panic!("expected the outside directory to be followed");
+		};
+
+		for (link, followed_links) in [
+			(location.join("photos link"), vec![]),
+			(location.join("photos").join("loop"), vec![]),
+			(location.join("broken"), vec![]),
+			(location.join("outside").join("back"), vec![dir_id]),
+		] {
+			assert!(
+				matches!(
+					follow.resolve(&link, &followed_links).await.unwrap(),
+					ResolvedSymlink::Link { .. }
+				),
+				"expected {} to be indexed as a link",
+				link.display()
+			);
+		}
+
+		assert!(matches!(
+			SymlinkResolver::new(SymlinkPolicy::IndexAsLink, &location)
+				.resolve(location.join("outside"), &[])
+				.await
+				.unwrap(),
+			ResolvedSymlink::Link { target } if target == outside
+		));
+
+		assert!(matches!(
+			SymlinkResolver::new(SymlinkPolicy::Skip, &location)
+				.resolve(location.join("outside"), &[])
+				.await
+				.unwrap(),
+			ResolvedSymlink::Skipped
+		));
+	}
+}
@@ -1,9 +1,15 @@
 use crate::{
 	api::{notifications::Notification, BackendFeature},
-	object::media::thumbnail::preferences::ThumbnailerPreferences,
+	library::StatisticsPreferences,
+	node::NetworkPreferences,
+	object::{
+		media::thumbnail::preferences::ThumbnailerPreferences,
+		validation::preferences::IntegrityPreferences,
+	},
 	util::version_manager::{Kind, ManagedVersion, VersionManager, VersionManagerError},
 };
 
+use sd_crypto::types::{Algorithm, HashingAlgorithm, Params};
 use sd_p2p::{Keypair, ManagerConfig};
 use sd_utils::error::FileIOError;
 
@@ -28,6 +34,25 @@ use uuid::Uuid;
 /// NODE_STATE_CONFIG_NAME is the name of the file which stores the NodeState
 pub const NODE_STATE_CONFIG_NAME: &str = "node_state.sdconfig";
 
+/// The default for [`NodeConfig::db_connection_limit`].
+pub const DEFAULT_DB_CONNECTION_LIMIT: u32 = 1;
+/// The default for [`NodeConfig::db_busy_timeout_secs`].
+pub const DEFAULT_DB_BUSY_TIMEOUT_SECS: u32 = 15;
+/// The default for [`NodeConfig::shutdown_timeout_secs`].
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u32 = 10;
+/// The default for [`NodeConfig::allowed_origins`], the origins of the desktop app's webview.
+pub const DEFAULT_ALLOWED_ORIGINS: &[&str] = &[
+	// macOS and Linux
+	"tauri://localhost",
+	// Windows
+	"https://tauri.localhost",
+	// The desktop and web dev servers
+	#[cfg(debug_assertions)]
+	"http://localhost:8001",
+	#[cfg(debug_assertions)]
+	"http://localhost:8002",
+];
+
 /// NodeConfig is the configuration for a node. This is shared between all libraries and is stored in a JSON file on disk.
 #[derive(Debug, Clone, Serialize, Deserialize)] // If you are adding `specta::Type` on this your probably about to leak the P2P private key
 pub struct NodeConfig {
@@ -56,6 +81,34 @@ pub struct NodeConfig {
 	pub preferences: NodePreferences,
 	// Model version for the image labeler
 	pub image_labeler_version: Option<String>,
+	/// How many connections each library database can have open, defaults to [`DEFAULT_DB_CONNECTION_LIMIT`].
+	///
+	/// More connections lets reads happen while something else writes, but each one holds file handles
+	/// and the database's `-wal` file can grow until they are all idle. For libraries on removable
+	/// media, 1 is safest as there are fewer handles to be left behind if the drive is unplugged.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub db_connection_limit: Option<u32>,
+	/// How long a query waits for a locked database before failing with "database is locked",
+	/// defaults to [`DEFAULT_DB_BUSY_TIMEOUT_SECS`].
+	///
+	/// Slow removable media may need a larger value, at the cost of the UI hanging for longer when
+	/// the database really is stuck.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub db_busy_timeout_secs: Option<u32>,
+	/// How long each subsystem gets to shut down before it's abandoned, defaults to
+	/// [`DEFAULT_SHUTDOWN_TIMEOUT_SECS`].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub shutdown_timeout_secs: Option<u32>,
+	/// The origins whose pages can fetch files and thumbnails from the custom_uri server, defaults
+	/// to [`DEFAULT_ALLOWED_ORIGINS`].
+	///
+	/// A frontend served from anywhere else, like a separate UI for a headless core, has to be added
+	/// here, or `*` to allow every origin. Requests from other origins are rejected.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub allowed_origins: Option<Vec<String>>,
+	/// The algorithms used when the core encrypts files and the request doesn't pick them.
+	#[serde(default)]
+	pub crypto_defaults: CryptoDefaults,
 
 	version: NodeConfigVersion,
 }
@@ -63,6 +116,28 @@ pub struct NodeConfig {
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Type)]
 pub struct NodePreferences {
 	pub thumbnailer: ThumbnailerPreferences,
+	#[serde(default)]
+	pub integrity: IntegrityPreferences,
+	#[serde(default)]
+	pub statistics: StatisticsPreferences,
+	#[serde(default)]
+	pub network: NetworkPreferences,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+pub struct CryptoDefaults {
+	pub algorithm: Algorithm,
+	/// Used for hashing the password of each file's keyslot, stronger params take longer to hash.
+	pub hashing_algorithm: HashingAlgorithm,
+}
+
+impl Default for CryptoDefaults {
+	fn default() -> Self {
+		Self {
+			algorithm: Algorithm::XChaCha20Poly1305,
+			hashing_algorithm: HashingAlgorithm::Argon2id(Params::Standard),
+		}
+	}
 }
 
 #[derive(
@@ -108,11 +183,38 @@ impl ManagedVersion<NodeConfigVersion> for NodeConfig {
 			sd_api_origin: None,
 			preferences: NodePreferences::default(),
 			image_labeler_version,
+			db_connection_limit: None,
+			db_busy_timeout_secs: None,
+			shutdown_timeout_secs: None,
+			allowed_origins: None,
+			crypto_defaults: CryptoDefaults::default(),
 		})
 	}
 }
 
 impl NodeConfig {
+	/// The SQLite connection URL for the library database at `db_path`.
+	pub fn db_url(&self, db_path: &str) -> String {
+		format!(
+			"file:{db_path}?socket_timeout={}&connection_limit={}",
+			self.db_busy_timeout_secs
+				.unwrap_or(DEFAULT_DB_BUSY_TIMEOUT_SECS),
+			self.db_connection_limit
+				.unwrap_or(DEFAULT_DB_CONNECTION_LIMIT)
+				.max(1),
+		)
+	}
+
+	/// Whether pages from `origin` can fetch from the custom_uri server, see [`NodeConfig::allowed_origins`].
+	pub fn allows_origin(&self, origin: &str) -> bool {
+		match &self.allowed_origins {
+			Some(allowed_origins) => allowed_origins.iter().any(|allowed| {
+				allowed == "*" || allowed.trim_end_matches('/').eq_ignore_ascii_case(origin)
+			}),
+			None => DEFAULT_ALLOWED_ORIGINS.contains(&origin),
+		}
+	}
+
 	pub async fn load(path: impl AsRef<Path>) -> Result<Self, NodeConfigError> {
 		let path = path.as_ref();
 		VersionManager::<Self, NodeConfigVersion>::migrate_and_load(
@@ -301,3 +403,24 @@ pub enum NodeConfigError {
 	#[error(transparent)]
 	FileIO(#[from] FileIOError),
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_allows_origin() {
+		let mut config = NodeConfig::from_latest_version().unwrap();
+
+		assert!(config.allows_origin("tauri://localhost"));
+		assert!(!config.allows_origin("https://example.com"));
+
+		config.allowed_origins = Some(vec!["https://Spacedrive.Example.com/".to_string()]);
+		assert!(config.allows_origin("https://spacedrive.example.com"));
+		assert!(!config.allows_origin("tauri://localhost"));
+
+		config.allowed_origins = Some(vec!["*".to_string()]);
+		assert!(config.allows_origin("https://example.com"));
+	}
+}
@@ -0,0 +1,256 @@
+use sd_utils::error::FileIOError;
+
+use std::{
+	ffi::OsString,
+	fs::{self, File},
+	io,
+	path::{Path, PathBuf},
+};
+
+use blake3::Hasher;
+use thiserror::Error;
+use tokio::task::{spawn_blocking, JoinError};
+use tracing::{error, info};
+
+#[derive(Debug, Error)]
+pub enum DataDirMigrationError {
+	#[error("data directory not found: {}", .0.display())]
+	NotFound(PathBuf),
+	#[error("the new data directory can't be inside the current one or contain it: {}", .0.display())]
+	Overlapping(PathBuf),
+	#[error("the new data directory already exists and isn't empty: {}", .0.display())]
+	TargetNotEmpty(PathBuf),
+	#[error("copied file doesn't match the original: {}", .0.display())]
+	IntegrityMismatch(PathBuf),
+	#[error(transparent)]
+	FileIO(#[from] FileIOError),
+	#[error("error joining tokio task: {0}")]
+	TaskJoin(#[from] JoinError),
+}
+
+/// Moves the whole data directory (config, libraries, thumbnails, logs...) to `new_dir`.
+///
+/// This must only run while no [`Node`](crate::Node) is using `data_dir`, as the databases and
+/// config files must not change while they're being copied.
+///
+/// Everything is copied to a staging directory next to `new_dir` and verified against the
+/// original before being renamed into place, so a failure leaves the original data directory
+/// untouched and removes the partial copy. The original is only removed once the switch is done.
+///
+/// Paths stored inside the data directory are all relative to it, so nothing has to be rewritten,
+/// but the app must start the [`Node`](crate::Node) with `new_dir` from now on.
+pub async fn migrate_data_dir(
+	data_dir: impl AsRef<Path>,
+	new_dir: impl AsRef<Path>,
+) -> Result<(), DataDirMigrationError> {
+	let data_dir = data_dir.as_ref().to_path_buf();
+	let new_dir = new_dir.as_ref().to_path_buf();
+
+	spawn_blocking(move || migrate(&data_dir, &new_dir)).await?
+}
+
+fn migrate(data_dir: &Path, new_dir: &Path) -> Result<(), DataDirMigrationError> {
+	let data_dir = fs::canonicalize(data_dir).map_err(|e| {
+		if e.kind() == io::ErrorKind::NotFound {
+			DataDirMigrationError::NotFound(data_dir.into())
+		} else {
+			FileIOError::from((data_dir, e)).into()
+		}
+	})?;
+
+	let (Some(new_dir_parent), Some(new_dir_name)) = (new_dir.parent(), new_dir.file_name()) else {
+		return Err(DataDirMigrationError::Overlapping(new_dir.into()));
+	};
+
+	fs::create_dir_all(new_dir_parent).map_err(|e| FileIOError::from((new_dir_parent, e)))?;
+	let new_dir = fs::canonicalize(new_dir_parent)
+		.map_err(|e| FileIOError::from((new_dir_parent, e)))?
+		.join(new_dir_name);
+
+	if new_dir.starts_with(&data_dir) || data_dir.starts_with(&new_dir) {
+		return Err(DataDirMigrationError::Overlapping(new_dir));
+	}
+
+	match fs::read_dir(&new_dir) {
+		Ok(mut entries) => {
+			if entries.next().is_some() {
+				return Err(DataDirMigrationError::TargetNotEmpty(new_dir));
+			}
+		}
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+		Err(e) => return Err(FileIOError::from((new_dir, e)).into()),
+	}
+
+	// The staging directory is a sibling of the new one, so the final rename is atomic
+	let mut staging_dir_name = OsString::from(".");
+	staging_dir_name.push(new_dir_name);
+	staging_dir_name.push(".migrating");
+	let staging_dir = new_dir.with_file_name(staging_dir_name);
+
+	if staging_dir.exists() {
+		fs::remove_dir_all(&staging_dir).map_err(|e| {
+			FileIOError::from((
+				&staging_dir,
+				e,
+				"Failed to remove leftover staging directory",
+			))
+		})?;
+	}
+
+	info!(
+		"Migrating data directory from '{}' to '{}'",
+		data_dir.display(),
+		new_dir.display()
+	);
+
+	if let Err(e) = copy_and_verify(&data_dir, &staging_dir) {
+		if let Err(e) = fs::remove_dir_all(&staging_dir) {
+			error!(
+				"Failed to clean up partial data directory copy: {:#?}",
+				FileIOError::from((&staging_dir, e))
+			);
+		}
+
+		return Err(e);
+	}
+
+	// `rename` can't replace a directory on every platform, we already know it's empty
+	if new_dir.exists() {
+		fs::remove_dir(&new_dir).map_err(|e| FileIOError::from((&new_dir, e)))?;
+	}
+
+	fs::rename(&staging_dir, &new_dir).map_err(|e| {
+		FileIOError::from((&new_dir, e, "Failed to move data directory into place"))
+	})?;
+
+	// The data is safe in its new place at this point, a leftover original is just wasted space
+	if let Err(e) = fs::remove_dir_all(&data_dir) {
+		error!(
+			"Failed to remove the old data directory: {:#?}",
+			FileIOError::from((&data_dir, e))
+		);
+	}
+
+	info!("Data directory migrated to '{}'", new_dir.display());
+
+	Ok(())
+}
+
+fn copy_and_verify(from: &Path, to: &Path) -> Result<(), DataDirMigrationError> {
+	fs::create_dir(to).map_err(|e| FileIOError::from((to, e)))?;
+
+	for entry in fs::read_dir(from).map_err(|e| FileIOError::from((from, e)))? {
+		let entry = entry.map_err(|e| FileIOError::from((from, e)))?;
+		let source = entry.path();
+		let target = to.join(entry.file_name());
+
+		let file_type = entry
+			.file_type()
+			.map_err(|e| FileIOError::from((&source, e)))?;
+
+		if file_type.is_dir() {
+			copy_and_verify(&source, &target)?;
+		} else if file_type.is_symlink() {
+			// Symlinks are recreated as-is instead of copying what they point to
+			let link = fs::read_link(&source).map_err(|e| FileIOError::from((&source, e)))?;
+			symlink(&link, &target).map_err(|e| FileIOError::from((&target, e)))?;
+		} else {
+			fs::copy(&source, &target).map_err(|e| FileIOError::from((&source, e)))?;
+
+			if hash_file(&source)? != hash_file(&target)? {
+				return Err(DataDirMigrationError::IntegrityMismatch(source));
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn hash_file(path: &Path) -> Result<blake3::Hash, FileIOError> {
+	let mut hasher = Hasher::new();
+	io::copy(
+		&mut File::open(path).map_err(|e| FileIOError::from((path, e)))?,
+		&mut hasher,
+	)
+	.map_err(|e| FileIOError::from((path, e)))?;
+
+	Ok(hasher.finalize())
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+	std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+	if original.is_dir() {
+		std::os::windows::fs::symlink_dir(original, link)
+	} else {
+		std::os::windows::fs::symlink_file(original, link)
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use tempfile::tempdir;
+
+	fn create_data_dir(path: &Path) {
+		fs::create_dir_all(path.join("libraries")).unwrap();
+		fs::create_dir_all(path.join("thumbnails/ephemeral")).unwrap();
+		fs::write(path.join("node_state.sdconfig"), b"{}").unwrap();
+		fs::write(path.join("libraries/library.db"), b"database").unwrap();
+		fs::write(path.join("thumbnails/ephemeral/thumb.webp"), b"thumbnail").unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_migrate_data_dir() {
+		let root = tempdir().unwrap();
+		let data_dir = root.path().join("old");
+		let new_dir = root.path().join("bigger-drive/spacedrive");
+		create_data_dir(&data_dir);
+
+		migrate_data_dir(&data_dir, &new_dir).await.unwrap();
+
+		assert!(!data_dir.exists());
+		assert_eq!(
+			fs::read(new_dir.join("libraries/library.db")).unwrap(),
+			b"database"
+		);
+		assert_eq!(
+			fs::read(new_dir.join("thumbnails/ephemeral/thumb.webp")).unwrap(),
+			b"thumbnail"
+		);
+		assert!(!root
+			.path()
+			.join("bigger-drive/.spacedrive.migrating")
+			.exists());
+	}
+
+	#[tokio::test]
+	async fn test_migrate_data_dir_failure_keeps_original() {
+		let root = tempdir().unwrap();
+		let data_dir = root.path().join("old");
+		let new_dir = root.path().join("new");
+		create_data_dir(&data_dir);
+		fs::create_dir_all(&new_dir).unwrap();
+		fs::write(new_dir.join("something"), b"else").unwrap();
+
+		assert!(matches!(
+			migrate_data_dir(&data_dir, &new_dir).await,
+			Err(DataDirMigrationError::TargetNotEmpty(_))
+		));
+		assert!(matches!(
+			migrate_data_dir(&data_dir, data_dir.join("nested")).await,
+			Err(DataDirMigrationError::Overlapping(_))
+		));
+
+		assert_eq!(
+			fs::read(data_dir.join("libraries/library.db")).unwrap(),
+			b"database"
+		);
+	}
+}
@@ -0,0 +1,520 @@
+use crate::api::{
+	utils::{InvalidateOperationEvent, SingleInvalidateOperationEvent},
+	CoreEvent,
+};
+
+use std::{
+	collections::{hash_map::Entry, HashMap},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex, PoisonError,
+	},
+	time::Duration,
+};
+
+use async_stream::stream;
+use futures::{
+	future::ready,
+	stream::{select_all, Stream, StreamExt},
+};
+use serde::Serialize;
+use serde_hashkey::Key;
+use specta::Type;
+use tokio::{
+	runtime::Handle,
+	sync::broadcast::{self, error::RecvError},
+	time::sleep,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+/// How many events of each category a subscriber can fall behind before losing events.
+pub const DEFAULT_EVENT_BUS_CAPACITY: usize = 1024;
+
+/// How long identical invalidations are collected before being sent once, so bulk operations
+/// invalidating a query per item don't make clients refetch it for every item.
+pub const INVALIDATION_COALESCING_WINDOW: Duration = Duration::from_millis(30);
+
+/// The kind of a [`CoreEvent`], each one is sent over its own channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventCategory {
+	Thumbnails,
+	Jobs,
+	Invalidation,
+	Readiness,
+	Models,
+	CloudSync,
+}
+
+impl EventCategory {
+	const ALL: [Self; 6] = [
+		Self::Thumbnails,
+		Self::Jobs,
+		Self::Invalidation,
+		Self::Readiness,
+		Self::Models,
+		Self::CloudSync,
+	];
+
+	fn of(event: &CoreEvent) -> Self {
+		match event {
+			CoreEvent::NewThumbnail { .. } => Self::Thumbnails,
+			CoreEvent::JobProgress(_) => Self::Jobs,
+			CoreEvent::InvalidateOperation(_) => Self::Invalidation,
+			CoreEvent::ReadinessChanged(_) => Self::Readiness,
+			CoreEvent::ModelDownloadProgress(_) => Self::Models,
+			CoreEvent::CloudSyncReset(_) => Self::CloudSync,
+		}
+	}
+}
+
+/// Which events a subscriber receives from [`EventBus::subscribe`].
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+	/// Only events of these categories are received, or all of them if empty.
+	pub categories: Vec<EventCategory>,
+	/// Events of other libraries are dropped, events which don't belong to a library always pass.
+	pub library_id: Option<Uuid>,
+}
+
+impl EventFilter {
+	pub fn all() -> Self {
+		Self::default()
+	}
+
+	pub fn categories(categories: impl IntoIterator<Item = EventCategory>) -> Self {
+		Self {
+			categories: categories.into_iter().collect(),
+			library_id: None,
+		}
+	}
+
+	pub fn library(mut self, library_id: Uuid) -> Self {
+		self.library_id = Some(library_id);
+		self
+	}
+
+	fn matches(&self, event: &CoreEvent) -> bool {
+		let Some(library_id) = self.library_id else {
+			return true;
+		};
+
+		let event_library_id = match event {
+			CoreEvent::JobProgress(progress) => Some(progress.library_id),
+			CoreEvent::CloudSyncReset(event) => Some(event.library_id),
+			// Indexed thumbnail keys start with the library id, ephemeral ones don't belong to any library
+			CoreEvent::NewThumbnail { thumb_key } => thumb_key
+				.first()
+				.and_then(|first| Uuid::parse_str(first).ok()),
+			CoreEvent::InvalidateOperation(InvalidateOperationEvent::Single(event)) => {
+				event.library_id()
+			}
+			_ => None,
+		};
+
+		event_library_id.map_or(true, |event_library_id| event_library_id == library_id)
+	}
+}
+
+/// An item of the stream returned by [`EventBus::subscribe`].
+#[derive(Debug, Clone)]
+pub enum BusEvent {
+	Event(CoreEvent),
+	/// The subscriber fell too far behind and `count` events were lost, subscribers that can't
+	/// miss events (like invalidations) must assume anything could have changed.
+	EventsDropped {
+		count: u64,
+	},
+}
+
+/// How often subscribers fall behind, to tune the capacity of the event bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+pub struct EventBusMetrics {
+	pub capacity: u32,
+	/// How many times a subscriber fell behind and lost events.
+	pub lagged: u32,
+	/// How many events were lost in total.
+	pub dropped_events: u32,
+}
+
+#[derive(Debug, Default)]
+struct LagCounters {
+	lagged: AtomicU64,
+	dropped_events: AtomicU64,
+}
+
+/// The node's event bus.
+///
+/// Every [`EventCategory`] has its own channel, so a burst of high-frequency events (like
+/// thumbnails while indexing) can't push other events out of a subscriber's buffer, and
+/// subscribers only pay for the categories they asked for.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+	channels: [broadcast::Sender<CoreEvent>; EventCategory::ALL.len()],
+	capacity: usize,
+	lag_counters: Arc<LagCounters>,
+	pending_invalidations: Arc<Mutex<HashMap<Key, SingleInvalidateOperationEvent>>>,
+}
+
+impl Default for EventBus {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl EventBus {
+	pub fn new() -> Self {
+		Self::with_capacity(DEFAULT_EVENT_BUS_CAPACITY)
+	}
+
+	pub fn with_capacity(capacity: usize) -> Self {
+		// `broadcast::channel` panics with a capacity of 0
+		let capacity = capacity.max(1);
+
+		Self {
+			channels: EventCategory::ALL.map(|_| broadcast::channel(capacity).0),
+			capacity,
+			lag_counters: Arc::default(),
+			pending_invalidations: Arc::default(),
+		}
+	}
+
+	pub fn metrics(&self) -> EventBusMetrics {
+		let saturating = |value: u64| u32::try_from(value).unwrap_or(u32::MAX);
+
+		EventBusMetrics {
+			capacity: saturating(self.capacity as u64),
+			lagged: saturating(self.lag_counters.lagged.load(Ordering::Relaxed)),
+			dropped_events: saturating(self.lag_counters.dropped_events.load(Ordering::Relaxed)),
+		}
+	}
+
+	/// Sends the event to everyone subscribed to its category, not having subscribers is fine as
+	/// the frontend may not be connected.
+	///
+	/// Invalidations are coalesced: the first one of a query starts a
+	/// [`INVALIDATION_COALESCING_WINDOW`] at the end of which it's sent once, with the arguments
+	/// and result of the last one received. [`InvalidateOperationEvent::all`] is sent right away.
+	pub fn send(&self, event: CoreEvent) {
+		match event {
+			CoreEvent::InvalidateOperation(InvalidateOperationEvent::Single(event)) => {
+				self.send_coalesced(event)
+			}
+			event => self.send_now(event),
+		}
+	}
+
+	fn send_now(&self, event: CoreEvent) {
+		self.channels[EventCategory::of(&event) as usize]
+			.send(event)
+			.ok();
+	}
+
+	fn send_coalesced(&self, event: SingleInvalidateOperationEvent) {
+		// Without a runtime there's nothing to send the batch later, so nothing is coalesced
+		let Ok(runtime) = Handle::try_current() else {
+			return self.send_now(CoreEvent::InvalidateOperation(
+				InvalidateOperationEvent::Single(event),
+			));
+		};
+
+		let key = match event.coalescing_key() {
+			Ok(key) => key,
+			Err(e) => {
+				warn!("Failed to derive key to coalesce invalidate operation '{event:?}': {e:#?}");
+				return self.send_now(CoreEvent::InvalidateOperation(
+					InvalidateOperationEvent::Single(event),
+				));
+			}
+		};
+
+		let mut pending_invalidations = self
+			.pending_invalidations
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+
+		match pending_invalidations.entry(key) {
+			// Already waiting to be sent, newer data replaces older data
+			Entry::Occupied(mut entry) => {
+				entry.insert(event);
+			}
+			Entry::Vacant(entry) => {
+				let key = entry.key().clone();
+				entry.insert(event);
+
+				let bus = self.clone();
+				runtime.spawn(async move {
+					sleep(INVALIDATION_COALESCING_WINDOW).await;
+
+					let event = bus
+						.pending_invalidations
+						.lock()
+						.unwrap_or_else(PoisonError::into_inner)
+						.remove(&key);
+
+					if let Some(event) = event {
+						bus.send_now(CoreEvent::InvalidateOperation(
+							InvalidateOperationEvent::Single(event),
+						));
+					}
+				});
+			}
+		}
+	}
+
+	/// Events are filtered before reaching the subscriber. They're in order within a category,
+	/// but not across categories.
+	pub fn subscribe(
+		&self,
+		filter: EventFilter,
+	) -> impl Stream<Item = BusEvent> + Unpin + Send + 'static {
+		let categories = if filter.categories.is_empty() {
+			EventCategory::ALL.to_vec()
+		} else {
+			filter.categories.clone()
+		};
+
+		select_all(categories.into_iter().map(|category| {
+			let mut rx = self.channels[category as usize].subscribe();
+			let capacity = self.capacity;
+			let lag_counters = Arc::clone(&self.lag_counters);
+
+			Box::pin(stream! {
+				loop {
+					match rx.recv().await {
+						Ok(event) => yield BusEvent::Event(event),
+						Err(RecvError::Lagged(count)) => {
+							warn!(
+								"Event bus subscriber fell behind and lost {count} {category:?} events, \
+								the capacity of {capacity} may be too small"
+							);
+							lag_counters.lagged.fetch_add(1, Ordering::Relaxed);
+							lag_counters.dropped_events.fetch_add(count, Ordering::Relaxed);
+
+							yield BusEvent::EventsDropped { count };
+						}
+						Err(RecvError::Closed) => break,
+					}
+				}
+			})
+		}))
+		.filter(move |event| {
+			ready(match event {
+				BusEvent::Event(event) => filter.matches(event),
+				BusEvent::EventsDropped { .. } => true,
+			})
+		})
+	}
+
+	/// Like [`EventBus::subscribe`], but lost events are replaced with a single
+	/// [`InvalidateOperationEvent::all`], so the client does a full resync instead of being left
+	/// with stale data.
+	pub fn subscribe_resyncing(
+		&self,
+		filter: EventFilter,
+	) -> impl Stream<Item = CoreEvent> + Unpin + Send + 'static {
+		let mut events = self.subscribe(filter);
+
+		Box::pin(stream! {
+			let mut resyncing = false;
+
+			while let Some(event) = events.next().await {
+				match event {
+					BusEvent::Event(event) => {
+						resyncing = false;
+						yield event;
+					}
+					// Events lost back to back are covered by the same resync
+					BusEvent::EventsDropped { .. } if resyncing => {}
+					BusEvent::EventsDropped { .. } => {
+						resyncing = true;
+						yield CoreEvent::InvalidateOperation(InvalidateOperationEvent::all());
+					}
+				}
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::panic)]
+mod tests {
+	use super::*;
+
+	use crate::job::JobProgressEvent;
+
+	use std::time::Duration;
+
+	use chrono::Utc;
+	use serde_json::Value;
+	use tokio::time::timeout;
+
+	fn invalidation(arg: u32) -> CoreEvent {
+		CoreEvent::InvalidateOperation(InvalidateOperationEvent::dangerously_create(
+			"test",
+			arg.into(),
+			None,
+		))
+	}
+
+	fn job_progress(library_id: Uuid) -> CoreEvent {
+		CoreEvent::JobProgress(JobProgressEvent {
+			id: Uuid::new_v4(),
+			library_id,
+			task_count: 1,
+			completed_task_count: 0,
+			phase: String::new(),
+			message: String::new(),
+			estimated_completion: Utc::now(),
+		})
+	}
+
+	async fn next(events: &mut (impl Stream<Item = BusEvent> + Unpin)) -> Option<BusEvent> {
+		timeout(Duration::from_millis(100), events.next())
+			.await
+			.ok()
+			.flatten()
+	}
+
+	#[tokio::test]
+	async fn test_slow_subscriber_does_not_make_others_miss_invalidations() {
+		let bus = EventBus::with_capacity(64);
+		let mut invalidations =
+			bus.subscribe(EventFilter::categories([EventCategory::Invalidation]));
+		// Subscribed to everything but never read until the end
+		let mut slow = bus.subscribe(EventFilter::all());
+
+		let mut sent = 0;
+		for i in 0..(64 * 10) {
+			bus.send(CoreEvent::NewThumbnail {
+				thumb_key: vec!["ephemeral".to_string(), i.to_string()],
+			});
+
+			if i % 10 == 0 {
+				bus.send(invalidation(i));
+				sent += 1;
+			}
+		}
+
+		let mut received = 0;
+		while let Some(event) = next(&mut invalidations).await {
+			match event {
+				BusEvent::Event(CoreEvent::InvalidateOperation(_)) => received += 1,
+				event => panic!("unexpected event: {event:?}"),
+			}
+		}
+		assert_eq!(received, sent);
+
+		// The slow subscriber is told it lost events instead of silently missing them
+		let mut dropped = 0;
+		while let Some(event) = next(&mut slow).await {
+			if let BusEvent::EventsDropped { count } = event {
+				dropped += count;
+			}
+		}
+		assert_eq!(dropped, 64 * 9);
+		assert_eq!(
+			bus.metrics(),
+			EventBusMetrics {
+				capacity: 64,
+				lagged: 1,
+				dropped_events: 64 * 9,
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resyncing_subscriber_gets_a_single_full_invalidation() {
+		let bus = EventBus::with_capacity(4);
+		let mut events = bus.subscribe_resyncing(EventFilter::categories([
+			EventCategory::Thumbnails,
+			EventCategory::Invalidation,
+		]));
+
+		for i in 0..10 {
+			bus.send(invalidation(i));
+			bus.send(CoreEvent::NewThumbnail {
+				thumb_key: vec!["ephemeral".to_string()],
+			});
+		}
+
+		let mut full_invalidations = 0;
+		let mut received = 0;
+		while let Ok(Some(event)) = timeout(Duration::from_millis(100), events.next()).await {
+			match event {
+				CoreEvent::InvalidateOperation(InvalidateOperationEvent::All) => {
+					full_invalidations += 1
+				}
+				_ => received += 1,
+			}
+		}
+
+		// Only the last 4 events of each category are kept
+		assert_eq!(received, 8);
+		assert!((1..=2).contains(&full_invalidations));
+	}
+
+	#[tokio::test]
+	async fn test_identical_invalidations_are_coalesced() {
+		let bus = EventBus::new();
+		let mut events = bus.subscribe(EventFilter::categories([EventCategory::Invalidation]));
+		let library_id = Uuid::new_v4();
+
+		for _ in 0..1000 {
+			bus.send(CoreEvent::InvalidateOperation(
+				InvalidateOperationEvent::dangerously_create("test", Value::Null, None)
+					.with_library_id(library_id),
+			));
+		}
+		// Same query in another library
+		bus.send(CoreEvent::InvalidateOperation(
+			InvalidateOperationEvent::dangerously_create("test", Value::Null, None)
+				.with_library_id(Uuid::new_v4()),
+		));
+		bus.send(CoreEvent::InvalidateOperation(
+			InvalidateOperationEvent::all(),
+		));
+
+		// Full invalidations aren't delayed
+		assert!(matches!(
+			next(&mut events).await,
+			Some(BusEvent::Event(CoreEvent::InvalidateOperation(
+				InvalidateOperationEvent::All
+			)))
+		));
+
+		let mut received = HashMap::<_, u32>::new();
+		while let Some(event) = next(&mut events).await {
+			match event {
+				BusEvent::Event(CoreEvent::InvalidateOperation(
+					InvalidateOperationEvent::Single(event),
+				)) => *received.entry(event.library_id()).or_default() += 1,
+				event => panic!("unexpected event: {event:?}"),
+			}
+		}
+
+		assert_eq!(received.len(), 2);
+		// The burst could straddle two windows on a slow machine
+		assert!((1..=2).contains(&received[&Some(library_id)]));
+	}
+
+	#[tokio::test]
+	async fn test_filter_by_library() {
+		let bus = EventBus::new();
+		let library_id = Uuid::new_v4();
+		let mut events = bus.subscribe(EventFilter::all().library(library_id));
+
+		bus.send(job_progress(Uuid::new_v4()));
+		bus.send(job_progress(library_id));
+		bus.send(CoreEvent::InvalidateOperation(
+			InvalidateOperationEvent::dangerously_create("test", Value::Null, None)
+				.with_library_id(Uuid::new_v4()),
+		));
+
+		assert!(matches!(
+			next(&mut events).await,
+			Some(BusEvent::Event(CoreEvent::JobProgress(progress))) if progress.library_id == library_id
+		));
+		assert!(next(&mut events).await.is_none());
+	}
+}
@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use once_cell::sync::OnceCell;
+use thiserror::Error;
+use tracing::info;
+use tracing_subscriber::{filter::ParseError, reload, EnvFilter, Registry};
+
+/// Set by [`Node::init_logger`](crate::Node::init_logger), nodes created without it can't change
+/// their log filter.
+static LOG_FILTER: OnceCell<LogFilter> = OnceCell::new();
+
+#[derive(Error, Debug)]
+pub enum LogFilterError {
+	#[error("the logger wasn't initialized by the core")]
+	NotInitialized,
+	#[error("invalid log directive: {0}")]
+	InvalidDirective(#[from] ParseError),
+	#[error("failed to reload the log filter: {0}")]
+	Reload(#[from] reload::Error),
+}
+
+impl From<LogFilterError> for rspc::Error {
+	fn from(e: LogFilterError) -> Self {
+		let code = match e {
+			LogFilterError::InvalidDirective(_) => rspc::ErrorCode::BadRequest,
+			_ => rspc::ErrorCode::InternalServerError,
+		};
+
+		Self::with_cause(code, e.to_string(), e)
+	}
+}
+
+/// Changes which logs are written while running, with the same directives as `RUST_LOG`.
+#[derive(Clone)]
+pub struct LogFilter {
+	handle: reload::Handle<EnvFilter, Registry>,
+	directive: Arc<Mutex<String>>,
+}
+
+impl LogFilter {
+	pub(crate) fn register(handle: reload::Handle<EnvFilter, Registry>, directive: String) {
+		LOG_FILTER
+			.set(Self {
+				handle,
+				directive: Arc::new(Mutex::new(directive)),
+			})
+			.ok();
+	}
+
+	pub(crate) fn get() -> Option<Self> {
+		LOG_FILTER.get().cloned()
+	}
+
+	/// Applies a new directive, like `sd_core=trace`, returning the one it replaced.
+	pub fn set(&self, directive: String) -> Result<String, LogFilterError> {
+		let filter = EnvFilter::try_new(&directive)?;
+
+		let mut current = self
+			.directive
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		self.handle.reload(filter)?;
+
+		info!("Log filter changed from '{current}' to '{directive}'");
+
+		Ok(std::mem::replace(&mut *current, directive))
+	}
+}
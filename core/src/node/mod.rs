@@ -1,6 +1,18 @@
 pub mod config;
+mod data_dir;
+mod event_bus;
 mod hardware;
+mod log_filter;
+mod network_usage;
+pub mod open_with;
 mod platform;
+pub mod readiness;
+mod shutdown;
 
+pub use data_dir::*;
+pub use event_bus::*;
 pub use hardware::*;
+pub use log_filter::*;
+pub use network_usage::*;
 pub use platform::*;
+pub use shutdown::*;
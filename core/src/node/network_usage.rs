@@ -0,0 +1,390 @@
+use super::config::NodePreferences;
+
+use sd_utils::error::FileIOError;
+
+use std::{
+	collections::BTreeMap,
+	io,
+	path::{Path, PathBuf},
+	pin::Pin,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Mutex, PoisonError,
+	},
+	task::{Context, Poll},
+	time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use thiserror::Error;
+use tokio::{
+	fs,
+	io::{AsyncRead, AsyncWrite, ReadBuf},
+	spawn,
+	sync::watch,
+	time::{interval, MissedTickBehavior},
+};
+use tracing::error;
+
+const NETWORK_USAGE_FILE: &str = "network_usage.json";
+/// Days older than this are forgotten.
+const RETENTION_DAYS: i64 = 90;
+/// How often the counters are written to disk, they're also written on shutdown.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum NetworkUsageError {
+	#[error(transparent)]
+	FileIO(#[from] FileIOError),
+	#[error("failed to (de)serialize network usage: {0}")]
+	Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default, Type)]
+pub struct NetworkPreferences {
+	/// Pauses sending to the cloud and files over P2P, Spacedrops started by the user still go.
+	metered_connection: bool,
+}
+
+impl NetworkPreferences {
+	pub fn metered_connection(&self) -> bool {
+		self.metered_connection
+	}
+
+	pub fn set_metered_connection(&mut self, metered_connection: bool) -> &mut Self {
+		self.metered_connection = metered_connection;
+
+		self
+	}
+}
+
+/// What the data moved over the network was for.
+#[derive(
+	Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Type,
+)]
+pub enum NetworkUsageCategory {
+	CloudSync,
+	P2PSync,
+	Spacedrop,
+	FilesOverP2P,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+struct Traffic {
+	sent: u64,
+	received: u64,
+}
+
+#[derive(Serialize, Type, Debug, PartialEq, Eq)]
+pub struct DailyNetworkUsage {
+	/// The UTC day, as `YYYY-MM-DD`.
+	pub date: String,
+	pub category: NetworkUsageCategory,
+	pub sent: String,
+	pub received: String,
+}
+
+type Days = BTreeMap<NaiveDate, BTreeMap<NetworkUsageCategory, Traffic>>;
+
+/// Bytes moved over the network per UTC day and category, kept across restarts.
+pub struct NetworkUsage {
+	path: PathBuf,
+	days: Mutex<Days>,
+	dirty: AtomicBool,
+	preferences_rx: watch::Receiver<NodePreferences>,
+}
+
+impl NetworkUsage {
+	pub(crate) async fn load(
+		data_dir: impl AsRef<Path>,
+		preferences_rx: watch::Receiver<NodePreferences>,
+	) -> Arc<Self> {
+		let path = data_dir.as_ref().join(NETWORK_USAGE_FILE);
+
+		let days = match fs::read(&path).await {
+			Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+				error!("Failed to read network usage, starting over: {e:#?}");
+				Days::new()
+			}),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Days::new(),
+			Err(e) => {
+				error!(
+					"Failed to read network usage, starting over: {:#?}",
+					FileIOError::from((&path, e))
+				);
+				Days::new()
+			}
+		};
+
+		let this = Arc::new(Self {
+			path,
+			days: Mutex::new(days),
+			dirty: AtomicBool::new(false),
+			preferences_rx,
+		});
+
+		spawn({
+			let this = Arc::downgrade(&this);
+
+			async move {
+				let mut interval = interval(SAVE_INTERVAL);
+				interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+				// The first tick is right away
+				interval.tick().await;
+
+				loop {
+					interval.tick().await;
+
+					let Some(this) = this.upgrade() else {
+						break;
+					};
+
+					if let Err(e) = this.save().await {
+						error!("Failed to save network usage: {e:#?}");
+					}
+				}
+			}
+		});
+
+		this
+	}
+
+	/// Whether the user is on a connection they pay by the byte, so what can wait should.
+	pub fn is_metered(&self) -> bool {
+		self.preferences_rx.borrow().network.metered_connection()
+	}
+
+	pub fn record_sent(&self, category: NetworkUsageCategory, bytes: u64) {
+		self.record(category, Utc::now(), bytes, 0);
+	}
+
+	pub fn record_received(&self, category: NetworkUsageCategory, bytes: u64) {
+		self.record(category, Utc::now(), 0, bytes);
+	}
+
+	fn record(&self, category: NetworkUsageCategory, at: DateTime<Utc>, sent: u64, received: u64) {
+		if sent == 0 && received == 0 {
+			return;
+		}
+
+		let today = at.date_naive();
+
+		let mut days = self.days.lock().unwrap_or_else(PoisonError::into_inner);
+
+		let traffic = days.entry(today).or_default().entry(category).or_default();
+		traffic.sent = traffic.sent.saturating_add(sent);
+		traffic.received = traffic.received.saturating_add(received);
+
+		// Only a new day can push the oldest out
+		if days.len() as i64 > RETENTION_DAYS {
+			let oldest_kept = today - chrono::Duration::days(RETENTION_DAYS - 1);
+			days.retain(|day, _| *day >= oldest_kept);
+		}
+
+		self.dirty.store(true, Ordering::Relaxed);
+	}
+
+	/// The usage of each category on each UTC day from `from` to `to`, both included.
+	pub fn usage(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<DailyNetworkUsage> {
+		let (from, to) = (from.date_naive(), to.date_naive());
+		if from > to {
+			return vec![];
+		}
+
+		self.days
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.range(from..=to)
+			.flat_map(|(day, categories)| {
+				categories
+					.iter()
+					.map(|(category, traffic)| DailyNetworkUsage {
+						date: day.format("%Y-%m-%d").to_string(),
+						category: *category,
+						sent: traffic.sent.to_string(),
+						received: traffic.received.to_string(),
+					})
+			})
+			.collect()
+	}
+
+	/// Wraps a stream so the bytes going through it are counted under `category`.
+	pub fn count<S>(self: &Arc<Self>, stream: S, category: NetworkUsageCategory) -> Counted<S> {
+		Counted {
+			inner: stream,
+			usage: Arc::clone(self),
+			category,
+		}
+	}
+
+	async fn save(&self) -> Result<(), NetworkUsageError> {
+		if !self.dirty.swap(false, Ordering::Relaxed) {
+			return Ok(());
+		}
+
+		let bytes = serde_json::to_vec(&*self.days.lock().unwrap_or_else(PoisonError::into_inner))?;
+
+		if let Err(e) = fs::write(&self.path, bytes).await {
+			// So it's tried again on the next save
+			self.dirty.store(true, Ordering::Relaxed);
+
+			return Err(FileIOError::from((&self.path, e)).into());
+		}
+
+		Ok(())
+	}
+
+	pub(crate) async fn shutdown(&self) {
+		if let Err(e) = self.save().await {
+			error!("Failed to save network usage on shutdown: {e:#?}");
+		}
+	}
+}
+
+/// A stream whose traffic is recorded in the [`NetworkUsage`] as it goes, see
+/// [`NetworkUsage::count`].
+pub struct Counted<S> {
+	inner: S,
+	usage: Arc<NetworkUsage>,
+	category: NetworkUsageCategory,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Counted<S> {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		let filled = buf.filled().len();
+
+		let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+		if let Poll::Ready(Ok(())) = res {
+			self.usage
+				.record_received(self.category, (buf.filled().len() - filled) as u64);
+		}
+
+		res
+	}
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Counted<S> {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+		if let Poll::Ready(Ok(written)) = res {
+			self.usage.record_sent(self.category, written as u64);
+		}
+
+		res
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.inner).poll_flush(cx)
+	}
+
+	fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.inner).poll_shutdown(cx)
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use chrono::TimeZone;
+	use tempfile::tempdir;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	fn preferences_rx() -> watch::Receiver<NodePreferences> {
+		watch::channel(NodePreferences::default()).1
+	}
+
+	#[tokio::test]
+	async fn test_usage_resets_at_utc_day_boundary() {
+		let data_dir = tempdir().unwrap();
+		let usage = NetworkUsage::load(data_dir.path(), preferences_rx()).await;
+
+		let before_midnight = Utc.with_ymd_and_hms(2024, 1, 30, 23, 59, 59).unwrap();
+		let after_midnight = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 1).unwrap();
+
+		usage.record(NetworkUsageCategory::CloudSync, before_midnight, 100, 10);
+		usage.record(NetworkUsageCategory::CloudSync, before_midnight, 50, 0);
+		usage.record(NetworkUsageCategory::Spacedrop, after_midnight, 0, 7);
+
+		assert_eq!(
+			usage.usage(before_midnight, after_midnight),
+			vec![
+				DailyNetworkUsage {
+					date: "2024-01-30".to_string(),
+					category: NetworkUsageCategory::CloudSync,
+					sent: "150".to_string(),
+					received: "10".to_string(),
+				},
+				DailyNetworkUsage {
+					date: "2024-01-31".to_string(),
+					category: NetworkUsageCategory::Spacedrop,
+					sent: "0".to_string(),
+					received: "7".to_string(),
+				},
+			]
+		);
+		assert_eq!(usage.usage(after_midnight, after_midnight).len(), 1);
+		assert!(usage.usage(after_midnight, before_midnight).is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_usage_survives_restart() {
+		let data_dir = tempdir().unwrap();
+		let day = Utc.with_ymd_and_hms(2024, 1, 30, 12, 0, 0).unwrap();
+
+		let usage = NetworkUsage::load(data_dir.path(), preferences_rx()).await;
+		usage.record(NetworkUsageCategory::P2PSync, day, 42, 24);
+		usage.shutdown().await;
+		drop(usage);
+
+		let usage = NetworkUsage::load(data_dir.path(), preferences_rx()).await;
+		assert_eq!(
+			usage.usage(day, day),
+			vec![DailyNetworkUsage {
+				date: "2024-01-30".to_string(),
+				category: NetworkUsageCategory::P2PSync,
+				sent: "42".to_string(),
+				received: "24".to_string(),
+			}]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_counted_stream() {
+		let data_dir = tempdir().unwrap();
+		let usage = NetworkUsage::load(data_dir.path(), preferences_rx()).await;
+
+		let (local, mut remote) = tokio::io::duplex(64);
+		let mut local = usage.count(local, NetworkUsageCategory::FilesOverP2P);
+
+		local.write_all(b"hello").await.unwrap();
+		remote.write_all(b"hi").await.unwrap();
+
+		let mut buf = [0; 2];
+		local.read_exact(&mut buf).await.unwrap();
+
+		let now = Utc::now();
+		assert_eq!(
+			usage.usage(now, now),
+			vec![DailyNetworkUsage {
+				date: now.format("%Y-%m-%d").to_string(),
+				category: NetworkUsageCategory::FilesOverP2P,
+				sent: "5".to_string(),
+				received: "2".to_string(),
+			}]
+		);
+	}
+}
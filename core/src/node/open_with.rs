@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use rspc::ErrorCode;
+use serde::Serialize;
+use specta::Type;
+use thiserror::Error;
+
+/// An application which can open a file.
+#[derive(Serialize, Type, Debug, Clone)]
+pub struct OpenWithApplication {
+	/// Opaque id of the application, to be passed back to `files.openWith`.
+	pub handler_id: String,
+	pub name: String,
+	/// Platform specific hint for the frontend to find the application's icon, like the app bundle on macOS.
+	pub icon_hint: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum OpenWithError {
+	#[error("opening files is not supported on this platform")]
+	NotSupported,
+	#[error("no application found with id '{0}'")]
+	HandlerNotFound(String),
+	#[error("the sandbox doesn't allow opening '{}'", .0.display())]
+	Sandboxed(PathBuf),
+	#[error("failed to open '{}': {1}", .0.display())]
+	Failed(PathBuf, String),
+}
+
+impl From<OpenWithError> for rspc::Error {
+	fn from(err: OpenWithError) -> Self {
+		let code = match err {
+			OpenWithError::NotSupported => ErrorCode::MethodNotSupported,
+			OpenWithError::HandlerNotFound(_) => ErrorCode::NotFound,
+			OpenWithError::Sandboxed(_) => ErrorCode::Forbidden,
+			OpenWithError::Failed(_, _) => ErrorCode::InternalServerError,
+		};
+
+		rspc::Error::with_cause(code, err.to_string(), err)
+	}
+}
+
+/// Opens files with the OS' applications.
+///
+/// This needs platform APIs the core doesn't link to, so it's implemented by the host app and
+/// passed in through [`Env`](crate::Env). Headless hosts can leave it out.
+#[async_trait]
+pub trait OpenWith: Send + Sync {
+	/// The applications which can open the file.
+	async fn applications(&self, path: &Path) -> Result<Vec<OpenWithApplication>, OpenWithError>;
+
+	async fn open_with(&self, path: &Path, handler_id: &str) -> Result<(), OpenWithError>;
+
+	/// Open the file with the OS' default application for it.
+	async fn open_default(&self, path: &Path) -> Result<(), OpenWithError>;
+
+	/// Show the file selected in the OS' file manager.
+	async fn reveal(&self, path: &Path) -> Result<(), OpenWithError>;
+}
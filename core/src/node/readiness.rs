@@ -0,0 +1,146 @@
+use crate::{api::CoreEvent, node::EventBus};
+
+use std::fmt;
+
+use serde::Serialize;
+use specta::Type;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// The state of a single subsystem during startup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Type)]
+#[serde(tag = "status", content = "error")]
+pub enum SubsystemStatus {
+	#[default]
+	Pending,
+	Ready,
+	Failed(String),
+}
+
+/// The subsystems which are initialised by `Node::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+	Config,
+	Thumbnailer,
+	P2P,
+	Libraries,
+}
+
+impl fmt::Display for Subsystem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Config => "config",
+			Self::Thumbnailer => "thumbnailer",
+			Self::P2P => "p2p",
+			Self::Libraries => "libraries",
+		})
+	}
+}
+
+/// Which parts of the core are up. A host app can use this to show a splash screen until the core is ready.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Type)]
+pub struct Readiness {
+	pub config: SubsystemStatus,
+	pub thumbnailer: SubsystemStatus,
+	pub p2p: SubsystemStatus,
+	pub libraries: SubsystemStatus,
+}
+
+impl Readiness {
+	pub fn is_ready(&self) -> bool {
+		[&self.config, &self.thumbnailer, &self.p2p, &self.libraries]
+			.into_iter()
+			.all(|status| *status == SubsystemStatus::Ready)
+	}
+
+	/// The first subsystem which failed to initialise, if any.
+	pub fn failed(&self) -> Option<(Subsystem, &str)> {
+		[
+			(Subsystem::Config, &self.config),
+			(Subsystem::Thumbnailer, &self.thumbnailer),
+			(Subsystem::P2P, &self.p2p),
+			(Subsystem::Libraries, &self.libraries),
+		]
+		.into_iter()
+		.find_map(|(subsystem, status)| match status {
+			SubsystemStatus::Failed(err) => Some((subsystem, err.as_str())),
+			_ => None,
+		})
+	}
+
+	fn get_mut(&mut self, subsystem: Subsystem) -> &mut SubsystemStatus {
+		match subsystem {
+			Subsystem::Config => &mut self.config,
+			Subsystem::Thumbnailer => &mut self.thumbnailer,
+			Subsystem::P2P => &mut self.p2p,
+			Subsystem::Libraries => &mut self.libraries,
+		}
+	}
+}
+
+/// Tracks the [`Readiness`] of the core as it starts up.
+///
+/// This lives on [`Env`](crate::Env) so a host app can subscribe to it before `Node::new` returns.
+#[derive(Debug)]
+pub struct ReadinessTracker(watch::Sender<Readiness>);
+
+impl Default for ReadinessTracker {
+	fn default() -> Self {
+		Self(watch::channel(Readiness::default()).0)
+	}
+}
+
+impl ReadinessTracker {
+	pub fn get(&self) -> Readiness {
+		self.0.borrow().clone()
+	}
+
+	pub fn subscribe(&self) -> watch::Receiver<Readiness> {
+		self.0.subscribe()
+	}
+
+	pub(crate) fn set(&self, subsystem: Subsystem, status: SubsystemStatus, event_bus: &EventBus) {
+		match &status {
+			SubsystemStatus::Failed(err) => warn!("Subsystem '{subsystem}' failed to start: {err}"),
+			_ => info!("Subsystem '{subsystem}' is {status:?}"),
+		}
+
+		self.0
+			.send_modify(|readiness| *readiness.get_mut(subsystem) = status);
+
+		event_bus.send(CoreEvent::ReadinessChanged(self.get()));
+	}
+
+	/// Mark the subsystem as ready or failed depending on the result of it's initialisation.
+	pub(crate) fn track<T, E: fmt::Display>(
+		&self,
+		subsystem: Subsystem,
+		result: Result<T, E>,
+		event_bus: &EventBus,
+	) -> Result<T, E> {
+		let result = self.track_failure(subsystem, result, event_bus);
+		if result.is_ok() {
+			self.set(subsystem, SubsystemStatus::Ready, event_bus);
+		}
+
+		result
+	}
+
+	/// Mark the subsystem as failed if an intermediate step of it's initialisation fails.
+	pub(crate) fn track_failure<T, E: fmt::Display>(
+		&self,
+		subsystem: Subsystem,
+		result: Result<T, E>,
+		event_bus: &EventBus,
+	) -> Result<T, E> {
+		if let Err(err) = &result {
+			self.set(
+				subsystem,
+				SubsystemStatus::Failed(err.to_string()),
+				event_bus,
+			);
+		}
+
+		result
+	}
+}
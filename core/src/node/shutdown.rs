@@ -0,0 +1,41 @@
+use std::{future::Future, time::Duration};
+
+use tokio::time::{timeout, Instant};
+use tracing::{error, info};
+
+/// What happened to each subsystem while the [`Node`](crate::Node) was shutting down.
+///
+/// Abandoned subsystems may still have tasks running, so the host app might want to force-exit
+/// instead of waiting for the runtime to wind down.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct ShutdownReport {
+	/// Subsystems which shut down within the timeout.
+	pub clean: Vec<&'static str>,
+	/// Subsystems which didn't shut down within the timeout and were left behind.
+	pub abandoned: Vec<&'static str>,
+}
+
+impl ShutdownReport {
+	pub fn is_clean(&self) -> bool {
+		self.abandoned.is_empty()
+	}
+
+	/// Wait for the subsystem to shut down, giving up on it after `limit`.
+	pub(crate) async fn shutdown(
+		&mut self,
+		subsystem: &'static str,
+		limit: Duration,
+		fut: impl Future<Output = ()>,
+	) {
+		let start = Instant::now();
+
+		if timeout(limit, fut).await.is_ok() {
+			info!("Subsystem '{subsystem}' shut down in {:?}", start.elapsed());
+			self.clean.push(subsystem);
+		} else {
+			error!("Subsystem '{subsystem}' didn't shut down within {limit:?}, abandoning it");
+			self.abandoned.push(subsystem);
+		}
+	}
+}
@@ -20,6 +20,15 @@ const_assert!((HEADER_OR_FOOTER_SIZE * 2 + SAMPLE_COUNT * SAMPLE_SIZE) < MINIMUM
 // Asserting that the sample size is larger than header/footer size, as the same buffer is used for both
 const_assert!(SAMPLE_SIZE > HEADER_OR_FOOTER_SIZE);
 
+/// How many bytes [`generate_cas_id`] reads from a file of `size` bytes.
+pub const fn cas_id_bytes_read(size: u64) -> u64 {
+	if size <= MINIMUM_FILE_SIZE {
+		size
+	} else {
+		HEADER_OR_FOOTER_SIZE * 2 + SAMPLE_COUNT * SAMPLE_SIZE
+	}
+}
+
 pub async fn generate_cas_id(path: impl AsRef<Path>, size: u64) -> Result<String, io::Error> {
 	let mut hasher = Hasher::new();
 	hasher.update(&size.to_le_bytes());
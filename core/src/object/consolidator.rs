@@ -0,0 +1,632 @@
+use crate::{
+	invalidate_query,
+	job::{
+		CurrentStep, JobError, JobInitOutput, JobResult, JobRunMetadata, JobStepOutput,
+		StatefulJob, WorkerContext,
+	},
+	library::Library,
+	object::{
+		file_identifier::{connect_file_path_to_object, is_same_content},
+		orphan_remover::remove_orphan_objects,
+	},
+};
+
+use sd_prisma::{
+	prisma::{file_path, location, object, tag_on_object, PrismaClient, SortOrder},
+	prisma_sync,
+};
+use sd_sync::OperationFactory;
+use sd_utils::from_bytes_to_uuid;
+
+use std::hash::{Hash, Hasher};
+
+use chrono::Utc;
+use itertools::Itertools;
+use prisma_client_rust::{raw, PrismaValue, QueryError};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+const BATCH_SIZE: usize = 100;
+
+// Tables which only link objects to something else, so the links of a merged object can be moved
+const OBJECT_LINK_TABLES: [&str; 3] = ["label_on_object", "object_in_space", "object_in_album"];
+
+object::select!(object_to_consolidate {
+	id
+	pub_id
+	hidden
+	favorite
+	important
+	note
+	file_paths: select { pub_id cas_id integrity_checksum }
+	tags: select { tag: select { id pub_id } }
+});
+
+/// Reconciles objects after their files were reidentified: objects which ended up sharing a
+/// `cas_id` are merged into the oldest one, and objects left without any file path are removed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ObjectConsolidatorJobInit {
+	/// Only merges the objects of files in this location, orphaned objects are always removed
+	/// library wide as they don't belong to any location anymore.
+	pub location_id: Option<location::id::Type>,
+}
+
+impl Hash for ObjectConsolidatorJobInit {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		if let Some(location_id) = self.location_id {
+			location_id.hash(state);
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ObjectConsolidatorJobStep {
+	Merge(Vec<String>),
+	RemoveOrphans,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct ObjectConsolidatorJobRunMetadata {
+	objects_merged: usize,
+	orphans_removed: usize,
+}
+
+impl JobRunMetadata for ObjectConsolidatorJobRunMetadata {
+	fn update(&mut self, new_data: Self) {
+		self.objects_merged += new_data.objects_merged;
+		self.orphans_removed += new_data.orphans_removed;
+	}
+}
+
+#[async_trait::async_trait]
+impl StatefulJob for ObjectConsolidatorJobInit {
+	type Data = ();
+	type Step = ObjectConsolidatorJobStep;
+	type RunMetadata = ObjectConsolidatorJobRunMetadata;
+
+	const NAME: &'static str = "object_consolidator";
+
+	fn target_location(&self) -> location::id::Type {
+		// Library wide runs don't target any location, and no location has the id 0
+		self.location_id.unwrap_or_default()
+	}
+
+	async fn init(
+		&self,
+		ctx: &WorkerContext,
+		data: &mut Option<Self::Data>,
+	) -> Result<JobInitOutput<Self::RunMetadata, Self::Step>, JobError> {
+		let init = self;
+		let Library { db, .. } = &*ctx.library;
+
+		let cas_ids = find_shared_cas_ids(db, init.location_id).await?;
+
+		debug!(
+			"Found {} cas_ids shared by more than one object",
+			cas_ids.len()
+		);
+
+		let steps = cas_ids
+			.into_iter()
+			.chunks(BATCH_SIZE)
+			.into_iter()
+			.map(|chunk| ObjectConsolidatorJobStep::Merge(chunk.collect()))
+			// Merging leaves the duplicates without file paths, so orphans are removed last
+			.chain([ObjectConsolidatorJobStep::RemoveOrphans])
+			.collect::<Vec<_>>();
+
+		// Must fill in the data, otherwise the job will not run
+		*data = Some(());
+
+		Ok(steps.into())
+	}
+
+	async fn execute_step(
+		&self,
+		ctx: &WorkerContext,
+		CurrentStep { step, .. }: CurrentStep<'_, Self::Step>,
+		_: &Self::Data,
+		_: &Self::RunMetadata,
+	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
+		let library = &*ctx.library;
+
+		let mut run_metadata = ObjectConsolidatorJobRunMetadata::default();
+
+		match step {
+			ObjectConsolidatorJobStep::Merge(cas_ids) => {
+				for cas_id in cas_ids {
+					run_metadata.objects_merged +=
+						merge_objects_with_cas_id(library, cas_id).await?;
+				}
+			}
+			ObjectConsolidatorJobStep::RemoveOrphans => {
+				run_metadata.orphans_removed =
+					remove_orphan_objects(library).await?.removed as usize;
+			}
+		}
+
+		Ok(run_metadata.into())
+	}
+
+	async fn finalize(
+		&self,
+		ctx: &WorkerContext,
+		_: &Option<Self::Data>,
+		run_metadata: &Self::RunMetadata,
+	) -> JobResult {
+		let init = self;
+
+		info!(
+			"finalizing object consolidator job: {} objects merged, {} orphaned objects removed",
+			run_metadata.objects_merged, run_metadata.orphans_removed
+		);
+
+		if run_metadata.objects_merged > 0 || run_metadata.orphans_removed > 0 {
+			invalidate_query!(ctx.library, "search.paths");
+			invalidate_query!(ctx.library, "search.objects");
+		}
+
+		Ok(Some(json!({ "init": init, "run_metadata": run_metadata })))
+	}
+}
+
+async fn find_shared_cas_ids(
+	db: &PrismaClient,
+	location_id: Option<location::id::Type>,
+) -> Result<Vec<String>, QueryError> {
+	#[derive(Deserialize)]
+	struct CasIdRow {
+		cas_id: String,
+	}
+
+	let rows = match location_id {
+		Some(location_id) => {
+			db._query_raw::<CasIdRow>(raw!(
+				"SELECT cas_id FROM file_path
+				WHERE
+					cas_id IS NOT NULL
+					AND object_id IS NOT NULL
+					AND cas_id IN (SELECT cas_id FROM file_path WHERE location_id = {})
+				GROUP BY cas_id
+				HAVING COUNT(DISTINCT object_id) > 1",
+				PrismaValue::Int(location_id as i64)
+			))
+			.exec()
+			.await?
+		}
+		None => {
+			db._query_raw::<CasIdRow>(raw!(
+				"SELECT cas_id FROM file_path
+				WHERE cas_id IS NOT NULL AND object_id IS NOT NULL
+				GROUP BY cas_id
+				HAVING COUNT(DISTINCT object_id) > 1"
+			))
+			.exec()
+			.await?
+		}
+	};
+
+	Ok(rows.into_iter().map(|row| row.cas_id).collect())
+}
+
+/// Merges the objects of the files with this `cas_id` into the oldest one, returning how many
+/// were merged.
+///
+/// An object is left alone if any of its files is known to have a different content, like when
+/// their full checksums differ, as then the `cas_id` only matched by its sampling.
+async fn merge_objects_with_cas_id(library: &Library, cas_id: String) -> Result<usize, QueryError> {
+	let Library { db, .. } = library;
+
+	let mut objects = db
+		.object()
+		.find_many(vec![object::file_paths::some(vec![
+			file_path::cas_id::equals(Some(cas_id)),
+		])])
+		.order_by(object::id::order(SortOrder::Asc))
+		.select(object_to_consolidate::select())
+		.exec()
+		.await?
+		.into_iter();
+
+	let Some(target) = objects.next() else {
+		return Ok(0);
+	};
+
+	let mut merged = 0;
+
+	for duplicate in objects {
+		let same_content = target.file_paths.iter().all(|target_file_path| {
+			duplicate.file_paths.iter().all(|duplicate_file_path| {
+				target_file_path.cas_id.is_none()
+					|| duplicate_file_path.cas_id.is_none()
+					|| is_same_content(
+						(
+							target_file_path.cas_id.as_ref(),
+							target_file_path.integrity_checksum.as_ref(),
+						),
+						(
+							duplicate_file_path.cas_id.as_ref(),
+							duplicate_file_path.integrity_checksum.as_ref(),
+						),
+					)
+			})
+		});
+
+		if !same_content {
+			debug!(
+				"Not merging <Object id='{}'> into <Object id='{}'> as their contents differ",
+				duplicate.id, target.id
+			);
+			continue;
+		}
+
+		merge_object(library, &target, duplicate).await?;
+		merged += 1;
+	}
+
+	Ok(merged)
+}
+
+async fn merge_object(
+	Library { db, sync, .. }: &Library,
+	target: &object_to_consolidate::Data,
+	duplicate: object_to_consolidate::Data,
+) -> Result<(), QueryError> {
+	debug!(
+		"Merging <Object id='{}'> into <Object id='{}'>",
+		duplicate.id, target.id
+	);
+
+	let target_uuid = from_bytes_to_uuid(&target.pub_id);
+
+	let (sync_ops, db_updates): (Vec<_>, Vec<_>) = duplicate
+		.file_paths
+		.iter()
+		.map(|file_path| {
+			connect_file_path_to_object(
+				from_bytes_to_uuid(&file_path.pub_id),
+				target_uuid,
+				sync,
+				db,
+			)
+		})
+		.unzip();
+
+	sync.write_ops(db, (sync_ops, db_updates)).await?;
+
+	if !duplicate.tags.is_empty() {
+		macro_rules! sync_id {
+			($tag_pub_id:expr, $object_pub_id:expr) => {
+				prisma_sync::tag_on_object::SyncId {
+					tag: prisma_sync::tag::SyncId {
+						pub_id: $tag_pub_id.clone(),
+					},
+					object: prisma_sync::object::SyncId {
+						pub_id: $object_pub_id.clone(),
+					},
+				}
+			};
+		}
+
+		let (sync_ops, db_creates): (Vec<_>, Vec<_>) = duplicate
+			.tags
+			.iter()
+			.map(|tag_on_object| {
+				(
+					sync.relation_create(sync_id!(tag_on_object.tag.pub_id, target.pub_id), []),
+					tag_on_object::CreateUnchecked {
+						tag_id: tag_on_object.tag.id,
+						object_id: target.id,
+						_params: vec![tag_on_object::date_created::set(Some(Utc::now().into()))],
+					},
+				)
+			})
+			.unzip();
+
+		sync.write_ops(
+			db,
+			(
+				sync_ops.into_iter().flatten().collect(),
+				db.tag_on_object().create_many(db_creates).skip_duplicates(),
+			),
+		)
+		.await?;
+
+		sync.write_ops(
+			db,
+			(
+				duplicate
+					.tags
+					.iter()
+					.map(|tag_on_object| {
+						sync.relation_delete(sync_id!(tag_on_object.tag.pub_id, duplicate.pub_id))
+					})
+					.collect(),
+				db.tag_on_object()
+					.delete_many(vec![tag_on_object::object_id::equals(duplicate.id)]),
+			),
+		)
+		.await?;
+	}
+
+	// Links the target already has are kept as they are, and the duplicate's ones are dropped
+	for table in OBJECT_LINK_TABLES {
+		db._execute_raw(raw!(
+			&format!("UPDATE OR IGNORE {table} SET object_id = {{}} WHERE object_id = {{}}"),
+			PrismaValue::Int(target.id as i64),
+			PrismaValue::Int(duplicate.id as i64)
+		))
+		.exec()
+		.await?;
+
+		db._execute_raw(raw!(
+			&format!("DELETE FROM {table} WHERE object_id = {{}}"),
+			PrismaValue::Int(duplicate.id as i64)
+		))
+		.exec()
+		.await?;
+	}
+
+	let hidden = merge_flag(target.hidden, duplicate.hidden);
+	let favorite = merge_flag(target.favorite, duplicate.favorite);
+	let important = merge_flag(target.important, duplicate.important);
+	let note = target.note.clone().or(duplicate.note);
+
+	// Only the fields the duplicate changed on the target are written
+	let (sync_params, db_params): (Vec<_>, Vec<_>) = [
+		(hidden != target.hidden).then(|| {
+			(
+				(object::hidden::NAME, json!(hidden)),
+				object::hidden::set(hidden),
+			)
+		}),
+		(favorite != target.favorite).then(|| {
+			(
+				(object::favorite::NAME, json!(favorite)),
+				object::favorite::set(favorite),
+			)
+		}),
+		(important != target.important).then(|| {
+			(
+				(object::important::NAME, json!(important)),
+				object::important::set(important),
+			)
+		}),
+		(note != target.note).then(|| {
+			(
+				(object::note::NAME, json!(note)),
+				object::note::set(note.clone()),
+			)
+		}),
+	]
+	.into_iter()
+	.flatten()
+	.unzip();
+
+	if !db_params.is_empty() {
+		sync.write_ops(
+			db,
+			(
+				sync_params
+					.into_iter()
+					.map(|(field, value)| {
+						sync.shared_update(
+							prisma_sync::object::SyncId {
+								pub_id: target.pub_id.clone(),
+							},
+							field,
+							value,
+						)
+					})
+					.collect(),
+				db.object().update(object::id::equals(target.id), db_params),
+			),
+		)
+		.await?;
+	}
+
+	// Its media data and recent accesses are removed along with it
+	sync.write_ops(
+		db,
+		(
+			vec![sync.shared_delete(prisma_sync::object::SyncId {
+				pub_id: duplicate.pub_id,
+			})],
+			db.object().delete(object::id::equals(duplicate.id)),
+		),
+	)
+	.await?;
+
+	Ok(())
+}
+
+/// A flag stays set if it was set on any of the merged objects.
+fn merge_flag(target: Option<bool>, duplicate: Option<bool>) -> Option<bool> {
+	match (target, duplicate) {
+		(None, None) => None,
+		(target, duplicate) => Some(target.unwrap_or(false) || duplicate.unwrap_or(false)),
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_library;
+
+	use sd_prisma::prisma::tag;
+	use sd_sync::CRDTOperationData;
+	use sd_utils::uuid_to_bytes;
+
+	use uuid::Uuid;
+
+	async fn create_object(
+		db: &PrismaClient,
+		cas_id: &str,
+		params: Vec<object::SetParam>,
+	) -> (object::Data, file_path::Data) {
+		let object = db
+			.object()
+			.create(uuid_to_bytes(Uuid::new_v4()), params)
+			.exec()
+			.await
+			.unwrap();
+
+		let file_path = db
+			.file_path()
+			.create(
+				uuid_to_bytes(Uuid::new_v4()),
+				vec![
+					file_path::cas_id::set(Some(cas_id.to_string())),
+					file_path::object::connect(object::id::equals(object.id)),
+				],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		(object, file_path)
+	}
+
+	#[tokio::test]
+	async fn test_merge_objects_with_cas_id() {
+		let (_data_dir, _node, library) = test_library("Consolidator").await;
+		let db = &library.db;
+
+		let (target, target_file_path) =
+			create_object(db, "cas", vec![object::hidden::set(Some(false))]).await;
+		let (duplicate, duplicate_file_path) = create_object(
+			db,
+			"cas",
+			vec![
+				object::favorite::set(Some(true)),
+				object::note::set(Some("Keep".to_string())),
+			],
+		)
+		.await;
+		// Its sampled content matches, but not its full one
+		let (different, _) = create_object(db, "cas", vec![]).await;
+		db.file_path()
+			.update_many(
+				vec![file_path::object_id::equals(Some(different.id))],
+				vec![file_path::integrity_checksum::set(Some(
+					"other".to_string(),
+				))],
+			)
+			.exec()
+			.await
+			.unwrap();
+		db.file_path()
+			.update(
+				file_path::id::equals(target_file_path.id),
+				vec![file_path::integrity_checksum::set(Some("same".to_string()))],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		let tag = db
+			.tag()
+			.create(uuid_to_bytes(Uuid::new_v4()), vec![])
+			.exec()
+			.await
+			.unwrap();
+		db.tag_on_object()
+			.create_many(vec![tag_on_object::create_unchecked(
+				tag.id,
+				duplicate.id,
+				vec![],
+			)])
+			.exec()
+			.await
+			.unwrap();
+
+		assert_eq!(find_shared_cas_ids(db, None).await.unwrap(), vec!["cas"]);
+		assert_eq!(
+			merge_objects_with_cas_id(&library, "cas".to_string())
+				.await
+				.unwrap(),
+			1
+		);
+
+		let merged = db
+			.object()
+			.find_unique(object::id::equals(target.id))
+			.select(object_to_consolidate::select())
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(
+			merged
+				.file_paths
+				.iter()
+				.map(|file_path| &file_path.pub_id)
+				.sorted()
+				.collect::<Vec<_>>(),
+			[&target_file_path.pub_id, &duplicate_file_path.pub_id]
+				.into_iter()
+				.sorted()
+				.collect::<Vec<_>>()
+		);
+		assert_eq!(
+			merged
+				.tags
+				.iter()
+				.map(|tag_on_object| tag_on_object.tag.id)
+				.collect::<Vec<_>>(),
+			vec![tag.id]
+		);
+		assert_eq!(merged.hidden, Some(false));
+		assert_eq!(merged.favorite, Some(true));
+		assert_eq!(merged.important, None);
+		assert_eq!(merged.note.as_deref(), Some("Keep"));
+
+		assert!(db
+			.object()
+			.find_unique(object::id::equals(duplicate.id))
+			.exec()
+			.await
+			.unwrap()
+			.is_none());
+		assert!(db
+			.object()
+			.find_unique(object::id::equals(different.id))
+			.exec()
+			.await
+			.unwrap()
+			.is_some());
+
+		// Other instances receive only the fields which changed on the target
+		let ops = library
+			.sync
+			.get_instance_ops(library.instance_uuid, sd_sync::NTP64(0), 1000)
+			.await
+			.unwrap();
+		let mut updated_fields = ops
+			.iter()
+			.filter(|op| {
+				op.model == object::NAME && op.record_id == json!({ "pub_id": target.pub_id })
+			})
+			.filter_map(|op| match &op.data {
+				CRDTOperationData::Update { field, .. } => Some(field.as_str()),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		updated_fields.sort_unstable();
+		assert_eq!(
+			updated_fields,
+			vec![object::favorite::NAME, object::note::NAME]
+		);
+	}
+
+	#[test]
+	fn test_merge_flag() {
+		assert_eq!(merge_flag(None, None), None);
+		assert_eq!(merge_flag(Some(false), None), Some(false));
+		assert_eq!(merge_flag(None, Some(true)), Some(true));
+		assert_eq!(merge_flag(Some(true), Some(false)), Some(true));
+	}
+}
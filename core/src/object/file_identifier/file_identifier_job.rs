@@ -34,6 +34,10 @@ use super::{process_identifier_file_paths, FileIdentifierJobError, CHUNK_SIZE};
 pub struct FileIdentifierJobInit {
 	pub location: location::Data,
 	pub sub_path: Option<PathBuf>, // subpath to start from
+	/// Also store the full checksum of each file, which reads them whole instead of sampling them
+	/// like the cas_id. Set from the library's `full_hash` preference.
+	#[serde(default)]
+	pub full_hash: bool,
 }
 
 impl Hash for FileIdentifierJobInit {
@@ -42,6 +46,7 @@ impl Hash for FileIdentifierJobInit {
 		if let Some(ref sub_path) = self.sub_path {
 			sub_path.hash(state);
 		}
+		self.full_hash.hash(state);
 	}
 }
 
@@ -162,6 +167,7 @@ impl StatefulJob for FileIdentifierJobInit {
 			.expect("We already validated before that there are orphans `file_path`s");
 
 		ctx.progress(vec![
+			JobReportUpdate::Phase("identifying".to_string()),
 			JobReportUpdate::TaskCount(orphan_count),
 			JobReportUpdate::Message(format!("Found {orphan_count} files to be identified")),
 		]);
@@ -216,6 +222,7 @@ impl StatefulJob for FileIdentifierJobInit {
 				run_metadata.cursor,
 				&ctx.library,
 				run_metadata.total_orphan_paths,
+				init.full_hash,
 			)
 			.await?;
 
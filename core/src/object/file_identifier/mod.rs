@@ -1,7 +1,7 @@
 use crate::{
 	job::JobError,
 	library::Library,
-	object::{cas::generate_cas_id, object_for_file_identifier},
+	object::{cas::generate_cas_id, object_for_file_identifier, validation::hash::file_checksum},
 };
 
 use sd_file_ext::{extensions::Extension, kind::ObjectKind};
@@ -48,6 +48,8 @@ pub enum FileIdentifierJobError {
 #[derive(Debug, Clone)]
 pub struct FileMetadata {
 	pub cas_id: Option<String>,
+	/// Only computed with [`FileMetadata::with_integrity_checksum`], as it reads the whole file
+	pub integrity_checksum: Option<String>,
 	pub kind: ObjectKind,
 	pub fs_metadata: std::fs::Metadata,
 }
@@ -89,16 +91,54 @@ impl FileMetadata {
 
 		Ok(FileMetadata {
 			cas_id,
+			integrity_checksum: None,
 			kind,
 			fs_metadata,
 		})
 	}
+
+	/// Also computes the full BLAKE3 checksum of the file, unless it's empty like for the `cas_id`
+	pub async fn with_integrity_checksum(
+		mut self,
+		path: impl AsRef<Path>,
+	) -> Result<FileMetadata, FileIOError> {
+		let path = path.as_ref();
+
+		if self.cas_id.is_some() {
+			self.integrity_checksum = Some(
+				file_checksum(path)
+					.await
+					.map_err(|e| FileIOError::from((path, e)))?,
+			);
+		}
+
+		Ok(self)
+	}
+}
+
+/// Whether two files have the same contents. The `cas_id` only samples big files, so when both
+/// of them have a full checksum it has the final say.
+pub(crate) fn is_same_content(
+	(cas_id, integrity_checksum): (Option<&String>, Option<&String>),
+	(other_cas_id, other_integrity_checksum): (Option<&String>, Option<&String>),
+) -> bool {
+	if cas_id.is_none() || cas_id != other_cas_id {
+		return false;
+	}
+
+	match (integrity_checksum, other_integrity_checksum) {
+		(Some(integrity_checksum), Some(other_integrity_checksum)) => {
+			integrity_checksum == other_integrity_checksum
+		}
+		_ => true,
+	}
 }
 
 async fn identifier_job_step(
 	Library { db, sync, .. }: &Library,
 	location: &location::Data,
 	file_paths: &[file_path_for_file_identifier::Data],
+	full_hash: bool,
 ) -> Result<(usize, usize), JobError> {
 	let location_path = maybe_missing(&location.path, "location.path").map(Path::new)?;
 
@@ -112,8 +152,16 @@ async fn identifier_job_step(
 					.ok()
 			})
 			.map(|(iso_file_path, file_path)| async move {
-				FileMetadata::new(&location_path, &iso_file_path)
-					.await
+				let metadata = match FileMetadata::new(&location_path, &iso_file_path).await {
+					Ok(metadata) if full_hash => {
+						metadata
+							.with_integrity_checksum(location_path.join(&iso_file_path))
+							.await
+					}
+					res => res,
+				};
+
+				metadata
 					.map(|metadata| {
 						(
 							// SAFETY: This should never happen
@@ -153,25 +201,48 @@ async fn identifier_job_step(
 		.into_iter()
 		.collect();
 
-	// Assign cas_id to each file path
+	// Assign cas_id, and the full checksum when we computed it, to each file path
 	sync.write_ops(
 		db,
 		file_paths_metadatas
 			.iter()
-			.map(|(pub_id, (metadata, _))| {
-				(
-					sync.shared_update(
-						prisma_sync::file_path::SyncId {
-							pub_id: sd_utils::uuid_to_bytes(*pub_id),
-						},
+			.flat_map(|(pub_id, (metadata, _))| {
+				[
+					Some((
 						file_path::cas_id::NAME,
 						json!(&metadata.cas_id),
-					),
-					db.file_path().update(
-						file_path::pub_id::equals(sd_utils::uuid_to_bytes(*pub_id)),
-						vec![file_path::cas_id::set(metadata.cas_id.clone())],
-					),
-				)
+						file_path::cas_id::set(metadata.cas_id.clone()),
+					)),
+					metadata
+						.integrity_checksum
+						.as_ref()
+						.map(|integrity_checksum| {
+							(
+								file_path::integrity_checksum::NAME,
+								json!(integrity_checksum),
+								file_path::integrity_checksum::set(Some(
+									integrity_checksum.clone(),
+								)),
+							)
+						}),
+				]
+				.into_iter()
+				.flatten()
+				.map(|(field, value, param)| {
+					(
+						sync.shared_update(
+							prisma_sync::file_path::SyncId {
+								pub_id: sd_utils::uuid_to_bytes(*pub_id),
+							},
+							field,
+							value,
+						),
+						db.file_path().update(
+							file_path::pub_id::equals(sd_utils::uuid_to_bytes(*pub_id)),
+							vec![param],
+						),
+					)
+				})
 			})
 			.unzip::<_, _, _, Vec<_>>(),
 	)
@@ -187,44 +258,39 @@ async fn identifier_job_step(
 		.exec()
 		.await?;
 
-	let existing_object_cas_ids = existing_objects
+	// Attempt to associate each file path with an object that has been
+	// connected to file paths with the same contents
+	let matching_objects = file_paths_metadatas
 		.iter()
-		.flat_map(|object| {
-			object
-				.file_paths
+		.filter_map(|(pub_id, (metadata, _))| {
+			existing_objects
 				.iter()
-				.filter_map(|file_path| file_path.cas_id.as_ref())
+				.find(|object| {
+					object.file_paths.iter().any(|file_path| {
+						is_same_content(
+							(
+								metadata.cas_id.as_ref(),
+								metadata.integrity_checksum.as_ref(),
+							),
+							(
+								file_path.cas_id.as_ref(),
+								file_path.integrity_checksum.as_ref(),
+							),
+						)
+					})
+				})
+				.map(|object| (*pub_id, object))
 		})
-		.collect::<HashSet<_>>();
+		.collect::<HashMap<_, _>>();
 
-	// Attempt to associate each file path with an object that has been
-	// connected to file paths with the same cas_id
 	let updated_file_paths = sync
 		.write_ops(
 			db,
-			file_paths_metadatas
+			matching_objects
 				.iter()
-				.filter_map(|(pub_id, (metadata, file_path))| {
-					// Filtering out files without cas_id due to being empty
-					metadata
-						.cas_id
-						.is_some()
-						.then_some((pub_id, (metadata, file_path)))
-				})
-				.flat_map(|(pub_id, (metadata, _))| {
-					existing_objects
-						.iter()
-						.find(|object| {
-							object
-								.file_paths
-								.iter()
-								.any(|file_path| file_path.cas_id == metadata.cas_id)
-						})
-						.map(|object| (*pub_id, object))
-				})
 				.map(|(pub_id, object)| {
 					let (crdt_op, db_op) = connect_file_path_to_object(
-						pub_id,
+						*pub_id,
 						// SAFETY: This pub_id is generated by the uuid lib, but we have to store bytes in sqlite
 						Uuid::from_slice(&object.pub_id).expect("uuid bytes are invalid"),
 						sync,
@@ -245,12 +311,7 @@ async fn identifier_job_step(
 	// extract objects that don't already exist in the database
 	let file_paths_requiring_new_object = file_paths_metadatas
 		.into_iter()
-		.filter(|(_, (FileMetadata { cas_id, .. }, _))| {
-			cas_id
-				.as_ref()
-				.map(|cas_id| !existing_object_cas_ids.contains(cas_id))
-				.unwrap_or(true)
-		})
+		.filter(|(pub_id, _)| !matching_objects.contains_key(pub_id))
 		.collect::<Vec<_>>();
 
 	let total_created = if !file_paths_requiring_new_object.is_empty() {
@@ -349,7 +410,7 @@ async fn identifier_job_step(
 	Ok((total_created, updated_file_paths.len()))
 }
 
-fn connect_file_path_to_object<'db>(
+pub(crate) fn connect_file_path_to_object<'db>(
 	file_path_id: Uuid,
 	object_id: Uuid,
 	sync: &crate::sync::Manager,
@@ -384,6 +445,7 @@ async fn process_identifier_file_paths(
 	cursor: file_path::id::Type,
 	library: &Library,
 	orphan_count: usize,
+	full_hash: bool,
 ) -> Result<(usize, usize, file_path::id::Type), JobError> {
 	trace!(
 		"Processing {:?} orphan Paths. ({} completed of {})",
@@ -393,7 +455,7 @@ async fn process_identifier_file_paths(
 	);
 
 	let (total_objects_created, total_objects_linked) =
-		identifier_job_step(library, location, file_paths).await?;
+		identifier_job_step(library, location, file_paths, full_hash).await?;
 
 	Ok((
 		total_objects_created,
@@ -405,3 +467,43 @@ async fn process_identifier_file_paths(
 			.unwrap_or(cursor),
 	))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_is_same_content() {
+		let (cas_id, other_cas_id) = ("cas_id".to_string(), "other_cas_id".to_string());
+		let (checksum, other_checksum) = ("checksum".to_string(), "other_checksum".to_string());
+
+		// Without full checksums we can only trust the cas_id
+		assert!(is_same_content(
+			(Some(&cas_id), None),
+			(Some(&cas_id), None)
+		));
+		assert!(is_same_content(
+			(Some(&cas_id), Some(&checksum)),
+			(Some(&cas_id), None)
+		));
+		assert!(!is_same_content(
+			(Some(&cas_id), None),
+			(Some(&other_cas_id), None)
+		));
+
+		// Both hashes agree
+		assert!(is_same_content(
+			(Some(&cas_id), Some(&checksum)),
+			(Some(&cas_id), Some(&checksum))
+		));
+
+		// The sampled cas_id collided, but the full checksums tell the files apart
+		assert!(!is_same_content(
+			(Some(&cas_id), Some(&checksum)),
+			(Some(&cas_id), Some(&other_checksum))
+		));
+
+		// Empty files have no cas_id and are never the same content as anything
+		assert!(!is_same_content((None, None), (None, None)));
+	}
+}
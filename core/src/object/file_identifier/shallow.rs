@@ -1,4 +1,4 @@
-use crate::{invalidate_query, job::JobError, library::Library};
+use crate::{invalidate_query, job::JobError, library::Library, preferences::LibraryPreferences};
 
 use sd_file_path_helper::{
 	ensure_file_path_exists, ensure_sub_path_is_directory, ensure_sub_path_is_in_location,
@@ -83,6 +83,8 @@ pub async fn shallow(
 		return Ok(());
 	};
 
+	let full_hash = LibraryPreferences::read(db).await?.full_hash();
+
 	// Initializing `state.data` here because we need a complete state in case of early finish
 	let mut data = ShallowFileIdentifierJobState {
 		cursor: first_path.id,
@@ -106,6 +108,7 @@ pub async fn shallow(
 			*cursor,
 			library,
 			orphan_count,
+			full_hash,
 		)
 		.await?;
 		*cursor = new_cursor;
@@ -117,6 +120,53 @@ pub async fn shallow(
 	Ok(())
 }
 
+/// Identifies a single file, if it's still missing its object, without looking at the rest of
+/// its directory.
+pub async fn shallow_file(
+	location: &location::Data,
+	sub_path: &Path,
+	library: &Library,
+) -> Result<(), JobError> {
+	let Library { db, .. } = library;
+
+	let location_path = maybe_missing(&location.path, "location.path").map(Path::new)?;
+
+	let full_path = ensure_sub_path_is_in_location(location_path, sub_path)
+		.await
+		.map_err(FileIdentifierJobError::from)?;
+
+	let iso_file_path = IsolatedFilePathData::new(location.id, location_path, &full_path, false)
+		.map_err(FileIdentifierJobError::from)?;
+
+	let Some(file_path) = db
+		.file_path()
+		.find_first(vec![
+			(&iso_file_path).into(),
+			or!(
+				file_path::object_id::equals(None),
+				file_path::cas_id::equals(None)
+			),
+			file_path::size_in_bytes_bytes::not(Some(0u64.to_be_bytes().to_vec())),
+		])
+		.select(file_path_for_file_identifier::select())
+		.exec()
+		.await?
+	else {
+		return Ok(());
+	};
+
+	let full_hash = LibraryPreferences::read(db).await?.full_hash();
+
+	let cursor = file_path.id;
+
+	process_identifier_file_paths(location, &[file_path], 0, cursor, library, 1, full_hash).await?;
+
+	invalidate_query!(library, "search.paths");
+	invalidate_query!(library, "search.objects");
+
+	Ok(())
+}
+
 fn orphan_path_filters(
 	location_id: location::id::Type,
 	file_path_id: Option<file_path::id::Type>,
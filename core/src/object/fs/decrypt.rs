@@ -1,159 +1,251 @@
-// use crate::{
-// 	invalidate_query,
-// 	job::{
-// 		JobError, JobInitData, JobReportUpdate, JobResult, JobState, StatefulJob, WorkerContext,
-// 	},
-// 	library::Library,
-// 	location::{file_path_helper:: location::id::Type},
-// 	util::error::FileIOError,
-// };
-
-// use sd_crypto::{crypto::Decryptor, header::file::FileHeader, Protected};
-
-// use serde::{Deserialize, Serialize};
-// use specta::Type;
-// use tokio::fs::File;
-
-// use super::{get_location_path_from_location_id, get_many_files_datas, FileData, BYTES_EXT};
-// pub struct FileDecryptorJob;
-
-// // decrypt could have an option to restore metadata (and another specific option for file name? - would turn "output file" into "output path" in the UI)
-// #[derive(Serialize, Deserialize, Debug, Type, Hash)]
-// pub struct FileDecryptorJobInit {
-// 	pub location_id: location::id::Type,
-// 	pub file_path_ids: Vec<file_path::id::Type>,
-// 	pub mount_associated_key: bool,
-// 	pub password: Option<String>, // if this is set, we can assume the user chose password decryption
-// 	pub save_to_library: Option<bool>,
-// }
-
-// impl JobInitData for FileDecryptorJobInit {
-// 	type Job = FileDecryptorJob;
-// }
-
-// #[async_trait::async_trait]
-// impl StatefulJob for FileDecryptorJob {
-// 	type Init = FileDecryptorJobInit;
-// 	type Data = ();
-// 	type Step = FileData;
-
-// 	const NAME: &'static str = "file_decryptor";
-
-// 	fn new() -> Self {
-// 		Self {}
-// 	}
-
-// 	async fn init(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> Result<(), JobError> {
-// 		let Library { db, .. } = &*ctx.library;
-
-// 		state.steps = get_many_files_datas(
-// 			db,
-// 			get_location_path_from_location_id(db, state.init.location_id).await?,
-// 			&state.init.file_path_ids,
-// 		)
-// 		.await?
-// 		.into();
-
-// 		ctx.progress(vec![JobReportUpdate::TaskCount(state.steps.len())]);
-
-// 		Ok(())
-// 	}
-
-// 	async fn execute_step(
-// 		&self,
-// 		ctx: WorkerContext,
-// 		state: &mut JobState<Self>,
-// 	) -> Result<(), JobError> {
-// 		let step = &state.steps[0];
-// 		let key_manager = &ctx.library.key_manager;
-
-// 		// handle overwriting checks, and making sure there's enough available space
-// 		let output_path = {
-// 			let mut path = step.full_path.clone();
-// 			let extension = path.extension().map_or("decrypted", |ext| {
-// 				if ext == BYTES_EXT {
-// 					""
-// 				} else {
-// 					"decrypted"
-// 				}
-// 			});
-// 			path.set_extension(extension);
-// 			path
-// 		};
-
-// 		let mut reader = File::open(&step.full_path)
-// 			.await
-// 			.map_err(|e| FileIOError::from((&step.full_path, e)))?;
-// 		let mut writer = File::create(&output_path)
-// 			.await
-// 			.map_err(|e| FileIOError::from((output_path, e)))?;
-
-// 		let (header, aad) = FileHeader::from_reader(&mut reader).await?;
-
-// 		let master_key = if let Some(password) = state.init.password.clone() {
-// 			if let Some(save_to_library) = state.init.save_to_library {
-// 				// we can do this first, as `find_key_index` requires a successful decryption (just like `decrypt_master_key`)
-// 				let password_bytes = Protected::new(password.as_bytes().to_vec());
-
-// 				if save_to_library {
-// 					let index = header.find_key_index(password_bytes.clone()).await?;
-
-// 					// inherit the encryption algorithm from the keyslot
-// 					key_manager
-// 						.add_to_keystore(
-// 							Protected::new(password),
-// 							header.algorithm,
-// 							header.keyslots[index].hashing_algorithm,
-// 							false,
-// 							false,
-// 							Some(header.keyslots[index].salt),
-// 						)
-// 						.await?;
-// 				}
-
-// 				header.decrypt_master_key(password_bytes).await?
-// 			} else {
-// 				return Err(JobError::JobDataNotFound(String::from(
-// 					"Password decryption selected, but save to library boolean was not included",
-// 				)));
-// 			}
-// 		} else {
-// 			if state.init.mount_associated_key {
-// 				for key in key_manager.dump_keystore().iter().filter(|x| {
-// 					header
-// 						.keyslots
-// 						.iter()
-// 						.any(|k| k.content_salt == x.content_salt)
-// 				}) {
-// 					key_manager.mount(key.uuid).await.ok();
-// 				}
-// 			}
-
-// 			let keys = key_manager.enumerate_hashed_keys();
-
-// 			header.decrypt_master_key_from_prehashed(keys).await?
-// 		};
-
-// 		let decryptor = Decryptor::new(master_key, header.nonce, header.algorithm)?;
-
-// 		decryptor
-// 			.decrypt_streams(&mut reader, &mut writer, &aad)
-// 			.await?;
-
-// 		// need to decrypt preview media/metadata, and maybe add an option in the UI so the user can chosoe to restore these values
-// 		// for now this can't easily be implemented, as we don't know what the new object id for the file will be (we know the old one, but it may differ)
-
-// 		ctx.progress(vec![JobReportUpdate::CompletedTaskCount(
-// 			state.step_number + 1,
-// 		)]);
-
-// 		Ok(())
-// 	}
-
-// 	async fn finalize(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> JobResult {
-// 		invalidate_query!(ctx.library, "search.paths");
-
-// 		// mark job as successful
-// 		Ok(Some(serde_json::to_value(&state.init)?))
-// 	}
-// }
+use crate::{
+	invalidate_query,
+	job::{
+		CurrentStep, JobError, JobInitOutput, JobResult, JobRunErrors, JobRunMetadata,
+		JobStepOutput, StatefulJob, WorkerContext,
+	},
+	library::Library,
+	location::get_location_path_from_location_id,
+};
+
+use sd_crypto::{
+	crypto::{CancellationFlag, Decryptor, ProgressHandler},
+	fs::output::with_output_file,
+	header::file::FileHeader,
+	Protected,
+};
+use sd_prisma::prisma::{file_path, location};
+use sd_utils::{db::maybe_missing, error::FileIOError};
+
+use std::{
+	hash::{Hash, Hasher},
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+use tokio::{
+	fs::{self, File},
+	io::AsyncSeekExt,
+};
+use tracing::trace;
+
+use super::{
+	error::FileSystemJobsError, find_available_target_path, get_many_files_datas,
+	index_written_files, run_crypto_task, FileData, ENCRYPTED_FILE_EXTENSION,
+};
+
+#[derive(Serialize, Deserialize, Type, Debug)]
+pub struct FileDecryptorJobInit {
+	pub location_id: location::id::Type,
+	pub file_path_ids: Vec<file_path::id::Type>,
+	/// Never persisted with the job's state, so the job is canceled instead of resumed after a restart.
+	#[serde(skip_serializing, default)]
+	pub password: Option<Protected<String>>,
+	/// Delete each encrypted file once it's decrypted.
+	#[serde(default)]
+	pub delete_original: bool,
+}
+
+impl Hash for FileDecryptorJobInit {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.location_id.hash(state);
+		self.file_path_ids.hash(state);
+		self.password.as_ref().map(Protected::expose).hash(state);
+		self.delete_original.hash(state);
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileDecryptorJobData {
+	location_path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct FileDecryptorJobRunMetadata {
+	/// Directories with decrypted files, relative to the location, to be indexed once we're done.
+	sub_paths_to_index: Vec<PathBuf>,
+}
+
+impl JobRunMetadata for FileDecryptorJobRunMetadata {
+	fn update(&mut self, new_data: Self) {
+		self.sub_paths_to_index.extend(new_data.sub_paths_to_index);
+	}
+}
+
+#[async_trait::async_trait]
+impl StatefulJob for FileDecryptorJobInit {
+	type Data = FileDecryptorJobData;
+	type Step = FileData;
+	type RunMetadata = FileDecryptorJobRunMetadata;
+
+	const NAME: &'static str = "file_decryptor";
+	// The password is never persisted with the job's state
+	const IS_RESUMABLE: bool = false;
+
+	fn target_location(&self) -> location::id::Type {
+		self.location_id
+	}
+
+	async fn init(
+		&self,
+		ctx: &WorkerContext,
+		data: &mut Option<Self::Data>,
+	) -> Result<JobInitOutput<Self::RunMetadata, Self::Step>, JobError> {
+		let init = self;
+		let Library { db, .. } = &*ctx.library;
+
+		let location_path = get_location_path_from_location_id(db, init.location_id)
+			.await
+			.map_err(FileSystemJobsError::from)?;
+
+		let steps = get_many_files_datas(db, &location_path, &init.file_path_ids).await?;
+
+		*data = Some(FileDecryptorJobData { location_path });
+
+		Ok((Default::default(), steps).into())
+	}
+
+	async fn execute_step(
+		&self,
+		ctx: &WorkerContext,
+		CurrentStep { step, .. }: CurrentStep<'_, Self::Step>,
+		data: &Self::Data,
+		_: &Self::RunMetadata,
+	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
+		let init = self;
+		let password = init
+			.password
+			.as_ref()
+			.ok_or(FileSystemJobsError::MissingPassword)?;
+
+		if maybe_missing(step.file_path.is_dir, "file_path.is_dir")?
+			|| step.file_path.extension.as_deref() != Some(ENCRYPTED_FILE_EXTENSION)
+		{
+			return Ok(JobRunErrors(vec![format!(
+				"Skipping {}, it isn't a .{ENCRYPTED_FILE_EXTENSION} file",
+				step.full_path.display()
+			)])
+			.into());
+		}
+
+		// The encryptor appends the extension to the full file name, so this is the original path
+		let target_path = find_available_target_path(step.full_path.with_extension("")).await?;
+
+		ctx.progress_msg(format!("Decrypting {}", step.full_path.display()));
+		trace!(
+			"Decrypting {} to {}",
+			step.full_path.display(),
+			target_path.display()
+		);
+
+		let (source_path, task_target_path, password) = (
+			step.full_path.clone(),
+			target_path.clone(),
+			password.clone(),
+		);
+
+		if let Err(e) = run_crypto_task(
+			ctx,
+			"Decrypting",
+			&step.full_path,
+			move |progress, cancel| async move {
+				decrypt_file(
+					&source_path,
+					&task_target_path,
+					&password,
+					progress,
+					&cancel,
+				)
+				.await
+			},
+		)
+		.await?
+		{
+			return Ok(JobRunErrors(vec![format!(
+				"Failed to decrypt {}: {e}",
+				step.full_path.display()
+			)])
+			.into());
+		}
+
+		let new_metadata = FileDecryptorJobRunMetadata {
+			sub_paths_to_index: target_path
+				.parent()
+				.and_then(|parent| parent.strip_prefix(&data.location_path).ok())
+				.map(Path::to_path_buf)
+				.into_iter()
+				.collect(),
+		};
+
+		if init.delete_original {
+			if let Err(e) = fs::remove_file(&step.full_path).await {
+				return Ok((
+					new_metadata,
+					JobRunErrors(vec![FileIOError::from((&step.full_path, e)).to_string()]),
+				)
+					.into());
+			}
+		}
+
+		Ok(new_metadata.into())
+	}
+
+	async fn finalize(
+		&self,
+		ctx: &WorkerContext,
+		_data: &Option<Self::Data>,
+		run_metadata: &Self::RunMetadata,
+	) -> JobResult {
+		let init = self;
+
+		index_written_files(
+			&ctx.node,
+			&ctx.library,
+			init.location_id,
+			run_metadata.sub_paths_to_index.clone(),
+		)
+		.await;
+
+		invalidate_query!(ctx.library, "search.paths");
+
+		Ok(Some(json!({ "init": init })))
+	}
+}
+
+async fn decrypt_file(
+	source_path: &Path,
+	target_path: &Path,
+	password: &Protected<String>,
+	progress: impl ProgressHandler,
+	cancel: &CancellationFlag,
+) -> Result<(), FileSystemJobsError> {
+	let mut reader = File::open(source_path)
+		.await
+		.map_err(|e| FileIOError::from((source_path, e)))?;
+
+	let (header, aad) = FileHeader::from_reader(&mut reader).await?;
+
+	// Only the data after the header goes through the stream
+	let total_bytes = match (reader.metadata().await, reader.stream_position().await) {
+		(Ok(metadata), Ok(position)) => Some(metadata.len().saturating_sub(position)),
+		_ => None,
+	};
+	let master_key = header.decrypt_master_key(password.clone().into()).await?;
+
+	with_output_file(target_path, |mut writer| async move {
+		Decryptor::new(master_key, header.nonce, header.algorithm)?
+			.decrypt_streams_with_progress(
+				&mut reader,
+				&mut writer,
+				&aad,
+				total_bytes,
+				progress,
+				cancel,
+			)
+			.await
+	})
+	.await
+	.map_err(Into::into)
+}
@@ -1,261 +1,286 @@
-// use crate::{
-// 	invalidate_query,
-// 	job::*,
-// 	library::Library,
-// 	location::{file_path_helper:: location::id::Type},
-// 	util::error::{FileIOError, NonUtf8PathError},
-// };
-
-// use sd_crypto::{
-// 	crypto::Encryptor,
-// 	header::{file::FileHeader, keyslot::Keyslot},
-// 	primitives::{LATEST_FILE_HEADER, LATEST_KEYSLOT, LATEST_METADATA, LATEST_PREVIEW_MEDIA},
-// 	types::{Algorithm, Key},
-// };
-
-// use chrono::FixedOffset;
-// use serde::{Deserialize, Serialize};
-// use specta::Type;
-// use tokio::{
-// 	fs::{self, File},
-// 	io,
-// };
-// use tracing::{error, warn};
-// use uuid::Uuid;
-
-// use super::{
-// 	error::FileSystemJobsError, get_location_path_from_location_id, get_many_files_datas, FileData,
-// 	BYTES_EXT,
-// };
-
-// pub struct FileEncryptorJob;
-
-// #[derive(Serialize, Deserialize, Type, Hash)]
-// pub struct FileEncryptorJobInit {
-// 	pub location_id: location::id::Type,
-// 	pub file_path_ids: Vec<file_path::id::Type>,
-// 	pub key_uuid: Uuid,
-// 	pub algorithm: Algorithm,
-// 	pub metadata: bool,
-// 	pub preview_media: bool,
-// }
-
-// #[derive(Serialize, Deserialize)]
-// pub struct Metadata {
-// 	pub file_path_id: file_path::id::Type,
-// 	pub name: String,
-// 	pub hidden: bool,
-// 	pub favorite: bool,
-// 	pub important: bool,
-// 	pub note: Option<String>,
-// 	pub date_created: chrono::DateTime<FixedOffset>,
-// }
-
-// impl JobInitData for FileEncryptorJobInit {
-// 	type Job = FileEncryptorJob;
-// }
-
-// #[async_trait::async_trait]
-// impl StatefulJob for FileEncryptorJob {
-// 	type Init = FileEncryptorJobInit;
-// 	type Data = ();
-// 	type Step = FileData;
-
-// 	const NAME: &'static str = "file_encryptor";
-
-// 	fn new() -> Self {
-// 		Self {}
-// 	}
-
-// 	async fn init(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> Result<(), JobError> {
-// 		let Library { db, .. } = &*ctx.library;
-
-// 		state.steps = get_many_files_datas(
-// 			db,
-// 			get_location_path_from_location_id(db, state.init.location_id).await?,
-// 			&state.init.file_path_ids,
-// 		)
-// 		.await?
-// 		.into();
-
-// 		ctx.progress(vec![JobReportUpdate::TaskCount(state.steps.len())]);
-
-// 		Ok(())
-// 	}
-
-// 	async fn execute_step(
-// 		&self,
-// 		ctx: WorkerContext,
-// 		state: &mut JobState<Self>,
-// 	) -> Result<(), JobError> {
-// 		let step = &state.steps[0];
-
-// 		let Library { key_manager, .. } = &*ctx.library;
-
-// 		if !step.file_path.is_dir {
-// 			// handle overwriting checks, and making sure there's enough available space
-
-// 			let user_key = key_manager
-// 				.access_keymount(state.init.key_uuid)
-// 				.await?
-// 				.hashed_key;
-
-// 			let user_key_details = key_manager.access_keystore(state.init.key_uuid).await?;
-
-// 			let output_path = {
-// 				let mut path = step.full_path.clone();
-// 				let extension = path.extension().map_or_else(
-// 					|| Ok("bytes".to_string()),
-// 					|extension| {
-// 						Ok::<String, JobError>(format!(
-// 							"{}{BYTES_EXT}",
-// 							extension.to_str().ok_or(FileSystemJobsError::FilePath(
-// 								NonUtf8PathError(step.full_path.clone().into_boxed_path()).into()
-// 							))?
-// 						))
-// 					},
-// 				)?;
-
-// 				path.set_extension(extension);
-// 				path
-// 			};
-
-// 			let _guard = ctx
-// 				.library
-// 				.location_manager()
-// 				.temporary_ignore_events_for_path(
-// 					state.init.location_id,
-// 					ctx.library.clone(),
-// 					&output_path,
-// 				)
-// 				.await
-// 				.map_or_else(
-// 					|e| {
-// 						error!(
-// 							"Failed to make location manager ignore the path {}; Error: {e:#?}",
-// 							output_path.display()
-// 						);
-// 						None
-// 					},
-// 					Some,
-// 				);
-
-// 			let mut reader = File::open(&step.full_path)
-// 				.await
-// 				.map_err(|e| FileIOError::from((&step.full_path, e)))?;
-// 			let mut writer = File::create(&output_path)
-// 				.await
-// 				.map_err(|e| FileIOError::from((output_path, e)))?;
-
-// 			let master_key = Key::generate();
-
-// 			let mut header = FileHeader::new(
-// 				LATEST_FILE_HEADER,
-// 				state.init.algorithm,
-// 				vec![
-// 					Keyslot::new(
-// 						LATEST_KEYSLOT,
-// 						state.init.algorithm,
-// 						user_key_details.hashing_algorithm,
-// 						user_key_details.content_salt,
-// 						user_key,
-// 						master_key.clone(),
-// 					)
-// 					.await?,
-// 				],
-// 			)?;
-
-// 			if state.init.metadata || state.init.preview_media {
-// 				// if any are requested, we can make the query as it'll be used at least once
-// 				if let Some(ref object) = step.file_path.object {
-// 					if state.init.metadata {
-// 						let metadata = Metadata {
-// 							file_path_id: step.file_path.id,
-// 							name: step.file_path.materialized_path.clone(),
-// 							hidden: object.hidden,
-// 							favorite: object.favorite,
-// 							important: object.important,
-// 							note: object.note.clone(),
-// 							date_created: object.date_created,
-// 						};
-
-// 						header
-// 							.add_metadata(
-// 								LATEST_METADATA,
-// 								state.init.algorithm,
-// 								master_key.clone(),
-// 								&metadata,
-// 							)
-// 							.await?;
-// 					}
-
-// 					// if state.init.preview_media
-// 					// 	&& (object.has_thumbnail
-// 					// 		|| object.has_video_preview || object.has_thumbstrip)
-
-// 					// may not be the best - preview media (thumbnail) isn't guaranteed to be webp
-// 					let thumbnail_path = ctx
-// 						.library
-// 						.config()
-// 						.data_directory()
-// 						.join("thumbnails")
-// 						.join(
-// 							step.file_path
-// 								.cas_id
-// 								.as_ref()
-// 								.ok_or(JobError::MissingCasId)?,
-// 						)
-// 						.with_extension("wepb");
-
-// 					match fs::read(&thumbnail_path).await {
-// 						Ok(thumbnail_bytes) => {
-// 							header
-// 								.add_preview_media(
-// 									LATEST_PREVIEW_MEDIA,
-// 									state.init.algorithm,
-// 									master_key.clone(),
-// 									&thumbnail_bytes,
-// 								)
-// 								.await?;
-// 						}
-// 						Err(e) if e.kind() == io::ErrorKind::NotFound => {
-// 							// If the file just doesn't exist, then we don't care
-// 						}
-// 						Err(e) => {
-// 							return Err(FileIOError::from((thumbnail_path, e)).into());
-// 						}
-// 					}
-// 				} else {
-// 					// should use container encryption if it's a directory
-// 					warn!("skipping metadata/preview media inclusion, no associated object found")
-// 				}
-// 			}
-
-// 			header.write(&mut writer).await?;
-
-// 			let encryptor = Encryptor::new(master_key, header.nonce, header.algorithm)?;
-
-// 			encryptor
-// 				.encrypt_streams(&mut reader, &mut writer, &header.generate_aad())
-// 				.await?;
-// 		} else {
-// 			warn!(
-// 				"encryption is skipping {}/{} as it isn't a file",
-// 				step.file_path.materialized_path, step.file_path.name
-// 			)
-// 		}
-
-// 		ctx.progress(vec![JobReportUpdate::CompletedTaskCount(
-// 			state.step_number + 1,
-// 		)]);
-
-// 		Ok(())
-// 	}
-
-// 	async fn finalize(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> JobResult {
-// 		invalidate_query!(ctx.library, "search.paths");
-
-// 		// mark job as successful
-// 		Ok(Some(serde_json::to_value(&state.init)?))
-// 	}
-// }
+use crate::{
+	invalidate_query,
+	job::{
+		CurrentStep, JobError, JobInitOutput, JobResult, JobRunErrors, JobRunMetadata,
+		JobStepOutput, StatefulJob, WorkerContext,
+	},
+	library::Library,
+	location::get_location_path_from_location_id,
+};
+
+use sd_crypto::{
+	crypto::{CancellationFlag, Encryptor, ProgressHandler},
+	fs::output::with_output_file,
+	header::{file::FileHeader, keyslot::Keyslot},
+	primitives::{LATEST_FILE_HEADER, LATEST_KEYSLOT},
+	types::{Algorithm, HashingAlgorithm, Key, Salt},
+	Protected,
+};
+use sd_prisma::prisma::{file_path, location};
+use sd_utils::{db::maybe_missing, error::FileIOError};
+
+use std::{
+	hash::{Hash, Hasher},
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+use tokio::fs::{self, File};
+use tracing::trace;
+
+use super::{
+	error::FileSystemJobsError, find_available_target_path, get_many_files_datas,
+	index_written_files, run_crypto_task, FileData, ENCRYPTED_FILE_EXTENSION,
+};
+
+#[derive(Serialize, Deserialize, Type, Debug)]
+pub struct FileEncryptorJobInit {
+	pub location_id: location::id::Type,
+	pub file_path_ids: Vec<file_path::id::Type>,
+	/// Defaults to the node's [`CryptoDefaults`](crate::node::config::CryptoDefaults).
+	#[serde(default)]
+	pub algorithm: Option<Algorithm>,
+	/// Defaults to the node's [`CryptoDefaults`](crate::node::config::CryptoDefaults).
+	#[serde(default)]
+	pub hashing_algorithm: Option<HashingAlgorithm>,
+	/// Never persisted with the job's state, so the job is canceled instead of resumed after a restart.
+	#[serde(skip_serializing, default)]
+	pub password: Option<Protected<String>>,
+	/// Delete each original file once it's encrypted.
+	#[serde(default)]
+	pub delete_original: bool,
+}
+
+impl Hash for FileEncryptorJobInit {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.location_id.hash(state);
+		self.file_path_ids.hash(state);
+		self.algorithm.hash(state);
+		self.hashing_algorithm.hash(state);
+		self.password.as_ref().map(Protected::expose).hash(state);
+		self.delete_original.hash(state);
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileEncryptorJobData {
+	location_path: PathBuf,
+	algorithm: Algorithm,
+	hashing_algorithm: HashingAlgorithm,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct FileEncryptorJobRunMetadata {
+	/// Directories with encrypted files, relative to the location, to be indexed once we're done.
+	sub_paths_to_index: Vec<PathBuf>,
+}
+
+impl JobRunMetadata for FileEncryptorJobRunMetadata {
+	fn update(&mut self, new_data: Self) {
+		self.sub_paths_to_index.extend(new_data.sub_paths_to_index);
+	}
+}
+
+#[async_trait::async_trait]
+impl StatefulJob for FileEncryptorJobInit {
+	type Data = FileEncryptorJobData;
+	type Step = FileData;
+	type RunMetadata = FileEncryptorJobRunMetadata;
+
+	const NAME: &'static str = "file_encryptor";
+	// The password is never persisted with the job's state
+	const IS_RESUMABLE: bool = false;
+
+	fn target_location(&self) -> location::id::Type {
+		self.location_id
+	}
+
+	async fn init(
+		&self,
+		ctx: &WorkerContext,
+		data: &mut Option<Self::Data>,
+	) -> Result<JobInitOutput<Self::RunMetadata, Self::Step>, JobError> {
+		let init = self;
+		let Library { db, .. } = &*ctx.library;
+
+		let location_path = get_location_path_from_location_id(db, init.location_id)
+			.await
+			.map_err(FileSystemJobsError::from)?;
+
+		let steps = get_many_files_datas(db, &location_path, &init.file_path_ids).await?;
+
+		let defaults = ctx.node.config.get().await.crypto_defaults;
+
+		*data = Some(FileEncryptorJobData {
+			location_path,
+			algorithm: init.algorithm.unwrap_or(defaults.algorithm),
+			hashing_algorithm: init.hashing_algorithm.unwrap_or(defaults.hashing_algorithm),
+		});
+
+		Ok((Default::default(), steps).into())
+	}
+
+	async fn execute_step(
+		&self,
+		ctx: &WorkerContext,
+		CurrentStep { step, .. }: CurrentStep<'_, Self::Step>,
+		data: &Self::Data,
+		_: &Self::RunMetadata,
+	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
+		let init = self;
+		let password = init
+			.password
+			.as_ref()
+			.ok_or(FileSystemJobsError::MissingPassword)?;
+
+		if maybe_missing(step.file_path.is_dir, "file_path.is_dir")? {
+			return Ok(JobRunErrors(vec![format!(
+				"Skipping directory {}, only files can be encrypted",
+				step.full_path.display()
+			)])
+			.into());
+		}
+
+		let mut target_path = step.full_path.clone().into_os_string();
+		target_path.push(".");
+		target_path.push(ENCRYPTED_FILE_EXTENSION);
+		let target_path = find_available_target_path(target_path).await?;
+
+		ctx.progress_msg(format!("Encrypting {}", step.full_path.display()));
+		trace!(
+			"Encrypting {} to {}",
+			step.full_path.display(),
+			target_path.display()
+		);
+
+		let (source_path, task_target_path, password) = (
+			step.full_path.clone(),
+			target_path.clone(),
+			password.clone(),
+		);
+		let (algorithm, hashing_algorithm) = (data.algorithm, data.hashing_algorithm);
+
+		if let Err(e) = run_crypto_task(
+			ctx,
+			"Encrypting",
+			&step.full_path,
+			move |progress, cancel| async move {
+				encrypt_file(
+					&source_path,
+					&task_target_path,
+					algorithm,
+					hashing_algorithm,
+					&password,
+					progress,
+					&cancel,
+				)
+				.await
+			},
+		)
+		.await?
+		{
+			return Ok(JobRunErrors(vec![format!(
+				"Failed to encrypt {}: {e}",
+				step.full_path.display()
+			)])
+			.into());
+		}
+
+		let new_metadata = FileEncryptorJobRunMetadata {
+			sub_paths_to_index: target_path
+				.parent()
+				.and_then(|parent| parent.strip_prefix(&data.location_path).ok())
+				.map(Path::to_path_buf)
+				.into_iter()
+				.collect(),
+		};
+
+		if init.delete_original {
+			if let Err(e) = fs::remove_file(&step.full_path).await {
+				return Ok((
+					new_metadata,
+					JobRunErrors(vec![FileIOError::from((&step.full_path, e)).to_string()]),
+				)
+					.into());
+			}
+		}
+
+		Ok(new_metadata.into())
+	}
+
+	async fn finalize(
+		&self,
+		ctx: &WorkerContext,
+		_data: &Option<Self::Data>,
+		run_metadata: &Self::RunMetadata,
+	) -> JobResult {
+		let init = self;
+
+		index_written_files(
+			&ctx.node,
+			&ctx.library,
+			init.location_id,
+			run_metadata.sub_paths_to_index.clone(),
+		)
+		.await;
+
+		invalidate_query!(ctx.library, "search.paths");
+
+		Ok(Some(json!({ "init": init })))
+	}
+}
+
+/// Encrypt the file with a fresh master key, unlocked by a single password keyslot.
+///
+/// The output is removed if the encryption fails or is cancelled.
+async fn encrypt_file(
+	source_path: &Path,
+	target_path: &Path,
+	algorithm: Algorithm,
+	hashing_algorithm: HashingAlgorithm,
+	password: &Protected<String>,
+	progress: impl ProgressHandler,
+	cancel: &CancellationFlag,
+) -> Result<(), FileSystemJobsError> {
+	let mut reader = File::open(source_path)
+		.await
+		.map_err(|e| FileIOError::from((source_path, e)))?;
+	let total_bytes = reader.metadata().await.ok().map(|metadata| metadata.len());
+
+	let master_key = Key::generate();
+	let content_salt = Salt::generate();
+	let hashed_password = hashing_algorithm.hash(password.clone().into(), content_salt, None)?;
+
+	let keyslots = vec![
+		Keyslot::new(
+			LATEST_KEYSLOT,
+			algorithm,
+			hashing_algorithm,
+			content_salt,
+			hashed_password,
+			master_key.clone(),
+		)
+		.await?,
+	];
+
+	let header = FileHeader::new(LATEST_FILE_HEADER, algorithm, keyslots)?;
+
+	with_output_file(target_path, |mut writer| async move {
+		header.write(&mut writer).await?;
+
+		Encryptor::new(master_key, header.nonce, header.algorithm)?
+			.encrypt_streams_with_progress(
+				&mut reader,
+				&mut writer,
+				&header.generate_aad(),
+				total_bytes,
+				progress,
+				cancel,
+			)
+			.await
+	})
+	.await
+	.map_err(Into::into)
+}
@@ -27,6 +27,8 @@ pub enum FileSystemJobsError {
 	Database(#[from] QueryError),
 	#[error(transparent)]
 	FilePath(#[from] FilePathError),
+	#[error("invalid file name: <name='{0}'>")]
+	InvalidFileName(String),
 	#[error("action would overwrite another file: {}", .0.display())]
 	WouldOverwrite(Box<Path>),
 	#[error("missing-field: {0}")]
@@ -41,10 +43,23 @@ pub enum FileSystemJobsError {
 	NonUTF8Path(#[from] NonUtf8PathError),
 	#[error("failed to find an available name to avoid duplication: <path='{}'>", .0.display())]
 	FailedToFindAvailableName(Box<Path>),
+	#[error(transparent)]
+	Crypto(#[from] sd_crypto::Error),
+	#[error(
+		"the password isn't available anymore, encryption jobs can't be resumed after a restart"
+	)]
+	MissingPassword,
 }
 
 impl From<FileSystemJobsError> for rspc::Error {
 	fn from(e: FileSystemJobsError) -> Self {
-		Self::with_cause(rspc::ErrorCode::InternalServerError, e.to_string(), e)
+		let code = match e {
+			FileSystemJobsError::InvalidFileName(_) => rspc::ErrorCode::BadRequest,
+			FileSystemJobsError::FilePathIdNotFound(_) => rspc::ErrorCode::NotFound,
+			FileSystemJobsError::WouldOverwrite(_) => rspc::ErrorCode::Conflict,
+			_ => rspc::ErrorCode::InternalServerError,
+		};
+
+		Self::with_cause(code, e.to_string(), e)
 	}
 }
@@ -1,5 +1,11 @@
-use crate::location::LocationError;
+use crate::{
+	job::{JobError, WorkerContext},
+	library::Library,
+	location::{find_location, light_scan_location, location_with_indexer_rules, LocationError},
+	Node,
+};
 
+use sd_crypto::crypto::{CancellationFlag, Progress};
 use sd_file_path_helper::{file_path_with_object, IsolatedFilePathData};
 use sd_prisma::prisma::{file_path, location, PrismaClient};
 use sd_utils::{
@@ -9,7 +15,9 @@ use sd_utils::{
 
 use std::{
 	ffi::OsStr,
+	future::Future,
 	path::{Path, PathBuf},
+	sync::Arc,
 };
 
 use once_cell::sync::Lazy;
@@ -22,18 +30,22 @@ pub mod erase;
 pub mod copy;
 pub mod cut;
 
-// pub mod decrypt;
-// pub mod encrypt;
+pub mod decrypt;
+pub mod encrypt;
 
 pub mod error;
+pub mod rename;
+pub mod text_preview;
 
 use error::FileSystemJobsError;
-use tokio::{fs, io};
+use tokio::{fs, io, sync::mpsc};
+use tracing::error;
 
 static DUPLICATE_PATTERN: Lazy<Regex> =
 	Lazy::new(|| Regex::new(r" \(\d+\)").expect("Failed to compile hardcoded regex"));
 
-// pub const BYTES_EXT: &str = ".bytes";
+/// Appended to the full name of files encrypted by the [`encrypt::FileEncryptorJobInit`] job.
+pub const ENCRYPTED_FILE_EXTENSION: &str = "sdenc";
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum ObjectType {
@@ -215,3 +227,105 @@ pub async fn find_available_filename_for_duplicate(
 		target_path.to_path_buf().into_boxed_path(),
 	))
 }
+
+/// The path to write a new file to, picking a new name if something already exists at `target_path`.
+async fn find_available_target_path(
+	target_path: impl AsRef<Path>,
+) -> Result<PathBuf, FileSystemJobsError> {
+	let target_path = target_path.as_ref();
+
+	match fs::metadata(target_path).await {
+		Ok(_) => find_available_filename_for_duplicate(target_path).await,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(target_path.to_path_buf()),
+		Err(e) => Err(FileIOError::from((target_path, e)).into()),
+	}
+}
+
+/// Run the encryption or decryption of a file on it's own task, reporting it's progress to the job.
+///
+/// The job system aborts the running step when a job is canceled, which would leave a partial output
+/// file behind. With it's own task, the cancellation flag stops it between blocks instead and the
+/// output is removed.
+async fn run_crypto_task<Fut>(
+	ctx: &WorkerContext,
+	action: &str,
+	path: &Path,
+	task: impl FnOnce(mpsc::UnboundedSender<Progress>, CancellationFlag) -> Fut,
+) -> Result<Result<(), FileSystemJobsError>, JobError>
+where
+	Fut: Future<Output = Result<(), FileSystemJobsError>> + Send + 'static,
+{
+	struct CancelOnDrop(CancellationFlag);
+
+	impl Drop for CancelOnDrop {
+		fn drop(&mut self) {
+			self.0.cancel();
+		}
+	}
+
+	let cancel = CancellationFlag::new();
+	let _cancel_on_drop = CancelOnDrop(cancel.clone());
+
+	let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+	let handle = tokio::spawn(task(progress_tx, cancel));
+
+	while let Some(Progress {
+		bytes_done,
+		total_bytes,
+	}) = progress_rx.recv().await
+	{
+		if let Some(total_bytes) = total_bytes.filter(|total_bytes| *total_bytes > 0) {
+			ctx.progress_msg(format!(
+				"{action} {} ({}%)",
+				path.display(),
+				bytes_done * 100 / total_bytes
+			));
+		}
+	}
+
+	handle.await.map_err(Into::into)
+}
+
+/// Index the files written by a job to the location's directories at `sub_paths`, so they show up
+/// without waiting for the location watcher, if the location is being watched at all.
+async fn index_written_files(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	location_id: location::id::Type,
+	mut sub_paths: Vec<PathBuf>,
+) {
+	let location = match find_location(library, location_id)
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await
+	{
+		Ok(Some(location)) => location,
+		Ok(None) => {
+			error!("Location {location_id} not found while indexing written files");
+			return;
+		}
+		Err(e) => {
+			error!("Failed to fetch location {location_id} to index written files: {e:#?}");
+			return;
+		}
+	};
+
+	sub_paths.sort();
+	sub_paths.dedup();
+
+	for sub_path in sub_paths {
+		if let Err(e) = light_scan_location(
+			Arc::clone(node),
+			Arc::clone(library),
+			location.clone(),
+			&sub_path,
+		)
+		.await
+		{
+			error!(
+				"Failed to index written files at {}: {e:#?}",
+				sub_path.display()
+			);
+		}
+	}
+}
@@ -0,0 +1,266 @@
+use crate::{
+	invalidate_query,
+	library::Library,
+	location::{find_location, normalization::location_is_case_insensitive, LocationError},
+	Node,
+};
+
+use sd_file_ext::{extensions::Extension, kind::ObjectKind};
+use sd_file_path_helper::{file_path_with_object, path_is_hidden, IsolatedFilePathData};
+use sd_prisma::{
+	prisma::{file_path, location, object},
+	prisma_sync,
+};
+use sd_sync::OperationFactory;
+use sd_utils::{db::maybe_missing, error::FileIOError};
+
+use std::{
+	io::ErrorKind,
+	path::{is_separator, PathBuf},
+	sync::Arc,
+};
+
+use serde_json::json;
+use tokio::fs;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::error::FileSystemJobsError;
+
+/// Renames a file or directory on disk and updates its `file_path`, along with the materialized
+/// paths of everything inside it, instead of waiting for the location watcher to pick it up.
+///
+/// Changing only the case of a name also works on case-insensitive filesystems, by renaming
+/// through a temporary name first.
+pub async fn rename_file_path(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	file_path_id: file_path::id::Type,
+	new_name: &str,
+) -> Result<(), FileSystemJobsError> {
+	if new_name.is_empty()
+		|| new_name == "."
+		|| new_name == ".."
+		|| new_name.chars().any(is_separator)
+		|| !IsolatedFilePathData::accept_file_name(new_name)
+	{
+		return Err(FileSystemJobsError::InvalidFileName(new_name.to_string()));
+	}
+
+	let Library { db, sync, .. } = &**library;
+
+	let file_path = db
+		.file_path()
+		.find_unique(file_path::id::equals(file_path_id))
+		.include(file_path_with_object::include())
+		.exec()
+		.await?
+		.ok_or(FileSystemJobsError::FilePathIdNotFound(file_path_id))?;
+
+	let location_id = maybe_missing(file_path.location_id, "file_path.location_id")?;
+	let is_dir = maybe_missing(file_path.is_dir, "file_path.is_dir")?;
+
+	let location = find_location(library, location_id)
+		.select(location::select!({ path case_insensitive }))
+		.exec()
+		.await?
+		.ok_or(LocationError::IdNotFound(location_id))?;
+
+	let location_path = location
+		.path
+		.as_ref()
+		.map(PathBuf::from)
+		.ok_or(LocationError::MissingPath(location_id))?;
+
+	let old_iso_file_path = IsolatedFilePathData::try_from(&file_path)?;
+	if old_iso_file_path.full_name() == new_name {
+		return Ok(());
+	}
+
+	let old_path = location_path.join(&old_iso_file_path);
+	let new_path = old_path.with_file_name(new_name);
+	let new_iso_file_path =
+		IsolatedFilePathData::new(location_id, &location_path, &new_path, is_dir)?;
+
+	let case_insensitive = location_is_case_insensitive(
+		db,
+		location_id,
+		Some(&location_path),
+		location.case_insensitive,
+	)
+	.await?;
+
+	let _ignore_old_path = node
+		.locations
+		.temporary_ignore_events_for_path(location_id, library.clone(), &old_path)
+		.await
+		.map_err(LocationError::from)?;
+	let _ignore_new_path = node
+		.locations
+		.temporary_ignore_events_for_path(location_id, library.clone(), &new_path)
+		.await
+		.map_err(LocationError::from)?;
+
+	match fs::metadata(&new_path).await {
+		// The filesystem considers both names the same, so what we found is the file itself
+		Ok(_)
+			if case_insensitive
+				&& old_iso_file_path.normalized_name(true)
+					== new_iso_file_path.normalized_name(true) =>
+		{
+			let temp_path = old_path.with_file_name(format!(".{}.sdrename", Uuid::new_v4()));
+
+			let _ignore_temp_path = node
+				.locations
+				.temporary_ignore_events_for_path(location_id, library.clone(), &temp_path)
+				.await
+				.map_err(LocationError::from)?;
+
+			fs::rename(&old_path, &temp_path)
+				.await
+				.map_err(|e| FileIOError::from((&old_path, e)))?;
+			fs::rename(&temp_path, &new_path)
+				.await
+				.map_err(|e| FileIOError::from((&temp_path, e)))?;
+		}
+		Ok(_) => {
+			return Err(FileSystemJobsError::WouldOverwrite(
+				new_path.into_boxed_path(),
+			))
+		}
+		Err(e) if e.kind() == ErrorKind::NotFound => {
+			fs::rename(&old_path, &new_path)
+				.await
+				.map_err(|e| FileIOError::from((&old_path, e)))?;
+		}
+		Err(e) => return Err(FileIOError::from((&new_path, e)).into()),
+	}
+
+	let metadata = fs::metadata(&new_path)
+		.await
+		.map_err(|e| FileIOError::from((&new_path, e)))?;
+
+	let new_parts = new_iso_file_path.to_parts();
+	let is_hidden = path_is_hidden(&new_path, &metadata);
+
+	let (mut sync_ops, db_params): (Vec<_>, Vec<_>) = [
+		(
+			(file_path::name::NAME, json!(new_parts.name)),
+			file_path::name::set(Some(new_parts.name.to_string())),
+		),
+		(
+			(file_path::extension::NAME, json!(new_parts.extension)),
+			file_path::extension::set(Some(new_parts.extension.to_string())),
+		),
+		(
+			(file_path::hidden::NAME, json!(is_hidden)),
+			file_path::hidden::set(Some(is_hidden)),
+		),
+	]
+	.into_iter()
+	.map(|((field, value), db_param)| {
+		(
+			sync.shared_update(
+				prisma_sync::file_path::SyncId {
+					pub_id: file_path.pub_id.clone(),
+				},
+				field,
+				value,
+			),
+			db_param,
+		)
+	})
+	.unzip();
+
+	let mut db_updates = vec![db.file_path().update(
+		file_path::id::equals(file_path.id),
+		db_params
+			.into_iter()
+			// Local only, as other instances can have the location in another filesystem
+			.chain([file_path::name_normalized::set(Some(
+				new_iso_file_path.normalized_name(case_insensitive),
+			))])
+			.collect(),
+	)];
+
+	// Everything inside a directory has a materialized path starting with the directory's own
+	if let (Some(old_prefix), Some(new_prefix)) = (
+		old_iso_file_path.materialized_path_for_children(),
+		new_iso_file_path.materialized_path_for_children(),
+	) {
+		let children = db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(Some(location_id)),
+				file_path::materialized_path::starts_with(old_prefix.clone()),
+			])
+			.select(file_path::select!({ id pub_id materialized_path }))
+			.exec()
+			.await?;
+
+		for child in children {
+			let Some(materialized_path) = child
+				.materialized_path
+				.as_deref()
+				.and_then(|materialized_path| materialized_path.strip_prefix(&old_prefix))
+				.map(|rest| format!("{new_prefix}{rest}"))
+			else {
+				continue;
+			};
+
+			sync_ops.push(sync.shared_update(
+				prisma_sync::file_path::SyncId {
+					pub_id: child.pub_id,
+				},
+				file_path::materialized_path::NAME,
+				json!(materialized_path),
+			));
+			db_updates.push(db.file_path().update(
+				file_path::id::equals(child.id),
+				vec![file_path::materialized_path::set(Some(materialized_path))],
+			));
+		}
+	}
+
+	sync.write_ops(db, (sync_ops, db_updates)).await?;
+
+	// A new extension can mean another kind of object
+	if let Some(object) = file_path
+		.object
+		.as_ref()
+		.filter(|_| !is_dir && old_iso_file_path.extension() != new_parts.extension)
+	{
+		let kind = Extension::resolve_conflicting(&new_path, false)
+			.await
+			.map(Into::into)
+			.unwrap_or(ObjectKind::Unknown) as i32;
+
+		if object.kind != Some(kind) {
+			sync.write_op(
+				db,
+				sync.shared_update(
+					prisma_sync::object::SyncId {
+						pub_id: object.pub_id.clone(),
+					},
+					object::kind::NAME,
+					json!(kind),
+				),
+				db.object().update(
+					object::id::equals(object.id),
+					vec![object::kind::set(Some(kind))],
+				),
+			)
+			.await?;
+		}
+	}
+
+	debug!(
+		"Renamed <file_path_id={file_path_id}> from '{}' to '{new_name}'",
+		old_iso_file_path.full_name()
+	);
+
+	invalidate_query!(library, "search.paths");
+	invalidate_query!(library, "search.objects");
+
+	Ok(())
+}
@@ -0,0 +1,269 @@
+use crate::{library::Library, location::get_location_path_from_location_id};
+
+use sd_file_ext::{
+	extensions::{CodeExtension, ConfigExtension, TextExtension},
+	text::is_text,
+};
+use sd_file_path_helper::{file_path_to_isolate, IsolatedFilePathData};
+use sd_prisma::prisma::file_path;
+use sd_utils::error::FileIOError;
+
+use std::str::FromStr;
+
+use serde::Serialize;
+use specta::Type;
+use tokio::{fs::File, io::AsyncReadExt};
+
+use super::error::FileSystemJobsError;
+
+/// The most a preview reads, whatever the client asks for.
+pub const MAX_TEXT_PREVIEW_BYTES: u32 = 256 * 1024;
+
+#[derive(Serialize, Type, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+	Utf8,
+	Utf16Le,
+	Utf16Be,
+	Utf32Le,
+	Utf32Be,
+	Latin1,
+}
+
+#[derive(Serialize, Type, Debug, PartialEq, Eq)]
+pub struct TextPreview {
+	/// `None` for binary files, as decoding them would only give garbage.
+	pub text: Option<String>,
+	pub encoding: Option<TextEncoding>,
+	pub binary: bool,
+	/// Whether the file goes on after the previewed text.
+	pub truncated: bool,
+	/// What to highlight the text as, from the file's extension.
+	pub language: Option<String>,
+}
+
+/// Reads the head of a file to preview it as text, so the whole file doesn't have to be fetched
+/// just to show its first lines.
+pub async fn text_preview(
+	library: &Library,
+	file_path_id: file_path::id::Type,
+	max_bytes: u32,
+) -> Result<TextPreview, FileSystemJobsError> {
+	let Library { db, .. } = library;
+
+	let iso_file_path = IsolatedFilePathData::try_from(
+		db.file_path()
+			.find_unique(file_path::id::equals(file_path_id))
+			.select(file_path_to_isolate::select())
+			.exec()
+			.await?
+			.ok_or(FileSystemJobsError::FilePathIdNotFound(file_path_id))?,
+	)?;
+
+	let path = get_location_path_from_location_id(db, iso_file_path.location_id())
+		.await?
+		.join(&iso_file_path);
+
+	let file = File::open(&path)
+		.await
+		.map_err(|e| FileIOError::from((&path, e)))?;
+	let len = file
+		.metadata()
+		.await
+		.map_err(|e| FileIOError::from((&path, e)))?
+		.len();
+
+	let max_bytes = preview_len(max_bytes);
+
+	let mut head = Vec::with_capacity(len.min(u64::from(max_bytes)) as usize);
+	file.take(u64::from(max_bytes))
+		.read_to_end(&mut head)
+		.await
+		.map_err(|e| FileIOError::from((&path, e)))?;
+
+	let (text, encoding) = match decode(&head) {
+		Some((text, encoding)) => (Some(text), Some(encoding)),
+		None => (None, None),
+	};
+
+	Ok(TextPreview {
+		binary: text.is_none(),
+		text,
+		encoding,
+		truncated: len > head.len() as u64,
+		language: language_hint(iso_file_path.extension()),
+	})
+}
+
+/// Decodes text in the encoding it looks to be in, or `None` if it doesn't look like text at all.
+fn decode(bytes: &[u8]) -> Option<(String, TextEncoding)> {
+	if bytes.is_empty() {
+		return Some((String::new(), TextEncoding::Utf8));
+	}
+
+	Some(match is_text(bytes, false)? {
+		"utf-8" => {
+			let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+			// A character can be cut off at the end of what was read
+			let bytes = match std::str::from_utf8(bytes) {
+				Err(e) if e.error_len().is_none() => &bytes[..e.valid_up_to()],
+				_ => bytes,
+			};
+
+			(
+				String::from_utf8_lossy(bytes).into_owned(),
+				TextEncoding::Utf8,
+			)
+		}
+		"utf-16le" => (
+			decode_utf16(bytes, u16::from_le_bytes),
+			TextEncoding::Utf16Le,
+		),
+		"utf-16be" => (
+			decode_utf16(bytes, u16::from_be_bytes),
+			TextEncoding::Utf16Be,
+		),
+		"utf-32le" => (
+			decode_utf32(bytes, u32::from_le_bytes),
+			TextEncoding::Utf32Le,
+		),
+		"utf-32be" => (
+			decode_utf32(bytes, u32::from_be_bytes),
+			TextEncoding::Utf32Be,
+		),
+		// Every byte is the character with the same code point
+		_ => (
+			bytes.iter().copied().map(char::from).collect(),
+			TextEncoding::Latin1,
+		),
+	})
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+	char::decode_utf16(
+		bytes
+			.chunks_exact(2)
+			.map(|unit| from_bytes([unit[0], unit[1]]))
+			// Byte order mark
+			.skip(1),
+	)
+	.map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+	.collect()
+}
+
+fn decode_utf32(bytes: &[u8], from_bytes: fn([u8; 4]) -> u32) -> String {
+	bytes
+		.chunks_exact(4)
+		.map(|unit| from_bytes([unit[0], unit[1], unit[2], unit[3]]))
+		// Byte order mark
+		.skip(1)
+		.map(|c| char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER))
+		.collect()
+}
+
+/// Same language names as the interface's text viewer, which are the extensions themselves
+/// unless Prism knows the language by another name.
+fn language_hint(extension: &str) -> Option<String> {
+	use CodeExtension::*;
+
+	if let Ok(code) = CodeExtension::from_str(extension) {
+		return Some(
+			match code {
+				Scpt | Scptd => "applescript",
+				Zsh | Fish => "sh",
+				H => "c",
+				Hpp => "cpp",
+				Mjs => "js",
+				Cr => "crystal",
+				Csx => "cs",
+				Make => "makefile",
+				Nims => "nim",
+				M | Mm => "objc",
+				Ml | Mli | Mll | Mly => "ocaml",
+				Pl => "perl",
+				Php | Php1 | Php2 | Php3 | Php4 | Php5 | Php6 | Phps | Phpt | Phtml => "php",
+				Ps1 | Psd1 | Psm1 => "powershell",
+				Rs => "rust",
+				_ => return Some(code.to_string()),
+			}
+			.to_string(),
+		);
+	}
+
+	if let Ok(config) = ConfigExtension::from_str(extension) {
+		return Some(config.to_string());
+	}
+
+	match TextExtension::from_str(extension) {
+		Ok(TextExtension::Md | TextExtension::Markdown) => Some("markdown".to_string()),
+		_ => None,
+	}
+}
+
+/// How many bytes are read for a preview of at most `max_bytes`: whole code units, so UTF-16 and
+/// UTF-32 aren't cut in the middle of one, and at least one of them.
+fn preview_len(max_bytes: u32) -> u32 {
+	max_bytes.clamp(4, MAX_TEXT_PREVIEW_BYTES) & !3
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_decode() {
+		assert_eq!(
+			decode("Olá, mundo!\n".as_bytes()),
+			Some(("Olá, mundo!\n".to_string(), TextEncoding::Utf8))
+		);
+
+		// With a byte order mark and the last character cut in half
+		let mut utf8 = b"\xEF\xBB\xBF".to_vec();
+		utf8.extend_from_slice("açã".as_bytes());
+		utf8.pop();
+		assert_eq!(decode(&utf8), Some(("aç".to_string(), TextEncoding::Utf8)));
+
+		let utf16 = [0xFF, 0xFE, b'h', 0, b'i', 0, 0x3D, 0xD8, 0x00, 0xDE];
+		assert_eq!(
+			decode(&utf16),
+			Some(("hi😀".to_string(), TextEncoding::Utf16Le))
+		);
+
+		let utf16 = [0xFE, 0xFF, 0, b'h', 0, b'i'];
+		assert_eq!(
+			decode(&utf16),
+			Some(("hi".to_string(), TextEncoding::Utf16Be))
+		);
+
+		assert_eq!(
+			decode(b"caf\xE9 cr\xE8me"),
+			Some(("café crème".to_string(), TextEncoding::Latin1))
+		);
+	}
+
+	#[test]
+	fn test_decode_binary() {
+		assert_eq!(decode(b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR"), None);
+		assert_eq!(decode(&[0, 1, 2, 3, 4, 5, 6, 7]), None);
+	}
+
+	#[test]
+	fn test_preview_len() {
+		assert_eq!(preview_len(0), 4);
+		assert_eq!(preview_len(3), 4);
+		assert_eq!(preview_len(7), 4);
+		assert_eq!(preview_len(1024), 1024);
+		assert_eq!(preview_len(u32::MAX), MAX_TEXT_PREVIEW_BYTES);
+	}
+
+	#[test]
+	fn test_language_hint() {
+		assert_eq!(language_hint("rs").as_deref(), Some("rust"));
+		assert_eq!(language_hint("RS").as_deref(), Some("rust"));
+		assert_eq!(language_hint("ts").as_deref(), Some("ts"));
+		assert_eq!(language_hint("toml").as_deref(), Some("toml"));
+		assert_eq!(language_hint("md").as_deref(), Some("markdown"));
+		assert_eq!(language_hint("txt"), None);
+		assert_eq!(language_hint("png"), None);
+	}
+}
@@ -3,7 +3,7 @@ use crate::job::JobRunErrors;
 use sd_file_ext::extensions::{Extension, ImageExtension, ALL_IMAGE_EXTENSIONS};
 use sd_file_path_helper::{file_path_for_media_processor, IsolatedFilePathData};
 use sd_media_metadata::ImageMetadata;
-use sd_prisma::prisma::{location, media_data, PrismaClient};
+use sd_prisma::prisma::{location, media_data, object, PrismaClient};
 use sd_utils::error::FileIOError;
 
 use std::{collections::HashSet, path::Path};
@@ -62,6 +62,29 @@ pub async fn extract_media_data(path: impl AsRef<Path>) -> Result<ImageMetadata,
 		.map_err(Into::into)
 }
 
+/// Extract and save the media data for a single object, returning whether the file had any.
+pub async fn process_single(
+	path: impl AsRef<Path>,
+	object_id: object::id::Type,
+	db: &PrismaClient,
+) -> Result<bool, MediaDataError> {
+	let media_data = match extract_media_data(path).await {
+		Ok(media_data) => media_data,
+		Err(MediaDataError::MediaData(sd_media_metadata::Error::NoExifDataOnPath(_))) => {
+			return Ok(false)
+		}
+		Err(e) => return Err(e),
+	};
+
+	db.media_data()
+		.create_many(vec![media_data_image_to_query(media_data, object_id)?])
+		.skip_duplicates()
+		.exec()
+		.await?;
+
+	Ok(true)
+}
+
 pub async fn process(
 	files_paths: &[file_path_for_media_processor::Data],
 	location_id: location::id::Type,
@@ -17,6 +17,9 @@ use sd_file_path_helper::{
 	file_path_for_media_processor, IsolatedFilePathData,
 };
 use sd_prisma::prisma::{location, PrismaClient};
+
+#[cfg(feature = "ai")]
+use sd_prisma::prisma::{file_path, label_on_object, object};
 use sd_utils::db::maybe_missing;
 
 #[cfg(feature = "ai")]
@@ -25,6 +28,9 @@ use sd_ai::image_labeler::{BatchToken as ImageLabelerBatchToken, LabelerOutput};
 #[cfg(feature = "ai")]
 use std::sync::Arc;
 
+#[cfg(feature = "ai")]
+use prisma_client_rust::or;
+
 use std::{
 	hash::Hash,
 	path::{Path, PathBuf},
@@ -55,6 +61,9 @@ pub struct MediaProcessorJobInit {
 	pub sub_path: Option<PathBuf>,
 	pub regenerate_thumbnails: bool,
 	pub regenerate_labels: bool,
+	/// When relabeling, drop labels which weren't detected again with at least this confidence.
+	#[serde(default)]
+	pub labels_min_confidence: Option<f64>,
 }
 
 impl Hash for MediaProcessorJobInit {
@@ -145,15 +154,20 @@ impl StatefulJob for MediaProcessorJobInit {
 			"Searching for media files in location {location_id} at directory \"{iso_file_path}\""
 		);
 
-		let thumbs_to_process_count = dispatch_thumbnails_for_processing(
-			location_id,
-			&location_path,
-			&iso_file_path,
-			&ctx.library,
-			&ctx.node,
-			self.regenerate_thumbnails,
-		)
-		.await?;
+		let thumbs_to_process_count = if self.location.generate_preview_media.unwrap_or(true) {
+			dispatch_thumbnails_for_processing(
+				location_id,
+				&location_path,
+				&iso_file_path,
+				&ctx.library,
+				&ctx.node,
+				self.regenerate_thumbnails,
+			)
+			.await?
+		} else {
+			debug!("Skipping thumbnails for location {location_id} as preview media is disabled");
+			0
+		};
 
 		let maybe_thumbnailer_progress_rx = if thumbs_to_process_count > 0 {
 			let (progress_tx, progress_rx) = chan::unbounded();
@@ -177,6 +191,18 @@ impl StatefulJob for MediaProcessorJobInit {
 		#[cfg(feature = "ai")]
 		let total_files_for_labeling = file_paths_for_labeling.len();
 
+		// Labels which aren't detected again will keep a `null` confidence and get pruned once labeling is done
+		#[cfg(feature = "ai")]
+		if self.labels_min_confidence.is_some() && total_files_for_labeling > 0 {
+			db.label_on_object()
+				.update_many(
+					labels_in_location(location_id),
+					vec![label_on_object::confidence::set(None)],
+				)
+				.exec()
+				.await?;
+		}
+
 		#[cfg(feature = "ai")]
 		let (labeler_batch_token, labels_rx) = ctx
 			.node
@@ -224,8 +250,8 @@ impl StatefulJob for MediaProcessorJobInit {
 			.collect::<Vec<_>>();
 
 		ctx.progress(vec![
-			JobReportUpdate::TaskCount(total_files),
 			JobReportUpdate::Phase("media_data".to_string()),
+			JobReportUpdate::TaskCount(total_files),
 			JobReportUpdate::Message(format!(
 				"Preparing to process {total_files} files in {} chunks",
 				chunked_files.len()
@@ -277,8 +303,8 @@ impl StatefulJob for MediaProcessorJobInit {
 
 			MediaProcessorJobStep::WaitThumbnails(total_thumbs) => {
 				ctx.progress(vec![
-					JobReportUpdate::TaskCount(*total_thumbs),
 					JobReportUpdate::Phase("thumbnails".to_string()),
+					JobReportUpdate::TaskCount(*total_thumbs),
 					JobReportUpdate::Message(format!(
 						"Waiting for processing of {total_thumbs} thumbnails",
 					)),
@@ -323,8 +349,8 @@ impl StatefulJob for MediaProcessorJobInit {
 			#[cfg(feature = "ai")]
 			MediaProcessorJobStep::WaitLabels(total_labels) => {
 				ctx.progress(vec![
-					JobReportUpdate::TaskCount(*total_labels),
 					JobReportUpdate::Phase("labels".to_string()),
+					JobReportUpdate::TaskCount(*total_labels),
 					JobReportUpdate::Message(
 						format!("Extracting labels for {total_labels} files",),
 					),
@@ -369,7 +395,28 @@ impl StatefulJob for MediaProcessorJobInit {
 					}
 				}
 
+				if let Some(min_confidence) = self.labels_min_confidence {
+					let pruned = ctx
+						.library
+						.db
+						.label_on_object()
+						.delete_many(
+							labels_in_location(self.location.id)
+								.into_iter()
+								.chain([or![
+									label_on_object::confidence::equals(None),
+									label_on_object::confidence::lt(min_confidence),
+								]])
+								.collect(),
+						)
+						.exec()
+						.await?;
+
+					debug!("Pruned {pruned} labels below {min_confidence} confidence");
+				}
+
 				invalidate_query!(&ctx.library, "labels.list");
+				invalidate_query!(&ctx.library, "labels.listWithThumbnails");
 				invalidate_query!(&ctx.library, "labels.getForObject");
 				invalidate_query!(&ctx.library, "labels.getWithObjects");
 
@@ -494,6 +541,13 @@ async fn get_files_for_media_data_extraction(
 	.map_err(Into::into)
 }
 
+#[cfg(feature = "ai")]
+fn labels_in_location(location_id: location::id::Type) -> Vec<label_on_object::WhereParam> {
+	vec![label_on_object::object::is(vec![object::file_paths::some(
+		vec![file_path::location_id::equals(Some(location_id))],
+	)])]
+}
+
 #[cfg(feature = "ai")]
 async fn get_files_for_labeling(
 	db: &PrismaClient,
@@ -2,9 +2,11 @@ use crate::job::{JobRunErrors, JobRunMetadata};
 
 use sd_file_path_helper::{file_path_for_media_processor, FilePathError};
 use sd_prisma::prisma::{location, PrismaClient};
+use sd_utils::db::MissingFieldError;
 
 use std::path::Path;
 
+use rspc::ErrorCode;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::error;
@@ -18,17 +20,21 @@ mod job;
 mod shallow;
 
 pub use job::MediaProcessorJobInit;
-pub use shallow::shallow;
+pub use shallow::{prewarm_thumbnails, shallow};
 
 #[derive(Error, Debug)]
 pub enum MediaProcessorError {
 	#[error("sub path not found: <path='{}'>", .0.display())]
 	SubPathNotFound(Box<Path>),
+	#[error("preview media is disabled for location <id='{0}'>")]
+	PreviewMediaDisabled(location::id::Type),
 
 	#[error("database error: {0}")]
 	Database(#[from] prisma_client_rust::QueryError),
 	#[error(transparent)]
 	FilePath(#[from] FilePathError),
+	#[error("missing-field: {0}")]
+	MissingField(#[from] MissingFieldError),
 
 	#[error(transparent)]
 	Thumbnailer(#[from] ThumbnailerError),
@@ -36,6 +42,29 @@ pub enum MediaProcessorError {
 	MediaDataExtractor(#[from] MediaDataError),
 }
 
+impl From<MediaProcessorError> for rspc::Error {
+	fn from(err: MediaProcessorError) -> Self {
+		use MediaProcessorError::*;
+
+		match err {
+			SubPathNotFound(_)
+			| FilePath(FilePathError::IdNotFound(_) | FilePathError::NotFound(_)) => {
+				Self::with_cause(ErrorCode::NotFound, err.to_string(), err)
+			}
+
+			PreviewMediaDisabled(_)
+			| FilePath(
+				FilePathError::InvalidSubPath { .. }
+				| FilePathError::SubPathNotDirectory(_)
+				| FilePathError::SubPathParentNotInLocation { .. },
+			) => Self::with_cause(ErrorCode::BadRequest, err.to_string(), err),
+
+			MissingField(missing_error) => missing_error.into(),
+			_ => Self::with_cause(ErrorCode::InternalServerError, err.to_string(), err),
+		}
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct MediaProcessorMetadata {
 	media_data: MediaDataExtractorMetadata,
@@ -25,6 +25,7 @@ use std::sync::Arc;
 use itertools::Itertools;
 use prisma_client_rust::{raw, PrismaValue};
 use tracing::{debug, error};
+use uuid::Uuid;
 
 #[cfg(feature = "ai")]
 use futures::StreamExt;
@@ -47,43 +48,24 @@ pub async fn shallow(
 	let location_id = location.id;
 	let location_path = maybe_missing(&location.path, "location.path").map(PathBuf::from)?;
 
-	let iso_file_path = if sub_path != Path::new("") {
-		let full_path = ensure_sub_path_is_in_location(&location_path, &sub_path)
-			.await
-			.map_err(MediaProcessorError::from)?;
-		ensure_sub_path_is_directory(&location_path, &sub_path)
-			.await
-			.map_err(MediaProcessorError::from)?;
+	let iso_file_path =
+		get_sub_path_iso_file_path(location_id, &location_path, sub_path, db).await?;
 
-		let sub_iso_file_path =
-			IsolatedFilePathData::new(location_id, &location_path, &full_path, true)
-				.map_err(MediaProcessorError::from)?;
+	debug!("Searching for media in location {location_id} at path {iso_file_path}");
 
-		ensure_file_path_exists(
-			&sub_path,
-			&sub_iso_file_path,
-			db,
-			MediaProcessorError::SubPathNotFound,
+	if location.generate_preview_media.unwrap_or(true) {
+		dispatch_thumbnails_for_processing(
+			location.id,
+			&location_path,
+			&iso_file_path,
+			library,
+			node,
+			false,
 		)
 		.await?;
-
-		sub_iso_file_path
 	} else {
-		IsolatedFilePathData::new(location_id, &location_path, &location_path, true)
-			.map_err(MediaProcessorError::from)?
-	};
-
-	debug!("Searching for media in location {location_id} at path {iso_file_path}");
-
-	dispatch_thumbnails_for_processing(
-		location.id,
-		&location_path,
-		&iso_file_path,
-		library,
-		node,
-		false,
-	)
-	.await?;
+		debug!("Skipping thumbnails for location {location_id} as preview media is disabled");
+	}
 
 	let file_paths = get_files_for_media_data_extraction(db, &iso_file_path).await?;
 
@@ -170,6 +152,71 @@ pub async fn shallow(
 	Ok(())
 }
 
+/// Generates the thumbnails of the files directly in `sub_path` in foreground, returning the id of
+/// the batch to follow its progress with [`Thumbnailer::prewarm_batch_progress`].
+///
+/// [`Thumbnailer::prewarm_batch_progress`]: thumbnail::actor::Thumbnailer::prewarm_batch_progress
+pub async fn prewarm_thumbnails(
+	location: &location::Data,
+	sub_path: impl AsRef<Path>,
+	library: &Library,
+	node: &Node,
+) -> Result<Uuid, MediaProcessorError> {
+	let location_id = location.id;
+
+	if !location.generate_preview_media.unwrap_or(true) {
+		return Err(MediaProcessorError::PreviewMediaDisabled(location_id));
+	}
+
+	let location_path = maybe_missing(&location.path, "location.path").map(PathBuf::from)?;
+
+	let iso_file_path =
+		get_sub_path_iso_file_path(location_id, &location_path, sub_path, &library.db).await?;
+
+	let batch =
+		get_thumbnails_batch(location_id, &location_path, &iso_file_path, &library.db).await?;
+
+	debug!(
+		"Prewarming {} thumbnails in location {location_id} at path {iso_file_path}",
+		batch.len()
+	);
+
+	Ok(node
+		.thumbnailer
+		.new_indexed_thumbnails_prewarm_batch(BatchToProcess::new(batch, false, false), library.id)
+		.await)
+}
+
+async fn get_sub_path_iso_file_path(
+	location_id: location::id::Type,
+	location_path: &Path,
+	sub_path: impl AsRef<Path>,
+	db: &PrismaClient,
+) -> Result<IsolatedFilePathData<'static>, MediaProcessorError> {
+	let sub_path = sub_path.as_ref();
+
+	if sub_path != Path::new("") {
+		let full_path = ensure_sub_path_is_in_location(location_path, sub_path).await?;
+		ensure_sub_path_is_directory(location_path, sub_path).await?;
+
+		let sub_iso_file_path =
+			IsolatedFilePathData::new(location_id, location_path, &full_path, true)?;
+
+		ensure_file_path_exists(
+			sub_path,
+			&sub_iso_file_path,
+			db,
+			MediaProcessorError::SubPathNotFound,
+		)
+		.await?;
+
+		Ok(sub_iso_file_path)
+	} else {
+		IsolatedFilePathData::new(location_id, location_path, location_path, true)
+			.map_err(Into::into)
+	}
+}
+
 async fn get_files_for_media_data_extraction(
 	db: &PrismaClient,
 	parent_iso_file_path: &IsolatedFilePathData<'_>,
@@ -232,8 +279,33 @@ async fn dispatch_thumbnails_for_processing(
 	node: &Node,
 	should_regenerate: bool,
 ) -> Result<(), MediaProcessorError> {
-	let Library { db, .. } = library;
+	let current_batch = get_thumbnails_batch(
+		location_id,
+		location_path,
+		parent_iso_file_path,
+		&library.db,
+	)
+	.await?;
 
+	// Let's not send an empty batch lol
+	if !current_batch.is_empty() {
+		node.thumbnailer
+			.new_indexed_thumbnails_batch(
+				BatchToProcess::new(current_batch, should_regenerate, false),
+				library.id,
+			)
+			.await;
+	}
+
+	Ok(())
+}
+
+async fn get_thumbnails_batch(
+	location_id: location::id::Type,
+	location_path: impl AsRef<Path>,
+	parent_iso_file_path: &IsolatedFilePathData<'_>,
+	db: &PrismaClient,
+) -> Result<Vec<GenerateThumbnailArgs>, MediaProcessorError> {
 	let location_path = location_path.as_ref();
 
 	let file_paths = get_files_by_extensions(
@@ -243,7 +315,7 @@ async fn dispatch_thumbnails_for_processing(
 	)
 	.await?;
 
-	let current_batch = file_paths
+	Ok(file_paths
 		.into_iter()
 		.filter_map(|file_path| {
 			if let Some(cas_id) = file_path.cas_id.as_ref() {
@@ -267,19 +339,7 @@ async fn dispatch_thumbnails_for_processing(
 
 			GenerateThumbnailArgs::new(iso_file_path.extension().to_string(), cas_id, full_path)
 		})
-		.collect::<Vec<_>>();
-
-	// Let's not send an empty batch lol
-	if !current_batch.is_empty() {
-		node.thumbnailer
-			.new_indexed_thumbnails_batch(
-				BatchToProcess::new(current_batch, should_regenerate, false),
-				library.id,
-			)
-			.await;
-	}
-
-	Ok(())
+		.collect())
 }
 
 async fn get_files_by_extensions(
@@ -3,9 +3,11 @@ pub mod media_processor;
 pub mod thumbnail;
 
 pub use media_processor::MediaProcessorJobInit;
-use sd_media_metadata::ImageMetadata;
+use sd_media_metadata::{image::MediaDate, ImageMetadata};
 use sd_prisma::prisma::media_data::*;
 
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime};
+
 use self::media_data_extractor::MediaDataError;
 
 pub fn media_data_image_to_query(
@@ -60,6 +62,28 @@ pub fn media_data_image_from_prisma_data(
 	})
 }
 
+/// When a media file was captured, in the node's timezone.
+///
+/// It's the date from its media data, else a date found in its file name, else when the file was
+/// created. Dates without a timezone are kept as they are, being in the timezone of the camera.
+#[must_use]
+pub fn capture_time(
+	media_date: Option<Vec<u8>>,
+	file_name: &str,
+	date_created: Option<DateTime<FixedOffset>>,
+) -> Option<NaiveDateTime> {
+	from_slice_option_to_option::<Option<MediaDate>>(media_date)
+		.flatten()
+		.or_else(|| MediaDate::from_file_name(file_name))
+		.map(|media_date| match media_date {
+			MediaDate::Naive(date_time) => date_time,
+			MediaDate::Utc(date_time) => date_time.with_timezone(&Local).naive_local(),
+		})
+		.or_else(|| {
+			date_created.map(|date_created| date_created.with_timezone(&Local).naive_local())
+		})
+}
+
 #[must_use]
 fn from_slice_option_to_option<T: serde::Serialize + serde::de::DeserializeOwned>(
 	value: Option<Vec<u8>>,
@@ -1,7 +1,6 @@
 use crate::{
-	api::CoreEvent,
 	library::{Libraries, LibraryId, LibraryManagerEvent},
-	node::config::NodePreferences,
+	node::{config::NodePreferences, EventBus},
 };
 
 use sd_prisma::prisma::{location, PrismaClient};
@@ -13,11 +12,14 @@ use std::{
 };
 
 use async_channel as chan;
+use mini_moka::sync::Cache;
 use once_cell::sync::OnceCell;
+use serde::Serialize;
+use specta::Type;
 use thiserror::Error;
 use tokio::{
 	fs, spawn,
-	sync::{broadcast, oneshot, watch, Mutex},
+	sync::{oneshot, watch, Mutex},
 	time::{sleep, Instant},
 };
 use tracing::{error, trace};
@@ -25,14 +27,20 @@ use uuid::Uuid;
 
 use super::{
 	directory::init_thumbnail_dir,
+	find_thumbnail_format,
 	process::{generate_thumbnail, ThumbData},
-	state::RegisterReporter,
+	state::{QueuedThumbnails, RegisterReporter},
+	thumbnail_path_stem,
 	worker::{worker, WorkerChannels},
-	BatchToProcess, ThumbnailKind, ThumbnailerError, ONE_SEC, THUMBNAIL_CACHE_DIR_NAME,
+	BatchToProcess, ThumbnailFormat, ThumbnailKind, ThumbnailStatus, ThumbnailerError, ONE_SEC,
+	THUMBNAIL_CACHE_DIR_NAME,
 };
 
 static AVAILABLE_PARALLELISM: OnceCell<usize> = OnceCell::new();
 
+/// How many prewarm batches we keep the progress of, finished ones included.
+const PREWARM_BATCHES_CAPACITY: u64 = 64;
+
 #[derive(Error, Debug)]
 pub(super) enum ActorError {
 	#[error("database error")]
@@ -56,25 +64,65 @@ pub(super) enum DatabaseMessage {
 // ├── thumbs_to_process.bin # processing save state
 // ├── ephemeral/ # ephemeral ones have it's own directory
 // │  └── <cas_id>[0..3]/ # sharding
-// │     └── <cas_id>.webp
+// │     └── <cas_id>.<webp|jpg|png> # in the format preferred when it was generated
 // └── <library_id>/ # we segregate thumbnails by library
 //    └── <cas_id>[0..3]/ # sharding
-//       └── <cas_id>.webp
+//       └── <cas_id>.<webp|jpg|png>
 pub struct Thumbnailer {
 	thumbnails_directory: Arc<PathBuf>,
 	cas_ids_to_delete_tx: chan::Sender<(Vec<String>, ThumbnailKind)>,
 	thumbnails_to_generate_tx: chan::Sender<(BatchToProcess, ThumbnailKind)>,
 	progress_reporter_tx: chan::Sender<RegisterReporter>,
 	last_single_thumb_generated: Mutex<Instant>,
-	reporter: broadcast::Sender<CoreEvent>,
+	reporter: EventBus,
 	cancel_tx: chan::Sender<oneshot::Sender<()>>,
+	prewarm_batches: Cache<Uuid, watch::Receiver<BatchProgress>>,
+	queued_thumbnails: Arc<QueuedThumbnails>,
+	node_preferences_rx: watch::Receiver<NodePreferences>,
+}
+
+/// Progress of a batch sent with [`Thumbnailer::new_indexed_thumbnails_prewarm_batch`].
+#[derive(Debug, Clone, Copy, Serialize, Type)]
+pub struct BatchProgress {
+	pub completed: u32,
+	pub total: u32,
+	pub percentage: u8,
+}
+
+impl BatchProgress {
+	fn new(total: u32) -> Self {
+		let mut progress = Self {
+			completed: 0,
+			total,
+			percentage: 0,
+		};
+		progress.update_percentage();
+		progress
+	}
+
+	fn add_completed(&mut self) {
+		self.completed += 1;
+		self.update_percentage();
+	}
+
+	fn update_percentage(&mut self) {
+		self.percentage = if self.total == 0 {
+			100
+		} else {
+			(u64::from(self.completed) * 100 / u64::from(self.total)) as u8
+		};
+	}
+
+	pub fn is_done(&self) -> bool {
+		self.completed >= self.total
+	}
 }
 
 impl Thumbnailer {
 	pub async fn new(
 		data_dir: impl AsRef<Path>,
 		libraries_manager: Arc<Libraries>,
-		reporter: broadcast::Sender<CoreEvent>,
+		reporter: EventBus,
 		node_preferences_rx: watch::Receiver<NodePreferences>,
 	) -> Self {
 		let data_dir = data_dir.as_ref();
@@ -94,6 +142,8 @@ impl Thumbnailer {
 		let (cas_ids_to_delete_tx, cas_ids_to_delete_rx) = chan::bounded(16);
 		let (cancel_tx, cancel_rx) = chan::bounded(1);
 
+		let queued_thumbnails = Arc::new(QueuedThumbnails::default());
+
 		AVAILABLE_PARALLELISM
 			.set(std::thread::available_parallelism().map_or_else(
 				|e| {
@@ -108,6 +158,7 @@ impl Thumbnailer {
 			let progress_management_rx = progress_management_rx.clone();
 			let cancel_rx = cancel_rx.clone();
 			let thumbnails_directory = Arc::clone(&thumbnails_directory);
+			let queued_thumbnails = Arc::clone(&queued_thumbnails);
 			let reporter = reporter.clone();
 			let node_preferences = node_preferences_rx.clone();
 
@@ -119,6 +170,7 @@ impl Thumbnailer {
 					node_preferences.clone(),
 					reporter.clone(),
 					thumbnails_directory.clone(),
+					Arc::clone(&queued_thumbnails),
 					WorkerChannels {
 						progress_management_rx: progress_management_rx.clone(),
 						databases_rx: databases_rx.clone(),
@@ -203,12 +255,17 @@ impl Thumbnailer {
 			last_single_thumb_generated: Mutex::new(Instant::now()),
 			reporter,
 			cancel_tx,
+			prewarm_batches: Cache::new(PREWARM_BATCHES_CAPACITY),
+			queued_thumbnails,
+			node_preferences_rx,
 		}
 	}
 
 	#[inline]
 	async fn new_batch(&self, batch: BatchToProcess, kind: ThumbnailKind) {
 		if !batch.batch.is_empty() {
+			self.queued_thumbnails.add(&batch, kind);
+
 			self.thumbnails_to_generate_tx
 				.send((batch, kind))
 				.await
@@ -242,6 +299,86 @@ impl Thumbnailer {
 			.await;
 	}
 
+	/// Generates the thumbnails in foreground, tracking the batch's progress under the returned id.
+	///
+	/// The progress is lost if the thumbnailer shuts down before it's done, the leftovers are
+	/// processed in background later.
+	pub async fn new_indexed_thumbnails_prewarm_batch(
+		&self,
+		mut batch: BatchToProcess,
+		library_id: LibraryId,
+	) -> Uuid {
+		let batch_id = Uuid::new_v4();
+
+		let (progress_tx, progress_rx) = chan::unbounded();
+		let (watch_tx, watch_rx) = watch::channel(BatchProgress::new(batch.batch.len() as u32));
+
+		batch.in_background = false;
+		batch.progress_tx = Some(progress_tx);
+
+		self.prewarm_batches.insert(batch_id, watch_rx);
+
+		// Finishes once the batch is processed, and the batch processor drops the sender
+		spawn(async move {
+			while progress_rx.recv().await.is_ok() {
+				watch_tx.send_modify(BatchProgress::add_completed);
+			}
+		});
+
+		self.new_batch(batch, ThumbnailKind::Indexed(library_id))
+			.await;
+
+		batch_id
+	}
+
+	/// Progress of a batch sent with [`Self::new_indexed_thumbnails_prewarm_batch`], if it's
+	/// still being tracked.
+	pub fn prewarm_batch_progress(
+		&self,
+		batch_id: &Uuid,
+	) -> Option<watch::Receiver<BatchProgress>> {
+		self.prewarm_batches.get(batch_id)
+	}
+
+	/// The format new thumbnails are generated in, the thumbnail keys handed out point to it.
+	pub fn format(&self) -> ThumbnailFormat {
+		self.node_preferences_rx
+			.borrow()
+			.thumbnailer
+			.thumbnail_format()
+	}
+
+	/// Whether the thumbnail of `cas_id` is already generated, in any format, or waiting in a
+	/// batch to be.
+	pub async fn status(
+		&self,
+		cas_id: &str,
+		kind: ThumbnailKind,
+	) -> Result<ThumbnailStatus, FileIOError> {
+		let thumb_path_stem = thumbnail_path_stem(&self.thumbnails_directory, cas_id, kind);
+
+		// Checking the queue after the disk, as a thumbnail is only dequeued once it's written
+		let status = if find_thumbnail_format(&thumb_path_stem).await?.is_some() {
+			ThumbnailStatus::Ready
+		} else if self.queued_thumbnails.contains(cas_id, kind) {
+			ThumbnailStatus::Queued
+		} else {
+			ThumbnailStatus::None
+		};
+
+		Ok(status)
+	}
+
+	#[inline]
+	pub async fn indexed_status(
+		&self,
+		cas_id: &str,
+		library_id: LibraryId,
+	) -> Result<ThumbnailStatus, FileIOError> {
+		self.status(cas_id, ThumbnailKind::Indexed(library_id))
+			.await
+	}
+
 	#[inline]
 	pub async fn register_reporter(
 		&self,
@@ -322,6 +459,7 @@ impl Thumbnailer {
 				in_background: false,
 				should_regenerate: false,
 				kind,
+				format: self.format(),
 			},
 			self.reporter.clone(),
 		)
@@ -3,13 +3,26 @@ use crate::library::LibraryId;
 use sd_prisma::prisma::{file_path, PrismaClient};
 use sd_utils::error::FileIOError;
 
-use std::{collections::HashSet, ffi::OsString, path::PathBuf, sync::Arc};
+use std::{
+	collections::HashSet,
+	ffi::OsString,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 
 use futures_concurrency::future::Join;
 use tokio::{fs, spawn};
 use tracing::{debug, error};
 
-use super::{ThumbnailerError, EPHEMERAL_DIR, WEBP_EXTENSION};
+use super::{owner_thumbnail_name, ThumbnailFormat, ThumbnailerError, EPHEMERAL_DIR};
+
+/// Thumbnails in any format are cleaned up, not only in the one new thumbnails are generated in
+fn is_thumbnail(path: &Path) -> bool {
+	path.extension()
+		.and_then(|extension| extension.to_str())
+		.and_then(ThumbnailFormat::from_extension)
+		.is_some()
+}
 
 pub(super) async fn process_ephemeral_clean_up(
 	thumbnails_directory: Arc<PathBuf>,
@@ -46,8 +59,10 @@ pub(super) async fn process_ephemeral_clean_up(
 					.map_err(|e| FileIOError::from((&shard_path, e)))?
 				{
 					let thumb_path = thumb_entry.path();
-					if thumb_path.extension() == Some(WEBP_EXTENSION.as_ref())
-						&& !existing_ephemeral_thumbs.contains(&thumb_entry.file_name())
+					if is_thumbnail(&thumb_path)
+						&& thumb_path
+							.file_stem()
+							.is_some_and(|stem| !existing_ephemeral_thumbs.contains(stem))
 					{
 						to_remove.push(async move {
 							debug!(
@@ -99,12 +114,7 @@ pub(super) async fn process_indexed_clean_up(
 					.exec()
 					.await?
 					.into_iter()
-					.map(|file_path| {
-						OsString::from(format!(
-							"{}.webp",
-							file_path.cas_id.expect("we filtered right")
-						))
-					})
+					.map(|file_path| OsString::from(file_path.cas_id.expect("we filtered right")))
 					.collect::<HashSet<_>>();
 
 				let mut read_library_thumbs_dir = fs::read_dir(&library_thumbs_dir)
@@ -135,9 +145,10 @@ pub(super) async fn process_indexed_clean_up(
 							.map_err(|e| FileIOError::from((&shard_path, e)))?
 						{
 							let thumb_path = thumb_entry.path();
-							if thumb_path.extension() == Some(WEBP_EXTENSION.as_ref())
-								&& !existing_thumbs.contains(&thumb_entry.file_name())
-							{
+							if is_thumbnail(&thumb_path)
+								&& thumb_path.file_stem().is_some_and(|stem| {
+									!existing_thumbs.contains(owner_thumbnail_name(stem))
+								}) {
 								to_remove.push(async move {
 									debug!(
 										"Removing stale indexed thumbnail: {}",
@@ -9,14 +9,16 @@ use sd_utils::error::FileIOError;
 use sd_file_ext::extensions::{VideoExtension, ALL_VIDEO_EXTENSIONS};
 
 use std::{
+	ffi::OsStr,
 	path::{Path, PathBuf},
 	time::Duration,
 };
 
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use specta::Type;
 use thiserror::Error;
-use tokio::task;
+use tokio::{fs, io, task};
 use tracing::error;
 
 pub mod actor;
@@ -28,7 +30,10 @@ mod shard;
 mod state;
 mod worker;
 
-pub use process::{BatchToProcess, GenerateThumbnailArgs};
+pub use preferences::ThumbnailFormat;
+pub use process::{
+	generate_custom_thumbnail, generate_pdf_page_thumbnail, BatchToProcess, GenerateThumbnailArgs,
+};
 pub use shard::get_shard_hex;
 
 use directory::ThumbnailVersion;
@@ -37,8 +42,13 @@ use directory::ThumbnailVersion;
 const THUMBNAIL_CACHE_DIR_NAME: &str = "thumbnails";
 const SAVE_STATE_FILE: &str = "thumbs_to_process.bin";
 const VERSION_FILE: &str = "version.txt";
-pub const WEBP_EXTENSION: &str = "webp";
+const WEBP_EXTENSION: &str = "webp";
 const EPHEMERAL_DIR: &str = "ephemeral";
+/// Thumbnails of document pages are stored next to the document's own one, as `<cas_id>-p<page>`
+const PAGE_THUMBNAIL_SEPARATOR: &str = "-p";
+/// Thumbnails set by the user are stored next to the generated one, as `<cas_id>-custom`, so
+/// regenerating it never overwrites them
+const CUSTOM_THUMBNAIL_SUFFIX: &str = "-custom";
 
 /// This is the target pixel count for all thumbnails to be resized to, and it is eventually downscaled
 /// to [`TARGET_QUALITY`].
@@ -53,52 +63,234 @@ const ONE_SEC: Duration = Duration::from_secs(1);
 const THIRTY_SECS: Duration = Duration::from_secs(30);
 const HALF_HOUR: Duration = Duration::from_secs(30 * 60);
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ThumbnailKind {
 	Ephemeral,
 	Indexed(LibraryId),
 }
 
-pub fn get_indexed_thumbnail_path(node: &Node, cas_id: &str, library_id: LibraryId) -> PathBuf {
-	get_thumbnail_path(node, cas_id, ThumbnailKind::Indexed(library_id))
+/// Whether the thumbnail of an explorer item is generated, waiting to be, or neither.
+#[derive(Debug, Clone, Copy, Serialize, Type, PartialEq, Eq)]
+pub enum ThumbnailStatus {
+	None,
+	Queued,
+	Ready,
 }
 
-/// This does not check if a thumbnail exists, it just returns the path that it would exist at
-fn get_thumbnail_path(node: &Node, cas_id: &str, kind: ThumbnailKind) -> PathBuf {
-	let mut thumb_path = node.config.data_directory();
+/// This does not check if a thumbnail exists, it just returns the path that it would exist at,
+/// without the extension of its format
+fn thumbnail_path_stem(thumbnails_directory: &Path, name: &str, kind: ThumbnailKind) -> PathBuf {
+	let mut thumb_path = thumbnails_directory.to_path_buf();
 
-	thumb_path.push(THUMBNAIL_CACHE_DIR_NAME);
 	match kind {
 		ThumbnailKind::Ephemeral => thumb_path.push(EPHEMERAL_DIR),
 		ThumbnailKind::Indexed(library_id) => {
 			thumb_path.push(library_id.to_string());
 		}
 	}
-	thumb_path.push(get_shard_hex(cas_id));
-	thumb_path.push(cas_id);
-	thumb_path.set_extension(WEBP_EXTENSION);
+	thumb_path.push(get_shard_hex(name));
+	thumb_path.push(name);
 
 	thumb_path
 }
 
-pub fn get_indexed_thumb_key(cas_id: &str, library_id: LibraryId) -> Vec<String> {
-	get_thumb_key(cas_id, ThumbnailKind::Indexed(library_id))
+fn indexed_thumbnail_path_stem(node: &Node, name: &str, library_id: LibraryId) -> PathBuf {
+	thumbnail_path_stem(
+		&node.config.data_directory().join(THUMBNAIL_CACHE_DIR_NAME),
+		name,
+		ThumbnailKind::Indexed(library_id),
+	)
+}
+
+/// Which format the thumbnail at `thumb_path_stem` was generated in, if it was, as changing the
+/// preferred format leaves the ones already generated in theirs
+async fn find_thumbnail_format(
+	thumb_path_stem: &Path,
+) -> Result<Option<ThumbnailFormat>, FileIOError> {
+	for format in ThumbnailFormat::ALL {
+		let thumb_path = thumb_path_stem.with_extension(format.extension());
+		match fs::metadata(&thumb_path).await {
+			Ok(_) => return Ok(Some(format)),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+			Err(e) => return Err(FileIOError::from((thumb_path, e))),
+		}
+	}
+
+	Ok(None)
+}
+
+/// Removes the thumbnail at `thumb_path_stem` in every format but `keep`
+async fn remove_thumbnail_formats(
+	thumb_path_stem: &Path,
+	keep: Option<ThumbnailFormat>,
+) -> Result<(), FileIOError> {
+	for format in ThumbnailFormat::ALL {
+		if Some(format) == keep {
+			continue;
+		}
+
+		let thumb_path = thumb_path_stem.with_extension(format.extension());
+		match fs::remove_file(&thumb_path).await {
+			Ok(()) => {}
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(FileIOError::from((thumb_path, e))),
+		}
+	}
+
+	Ok(())
+}
+
+pub async fn find_indexed_thumbnail_format(
+	node: &Node,
+	cas_id: &str,
+	library_id: LibraryId,
+) -> Result<Option<ThumbnailFormat>, FileIOError> {
+	find_thumbnail_format(&indexed_thumbnail_path_stem(node, cas_id, library_id)).await
+}
+
+pub async fn remove_indexed_thumbnail(
+	node: &Node,
+	cas_id: &str,
+	library_id: LibraryId,
+) -> Result<(), FileIOError> {
+	remove_thumbnail_formats(&indexed_thumbnail_path_stem(node, cas_id, library_id), None).await
+}
+
+pub fn get_indexed_page_thumbnail_path(
+	node: &Node,
+	cas_id: &str,
+	page: u32,
+	library_id: LibraryId,
+	format: ThumbnailFormat,
+) -> PathBuf {
+	indexed_thumbnail_path_stem(node, &page_thumbnail_name(cas_id, page), library_id)
+		.with_extension(format.extension())
+}
+
+pub async fn find_indexed_page_thumbnail_format(
+	node: &Node,
+	cas_id: &str,
+	page: u32,
+	library_id: LibraryId,
+) -> Result<Option<ThumbnailFormat>, FileIOError> {
+	find_thumbnail_format(&indexed_thumbnail_path_stem(
+		node,
+		&page_thumbnail_name(cas_id, page),
+		library_id,
+	))
+	.await
+}
+
+pub fn get_indexed_custom_thumbnail_path(
+	node: &Node,
+	cas_id: &str,
+	library_id: LibraryId,
+	format: ThumbnailFormat,
+) -> PathBuf {
+	indexed_thumbnail_path_stem(node, &custom_thumbnail_name(cas_id), library_id)
+		.with_extension(format.extension())
+}
+
+pub async fn find_indexed_custom_thumbnail_format(
+	node: &Node,
+	cas_id: &str,
+	library_id: LibraryId,
+) -> Result<Option<ThumbnailFormat>, FileIOError> {
+	find_thumbnail_format(&indexed_thumbnail_path_stem(
+		node,
+		&custom_thumbnail_name(cas_id),
+		library_id,
+	))
+	.await
+}
+
+/// Removes the custom thumbnail in every format but `keep`, so only the latest one set is served
+pub async fn remove_indexed_custom_thumbnail(
+	node: &Node,
+	cas_id: &str,
+	library_id: LibraryId,
+	keep: Option<ThumbnailFormat>,
+) -> Result<(), FileIOError> {
+	remove_thumbnail_formats(
+		&indexed_thumbnail_path_stem(node, &custom_thumbnail_name(cas_id), library_id),
+		keep,
+	)
+	.await
+}
+
+/// The name a page thumbnail is stored by, which keeps the shard of the document's cas_id
+fn page_thumbnail_name(cas_id: &str, page: u32) -> String {
+	format!("{cas_id}{PAGE_THUMBNAIL_SEPARATOR}{page}")
+}
+
+/// The name a custom thumbnail is stored by, which keeps the shard of the file's cas_id
+fn custom_thumbnail_name(cas_id: &str) -> String {
+	format!("{cas_id}{CUSTOM_THUMBNAIL_SUFFIX}")
 }
 
-pub fn get_ephemeral_thumb_key(cas_id: &str) -> Vec<String> {
-	get_thumb_key(cas_id, ThumbnailKind::Ephemeral)
+/// The name of the thumbnail a page or custom thumbnail belongs to, from its file stem, so they're
+/// cleaned up together
+fn owner_thumbnail_name(file_stem: &OsStr) -> &OsStr {
+	let Some(stem) = file_stem.to_str() else {
+		return file_stem;
+	};
+
+	if let Some(cas_id) = stem.strip_suffix(CUSTOM_THUMBNAIL_SUFFIX) {
+		return OsStr::new(cas_id);
+	}
+
+	stem.rsplit_once(PAGE_THUMBNAIL_SEPARATOR)
+		.filter(|(_, page)| !page.is_empty() && page.chars().all(|c| c.is_ascii_digit()))
+		.map_or(file_stem, |(cas_id, _)| OsStr::new(cas_id))
+}
+
+pub fn get_indexed_thumb_key(
+	cas_id: &str,
+	library_id: LibraryId,
+	format: ThumbnailFormat,
+) -> Vec<String> {
+	get_thumb_key(cas_id, ThumbnailKind::Indexed(library_id), format)
+}
+
+pub fn get_indexed_page_thumb_key(
+	cas_id: &str,
+	page: u32,
+	library_id: LibraryId,
+	format: ThumbnailFormat,
+) -> Vec<String> {
+	get_thumb_key(
+		&page_thumbnail_name(cas_id, page),
+		ThumbnailKind::Indexed(library_id),
+		format,
+	)
+}
+
+pub fn get_indexed_custom_thumb_key(
+	cas_id: &str,
+	library_id: LibraryId,
+	format: ThumbnailFormat,
+) -> Vec<String> {
+	get_thumb_key(
+		&custom_thumbnail_name(cas_id),
+		ThumbnailKind::Indexed(library_id),
+		format,
+	)
+}
+
+pub fn get_ephemeral_thumb_key(cas_id: &str, format: ThumbnailFormat) -> Vec<String> {
+	get_thumb_key(cas_id, ThumbnailKind::Ephemeral, format)
 }
 
 // this is used to pass the relevant data to the frontend so it can request the thumbnail
 // it supports extending the shard hex to support deeper directory structures in the future
-fn get_thumb_key(cas_id: &str, kind: ThumbnailKind) -> Vec<String> {
+fn get_thumb_key(cas_id: &str, kind: ThumbnailKind, format: ThumbnailFormat) -> Vec<String> {
 	vec![
 		match kind {
 			ThumbnailKind::Ephemeral => String::from(EPHEMERAL_DIR),
 			ThumbnailKind::Indexed(library_id) => library_id.to_string(),
 		},
 		get_shard_hex(cas_id).to_string(),
-		cas_id.to_string(),
+		format!("{cas_id}.{}", format.extension()),
 	]
 }
 
@@ -151,6 +343,12 @@ pub enum ThumbnailerError {
 	VersionManager(#[from] VersionManagerError<ThumbnailVersion>),
 	#[error("failed to encode webp")]
 	WebPEncoding { path: Box<Path>, reason: String },
+	#[error("failed to encode {format:?} thumbnail: {error}")]
+	Encoding {
+		path: Box<Path>,
+		format: ThumbnailFormat,
+		error: image::ImageError,
+	},
 	#[error("error while converting the image")]
 	SdImages {
 		path: Box<Path>,
@@ -161,6 +359,8 @@ pub enum ThumbnailerError {
 	#[cfg(feature = "ffmpeg")]
 	#[error(transparent)]
 	FFmpeg(#[from] sd_ffmpeg::Error),
+	#[error("failed to decode the custom thumbnail: {0}")]
+	CustomThumbnail(image::ImageError),
 	#[error("thumbnail generation timed out for {}", .0.display())]
 	TimedOut(Box<Path>),
 }
@@ -188,9 +388,18 @@ pub const fn can_generate_thumbnail_for_video(video_extension: &VideoExtension)
 pub const fn can_generate_thumbnail_for_image(image_extension: &ImageExtension) -> bool {
 	use ImageExtension::*;
 
+	// These are decoded with libheif, which is only linked with the `heif` feature
+	#[cfg(feature = "heif")]
+	if matches!(
+		image_extension,
+		Heic | Heics | Heif | Heifs | Hif | Avif | Avci | Avcs
+	) {
+		return true;
+	}
+
 	matches!(
 		image_extension,
-		Jpg | Jpeg | Png | Webp | Gif | Svg | Heic | Heics | Heif | Heifs | Avif | Bmp | Ico
+		Jpg | Jpeg | Png | Webp | Gif | Svg | Bmp | Ico | Tiff
 	)
 }
 
@@ -199,3 +408,51 @@ pub const fn can_generate_thumbnail_for_document(document_extension: &DocumentEx
 
 	matches!(document_extension, Pdf)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_owner_thumbnail_name() {
+		assert_eq!(
+			owner_thumbnail_name(OsStr::new("0123456789abcdef-p12")),
+			OsStr::new("0123456789abcdef")
+		);
+		assert_eq!(
+			owner_thumbnail_name(OsStr::new("0123456789abcdef")),
+			OsStr::new("0123456789abcdef")
+		);
+		assert_eq!(
+			owner_thumbnail_name(OsStr::new("0123456789abcdef-pages")),
+			OsStr::new("0123456789abcdef-pages")
+		);
+		assert_eq!(
+			owner_thumbnail_name(OsStr::new("0123456789abcdef-custom")),
+			OsStr::new("0123456789abcdef")
+		);
+	}
+
+	#[test]
+	fn test_thumb_key_extension() {
+		let library_id = LibraryId::new_v4();
+
+		assert_eq!(
+			get_indexed_thumb_key("0123456789abcdef", library_id, ThumbnailFormat::Jpeg),
+			vec![
+				library_id.to_string(),
+				get_shard_hex("0123456789abcdef").to_string(),
+				"0123456789abcdef.jpg".to_string()
+			]
+		);
+		assert_eq!(
+			get_ephemeral_thumb_key("0123456789abcdef", ThumbnailFormat::WebP)[2],
+			"0123456789abcdef.webp"
+		);
+		assert_eq!(
+			ThumbnailFormat::from_extension("png"),
+			Some(ThumbnailFormat::Png)
+		);
+		assert_eq!(ThumbnailFormat::from_extension("gif"), None);
+	}
+}
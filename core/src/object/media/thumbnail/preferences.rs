@@ -1,15 +1,53 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+/// The image format new thumbnails are encoded in, the ones already generated stay in theirs.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, Type)]
+pub enum ThumbnailFormat {
+	#[default]
+	WebP,
+	Jpeg,
+	Png,
+}
+
+impl ThumbnailFormat {
+	pub const ALL: [Self; 3] = [Self::WebP, Self::Jpeg, Self::Png];
+
+	pub const fn extension(self) -> &'static str {
+		match self {
+			Self::WebP => "webp",
+			Self::Jpeg => "jpg",
+			Self::Png => "png",
+		}
+	}
+
+	pub const fn content_type(self) -> &'static str {
+		match self {
+			Self::WebP => "image/webp",
+			Self::Jpeg => "image/jpeg",
+			Self::Png => "image/png",
+		}
+	}
+
+	pub fn from_extension(extension: &str) -> Option<Self> {
+		Self::ALL
+			.into_iter()
+			.find(|format| format.extension() == extension)
+	}
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Type)]
 pub struct ThumbnailerPreferences {
 	background_processing_percentage: u8, // 0-100
+	#[serde(default)]
+	thumbnail_format: ThumbnailFormat,
 }
 
 impl Default for ThumbnailerPreferences {
 	fn default() -> Self {
 		Self {
 			background_processing_percentage: 50, // 50% of CPU cores available
+			thumbnail_format: ThumbnailFormat::default(),
 		}
 	}
 }
@@ -31,4 +69,14 @@ impl ThumbnailerPreferences {
 
 		self
 	}
+
+	pub fn thumbnail_format(&self) -> ThumbnailFormat {
+		self.thumbnail_format
+	}
+
+	pub fn set_thumbnail_format(&mut self, thumbnail_format: ThumbnailFormat) -> &mut Self {
+		self.thumbnail_format = thumbnail_format;
+
+		self
+	}
 }
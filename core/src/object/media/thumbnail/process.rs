@@ -1,7 +1,7 @@
-use crate::api::CoreEvent;
+use crate::{api::CoreEvent, node::EventBus};
 
 use sd_file_ext::extensions::{DocumentExtension, ImageExtension};
-use sd_images::{format_image, scale_dimensions, ConvertableExtension};
+use sd_images::{format_image, format_pdf_page, scale_dimensions, ConvertableExtension};
 use sd_media_metadata::image::Orientation;
 use sd_prisma::prisma::location;
 use sd_utils::error::FileIOError;
@@ -9,6 +9,7 @@ use sd_utils::error::FileIOError;
 use std::{
 	collections::VecDeque,
 	ffi::OsString,
+	io::Cursor,
 	ops::Deref,
 	path::{Path, PathBuf},
 	str::FromStr,
@@ -17,11 +18,11 @@ use std::{
 
 use async_channel as chan;
 use futures_concurrency::future::{Join, Race};
-use image::{self, imageops, DynamicImage, GenericImageView};
+use image::{self, imageops, DynamicImage, GenericImageView, ImageOutputFormat};
 use serde::{Deserialize, Serialize};
 use tokio::{
-	fs, io,
-	sync::{broadcast, oneshot, Semaphore},
+	fs,
+	sync::{oneshot, Semaphore},
 	task::{spawn, spawn_blocking},
 	time::timeout,
 };
@@ -30,9 +31,10 @@ use tracing::{debug, error, trace, warn};
 use webp::Encoder;
 
 use super::{
-	can_generate_thumbnail_for_document, can_generate_thumbnail_for_image, get_thumb_key,
-	preferences::ThumbnailerPreferences, shard::get_shard_hex, ThumbnailKind, ThumbnailerError,
-	EPHEMERAL_DIR, TARGET_PX, TARGET_QUALITY, THIRTY_SECS, WEBP_EXTENSION,
+	can_generate_thumbnail_for_document, can_generate_thumbnail_for_image, find_thumbnail_format,
+	get_thumb_key, preferences::ThumbnailerPreferences, remove_thumbnail_formats,
+	state::QueuedThumbnails, thumbnail_path_stem, ThumbnailFormat, ThumbnailKind, ThumbnailerError,
+	TARGET_PX, TARGET_QUALITY, THIRTY_SECS,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,6 +60,9 @@ pub struct BatchToProcess {
 	pub(super) should_regenerate: bool,
 	pub(super) in_background: bool,
 	pub(super) location_id: Option<location::id::Type>,
+	// Notified for each processed thumbnail, it can't be saved to disk so it's lost on shutdown
+	#[serde(skip)]
+	pub(super) progress_tx: Option<chan::Sender<()>>,
 }
 
 impl BatchToProcess {
@@ -71,6 +76,7 @@ impl BatchToProcess {
 			should_regenerate,
 			in_background,
 			location_id: None,
+			progress_tx: None,
 		}
 	}
 }
@@ -83,12 +89,14 @@ pub(super) struct ProcessorControlChannels {
 
 pub(super) async fn batch_processor(
 	thumbnails_directory: Arc<PathBuf>,
+	queued_thumbnails: Arc<QueuedThumbnails>,
 	(
 		BatchToProcess {
 			batch,
 			should_regenerate,
 			in_background,
 			location_id,
+			progress_tx,
 		},
 		kind,
 	): (BatchToProcess, ThumbnailKind),
@@ -99,7 +107,7 @@ pub(super) async fn batch_processor(
 		batch_report_progress_tx,
 	}: ProcessorControlChannels,
 	leftovers_tx: chan::Sender<(BatchToProcess, ThumbnailKind)>,
-	reporter: broadcast::Sender<CoreEvent>,
+	reporter: EventBus,
 	(available_parallelism, thumbnailer_preferences): (usize, ThumbnailerPreferences),
 ) {
 	let in_parallel_count = if !in_background {
@@ -125,6 +133,8 @@ pub(super) async fn batch_processor(
 		},
 	);
 
+	let format = thumbnailer_preferences.thumbnail_format();
+
 	let semaphore = Arc::new(Semaphore::new(in_parallel_count));
 
 	let batch_size = batch.len();
@@ -166,6 +176,9 @@ pub(super) async fn batch_processor(
 					let reporter = reporter.clone();
 					let thumbnails_directory = thumbnails_directory.as_ref().clone();
 					let report_progress_tx = batch_report_progress_tx.clone();
+					let maybe_progress_tx = progress_tx.clone();
+					let queued_thumbnails = Arc::clone(&queued_thumbnails);
+					let queued_cas_id = cas_id.clone();
 					let maybe_cas_ids_tx = maybe_cas_ids_tx.clone();
 
 					async move {
@@ -179,6 +192,7 @@ pub(super) async fn batch_processor(
 									in_background,
 									should_regenerate,
 									kind,
+									format,
 								},
 								reporter,
 							)
@@ -188,10 +202,7 @@ pub(super) async fn batch_processor(
 								// the same capacity as the batch size, so there is always a space
 								// in the queue
 								if let Some(cas_ids_tx) = maybe_cas_ids_tx {
-									if cas_ids_tx
-										.send_blocking(OsString::from(format!("{}.webp", cas_id)))
-										.is_err()
-									{
+									if cas_ids_tx.send_blocking(OsString::from(cas_id)).is_err() {
 										warn!("No one to listen to generated ephemeral thumbnail cas id");
 									}
 								}
@@ -202,10 +213,16 @@ pub(super) async fn batch_processor(
 							Err(ThumbnailerError::TimedOut(path.into_boxed_path()))
 						});
 
+						queued_thumbnails.remove(queued_cas_id, kind);
+
 						if let Some(location_id) = location_id {
 							report_progress_tx.send((location_id, 1)).await.ok();
 						}
 
+						if let Some(progress_tx) = maybe_progress_tx {
+							progress_tx.send(()).await.ok();
+						}
+
 						drop(permit);
 
 						res
@@ -267,6 +284,7 @@ pub(super) async fn batch_processor(
 						should_regenerate,
 						in_background: true, // Leftovers should always be in background
 						location_id,
+						progress_tx,
 					},
 					kind,
 				))
@@ -311,6 +329,7 @@ pub(super) struct ThumbData<'ext, P: AsRef<Path>> {
 	pub in_background: bool,
 	pub should_regenerate: bool,
 	pub kind: ThumbnailKind,
+	pub format: ThumbnailFormat,
 }
 
 pub(super) async fn generate_thumbnail(
@@ -322,43 +341,41 @@ pub(super) async fn generate_thumbnail(
 		in_background,
 		should_regenerate,
 		kind,
+		format,
 	}: ThumbData<'_, impl AsRef<Path>>,
-	reporter: broadcast::Sender<CoreEvent>,
+	reporter: EventBus,
 ) -> Result<String, ThumbnailerError> {
 	let path = path.as_ref();
 	trace!("Generating thumbnail for {}", path.display());
 
-	let mut output_path = thumbnails_directory;
-	match kind {
-		ThumbnailKind::Ephemeral => output_path.push(EPHEMERAL_DIR),
-		ThumbnailKind::Indexed(library_id) => output_path.push(library_id.to_string()),
-	};
-	output_path.push(get_shard_hex(&cas_id));
-	output_path.push(&cas_id);
-	output_path.set_extension(WEBP_EXTENSION);
+	let thumb_path_stem = thumbnail_path_stem(&thumbnails_directory, &cas_id, kind);
+	let output_path = thumb_path_stem.with_extension(format.extension());
 
-	if let Err(e) = fs::metadata(&output_path).await {
-		if e.kind() != io::ErrorKind::NotFound {
+	// A thumbnail in another format than the preferred one is still good, unless regenerating
+	match find_thumbnail_format(&thumb_path_stem).await {
+		Ok(Some(_)) if !should_regenerate => {
+			trace!(
+				"Skipping thumbnail generation for {} because it already exists",
+				path.display()
+			);
+			return Ok(cas_id);
+		}
+		// Otherwise we good, thumbnail doesn't exist so we can generate it
+		Ok(_) => {}
+		Err(e) => {
 			error!(
 				"Failed to check if thumbnail exists, but we will try to generate it anyway: {e:#?}"
 			);
 		}
-	// Otherwise we good, thumbnail doesn't exist so we can generate it
-	} else if !should_regenerate {
-		trace!(
-			"Skipping thumbnail generation for {} because it already exists",
-			path.display()
-		);
-		return Ok(cas_id);
 	}
 
 	if let Ok(extension) = ImageExtension::from_str(extension) {
 		if can_generate_thumbnail_for_image(&extension) {
-			generate_image_thumbnail(&path, &output_path).await?;
+			generate_image_thumbnail(&path, &output_path, format).await?;
 		}
 	} else if let Ok(extension) = DocumentExtension::from_str(extension) {
 		if can_generate_thumbnail_for_document(&extension) {
-			generate_image_thumbnail(&path, &output_path).await?;
+			generate_image_thumbnail(&path, &output_path, format).await?;
 		}
 	}
 
@@ -369,21 +386,21 @@ pub(super) async fn generate_thumbnail(
 
 		if let Ok(extension) = VideoExtension::from_str(extension) {
 			if can_generate_thumbnail_for_video(&extension) {
-				generate_video_thumbnail(&path, &output_path).await?;
+				generate_video_thumbnail(&path, &output_path, format).await?;
 			}
 		}
 	}
 
+	// So the regenerated thumbnail isn't shadowed by the one it replaces in another format
+	if should_regenerate {
+		remove_thumbnail_formats(&thumb_path_stem, Some(format)).await?;
+	}
+
 	if !in_background {
 		trace!("Emitting new thumbnail event");
-		if reporter
-			.send(CoreEvent::NewThumbnail {
-				thumb_key: get_thumb_key(&cas_id, kind),
-			})
-			.is_err()
-		{
-			warn!("Error sending event to Node's event bus");
-		}
+		reporter.send(CoreEvent::NewThumbnail {
+			thumb_key: get_thumb_key(&cas_id, kind, format),
+		});
 	}
 
 	trace!("Generated thumbnail for {}", path.display());
@@ -394,10 +411,11 @@ pub(super) async fn generate_thumbnail(
 async fn generate_image_thumbnail(
 	file_path: impl AsRef<Path>,
 	output_path: impl AsRef<Path>,
+	format: ThumbnailFormat,
 ) -> Result<(), ThumbnailerError> {
 	let file_path = file_path.as_ref().to_path_buf();
 
-	let webp = spawn_blocking(move || -> Result<_, ThumbnailerError> {
+	let thumbnail = spawn_blocking(move || -> Result<_, ThumbnailerError> {
 		let mut img = format_image(&file_path).map_err(|e| ThumbnailerError::SdImages {
 			path: file_path.clone().into_boxed_path(),
 			error: e,
@@ -427,22 +445,124 @@ async fn generate_image_thumbnail(
 			}
 		}
 
-		// Create the WebP encoder for the above image
-		let encoder =
-			Encoder::from_image(&img).map_err(|reason| ThumbnailerError::WebPEncoding {
-				path: file_path.into_boxed_path(),
-				reason: reason.to_string(),
+		encode_thumbnail(&img, file_path, format)
+	})
+	.await??;
+
+	write_thumbnail(output_path.as_ref(), &thumbnail).await
+}
+
+/// Generates the thumbnail of a page of a PDF document, by its zero-based index, as it isn't
+/// done by the thumbnailer, which only generates the one of the first page.
+pub async fn generate_pdf_page_thumbnail(
+	file_path: impl AsRef<Path>,
+	page_index: u16,
+	output_path: impl AsRef<Path>,
+	format: ThumbnailFormat,
+) -> Result<(), ThumbnailerError> {
+	let file_path = file_path.as_ref().to_path_buf();
+
+	let thumbnail = spawn_blocking(move || -> Result<_, ThumbnailerError> {
+		let mut img =
+			format_pdf_page(&file_path, page_index).map_err(|e| ThumbnailerError::SdImages {
+				path: file_path.clone().into_boxed_path(),
+				error: e,
 			})?;
 
-		// Type WebPMemory is !Send, which makes the Future in this function !Send,
-		// this make us `deref` to have a `&[u8]` and then `to_owned` to make a Vec<u8>
-		// which implies on a unwanted clone...
-		Ok(encoder.encode(TARGET_QUALITY).deref().to_owned())
+		let (w, h) = img.dimensions();
+		let (w_scaled, h_scaled) = scale_dimensions(w as f32, h as f32, TARGET_PX);
+
+		if w != w_scaled && h != h_scaled {
+			img = DynamicImage::ImageRgba8(imageops::resize(
+				&img,
+				w_scaled,
+				h_scaled,
+				imageops::FilterType::Triangle,
+			));
+		}
+
+		encode_thumbnail(&img, file_path, format)
 	})
 	.await??;
 
-	let output_path = output_path.as_ref();
+	write_thumbnail(output_path.as_ref(), &thumbnail).await
+}
 
+/// Generates a thumbnail from an image the user provided, to be used instead of the generated one
+pub async fn generate_custom_thumbnail(
+	image_bytes: Vec<u8>,
+	output_path: impl AsRef<Path>,
+	format: ThumbnailFormat,
+) -> Result<(), ThumbnailerError> {
+	let output_path = output_path.as_ref().to_path_buf();
+
+	let thumbnail = spawn_blocking({
+		let output_path = output_path.clone();
+
+		move || -> Result<_, ThumbnailerError> {
+			let mut img =
+				image::load_from_memory(&image_bytes).map_err(ThumbnailerError::CustomThumbnail)?;
+
+			let (w, h) = img.dimensions();
+			let (w_scaled, h_scaled) = scale_dimensions(w as f32, h as f32, TARGET_PX);
+
+			if w != w_scaled && h != h_scaled {
+				img = DynamicImage::ImageRgba8(imageops::resize(
+					&img,
+					w_scaled,
+					h_scaled,
+					imageops::FilterType::Triangle,
+				));
+			}
+
+			encode_thumbnail(&img, output_path, format)
+		}
+	})
+	.await??;
+
+	write_thumbnail(&output_path, &thumbnail).await
+}
+
+fn encode_thumbnail(
+	img: &DynamicImage,
+	file_path: PathBuf,
+	format: ThumbnailFormat,
+) -> Result<Vec<u8>, ThumbnailerError> {
+	let (img, output_format) = match format {
+		ThumbnailFormat::WebP => return encode_webp(img, file_path),
+		// JPEG has no alpha channel
+		ThumbnailFormat::Jpeg => (
+			DynamicImage::ImageRgb8(img.to_rgb8()),
+			ImageOutputFormat::Jpeg(TARGET_QUALITY as u8),
+		),
+		ThumbnailFormat::Png => (img.clone(), ImageOutputFormat::Png),
+	};
+
+	let mut bytes = Cursor::new(Vec::new());
+	img.write_to(&mut bytes, output_format)
+		.map_err(|error| ThumbnailerError::Encoding {
+			path: file_path.into_boxed_path(),
+			format,
+			error,
+		})?;
+
+	Ok(bytes.into_inner())
+}
+
+fn encode_webp(img: &DynamicImage, file_path: PathBuf) -> Result<Vec<u8>, ThumbnailerError> {
+	// Create the WebP encoder for the above image
+	let encoder = Encoder::from_image(img).map_err(|reason| ThumbnailerError::WebPEncoding {
+		path: file_path.into_boxed_path(),
+		reason: reason.to_string(),
+	})?;
+
+	// Type WebPMemory is !Send, which makes the Future in this function !Send,
+	// this make us `deref` to have a `&[u8]` and then `to_owned` to make a Vec<u8>
+	// which implies on a unwanted clone...
+	Ok(encoder.encode(TARGET_QUALITY).deref().to_owned())
+}
+
+async fn write_thumbnail(output_path: &Path, thumbnail: &[u8]) -> Result<(), ThumbnailerError> {
 	if let Some(shard_dir) = output_path.parent() {
 		fs::create_dir_all(shard_dir)
 			.await
@@ -454,7 +574,7 @@ async fn generate_image_thumbnail(
 		);
 	}
 
-	fs::write(output_path, &webp)
+	fs::write(output_path, thumbnail)
 		.await
 		.map_err(|e| FileIOError::from((output_path, e)))
 		.map_err(Into::into)
@@ -464,10 +584,65 @@ async fn generate_image_thumbnail(
 async fn generate_video_thumbnail(
 	file_path: impl AsRef<Path>,
 	output_path: impl AsRef<Path>,
+	format: ThumbnailFormat,
 ) -> Result<(), ThumbnailerError> {
-	use sd_ffmpeg::to_thumbnail;
+	use sd_ffmpeg::{to_thumbnail, ThumbnailerBuilder};
 
-	to_thumbnail(file_path, output_path, 256, TARGET_QUALITY)
-		.await
-		.map_err(Into::into)
+	if format == ThumbnailFormat::WebP {
+		return to_thumbnail(file_path, output_path, 256, TARGET_QUALITY)
+			.await
+			.map_err(Into::into);
+	}
+
+	// FFmpeg only gives WebP frames, so they're converted to the other formats
+	let file_path = file_path.as_ref().to_path_buf();
+	let webp = ThumbnailerBuilder::new()
+		.with_film_strip(false)
+		.size(256)
+		.quality(TARGET_QUALITY)?
+		.build()
+		.process_to_webp_bytes(&file_path)
+		.await?;
+
+	let thumbnail = spawn_blocking(move || -> Result<_, ThumbnailerError> {
+		let img = image::load_from_memory_with_format(&webp, image::ImageFormat::WebP).map_err(
+			|error| ThumbnailerError::Encoding {
+				path: file_path.clone().into_boxed_path(),
+				format,
+				error,
+			},
+		)?;
+
+		encode_thumbnail(&img, file_path, format)
+	})
+	.await??;
+
+	write_thumbnail(output_path.as_ref(), &thumbnail).await
+}
+
+#[cfg(all(test, feature = "heif"))]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn heic_to_webp_thumbnail() {
+		let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sample.heic");
+		let output_dir = std::env::temp_dir().join(format!("sd-thumb-{}", uuid::Uuid::new_v4()));
+		let output_path = output_dir
+			.join("sample")
+			.with_extension(ThumbnailFormat::WebP.extension());
+
+		assert!(can_generate_thumbnail_for_image(&ImageExtension::Heic));
+
+		generate_image_thumbnail(&fixture, &output_path, ThumbnailFormat::WebP)
+			.await
+			.unwrap();
+
+		let thumb = image::open(&output_path).unwrap();
+		fs::remove_dir_all(&output_dir).await.unwrap();
+
+		let (w, h) = thumb.dimensions();
+		assert!(w > 0 && h > 0);
+		assert!((w * h) as f32 <= TARGET_PX * 1.01);
+	}
 }
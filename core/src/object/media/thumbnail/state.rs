@@ -5,8 +5,9 @@ use sd_utils::error::FileIOError;
 
 use std::{
 	collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
-	ffi::OsString,
+	ffi::{OsStr, OsString},
 	path::Path,
+	sync::{Mutex, PoisonError},
 };
 
 use async_channel as chan;
@@ -16,12 +17,14 @@ use tokio::{fs, io};
 use tracing::{error, info, trace};
 
 use super::{
-	actor::ActorError, get_shard_hex, BatchToProcess, ThumbnailKind, EPHEMERAL_DIR, SAVE_STATE_FILE,
+	actor::ActorError, remove_thumbnail_formats, thumbnail_path_stem, BatchToProcess,
+	ThumbnailKind, SAVE_STATE_FILE,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(super) struct ThumbsProcessingSaveState {
 	pub(super) bookkeeper: BookKeeper,
+	/// Without extension, as the thumbnails can be in any [`ThumbnailFormat`](super::ThumbnailFormat)
 	pub(super) ephemeral_file_names: HashSet<OsString>,
 	// This queues doubles as LIFO and FIFO, assuming LIFO in case of users asking for a new batch
 	// by entering a new directory in the explorer, otherwise processing as FIFO
@@ -31,6 +34,33 @@ pub(super) struct ThumbsProcessingSaveState {
 	pub(super) ephemeral_leftovers_queue: VecDeque<BatchToProcess>,
 }
 
+/// Thumbnails sent to the thumbnailer which weren't generated yet, shared with the batch processor.
+#[derive(Debug, Default)]
+pub(super) struct QueuedThumbnails(Mutex<HashSet<(ThumbnailKind, String)>>);
+
+impl QueuedThumbnails {
+	pub(super) fn add(&self, batch: &BatchToProcess, kind: ThumbnailKind) {
+		self.0
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.extend(batch.batch.iter().map(|args| (kind, args.cas_id.clone())));
+	}
+
+	pub(super) fn remove(&self, cas_id: String, kind: ThumbnailKind) {
+		self.0
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&(kind, cas_id));
+	}
+
+	pub(super) fn contains(&self, cas_id: &str, kind: ThumbnailKind) -> bool {
+		self.0
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.contains(&(kind, cas_id.to_string()))
+	}
+}
+
 impl Default for ThumbsProcessingSaveState {
 	fn default() -> Self {
 		Self {
@@ -49,11 +79,22 @@ impl ThumbsProcessingSaveState {
 
 		match fs::read(&resume_file).await {
 			Ok(bytes) => {
-				let this = rmp_serde::from_slice::<Self>(&bytes).unwrap_or_else(|e| {
+				let mut this = rmp_serde::from_slice::<Self>(&bytes).unwrap_or_else(|e| {
 					error!("Failed to deserialize save state at thumbnailer actor: {e:#?}");
 					Self::default()
 				});
 
+				// States saved when every thumbnail was a WebP one have the extension in the names
+				this.ephemeral_file_names = this
+					.ephemeral_file_names
+					.into_iter()
+					.map(|file_name| {
+						Path::new(&file_name)
+							.file_stem()
+							.map_or_else(|| file_name.clone(), OsStr::to_os_string)
+					})
+					.collect();
+
 				if let Err(e) = fs::remove_file(&resume_file).await {
 					error!(
 						"Failed to remove save state file at thumbnailer actor: {:#?}",
@@ -118,25 +159,14 @@ pub(super) async fn remove_by_cas_ids(
 	cas_ids: Vec<String>,
 	kind: ThumbnailKind,
 ) -> Result<(), ActorError> {
-	let base_dir = match kind {
-		ThumbnailKind::Ephemeral => thumbnails_directory.join(EPHEMERAL_DIR),
-		ThumbnailKind::Indexed(library_id) => thumbnails_directory.join(library_id.to_string()),
-	};
-
 	cas_ids
 		.into_iter()
 		.map(|cas_id| {
-			let thumbnail_path = base_dir.join(format!("{}/{cas_id}.webp", get_shard_hex(&cas_id)));
+			let thumb_path_stem = thumbnail_path_stem(thumbnails_directory, &cas_id, kind);
 
-			trace!("Removing thumbnail: {}", thumbnail_path.display());
+			trace!("Removing thumbnail: {}", thumb_path_stem.display());
 
-			async move {
-				match fs::remove_file(&thumbnail_path).await {
-					Ok(()) => Ok(()),
-					Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
-					Err(e) => Err(FileIOError::from((thumbnail_path, e))),
-				}
-			}
+			async move { remove_thumbnail_formats(&thumb_path_stem, None).await }
 		})
 		.collect::<Vec<_>>()
 		.try_join()
@@ -1,4 +1,4 @@
-use crate::{api::CoreEvent, node::config::NodePreferences};
+use crate::node::{config::NodePreferences, EventBus};
 
 use sd_prisma::prisma::location;
 
@@ -8,7 +8,7 @@ use async_channel as chan;
 use futures_concurrency::stream::Merge;
 use tokio::{
 	spawn,
-	sync::{broadcast, oneshot, watch},
+	sync::{oneshot, watch},
 	time::{interval, interval_at, timeout, Instant, MissedTickBehavior},
 };
 use tokio_stream::{
@@ -22,7 +22,7 @@ use super::{
 	clean_up::{process_ephemeral_clean_up, process_indexed_clean_up},
 	preferences::ThumbnailerPreferences,
 	process::{batch_processor, ProcessorControlChannels},
-	state::{remove_by_cas_ids, RegisterReporter, ThumbsProcessingSaveState},
+	state::{remove_by_cas_ids, QueuedThumbnails, RegisterReporter, ThumbsProcessingSaveState},
 	BatchToProcess, ThumbnailKind, HALF_HOUR, ONE_SEC, THIRTY_SECS,
 };
 
@@ -38,8 +38,9 @@ pub(super) struct WorkerChannels {
 pub(super) async fn worker(
 	available_parallelism: usize,
 	node_preferences_rx: watch::Receiver<NodePreferences>,
-	reporter: broadcast::Sender<CoreEvent>,
+	reporter: EventBus,
 	thumbnails_directory: Arc<PathBuf>,
+	queued_thumbnails: Arc<QueuedThumbnails>,
 	WorkerChannels {
 		progress_management_rx,
 		databases_rx,
@@ -79,6 +80,22 @@ pub(super) async fn worker(
 		mut ephemeral_leftovers_queue,
 	} = ThumbsProcessingSaveState::load(thumbnails_directory.as_ref()).await;
 
+	// Batches resumed from the save state never went through the thumbnailer's `new_batch`
+	queue
+		.iter()
+		.map(|(batch, kind)| (batch, *kind))
+		.chain(
+			indexed_leftovers_queue
+				.iter()
+				.map(|(batch, library_id)| (batch, ThumbnailKind::Indexed(*library_id))),
+		)
+		.chain(
+			ephemeral_leftovers_queue
+				.iter()
+				.map(|batch| (batch, ThumbnailKind::Ephemeral)),
+		)
+		.for_each(|(batch, kind)| queued_thumbnails.add(batch, kind));
+
 	let (generated_ephemeral_thumbnails_tx, ephemeral_thumbnails_cas_ids_rx) = chan::bounded(32);
 	let (leftovers_tx, leftovers_rx) = chan::bounded(8);
 	let (batch_report_progress_tx, batch_report_progress_rx) = chan::bounded(8);
@@ -146,6 +163,7 @@ pub(super) async fn worker(
 
 					spawn(batch_processor(
 						thumbnails_directory.clone(),
+						Arc::clone(&queued_thumbnails),
 						batch_and_kind,
 						generated_ephemeral_thumbnails_tx.clone(),
 						ProcessorControlChannels {
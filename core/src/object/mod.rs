@@ -4,10 +4,12 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 
 pub mod cas;
+pub mod consolidator;
 pub mod file_identifier;
 pub mod fs;
 pub mod media;
 pub mod orphan_remover;
+pub mod recent;
 pub mod tag;
 pub mod validation;
 
@@ -18,7 +20,7 @@ pub mod validation;
 // Object selectables!
 object::select!(object_for_file_identifier {
 	pub_id
-	file_paths: select { pub_id cas_id extension is_dir materialized_path name }
+	file_paths: select { pub_id cas_id integrity_checksum extension is_dir materialized_path name }
 });
 
 // The response to provide the Explorer when looking at Objects
@@ -1,97 +1,356 @@
-use sd_prisma::prisma::{object, tag_on_object, PrismaClient};
-
-use std::{sync::Arc, time::Duration};
+use crate::{
+	invalidate_query,
+	job::{
+		CurrentStep, JobError, JobInitOutput, JobResult, JobRunMetadata, JobStepOutput,
+		StatefulJob, WorkerContext,
+	},
+	library::Library,
+};
 
-use tokio::{
-	select,
-	sync::mpsc,
-	time::{interval_at, Instant, MissedTickBehavior},
+use sd_prisma::{
+	prisma::{
+		label_on_object, location, object, object_in_album, object_in_space, tag_on_object,
+		PrismaClient,
+	},
+	prisma_sync,
 };
-use tracing::{error, trace};
+use sd_sync::OperationFactory;
+
+use std::hash::{Hash, Hasher};
+
+use prisma_client_rust::{and, or, QueryError};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+use tracing::{info, trace};
 
-const TEN_SECONDS: Duration = Duration::from_secs(10);
-const ONE_MINUTE: Duration = Duration::from_secs(60);
+const BATCH_SIZE: i64 = 512;
 
-// Actor that can be invoked to find and delete objects with no matching file paths
-#[derive(Clone)]
-pub struct OrphanRemoverActor {
-	tx: mpsc::Sender<()>,
+object::select!(orphan_to_remove {
+	id
+	pub_id
+	tags: select { tag: select { pub_id } }
+});
+
+/// Removes the objects left without any file path, which happens when their last file was
+/// deleted or reidentified. Objects with a note or marked as favorite are kept.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrphanRemoverJobInit {}
+
+impl Hash for OrphanRemoverJobInit {
+	fn hash<H: Hasher>(&self, _: &mut H) {
+		// Runs library wide, so there is only ever one of them
+	}
 }
 
-impl OrphanRemoverActor {
-	pub fn spawn(db: Arc<PrismaClient>) -> Self {
-		let (tx, mut rx) = mpsc::channel(4);
-
-		tokio::spawn(async move {
-			let mut last_checked = Instant::now();
-
-			let mut check_interval = interval_at(Instant::now() + ONE_MINUTE, ONE_MINUTE);
-			check_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-
-			loop {
-				// Here we wait for a signal or for the tick interval to be reached
-				select! {
-					_ =  check_interval.tick() => {}
-					signal = rx.recv() => {
-						if signal.is_none() {
-							break;
-						}
-					}
-				}
-
-				// For any of them we process a clean up if a time since the last one already passed
-				if last_checked.elapsed() > TEN_SECONDS {
-					Self::process_clean_up(&db).await;
-					last_checked = Instant::now();
-				}
-			}
-		});
+/// How many orphaned objects a clean up found.
+#[derive(Serialize, Deserialize, Type, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrphansCleanup {
+	pub removed: u32,
+	/// Orphans kept because the user left something on them, a note or a favorite.
+	pub preserved: u32,
+}
 
-		Self { tx }
+impl JobRunMetadata for OrphansCleanup {
+	fn update(&mut self, new_data: Self) {
+		self.removed += new_data.removed;
+		self.preserved = new_data.preserved;
 	}
+}
+
+#[async_trait::async_trait]
+impl StatefulJob for OrphanRemoverJobInit {
+	type Data = ();
+	type Step = ();
+	type RunMetadata = OrphansCleanup;
+
+	const NAME: &'static str = "orphan_remover";
 
-	pub async fn invoke(&self) {
-		self.tx.send(()).await.ok();
+	fn target_location(&self) -> location::id::Type {
+		// Library wide runs don't target any location, and no location has the id 0
+		0
 	}
 
-	async fn process_clean_up(db: &PrismaClient) {
-		loop {
-			let Ok(objects_ids) = db
-				.object()
-				.find_many(vec![object::file_paths::none(vec![])])
-				.take(512)
-				.select(object::select!({ id }))
-				.exec()
-				.await
-				.map(|objects| {
-					objects
-						.into_iter()
-						.map(|object| object.id)
-						.collect::<Vec<_>>()
-				})
-				.map_err(|e| error!("Failed to fetch orphaned objects: {e:#?}"))
-			else {
-				break;
-			};
-
-			if objects_ids.is_empty() {
-				break;
-			}
+	async fn init(
+		&self,
+		_: &WorkerContext,
+		data: &mut Option<Self::Data>,
+	) -> Result<JobInitOutput<Self::RunMetadata, Self::Step>, JobError> {
+		// Must fill in the data, otherwise the job will not run
+		*data = Some(());
+
+		Ok(vec![()].into())
+	}
+
+	async fn execute_step(
+		&self,
+		ctx: &WorkerContext,
+		_: CurrentStep<'_, Self::Step>,
+		_: &Self::Data,
+		_: &Self::RunMetadata,
+	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
+		Ok(remove_orphan_objects(&ctx.library).await?.into())
+	}
+
+	async fn finalize(
+		&self,
+		ctx: &WorkerContext,
+		_: &Option<Self::Data>,
+		run_metadata: &Self::RunMetadata,
+	) -> JobResult {
+		let init = self;
+
+		info!(
+			"finalizing orphan remover job: {} orphaned objects removed, {} preserved",
+			run_metadata.removed, run_metadata.preserved
+		);
+
+		if run_metadata.removed > 0 {
+			invalidate_query!(ctx.library, "search.objects");
+			invalidate_query!(ctx.library, "library.statistics");
+		}
+
+		Ok(Some(json!({ "init": init, "run_metadata": run_metadata })))
+	}
+}
+
+/// Orphans the user left something on, which would be lost along with them.
+fn preserved_orphans() -> object::WhereParam {
+	or![
+		object::favorite::equals(Some(true)),
+		and![
+			object::note::not(None),
+			object::note::not(Some(String::new()))
+		],
+	]
+}
+
+/// Spelled out instead of negating [`preserved_orphans`], as SQL doesn't negate `NULL`s.
+fn removable_orphans() -> Vec<object::WhereParam> {
+	vec![
+		object::file_paths::none(vec![]),
+		or![
+			object::favorite::equals(None),
+			object::favorite::equals(Some(false))
+		],
+		or![
+			object::note::equals(None),
+			object::note::equals(Some(String::new()))
+		],
+	]
+}
+
+/// Deletes the objects which have no file paths left, along with their tags, labels and their
+/// places in spaces and albums, except for the ones with a note or marked as favorite.
+pub(crate) async fn remove_orphan_objects(library: &Library) -> Result<OrphansCleanup, QueryError> {
+	let Library { db, sync, .. } = library;
 
-			trace!("Removing {} orphaned objects", objects_ids.len());
+	let mut cleanup = OrphansCleanup::default();
 
-			if let Err(e) = db
-				._batch((
-					db.tag_on_object()
-						.delete_many(vec![tag_on_object::object_id::in_vec(objects_ids.clone())]),
+	loop {
+		let orphans = db
+			.object()
+			.find_many(removable_orphans())
+			.take(BATCH_SIZE)
+			.select(orphan_to_remove::select())
+			.exec()
+			.await?;
+
+		if orphans.is_empty() {
+			break;
+		}
+
+		trace!("Removing {} orphaned objects", orphans.len());
+
+		let objects_ids = orphans.iter().map(|orphan| orphan.id).collect::<Vec<_>>();
+
+		sync.write_ops(
+			db,
+			(
+				orphans
+					.iter()
+					.flat_map(|orphan| {
+						orphan.tags.iter().map(|tag_on_object| {
+							sync.relation_delete(prisma_sync::tag_on_object::SyncId {
+								tag: prisma_sync::tag::SyncId {
+									pub_id: tag_on_object.tag.pub_id.clone(),
+								},
+								object: prisma_sync::object::SyncId {
+									pub_id: orphan.pub_id.clone(),
+								},
+							})
+						})
+					})
+					.collect(),
+				db.tag_on_object()
+					.delete_many(vec![tag_on_object::object_id::in_vec(objects_ids.clone())]),
+			),
+		)
+		.await?;
+
+		// Labels, spaces and albums aren't synced
+		remove_local_links(db, &objects_ids).await?;
+
+		let removed = sync
+			.write_ops(
+				db,
+				(
+					orphans
+						.into_iter()
+						.map(|orphan| {
+							sync.shared_delete(prisma_sync::object::SyncId {
+								pub_id: orphan.pub_id,
+							})
+						})
+						.collect(),
 					db.object()
 						.delete_many(vec![object::id::in_vec(objects_ids)]),
-				))
-				.await
-			{
-				error!("Failed to remove orphaned objects: {e:#?}");
-				break;
+				),
+			)
+			.await?;
+
+		cleanup.removed += removed as u32;
+	}
+
+	cleanup.preserved = db
+		.object()
+		.count(vec![object::file_paths::none(vec![]), preserved_orphans()])
+		.exec()
+		.await? as u32;
+
+	Ok(cleanup)
+}
+
+async fn remove_local_links(
+	db: &PrismaClient,
+	objects_ids: &[object::id::Type],
+) -> Result<(), QueryError> {
+	db._batch((
+		db.label_on_object()
+			.delete_many(vec![label_on_object::object_id::in_vec(
+				objects_ids.to_vec(),
+			)]),
+		db.object_in_space()
+			.delete_many(vec![object_in_space::object_id::in_vec(
+				objects_ids.to_vec(),
+			)]),
+		db.object_in_album()
+			.delete_many(vec![object_in_album::object_id::in_vec(
+				objects_ids.to_vec(),
+			)]),
+	))
+	.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_library;
+
+	use sd_prisma::prisma::{file_path, label, tag};
+	use sd_utils::uuid_to_bytes;
+
+	use uuid::Uuid;
+
+	async fn create_object(db: &PrismaClient, params: Vec<object::SetParam>) -> object::Data {
+		db.object()
+			.create(uuid_to_bytes(Uuid::new_v4()), params)
+			.exec()
+			.await
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn test_remove_orphan_objects() {
+		let (_data_dir, _node, library) = test_library("Orphans").await;
+		let db = &library.db;
+
+		let orphan = create_object(db, vec![]).await;
+		let object = create_object(db, vec![]).await;
+		let favorite = create_object(db, vec![object::favorite::set(Some(true))]).await;
+		let noted = create_object(db, vec![object::note::set(Some("Keep".to_string()))]).await;
+		// A blank note is no note at all
+		create_object(db, vec![object::note::set(Some(String::new()))]).await;
+
+		db.file_path()
+			.create(
+				uuid_to_bytes(Uuid::new_v4()),
+				vec![file_path::object::connect(object::id::equals(object.id))],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		// Neither a label nor a tag keep the orphan from being deleted
+		let label = db
+			.label()
+			.create(uuid_to_bytes(Uuid::new_v4()), "Cat".to_string(), vec![])
+			.exec()
+			.await
+			.unwrap();
+		db.label_on_object()
+			.create_many(vec![label_on_object::create_unchecked(
+				label.id,
+				orphan.id,
+				vec![],
+			)])
+			.exec()
+			.await
+			.unwrap();
+		let tag = db
+			.tag()
+			.create(uuid_to_bytes(Uuid::new_v4()), vec![])
+			.exec()
+			.await
+			.unwrap();
+		db.tag_on_object()
+			.create_many(vec![tag_on_object::create_unchecked(
+				tag.id,
+				orphan.id,
+				vec![],
+			)])
+			.exec()
+			.await
+			.unwrap();
+
+		assert_eq!(
+			remove_orphan_objects(&library).await.unwrap(),
+			OrphansCleanup {
+				removed: 2,
+				preserved: 2,
 			}
-		}
+		);
+
+		let mut remaining = db
+			.object()
+			.find_many(vec![])
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|object| object.id)
+			.collect::<Vec<_>>();
+		remaining.sort_unstable();
+		assert_eq!(remaining, vec![object.id, favorite.id, noted.id]);
+
+		assert!(db
+			.label()
+			.find_unique(label::id::equals(label.id))
+			.exec()
+			.await
+			.unwrap()
+			.is_some());
+		assert!(db
+			.tag()
+			.find_unique(tag::id::equals(tag.id))
+			.exec()
+			.await
+			.unwrap()
+			.is_some());
+		assert_eq!(db.tag_on_object().count(vec![]).exec().await.unwrap(), 0);
 	}
 }
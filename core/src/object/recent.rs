@@ -0,0 +1,144 @@
+use sd_prisma::prisma::{object, recent_access, PrismaClient, SortOrder};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use prisma_client_rust::QueryError;
+
+/// How many objects are kept in the recents, the least recently accessed ones are evicted first.
+pub const MAX_RECENT_ACCESSES: i64 = 1000;
+
+/// Marks the objects as accessed now. Accessing an object that's already in the recents just
+/// updates its timestamp, so each object shows up once.
+pub async fn record_access(
+	db: &PrismaClient,
+	object_ids: Vec<object::id::Type>,
+) -> Result<(), QueryError> {
+	let accessed_at: DateTime<FixedOffset> = Utc::now().into();
+
+	// Objects can be deleted while the explorer still shows them
+	let object_ids = db
+		.object()
+		.find_many(vec![object::id::in_vec(object_ids)])
+		.select(object::select!({ id }))
+		.exec()
+		.await?;
+
+	if object_ids.is_empty() {
+		return Ok(());
+	}
+
+	db._batch(
+		object_ids
+			.into_iter()
+			.map(|object| {
+				db.recent_access().upsert(
+					recent_access::object_id::equals(object.id),
+					recent_access::create(object::id::equals(object.id), accessed_at, vec![]),
+					vec![recent_access::accessed_at::set(accessed_at)],
+				)
+			})
+			.collect::<Vec<_>>(),
+	)
+	.await?;
+
+	let evicted = db
+		.recent_access()
+		.find_many(vec![])
+		.order_by(recent_access::accessed_at::order(SortOrder::Desc))
+		.skip(MAX_RECENT_ACCESSES)
+		.select(recent_access::select!({ object_id }))
+		.exec()
+		.await?;
+
+	if !evicted.is_empty() {
+		db.recent_access()
+			.delete_many(vec![recent_access::object_id::in_vec(
+				evicted.into_iter().map(|access| access.object_id).collect(),
+			)])
+			.exec()
+			.await?;
+	}
+
+	Ok(())
+}
+
+pub async fn remove_access(
+	db: &PrismaClient,
+	object_ids: Vec<object::id::Type>,
+) -> Result<(), QueryError> {
+	db.recent_access()
+		.delete_many(vec![recent_access::object_id::in_vec(object_ids)])
+		.exec()
+		.await
+		.map(|_| ())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use sd_utils::{db::load_and_migrate, uuid_to_bytes};
+
+	use std::time::Duration;
+
+	use tempfile::tempdir;
+	use tokio::time::sleep;
+	use uuid::Uuid;
+
+	#[tokio::test]
+	async fn test_record_access_dedups_and_evicts_oldest() {
+		let dir = tempdir().unwrap();
+		let db = load_and_migrate(&format!("file:{}", dir.path().join("library.db").display()))
+			.await
+			.unwrap();
+
+		db.object()
+			.create_many(
+				(0..=MAX_RECENT_ACCESSES)
+					.map(|_| object::create_unchecked(uuid_to_bytes(Uuid::new_v4()), vec![]))
+					.collect(),
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		let ids = db
+			.object()
+			.find_many(vec![])
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|object| object.id)
+			.collect::<Vec<_>>();
+
+		record_access(&db, vec![ids[0]]).await.unwrap();
+		sleep(Duration::from_millis(10)).await;
+		record_access(&db, ids[1..].to_vec()).await.unwrap();
+
+		let count = || db.recent_access().count(vec![]).exec();
+		assert_eq!(count().await.unwrap(), MAX_RECENT_ACCESSES);
+		assert!(db
+			.recent_access()
+			.find_unique(recent_access::object_id::equals(ids[0]))
+			.exec()
+			.await
+			.unwrap()
+			.is_none());
+
+		// Accessing it again only moves it to the top
+		sleep(Duration::from_millis(10)).await;
+		record_access(&db, vec![ids[1]]).await.unwrap();
+		assert_eq!(count().await.unwrap(), MAX_RECENT_ACCESSES);
+
+		let most_recent = db
+			.recent_access()
+			.find_first(vec![])
+			.order_by(recent_access::accessed_at::order(SortOrder::Desc))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(most_recent.object_id, ids[1]);
+	}
+}
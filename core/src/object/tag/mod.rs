@@ -3,6 +3,8 @@ use crate::library::Library;
 use sd_prisma::{prisma::tag, prisma_sync};
 use sd_sync::*;
 
+use std::collections::HashSet;
+
 use chrono::{DateTime, FixedOffset, Utc};
 
 use serde::Deserialize;
@@ -19,11 +21,16 @@ pub struct TagCreateArgs {
 }
 
 impl TagCreateArgs {
-	pub async fn exec(
+	pub async fn exec(self, library: &Library) -> prisma_client_rust::Result<tag::Data> {
+		self.exec_with_pub_id(library, Uuid::new_v4()).await
+	}
+
+	pub(crate) async fn exec_with_pub_id(
 		self,
 		Library { db, sync, .. }: &Library,
+		pub_id: Uuid,
 	) -> prisma_client_rust::Result<tag::Data> {
-		let pub_id = Uuid::new_v4().as_bytes().to_vec();
+		let pub_id = pub_id.as_bytes().to_vec();
 		let date_created: DateTime<FixedOffset> = Utc::now().into();
 
 		sync.write_ops(
@@ -54,3 +61,113 @@ impl TagCreateArgs {
 		.await
 	}
 }
+
+/// The order tags are listed in, tags without a position go last in the order they were created.
+pub fn sort_tags(tags: &mut [tag::Data]) {
+	tags.sort_by_key(|tag| (tag.sort_order.is_none(), tag.sort_order, tag.id));
+}
+
+/// Rewrites the position of every tag so they are listed in the given order, all at once.
+///
+/// Ids of tags that don't exist anymore are ignored, and the tags left out keep their current
+/// order after the given ones, so a client with an outdated list can't lose any tag.
+pub async fn reorder_tags(
+	Library { db, sync, .. }: &Library,
+	ordered_ids: Vec<tag::id::Type>,
+) -> prisma_client_rust::Result<()> {
+	let mut tags = db.tag().find_many(vec![]).exec().await?;
+	sort_tags(&mut tags);
+
+	let mut seen = HashSet::with_capacity(ordered_ids.len());
+	let ordered = ordered_ids
+		.into_iter()
+		.filter(|id| seen.insert(*id))
+		.filter_map(|id| tags.iter().position(|tag| tag.id == id))
+		.collect::<Vec<_>>();
+
+	let (sync_ops, db_updates): (Vec<_>, Vec<_>) = ordered
+		.iter()
+		.copied()
+		.chain((0..tags.len()).filter(|idx| !ordered.contains(idx)))
+		.enumerate()
+		.filter_map(|(sort_order, idx)| {
+			let tag = &tags[idx];
+			let sort_order = sort_order as i32;
+
+			(tag.sort_order != Some(sort_order)).then(|| {
+				(
+					sync.shared_update(
+						prisma_sync::tag::SyncId {
+							pub_id: tag.pub_id.clone(),
+						},
+						tag::sort_order::NAME,
+						json!(sort_order),
+					),
+					db.tag().update(
+						tag::id::equals(tag.id),
+						vec![tag::sort_order::set(Some(sort_order))],
+					),
+				)
+			})
+		})
+		.unzip();
+
+	if !db_updates.is_empty() {
+		sync.write_ops(db, (sync_ops, db_updates)).await?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_library;
+
+	#[tokio::test]
+	async fn test_reorder_tags() {
+		let (_data_dir, _node, library) = test_library("Tags").await;
+		// Starting from no tags at all
+		library.db.tag().delete_many(vec![]).exec().await.unwrap();
+
+		let mut ids = vec![];
+		for name in ["A", "B", "C"] {
+			let tag = TagCreateArgs {
+				name: name.to_string(),
+				color: "#000000".to_string(),
+			}
+			.exec(&library)
+			.await
+			.unwrap();
+			ids.push(tag.id);
+		}
+		let (a, b, c) = (ids[0], ids[1], ids[2]);
+
+		let list = || async {
+			let mut tags = library.db.tag().find_many(vec![]).exec().await.unwrap();
+			sort_tags(&mut tags);
+			tags.into_iter()
+				.map(|tag| (tag.id, tag.sort_order))
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(list().await, vec![(a, None), (b, None), (c, None)]);
+
+		// Duplicated and unknown ids are ignored, and left out tags go last
+		reorder_tags(&library, vec![c, 999, a, c]).await.unwrap();
+		assert_eq!(list().await, vec![(c, Some(0)), (a, Some(1)), (b, Some(2))]);
+
+		// A gap left by a deleted tag doesn't matter
+		library
+			.db
+			.tag()
+			.delete(tag::id::equals(a))
+			.exec()
+			.await
+			.unwrap();
+		reorder_tags(&library, vec![b]).await.unwrap();
+		assert_eq!(list().await, vec![(b, Some(0)), (c, Some(1))]);
+	}
+}
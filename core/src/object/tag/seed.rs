@@ -1,34 +1,118 @@
 use crate::library::Library;
 
+use sd_prisma::prisma::{tag, PrismaClient};
+use sd_utils::uuid_to_bytes;
+
+use uuid::Uuid;
+
 use super::TagCreateArgs;
 
-/// Seeds tags in a new library.
-/// Shouldn't be called more than once!
-pub async fn new_library(library: &Library) -> prisma_client_rust::Result<()> {
-	// remove type after tags are added
-
-	let tags = [
-		TagCreateArgs {
-			name: "Keepsafe".to_string(),
-			color: "#D9188E".to_string(),
-		},
-		TagCreateArgs {
-			name: "Hidden".to_string(),
-			color: "#646278".to_string(),
-		},
-		TagCreateArgs {
-			name: "Projects".to_string(),
-			color: "#42D097".to_string(),
-		},
-		TagCreateArgs {
-			name: "Memes".to_string(),
-			color: "#A718D9".to_string(),
-		},
-	];
-
-	for tag in tags {
-		tag.exec(library).await?;
+/// The built-in tags, each with a fixed pub_id so it can be found again even after being renamed.
+// DO NOT REORDER THIS ARRAY!
+fn default_tags() -> Vec<(Uuid, TagCreateArgs)> {
+	[
+		("Keepsafe", "#D9188E"),
+		("Hidden", "#646278"),
+		("Projects", "#42D097"),
+		("Memes", "#A718D9"),
+	]
+	.into_iter()
+	.enumerate()
+	.map(|(i, (name, color))| {
+		(
+			Uuid::from_u128(i as u128),
+			TagCreateArgs {
+				name: name.to_string(),
+				color: color.to_string(),
+			},
+		)
+	})
+	.collect()
+}
+
+/// Seeds the default tags into a new or existing library, re-creating the ones which were deleted.
+///
+/// Returns how many tags were created, running it again never duplicates them.
+pub async fn new_or_existing_library(library: &Library) -> prisma_client_rust::Result<usize> {
+	let missing = missing_default_tags(&library.db).await?;
+	let created = missing.len();
+
+	for (pub_id, tag) in missing {
+		tag.exec_with_pub_id(library, pub_id).await?;
 	}
 
-	Ok(())
+	Ok(created)
+}
+
+/// Default tags are matched by their pub_id, or by their name as libraries created before the
+/// pub_ids were fixed have them with random ones.
+async fn missing_default_tags(
+	db: &PrismaClient,
+) -> prisma_client_rust::Result<Vec<(Uuid, TagCreateArgs)>> {
+	let existing = db
+		.tag()
+		.find_many(vec![])
+		.select(tag::select!({ pub_id name }))
+		.exec()
+		.await?;
+
+	Ok(default_tags()
+		.into_iter()
+		.filter(|(pub_id, args)| {
+			let pub_id = uuid_to_bytes(*pub_id);
+
+			!existing
+				.iter()
+				.any(|tag| tag.pub_id == pub_id || tag.name.as_ref() == Some(&args.name))
+		})
+		.collect())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use sd_utils::db::load_and_migrate;
+
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_missing_default_tags() {
+		let dir = tempdir().unwrap();
+		let db = load_and_migrate(&format!("file:{}", dir.path().join("library.db").display()))
+			.await
+			.unwrap();
+
+		assert_eq!(missing_default_tags(&db).await.unwrap().len(), 4);
+
+		db.tag()
+			.create_many(vec![
+				// A renamed default tag
+				tag::create_unchecked(
+					uuid_to_bytes(Uuid::from_u128(0)),
+					vec![tag::name::set(Some("Safe".to_string()))],
+				),
+				// A default tag seeded before they had fixed pub_ids
+				tag::create_unchecked(
+					uuid_to_bytes(Uuid::new_v4()),
+					vec![tag::name::set(Some("Hidden".to_string()))],
+				),
+			])
+			.exec()
+			.await
+			.unwrap();
+
+		let missing = missing_default_tags(&db).await.unwrap();
+		assert_eq!(
+			missing
+				.iter()
+				.map(|(pub_id, args)| (*pub_id, args.name.as_str()))
+				.collect::<Vec<_>>(),
+			vec![
+				(Uuid::from_u128(2), "Projects"),
+				(Uuid::from_u128(3), "Memes")
+			]
+		);
+	}
 }
@@ -0,0 +1,417 @@
+use crate::{
+	api::notifications::{NotificationData, NotificationKind},
+	invalidate_query,
+	job::{
+		CurrentStep, JobError, JobInitOutput, JobReportUpdate, JobResult, JobRunMetadata,
+		JobStepOutput, StatefulJob, WorkerContext,
+	},
+	library::Library,
+	object::cas::{cas_id_bytes_read, generate_cas_id},
+};
+
+use sd_file_path_helper::{file_path_for_integrity_verifier, IsolatedFilePathData};
+use sd_prisma::prisma::{file_path, integrity_mismatch, location, object, PrismaClient, SortOrder};
+use sd_utils::db::maybe_missing;
+
+use std::{
+	hash::{Hash, Hasher},
+	io,
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{fs, time::sleep};
+use tracing::{info, warn};
+
+use super::{hash::file_checksum, ValidatorError};
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IntegrityVerifierJobData {
+	location_path: PathBuf,
+}
+
+/// Re-hashes the files of a location, or only the ones of some of its objects, and records the
+/// ones which no longer match their `cas_id` in the location's integrity report.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IntegrityVerifierJobInit {
+	pub location: location::Data,
+	pub object_ids: Option<Vec<object::id::Type>>,
+}
+
+impl Hash for IntegrityVerifierJobInit {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.location.id.hash(state);
+		if let Some(ref object_ids) = self.object_ids {
+			object_ids.hash(state);
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IntegrityVerifierJobRunMetadata {
+	total_verified: usize,
+	total_skipped: usize,
+	total_mismatches: usize,
+}
+
+impl JobRunMetadata for IntegrityVerifierJobRunMetadata {
+	fn update(&mut self, new_data: Self) {
+		self.total_verified += new_data.total_verified;
+		self.total_skipped += new_data.total_skipped;
+		self.total_mismatches += new_data.total_mismatches;
+	}
+}
+
+#[async_trait::async_trait]
+impl StatefulJob for IntegrityVerifierJobInit {
+	type Data = IntegrityVerifierJobData;
+	type Step = Vec<file_path_for_integrity_verifier::Data>;
+	type RunMetadata = IntegrityVerifierJobRunMetadata;
+
+	const NAME: &'static str = "integrity_verifier";
+	const IS_BACKGROUND: bool = true;
+	const IS_BATCHED: bool = true;
+
+	fn target_location(&self) -> location::id::Type {
+		self.location.id
+	}
+
+	async fn init(
+		&self,
+		ctx: &WorkerContext,
+		data: &mut Option<Self::Data>,
+	) -> Result<JobInitOutput<Self::RunMetadata, Self::Step>, JobError> {
+		let init = self;
+		let Library { db, .. } = &*ctx.library;
+
+		let location_path =
+			maybe_missing(&init.location.path, "location.path").map(PathBuf::from)?;
+
+		// Files without a cas_id haven't been identified yet, so there is nothing to compare with
+		let file_paths =
+			db.file_path()
+				.find_many(sd_utils::chain_optional_iter(
+					[
+						file_path::location_id::equals(Some(init.location.id)),
+						file_path::is_dir::equals(Some(false)),
+						file_path::cas_id::not(None),
+					],
+					[init.object_ids.clone().map(|object_ids| {
+						file_path::object::is(vec![object::id::in_vec(object_ids)])
+					})],
+				))
+				.order_by(file_path::id::order(SortOrder::Asc))
+				.select(file_path_for_integrity_verifier::select())
+				.exec()
+				.await
+				.map_err(ValidatorError::from)?;
+
+		let total_files = file_paths.len();
+
+		let steps = file_paths
+			.into_iter()
+			.chunks(BATCH_SIZE)
+			.into_iter()
+			.map(|chunk| chunk.collect::<Vec<_>>())
+			.collect::<Vec<_>>();
+
+		ctx.progress(vec![
+			JobReportUpdate::TaskCount(total_files),
+			JobReportUpdate::Message(format!("Verifying the integrity of {total_files} files")),
+		]);
+
+		*data = Some(IntegrityVerifierJobData { location_path });
+
+		Ok(steps.into())
+	}
+
+	async fn execute_step(
+		&self,
+		ctx: &WorkerContext,
+		CurrentStep {
+			step: file_paths,
+			step_number,
+		}: CurrentStep<'_, Self::Step>,
+		data: &Self::Data,
+		_: &Self::RunMetadata,
+	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
+		let init = self;
+		let Library { db, .. } = &*ctx.library;
+
+		let started_at = Instant::now();
+		let mut bytes_read = 0;
+		let mut run_metadata = IntegrityVerifierJobRunMetadata::default();
+		let mut matching_ids = Vec::with_capacity(file_paths.len());
+
+		for (i, file_path) in file_paths.iter().enumerate() {
+			let full_path = data.location_path.join(IsolatedFilePathData::try_from((
+				init.location.id,
+				file_path,
+			))?);
+
+			match verify_file(file_path, &full_path).await {
+				Verification::Match { bytes } => {
+					bytes_read += bytes;
+					run_metadata.total_verified += 1;
+					matching_ids.push(file_path.id);
+				}
+				Verification::Skipped => {
+					run_metadata.total_skipped += 1;
+					matching_ids.push(file_path.id);
+				}
+				Verification::Mismatch {
+					expected_cas_id,
+					actual_cas_id,
+					bytes,
+				} => {
+					bytes_read += bytes;
+					run_metadata.total_verified += 1;
+					run_metadata.total_mismatches += 1;
+
+					warn!(
+						"Integrity mismatch at '{}': expected cas_id {expected_cas_id}, found {}",
+						full_path.display(),
+						actual_cas_id.as_deref().unwrap_or("an unreadable file")
+					);
+
+					record_mismatch(
+						db,
+						init.location.id,
+						file_path.id,
+						expected_cas_id,
+						actual_cas_id,
+					)
+					.await
+					.map_err(ValidatorError::from)?;
+				}
+			}
+
+			ctx.progress(vec![JobReportUpdate::CompletedTaskCount(
+				step_number * BATCH_SIZE + i + 1,
+			)]);
+		}
+
+		// Files which are fine now, or were changed since, are no longer mismatches
+		db.integrity_mismatch()
+			.delete_many(vec![integrity_mismatch::file_path_id::in_vec(matching_ids)])
+			.exec()
+			.await
+			.map_err(ValidatorError::from)?;
+
+		// Read at the preferred pace, so verifying a big location doesn't hog the disk
+		if let Some(max_throughput) = ctx
+			.node
+			.config
+			.get()
+			.await
+			.preferences
+			.integrity
+			.max_throughput()
+		{
+			let expected = Duration::from_secs_f64(bytes_read as f64 / max_throughput as f64);
+			let elapsed = started_at.elapsed();
+			if expected > elapsed {
+				sleep(expected - elapsed).await;
+			}
+		}
+
+		Ok(run_metadata.into())
+	}
+
+	async fn finalize(
+		&self,
+		ctx: &WorkerContext,
+		data: &Option<Self::Data>,
+		run_metadata: &Self::RunMetadata,
+	) -> JobResult {
+		let init = self;
+		let data = data
+			.as_ref()
+			.expect("critical error: missing data on job state");
+
+		info!(
+			"finalizing integrity verifier job at {}: {} files verified, {} skipped, {} mismatches",
+			data.location_path.display(),
+			run_metadata.total_verified,
+			run_metadata.total_skipped,
+			run_metadata.total_mismatches,
+		);
+
+		// Only a run over the whole location tells us when all of it was last verified
+		if init.object_ids.is_none() {
+			// Local only, as it's about this instance's copy of the files
+			ctx.library
+				.db
+				.location()
+				.update(
+					location::id::equals(init.location.id),
+					vec![location::integrity_verified_at::set(Some(
+						Utc::now().into(),
+					))],
+				)
+				.exec()
+				.await
+				.map_err(ValidatorError::from)?;
+		}
+
+		invalidate_query!(ctx.library, "files.integrityReport");
+
+		let location_name = init.location.name.as_deref().unwrap_or_default();
+
+		ctx.library
+			.emit_notification(
+				if run_metadata.total_mismatches == 0 {
+					NotificationData {
+						title: "Integrity verified".to_string(),
+						content: format!(
+							"All {} verified files in \"{location_name}\" are intact",
+							run_metadata.total_verified
+						),
+						kind: NotificationKind::Success,
+					}
+				} else {
+					NotificationData {
+						title: "Integrity issues found".to_string(),
+						content: format!(
+							"{} of {} verified files in \"{location_name}\" no longer match their \
+							contents when they were indexed",
+							run_metadata.total_mismatches, run_metadata.total_verified
+						),
+						kind: NotificationKind::Warning,
+					}
+				},
+				None,
+			)
+			.await;
+
+		Ok(Some(json!({ "init": init, "run_metadata": run_metadata })))
+	}
+}
+
+enum Verification {
+	Match {
+		bytes: u64,
+	},
+	/// The file was changed or removed since it was indexed, so its cas_id is just outdated
+	Skipped,
+	Mismatch {
+		expected_cas_id: String,
+		actual_cas_id: Option<String>,
+		bytes: u64,
+	},
+}
+
+async fn verify_file(
+	file_path: &file_path_for_integrity_verifier::Data,
+	full_path: &Path,
+) -> Verification {
+	let (Some(expected_cas_id), Some(date_modified), Some(size)) = (
+		&file_path.cas_id,
+		&file_path.date_modified,
+		file_path
+			.size_in_bytes_bytes
+			.as_deref()
+			.and_then(|size| size.try_into().ok())
+			.map(u64::from_be_bytes),
+	) else {
+		return Verification::Skipped;
+	};
+
+	let metadata = match fs::metadata(full_path).await {
+		Ok(metadata) => metadata,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Verification::Skipped,
+		Err(_) => {
+			return Verification::Mismatch {
+				expected_cas_id: expected_cas_id.clone(),
+				actual_cas_id: None,
+				bytes: 0,
+			}
+		}
+	};
+
+	if was_modified(&metadata, size, date_modified) {
+		return Verification::Skipped;
+	}
+
+	// The cas_id only samples big files, so we prefer the full checksum when we have one
+	let (intact, mut bytes) = match &file_path.integrity_checksum {
+		Some(expected_checksum) => (
+			file_checksum(full_path)
+				.await
+				.is_ok_and(|checksum| &checksum == expected_checksum),
+			size,
+		),
+		None => (
+			generate_cas_id(full_path, size)
+				.await
+				.is_ok_and(|cas_id| &cas_id == expected_cas_id),
+			cas_id_bytes_read(size),
+		),
+	};
+
+	if intact {
+		return Verification::Match { bytes };
+	}
+
+	// The report shows what the cas_id changed to, even when it was the full checksum that differed
+	let actual_cas_id = generate_cas_id(full_path, size).await.ok();
+	bytes += cas_id_bytes_read(size);
+
+	Verification::Mismatch {
+		expected_cas_id: expected_cas_id.clone(),
+		actual_cas_id,
+		bytes,
+	}
+}
+
+fn was_modified(
+	metadata: &std::fs::Metadata,
+	size: u64,
+	date_modified: &DateTime<FixedOffset>,
+) -> bool {
+	metadata.len() != size
+		|| metadata.modified().map_or(true, |modified| {
+			// Datetimes stored in DB loses a bit of precision, so we check against a delta
+			(DateTime::<FixedOffset>::from(DateTime::<Utc>::from(modified)) - *date_modified)
+				.num_milliseconds()
+				.abs() > 1
+		})
+}
+
+async fn record_mismatch(
+	db: &PrismaClient,
+	location_id: location::id::Type,
+	file_path_id: file_path::id::Type,
+	expected_cas_id: String,
+	actual_cas_id: Option<String>,
+) -> Result<(), prisma_client_rust::QueryError> {
+	let date_detected = Utc::now().into();
+
+	db.integrity_mismatch()
+		.upsert(
+			integrity_mismatch::file_path_id::equals(file_path_id),
+			integrity_mismatch::create(
+				file_path::id::equals(file_path_id),
+				location_id,
+				expected_cas_id.clone(),
+				date_detected,
+				vec![integrity_mismatch::actual_cas_id::set(
+					actual_cas_id.clone(),
+				)],
+			),
+			vec![
+				integrity_mismatch::expected_cas_id::set(expected_cas_id),
+				integrity_mismatch::actual_cas_id::set(actual_cas_id),
+				integrity_mismatch::date_detected::set(date_detected),
+			],
+		)
+		.exec()
+		.await
+		.map(|_| ())
+}
@@ -6,6 +6,8 @@ use std::path::Path;
 use thiserror::Error;
 
 pub mod hash;
+pub mod integrity_job;
+pub mod preferences;
 pub mod validator_job;
 
 #[derive(Error, Debug)]
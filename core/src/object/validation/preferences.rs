@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Type)]
+pub struct IntegrityPreferences {
+	max_throughput_mb_per_sec: u32, // 0 means unlimited
+}
+
+impl Default for IntegrityPreferences {
+	fn default() -> Self {
+		Self {
+			max_throughput_mb_per_sec: 50, // gentle enough to not slow down the rest of the system
+		}
+	}
+}
+
+impl IntegrityPreferences {
+	/// How many bytes per second the integrity verifier can read, `None` if it isn't throttled.
+	pub fn max_throughput(&self) -> Option<u64> {
+		(self.max_throughput_mb_per_sec != 0)
+			.then(|| u64::from(self.max_throughput_mb_per_sec) * 1024 * 1024)
+	}
+
+	pub fn set_max_throughput_mb_per_sec(&mut self, max_throughput_mb_per_sec: u32) -> &mut Self {
+		self.max_throughput_mb_per_sec = max_throughput_mb_per_sec;
+
+		self
+	}
+}
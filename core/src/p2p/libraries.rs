@@ -2,7 +2,10 @@
 
 use crate::library::{Libraries, Library, LibraryManagerEvent};
 
-use sd_p2p::{spacetunnel::IdentityOrRemoteIdentity, Service};
+use sd_p2p::{
+	spacetunnel::{IdentityOrRemoteIdentity, RemoteIdentity},
+	Service,
+};
 
 use std::{
 	collections::HashMap,
@@ -14,7 +17,7 @@ use tokio::sync::mpsc;
 use tracing::{error, warn};
 use uuid::Uuid;
 
-use super::{LibraryMetadata, P2PManager};
+use super::{operations, LibraryMetadata, P2PManager};
 
 pub struct LibraryServices {
 	services: RwLock<HashMap<Uuid, Arc<Service<LibraryMetadata>>>>,
@@ -61,7 +64,10 @@ impl LibraryServices {
 								.await
 						}
 						LibraryManagerEvent::Edit(library) => {
-							manager.libraries.edit_library(&library).await
+							manager
+								.libraries
+								.edit_library(manager.clone(), &library)
+								.await
 						}
 						LibraryManagerEvent::Delete(library) => {
 							manager.libraries.delete_library(&library).await
@@ -104,9 +110,21 @@ impl LibraryServices {
 							None
 						}
 						Ok(IdentityOrRemoteIdentity::Identity(_)) => None,
-						Ok(IdentityOrRemoteIdentity::RemoteIdentity(identity)) => Some(identity),
+						Ok(IdentityOrRemoteIdentity::RemoteIdentity(identity)) => {
+							Some((identity, i.node_remote_identity))
+						}
 					},
 				)
+				.flat_map(|(identity, node_remote_identity)| {
+					// Peers connect with their node's identity, known once they've spoken for the instance
+					[
+						Some(identity),
+						node_remote_identity
+							.and_then(|bytes| RemoteIdentity::from_bytes(&bytes).ok()),
+					]
+					.into_iter()
+					.flatten()
+				})
 				.collect(),
 			Err(err) => {
 				warn!("error loading library '{}': {err:?}", library.id);
@@ -136,16 +154,27 @@ impl LibraryServices {
 		};
 
 		if inserted {
-			service.update(LibraryMetadata {});
+			service.update(LibraryMetadata {
+				name: library.config().await.name.into(),
+			});
 			if self.register_service_tx.send(service).await.is_err() {
 				warn!("error sending on 'register_service_tx'. This indicates a bug!");
 			}
 		}
 	}
 
-	pub(crate) async fn edit_library(&self, _library: &Library) {
-		// TODO: Send changes to all connected nodes!
-		// TODO: Update mdns
+	pub(crate) async fn edit_library(&self, manager: Arc<P2PManager>, library: &Library) {
+		self.update_metadata(library).await;
+		operations::library_metadata::broadcast(&manager, library).await;
+	}
+
+	/// Refreshes the metadata we advertise for the library, without telling connected peers.
+	pub(crate) async fn update_metadata(&self, library: &Library) {
+		if let Some(service) = self.get(&library.id) {
+			service.update(LibraryMetadata {
+				name: library.config().await.name.into(),
+			});
+		}
 	}
 
 	pub(crate) async fn delete_library(&self, library: &Library) {
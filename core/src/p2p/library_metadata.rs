@@ -6,17 +6,24 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 
 #[derive(Debug, Clone, Type, Serialize, Deserialize)]
-pub struct LibraryMetadata {}
+pub struct LibraryMetadata {
+	pub name: String,
+}
 
 impl Metadata for LibraryMetadata {
 	fn to_hashmap(self) -> HashMap<String, String> {
-		HashMap::with_capacity(0)
+		let mut map = HashMap::with_capacity(1);
+		map.insert("name".to_owned(), self.name);
+		map
 	}
 
-	fn from_hashmap(_: &HashMap<String, String>) -> Result<Self, String>
+	fn from_hashmap(data: &HashMap<String, String>) -> Result<Self, String>
 	where
 		Self: Sized,
 	{
-		Ok(Self {})
+		Ok(Self {
+			// Older nodes don't advertise it
+			name: data.get("name").cloned().unwrap_or_default(),
+		})
 	}
 }
@@ -0,0 +1,357 @@
+use crate::{
+	invalidate_query,
+	library::{Library, LibraryName},
+	p2p::{Header, HeaderLibraryMetadata, P2PManager},
+	Node,
+};
+
+use sd_p2p::{
+	spacetunnel::{IdentityOrRemoteIdentity, RemoteIdentity},
+	PeerMessageEvent, PeerStatus,
+};
+use sd_prisma::prisma::instance;
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+/// What an instance signs to vouch for the node it lives on. Pairing only tells other instances
+/// its own identity, while P2P connections are authenticated with the node's identity.
+fn node_claim(library_id: Uuid, node_identity: &RemoteIdentity) -> Vec<u8> {
+	[library_id.as_bytes().as_slice(), &node_identity.get_bytes()].concat()
+}
+
+/// Whether the instance signed that it lives on the node with `node_identity`.
+fn is_instance_node(
+	instance: &instance::Data,
+	library_id: Uuid,
+	node_identity: &RemoteIdentity,
+	node_signature: &[u8],
+) -> bool {
+	IdentityOrRemoteIdentity::from_bytes(&instance.identity).map_or(false, |identity| {
+		identity
+			.remote_identity()
+			.verify(&node_claim(library_id, node_identity), node_signature)
+	})
+}
+
+/// Sends the library's metadata to the instances we are connected to, so they see changes like a
+/// rename right away instead of when metadata is exchanged again.
+pub(crate) async fn broadcast(p2p: &Arc<P2PManager>, library: &Library) {
+	let Some(service) = p2p.get_library_service(&library.id) else {
+		return;
+	};
+
+	let header = Header::LibraryMetadata(HeaderLibraryMetadata {
+		library_id: library.id,
+		instance_id: library.instance_uuid,
+		library_name: library.config().await.name.into(),
+		node_name: p2p.node_config_manager.get().await.name,
+		node_signature: library
+			.identity
+			.sign(&node_claim(library.id, &p2p.manager.identity()))
+			.to_vec(),
+	})
+	.to_bytes();
+
+	for (remote_identity, status) in service.get_state() {
+		let PeerStatus::Connected = status else {
+			continue;
+		};
+
+		let p2p = p2p.clone();
+		let service = service.clone();
+		let header = header.clone();
+		let library_id = library.id;
+
+		tokio::spawn(async move {
+			debug!("Sending metadata of library '{library_id}' to peer '{remote_identity}'");
+
+			let mut stream = match service.connect(p2p.manager.clone(), &remote_identity).await {
+				Ok(stream) => stream,
+				Err(err) => {
+					warn!("Failed to connect to peer '{remote_identity}': {err:?}");
+					return;
+				}
+			};
+
+			if let Err(err) = stream.write_all(&header).await {
+				warn!("Failed to send library metadata to peer '{remote_identity}': {err}");
+			}
+		});
+	}
+}
+
+pub(crate) async fn receiver(
+	node: &Arc<Node>,
+	HeaderLibraryMetadata {
+		library_id,
+		instance_id,
+		library_name,
+		node_name,
+		node_signature,
+	}: HeaderLibraryMetadata,
+	event: PeerMessageEvent,
+) -> Result<(), ()> {
+	let library = node
+		.libraries
+		.get_library(&library_id)
+		.await
+		.ok_or_else(|| {
+			warn!(
+				"Peer '{}' sent metadata for unknown library '{library_id}'",
+				event.identity
+			);
+		})?;
+
+	let instance = library
+		.db
+		.instance()
+		.find_unique(instance::pub_id::equals(instance_id.as_bytes().to_vec()))
+		.exec()
+		.await
+		.map_err(|err| error!("Failed to fetch instance '{instance_id}': {err}"))?
+		.ok_or_else(|| {
+			warn!(
+				"Peer '{}' sent metadata for unknown instance '{instance_id}' of library '{library_id}'",
+				event.identity
+			);
+		})?;
+
+	// Only the node owning the instance can tell us about it. It's remembered, so it can also request files.
+	if !is_instance_node(&instance, library_id, &event.identity, &node_signature) {
+		warn!(
+			"Peer '{}' sent metadata for instance '{instance_id}' of library '{library_id}' which isn't theirs",
+			event.identity
+		);
+		return Err(());
+	}
+	let node_remote_identity = event.identity.get_bytes().to_vec();
+
+	library
+		.db
+		.instance()
+		.update(
+			instance::id::equals(instance.id),
+			vec![
+				instance::node_name::set(node_name),
+				instance::node_remote_identity::set(Some(node_remote_identity)),
+				instance::last_seen::set(Utc::now().into()),
+			],
+		)
+		.exec()
+		.await
+		.map_err(|err| error!("Failed to update instance '{instance_id}': {err}"))?;
+
+	if library.config().await.name.as_str() != library_name {
+		let name = LibraryName::new(library_name).map_err(|err| {
+			warn!(
+				"Peer '{}' sent an invalid library name: {err}",
+				event.identity
+			);
+		})?;
+
+		library
+			.update_config(
+				|config| config.name = name,
+				node.libraries
+					.libraries_dir
+					.join(format!("{library_id}.sdlibrary")),
+			)
+			.await
+			.map_err(|err| error!("Failed to rename library '{library_id}': {err}"))?;
+
+		// Not broadcasting it again, otherwise concurrent renames would bounce between nodes forever
+		node.p2p.libraries.update_metadata(&library).await;
+	}
+
+	invalidate_query!(library, "library.list");
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::util::{
+		test_utils::{test_library, test_node},
+		MaybeUndefined,
+	};
+
+	use sd_p2p::spacetunnel::Identity;
+
+	use std::time::Duration;
+
+	use tokio::time::{sleep, timeout};
+
+	#[tokio::test]
+	async fn test_only_the_instance_vouches_for_its_node() {
+		let (_data_dir, _node, library) = test_library("Photos").await;
+		let instance = library
+			.db
+			.instance()
+			.find_unique(instance::pub_id::equals(
+				library.instance_uuid.as_bytes().to_vec(),
+			))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+
+		let node_identity = Identity::new().to_remote_identity();
+		let signature = library
+			.identity
+			.sign(&node_claim(library.id, &node_identity));
+		assert!(is_instance_node(
+			&instance,
+			library.id,
+			&node_identity,
+			&signature
+		));
+
+		// Nobody else can claim the instance
+		let other_signature = Identity::new().sign(&node_claim(library.id, &node_identity));
+		assert!(!is_instance_node(
+			&instance,
+			library.id,
+			&node_identity,
+			&other_signature
+		));
+		assert!(!is_instance_node(
+			&instance,
+			library.id,
+			&node_identity,
+			&[]
+		));
+
+		// Nor can the signature be replayed for another node or library
+		assert!(!is_instance_node(
+			&instance,
+			library.id,
+			&Identity::new().to_remote_identity(),
+			&signature
+		));
+		assert!(!is_instance_node(
+			&instance,
+			Uuid::new_v4(),
+			&node_identity,
+			&signature
+		));
+	}
+
+	#[tokio::test]
+	async fn test_library_rename_reaches_connected_peer() {
+		let (_data_dir_a, node_a, library_a) = test_library("Photos").await;
+		let (_data_dir_b, node_b) = test_node().await;
+
+		let library_b = node_b
+			.libraries
+			.create_with_uuid(
+				library_a.id,
+				LibraryName::new("Photos").unwrap(),
+				None,
+				false,
+				None,
+				&node_b,
+			)
+			.await
+			.unwrap();
+
+		// Like after pairing, each instance knows the other one and the node it lives on
+		for ((node, library), (other_node, other_library)) in [
+			((&node_a, &library_a), (&node_b, &library_b)),
+			((&node_b, &library_b), (&node_a, &library_a)),
+		] {
+			let other_instance = other_library
+				.db
+				.instance()
+				.find_unique(instance::pub_id::equals(
+					other_library.instance_uuid.as_bytes().to_vec(),
+				))
+				.exec()
+				.await
+				.unwrap()
+				.unwrap();
+
+			library
+				.db
+				.instance()
+				.create(
+					other_instance.pub_id,
+					IdentityOrRemoteIdentity::RemoteIdentity(
+						IdentityOrRemoteIdentity::from_bytes(&other_instance.identity)
+							.unwrap()
+							.remote_identity(),
+					)
+					.to_bytes(),
+					other_instance.node_id,
+					other_instance.node_name,
+					other_instance.node_platform,
+					other_instance.last_seen,
+					other_instance.date_created,
+					vec![instance::node_remote_identity::set(Some(
+						other_node.p2p.manager.identity().get_bytes().to_vec(),
+					))],
+				)
+				.exec()
+				.await
+				.unwrap();
+
+			node.libraries.update_instances(library.clone()).await;
+		}
+
+		// Connecting once, the rename must then arrive without reconnecting
+		let service = node_a.p2p.get_library_service(&library_a.id).unwrap();
+		let identity_b = node_b.p2p.manager.identity();
+		timeout(Duration::from_secs(30), async {
+			loop {
+				if let Ok(mut stream) = service
+					.connect(node_a.p2p.manager.clone(), &identity_b)
+					.await
+				{
+					stream.write_all(&Header::Ping.to_bytes()).await.unwrap();
+					break;
+				}
+
+				sleep(Duration::from_millis(100)).await;
+			}
+		})
+		.await
+		.expect("node B was never discovered by node A");
+
+		node_a
+			.libraries
+			.edit(
+				library_a.id,
+				Some(LibraryName::new("Holidays").unwrap()),
+				MaybeUndefined::Undefined,
+				MaybeUndefined::Undefined,
+			)
+			.await
+			.unwrap();
+
+		timeout(Duration::from_secs(10), async {
+			while library_b.config().await.name.as_str() != "Holidays" {
+				sleep(Duration::from_millis(100)).await;
+			}
+		})
+		.await
+		.expect("the rename didn't reach node B");
+
+		let instance_a = library_b
+			.db
+			.instance()
+			.find_unique(instance::pub_id::equals(
+				library_a.instance_uuid.as_bytes().to_vec(),
+			))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(instance_a.node_name, node_a.config.get().await.name);
+	}
+}
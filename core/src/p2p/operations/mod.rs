@@ -1,6 +1,8 @@
+pub mod library_metadata;
 pub mod ping;
 pub mod request_file;
 pub mod spacedrop;
+mod transfer_encryption;
 
-pub use request_file::request_file;
+pub use request_file::{pull_file, request_file};
 pub use spacedrop::spacedrop;
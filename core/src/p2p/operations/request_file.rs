@@ -1,41 +1,44 @@
 use crate::{
 	library::Library,
-	p2p::{Header, HeaderFile},
+	node::NetworkUsageCategory,
+	p2p::{Header, HeaderFile, P2PEvent, P2PManager},
 	Node,
 };
 
 use sd_file_path_helper::{file_path_to_handle_p2p_serve_file, IsolatedFilePathData};
 use sd_p2p::{
 	spaceblock::{BlockSize, Range, SpaceblockRequest, SpaceblockRequests, Transfer},
-	spacetime::UnicastStream,
+	spacetunnel::RemoteIdentity,
 	PeerMessageEvent,
 };
-use sd_prisma::prisma::file_path;
+use sd_prisma::prisma::{file_path, instance};
 
 use std::{
-	path::Path,
+	path::{Path, PathBuf},
 	sync::{
 		atomic::{AtomicBool, Ordering},
 		Arc,
 	},
 };
 
+use prisma_client_rust::QueryError;
 use tokio::{
 	fs::File,
-	io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+	io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter},
 };
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 /// Request a file from the remote machine over P2P. This is used for preview media and quick preview.
 ///
-/// DO NOT USE THIS WITHOUT `node.files_over_p2p_flag == true`
+/// DO NOT USE THIS WITHOUT `node.files_over_p2p_flag == true`, or while on a metered connection.
 pub async fn request_file(
-	mut stream: UnicastStream,
+	mut stream: impl AsyncRead + AsyncWrite + Unpin,
 	library: &Library,
 	file_path_id: Uuid,
 	range: Range,
 	output: impl AsyncWrite + Unpin,
+	on_progress: impl Fn(u8),
 ) -> Result<(), ()> {
 	let id = Uuid::new_v4();
 	// TODO: Tunnel for encryption + authentication
@@ -88,6 +91,7 @@ pub async fn request_file(
 				"P2P receiving file path '{}' - progress {}%",
 				file_path_id, percent
 			);
+			on_progress(percent);
 		},
 		&Arc::new(AtomicBool::new(false)),
 	)
@@ -103,6 +107,88 @@ pub async fn request_file(
 	Ok(())
 }
 
+/// Pull a file from a paired instance of the library and save it to `path`.
+///
+/// DO NOT USE THIS WITHOUT `node.files_over_p2p_flag == true`. Refuses to while on a metered
+/// connection.
+pub async fn pull_file(
+	p2p: Arc<P2PManager>,
+	library: Arc<Library>,
+	identity: RemoteIdentity,
+	file_path_id: Uuid,
+	path: PathBuf,
+) -> Result<Uuid, ()> {
+	if p2p.network_usage.is_metered() {
+		warn!("failed to pull file: files over P2P are paused on a metered connection");
+		return Err(());
+	}
+
+	let service = p2p.get_library_service(&library.id).ok_or_else(|| {
+		warn!(
+			"failed to pull file: library '{}' has no P2P service",
+			library.id
+		);
+	})?;
+
+	let stream = service
+		.connect(p2p.manager.clone(), &identity)
+		.await
+		.map_err(|err| {
+			warn!("failed to pull file: error connecting to '{identity}': {err:?}");
+		})?;
+	let stream = p2p
+		.network_usage
+		.count(stream, NetworkUsageCategory::FilesOverP2P);
+
+	let file = File::create(&path).await.map_err(|err| {
+		warn!("failed to pull file: error creating file '{path:?}': {err:?}");
+	})?;
+
+	let id = Uuid::new_v4();
+	tokio::spawn(async move {
+		debug!("({id}): pulling '{file_path_id}' from '{identity}' into '{path:?}'");
+
+		if request_file(
+			stream,
+			&library,
+			file_path_id,
+			Range::Full,
+			BufWriter::new(file),
+			|percent| {
+				p2p.events
+					.0
+					.send(P2PEvent::FilePullProgress { id, percent })
+					.ok();
+			},
+		)
+		.await
+		.is_ok()
+		{
+			info!("({id}): pulled '{file_path_id}' into '{path:?}'");
+		}
+	});
+
+	Ok(id)
+}
+
+/// Whether the node behind `identity` owns one of the library's instances, which means it's paired with it.
+///
+/// P2P connections are authenticated with the node's identity, not the instance's, so this can't check `instance.identity`.
+pub(crate) async fn is_paired(
+	library: &Library,
+	identity: &RemoteIdentity,
+) -> Result<bool, QueryError> {
+	Ok(library
+		.db
+		.instance()
+		.find_first(vec![instance::node_remote_identity::equals(Some(
+			identity.get_bytes().to_vec(),
+		))])
+		.exec()
+		.await?
+		.is_some())
+}
+
 pub(crate) async fn receiver(
 	node: &Arc<Node>,
 	HeaderFile {
@@ -113,10 +199,20 @@ pub(crate) async fn receiver(
 	}: HeaderFile,
 	event: PeerMessageEvent,
 ) -> Result<(), ()> {
-	let mut stream = event.stream;
-	#[allow(clippy::panic)] // If you've made it this far that's on you.
+	let mut stream = node
+		.network_usage
+		.count(event.stream, NetworkUsageCategory::FilesOverP2P);
 	if !node.files_over_p2p_flag.load(Ordering::Relaxed) {
-		panic!("Files over P2P is disabled!");
+		warn!(
+			"({id}): rejecting request for file '{file_path_id:?}' as files over P2P is disabled"
+		);
+		return Err(());
+	}
+	if node.network_usage.is_metered() {
+		warn!(
+			"({id}): rejecting request for file '{file_path_id:?}' as we're on a metered connection"
+		);
+		return Err(());
 	}
 
 	// TODO: Tunnel and authentication
@@ -133,6 +229,18 @@ pub(crate) async fn receiver(
 			// TODO: Send error to remote peer??? -> Can we avoid constructing connection until this is done so it's only an error on one side?
 		})?;
 
+	// Only nodes paired with the library are allowed to access it's files
+	let is_paired = is_paired(&library, &event.identity).await.map_err(|err| {
+		warn!("({id}): error querying for instances: {err:?}");
+	})?;
+	if !is_paired {
+		warn!(
+			"({id}): rejecting request from '{}' as it's not paired with library '{library_id:?}'",
+			event.identity
+		);
+		return Err(());
+	}
+
 	let file_path = library
 		.db
 		.file_path()
@@ -244,4 +352,50 @@ pub(crate) async fn receiver(
 	Ok(())
 }
 
-// TODO: Unit tests
+// TODO: Unit tests for the transfer itself
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_library;
+
+	use sd_p2p::spacetunnel::{Identity, IdentityOrRemoteIdentity};
+
+	use chrono::Utc;
+
+	#[tokio::test]
+	async fn test_request_from_paired_node_is_accepted() {
+		let (_data_dir, node, library) = test_library("Paired").await;
+
+		// Like after pairing, the other node's instance has its own identity and is reached through the node's
+		let remote_node_identity = Identity::new().to_remote_identity();
+		library
+			.db
+			.instance()
+			.create(
+				Uuid::new_v4().as_bytes().to_vec(),
+				IdentityOrRemoteIdentity::RemoteIdentity(Identity::new().to_remote_identity())
+					.to_bytes(),
+				Uuid::new_v4().as_bytes().to_vec(),
+				"Remote".to_string(),
+				0,
+				Utc::now().into(),
+				Utc::now().into(),
+				vec![instance::node_remote_identity::set(Some(
+					remote_node_identity.get_bytes().to_vec(),
+				))],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		assert!(is_paired(&library, &remote_node_identity).await.unwrap());
+		assert!(is_paired(&library, &node.p2p.manager.identity())
+			.await
+			.unwrap());
+		assert!(!is_paired(&library, &Identity::new().to_remote_identity())
+			.await
+			.unwrap());
+	}
+}
@@ -1,14 +1,32 @@
-use crate::p2p::{Header, P2PEvent, P2PManager};
+use crate::{
+	library::Library,
+	location::{
+		find_location, location_with_indexer_rules, scan_location_sub_path, LocationError,
+		LocationManagerError,
+	},
+	node::NetworkUsageCategory,
+	p2p::{Header, P2PEvent, P2PManager, SpacedropDirectory},
+	Node,
+};
 
+use sd_crypto::primitives::BLOCK_LEN;
+use sd_file_path_helper::{
+	ensure_sub_path_is_directory, ensure_sub_path_is_in_location, IsolatedFilePathData,
+};
 use sd_p2p::{
-	spaceblock::{BlockSize, Range, SpaceblockRequest, SpaceblockRequests, Transfer},
+	spaceblock::{
+		BlockSize, Range, SpaceblockRequest, SpaceblockRequests, Transfer, MAX_REQUESTS_V1,
+	},
 	spacetunnel::RemoteIdentity,
 	PeerMessageEvent,
 };
+use sd_prisma::prisma::{file_path, location};
+use sd_utils::{db::maybe_missing, error::FileIOError};
 
 use std::{
 	borrow::Cow,
-	path::PathBuf,
+	collections::VecDeque,
+	path::{Component, Path, PathBuf},
 	sync::{
 		atomic::{AtomicBool, Ordering},
 		Arc,
@@ -16,19 +34,87 @@ use std::{
 	time::Duration,
 };
 
-use futures::future::join_all;
+use prisma_client_rust::operator::or;
 use tokio::{
-	fs::{create_dir_all, File},
-	io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+	fs::{self, create_dir_all, File},
+	io::{self, duplex, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
 	sync::oneshot,
 	time::{sleep, Instant},
 };
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use super::transfer_encryption::{decrypt_file, encrypt_file, encrypted_requests, transfer_key};
+
 /// The amount of time to wait for a Spacedrop request to be accepted or rejected before it's automatically rejected
 pub(crate) const SPACEDROP_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// The amount of time to wait for the files received into a location to be indexed
+const SPACEDROP_INDEX_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// The number of `file_path`s to look up per query while waiting for them to be indexed
+const SPACEDROP_INDEX_QUERY_CHUNK_SIZE: usize = 200;
+
+/// The files which make up a Spacedrop.
+struct SpacedropManifest {
+	paths: Vec<PathBuf>,
+	requests: Vec<SpaceblockRequest>,
+	/// Every directory being sent, named like the files, so empty ones are sent too.
+	directories: Vec<String>,
+	/// Entries which were skipped and should be included in the final report.
+	warnings: Vec<String>,
+}
+
+impl SpacedropManifest {
+	/// Enumerate the files to send. Directories are walked recursively and each file is named by it's path relative to the directory's parent so the tree can be reconstructed by the receiver.
+	async fn new(paths: Vec<PathBuf>) -> Result<Self, std::io::Error> {
+		let mut this = Self {
+			paths: Vec::new(),
+			requests: Vec::new(),
+			directories: Vec::new(),
+			warnings: Vec::new(),
+		};
+
+		let mut pending = paths
+			.into_iter()
+			.map(|path| {
+				let name = path
+					.file_name()
+					.map(|v| v.to_string_lossy())
+					.unwrap_or(Cow::Borrowed(""))
+					.to_string();
+
+				(path, name)
+			})
+			.collect::<VecDeque<_>>();
+
+		while let Some((path, name)) = pending.pop_front() {
+			let metadata = fs::symlink_metadata(&path).await?;
+
+			if metadata.is_symlink() {
+				warn!("Skipping symlink '{path:?}' in Spacedrop");
+				this.warnings.push(format!("Skipped symlink '{name}'"));
+			} else if metadata.is_dir() {
+				let mut read_dir = fs::read_dir(&path).await?;
+				while let Some(entry) = read_dir.next_entry().await? {
+					let entry_name = format!("{name}/{}", entry.file_name().to_string_lossy());
+					pending.push_back((entry.path(), entry_name));
+				}
+
+				this.directories.push(name);
+			} else {
+				this.paths.push(path);
+				this.requests.push(SpaceblockRequest {
+					name,
+					size: metadata.len(),
+					range: Range::Full,
+				});
+			}
+		}
+
+		Ok(this)
+	}
+}
+
 // TODO: Proper error handling
 pub async fn spacedrop(
 	p2p: Arc<P2PManager>,
@@ -40,53 +126,69 @@ pub async fn spacedrop(
 		return Err(());
 	}
 
-	let (files, requests): (Vec<_>, Vec<_>) = join_all(paths.into_iter().map(|path| async move {
-		let file = File::open(&path).await?;
-		let metadata = file.metadata().await?;
-		let name = path
-			.file_name()
-			.map(|v| v.to_string_lossy())
-			.unwrap_or(Cow::Borrowed(""))
-			.to_string();
-
-		Ok((
-			(path, file),
-			SpaceblockRequest {
-				name,
-				size: metadata.len(),
-				range: Range::Full,
-			},
-		))
-	}))
-	.await
-	.into_iter()
-	.collect::<Result<Vec<_>, std::io::Error>>()
-	.map_err(|_| ())? // TODO: Error handling
-	.into_iter()
-	.unzip();
+	let SpacedropManifest {
+		paths,
+		requests,
+		directories,
+		warnings,
+	} = SpacedropManifest::new(paths).await.map_err(|err| {
+		debug!("failed to enumerate Spacedrop files: {err}");
+		// TODO: Error handling
+	})?;
+
+	if requests.is_empty() && directories.is_empty() {
+		return Err(());
+	}
 
 	let total_length: u64 = requests.iter().map(|req| req.size).sum();
 
+	// Peers which don't advertise support for it can't read an encrypted Spacedrop
+	let encrypted = p2p
+		.node
+		.get_discovered()
+		.into_iter()
+		.any(|peer| peer.identity == identity && peer.metadata.encrypted_spacedrop);
+
 	let id = Uuid::new_v4();
-	debug!("({id}): starting Spacedrop with peer '{identity}");
-	let mut stream = p2p.manager.stream(identity).await.map_err(|err| {
+	debug!("({id}): starting Spacedrop with peer '{identity}' (encrypted: {encrypted})");
+	let stream = p2p.manager.stream(identity).await.map_err(|err| {
 		debug!("({id}): failed to connect: {err:?}");
 		// TODO: Proper error
 	})?;
+	let mut stream = p2p
+		.network_usage
+		.count(stream, NetworkUsageCategory::Spacedrop);
 
 	tokio::spawn(async move {
 		debug!("({id}): connected, sending header");
-		let header = Header::Spacedrop(SpaceblockRequests {
+		let requests = SpaceblockRequests {
 			id,
 			block_size: BlockSize::from_size(total_length),
 			requests,
-		});
+		};
+		let header = if encrypted {
+			Header::EncryptedSpacedrop(SpacedropDirectory {
+				requests,
+				directories,
+			})
+		} else if directories.is_empty() && requests.requests.len() <= MAX_REQUESTS_V1 {
+			// The header every version of Spacedrive understands
+			Header::Spacedrop(requests)
+		} else {
+			Header::SpacedropDirectory(SpacedropDirectory {
+				requests,
+				directories,
+			})
+		};
 		if let Err(err) = stream.write_all(&header.to_bytes()).await {
 			debug!("({id}): failed to send header: {err}");
 			return;
 		}
-		let Header::Spacedrop(requests) = header else {
-			unreachable!();
+		let requests = match header {
+			Header::Spacedrop(requests)
+			| Header::SpacedropDirectory(SpacedropDirectory { requests, .. })
+			| Header::EncryptedSpacedrop(SpacedropDirectory { requests, .. }) => requests,
+			_ => unreachable!(),
 		};
 
 		debug!("({id}): waiting for response");
@@ -120,6 +222,12 @@ pub async fn spacedrop(
 		debug!("({id}): starting transfer");
 		let i = Instant::now();
 
+		let key = encrypted.then(|| transfer_key(&p2p, &identity, id));
+		let requests = if encrypted {
+			encrypted_requests(&requests)
+		} else {
+			requests
+		};
 		let mut transfer = Transfer::new(
 			&requests,
 			|percent| {
@@ -131,10 +239,34 @@ pub async fn spacedrop(
 			&cancelled,
 		);
 
-		for (file_id, (path, file)) in files.into_iter().enumerate() {
+		for (file_id, path) in paths.into_iter().enumerate() {
 			debug!("({id}): transmitting '{file_id}' from '{path:?}'");
-			let file = BufReader::new(file);
-			if let Err(err) = transfer.send(&mut stream, file).await {
+			// We open each file as we get to it so large directories don't exhaust the file descriptor limit
+			let file = match File::open(&path).await {
+				Ok(file) => BufReader::new(file),
+				Err(err) => {
+					debug!("({id}): failed to open file '{file_id}': {err}");
+					return;
+				}
+			};
+
+			let Some(key) = &key else {
+				if let Err(err) = transfer.send(&mut stream, file).await {
+					debug!("({id}): failed to send file '{file_id}': {err}");
+					// TODO: Error to frontend
+					return;
+				}
+
+				continue;
+			};
+
+			// The file is encrypted into a pipe which is read from by the transfer
+			let (reader, writer) = duplex(BLOCK_LEN);
+			let (sent, encrypted) = tokio::join!(
+				transfer.send(&mut stream, BufReader::new(reader)),
+				encrypt_file(key.clone(), id, file, writer)
+			);
+			if let Err(err) = sent {
 				debug!("({id}): failed to send file '{file_id}': {err}");
 				// TODO: Error to frontend
 				// p2p.events
@@ -143,21 +275,95 @@ pub async fn spacedrop(
 				// 	.ok();
 				return;
 			}
+			if let Err(err) = encrypted {
+				debug!("({id}): failed to encrypt file '{file_id}': {err}");
+				return;
+			}
 		}
 
 		debug!("({id}): finished; took '{:?}", i.elapsed());
+		p2p.events
+			.0
+			.send(P2PEvent::SpacedropCompleted { id, warnings })
+			.ok();
 	});
 
 	Ok(id)
 }
 
+/// Where an accepted Spacedrop will be saved.
+pub(crate) struct SpacedropDestination {
+	path: PathBuf,
+	/// When set the received files will be indexed into this location once the transfer completes.
+	location: Option<(Arc<Library>, location_with_indexer_rules::Data)>,
+}
+
 // TODO: Move these off the manager
 impl P2PManager {
 	pub async fn accept_spacedrop(&self, id: Uuid, path: String) {
+		self.accept_spacedrop_to(
+			id,
+			SpacedropDestination {
+				path: PathBuf::from(path),
+				location: None,
+			},
+		)
+		.await
+	}
+
+	/// Accept a Spacedrop into a sub path of a location.
+	/// The location must be online and writable or the Spacedrop is left pending so the user can choose somewhere else.
+	pub async fn accept_spacedrop_into_location(
+		&self,
+		node: &Node,
+		id: Uuid,
+		library: Arc<Library>,
+		location_id: location::id::Type,
+		sub_path: Option<String>,
+	) -> Result<(), LocationError> {
+		let location = find_location(&library, location_id)
+			.include(location_with_indexer_rules::include())
+			.exec()
+			.await?
+			.ok_or(LocationError::IdNotFound(location_id))?;
+
+		let location_pub_id =
+			Uuid::from_slice(&location.pub_id).map_err(LocationManagerError::from)?;
+		if !node.locations.is_online(&location_pub_id).await {
+			return Err(LocationError::Offline(location_id));
+		}
+
+		let location_path = maybe_missing(&location.path, "location.path")?;
+		let path = match sub_path {
+			Some(sub_path) => ensure_sub_path_is_in_location(location_path, sub_path).await?,
+			None => PathBuf::from(location_path),
+		};
+		ensure_sub_path_is_directory(location_path, &path).await?;
+
+		let metadata = fs::metadata(&path)
+			.await
+			.map_err(|e| FileIOError::from((&path, e)))?;
+		if metadata.permissions().readonly() {
+			return Err(LocationError::ReadOnly(path.into_boxed_path()));
+		}
+
+		self.accept_spacedrop_to(
+			id,
+			SpacedropDestination {
+				path,
+				location: Some((library, location)),
+			},
+		)
+		.await;
+
+		Ok(())
+	}
+
+	async fn accept_spacedrop_to(&self, id: Uuid, destination: SpacedropDestination) {
 		if let Some(chan) = self.spacedrop_pairing_reqs.lock().await.remove(&id) {
-			chan.send(Some(path))
-				.map_err(|err| {
-					warn!("error accepting Spacedrop '{id:?}': '{err:?}'");
+			chan.send(Some(destination))
+				.map_err(|_| {
+					warn!("error accepting Spacedrop '{id:?}': receiver dropped");
 				})
 				.ok();
 		}
@@ -182,15 +388,49 @@ impl P2PManager {
 
 pub(crate) async fn reciever(
 	this: &Arc<P2PManager>,
+	node: &Arc<Node>,
 	req: SpaceblockRequests,
 	event: PeerMessageEvent,
+	directories: Vec<String>,
+	encrypted: bool,
 ) -> Result<(), ()> {
 	let id = req.id;
-	let mut stream = event.stream;
+	let is_directory = !directories.is_empty();
+	let mut stream = this
+		.network_usage
+		.count(event.stream, NetworkUsageCategory::Spacedrop);
+
+	// Every name is checked before the Spacedrop is shown to the user, as once it's accepted the
+	// files follow each other on the stream and we can't skip one part way through
+	let (Some(relative_paths), Some(directory_paths)) = (
+		req.requests
+			.iter()
+			.map(|req| sanitise_relative_path(&req.name))
+			.collect::<Option<Vec<_>>>(),
+		directories
+			.iter()
+			.map(|name| sanitise_relative_path(name))
+			.collect::<Option<Vec<_>>>(),
+	) else {
+		warn!(
+			"({id}): rejecting Spacedrop from peer '{}' with an invalid file name",
+			event.identity
+		);
+
+		stream.write_all(&[0]).await.map_err(|err| {
+			error!("({id}): error sending rejection: '{err:?}'");
+		})?;
+		stream.flush().await.map_err(|err| {
+			error!("({id}): error flushing rejection: '{err:?}'");
+		})?;
+
+		return Ok(());
+	};
+
 	let (tx, rx) = oneshot::channel();
 
 	info!(
-		"({id}): received '{}' files from peer '{}' with block size '{:?}'",
+		"({id}): received '{}' files from peer '{}' with block size '{:?}', encrypted: {encrypted}",
 		req.requests.len(),
 		event.identity,
 		req.block_size
@@ -217,6 +457,15 @@ pub(crate) async fn reciever(
 				.iter()
 				.map(|req| req.name.clone())
 				.collect::<Vec<_>>(),
+			is_directory,
+			encrypted,
+			total_size: req
+				.requests
+				.iter()
+				.map(|req| req.size)
+				.sum::<u64>()
+				.to_string(),
+			file_count: req.requests.len() as u32,
 		})
 		.is_err()
 	{
@@ -237,9 +486,9 @@ pub(crate) async fn reciever(
 				error!("({id}): error flushing reject bit: '{err:?}'");
 			})?;
 		}
-		file_path = rx => {
-			match file_path {
-				Ok(Some(file_path)) => {
+		destination = rx => {
+			match destination {
+				Ok(Some(SpacedropDestination { path: file_path, location })) => {
 					info!("({id}): accepted saving to '{:?}'", file_path);
 
 					let cancelled = Arc::new(AtomicBool::new(false));
@@ -257,18 +506,35 @@ pub(crate) async fn reciever(
 					})?;
 
 					let names = req.requests.iter().map(|req| req.name.clone()).collect::<Vec<_>>();
-					let mut transfer = Transfer::new(&req, |percent| {
+					let joins_names = is_directory || names.len() != 1 || location.is_some();
+					if joins_names {
+						for directory in &directory_paths {
+							let path = file_path.join(directory);
+							create_dir_all(&path).await.map_err(|err| {
+								error!("({id}): error creating directory '{path:?}': '{err:?}'");
+
+								// TODO: Send error to the frontend
+							})?;
+						}
+					}
+
+					let (key, transfer_req) = if encrypted {
+						(Some(transfer_key(this, &event.identity, id)), encrypted_requests(&req))
+					} else {
+						(None, req.clone())
+					};
+					let mut transfer = Transfer::new(&transfer_req, |percent| {
 						this.events.0.send(P2PEvent::SpacedropProgress { id, percent }).ok();
 					}, &cancelled);
 
-					let file_path = PathBuf::from(file_path);
 					let names_len = names.len();
-					for file_name in names {
-						 // When transferring more than 1 file we wanna join the incoming file name to the directory provided by the user
+					let mut received = Vec::with_capacity(names_len);
+					for (file_name, relative_path) in names.into_iter().zip(relative_paths) {
+						 // When transferring a directory, more than 1 file or into a location we wanna join the incoming file name to the directory provided by the user
 						 let mut path = file_path.clone();
-						 if names_len != 1 {
+						 if joins_names {
 							// We know the `file_path` will be a directory so we can just push the file name to it
-							path.push(&file_name);
+							path.push(relative_path);
 						}
 
 						debug!("({id}): accepting '{file_name}' and saving to '{:?}'", path);
@@ -291,16 +557,42 @@ pub(crate) async fn reciever(
 							// TODO: Send error to remote peer
 						})?;
 						let f = BufWriter::new(f);
-						if let Err(err) = transfer.receive(&mut stream, f).await {
+						let received_file = if let Some(key) = &key {
+							// The transfer writes into a pipe which is decrypted into the file
+							let (reader, writer) = duplex(BLOCK_LEN);
+							let (received_file, decrypted) = tokio::join!(
+								transfer.receive(&mut stream, writer),
+								decrypt_file(key.clone(), id, reader, f)
+							);
+
+							received_file.and_then(|()| decrypted.map_err(io::Error::other))
+						} else {
+							transfer.receive(&mut stream, f).await
+						};
+						if let Err(err) = received_file {
 							error!("({id}): error receiving file '{file_name}': '{err:?}'");
 
 							// TODO: Send error to frontend
 
 							break;
 						}
+
+						received.push(path);
 					}
 
 					info!("({id}): complete");
+
+					if let Some((library, location)) = location {
+						if received.len() == names_len {
+							// We only rescan the top level directory which was received so we don't reindex the whole location
+							let scan_path = match received_root(&req, &directories) {
+								Some(root) if is_directory => file_path.join(root),
+								_ => file_path,
+							};
+
+							index_received(this, node, id, library, location, scan_path, received).await;
+						}
+					}
 				}
 				Ok(None) => {
 					info!("({id}): rejected");
@@ -321,3 +613,150 @@ pub(crate) async fn reciever(
 
 	Ok(())
 }
+
+/// The top level directory shared by every file and directory in a directory Spacedrop.
+fn received_root(req: &SpaceblockRequests, directories: &[String]) -> Option<PathBuf> {
+	let mut roots = req
+		.requests
+		.iter()
+		.map(|req| req.name.as_str())
+		.chain(directories.iter().map(String::as_str))
+		.map(|name| name.split('/').next().unwrap_or_default());
+	let root = roots.next()?;
+
+	roots
+		.all(|name| name == root)
+		.then(|| sanitise_relative_path(root))
+		.flatten()
+}
+
+/// Index the files received into a location and report their `file_path` ids once they show up in the database.
+async fn index_received(
+	this: &P2PManager,
+	node: &Arc<Node>,
+	id: Uuid,
+	library: Arc<Library>,
+	location: location_with_indexer_rules::Data,
+	scan_path: PathBuf,
+	received: Vec<PathBuf>,
+) {
+	let location_id = location.id;
+	let Some(location_path) = location.path.clone() else {
+		error!("({id}): location '{location_id}' is missing it's path");
+		return;
+	};
+
+	let iso_file_paths = match received
+		.iter()
+		.map(|path| IsolatedFilePathData::new(location_id, &location_path, path, false))
+		.collect::<Result<Vec<_>, _>>()
+	{
+		Ok(iso_file_paths) => iso_file_paths,
+		Err(err) => {
+			error!("({id}): received files aren't within location '{location_id}': '{err:?}'");
+			return;
+		}
+	};
+
+	if let Err(err) = scan_location_sub_path(node, &library, location, &scan_path).await {
+		error!("({id}): error scanning '{scan_path:?}': '{err:?}'");
+		return;
+	}
+
+	// The indexer runs as a job so we wait for the new `file_path`s to be created
+	let started_at = Instant::now();
+	let mut file_path_ids = Vec::new();
+	while started_at.elapsed() < SPACEDROP_INDEX_TIMEOUT {
+		sleep(Duration::from_secs(1)).await;
+
+		file_path_ids.clear();
+		for chunk in iso_file_paths.chunks(SPACEDROP_INDEX_QUERY_CHUNK_SIZE) {
+			match library
+				.db
+				.file_path()
+				.find_many(vec![or(chunk.iter().map(Into::into).collect())])
+				.select(file_path::select!({ id }))
+				.exec()
+				.await
+			{
+				Ok(file_paths) => file_path_ids.extend(file_paths.into_iter().map(|f| f.id)),
+				Err(err) => {
+					error!("({id}): error fetching indexed files: '{err:?}'");
+					return;
+				}
+			}
+		}
+
+		if file_path_ids.len() == iso_file_paths.len() {
+			break;
+		}
+	}
+
+	if file_path_ids.len() != iso_file_paths.len() {
+		warn!(
+			"({id}): only '{}' of '{}' received files were indexed before timing out",
+			file_path_ids.len(),
+			iso_file_paths.len()
+		);
+	}
+
+	this.events
+		.0
+		.send(P2PEvent::SpacedropIndexed {
+			id,
+			library_id: library.id,
+			location_id,
+			file_path_ids,
+		})
+		.ok();
+}
+
+/// Convert a `/` separated name from the remote peer into a relative path.
+/// Returns `None` if it would escape the destination directory.
+fn sanitise_relative_path(name: &str) -> Option<PathBuf> {
+	let mut path = PathBuf::new();
+	for segment in name.split('/') {
+		let mut components = Path::new(segment).components();
+		match (components.next(), components.next()) {
+			(Some(Component::Normal(component)), None) => path.push(component),
+			_ => return None,
+		}
+	}
+
+	Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_sanitise_relative_path() {
+		assert_eq!(
+			sanitise_relative_path("Demo/a/b.txt"),
+			Some(PathBuf::from("Demo").join("a").join("b.txt"))
+		);
+		assert_eq!(sanitise_relative_path("Demo/../b.txt"), None);
+		assert_eq!(sanitise_relative_path("/etc/passwd"), None);
+		assert_eq!(sanitise_relative_path("Demo//b.txt"), None);
+		assert_eq!(sanitise_relative_path("."), None);
+	}
+
+	#[test]
+	fn test_received_root_with_empty_directories() {
+		let req = SpaceblockRequests {
+			id: Uuid::new_v4(),
+			block_size: BlockSize::from_size(0),
+			requests: vec![],
+		};
+
+		assert_eq!(
+			received_root(&req, &["Demo".to_string(), "Demo/empty".to_string()]),
+			Some(PathBuf::from("Demo"))
+		);
+		assert_eq!(
+			received_root(&req, &["Demo".to_string(), "Other".to_string()]),
+			None
+		);
+	}
+}
@@ -0,0 +1,184 @@
+//! End-to-end encryption of the files sent with Spacedrop, on top of the encryption done by the transport.
+//!
+//! The key of a transfer is derived from the secret shared by the identities of both peers, salted with the transfer's id.
+//! Each file is sent as an `sd_crypto` [`FileHeader`], with a keyslot wrapping a random master key with the transfer's key, followed by the encrypted file.
+//! The transfer's id is part of the AAD, so blocks from one transfer can't be spliced into another.
+
+use crate::p2p::P2PManager;
+
+use sd_crypto::{
+	crypto::{Decryptor, Encryptor},
+	header::{
+		file::{FileHeader, FileHeaderVersion},
+		keyslot::{Keyslot, KEYSLOT_SIZE},
+	},
+	primitives::{AEAD_TAG_LEN, BLOCK_LEN, LATEST_KEYSLOT},
+	types::{Algorithm, Key, Salt},
+};
+use sd_p2p::{
+	spaceblock::{SpaceblockRequest, SpaceblockRequests},
+	spacetunnel::RemoteIdentity,
+};
+
+use std::io::Cursor;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use uuid::Uuid;
+
+const TRANSFER_KEY_CONTEXT: &str =
+	"spacedrive 2024-01-15 12:00:00 spacedrop transfer key derivation";
+
+const ALGORITHM: Algorithm = Algorithm::XChaCha20Poly1305;
+
+/// Pinned, as both peers need to agree on the header's size before reading it.
+const HEADER_VERSION: FileHeaderVersion = FileHeaderVersion::V2;
+
+/// The size of the header sent before each file, it has a single keyslot and no metadata or preview media.
+///
+/// The 2 trailing bytes flag that there's no metadata and no preview media.
+const HEADER_LEN: usize = FileHeader::size(HEADER_VERSION) + KEYSLOT_SIZE * 2 + 2;
+
+/// The key for the transfer with the peer, which only we and the peer can derive.
+pub(crate) fn transfer_key(p2p: &P2PManager, identity: &RemoteIdentity, transfer_id: Uuid) -> Key {
+	Key::derive_from_secret(
+		p2p.manager.shared_secret(identity).as_slice(),
+		Salt(transfer_id.into_bytes()),
+		TRANSFER_KEY_CONTEXT,
+	)
+}
+
+/// The requests with the size each file will have on the wire once encrypted.
+///
+/// Both peers exchange the plaintext sizes, and use this for the `Transfer`.
+pub(crate) fn encrypted_requests(requests: &SpaceblockRequests) -> SpaceblockRequests {
+	SpaceblockRequests {
+		id: requests.id,
+		block_size: requests.block_size.clone(),
+		requests: requests
+			.requests
+			.iter()
+			.map(|req| SpaceblockRequest {
+				name: req.name.clone(),
+				size: encrypted_size(req.size),
+				range: req.range.clone(),
+			})
+			.collect(),
+	}
+}
+
+/// Every block has a tag, including the last one which may be empty.
+const fn encrypted_size(size: u64) -> u64 {
+	HEADER_LEN as u64 + size + AEAD_TAG_LEN as u64 * (size / BLOCK_LEN as u64 + 1)
+}
+
+fn transfer_aad(header_aad: Vec<u8>, transfer_id: Uuid) -> Vec<u8> {
+	let mut aad = header_aad;
+	aad.extend_from_slice(transfer_id.as_bytes());
+	aad
+}
+
+/// Encrypt the file into `writer`, which is closed once it's done.
+pub(crate) async fn encrypt_file(
+	key: Key,
+	transfer_id: Uuid,
+	file: impl AsyncRead + Unpin + Send,
+	mut writer: impl AsyncWrite + Unpin + Send,
+) -> Result<(), sd_crypto::Error> {
+	let master_key = Key::generate();
+
+	let header = FileHeader::new(
+		HEADER_VERSION,
+		ALGORITHM,
+		vec![Keyslot::new_keyfile(LATEST_KEYSLOT, ALGORITHM, key, master_key.clone()).await?],
+	)?;
+	header.write(&mut writer).await?;
+
+	Encryptor::new(master_key, header.nonce, header.algorithm)?
+		.encrypt_streams(
+			file,
+			&mut writer,
+			&transfer_aad(header.generate_aad(), transfer_id),
+		)
+		.await
+}
+
+/// Decrypt a file received from `reader` into `file`.
+pub(crate) async fn decrypt_file(
+	key: Key,
+	transfer_id: Uuid,
+	mut reader: impl AsyncRead + Unpin + Send,
+	file: impl AsyncWrite + Unpin + Send,
+) -> Result<(), sd_crypto::Error> {
+	let mut header = vec![0u8; HEADER_LEN];
+	reader.read_exact(&mut header).await?;
+	let (header, aad) = FileHeader::from_reader(&mut Cursor::new(header)).await?;
+
+	let master_key = header.decrypt_master_key_with_keyfile(key).await?;
+
+	Decryptor::new(master_key, header.nonce, header.algorithm)?
+		.decrypt_streams(reader, file, &transfer_aad(aad, transfer_id))
+		.await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sd_p2p::spaceblock::{BlockSize, Range};
+
+	use tokio::io::duplex;
+
+	async fn round_trip(data: &[u8]) {
+		let key = Key::generate();
+		let transfer_id = Uuid::new_v4();
+
+		let mut encrypted = Vec::new();
+		encrypt_file(key.clone(), transfer_id, data, &mut encrypted)
+			.await
+			.unwrap();
+
+		let requests = encrypted_requests(&SpaceblockRequests {
+			id: transfer_id,
+			block_size: BlockSize::from_size(data.len() as u64),
+			requests: vec![SpaceblockRequest {
+				name: "Demo".to_string(),
+				size: data.len() as u64,
+				range: Range::Full,
+			}],
+		});
+		assert_eq!(requests.requests[0].size, encrypted.len() as u64);
+
+		let mut decrypted = Vec::new();
+		decrypt_file(key, transfer_id, encrypted.as_slice(), &mut decrypted)
+			.await
+			.unwrap();
+		assert_eq!(decrypted, data);
+	}
+
+	#[tokio::test]
+	async fn test_round_trip() {
+		round_trip(b"").await;
+		round_trip(b"Spacedrive").await;
+		round_trip(&vec![1u8; BLOCK_LEN]).await;
+		round_trip(&vec![2u8; BLOCK_LEN * 2 + 7]).await;
+	}
+
+	#[tokio::test]
+	async fn test_other_transfer_fails() {
+		let key = Key::generate();
+
+		let (reader, writer) = duplex(BLOCK_LEN);
+		let (encrypted, decrypted) = tokio::join!(
+			encrypt_file(
+				key.clone(),
+				Uuid::new_v4(),
+				b"Spacedrive".as_slice(),
+				writer
+			),
+			decrypt_file(key, Uuid::new_v4(), reader, Vec::new())
+		);
+
+		encrypted.unwrap();
+		assert!(decrypted.is_err());
+	}
+}
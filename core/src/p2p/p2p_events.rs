@@ -1,4 +1,5 @@
-use sd_p2p::spacetunnel::RemoteIdentity;
+use sd_p2p::{spacetunnel::RemoteIdentity, DiscoveryMode};
+use sd_prisma::prisma::{file_path, location};
 
 use serde::Serialize;
 use specta::Type;
@@ -28,15 +29,44 @@ pub enum P2PEvent {
 		identity: RemoteIdentity,
 		peer_name: String,
 		files: Vec<String>,
+		/// Whether a directory is being sent, in which case `files` are paths relative to the accepted directory.
+		is_directory: bool,
+		/// Whether the files are end-to-end encrypted between the peers, on top of the connection's encryption.
+		encrypted: bool,
+		// This is a `u64` but we send it as a string because `specta` doesn't support bigint
+		total_size: String,
+		file_count: u32,
 	},
 	SpacedropProgress {
 		id: Uuid,
 		percent: u8,
 	},
+	/// Progress of a file being pulled from a remote instance with `p2p.requestFile`.
+	FilePullProgress {
+		id: Uuid,
+		percent: u8,
+	},
 	SpacedropTimedout {
 		id: Uuid,
 	},
 	SpacedropRejected {
 		id: Uuid,
 	},
+	/// Emitted by the receiver once the files from a Spacedrop accepted into a location have been indexed.
+	SpacedropIndexed {
+		id: Uuid,
+		library_id: Uuid,
+		location_id: location::id::Type,
+		file_path_ids: Vec<file_path::id::Type>,
+	},
+	/// Emitted by the sender once a Spacedrop has been fully transmitted.
+	SpacedropCompleted {
+		id: Uuid,
+		/// Files which were skipped, such as symlinks.
+		warnings: Vec<String>,
+	},
+	/// Emitted once a new discovery mode set with `p2p.setDiscoveryMode` has been applied.
+	DiscoveryModeChanged {
+		mode: DiscoveryMode,
+	},
 }
@@ -1,5 +1,5 @@
 use crate::{
-	node::{config, get_hardware_model_name, HardwareModel},
+	node::{config, get_hardware_model_name, HardwareModel, NetworkUsage},
 	p2p::{OperatingSystem, SPACEDRIVE_APP_ID},
 };
 
@@ -18,7 +18,10 @@ use tokio::sync::{broadcast, mpsc, oneshot, Mutex};
 use tracing::info;
 use uuid::Uuid;
 
-use super::{LibraryMetadata, LibraryServices, P2PEvent, P2PManagerActor, PeerMetadata};
+use super::{
+	operations::spacedrop::SpacedropDestination, LibraryMetadata, LibraryServices, P2PEvent,
+	P2PManagerActor, PeerMetadata,
+};
 
 pub struct P2PManager {
 	pub(crate) node: Service<PeerMetadata>,
@@ -26,15 +29,18 @@ pub struct P2PManager {
 
 	pub events: (broadcast::Sender<P2PEvent>, broadcast::Receiver<P2PEvent>),
 	pub manager: Arc<Manager>,
-	pub(super) spacedrop_pairing_reqs: Arc<Mutex<HashMap<Uuid, oneshot::Sender<Option<String>>>>>,
+	pub(super) spacedrop_pairing_reqs:
+		Arc<Mutex<HashMap<Uuid, oneshot::Sender<Option<SpacedropDestination>>>>>,
 	pub(super) spacedrop_cancelations: Arc<Mutex<HashMap<Uuid, Arc<AtomicBool>>>>,
-	node_config_manager: Arc<config::Manager>,
+	pub(super) node_config_manager: Arc<config::Manager>,
+	pub(crate) network_usage: Arc<NetworkUsage>,
 }
 
 impl P2PManager {
 	pub async fn new(
 		node_config: Arc<config::Manager>,
 		libraries: Arc<crate::library::Libraries>,
+		network_usage: Arc<NetworkUsage>,
 	) -> Result<(Arc<P2PManager>, P2PManagerActor), ManagerError> {
 		let (keypair, manager_config) = {
 			let config = node_config.get().await;
@@ -61,6 +67,7 @@ impl P2PManager {
 			spacedrop_pairing_reqs: Default::default(),
 			spacedrop_cancelations: Default::default(),
 			node_config_manager: node_config,
+			network_usage,
 		});
 		this.update_metadata().await;
 
@@ -88,10 +95,26 @@ impl P2PManager {
 				operating_system: Some(OperatingSystem::get_os()),
 				device_model: Some(get_hardware_model_name().unwrap_or(HardwareModel::Other)),
 				version: Some(env!("CARGO_PKG_VERSION").to_string()),
+				encrypted_spacedrop: true,
 			}
 		});
 	}
 
+	/// Attempt to reach instances of the loaded libraries which we know about but haven't discovered on the local network through the configured relays.
+	pub(super) async fn connect_undiscovered_peers(&self) {
+		if self.node_config_manager.get().await.p2p.relays.is_empty() {
+			return;
+		}
+
+		for (_, service) in self.libraries.libraries() {
+			for (identity, status) in service.get_state() {
+				if matches!(status, PeerStatus::Unavailable) {
+					self.manager.connect_via_relay(identity).await;
+				}
+			}
+		}
+	}
+
 	pub fn subscribe(&self) -> broadcast::Receiver<P2PEvent> {
 		self.events.0.subscribe()
 	}
@@ -1,14 +1,20 @@
-use crate::Node;
+use crate::{node::NetworkUsageCategory, Node};
 
 use sd_p2p::{spacetunnel::Tunnel, Event, ManagerStream, Service, ServiceEvent};
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use futures::StreamExt;
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, time::interval};
 use tracing::error;
 
-use super::{operations, sync::SyncMessage, Header, LibraryMetadata, P2PEvent, P2PManager};
+use super::{
+	operations, sync::SyncMessage, Header, LibraryMetadata, P2PEvent, P2PManager,
+	SpacedropDirectory,
+};
+
+/// How often we try to reach known peers which haven't been discovered on the local network
+const RELAY_CONNECT_INTERVAL: Duration = Duration::from_secs(30);
 
 pub struct P2PManagerActor {
 	pub(super) manager: Arc<P2PManager>,
@@ -27,11 +33,13 @@ impl P2PManagerActor {
 		tokio::spawn({
 			async move {
 				let mut node_rx = this.node.listen();
+				let mut relay_interval = interval(RELAY_CONNECT_INTERVAL);
 
 				loop {
 					tokio::select! {
 					   // TODO: We ignore the response of this but I suspect it will be useful in the future so it stays for now.
 					   Some(_event) = register_service_rx.recv() => {},
+					   _ = relay_interval.tick() => this.connect_undiscovered_peers().await,
 					   // TODO: We should subscribe to library-level events too but frontend isn't cut out for them right now.
 					   Some(Ok(event)) = node_rx.next() => {
 								this.events.0
@@ -81,13 +89,22 @@ impl P2PManagerActor {
 										match header {
 											Header::Ping => operations::ping::reciever(event).await,
 											Header::Spacedrop(req) => {
-												operations::spacedrop::reciever(&this, req, event).await?
+												operations::spacedrop::reciever(&this, &node, req, event, vec![], false).await?
+											}
+											Header::SpacedropDirectory(SpacedropDirectory { requests, directories }) => {
+												operations::spacedrop::reciever(&this, &node, requests, event, directories, false).await?
+											}
+											Header::EncryptedSpacedrop(SpacedropDirectory { requests, directories }) => {
+												operations::spacedrop::reciever(&this, &node, requests, event, directories, true).await?
 											}
 											Header::Sync(library_id) => {
-												let mut tunnel =
+												let tunnel =
 													Tunnel::responder(event.stream).await.map_err(|err| {
 														error!("Failed `Tunnel::responder`: {}", err);
 													})?;
+												let mut tunnel = this
+													.network_usage
+													.count(tunnel, NetworkUsageCategory::P2PSync);
 
 												let msg =
 													SyncMessage::from_stream(&mut tunnel).await.map_err(|err| {
@@ -110,6 +127,9 @@ impl P2PManagerActor {
 											Header::File(req) => {
 												operations::request_file::receiver(&node, req, event).await?;
 											}
+											Header::LibraryMetadata(req) => {
+												operations::library_metadata::receiver(&node, req, event).await?;
+											}
 										}
 
 										Ok::<_, ()>(())
@@ -13,11 +13,13 @@ pub struct PeerMetadata {
 	pub operating_system: Option<OperatingSystem>,
 	pub device_model: Option<HardwareModel>,
 	pub version: Option<String>,
+	/// Whether the peer can receive end-to-end encrypted Spacedrops. Older nodes don't advertise it.
+	pub encrypted_spacedrop: bool,
 }
 
 impl Metadata for PeerMetadata {
 	fn to_hashmap(self) -> HashMap<String, String> {
-		let mut map = HashMap::with_capacity(5);
+		let mut map = HashMap::with_capacity(6);
 		map.insert("name".to_owned(), self.name);
 		if let Some(os) = self.operating_system {
 			map.insert("os".to_owned(), os.to_string());
@@ -28,6 +30,9 @@ impl Metadata for PeerMetadata {
 		if let Some(device_model) = self.device_model {
 			map.insert("device_model".to_owned(), device_model.to_string());
 		}
+		if self.encrypted_spacedrop {
+			map.insert("encrypted_spacedrop".to_owned(), "true".to_owned());
+		}
 		map
 	}
 
@@ -53,6 +58,7 @@ impl Metadata for PeerMetadata {
 					.unwrap_or("Other"),
 			)),
 			version: data.get("version").map(|v| v.to_owned()),
+			encrypted_spacedrop: data.get("encrypted_spacedrop").is_some_and(|v| v == "true"),
 		})
 	}
 }
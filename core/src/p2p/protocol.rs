@@ -16,6 +16,59 @@ pub struct HeaderFile {
 	pub(crate) range: Range,
 }
 
+/// The library's metadata as the sending instance has it, sent when it changes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct HeaderLibraryMetadata {
+	pub(crate) library_id: Uuid,
+	/// `instance.pub_id` of the sender in this library
+	pub(crate) instance_id: Uuid,
+	pub(crate) library_name: String,
+	pub(crate) node_name: String,
+	/// The instance's signature of the node it lives on, see `library_metadata::node_claim`
+	pub(crate) node_signature: Vec<u8>,
+}
+
+/// The files of a Spacedrop and the directories they're in, for the headers which were added with
+/// directory support, so they use [`SpaceblockRequests::to_bytes_v2`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct SpacedropDirectory {
+	pub(crate) requests: SpaceblockRequests,
+	/// Every directory sent, named like the files, so empty ones are recreated too.
+	pub(crate) directories: Vec<String>,
+}
+
+impl SpacedropDirectory {
+	async fn from_stream(stream: &mut (impl AsyncRead + Unpin)) -> Result<Self, HeaderError> {
+		let requests = SpaceblockRequests::from_stream_v2(stream).await?;
+
+		let len = stream
+			.read_u32_le()
+			.await
+			.map_err(|err| HeaderError::SpacedropDirectories(err.into()))?;
+		let mut directories = Vec::new();
+		for _ in 0..len {
+			directories.push(
+				decode::string(stream)
+					.await
+					.map_err(HeaderError::SpacedropDirectories)?,
+			);
+		}
+
+		Ok(Self {
+			requests,
+			directories,
+		})
+	}
+
+	fn to_bytes(&self, buf: &mut Vec<u8>) {
+		buf.extend_from_slice(&self.requests.to_bytes_v2());
+		buf.extend_from_slice(&(self.directories.len() as u32).to_le_bytes());
+		for directory in &self.directories {
+			encode::string(buf, directory);
+		}
+	}
+}
+
 /// TODO
 #[derive(Debug, PartialEq, Eq)]
 pub enum Header {
@@ -24,6 +77,13 @@ pub enum Header {
 	Spacedrop(SpaceblockRequests),
 	Sync(Uuid),
 	File(HeaderFile),
+	/// A Spacedrop of one or more directories.
+	/// Each request's name is it's path relative to the parent of the directory being sent, using `/` as the separator.
+	SpacedropDirectory(SpacedropDirectory),
+	/// A Spacedrop where each file is end-to-end encrypted with a key only the two peers can derive.
+	/// It's a directory Spacedrop when there are directories.
+	EncryptedSpacedrop(SpacedropDirectory),
+	LibraryMetadata(HeaderLibraryMetadata),
 }
 
 #[derive(Debug, Error)]
@@ -34,12 +94,16 @@ pub enum HeaderError {
 	DiscriminatorInvalid(u8),
 	#[error("error reading spacedrop request: {0}")]
 	SpacedropRequest(#[from] SpaceblockRequestsError),
+	#[error("error reading spacedrop directories: {0}")]
+	SpacedropDirectories(decode::Error),
 	#[error("error reading sync request: {0}")]
 	SyncRequest(decode::Error),
 	#[error("error reading header file: {0}")]
 	HeaderFile(decode::Error),
 	#[error("error invalid header file discriminator '{0}'")]
 	HeaderFileDiscriminatorInvalid(u8),
+	#[error("error reading library metadata: {0}")]
+	LibraryMetadata(decode::Error),
 }
 
 impl Header {
@@ -89,6 +153,29 @@ impl Header {
 					i => return Err(HeaderError::HeaderFileDiscriminatorInvalid(i)),
 				},
 			})),
+			5 => Ok(Self::SpacedropDirectory(
+				SpacedropDirectory::from_stream(stream).await?,
+			)),
+			6 => Ok(Self::EncryptedSpacedrop(
+				SpacedropDirectory::from_stream(stream).await?,
+			)),
+			7 => Ok(Self::LibraryMetadata(HeaderLibraryMetadata {
+				library_id: decode::uuid(stream)
+					.await
+					.map_err(HeaderError::LibraryMetadata)?,
+				instance_id: decode::uuid(stream)
+					.await
+					.map_err(HeaderError::LibraryMetadata)?,
+				library_name: decode::string(stream)
+					.await
+					.map_err(HeaderError::LibraryMetadata)?,
+				node_name: decode::string(stream)
+					.await
+					.map_err(HeaderError::LibraryMetadata)?,
+				node_signature: decode::buf(stream)
+					.await
+					.map_err(HeaderError::LibraryMetadata)?,
+			})),
 			d => Err(HeaderError::DiscriminatorInvalid(d)),
 		}
 	}
@@ -119,13 +206,94 @@ impl Header {
 				buf.extend_from_slice(&range.to_bytes());
 				buf
 			}
+			Self::SpacedropDirectory(transfer_request) => {
+				let mut bytes = vec![5];
+				transfer_request.to_bytes(&mut bytes);
+				bytes
+			}
+			Self::EncryptedSpacedrop(transfer_request) => {
+				let mut bytes = vec![6];
+				transfer_request.to_bytes(&mut bytes);
+				bytes
+			}
+			Self::LibraryMetadata(HeaderLibraryMetadata {
+				library_id,
+				instance_id,
+				library_name,
+				node_name,
+				node_signature,
+			}) => {
+				let mut buf = vec![7];
+				encode::uuid(&mut buf, library_id);
+				encode::uuid(&mut buf, instance_id);
+				encode::string(&mut buf, library_name);
+				encode::string(&mut buf, node_name);
+				encode::buf(&mut buf, node_signature);
+				buf
+			}
 		}
 	}
 }
 
 #[cfg(test)]
 mod tests {
-	// use super::*;
+	use super::*;
+
+	use sd_p2p::spaceblock::{BlockSize, SpaceblockRequest};
+
+	#[tokio::test]
+	async fn test_library_metadata_header() {
+		let original = Header::LibraryMetadata(HeaderLibraryMetadata {
+			library_id: Uuid::new_v4(),
+			instance_id: Uuid::new_v4(),
+			library_name: "Photos 📷".to_string(),
+			node_name: "Laptop".to_string(),
+			node_signature: vec![42; 64],
+		});
+
+		let mut cursor = std::io::Cursor::new(original.to_bytes());
+		assert_eq!(Header::from_stream(&mut cursor).await.unwrap(), original);
+	}
+
+	#[tokio::test]
+	async fn test_spacedrop_headers() {
+		let requests = || SpaceblockRequests {
+			id: Uuid::new_v4(),
+			block_size: BlockSize::from_size(42),
+			requests: vec![SpaceblockRequest {
+				name: "Demo/a.txt".to_string(),
+				size: 42,
+				range: Range::Full,
+			}],
+		};
+
+		for original in [
+			Header::Spacedrop(requests()),
+			Header::SpacedropDirectory(SpacedropDirectory {
+				requests: requests(),
+				directories: vec!["Demo".to_string(), "Demo/empty".to_string()],
+			}),
+			Header::EncryptedSpacedrop(SpacedropDirectory {
+				requests: requests(),
+				directories: vec![],
+			}),
+		] {
+			let mut cursor = std::io::Cursor::new(original.to_bytes());
+			assert_eq!(Header::from_stream(&mut cursor).await.unwrap(), original);
+		}
+
+		// The header of a plain Spacedrop can still be read by peers from before directories
+		let bytes = Header::Spacedrop(requests()).to_bytes();
+		assert_eq!(bytes[0], 0);
+		assert_eq!(
+			SpaceblockRequests::from_stream(&mut std::io::Cursor::new(&bytes[1..]))
+				.await
+				.unwrap()
+				.requests
+				.len(),
+			1
+		);
+	}
 
 	#[test]
 	fn test_header() {
@@ -2,6 +2,7 @@
 
 use crate::{
 	library::Library,
+	node::NetworkUsageCategory,
 	sync::{self, GetOpsArgs},
 };
 
@@ -112,7 +113,10 @@ mod originator {
 					.await
 					.unwrap();
 
-				let mut tunnel = Tunnel::initiator(stream).await.unwrap();
+				let mut tunnel = p2p.network_usage.count(
+					Tunnel::initiator(stream).await.unwrap(),
+					NetworkUsageCategory::P2PSync,
+				);
 
 				tunnel
 					.write_all(&SyncMessage::NewOperations.to_bytes())
@@ -228,7 +232,14 @@ mod responder {
 			let timestamps = match req {
 				Request::FinishedIngesting => break,
 				Request::Messages { timestamps } => timestamps,
-				_ => continue,
+				Request::Ingested { models } => {
+					library
+						.sync
+						.tx
+						.send(sync::SyncMessage::Ingested(models))
+						.ok();
+					continue;
+				}
 			};
 
 			debug!("Getting ops for timestamps {timestamps:?}");
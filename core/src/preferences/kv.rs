@@ -1,12 +1,17 @@
-use sd_prisma::prisma::{preference, PrismaClient};
+use crate::sync;
+
+use sd_prisma::{
+	prisma::{preference, PrismaClient},
+	prisma_sync,
+};
+use sd_sync::{CRDTOperation, OperationFactory};
 
 use std::collections::BTreeMap;
 
 use itertools::Itertools;
 use rmpv::Value;
 use serde::{de::DeserializeOwned, Serialize};
-
-use super::Preferences;
+use serde_json::json;
 
 #[derive(Debug)]
 pub struct PreferenceKey(Vec<String>);
@@ -111,24 +116,42 @@ impl PreferenceKVs {
 		self
 	}
 
-	pub fn into_upserts(self, db: &PrismaClient) -> Vec<preference::UpsertQuery> {
+	pub fn push(&mut self, key: PreferenceKey, value: PreferenceValue) {
+		self.0.push((key, value));
+	}
+
+	/// The CRDT operations and upserts writing these preferences, each key is synced on its own
+	/// so concurrent changes to different keys don't overwrite each other.
+	pub fn into_sync_upserts(
+		self,
+		sync: &sync::Manager,
+		db: &PrismaClient,
+	) -> (Vec<CRDTOperation>, Vec<preference::UpsertQuery>) {
 		self.0
 			.into_iter()
 			.map(|(key, value)| {
-				let value = vec![preference::value::set(Some(value.0))];
-
-				db.preference().upsert(
-					preference::key::equals(key.to_string()),
-					preference::create(key.to_string(), value.clone()),
-					value,
+				let key = key.to_string();
+				let params = vec![preference::value::set(Some(value.0.clone()))];
+
+				(
+					sync.shared_update(
+						prisma_sync::preference::SyncId { key: key.clone() },
+						preference::value::NAME,
+						json!(&value.0),
+					),
+					db.preference().upsert(
+						preference::key::equals(key.clone()),
+						preference::create(key, params.clone()),
+						params,
+					),
 				)
 			})
-			.collect()
+			.unzip()
 	}
 
-	pub fn parse<T: Preferences>(self) -> T {
-		let entries = self
-			.0
+	/// Nests the dot-separated keys into a tree of [`Entries`].
+	pub fn into_entries(self) -> Entries {
+		self.0
 			.into_iter()
 			.fold(BTreeMap::new(), |mut acc, (key, value)| {
 				let key_parts = key.0;
@@ -154,8 +177,6 @@ impl PreferenceKVs {
 				}
 
 				acc
-			});
-
-		T::from_entries(entries)
+			})
 	}
 }
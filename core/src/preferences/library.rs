@@ -1,4 +1,4 @@
-use crate::api::search;
+use crate::{api::search, library::Library};
 
 use sd_prisma::prisma::PrismaClient;
 
@@ -17,13 +17,41 @@ pub struct LibraryPreferences {
 	#[serde(default)]
 	#[specta(optional)]
 	location: HashMap<Uuid, Settings<LocationSettings>>,
+	/// The explorer settings used by views without their own, like locations that were never customized.
+	#[serde(default)]
+	#[specta(optional)]
+	explorer: Option<ExplorerSettings<search::file_path::FilePathOrder>>,
+	/// Whether GPS coordinates extracted from media are returned to the frontend.
+	#[serde(default)]
+	#[specta(optional)]
+	expose_location_metadata: Option<bool>,
+	/// Whether identifying files also stores their full BLAKE3 checksum, which reads them whole
+	/// instead of sampling them but tells apart big files the `cas_id` can't.
+	#[serde(default)]
+	#[specta(optional)]
+	full_hash: Option<bool>,
 }
 
+/// The version of the stored preferences, which is synced alongside them.
+const VERSION_KEY: &str = "version";
+
+/// Migrations of the stored preferences, the one at index `n` upgrades them from version `n` to
+/// `n + 1`. Push a migration here whenever a preference changes shape, the current version is the
+/// number of migrations. They must be idempotent, as an instance running an older version can
+/// sync its version back over a newer one.
+const MIGRATIONS: &[fn(&mut Entries)] = &[];
+
 impl LibraryPreferences {
-	pub async fn write(self, db: &PrismaClient) -> prisma_client_rust::Result<()> {
-		let kvs = self.to_kvs();
+	/// Writes the given preferences, each one is synced on its own so the last write of every key
+	/// wins instead of the last write of the whole set.
+	pub async fn write(self, Library { db, sync, .. }: &Library) -> prisma_client_rust::Result<()> {
+		let mut kvs = self.to_kvs();
+		kvs.push(
+			PreferenceKey::new(VERSION_KEY),
+			PreferenceValue::new(MIGRATIONS.len() as u32),
+		);
 
-		db._batch(kvs.into_upserts(db)).await?;
+		sync.write_ops(db, kvs.into_sync_upserts(sync, db)).await?;
 
 		Ok(())
 	}
@@ -31,7 +59,7 @@ impl LibraryPreferences {
 	pub async fn read(db: &PrismaClient) -> prisma_client_rust::Result<Self> {
 		let kvs = db.preference().find_many(vec![]).exec().await?;
 
-		let prefs = PreferenceKVs::new(
+		let mut entries = PreferenceKVs::new(
 			kvs.into_iter()
 				.filter_map(|data| {
 					rmpv::decode::read_value(&mut data.value?.as_slice())
@@ -45,9 +73,29 @@ impl LibraryPreferences {
 						})
 				})
 				.collect(),
-		);
+		)
+		.into_entries();
+
+		// Preferences written before they were versioned are version 0. Newer versions synced from
+		// another instance have no migrations here and are read as they are.
+		let version = entries
+			.remove(VERSION_KEY)
+			.map(Entry::expect_value::<u32>)
+			.unwrap_or(0);
+
+		for migrate in MIGRATIONS.iter().skip(version as usize) {
+			migrate(&mut entries);
+		}
+
+		Ok(Self::from_entries(entries))
+	}
+
+	pub fn expose_location_metadata(&self) -> bool {
+		self.expose_location_metadata.unwrap_or(false)
+	}
 
-		Ok(prefs.parse())
+	pub fn full_hash(&self) -> bool {
+		self.full_hash.unwrap_or(false)
 	}
 }
 
@@ -94,9 +142,37 @@ pub enum DoubleClickAction {
 
 impl Preferences for LibraryPreferences {
 	fn to_kvs(self) -> PreferenceKVs {
-		let Self { location } = self;
+		let Self {
+			location,
+			explorer,
+			expose_location_metadata,
+			full_hash,
+		} = self;
+
+		let mut kvs = location.to_kvs().with_prefix("location");
+
+		if let Some(explorer) = explorer {
+			kvs.push(
+				PreferenceKey::new("explorer"),
+				PreferenceValue::new(explorer),
+			);
+		}
+
+		if let Some(expose_location_metadata) = expose_location_metadata {
+			kvs.push(
+				PreferenceKey::new("exposeLocationMetadata"),
+				PreferenceValue::new(expose_location_metadata),
+			);
+		}
+
+		if let Some(full_hash) = full_hash {
+			kvs.push(
+				PreferenceKey::new("fullHash"),
+				PreferenceValue::new(full_hash),
+			);
+		}
 
-		location.to_kvs().with_prefix("location")
+		kvs
 	}
 
 	fn from_entries(mut entries: Entries) -> Self {
@@ -105,6 +181,11 @@ impl Preferences for LibraryPreferences {
 				.remove("location")
 				.map(|value| HashMap::from_entries(value.expect_nested()))
 				.unwrap_or_default(),
+			explorer: entries.remove("explorer").map(Entry::expect_value),
+			expose_location_metadata: entries
+				.remove("exposeLocationMetadata")
+				.map(Entry::expect_value),
+			full_hash: entries.remove("fullHash").map(Entry::expect_value),
 		}
 	}
 }
@@ -1,18 +1,20 @@
 // ! A system for loading a default set of data on startup. This is ONLY enabled in development builds.
 
 use crate::{
+	api::search::SearchFilterArgs,
 	job::JobManagerError,
 	library::Libraries,
-	library::{LibraryManagerError, LibraryName},
+	library::{Library, LibraryManagerError, LibraryName},
 	location::{
 		delete_location, scan_location, LocationCreateArgs, LocationError, LocationManagerError,
 	},
+	object::tag::TagCreateArgs,
 	util::AbortOnDrop,
 	Node,
 };
 
-use sd_prisma::prisma::location;
-use sd_utils::error::FileIOError;
+use sd_prisma::prisma::{location, saved_search, tag};
+use sd_utils::{chain_optional_iter, error::FileIOError};
 
 use std::{
 	io,
@@ -21,6 +23,7 @@ use std::{
 	time::Duration,
 };
 
+use chrono::{DateTime, FixedOffset, Utc};
 use prisma_client_rust::QueryError;
 use serde::Deserialize;
 use thiserror::Error;
@@ -35,6 +38,30 @@ use uuid::Uuid;
 #[serde(rename_all = "camelCase")]
 pub struct LocationInitConfig {
 	path: String,
+	#[serde(default = "default_run_initial_scan")]
+	run_initial_scan: bool,
+}
+
+fn default_run_initial_scan() -> bool {
+	true
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagInitConfig {
+	name: String,
+	color: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearchInitConfig {
+	name: String,
+	search: Option<String>,
+	#[serde(default)]
+	filters: Vec<SearchFilterArgs>,
+	description: Option<String>,
+	icon: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -46,6 +73,10 @@ pub struct LibraryInitConfig {
 	#[serde(default)]
 	reset_locations_on_startup: bool,
 	locations: Vec<LocationInitConfig>,
+	#[serde(default)]
+	tags: Vec<TagInitConfig>,
+	#[serde(default)]
+	saved_searches: Vec<SavedSearchInitConfig>,
 }
 
 #[derive(Deserialize)]
@@ -60,8 +91,15 @@ pub struct InitConfig {
 
 #[derive(Error, Debug)]
 pub enum InitConfigError {
-	#[error("error parsing the init data: {0}")]
-	Json(#[from] serde_json::Error),
+	#[error("error parsing the init data at '{path}': {source}")]
+	Json {
+		path: String,
+		source: serde_json::Error,
+	},
+	#[error("invalid init data at '{path}': {message}")]
+	Invalid { path: String, message: &'static str },
+	#[error("failed to serialize saved search filters: {0}")]
+	SavedSearchFilters(serde_json::Error),
 	#[error("job manager: {0}")]
 	JobManager(#[from] JobManagerError),
 	#[error("location manager: {0}")]
@@ -90,9 +128,7 @@ impl InitConfig {
 				.await
 				.map_err(|e| FileIOError::from((&path, e, "Failed to read init config file")))?;
 
-			let mut config = serde_json::from_slice::<InitConfig>(&config)?;
-
-			config.path = path;
+			let config = Self::parse(&config, path)?;
 
 			if config.reset_on_startup && metadata(data_dir).await.is_ok() {
 				warn!("previous 'SD_DATA_DIR' was removed on startup!");
@@ -107,6 +143,63 @@ impl InitConfig {
 		Ok(None)
 	}
 
+	/// Parses and validates the config, errors point to the offending JSON path, like `libraries[0].tags[1].color`.
+	fn parse(bytes: &[u8], path: PathBuf) -> Result<Self, InitConfigError> {
+		let mut config: Self =
+			serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_slice(bytes))
+				.map_err(|e| InitConfigError::Json {
+					path: e.path().to_string(),
+					source: e.into_inner(),
+				})?;
+
+		config.validate()?;
+		config.path = path;
+
+		Ok(config)
+	}
+
+	fn validate(&self) -> Result<(), InitConfigError> {
+		let invalid = |path: String, message| Err(InitConfigError::Invalid { path, message });
+
+		for (i, lib) in self.libraries.iter().enumerate() {
+			for (j, loc) in lib.locations.iter().enumerate() {
+				if loc.path.is_empty() {
+					return invalid(
+						format!("libraries[{i}].locations[{j}].path"),
+						"location path can't be empty",
+					);
+				}
+			}
+
+			for (j, tag) in lib.tags.iter().enumerate() {
+				if tag.name.is_empty() {
+					return invalid(
+						format!("libraries[{i}].tags[{j}].name"),
+						"tag name can't be empty",
+					);
+				}
+
+				if !is_hex_color(&tag.color) {
+					return invalid(
+						format!("libraries[{i}].tags[{j}].color"),
+						"tag color must be a hex color, like '#A455FF'",
+					);
+				}
+			}
+
+			for (j, search) in lib.saved_searches.iter().enumerate() {
+				if search.name.is_empty() {
+					return invalid(
+						format!("libraries[{i}].savedSearches[{j}].name"),
+						"saved search name can't be empty",
+					);
+				}
+			}
+		}
+
+		Ok(())
+	}
+
 	pub async fn apply(
 		self,
 		library_manager: &Arc<Libraries>,
@@ -174,11 +267,15 @@ impl InitConfig {
 					path: PathBuf::from(loc.path.clone()),
 					dry_run: false,
 					indexer_rules_ids: Vec::new(),
+					interactive: false,
+					force: false,
 				})
 				.create(node, &library)
 				.await?
 				{
-					scan_location(node, &library, location).await?;
+					if loc.run_initial_scan {
+						scan_location(node, &library, location, false).await?;
+					}
 				} else {
 					warn!(
 						"Debug init error: location '{}' was not found after being created!",
@@ -186,6 +283,9 @@ impl InitConfig {
 					);
 				}
 			}
+
+			create_tags(&library, lib.tags).await?;
+			create_saved_searches(&library, lib.saved_searches).await?;
 		}
 
 		info!("Initialized app from file: {}", self.path.display());
@@ -193,3 +293,178 @@ impl InitConfig {
 		Ok(())
 	}
 }
+
+fn is_hex_color(color: &str) -> bool {
+	color
+		.strip_prefix('#')
+		.filter(|hex| matches!(hex.len(), 3 | 6))
+		.is_some_and(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Tags that already exist with the same name are left alone, so the config can be applied on every startup
+async fn create_tags(library: &Library, tags: Vec<TagInitConfig>) -> Result<(), InitConfigError> {
+	for TagInitConfig { name, color } in tags {
+		if library
+			.db
+			.tag()
+			.find_first(vec![tag::name::equals(Some(name.clone()))])
+			.exec()
+			.await?
+			.is_some()
+		{
+			continue;
+		}
+
+		TagCreateArgs { name, color }.exec(library).await?;
+	}
+
+	Ok(())
+}
+
+/// Same as [`create_tags`], saved searches are matched by name
+async fn create_saved_searches(
+	library: &Library,
+	searches: Vec<SavedSearchInitConfig>,
+) -> Result<(), InitConfigError> {
+	for search in searches {
+		if library
+			.db
+			.saved_search()
+			.find_first(vec![saved_search::name::equals(Some(search.name.clone()))])
+			.exec()
+			.await?
+			.is_some()
+		{
+			continue;
+		}
+
+		let date_created: DateTime<FixedOffset> = Utc::now().into();
+
+		// Stored the same way the interface does, as a JSON array of filters
+		let filters = (!search.filters.is_empty())
+			.then(|| serde_json::to_string(&search.filters))
+			.transpose()
+			.map_err(InitConfigError::SavedSearchFilters)?;
+
+		library
+			.db
+			.saved_search()
+			.create(
+				Uuid::new_v4().as_bytes().to_vec(),
+				chain_optional_iter(
+					[
+						saved_search::date_created::set(Some(date_created)),
+						saved_search::name::set(Some(search.name)),
+					],
+					[
+						filters.map(Some).map(saved_search::filters::set),
+						search.search.map(Some).map(saved_search::search::set),
+						search
+							.description
+							.map(Some)
+							.map(saved_search::description::set),
+						search.icon.map(Some).map(saved_search::icon::set),
+					],
+				),
+			)
+			.exec()
+			.await?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::panic)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::test_node;
+
+	use tempfile::tempdir;
+
+	fn fixture(photos: &Path) -> String {
+		std::fs::read_to_string(
+			Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/init_config.json"),
+		)
+		.unwrap()
+		.replace(
+			"\"$PHOTOS\"",
+			&serde_json::to_string(&photos.to_str().unwrap()).unwrap(),
+		)
+	}
+
+	#[tokio::test]
+	async fn test_apply_init_config() {
+		let (_data_dir, node) = test_node().await;
+		let photos = tempdir().unwrap();
+
+		let config = InitConfig::parse(fixture(photos.path()).as_bytes(), PathBuf::new()).unwrap();
+		let library_id = config.libraries[0].id;
+
+		config.apply(&node.libraries, &node).await.unwrap();
+
+		let library = node.libraries.get_library(&library_id).await.unwrap();
+		let db = &library.db;
+
+		let locations = db.location().find_many(vec![]).exec().await.unwrap();
+		assert_eq!(locations.len(), 1);
+		assert_eq!(
+			locations[0].path.as_deref(),
+			Some(photos.path().to_str().unwrap())
+		);
+
+		// `runInitialScan` is off, so no indexer job was dispatched
+		assert_eq!(db.job().count(vec![]).exec().await.unwrap(), 0);
+
+		let mut tags = db
+			.tag()
+			.find_many(vec![])
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|tag| (tag.name.unwrap(), tag.color.unwrap()))
+			.collect::<Vec<_>>();
+		tags.sort();
+		assert_eq!(
+			tags,
+			[
+				("Holiday".to_string(), "#22C55E".to_string()),
+				("Important".to_string(), "#EF4444".to_string())
+			]
+		);
+
+		let searches = db.saved_search().find_many(vec![]).exec().await.unwrap();
+		assert_eq!(searches.len(), 1);
+		assert_eq!(searches[0].name.as_deref(), Some("Holiday photos"));
+		assert_eq!(searches[0].search.as_deref(), Some("beach"));
+		assert_eq!(
+			serde_json::from_str::<serde_json::Value>(searches[0].filters.as_deref().unwrap())
+				.unwrap(),
+			serde_json::json!([{ "object": { "kind": { "in": [5] } } }])
+		);
+	}
+
+	#[test]
+	fn test_init_config_errors_point_to_json_path() {
+		let mut config =
+			serde_json::from_str::<serde_json::Value>(&fixture(Path::new("/photos"))).unwrap();
+
+		config["libraries"][0]["tags"][1]["color"] = "green".into();
+		match InitConfig::parse(config.to_string().as_bytes(), PathBuf::new()) {
+			Err(InitConfigError::Invalid { path, .. }) => {
+				assert_eq!(path, "libraries[0].tags[1].color")
+			}
+			_ => panic!("expected a validation error"),
+		}
+
+		config["libraries"][0]["locations"][0]["runInitialScan"] = "yes".into();
+		match InitConfig::parse(config.to_string().as_bytes(), PathBuf::new()) {
+			Err(InitConfigError::Json { path, .. }) => {
+				assert_eq!(path, "libraries[0].locations[0].runInitialScan")
+			}
+			_ => panic!("expected a parsing error"),
+		}
+	}
+}
@@ -4,8 +4,16 @@
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use specta::Type;
 
+/// A field of an update which can be left out to keep the current value, set to `null` to clear it
+/// or set to a value.
+///
+/// Serde can't tell a missing field apart from a `null` one by the field's type alone, so fields
+/// must be marked with `#[serde(default)]` to be [`MaybeUndefined::Undefined`] when missing, and
+/// with `#[specta(optional)]` so their Typescript type is `field?: T | null`.
+///
+/// `T` can itself have `MaybeUndefined` fields to only update some of a nested value's fields.
 // This exports an incorrect Typescript type. https://github.com/oscartbeaumont/specta/issues/157
-#[derive(Debug, Clone, Type)]
+#[derive(Debug, Clone, PartialEq, Eq, Type)]
 #[specta(untagged)]
 pub enum MaybeUndefined<T> {
 	Undefined,
@@ -13,12 +21,53 @@ pub enum MaybeUndefined<T> {
 	Value(T),
 }
 
+// Combine with `#[serde(default)]` so a missing field is `Undefined` instead of `Null`.
+impl<T> Default for MaybeUndefined<T> {
+	fn default() -> Self {
+		Self::Undefined
+	}
+}
+
 impl<T> MaybeUndefined<T> {
 	// `Null | Value(T)` will return `true` else `false`.
 	pub fn is_defined(&self) -> bool {
 		!matches!(self, Self::Undefined)
 	}
 
+	// Use with `#[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]` so an `Undefined`
+	// field is left out instead of being serialized as `null`.
+	pub fn is_undefined(&self) -> bool {
+		matches!(self, Self::Undefined)
+	}
+
+	pub fn map<U>(self, f: impl FnOnce(T) -> U) -> MaybeUndefined<U> {
+		match self {
+			Self::Undefined => MaybeUndefined::Undefined,
+			Self::Null => MaybeUndefined::Null,
+			Self::Value(v) => MaybeUndefined::Value(f(v)),
+		}
+	}
+
+	pub fn as_ref(&self) -> MaybeUndefined<&T> {
+		match self {
+			Self::Undefined => MaybeUndefined::Undefined,
+			Self::Null => MaybeUndefined::Null,
+			Self::Value(v) => MaybeUndefined::Value(v),
+		}
+	}
+
+	/// The new value if it's being updated, `None` if it's [`MaybeUndefined::Undefined`].
+	pub fn as_option(&self) -> Option<Option<&T>> {
+		self.as_ref().into()
+	}
+
+	/// Updates `target` unless it's [`MaybeUndefined::Undefined`].
+	pub fn apply_to(self, target: &mut Option<T>) {
+		if let Some(value) = self.into() {
+			*target = value;
+		}
+	}
+
 	pub fn unwrap_or(self, t: T) -> T {
 		match self {
 			Self::Value(v) => v,
@@ -81,3 +130,84 @@ where
 		})
 	}
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use serde_json::json;
+	use specta::ts::{export, ExportConfig};
+
+	#[derive(Debug, PartialEq, Default, Serialize, Deserialize, Type)]
+	struct Description {
+		#[serde(default, skip_serializing_if = "MaybeUndefined::is_undefined")]
+		#[specta(optional)]
+		text: MaybeUndefined<String>,
+		#[serde(default, skip_serializing_if = "MaybeUndefined::is_undefined")]
+		#[specta(optional)]
+		emoji: MaybeUndefined<String>,
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize, Type)]
+	struct EditArgs {
+		#[serde(default, skip_serializing_if = "MaybeUndefined::is_undefined")]
+		#[specta(optional)]
+		description: MaybeUndefined<Description>,
+	}
+
+	#[test]
+	fn test_serde_round_trip() {
+		for (value, args) in [
+			(json!({}), MaybeUndefined::Undefined),
+			(json!({ "description": null }), MaybeUndefined::Null),
+			(
+				json!({ "description": {} }),
+				MaybeUndefined::Value(Description::default()),
+			),
+			(
+				json!({ "description": { "text": null, "emoji": "🌱" } }),
+				MaybeUndefined::Value(Description {
+					text: MaybeUndefined::Null,
+					emoji: MaybeUndefined::Value("🌱".to_string()),
+				}),
+			),
+		] {
+			let args = EditArgs { description: args };
+
+			assert_eq!(
+				serde_json::from_value::<EditArgs>(value.clone()).unwrap(),
+				args
+			);
+			assert_eq!(serde_json::to_value(&args).unwrap(), value);
+		}
+	}
+
+	#[test]
+	fn test_apply_to() {
+		let mut target = Some("old".to_string());
+
+		MaybeUndefined::Undefined.apply_to(&mut target);
+		assert_eq!(target, Some("old".to_string()));
+
+		MaybeUndefined::Value("new".to_string()).apply_to(&mut target);
+		assert_eq!(target, Some("new".to_string()));
+
+		MaybeUndefined::Null.apply_to(&mut target);
+		assert_eq!(target, None);
+	}
+
+	#[test]
+	fn test_typescript_type() {
+		let config = ExportConfig::default();
+
+		assert_eq!(
+			export::<MaybeUndefined<String>>(&config).unwrap(),
+			"export type MaybeUndefined<T> = null | T"
+		);
+		assert_eq!(
+			export::<EditArgs>(&config).unwrap(),
+			"export type EditArgs = { description?: MaybeUndefined<Description> }"
+		);
+	}
+}
@@ -6,6 +6,8 @@ mod infallible_request;
 mod maybe_undefined;
 pub mod mpscrr;
 mod observable;
+#[cfg(test)]
+pub mod test_utils;
 mod unsafe_streamed_query;
 pub mod version_manager;
 
@@ -0,0 +1,32 @@
+//! Fixtures for the tests which need a whole node or library.
+
+#![allow(clippy::unwrap_used)]
+
+use crate::{
+	library::{Library, LibraryName},
+	Env, Node,
+};
+
+use std::sync::Arc;
+
+use tempfile::{tempdir, TempDir};
+
+/// A node keeping its data in a temporary directory, which is deleted once the [`TempDir`] is dropped.
+pub async fn test_node() -> (TempDir, Arc<Node>) {
+	let data_dir = tempdir().unwrap();
+	let (node, _) = Node::new(data_dir.path(), Env::new("test")).await.unwrap();
+
+	(data_dir, node)
+}
+
+/// A new library on its own [`test_node`].
+pub async fn test_library(name: &str) -> (TempDir, Arc<Node>, Arc<Library>) {
+	let (data_dir, node) = test_node().await;
+	let library = node
+		.libraries
+		.create(LibraryName::new(name).unwrap(), None, &node)
+		.await
+		.unwrap();
+
+	(data_dir, node, library)
+}
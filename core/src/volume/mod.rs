@@ -5,8 +5,9 @@ use sd_cache::Model;
 use std::{
 	fmt::Display,
 	hash::{Hash, Hasher},
-	path::PathBuf,
+	path::{Path, PathBuf},
 	sync::OnceLock,
+	time::{Duration, Instant},
 };
 
 use serde::{Deserialize, Serialize};
@@ -19,11 +20,54 @@ use tracing::error;
 
 pub mod watcher;
 
+/// How long [`get_volumes_cached`] keeps handing out the same volumes.
+const VOLUMES_CACHE_TTL: Duration = Duration::from_secs(10);
+
 fn sys_guard() -> &'static Mutex<System> {
 	static SYS: OnceLock<Mutex<System>> = OnceLock::new();
 	SYS.get_or_init(|| Mutex::new(System::new_all()))
 }
 
+/// Same as [`get_volumes`], but reuses the volumes it got for a few seconds, as going through the
+/// disks isn't cheap.
+pub async fn get_volumes_cached() -> Vec<Volume> {
+	static CACHE: OnceLock<Mutex<Option<(Instant, Vec<Volume>)>>> = OnceLock::new();
+
+	let mut cache = CACHE.get_or_init(Default::default).lock().await;
+
+	if let Some((fetched_at, volumes)) = &*cache {
+		if fetched_at.elapsed() < VOLUMES_CACHE_TTL {
+			return volumes.clone();
+		}
+	}
+
+	let volumes = get_volumes().await;
+	*cache = Some((Instant::now(), volumes.clone()));
+
+	volumes
+}
+
+/// The volume hosting `path`, the one with the deepest mount point it's in.
+pub async fn get_volume_of(path: impl AsRef<Path>) -> Option<Volume> {
+	let path = path.as_ref();
+
+	get_volumes_cached()
+		.await
+		.into_iter()
+		.filter_map(|volume| {
+			let depth = volume
+				.mount_points
+				.iter()
+				.filter(|mount_point| path.starts_with(mount_point))
+				.map(|mount_point| mount_point.components().count())
+				.max()?;
+
+			Some((depth, volume))
+		})
+		.max_by_key(|(depth, _)| *depth)
+		.map(|(_, volume)| volume)
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Type, Hash, PartialEq, Eq)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum DiskType {
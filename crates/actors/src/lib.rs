@@ -1,18 +1,122 @@
-use futures::Future;
-use std::{collections::HashMap, pin::Pin, sync::Arc};
+use chrono::{DateTime, TimeZone, Utc};
+use futures::{Future, FutureExt};
+use serde::Serialize;
+use specta::Type;
+use std::{
+	any::Any,
+	collections::HashMap,
+	panic::AssertUnwindSafe,
+	pin::Pin,
+	sync::{
+		atomic::{AtomicI64, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
 use tokio::{
-	sync::{broadcast, oneshot, Mutex},
+	sync::{broadcast, Mutex},
 	task::AbortHandle,
+	time::{sleep, Instant},
 };
 
+/// What happens to an actor once its future stops by itself, instead of through [`Actors::stop`].
+#[derive(Debug, Clone, Copy, Default)]
+pub enum RestartPolicy {
+	#[default]
+	Never,
+	/// Restarts it only if it panicked.
+	OnFailure(Backoff),
+	/// Restarts it whether it panicked or returned.
+	Always(Backoff),
+}
+
+/// How long to wait before restarting an actor, doubling after each restart in a row up to `max`.
+///
+/// Restarts stop being in a row once the actor manages to run for `max`.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+	pub initial: Duration,
+	pub max: Duration,
+}
+
+impl Default for Backoff {
+	fn default() -> Self {
+		Self {
+			initial: Duration::from_secs(1),
+			max: Duration::from_secs(60),
+		}
+	}
+}
+
+impl Backoff {
+	fn delay(&self, restarts_in_a_row: u32) -> Duration {
+		self.initial
+			.saturating_mul(2u32.saturating_pow(restarts_in_a_row.saturating_sub(1)))
+			.min(self.max)
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Type, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum ActorEvent {
+	Started { name: String },
+	Stopped { name: String },
+	Crashed { name: String, error: String },
+	Restarted { name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Type, PartialEq, Eq)]
+pub struct ActorState {
+	pub name: String,
+	pub running: bool,
+	/// When the current run started, `None` if it isn't running.
+	pub started_at: Option<DateTime<Utc>>,
+	/// The last time the actor reported doing something, see [`ActorActivity`].
+	pub last_activity: Option<DateTime<Utc>>,
+	pub restart_count: u32,
+	pub crash_count: u32,
+	pub last_error: Option<String>,
+}
+
+/// Given to each actor so it can report when it does something, which tells an actor that's
+/// working apart from one that's just running.
+#[derive(Debug, Clone, Default)]
+pub struct ActorActivity(Arc<AtomicI64>);
+
+impl ActorActivity {
+	pub fn bump(&self) {
+		self.0
+			.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+	}
+
+	fn last(&self) -> Option<DateTime<Utc>> {
+		match self.0.load(Ordering::Relaxed) {
+			0 => None,
+			millis => Utc.timestamp_millis_opt(millis).single(),
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct Runs {
+	started_at: Option<DateTime<Utc>>,
+	restart_count: u32,
+	crash_count: u32,
+	last_error: Option<String>,
+}
+
 pub struct Actor {
 	pub abort_handle: Mutex<Option<AbortHandle>>,
 	pub spawn_fn: Arc<dyn Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>,
+	restart_policy: RestartPolicy,
+	activity: ActorActivity,
+	runs: Mutex<Runs>,
 }
 
 pub struct Actors {
 	pub invalidate_rx: broadcast::Receiver<()>,
 	invalidate_tx: broadcast::Sender<()>,
+	events_tx: broadcast::Sender<ActorEvent>,
 	actors: Arc<Mutex<HashMap<String, Arc<Actor>>>>,
 }
 
@@ -20,14 +124,23 @@ impl Actors {
 	pub async fn declare<F: Future<Output = ()> + Send + 'static>(
 		self: &Arc<Self>,
 		name: &str,
-		actor_fn: impl FnOnce() -> F + Send + Sync + Clone + 'static,
+		actor_fn: impl FnOnce(ActorActivity) -> F + Send + Sync + Clone + 'static,
+		restart_policy: RestartPolicy,
 		autostart: bool,
 	) {
+		let activity = ActorActivity::default();
+
 		self.actors.lock().await.insert(
 			name.to_string(),
 			Arc::new(Actor {
 				abort_handle: Default::default(),
-				spawn_fn: Arc::new(move || Box::pin((actor_fn.clone())()) as Pin<Box<_>>),
+				spawn_fn: Arc::new({
+					let activity = activity.clone();
+					move || Box::pin((actor_fn.clone())(activity.clone())) as Pin<Box<_>>
+				}),
+				restart_policy,
+				activity,
+				runs: Default::default(),
 			}),
 		);
 
@@ -49,33 +162,22 @@ impl Actors {
 			return;
 		}
 
-		let (tx, rx) = oneshot::channel();
-
-		let invalidate_tx = self.invalidate_tx.clone();
-
-		let spawn_fn = actor.spawn_fn.clone();
-
-		let task = tokio::spawn(async move {
-			(spawn_fn)().await;
-
-			tx.send(()).ok();
-		});
-
-		*abort_handle = Some(task.abort_handle());
-		invalidate_tx.send(()).ok();
-
-		tokio::spawn({
+		let task = tokio::spawn({
 			let actor = actor.clone();
+			let this = self.clone();
+			let name = name.clone();
+
 			async move {
-				#[allow(clippy::match_single_binding)]
-				match rx.await {
-					_ => {}
-				};
+				this.supervise(&name, &actor).await;
 
 				actor.abort_handle.lock().await.take();
-				invalidate_tx.send(()).ok();
+				actor.runs.lock().await.started_at = None;
+				this.emit(ActorEvent::Stopped { name });
 			}
 		});
+
+		*abort_handle = Some(task.abort_handle());
+		self.emit(ActorEvent::Started { name });
 	}
 
 	pub async fn stop(self: &Arc<Self>, name: &str) {
@@ -90,20 +192,101 @@ impl Actors {
 
 		if let Some(abort_handle) = abort_handle.take() {
 			abort_handle.abort();
+			actor.runs.lock().await.started_at = None;
+			self.emit(ActorEvent::Stopped { name });
 		}
 	}
 
-	pub async fn get_state(&self) -> HashMap<String, bool> {
+	/// The state of every actor, sorted by name.
+	pub async fn get_state(&self) -> Vec<ActorState> {
 		let actors = self.actors.lock().await;
 
-		let mut state = HashMap::new();
+		let mut state = Vec::with_capacity(actors.len());
 
 		for (name, actor) in &*actors {
-			state.insert(name.to_string(), actor.abort_handle.lock().await.is_some());
+			let runs = actor.runs.lock().await;
+
+			state.push(ActorState {
+				name: name.to_string(),
+				running: actor.abort_handle.lock().await.is_some(),
+				started_at: runs.started_at,
+				last_activity: actor.activity.last(),
+				restart_count: runs.restart_count,
+				crash_count: runs.crash_count,
+				last_error: runs.last_error.clone(),
+			});
 		}
 
+		state.sort_by(|a, b| a.name.cmp(&b.name));
+
 		state
 	}
+
+	pub fn subscribe_events(&self) -> broadcast::Receiver<ActorEvent> {
+		self.events_tx.subscribe()
+	}
+
+	/// Runs the actor until it stops by itself and its [`RestartPolicy`] doesn't bring it back.
+	async fn supervise(&self, name: &str, actor: &Actor) {
+		let mut restarts_in_a_row = 0;
+
+		loop {
+			let started_at = Instant::now();
+			actor.runs.lock().await.started_at = Some(Utc::now());
+
+			let crashed = match AssertUnwindSafe((actor.spawn_fn)()).catch_unwind().await {
+				Ok(()) => false,
+				Err(panic) => {
+					let error = panic_message(panic);
+
+					{
+						let mut runs = actor.runs.lock().await;
+						runs.crash_count += 1;
+						runs.last_error = Some(error.clone());
+					}
+
+					self.emit(ActorEvent::Crashed {
+						name: name.to_string(),
+						error,
+					});
+
+					true
+				}
+			};
+
+			let backoff = match actor.restart_policy {
+				RestartPolicy::Always(backoff) => backoff,
+				RestartPolicy::OnFailure(backoff) if crashed => backoff,
+				_ => return,
+			};
+
+			if started_at.elapsed() >= backoff.max {
+				restarts_in_a_row = 0;
+			}
+			restarts_in_a_row += 1;
+
+			actor.runs.lock().await.started_at = None;
+			sleep(backoff.delay(restarts_in_a_row)).await;
+
+			actor.runs.lock().await.restart_count += 1;
+			self.emit(ActorEvent::Restarted {
+				name: name.to_string(),
+			});
+		}
+	}
+
+	fn emit(&self, event: ActorEvent) {
+		self.events_tx.send(event).ok();
+		self.invalidate_tx.send(()).ok();
+	}
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+	panic
+		.downcast_ref::<&str>()
+		.map(|message| message.to_string())
+		.or_else(|| panic.downcast_ref::<String>().cloned())
+		.unwrap_or_else(|| "actor panicked".to_string())
 }
 
 impl Default for Actors {
@@ -111,11 +294,100 @@ impl Default for Actors {
 		let actors = Default::default();
 
 		let (invalidate_tx, invalidate_rx) = broadcast::channel(1);
+		let (events_tx, _) = broadcast::channel(32);
 
 		Self {
 			actors,
 			invalidate_rx,
 			invalidate_tx,
+			events_tx,
 		}
 	}
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use std::sync::atomic::AtomicU32;
+
+	#[test]
+	fn test_backoff_doubles_up_to_max() {
+		let backoff = Backoff {
+			initial: Duration::from_secs(2),
+			max: Duration::from_secs(10),
+		};
+
+		assert_eq!(backoff.delay(1), Duration::from_secs(2));
+		assert_eq!(backoff.delay(2), Duration::from_secs(4));
+		assert_eq!(backoff.delay(3), Duration::from_secs(8));
+		assert_eq!(backoff.delay(4), Duration::from_secs(10));
+		assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(10));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_actor_restarts_after_panic() {
+		let actors = Arc::new(Actors::default());
+		let mut events = actors.subscribe_events();
+		let runs = Arc::new(AtomicU32::new(0));
+
+		actors
+			.declare(
+				"Flaky",
+				{
+					let runs = runs.clone();
+					move |activity: ActorActivity| async move {
+						if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+							panic!("lost connection");
+						}
+
+						activity.bump();
+
+						futures::future::pending::<()>().await;
+					}
+				},
+				RestartPolicy::OnFailure(Backoff {
+					initial: Duration::from_secs(2),
+					max: Duration::from_secs(60),
+				}),
+				true,
+			)
+			.await;
+
+		let name = "Flaky".to_string();
+
+		assert_eq!(
+			events.recv().await.unwrap(),
+			ActorEvent::Started { name: name.clone() }
+		);
+		assert_eq!(
+			events.recv().await.unwrap(),
+			ActorEvent::Crashed {
+				name: name.clone(),
+				error: "lost connection".to_string(),
+			}
+		);
+
+		let crashed_at = Instant::now();
+
+		assert_eq!(
+			events.recv().await.unwrap(),
+			ActorEvent::Restarted { name: name.clone() }
+		);
+		assert!(crashed_at.elapsed() >= Duration::from_secs(2));
+
+		let state = actors.get_state().await.remove(0);
+		assert!(state.running);
+		assert!(state.started_at.is_some());
+		assert!(state.last_activity.is_some());
+		assert_eq!(state.restart_count, 1);
+		assert_eq!(state.crash_count, 1);
+		assert_eq!(state.last_error, Some("lost connection".to_string()));
+
+		actors.stop(&name).await;
+
+		assert_eq!(events.recv().await.unwrap(), ActorEvent::Stopped { name });
+		assert_eq!(runs.load(Ordering::SeqCst), 2);
+	}
+}
@@ -26,7 +26,7 @@ use tracing::{debug, error, info};
 use uuid::Uuid;
 
 use super::{
-	model::{Model, ModelAndSession},
+	model::{self, DownloadModelError, Model, ModelAndSession},
 	process::{spawned_processing, FinishStatus},
 	BatchToken, ImageLabelerError, LabelerOutput,
 };
@@ -64,6 +64,7 @@ struct ResumableBatch {
 
 pub struct ImageLabeler {
 	to_resume_batches_file_path: PathBuf,
+	models_dir: PathBuf,
 	new_batches_tx: chan::Sender<Batch>,
 	resume_batch_tx: chan::Sender<ResumeBatchRequest>,
 	update_model_tx: chan::Sender<UpdateModelRequest>,
@@ -78,10 +79,10 @@ impl ImageLabeler {
 		data_directory: impl AsRef<Path>,
 	) -> Result<Self, ImageLabelerError> {
 		let to_resume_batches_file_path = data_directory.as_ref().join(PENDING_BATCHES_FILE);
+		let models_dir = data_directory.as_ref().join("models");
 
-		let model_and_session = Arc::new(RwLock::new(
-			ModelAndSession::new(model, data_directory.as_ref().join("models")).await?,
-		));
+		let model_and_session =
+			Arc::new(RwLock::new(ModelAndSession::new(model, &models_dir).await?));
 
 		let to_resume_batches = Arc::new(RwLock::new(
 			match fs::read(&to_resume_batches_file_path).await {
@@ -150,6 +151,7 @@ impl ImageLabeler {
 
 		Ok(Self {
 			to_resume_batches_file_path,
+			models_dir,
 			new_batches_tx,
 			resume_batch_tx,
 			update_model_tx,
@@ -219,6 +221,36 @@ impl ImageLabeler {
 			.await
 	}
 
+	/// Download the model files ahead of [`ImageLabeler::change_model`], so batches aren't held up
+	/// by the download. `on_progress` receives the downloaded and total bytes.
+	pub async fn download_model(
+		&self,
+		model: &dyn Model,
+		on_progress: impl Fn(u64, Option<u64>),
+	) -> Result<PathBuf, DownloadModelError> {
+		model::download_model(
+			model.origin(),
+			self.models_dir.join(model.name()),
+			on_progress,
+		)
+		.await
+	}
+
+	/// The size in bytes of the model on disk, or `None` if it wasn't downloaded yet.
+	pub async fn downloaded_model_size(&self, model: &dyn Model) -> Option<u64> {
+		let file_path =
+			model::model_file_path(model.origin(), self.models_dir.join(model.name())).ok()?;
+
+		fs::metadata(file_path)
+			.await
+			.ok()
+			.filter(|metadata| metadata.is_file())
+			.map(|metadata| metadata.len())
+	}
+
+	/// Change the model used for inference. A batch being processed finishes on the current
+	/// model and the swap happens before the next one starts. If the new model fails to load,
+	/// the current one is kept.
 	pub async fn change_model(&self, model: Box<dyn Model>) -> Result<(), ImageLabelerError> {
 		let (tx, rx) = oneshot::channel();
 
@@ -348,6 +380,9 @@ async fn actor_loop(
 
 	let mut currently_processing = None;
 
+	// Model updates wait for the batch being processed, only the latest one is applied
+	let mut pending_model_update: Option<UpdateModelRequest> = None;
+
 	let mut msg_stream = pin!((
 		new_batches_rx.map(StreamMessage::NewBatch),
 		resume_batch_rx.map(|(token, db, done_tx)| StreamMessage::ResumeBatch(token, db, done_tx)),
@@ -424,29 +459,16 @@ async fn actor_loop(
 
 			StreamMessage::UpdateModel(new_model, update_done_tx) => {
 				if currently_processing.is_some() {
-					let (tx, rx) = oneshot::channel();
-
-					stop_tx.send(tx).await.expect("stop_tx unexpectedly closed");
-
-					if timeout(ONE_SEC, rx).await.is_err() {
-						error!("Failed to stop image labeller batch processor");
-						if stop_rx.is_full() {
-							stop_rx.recv().await.ok();
-						}
+					debug!("Deferring image labeller model update until the current batch is done");
+					if let Some((_, superseded_done_tx)) =
+						pending_model_update.replace((new_model, update_done_tx))
+					{
+						superseded_done_tx
+							.send(Err(ImageLabelerError::ModelUpdateCancelled))
+							.ok();
 					}
-				}
-
-				if update_done_tx
-					.send(
-						model_and_session
-							.write()
-							.await
-							.update_model(new_model)
-							.await,
-					)
-					.is_err()
-				{
-					error!("Failed to send model update result from image labeller");
+				} else {
+					update_model(&model_and_session, new_model, update_done_tx).await;
 				}
 			}
 
@@ -475,6 +497,10 @@ async fn actor_loop(
 
 				output_tx.close(); // So our listener can exit
 
+				if let Some((new_model, update_done_tx)) = pending_model_update.take() {
+					update_model(&model_and_session, new_model, update_done_tx).await;
+				}
+
 				if let Some(next_batch) = queue.pop_front() {
 					currently_processing = Some(spawn(spawned_processing(
 						Arc::clone(&model_and_session),
@@ -489,6 +515,12 @@ async fn actor_loop(
 			StreamMessage::Shutdown(shutdown_done_tx) => {
 				debug!("Shutting down image labeller batch processor");
 
+				if let Some((_, update_done_tx)) = pending_model_update.take() {
+					update_done_tx
+						.send(Err(ImageLabelerError::ModelUpdateCancelled))
+						.ok();
+				}
+
 				if let Some(handle) = currently_processing.take() {
 					let (tx, rx) = oneshot::channel();
 
@@ -567,3 +599,22 @@ async fn actor_loop(
 		}
 	}
 }
+
+async fn update_model(
+	model_and_session: &RwLock<ModelAndSession>,
+	new_model: Box<dyn Model>,
+	update_done_tx: oneshot::Sender<Result<(), ImageLabelerError>>,
+) {
+	if update_done_tx
+		.send(
+			model_and_session
+				.write()
+				.await
+				.update_model(new_model)
+				.await,
+		)
+		.is_err()
+	{
+		error!("Failed to send model update result from image labeller");
+	}
+}
@@ -39,6 +39,8 @@ pub enum ImageLabelerError {
 	ModelFileNotFound(Box<Path>),
 	#[error("no model available for inference")]
 	NoModelAvailable,
+	#[error("model update was cancelled before being applied")]
+	ModelUpdateCancelled,
 	#[error("failed to decode pending batches: {0}")]
 	Decode(#[from] rmp_serde::decode::Error),
 	#[error("failed to encode pending batches: {0}")]
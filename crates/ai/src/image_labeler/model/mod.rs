@@ -1,7 +1,7 @@
 use sd_utils::error::FileIOError;
 
 use std::{
-	collections::HashSet,
+	collections::HashMap,
 	path::{Path, PathBuf},
 };
 
@@ -48,10 +48,11 @@ pub trait Model: Send + Sync + 'static {
 		format: ImageFormat,
 	) -> Result<SessionInputs<'image>, ImageLabelerError>;
 
+	/// Returns the detected labels, each with the confidence of it's most confident detection.
 	fn process_output(
 		&self,
 		output: SessionOutputs<'_>,
-	) -> Result<HashSet<String>, ImageLabelerError>;
+	) -> Result<HashMap<String, f32>, ImageLabelerError>;
 }
 
 pub(super) struct ModelAndSession {
@@ -66,7 +67,7 @@ impl ModelAndSession {
 		data_dir: impl AsRef<Path>,
 	) -> Result<Self, DownloadModelError> {
 		let data_dir = data_dir.as_ref().join(model.name());
-		let model_path = download_model(model.origin(), &data_dir).await?;
+		let model_path = download_model(model.origin(), &data_dir, |_, _| {}).await?;
 
 		info!(
 			"Loading mode: {} from {}",
@@ -100,13 +101,15 @@ impl ModelAndSession {
 		self.maybe_session.is_some() && self.maybe_model.is_some()
 	}
 
+	/// Swap to the new model, keeping the current one if the new one fails to load.
 	pub async fn update_model(
 		&mut self,
 		new_model: Box<dyn Model>,
 	) -> Result<(), ImageLabelerError> {
 		info!("Attempting to change image labeler models...");
 
-		let model_path = download_model(new_model.origin(), &self.model_data_dir).await?;
+		let model_path =
+			download_model(new_model.origin(), &self.model_data_dir, |_, _| {}).await?;
 
 		info!(
 			"Change mode: {} to {}",
@@ -114,28 +117,23 @@ impl ModelAndSession {
 			model_path.display()
 		);
 
-		check_model_file(&model_path).await.and_then(|()| {
-			load_model(&model_path)
-				.map(|session| {
-					info!(
-						"Changing models: {} -> {}",
-						self.maybe_model
-							.as_ref()
-							.map(|old_model| old_model.name())
-							.unwrap_or("None"),
-						new_model.name()
-					);
-
-					self.maybe_model = Some(new_model);
-					self.maybe_session = Some(session);
-				})
-				.map_err(|e| {
-					self.maybe_model = None;
-					self.maybe_session = None;
-
-					e
-				})
-		})
+		check_model_file(&model_path).await?;
+
+		let session = load_model(&model_path)?;
+
+		info!(
+			"Changing models: {} -> {}",
+			self.maybe_model
+				.as_ref()
+				.map(|old_model| old_model.name())
+				.unwrap_or("None"),
+			new_model.name()
+		);
+
+		self.maybe_model = Some(new_model);
+		self.maybe_session = Some(session);
+
+		Ok(())
 	}
 
 	pub fn process_single_image(
@@ -143,7 +141,7 @@ impl ModelAndSession {
 		image_path: &Path,
 		image: Vec<u8>,
 		format: ImageFormat,
-	) -> Result<HashSet<String>, ImageLabelerError> {
+	) -> Result<HashMap<String, f32>, ImageLabelerError> {
 		if let (Some(session), Some(model)) = (&self.maybe_session, self.maybe_model.as_deref()) {
 			let inputs = model.prepare_input(image_path, &image, format)?;
 			let outputs = session.run(inputs)?;
@@ -178,23 +176,37 @@ fn load_model(model_path: impl AsRef<Path>) -> Result<Session, ImageLabelerError
 		.map_err(Into::into)
 }
 
-async fn download_model(
+/// Where the model file lives, downloaded models are stored in `data_dir`.
+pub(super) fn model_file_path(
 	model_origin: &ModelSource,
 	data_dir: impl AsRef<Path>,
+) -> Result<PathBuf, DownloadModelError> {
+	match model_origin {
+		ModelSource::Url(url) => url
+			.path_segments()
+			.and_then(|segments| segments.last())
+			.map(|file_name| data_dir.as_ref().join(file_name))
+			.ok_or_else(|| DownloadModelError::InvalidUrlFileName(url.to_owned())),
+		ModelSource::Path(file_path) => Ok(file_path.to_owned()),
+	}
+}
+
+/// Download the model if we don't have it yet, calling `on_progress` with the downloaded and total bytes.
+pub(super) async fn download_model(
+	model_origin: &ModelSource,
+	data_dir: impl AsRef<Path>,
+	on_progress: impl Fn(u64, Option<u64>),
 ) -> Result<PathBuf, DownloadModelError> {
 	let data_dir = data_dir.as_ref();
 
 	match model_origin {
 		ModelSource::Url(url) => {
-			let Some(file_name) = url.path_segments().and_then(|segments| segments.last()) else {
-				return Err(DownloadModelError::InvalidUrlFileName(url.to_owned()));
-			};
+			let file_path = model_file_path(model_origin, data_dir)?;
 
 			fs::create_dir_all(data_dir)
 				.await
 				.map_err(|e| FileIOError::from((data_dir, e, "Failed to create data directory")))?;
 
-			let file_path = data_dir.join(file_name);
 			match fs::metadata(&file_path).await {
 				Ok(_) => return Ok(file_path),
 				Err(e) if e.kind() != io::ErrorKind::NotFound => {
@@ -212,26 +224,47 @@ async fn download_model(
 						return Err(DownloadModelError::HttpStatusError(response.status()));
 					}
 
+					let total = response.content_length();
+
+					// Downloading to a temporary file so a failed download isn't mistaken for the model
+					let part_path = file_path.with_extension("part");
+
 					// Create or open a file at the specified path
-					let mut file = fs::File::create(&file_path).await.map_err(|e| {
+					let mut file = fs::File::create(&part_path).await.map_err(|e| {
 						FileIOError::from((
-							&file_path,
+							&part_path,
 							e,
 							"Failed to create the model file on disk",
 						))
 					})?;
 					// Stream the response body to the file
+					let mut downloaded = 0;
 					let mut body = response.bytes_stream();
 					while let Some(chunk) = body.next().await {
 						let chunk = chunk?;
 						file.write_all(&chunk).await.map_err(|e| {
 							FileIOError::from((
-								&file_path,
+								&part_path,
 								e,
 								"Failed to write chunk of data to the model file on disk",
 							))
 						})?;
+
+						downloaded += chunk.len() as u64;
+						on_progress(downloaded, total);
 					}
+
+					file.flush().await.map_err(|e| {
+						FileIOError::from((&part_path, e, "Failed to flush the model file to disk"))
+					})?;
+
+					fs::rename(&part_path, &file_path).await.map_err(|e| {
+						FileIOError::from((
+							&file_path,
+							e,
+							"Failed to move the downloaded model file into place",
+						))
+					})?;
 				}
 			}
 
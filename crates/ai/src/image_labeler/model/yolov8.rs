@@ -1,10 +1,6 @@
 use crate::utils::get_path_relative_to_exe;
 
-use std::{
-	collections::{HashMap, HashSet},
-	fmt::Display,
-	path::Path,
-};
+use std::{collections::HashMap, fmt::Display, path::Path};
 
 use half::f16;
 use image::{imageops::FilterType, load_from_memory_with_format, GenericImageView, ImageFormat};
@@ -113,7 +109,7 @@ impl Model for YoloV8 {
 	fn process_output(
 		&self,
 		output: SessionOutputs<'_>,
-	) -> Result<HashSet<String>, ImageLabelerError> {
+	) -> Result<HashMap<String, f32>, ImageLabelerError> {
 		#[rustfmt::skip]
 		const YOLOV8_CLASS_LABELS: [&str; 80] = [
 			"person", "bicycle", "car", "motorcycle", "airplane", "bus", "train", "truck",
@@ -151,14 +147,18 @@ impl Model for YoloV8 {
 					.reduce(|accum, row| if row.1 > accum.1 { row } else { accum })
 					.expect("not empty output")
 			})
-			.filter(|(_, probability)| probability.to_f32() > 0.6)
-			.map(|(class_id, _)| YOLOV8_CLASS_LABELS[class_id])
-			.fold(HashSet::default(), |mut set, label| {
-				if !set.contains(label) {
-					set.insert(label.to_string());
+			.map(|(class_id, probability)| (class_id, probability.to_f32()))
+			.filter(|(_, probability)| *probability > 0.6)
+			.fold(HashMap::default(), |mut labels, (class_id, probability)| {
+				let confidence = labels
+					.entry(YOLOV8_CLASS_LABELS[class_id].to_string())
+					.or_insert(probability);
+
+				if probability > *confidence {
+					*confidence = probability;
 				}
 
-				set
+				labels
 			}))
 	}
 }
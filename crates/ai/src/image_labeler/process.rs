@@ -3,7 +3,7 @@ use sd_prisma::prisma::{file_path, label, label_on_object, object, PrismaClient}
 use sd_utils::{db::MissingFieldError, error::FileIOError};
 
 use std::{
-	collections::{HashMap, HashSet, VecDeque},
+	collections::{HashMap, VecDeque},
 	path::{Path, PathBuf},
 	sync::Arc,
 };
@@ -382,36 +382,39 @@ async fn extract_file_data(
 		.map_err(|e| FileIOError::from((path, e, "Failed to read file to get labels")).into())
 }
 
+/// Attach the labels to the object, updating the confidence of labels it already has.
 pub async fn assign_labels(
 	object_id: object::id::Type,
-	mut labels: HashSet<String>,
+	labels: HashMap<String, f32>,
 	db: &PrismaClient,
 ) -> Result<bool, ImageLabelerError> {
 	let mut has_new_labels = false;
 
+	let mut to_create = labels.clone();
+
 	let mut labels_ids = db
 		.label()
-		.find_many(vec![label::name::in_vec(labels.iter().cloned().collect())])
+		.find_many(vec![label::name::in_vec(labels.keys().cloned().collect())])
 		.select(label::select!({ id name }))
 		.exec()
 		.await?
 		.into_iter()
 		.map(|label| {
-			labels.remove(&label.name);
+			to_create.remove(&label.name);
 
-			label.id
+			(label.id, label.name)
 		})
 		.collect::<Vec<_>>();
 
-	labels_ids.reserve(labels.len());
+	labels_ids.reserve(to_create.len());
 
 	let date_created: DateTime<FixedOffset> = Utc::now().into();
 
-	if !labels.is_empty() {
+	if !to_create.is_empty() {
 		labels_ids.extend(
 			db._batch(
-				labels
-					.into_iter()
+				to_create
+					.into_keys()
 					.map(|name| {
 						db.label()
 							.create(
@@ -419,33 +422,39 @@ pub async fn assign_labels(
 								name,
 								vec![label::date_created::set(date_created)],
 							)
-							.select(label::select!({ id }))
+							.select(label::select!({ id name }))
 					})
 					.collect::<Vec<_>>(),
 			)
 			.await?
 			.into_iter()
-			.map(|label| label.id),
+			.map(|label| (label.id, label.name)),
 		);
 		has_new_labels = true;
 	}
 
-	db.label_on_object()
-		.create_many(
-			labels_ids
-				.into_iter()
-				.map(|label_id| {
+	db._batch(
+		labels_ids
+			.into_iter()
+			.map(|(label_id, name)| {
+				let confidence = labels.get(&name).map(|confidence| f64::from(*confidence));
+
+				db.label_on_object().upsert(
+					label_on_object::label_id_object_id(label_id, object_id),
 					label_on_object::create_unchecked(
 						label_id,
 						object_id,
-						vec![label_on_object::date_created::set(date_created)],
-					)
-				})
-				.collect(),
-		)
-		.skip_duplicates()
-		.exec()
-		.await?;
+						vec![
+							label_on_object::date_created::set(date_created),
+							label_on_object::confidence::set(confidence),
+						],
+					),
+					vec![label_on_object::confidence::set(confidence)],
+				)
+			})
+			.collect::<Vec<_>>(),
+	)
+	.await?;
 
 	Ok(has_new_labels)
 }
@@ -174,16 +174,56 @@ impl<T: Model + Serialize + Type> Normalise for Vec<T> {
 		self,
 		id_fn: impl Fn(&Self::Item) -> String,
 	) -> (Vec<CacheNode>, Vec<Reference<Self::Item>>) {
-		let mut nodes = Vec::with_capacity(self.len());
-		let mut references = Vec::with_capacity(self.len());
+		let mut normaliser = Normaliser::with_capacity(self.len());
+		normaliser.extend(self, id_fn);
+		normaliser.finish()
+	}
+}
+
+/// Normalises items one at a time, as they're produced.
+///
+/// Useful when the items come from a paginated query or a stream, so they don't have to be collected into a `Vec` before being normalised.
+#[derive(Debug)]
+pub struct Normaliser<T> {
+	nodes: Vec<CacheNode>,
+	references: Vec<Reference<T>>,
+}
 
-		for item in self.into_iter() {
-			let id = id_fn(&item);
-			nodes.push(CacheNode::new(id.clone(), item));
-			references.push(Reference::new(id));
+impl<T: Model + Serialize + Type> Default for Normaliser<T> {
+	fn default() -> Self {
+		Self::with_capacity(0)
+	}
+}
+
+impl<T: Model + Serialize + Type> Normaliser<T> {
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self {
+			nodes: Vec::with_capacity(capacity),
+			references: Vec::with_capacity(capacity),
 		}
+	}
+
+	pub fn push(&mut self, id: String, item: T) {
+		self.nodes.push(CacheNode::new(id.clone(), item));
+		self.references.push(Reference::new(id));
+	}
+
+	pub fn extend(&mut self, items: impl IntoIterator<Item = T>, id_fn: impl Fn(&T) -> String) {
+		for item in items {
+			self.push(id_fn(&item), item);
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.references.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.references.is_empty()
+	}
 
-		(nodes, references)
+	pub fn finish(self) -> (Vec<CacheNode>, Vec<Reference<T>>) {
+		(self.nodes, self.references)
 	}
 }
 
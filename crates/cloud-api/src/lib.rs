@@ -474,6 +474,122 @@ pub mod library {
 				Ok(())
 			}
 		}
+
+		// Payloads too large for a single request are uploaded in chunks, which are reassembled by the receiving instances.
+		pub use upload_status::exec as upload_status;
+		pub mod upload_status {
+			use super::*;
+
+			#[derive(Deserialize, Debug)]
+			#[serde(rename_all = "camelCase")]
+			pub struct Response {
+				/// Indexes of the chunks of the upload the server has already received.
+				pub acknowledged: Vec<u32>,
+			}
+
+			pub async fn exec(
+				config: RequestConfig,
+				library_id: Uuid,
+				upload_id: Uuid,
+			) -> Result<Response, Error> {
+				let Some(auth_token) = config.auth_token else {
+					return Err(Error("Authentication required".to_string()));
+				};
+
+				config
+					.client
+					.get(&format!(
+						"{}/api/v1/libraries/{}/messageCollections/uploads/{}",
+						config.api_url, library_id, upload_id
+					))
+					.with_auth(auth_token)
+					.send()
+					.await
+					.and_then(|r| r.error_for_status())
+					.map_err(|e| Error(e.to_string()))?
+					.json()
+					.await
+					.map_err(|e| Error(e.to_string()))
+			}
+		}
+
+		pub use upload_chunk::exec as upload_chunk;
+		pub mod upload_chunk {
+			use super::*;
+
+			#[derive(Serialize, Debug)]
+			#[serde(rename_all = "camelCase")]
+			pub struct Input {
+				pub index: u32,
+				pub checksum: String,
+				/// Base64 encoded
+				pub contents: String,
+			}
+
+			pub async fn exec(
+				config: RequestConfig,
+				library_id: Uuid,
+				upload_id: Uuid,
+				chunk: Input,
+			) -> Result<(), Error> {
+				let Some(auth_token) = config.auth_token else {
+					return Err(Error("Authentication required".to_string()));
+				};
+
+				config
+					.client
+					.post(&format!(
+						"{}/api/v1/libraries/{}/messageCollections/uploads/{}/chunks",
+						config.api_url, library_id, upload_id
+					))
+					.json(&chunk)
+					.with_auth(auth_token)
+					.send()
+					.await
+					.and_then(|r| r.error_for_status())
+					.map_err(|e| Error(e.to_string()))?;
+
+				Ok(())
+			}
+		}
+
+		pub use get_chunk::exec as get_chunk;
+		pub mod get_chunk {
+			use super::*;
+
+			#[derive(Deserialize, Debug)]
+			#[serde(rename_all = "camelCase")]
+			pub struct Response {
+				/// Base64 encoded
+				pub contents: String,
+			}
+
+			pub async fn exec(
+				config: RequestConfig,
+				library_id: Uuid,
+				upload_id: Uuid,
+				index: u32,
+			) -> Result<Response, Error> {
+				let Some(auth_token) = config.auth_token else {
+					return Err(Error("Authentication required".to_string()));
+				};
+
+				config
+					.client
+					.get(&format!(
+						"{}/api/v1/libraries/{}/messageCollections/uploads/{}/chunks/{}",
+						config.api_url, library_id, upload_id, index
+					))
+					.with_auth(auth_token)
+					.send()
+					.await
+					.and_then(|r| r.error_for_status())
+					.map_err(|e| Error(e.to_string()))?
+					.json()
+					.await
+					.map_err(|e| Error(e.to_string()))
+			}
+		}
 	}
 }
 
@@ -2,7 +2,7 @@
 
 use sd_crypto::{
 	crypto::Encryptor,
-	header::{file::FileHeader, keyslot::Keyslot, metadata::MetadataVersion},
+	header::{file::FileHeader, keyslot::Keyslot},
 	primitives::{LATEST_FILE_HEADER, LATEST_KEYSLOT},
 	types::{Algorithm, HashingAlgorithm, Key, Params, Salt},
 	Protected,
@@ -11,6 +11,9 @@ use tokio::fs::File;
 const ALGORITHM: Algorithm = Algorithm::XChaCha20Poly1305;
 const HASHING_ALGORITHM: HashingAlgorithm = HashingAlgorithm::Argon2id(Params::Standard);
 
+// The same context is needed to decrypt the metadata
+const METADATA_CONTEXT: &str = "spacedrive 2024-01-16 12:00:00 example file metadata";
+
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct FileInformation {
 	pub file_name: String,
@@ -52,12 +55,7 @@ async fn encrypt() {
 	let mut header = FileHeader::new(LATEST_FILE_HEADER, ALGORITHM, keyslots).unwrap();
 
 	header
-		.add_metadata(
-			MetadataVersion::V1,
-			ALGORITHM,
-			master_key.clone(),
-			&embedded_metadata,
-		)
+		.add_metadata(&embedded_metadata, master_key.clone(), METADATA_CONTEXT)
 		.await
 		.unwrap();
 
@@ -84,8 +82,12 @@ async fn decrypt_metadata() {
 	// Deserialize the header, keyslots, etc from the encrypted file
 	let (header, _) = FileHeader::from_reader(&mut reader).await.unwrap();
 
-	// Decrypt the metadata
-	let file_info: FileInformation = header.decrypt_metadata(password).await.unwrap();
+	// Decrypt the master key, and then the metadata with it
+	let master_key = header.decrypt_master_key(password).await.unwrap();
+	let file_info: FileInformation = header
+		.decrypt_metadata(master_key, METADATA_CONTEXT)
+		.await
+		.unwrap();
 
 	println!("file name: {}", file_info.file_name);
 }
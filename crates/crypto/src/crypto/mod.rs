@@ -1,12 +1,76 @@
 //! This module contains all encryption and decryption items. These are used throughout the crate for all encryption/decryption needs.
 
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+
 use crate::Result;
-use tokio::io::AsyncReadExt;
+use tokio::{io::AsyncReadExt, sync::mpsc};
 
 mod stream;
 
 pub use self::stream::{Decryptor, Encryptor};
 
+/// This is reported after every block by the `encrypt/decrypt_streams_with_progress` functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+	/// The amount of bytes read from the reader so far
+	pub bytes_done: u64,
+	/// The size of the reader, if it was provided
+	pub total_bytes: Option<u64>,
+}
+
+/// This receives progress updates from the `encrypt/decrypt_streams_with_progress` functions.
+///
+/// It's implemented for closures and `mpsc` senders. Bounded senders drop updates if the channel is full, instead of stalling the stream.
+pub trait ProgressHandler: Send {
+	fn progress(&mut self, progress: Progress);
+}
+
+impl<F> ProgressHandler for F
+where
+	F: FnMut(Progress) + Send,
+{
+	fn progress(&mut self, progress: Progress) {
+		self(progress);
+	}
+}
+
+impl ProgressHandler for mpsc::Sender<Progress> {
+	fn progress(&mut self, progress: Progress) {
+		self.try_send(progress).ok();
+	}
+}
+
+impl ProgressHandler for mpsc::UnboundedSender<Progress> {
+	fn progress(&mut self, progress: Progress) {
+		self.send(progress).ok();
+	}
+}
+
+/// This is used for cooperatively cancelling the `encrypt/decrypt_streams_with_progress` functions.
+///
+/// Clones share the same flag, so one can be kept for cancelling while the other is passed to the stream.
+#[derive(Clone, Default, Debug)]
+pub struct CancellationFlag(Arc<AtomicBool>);
+
+impl CancellationFlag {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn cancel(&self) {
+		self.0.store(true, Ordering::Release);
+	}
+
+	#[must_use]
+	pub fn is_cancelled(&self) -> bool {
+		self.0.load(Ordering::Acquire)
+	}
+}
+
 /// This is used to exhaustively read from an asynchronous reader into a buffer.
 ///
 /// This function returns on three possible conditions, and they are:
@@ -351,6 +415,105 @@ mod tests {
 		assert_eq!(buf, output);
 	}
 
+	#[tokio::test]
+	async fn encrypt_streams_with_progress() {
+		let mut buf = vec![0u8; BLOCK_LEN * 5 + 10];
+		ChaCha20Rng::from_entropy().fill_bytes(&mut buf);
+		let total_bytes = buf.len() as u64;
+		let mut reader = Cursor::new(buf);
+		let mut writer = Cursor::new(Vec::new());
+
+		let mut updates = Vec::new();
+
+		let encryptor = Encryptor::new(KEY, XCHACHA_NONCE, Algorithm::XChaCha20Poly1305).unwrap();
+
+		encryptor
+			.encrypt_streams_with_progress(
+				&mut reader,
+				&mut writer,
+				&[],
+				Some(total_bytes),
+				|progress: Progress| updates.push(progress),
+				&CancellationFlag::new(),
+			)
+			.await
+			.unwrap();
+
+		assert_eq!(updates.len(), 6);
+		assert!(updates
+			.windows(2)
+			.all(|w| w[0].bytes_done < w[1].bytes_done));
+		assert_eq!(
+			updates.last(),
+			Some(&Progress {
+				bytes_done: total_bytes,
+				total_bytes: Some(total_bytes),
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn decrypt_streams_with_progress_over_channel() {
+		let mut buf = vec![0u8; BLOCK_LEN * 2];
+		ChaCha20Rng::from_entropy().fill_bytes(&mut buf);
+		let ciphertext = Encryptor::encrypt_bytes(KEY, AES_NONCE, Algorithm::Aes256Gcm, &buf, &[])
+			.await
+			.unwrap();
+
+		let (tx, mut rx) = mpsc::unbounded_channel();
+		let mut writer = Cursor::new(Vec::new());
+
+		let decryptor = Decryptor::new(KEY, AES_NONCE, Algorithm::Aes256Gcm).unwrap();
+
+		decryptor
+			.decrypt_streams_with_progress(
+				ciphertext.as_slice(),
+				&mut writer,
+				&[],
+				Some(ciphertext.len() as u64),
+				tx,
+				&CancellationFlag::new(),
+			)
+			.await
+			.unwrap();
+
+		let mut last = 0;
+		while let Some(progress) = rx.recv().await {
+			assert!(progress.bytes_done > last);
+			last = progress.bytes_done;
+		}
+
+		assert_eq!(last, ciphertext.len() as u64);
+		assert_eq!(buf, writer.into_inner());
+	}
+
+	#[tokio::test]
+	async fn cancelled_encryption_removes_output() {
+		let mut buf = vec![0u8; BLOCK_LEN * 5];
+		ChaCha20Rng::from_entropy().fill_bytes(&mut buf);
+
+		let path = std::env::temp_dir().join(format!("sd-crypto-{}", uuid::Uuid::new_v4()));
+		let cancel = CancellationFlag::new();
+
+		let encryptor = Encryptor::new(KEY, XCHACHA_NONCE, Algorithm::XChaCha20Poly1305).unwrap();
+
+		let res = crate::fs::output::with_output_file(&path, |file| {
+			encryptor.encrypt_streams_with_progress(
+				buf.as_slice(),
+				file,
+				&[],
+				None,
+				// cancel once the first block has been written
+				|_: Progress| cancel.cancel(),
+				&cancel,
+			)
+		})
+		.await;
+
+		assert!(matches!(res, Err(crate::Error::Cancelled)));
+		assert!(!path.exists());
+	}
+
 	#[tokio::test]
 	#[should_panic(expected = "NonceLengthMismatch")]
 	async fn encrypt_with_invalid_nonce() {
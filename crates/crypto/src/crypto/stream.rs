@@ -13,7 +13,7 @@ use aes_gcm::Aes256Gcm;
 use chacha20poly1305::XChaCha20Poly1305;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use super::exhaustive_read;
+use super::{exhaustive_read, CancellationFlag, Progress, ProgressHandler};
 
 macro_rules! impl_stream {
 	(
@@ -23,6 +23,7 @@ macro_rules! impl_stream {
 	$last_fn:ident, // "encrypt_last"
 	$stream_primitive:ident, // "DecryptorLE31"
 	$streams_fn:ident, // "encrypt_streams"
+	$streams_with_progress_fn:ident, // "encrypt_streams_with_progress"
 	$bytes_fn:ident, // "encrypt_bytes"
 	$bytes_return:ty,
 	$size:expr,
@@ -82,18 +83,54 @@ macro_rules! impl_stream {
 			///
 			/// The AAD will be authenticated with every block of data.
 			pub async fn $streams_fn<R, W>(
+				self,
+				reader: R,
+				writer: W,
+				aad: &[u8],
+			) -> Result<()>
+			where
+				R: AsyncReadExt + Unpin + Send,
+				W: AsyncWriteExt + Unpin + Send,
+			{
+				self.$streams_with_progress_fn(
+					reader,
+					writer,
+					aad,
+					None,
+					|_: Progress| {},
+					&CancellationFlag::new(),
+				)
+				.await
+			}
+
+			/// This is the same as the associated `encrypt/decrypt_streams` function, but it reports progress and can be cancelled.
+			///
+			/// The progress handler is called after every block, with the amount of bytes read from the reader so far.
+			/// `total_bytes` is only passed through to the handler, and it should be the size of the reader (if known).
+			///
+			/// The cancellation flag is checked before every block, and `Error::Cancelled` is returned once it's set.
+			/// The writer will contain partial output at that point - use `fs::output::with_output_file()` to remove it.
+			pub async fn $streams_with_progress_fn<R, W>(
 				mut self,
 				mut reader: R,
 				mut writer: W,
 				aad: &[u8],
+				total_bytes: Option<u64>,
+				mut progress: impl ProgressHandler,
+				cancel: &CancellationFlag,
 			) -> Result<()>
 			where
 				R: AsyncReadExt + Unpin + Send,
 				W: AsyncWriteExt + Unpin + Send,
 			{
 				let mut buffer = vec![0u8; $size].into_boxed_slice();
+				let mut bytes_done = 0u64;
 
 				loop {
+					if cancel.is_cancelled() {
+						return Err(Error::Cancelled);
+					}
+
 					let count = exhaustive_read(&mut reader, &mut buffer).await?;
 
 					let payload = Payload {
@@ -101,12 +138,23 @@ macro_rules! impl_stream {
 						msg: &buffer[..count],
 					};
 
+					bytes_done += count as u64;
+					let progress_update = Progress {
+						bytes_done,
+						total_bytes,
+					};
+
 					if count == $size {
 						let d = self.$next_fn(payload)?;
 						writer.write_all(&d).await?;
+						progress.progress(progress_update);
 					} else {
 						let d = self.$last_fn(payload)?;
 						writer.write_all(&d).await?;
+						// the last block is empty if the reader's size is a multiple of the block size
+						if count > 0 {
+							progress.progress(progress_update);
+						}
 						break;
 					}
 				}
@@ -147,6 +195,7 @@ impl_stream!(
 	encrypt_last,
 	EncryptorLE31,
 	encrypt_streams,
+	encrypt_streams_with_progress,
 	encrypt_bytes,
 	Vec<u8>,
 	BLOCK_LEN,
@@ -161,6 +210,7 @@ impl_stream!(
 	decrypt_last,
 	DecryptorLE31,
 	decrypt_streams,
+	decrypt_streams_with_progress,
 	decrypt_bytes,
 	Protected<Vec<u8>>,
 	(BLOCK_LEN + AEAD_TAG_LEN),
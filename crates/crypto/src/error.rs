@@ -27,6 +27,8 @@ pub enum Error {
 	NonceLengthMismatch,
 	#[error("error initialising stream encryption/decryption")]
 	StreamModeInit,
+	#[error("stream encryption/decryption was cancelled")]
+	Cancelled,
 
 	// header errors
 	#[error("no keyslots available")]
@@ -35,8 +37,14 @@ pub enum Error {
 	NoPreviewMedia,
 	#[error("no metadata found")]
 	NoMetadata,
+	#[error("the metadata is too large")]
+	MetadataTooLarge,
 	#[error("tried adding too many keyslots to a header")]
 	TooManyKeyslots,
+	#[error("keyslot expects a different unlock method (password/keyfile)")]
+	WrongUnlockMethod,
+	#[error("keyslot version doesn't support keyfiles")]
+	KeyfileUnsupported,
 
 	// key manager
 	#[error("requested key wasn't found in the key manager")]
@@ -1 +1,2 @@
 pub mod erase;
+pub mod output;
@@ -0,0 +1,29 @@
+use std::{future::Future, path::Path};
+
+use tokio::fs::{self, File};
+
+use crate::Result;
+
+/// This is used for writing encrypted/decrypted data to a file, without leaving partial output behind.
+///
+/// It creates the file at the provided path and passes it to `f`.
+///
+/// If `f` returns an error (e.g. `Error::Cancelled` from the `encrypt/decrypt_streams_with_progress` functions), the file is removed and the error is returned.
+pub async fn with_output_file<P, F, Fut, T>(path: P, f: F) -> Result<T>
+where
+	P: AsRef<Path> + Send,
+	F: FnOnce(File) -> Fut + Send,
+	Fut: Future<Output = Result<T>> + Send,
+{
+	let path = path.as_ref();
+	let file = File::create(path).await?;
+
+	match f(file).await {
+		Ok(v) => Ok(v),
+		Err(e) => {
+			// The file handle was dropped by `f` by now, so this works on Windows too
+			fs::remove_file(path).await.ok();
+			Err(e)
+		}
+	}
+}
@@ -69,6 +69,23 @@ pub struct FileHeader {
 #[derive(Clone, Copy)]
 pub enum FileHeaderVersion {
 	V1,
+	/// Flags whether metadata and preview media follow the keyslots, instead of leaving the reader to guess.
+	V2,
+}
+
+impl FileHeaderVersion {
+	/// Whether files with this header version may be encrypted with `algorithm`.
+	#[must_use]
+	pub const fn supports(&self, algorithm: Algorithm) -> bool {
+		match self {
+			Self::V1 | Self::V2 => {
+				matches!(
+					algorithm,
+					Algorithm::XChaCha20Poly1305 | Algorithm::Aes256Gcm
+				)
+			}
+		}
+	}
 }
 
 impl FileHeader {
@@ -100,7 +117,7 @@ impl FileHeader {
 	#[must_use]
 	pub const fn size(version: FileHeaderVersion) -> usize {
 		match version {
-			FileHeaderVersion::V1 => 36,
+			FileHeaderVersion::V1 | FileHeaderVersion::V2 => 36,
 		}
 	}
 
@@ -147,6 +164,26 @@ impl FileHeader {
 		Err(Error::IncorrectPassword)
 	}
 
+	/// This is a helper function to decrypt a master key from keyslots that are attached to a header, from a user-supplied keyfile.
+	///
+	/// Only keyfile keyslots are tried, password keyslots are skipped.
+	///
+	/// You receive an error if the keyfile doesn't match or if there are no keyslots.
+	#[allow(clippy::needless_pass_by_value)]
+	pub async fn decrypt_master_key_with_keyfile(&self, keyfile: Key) -> Result<Key> {
+		if self.keyslots.is_empty() {
+			return Err(Error::NoKeyslots);
+		}
+
+		for v in &self.keyslots {
+			if let Ok(key) = v.decrypt_master_key_with_keyfile(keyfile.clone()).await {
+				return Ok(key);
+			}
+		}
+
+		Err(Error::IncorrectPassword)
+	}
+
 	/// This is a helper function to serialize and write a header to a file.
 	pub async fn write<W>(&self, writer: &mut W) -> Result<()>
 	where
@@ -180,7 +217,7 @@ impl FileHeader {
 	#[must_use]
 	pub fn generate_aad(&self) -> Vec<u8> {
 		match self.version {
-			FileHeaderVersion::V1 => [
+			FileHeaderVersion::V1 | FileHeaderVersion::V2 => [
 				MAGIC_BYTES.as_ref(),
 				&self.version.to_bytes(),
 				&self.algorithm.to_bytes(),
@@ -200,50 +237,58 @@ impl FileHeader {
 	///
 	/// An error will be returned if there are no keyslots/more than two keyslots attached.
 	pub fn to_bytes(&self) -> Result<Vec<u8>> {
-		match self.version {
-			FileHeaderVersion::V1 => {
-				if self.keyslots.len() > 2 {
-					return Err(Error::TooManyKeyslots);
-				} else if self.keyslots.is_empty() {
-					return Err(Error::NoKeyslots);
-				}
+		if self.keyslots.len() > 2 {
+			return Err(Error::TooManyKeyslots);
+		} else if self.keyslots.is_empty() {
+			return Err(Error::NoKeyslots);
+		}
 
-				let mut keyslots: Vec<Vec<u8>> =
-					self.keyslots.iter().map(Keyslot::to_bytes).collect();
+		let mut keyslots = self
+			.keyslots
+			.iter()
+			.map(Keyslot::to_bytes)
+			.collect::<Result<Vec<_>>>()?;
 
-				if keyslots.len() == 1 {
-					keyslots.push(vec![0u8; KEYSLOT_SIZE]);
-				}
-
-				let metadata = self
-					.metadata
-					.as_ref()
-					.map_or(Vec::new(), Metadata::to_bytes);
-
-				let preview_media = self
-					.preview_media
-					.as_ref()
-					.map_or(Vec::new(), PreviewMedia::to_bytes);
-
-				let header = [
-					MAGIC_BYTES.as_ref(),
-					&self.version.to_bytes(),
-					&self.algorithm.to_bytes(),
-					&self.nonce,
-					&vec![0u8; 25 - self.nonce.len()],
-					&keyslots[0],
-					&keyslots[1],
-					&metadata,
-					&preview_media,
-				]
-				.into_iter()
-				.flatten()
-				.copied()
-				.collect();
-
-				Ok(header)
-			}
+		if keyslots.len() == 1 {
+			keyslots.push(vec![0u8; KEYSLOT_SIZE]);
 		}
+
+		let flags = match self.version {
+			FileHeaderVersion::V1 => Vec::new(),
+			FileHeaderVersion::V2 => vec![
+				u8::from(self.metadata.is_some()),
+				u8::from(self.preview_media.is_some()),
+			],
+		};
+
+		let metadata = self
+			.metadata
+			.as_ref()
+			.map_or(Vec::new(), Metadata::to_bytes);
+
+		let preview_media = self
+			.preview_media
+			.as_ref()
+			.map_or(Vec::new(), PreviewMedia::to_bytes);
+
+		let header = [
+			MAGIC_BYTES.as_ref(),
+			&self.version.to_bytes(),
+			&self.algorithm.to_bytes(),
+			&self.nonce,
+			&vec![0u8; 25 - self.nonce.len()],
+			&keyslots[0],
+			&keyslots[1],
+			&flags,
+			&metadata,
+			&preview_media,
+		]
+		.into_iter()
+		.flatten()
+		.copied()
+		.collect();
+
+		Ok(header)
 	}
 
 	/// This deserializes a header directly from a reader, and leaves the reader at the start of the encrypted data.
@@ -280,72 +325,93 @@ impl FileHeader {
 			.await?;
 
 		// read the header
-		let header = match version {
-			FileHeaderVersion::V1 => {
-				let mut algorithm = [0u8; 2];
-				reader.read_exact(&mut algorithm).await?;
-				let algorithm = Algorithm::from_bytes(algorithm)?;
+		let mut algorithm = [0u8; 2];
+		reader.read_exact(&mut algorithm).await?;
+		let algorithm = Algorithm::from_bytes(algorithm)?;
 
-				let mut nonce = vec![0u8; algorithm.nonce_len()];
-				reader.read_exact(&mut nonce).await?;
-				let nonce = Nonce::try_from(nonce)?;
+		let mut nonce = vec![0u8; algorithm.nonce_len()];
+		reader.read_exact(&mut nonce).await?;
+		let nonce = Nonce::try_from(nonce)?;
 
-				// read and discard the padding
-				reader.read_exact(&mut vec![0u8; 25 - nonce.len()]).await?;
+		// read and discard the padding
+		reader.read_exact(&mut vec![0u8; 25 - nonce.len()]).await?;
 
-				let mut keyslot_bytes = vec![0u8; KEYSLOT_SIZE * 2]; // length of 2x keyslots
-				let mut keyslots: Vec<Keyslot> = Vec::new();
+		let mut keyslot_bytes = vec![0u8; KEYSLOT_SIZE * 2]; // length of 2x keyslots
+		let mut keyslots: Vec<Keyslot> = Vec::new();
 
-				reader.read_exact(&mut keyslot_bytes).await?;
-				let mut keyslot_reader = Cursor::new(keyslot_bytes);
+		reader.read_exact(&mut keyslot_bytes).await?;
+		let mut keyslot_reader = Cursor::new(keyslot_bytes);
 
-				for _ in 0..2 {
-					Keyslot::from_reader(&mut keyslot_reader)
-						.map(|k| keyslots.push(k))
-						.ok();
-				}
+		for _ in 0..2 {
+			Keyslot::from_reader(&mut keyslot_reader)
+				.map(|k| keyslots.push(k))
+				.ok();
+		}
 
-				let metadata = if let Ok(metadata) = Metadata::from_reader(reader).await {
-					Ok::<Option<Metadata>, Error>(Some(metadata))
-				} else {
-					reader
-						.seek(SeekFrom::Start(
-							Self::size(version) as u64 + (KEYSLOT_SIZE * 2) as u64,
-						))
-						.await?;
-					Ok(None)
-				}?;
-
-				let preview_media =
-					if let Ok(preview_media) = PreviewMedia::from_reader(reader).await {
-						Ok::<Option<PreviewMedia>, Error>(Some(preview_media))
-					} else {
-						let seek_len = metadata.as_ref().map_or_else(
-							|| Self::size(version) as u64 + (KEYSLOT_SIZE * 2) as u64,
-							|metadata| {
-								Self::size(version) as u64
-									+ (KEYSLOT_SIZE * 2) as u64 + metadata.size() as u64
-							},
-						);
-
-						reader.seek(SeekFrom::Start(seek_len)).await?;
-
-						Ok(None)
-					}?;
-
-				Self {
-					version,
-					algorithm,
-					nonce,
-					keyslots,
-					metadata,
-					preview_media,
-				}
+		let (metadata, preview_media) = match version {
+			FileHeaderVersion::V1 => Self::guess_items(reader, version).await?,
+			FileHeaderVersion::V2 => {
+				let mut flags = [0u8; 2];
+				reader.read_exact(&mut flags).await?;
+
+				let metadata = match flags[0] {
+					0 => None,
+					1 => Some(Metadata::from_reader(reader).await?),
+					_ => return Err(Error::Serialization),
+				};
+
+				let preview_media = match flags[1] {
+					0 => None,
+					1 => Some(PreviewMedia::from_reader(reader).await?),
+					_ => return Err(Error::Serialization),
+				};
+
+				(metadata, preview_media)
 			}
 		};
 
+		let header = Self {
+			version,
+			algorithm,
+			nonce,
+			keyslots,
+			metadata,
+			preview_media,
+		};
+
 		Ok((header, aad))
 	}
+
+	/// V1 headers don't say which items follow the keyslots, so we try reading each of them and seek back if it isn't there.
+	async fn guess_items<R>(
+		reader: &mut R,
+		version: FileHeaderVersion,
+	) -> Result<(Option<Metadata>, Option<PreviewMedia>)>
+	where
+		R: AsyncReadExt + AsyncSeekExt + Unpin + Send,
+	{
+		let items_start = Self::size(version) as u64 + (KEYSLOT_SIZE * 2) as u64;
+
+		let metadata = if let Ok(metadata) = Metadata::from_reader(reader).await {
+			Some(metadata)
+		} else {
+			reader.seek(SeekFrom::Start(items_start)).await?;
+			None
+		};
+
+		let preview_media = if let Ok(preview_media) = PreviewMedia::from_reader(reader).await {
+			Some(preview_media)
+		} else {
+			let seek_len = metadata
+				.as_ref()
+				.map_or(items_start, |metadata| items_start + metadata.size() as u64);
+
+			reader.seek(SeekFrom::Start(seek_len)).await?;
+			None
+		};
+
+		Ok((metadata, preview_media))
+	}
 }
 
 #[cfg(test)]
@@ -353,6 +419,10 @@ mod tests {
 	use std::io::Cursor;
 
 	use crate::{
+		header::{
+			keyslot::{KeyslotKind, KeyslotVersion},
+			metadata::METADATA_MAX_LEN,
+		},
 		primitives::{LATEST_FILE_HEADER, LATEST_KEYSLOT, LATEST_PREVIEW_MEDIA},
 		types::{HashingAlgorithm, Params, Salt},
 	};
@@ -362,6 +432,8 @@ mod tests {
 	const ALGORITHM: Algorithm = Algorithm::XChaCha20Poly1305;
 	const HASHING_ALGORITHM: HashingAlgorithm = HashingAlgorithm::Argon2id(Params::Standard);
 	const PVM_BYTES: [u8; 4] = [0x01, 0x02, 0x03, 0x04];
+	#[cfg(feature = "serde")]
+	const METADATA_CONTEXT: &str = "spacedrive 2024-01-16 12:00:00 header metadata test";
 
 	#[tokio::test]
 	async fn serialize_and_deserialize_header() {
@@ -393,7 +465,7 @@ mod tests {
 
 		FileHeader::from_reader(&mut writer).await.unwrap();
 
-		assert!(writer.position() == 260);
+		assert!(writer.position() == 262);
 	}
 
 	#[tokio::test]
@@ -436,8 +508,6 @@ mod tests {
 	#[cfg(feature = "serde")]
 	#[tokio::test]
 	async fn serialize_and_deserialize_header_with_metadata() {
-		use crate::primitives::LATEST_METADATA;
-
 		#[derive(serde::Serialize)]
 		struct Metadata {
 			pub name: String,
@@ -469,7 +539,7 @@ mod tests {
 		.unwrap();
 
 		header
-			.add_metadata(LATEST_METADATA, ALGORITHM, mk, &md)
+			.add_metadata(&md, mk, METADATA_CONTEXT)
 			.await
 			.unwrap();
 
@@ -574,8 +644,6 @@ mod tests {
 	#[cfg(feature = "serde")]
 	#[tokio::test]
 	async fn serialize_and_deserialize_header_with_all() {
-		use crate::primitives::LATEST_METADATA;
-
 		#[derive(serde::Serialize)]
 		struct Metadata {
 			pub name: String,
@@ -619,7 +687,7 @@ mod tests {
 		.unwrap();
 
 		header
-			.add_metadata(LATEST_METADATA, ALGORITHM, mk.clone(), &md)
+			.add_metadata(&md, mk.clone(), METADATA_CONTEXT)
 			.await
 			.unwrap();
 
@@ -638,6 +706,102 @@ mod tests {
 		assert!(header.keyslots.len() == 2);
 	}
 
+	#[tokio::test]
+	async fn serialize_and_deserialize_header_with_mixed_keyslots() {
+		let mut writer: Cursor<Vec<u8>> = Cursor::new(vec![]);
+		let mk = Key::generate();
+		let content_salt = Salt::generate();
+		let password = Protected::new(b"password".to_vec());
+		let keyfile = Key::generate();
+
+		let header = FileHeader::new(
+			LATEST_FILE_HEADER,
+			ALGORITHM,
+			vec![
+				Keyslot::new(
+					LATEST_KEYSLOT,
+					ALGORITHM,
+					HASHING_ALGORITHM,
+					content_salt,
+					HASHING_ALGORITHM
+						.hash(password.clone(), content_salt, None)
+						.unwrap(),
+					mk.clone(),
+				)
+				.await
+				.unwrap(),
+				Keyslot::new_keyfile(LATEST_KEYSLOT, ALGORITHM, keyfile.clone(), mk.clone())
+					.await
+					.unwrap(),
+			],
+		)
+		.unwrap();
+
+		header.write(&mut writer).await.unwrap();
+
+		writer.rewind().await.unwrap();
+
+		let (header, _) = FileHeader::from_reader(&mut writer).await.unwrap();
+		assert!(header.keyslots.len() == 2);
+		assert!(header.keyslots[0].kind == KeyslotKind::Password(HASHING_ALGORITHM));
+		assert!(header.keyslots[1].kind == KeyslotKind::Keyfile);
+
+		assert_eq!(
+			header.decrypt_master_key(password).await.unwrap().expose(),
+			mk.expose()
+		);
+		assert_eq!(
+			header
+				.decrypt_master_key_with_keyfile(keyfile)
+				.await
+				.unwrap()
+				.expose(),
+			mk.expose()
+		);
+	}
+
+	#[tokio::test]
+	async fn keyfile_does_not_unlock_password_keyslot() {
+		let mk = Key::generate();
+		let hashed_pw = Key::generate();
+
+		let header = FileHeader::new(
+			LATEST_FILE_HEADER,
+			ALGORITHM,
+			vec![Keyslot::new(
+				LATEST_KEYSLOT,
+				ALGORITHM,
+				HASHING_ALGORITHM,
+				Salt::generate(),
+				hashed_pw.clone(),
+				mk,
+			)
+			.await
+			.unwrap()],
+		)
+		.unwrap();
+
+		// the same bytes would unwrap the master key if the keyslot kind wasn't checked
+		assert!(matches!(
+			header.decrypt_master_key_with_keyfile(hashed_pw).await,
+			Err(Error::IncorrectPassword)
+		));
+	}
+
+	#[tokio::test]
+	async fn keyfile_keyslot_requires_v2() {
+		assert!(matches!(
+			Keyslot::new_keyfile(
+				KeyslotVersion::V1,
+				ALGORITHM,
+				Key::generate(),
+				Key::generate()
+			)
+			.await,
+			Err(Error::KeyfileUnsupported)
+		));
+	}
+
 	#[tokio::test]
 	async fn aad_validity() {
 		let mut writer: Cursor<Vec<u8>> = Cursor::new(vec![]);
@@ -667,4 +831,152 @@ mod tests {
 		assert_eq!(header.generate_aad(), aad);
 		assert_eq!(&header.to_bytes().unwrap()[..36], aad);
 	}
+
+	async fn header_with_keyslot(version: FileHeaderVersion, mk: Key) -> FileHeader {
+		FileHeader::new(
+			version,
+			ALGORITHM,
+			vec![Keyslot::new(
+				LATEST_KEYSLOT,
+				ALGORITHM,
+				HASHING_ALGORITHM,
+				Salt::generate(),
+				Key::generate(),
+				mk,
+			)
+			.await
+			.unwrap()],
+		)
+		.unwrap()
+	}
+
+	#[cfg(feature = "serde")]
+	#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+	struct FileInformation {
+		name: String,
+		favorite: bool,
+	}
+
+	#[cfg(feature = "serde")]
+	fn file_information() -> FileInformation {
+		FileInformation {
+			name: "file.txt".to_string(),
+			favorite: true,
+		}
+	}
+
+	#[cfg(feature = "serde")]
+	#[tokio::test]
+	async fn metadata_round_trip() {
+		let mk = Key::generate();
+		let mut header = header_with_keyslot(LATEST_FILE_HEADER, mk.clone()).await;
+
+		header
+			.add_metadata(&file_information(), mk.clone(), METADATA_CONTEXT)
+			.await
+			.unwrap();
+		header
+			.add_preview_media(LATEST_PREVIEW_MEDIA, ALGORITHM, mk.clone(), &PVM_BYTES)
+			.await
+			.unwrap();
+
+		let mut bytes = header.to_bytes().unwrap();
+		bytes.extend_from_slice(b"encrypted data");
+		let mut reader = Cursor::new(bytes);
+
+		let (header, _) = FileHeader::from_reader(&mut reader).await.unwrap();
+		assert_eq!(
+			&reader.get_ref()[reader.position() as usize..],
+			b"encrypted data"
+		);
+
+		assert_eq!(
+			header
+				.decrypt_metadata::<FileInformation>(mk.clone(), METADATA_CONTEXT)
+				.await
+				.unwrap(),
+			file_information()
+		);
+		assert!(header
+			.decrypt_metadata::<FileInformation>(mk, "some other context")
+			.await
+			.is_err());
+	}
+
+	#[cfg(feature = "serde")]
+	#[tokio::test]
+	async fn metadata_tampering_is_detected() {
+		let mk = Key::generate();
+		let mut header = header_with_keyslot(LATEST_FILE_HEADER, mk.clone()).await;
+
+		header
+			.add_metadata(&file_information(), mk.clone(), METADATA_CONTEXT)
+			.await
+			.unwrap();
+
+		// the metadata is the last item in the header, so this flips a byte of its ciphertext
+		let mut bytes = header.to_bytes().unwrap();
+		*bytes.last_mut().unwrap() ^= 0x01;
+
+		let (header, _) = FileHeader::from_reader(&mut Cursor::new(bytes))
+			.await
+			.unwrap();
+
+		assert!(header
+			.decrypt_metadata::<FileInformation>(mk, METADATA_CONTEXT)
+			.await
+			.is_err());
+	}
+
+	#[cfg(feature = "serde")]
+	#[tokio::test]
+	async fn metadata_is_bound_to_header() {
+		let mk = Key::generate();
+		let mut header = header_with_keyslot(LATEST_FILE_HEADER, mk.clone()).await;
+		let mut other_header = header_with_keyslot(LATEST_FILE_HEADER, mk.clone()).await;
+
+		header
+			.add_metadata(&file_information(), mk.clone(), METADATA_CONTEXT)
+			.await
+			.unwrap();
+
+		// same master key, but the nonce (and so the AAD) differs
+		other_header.metadata = header.metadata.clone();
+
+		assert!(other_header
+			.decrypt_metadata::<FileInformation>(mk, METADATA_CONTEXT)
+			.await
+			.is_err());
+	}
+
+	#[cfg(feature = "serde")]
+	#[tokio::test]
+	async fn metadata_too_large() {
+		let mk = Key::generate();
+		let mut header = header_with_keyslot(LATEST_FILE_HEADER, mk.clone()).await;
+
+		assert!(matches!(
+			header
+				.add_metadata(&"a".repeat(METADATA_MAX_LEN), mk, METADATA_CONTEXT)
+				.await,
+			Err(Error::MetadataTooLarge)
+		));
+	}
+
+	#[tokio::test]
+	async fn deserialize_v1_header_without_items() {
+		let mk = Key::generate();
+		let header = header_with_keyslot(FileHeaderVersion::V1, mk).await;
+
+		let mut bytes = header.to_bytes().unwrap();
+		bytes.extend_from_slice(b"encrypted data");
+		let mut reader = Cursor::new(bytes);
+
+		let (header, aad) = FileHeader::from_reader(&mut reader).await.unwrap();
+		assert!(matches!(header.version, FileHeaderVersion::V1));
+		assert!(header.metadata.is_none());
+		assert!(header.preview_media.is_none());
+		assert_eq!(header.generate_aad(), aad);
+		assert_eq!(reader.position(), 260);
+	}
 }
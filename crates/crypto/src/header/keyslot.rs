@@ -21,6 +21,14 @@
 //!
 //! let keyslot = Keyslot::new(KeyslotVersion::V1, Algorithm::XChaCha20Poly1305, HashingAlgorithm::Argon2id(Params::Standard), user_password, &master_key).unwrap();
 //! ```
+//!
+//! Keyslots may also be unlocked with a keyfile (a random 32-byte key, e.g. stored on a USB stick) instead of a password. These require `KeyslotVersion::V2` or later.
+//!
+//! ```rust,ignore
+//! let keyfile = Key::generate();
+//!
+//! let keyslot = Keyslot::new_keyfile(LATEST_KEYSLOT, Algorithm::XChaCha20Poly1305, keyfile, master_key).await.unwrap();
+//! ```
 use std::io::Read;
 
 use crate::{
@@ -30,16 +38,16 @@ use crate::{
 	Error, Protected, Result,
 };
 
-/// A keyslot - 96 bytes (as of V1), and contains all the information for future-proofing while keeping the size reasonable
+/// A keyslot - 112 bytes (as of V2), and contains all the information for future-proofing while keeping the size reasonable
 ///
 /// The algorithm (should) be inherited from the parent (the header, in this case), but that's not a guarantee so we include it here too
 #[derive(Clone)]
 pub struct Keyslot {
 	pub version: KeyslotVersion,
-	pub algorithm: Algorithm,                // encryption algorithm
-	pub hashing_algorithm: HashingAlgorithm, // password hashing algorithm
-	pub salt: Salt, // the salt used for deriving a KEK from a (key/content salt) hash
-	pub content_salt: Salt,
+	pub algorithm: Algorithm,     // encryption algorithm
+	pub kind: KeyslotKind,        // how the keyslot is unlocked
+	pub salt: Salt,               // the salt used for deriving a KEK from a (key/content salt) hash
+	pub content_salt: Salt,       // zeroed for keyfile keyslots, as the keyfile isn't hashed
 	pub master_key: EncryptedKey, // this is encrypted so we can store it
 	pub nonce: Nonce,
 }
@@ -52,6 +60,18 @@ pub const KEYSLOT_SIZE: usize = 112;
 #[derive(Clone, Copy)]
 pub enum KeyslotVersion {
 	V1,
+	V2,
+}
+
+/// This defines how a keyslot is unlocked, and it's stored within the keyslot's flags (as of V2)
+///
+/// V1 keyslots are always password keyslots.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyslotKind {
+	/// The master key is wrapped with a password, hashed with the provided hashing algorithm
+	Password(HashingAlgorithm),
+	/// The master key is wrapped directly with a user-supplied 32-byte key, with no password hashing
+	Keyfile,
 }
 
 impl Keyslot {
@@ -68,6 +88,53 @@ impl Keyslot {
 		content_salt: Salt,
 		hashed_key: Key,
 		master_key: Key,
+	) -> Result<Self> {
+		Self::wrap_master_key(
+			version,
+			algorithm,
+			KeyslotKind::Password(hashing_algorithm),
+			content_salt,
+			hashed_key,
+			master_key,
+		)
+		.await
+	}
+
+	/// This should be used for creating a keyslot that is unlocked with a keyfile.
+	///
+	/// The master key is wrapped directly with the keyfile, so no password hashing is done. The keyfile should be generated with `Key::generate()`.
+	///
+	/// An error will be returned if the keyslot version doesn't support keyfiles.
+	#[allow(clippy::needless_pass_by_value)]
+	pub async fn new_keyfile(
+		version: KeyslotVersion,
+		algorithm: Algorithm,
+		keyfile: Key,
+		master_key: Key,
+	) -> Result<Self> {
+		if matches!(version, KeyslotVersion::V1) {
+			return Err(Error::KeyfileUnsupported);
+		}
+
+		Self::wrap_master_key(
+			version,
+			algorithm,
+			KeyslotKind::Keyfile,
+			Salt([0u8; SALT_LEN]),
+			keyfile,
+			master_key,
+		)
+		.await
+	}
+
+	#[allow(clippy::needless_pass_by_value)]
+	async fn wrap_master_key(
+		version: KeyslotVersion,
+		algorithm: Algorithm,
+		kind: KeyslotKind,
+		content_salt: Salt,
+		key: Key,
+		master_key: Key,
 	) -> Result<Self> {
 		let nonce = Nonce::generate(algorithm)?;
 
@@ -75,7 +142,7 @@ impl Keyslot {
 
 		let encrypted_master_key = EncryptedKey::try_from(
 			Encryptor::encrypt_bytes(
-				Key::derive(hashed_key, salt, FILE_KEY_CONTEXT),
+				Key::derive(key, salt, FILE_KEY_CONTEXT),
 				nonce,
 				algorithm,
 				master_key.expose(),
@@ -87,7 +154,7 @@ impl Keyslot {
 		Ok(Self {
 			version,
 			algorithm,
-			hashing_algorithm,
+			kind,
 			salt,
 			content_salt,
 			master_key: encrypted_master_key,
@@ -99,24 +166,18 @@ impl Keyslot {
 	///
 	/// This attempts to decrypt the master key for a single keyslot
 	///
-	/// An error will be returned on failure.
+	/// An error will be returned on failure, or if this is a keyfile keyslot.
 	#[allow(clippy::needless_pass_by_value)]
 	pub async fn decrypt_master_key(&self, password: Protected<Vec<u8>>) -> Result<Key> {
-		let key = self
-			.hashing_algorithm
+		let KeyslotKind::Password(hashing_algorithm) = self.kind else {
+			return Err(Error::WrongUnlockMethod);
+		};
+
+		let key = hashing_algorithm
 			.hash(password, self.content_salt, None)
 			.map_err(|_| Error::PasswordHash)?;
 
-		Key::try_from(
-			Decryptor::decrypt_bytes(
-				Key::derive(key, self.salt, FILE_KEY_CONTEXT),
-				self.nonce,
-				self.algorithm,
-				&self.master_key,
-				&[],
-			)
-			.await?,
-		)
+		self.unwrap_master_key(key).await
 	}
 
 	/// This function should not be used directly, use `header.decrypt_master_key()` instead
@@ -125,8 +186,29 @@ impl Keyslot {
 	///
 	/// No hashing is done internally.
 	///
-	/// An error will be returned on failure.
+	/// An error will be returned on failure, or if this is a keyfile keyslot.
 	pub async fn decrypt_master_key_from_prehashed(&self, key: Key) -> Result<Key> {
+		if self.kind == KeyslotKind::Keyfile {
+			return Err(Error::WrongUnlockMethod);
+		}
+
+		self.unwrap_master_key(key).await
+	}
+
+	/// This function should not be used directly, use `header.decrypt_master_key_with_keyfile()` instead
+	///
+	/// This attempts to decrypt the master key for a single keyslot, using a keyfile
+	///
+	/// An error will be returned on failure, or if this is a password keyslot.
+	pub async fn decrypt_master_key_with_keyfile(&self, keyfile: Key) -> Result<Key> {
+		if self.kind != KeyslotKind::Keyfile {
+			return Err(Error::WrongUnlockMethod);
+		}
+
+		self.unwrap_master_key(keyfile).await
+	}
+
+	async fn unwrap_master_key(&self, key: Key) -> Result<Key> {
 		Key::try_from(
 			Decryptor::decrypt_bytes(
 				Key::derive(key, self.salt, FILE_KEY_CONTEXT),
@@ -140,24 +222,56 @@ impl Keyslot {
 	}
 
 	/// This function is used to serialize a keyslot into bytes
-	#[must_use]
-	pub fn to_bytes(&self) -> Vec<u8> {
-		match self.version {
-			KeyslotVersion::V1 => [
-				self.version.to_bytes().as_ref(),
-				self.algorithm.to_bytes().as_ref(),
-				self.hashing_algorithm.to_bytes().as_ref(),
-				&self.salt,
-				&self.content_salt,
-				&self.master_key,
-				&self.nonce,
-				&vec![0u8; 26 - self.nonce.len()],
-			]
-			.into_iter()
-			.flatten()
-			.copied()
-			.collect(),
-		}
+	///
+	/// An error will be returned if the keyslot version can't represent the keyslot's kind.
+	pub fn to_bytes(&self) -> Result<Vec<u8>> {
+		let bytes = match self.version {
+			KeyslotVersion::V1 => {
+				let KeyslotKind::Password(hashing_algorithm) = self.kind else {
+					return Err(Error::KeyfileUnsupported);
+				};
+
+				[
+					self.version.to_bytes().as_ref(),
+					self.algorithm.to_bytes().as_ref(),
+					hashing_algorithm.to_bytes().as_ref(),
+					&self.salt,
+					&self.content_salt,
+					&self.master_key,
+					&self.nonce,
+					&vec![0u8; 26 - self.nonce.len()],
+				]
+				.into_iter()
+				.flatten()
+				.copied()
+				.collect()
+			}
+			KeyslotVersion::V2 => {
+				// keyfile keyslots have no hashing algorithm, so it's zeroed
+				let hashing_algorithm = match self.kind {
+					KeyslotKind::Password(hashing_algorithm) => hashing_algorithm.to_bytes(),
+					KeyslotKind::Keyfile => [0u8; 2],
+				};
+
+				[
+					self.version.to_bytes().as_ref(),
+					self.algorithm.to_bytes().as_ref(),
+					self.kind.to_bytes().as_ref(),
+					hashing_algorithm.as_ref(),
+					&self.salt,
+					&self.content_salt,
+					&self.master_key,
+					&self.nonce,
+					&vec![0u8; 24 - self.nonce.len()],
+				]
+				.into_iter()
+				.flatten()
+				.copied()
+				.collect()
+			}
+		};
+
+		Ok(bytes)
 	}
 
 	/// This function reads a keyslot from a reader
@@ -173,43 +287,56 @@ impl Keyslot {
 		reader.read_exact(&mut version)?;
 		let version = KeyslotVersion::from_bytes(version)?;
 
-		match version {
+		let mut algorithm = [0u8; 2];
+		reader.read_exact(&mut algorithm)?;
+		let algorithm = Algorithm::from_bytes(algorithm)?;
+
+		let kind = match version {
 			KeyslotVersion::V1 => {
-				let mut algorithm = [0u8; 2];
-				reader.read_exact(&mut algorithm)?;
-				let algorithm = Algorithm::from_bytes(algorithm)?;
+				let mut hashing_algorithm = [0u8; 2];
+				reader.read_exact(&mut hashing_algorithm)?;
+				KeyslotKind::Password(HashingAlgorithm::from_bytes(hashing_algorithm)?)
+			}
+			KeyslotVersion::V2 => {
+				let mut flags = [0u8; 2];
+				reader.read_exact(&mut flags)?;
 
 				let mut hashing_algorithm = [0u8; 2];
 				reader.read_exact(&mut hashing_algorithm)?;
-				let hashing_algorithm = HashingAlgorithm::from_bytes(hashing_algorithm)?;
 
-				let mut salt = [0u8; SALT_LEN];
-				reader.read_exact(&mut salt)?;
+				KeyslotKind::from_bytes(flags, hashing_algorithm)?
+			}
+		};
 
-				let mut content_salt = [0u8; SALT_LEN];
-				reader.read_exact(&mut content_salt)?;
+		let mut salt = [0u8; SALT_LEN];
+		reader.read_exact(&mut salt)?;
 
-				let mut master_key = [0u8; ENCRYPTED_KEY_LEN];
-				reader.read_exact(&mut master_key)?;
+		let mut content_salt = [0u8; SALT_LEN];
+		reader.read_exact(&mut content_salt)?;
 
-				let mut nonce = vec![0u8; algorithm.nonce_len()];
-				reader.read_exact(&mut nonce)?;
-				let nonce = Nonce::try_from(nonce)?;
+		let mut master_key = [0u8; ENCRYPTED_KEY_LEN];
+		reader.read_exact(&mut master_key)?;
 
-				reader.read_exact(&mut vec![0u8; 26 - nonce.len()])?;
+		let mut nonce = vec![0u8; algorithm.nonce_len()];
+		reader.read_exact(&mut nonce)?;
+		let nonce = Nonce::try_from(nonce)?;
 
-				let keyslot = Self {
-					version,
-					algorithm,
-					hashing_algorithm,
-					salt: Salt(salt),
-					content_salt: Salt(content_salt),
-					master_key: EncryptedKey(master_key),
-					nonce,
-				};
+		let padding_len = match version {
+			KeyslotVersion::V1 => 26,
+			KeyslotVersion::V2 => 24,
+		} - nonce.len();
+		reader.read_exact(&mut vec![0u8; padding_len])?;
 
-				Ok(keyslot)
-			}
-		}
+		let keyslot = Self {
+			version,
+			algorithm,
+			kind,
+			salt: Salt(salt),
+			content_salt: Salt(content_salt),
+			master_key: EncryptedKey(master_key),
+			nonce,
+		};
+
+		Ok(keyslot)
 	}
 }
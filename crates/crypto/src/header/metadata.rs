@@ -2,6 +2,8 @@
 //!
 //! This is an optional item, and anything that may be serialized with `serde` can be used here.
 //!
+//! The metadata is encrypted with a key derived from the master key and a context string, and is bound to the header through the AAD.
+//!
 //! # Examples
 //!
 //! ```rust,ignore
@@ -14,23 +16,19 @@
 //!     file_name: "filename.txt".to_string(),
 //! };
 //!
-//! // Ideally this will be generated via a key management system
-//! let md_salt = generate_salt();
+//! header
+//!     .add_metadata(&embedded_metadata, master_key.clone(), METADATA_CONTEXT)
+//!     .await?;
 //!
-//! let md = Metadata::new(
-//!     MetadataVersion::V1,
-//!     ALGORITHM,
-//!     HASHING_ALGORITHM,
-//!     password,
-//!     &md_salt,
-//!     &embedded_metadata,
-//! )
-//! .unwrap();
+//! let metadata: FileInformation = header
+//!     .decrypt_metadata(master_key, METADATA_CONTEXT)
+//!     .await?;
 //! ```
 
 #[cfg(feature = "serde")]
 use crate::{
 	crypto::{Decryptor, Encryptor},
+	primitives::LATEST_METADATA,
 	types::Key,
 	Protected,
 };
@@ -38,65 +36,79 @@ use crate::{
 use tokio::io::AsyncReadExt;
 
 use crate::{
-	types::{Algorithm, Nonce},
+	primitives::{AEAD_TAG_LEN, SALT_LEN},
+	types::{Algorithm, Nonce, Salt},
 	Error, Result,
 };
 
 use super::file::FileHeader;
 
-/// This is a metadata header item. You may add it to a header, and this will be stored with the file.
+/// The maximum length of the serialized metadata, before it's encrypted.
 ///
-/// The `Metadata::new()` function handles master key and metadata encryption.
+/// Metadata is read along with the header, so it's meant for small details like the file's name.
+pub const METADATA_MAX_LEN: usize = 4096;
+
+/// This is a metadata header item. You may add it to a header, and this will be stored with the file.
 ///
-/// The salt should be generated elsewhere (e.g. a key management system).
+/// The `FileHeader::add_metadata()` function handles key derivation and metadata encryption.
 #[derive(Clone)]
 pub struct Metadata {
 	pub version: MetadataVersion,
 	pub algorithm: Algorithm, // encryption algorithm
 	pub metadata_nonce: Nonce,
+	/// The salt used for deriving the metadata key from the master key. V1 metadata is encrypted with the master key itself, so this is zeroed.
+	pub salt: Salt,
 	pub metadata: Vec<u8>,
 }
 
 #[derive(Clone, Copy)]
 pub enum MetadataVersion {
 	V1,
+	/// Encrypted with a key derived from the master key, and bound to the header through the AAD.
+	V2,
 }
 
 impl FileHeader {
-	/// This should be used for creating a header metadata item.
-	///
-	/// It handles encrypting the master key and metadata.
+	/// This should be used for adding a metadata item to a header.
 	///
-	/// You will need to provide the user's password, and a semi-universal salt for hashing the user's password. This allows for extremely fast decryption.
+	/// The metadata is encrypted with a key derived from the master key and `context`, which must be provided again for decryption.
 	///
-	/// Metadata needs to be accessed switfly, so a key management system should handle the salt generation.
+	/// The serialized metadata may be at most [`METADATA_MAX_LEN`] bytes long.
 	#[cfg(feature = "serde")]
 	#[allow(clippy::needless_pass_by_value)]
 	pub async fn add_metadata<T>(
 		&mut self,
-		version: MetadataVersion,
-		algorithm: Algorithm,
-		master_key: Key,
 		metadata: &T,
+		master_key: Key,
+		context: &str,
 	) -> Result<()>
 	where
 		T: ?Sized + serde::Serialize + Sync + Send,
 	{
-		let metadata_nonce = Nonce::generate(algorithm)?;
+		let metadata =
+			Protected::new(serde_json::to_vec(metadata).map_err(|_| Error::Serialization)?);
+
+		if metadata.expose().len() > METADATA_MAX_LEN {
+			return Err(Error::MetadataTooLarge);
+		}
+
+		let metadata_nonce = Nonce::generate(self.algorithm)?;
+		let salt = Salt::generate();
 
 		let encrypted_metadata = Encryptor::encrypt_bytes(
-			master_key,
+			Key::derive(master_key, salt, context),
 			metadata_nonce,
-			algorithm,
-			&serde_json::to_vec(metadata).map_err(|_| Error::Serialization)?,
-			&[],
+			self.algorithm,
+			metadata.expose(),
+			&self.generate_aad(),
 		)
 		.await?;
 
 		self.metadata = Some(Metadata {
-			version,
-			algorithm,
+			version: LATEST_METADATA,
+			algorithm: self.algorithm,
 			metadata_nonce,
+			salt,
 			metadata: encrypted_metadata,
 		});
 
@@ -105,58 +117,36 @@ impl FileHeader {
 
 	/// This function should be used to retrieve the metadata for a file
 	///
-	/// All it requires is a password. Hashing is handled for you.
+	/// It requires the header's master key, and the context the metadata was added with.
 	///
 	/// A deserialized data type will be returned from this function
 	#[cfg(feature = "serde")]
-	pub async fn decrypt_metadata<T>(&self, password: Protected<Vec<u8>>) -> Result<T>
-	where
-		T: serde::de::DeserializeOwned,
-	{
-		let master_key = self.decrypt_master_key(password).await?;
-
-		if let Some(metadata) = self.metadata.as_ref() {
-			let metadata = Decryptor::decrypt_bytes(
-				master_key,
-				metadata.metadata_nonce,
-				metadata.algorithm,
-				&metadata.metadata,
-				&[],
-			)
-			.await?;
-
-			serde_json::from_slice::<T>(metadata.expose()).map_err(|_| Error::Serialization)
-		} else {
-			Err(Error::NoMetadata)
-		}
-	}
-
-	/// This function should be used to retrieve the metadata for a file
-	///
-	/// All it requires is pre-hashed keys returned from the key manager
-	///
-	/// A deserialized data type will be returned from this function
-	#[cfg(feature = "serde")]
-	pub async fn decrypt_metadata_from_prehashed<T>(&self, hashed_keys: Vec<Key>) -> Result<T>
+	#[allow(clippy::needless_pass_by_value)]
+	pub async fn decrypt_metadata<T>(&self, master_key: Key, context: &str) -> Result<T>
 	where
 		T: serde::de::DeserializeOwned,
 	{
-		let master_key = self.decrypt_master_key_from_prehashed(hashed_keys).await?;
-
-		if let Some(metadata) = self.metadata.as_ref() {
-			let metadata = Decryptor::decrypt_bytes(
-				master_key,
-				metadata.metadata_nonce,
-				metadata.algorithm,
-				&metadata.metadata,
-				&[],
-			)
-			.await?;
+		let metadata = self.metadata.as_ref().ok_or(Error::NoMetadata)?;
+
+		let (key, aad) = match metadata.version {
+			// V1 metadata was encrypted with the master key itself, and isn't bound to the header
+			MetadataVersion::V1 => (master_key, Vec::new()),
+			MetadataVersion::V2 => (
+				Key::derive(master_key, metadata.salt, context),
+				self.generate_aad(),
+			),
+		};
+
+		let decrypted_metadata = Decryptor::decrypt_bytes(
+			key,
+			metadata.metadata_nonce,
+			metadata.algorithm,
+			&metadata.metadata,
+			&aad,
+		)
+		.await?;
 
-			serde_json::from_slice::<T>(metadata.expose()).map_err(|_| Error::Serialization)
-		} else {
-			Err(Error::NoMetadata)
-		}
+		serde_json::from_slice::<T>(decrypted_metadata.expose()).map_err(|_| Error::Serialization)
 	}
 }
 
@@ -171,20 +161,24 @@ impl Metadata {
 	/// This also includes the encrypted metadata itself, so this may be sizeable
 	#[must_use]
 	pub fn to_bytes(&self) -> Vec<u8> {
-		match self.version {
-			MetadataVersion::V1 => [
-				self.version.to_bytes().as_ref(),
-				self.algorithm.to_bytes().as_ref(),
-				&self.metadata_nonce,
-				&vec![0u8; 24 - self.metadata_nonce.len()],
-				&(self.metadata.len() as u64).to_le_bytes(),
-				&self.metadata,
-			]
-			.into_iter()
-			.flatten()
-			.copied()
-			.collect(),
-		}
+		let salt: &[u8] = match self.version {
+			MetadataVersion::V1 => &[],
+			MetadataVersion::V2 => &self.salt,
+		};
+
+		[
+			self.version.to_bytes().as_ref(),
+			self.algorithm.to_bytes().as_ref(),
+			&self.metadata_nonce,
+			&vec![0u8; 24 - self.metadata_nonce.len()],
+			salt,
+			&(self.metadata.len() as u64).to_le_bytes(),
+			&self.metadata,
+		]
+		.into_iter()
+		.flatten()
+		.copied()
+		.collect()
 	}
 
 	/// This function reads a metadata header item from a reader
@@ -200,38 +194,49 @@ impl Metadata {
 		reader.read_exact(&mut version).await?;
 		let version = MetadataVersion::from_bytes(version).map_err(|_| Error::NoMetadata)?;
 
-		match version {
-			MetadataVersion::V1 => {
-				let mut algorithm = [0u8; 2];
-				reader.read_exact(&mut algorithm).await?;
-				let algorithm = Algorithm::from_bytes(algorithm)?;
-
-				let mut metadata_nonce = vec![0u8; algorithm.nonce_len()];
-				reader.read_exact(&mut metadata_nonce).await?;
-				let metadata_nonce = Nonce::try_from(metadata_nonce)?;
+		let mut algorithm = [0u8; 2];
+		reader.read_exact(&mut algorithm).await?;
+		let algorithm = Algorithm::from_bytes(algorithm)?;
 
-				reader
-					.read_exact(&mut vec![0u8; 24 - metadata_nonce.len()])
-					.await?;
+		let mut metadata_nonce = vec![0u8; algorithm.nonce_len()];
+		reader.read_exact(&mut metadata_nonce).await?;
+		let metadata_nonce = Nonce::try_from(metadata_nonce)?;
 
-				let mut metadata_length = [0u8; 8];
-				reader.read_exact(&mut metadata_length).await?;
+		reader
+			.read_exact(&mut vec![0u8; 24 - metadata_nonce.len()])
+			.await?;
 
-				let metadata_length = u64::from_le_bytes(metadata_length);
+		let salt = match version {
+			MetadataVersion::V1 => Salt([0u8; SALT_LEN]),
+			MetadataVersion::V2 => {
+				let mut salt = [0u8; SALT_LEN];
+				reader.read_exact(&mut salt).await?;
+				Salt(salt)
+			}
+		};
 
-				#[allow(clippy::cast_possible_truncation)]
-				let mut metadata = vec![0u8; metadata_length as usize];
-				reader.read_exact(&mut metadata).await?;
+		let mut metadata_length = [0u8; 8];
+		reader.read_exact(&mut metadata_length).await?;
 
-				let metadata = Self {
-					version,
-					algorithm,
-					metadata_nonce,
-					metadata,
-				};
+		let metadata_length = u64::from_le_bytes(metadata_length);
 
-				Ok(metadata)
-			}
+		// V1 metadata was never bounded, so we can't reject it here
+		if matches!(version, MetadataVersion::V2)
+			&& metadata_length > (METADATA_MAX_LEN + AEAD_TAG_LEN) as u64
+		{
+			return Err(Error::MetadataTooLarge);
 		}
+
+		#[allow(clippy::cast_possible_truncation)]
+		let mut metadata = vec![0u8; metadata_length as usize];
+		reader.read_exact(&mut metadata).await?;
+
+		Ok(Self {
+			version,
+			algorithm,
+			metadata_nonce,
+			salt,
+			metadata,
+		})
 	}
 }
@@ -9,7 +9,9 @@ use crate::{
 };
 
 use super::{
-	file::FileHeaderVersion, keyslot::KeyslotVersion, metadata::MetadataVersion,
+	file::FileHeaderVersion,
+	keyslot::{KeyslotKind, KeyslotVersion},
+	metadata::MetadataVersion,
 	preview_media::PreviewMediaVersion,
 };
 
@@ -18,12 +20,14 @@ impl FileHeaderVersion {
 	pub const fn to_bytes(&self) -> [u8; 2] {
 		match self {
 			Self::V1 => [0x0A, 0x01],
+			Self::V2 => [0x0A, 0x02],
 		}
 	}
 
 	pub const fn from_bytes(bytes: [u8; 2]) -> Result<Self> {
 		match bytes {
 			[0x0A, 0x01] => Ok(Self::V1),
+			[0x0A, 0x02] => Ok(Self::V2),
 			_ => Err(Error::Serialization),
 		}
 	}
@@ -33,6 +37,7 @@ impl Display for FileHeaderVersion {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match *self {
 			Self::V1 => write!(f, "V1"),
+			Self::V2 => write!(f, "V2"),
 		}
 	}
 }
@@ -42,12 +47,14 @@ impl KeyslotVersion {
 	pub const fn to_bytes(&self) -> [u8; 2] {
 		match self {
 			Self::V1 => [0x0D, 0x01],
+			Self::V2 => [0x0D, 0x02],
 		}
 	}
 
 	pub const fn from_bytes(bytes: [u8; 2]) -> Result<Self> {
 		match bytes {
 			[0x0D, 0x01] => Ok(Self::V1),
+			[0x0D, 0x02] => Ok(Self::V2),
 			_ => Err(Error::Serialization),
 		}
 	}
@@ -57,6 +64,38 @@ impl Display for KeyslotVersion {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match *self {
 			Self::V1 => write!(f, "V1"),
+			Self::V2 => write!(f, "V2"),
+		}
+	}
+}
+
+impl KeyslotKind {
+	/// These are the keyslot's flags, the hashing algorithm is serialized separately
+	#[must_use]
+	pub const fn to_bytes(&self) -> [u8; 2] {
+		match self {
+			Self::Password(_) => [0x0F, 0x01],
+			Self::Keyfile => [0x0F, 0x02],
+		}
+	}
+
+	pub const fn from_bytes(flags: [u8; 2], hashing_algorithm: [u8; 2]) -> Result<Self> {
+		match flags {
+			[0x0F, 0x01] => match HashingAlgorithm::from_bytes(hashing_algorithm) {
+				Ok(hashing_algorithm) => Ok(Self::Password(hashing_algorithm)),
+				Err(e) => Err(e),
+			},
+			[0x0F, 0x02] => Ok(Self::Keyfile),
+			_ => Err(Error::Serialization),
+		}
+	}
+}
+
+impl Display for KeyslotKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match *self {
+			Self::Password(h) => write!(f, "Password ({h})"),
+			Self::Keyfile => write!(f, "Keyfile"),
 		}
 	}
 }
@@ -90,12 +129,14 @@ impl MetadataVersion {
 	pub const fn to_bytes(&self) -> [u8; 2] {
 		match self {
 			Self::V1 => [0x1F, 0x01],
+			Self::V2 => [0x1F, 0x02],
 		}
 	}
 
 	pub const fn from_bytes(bytes: [u8; 2]) -> Result<Self> {
 		match bytes {
 			[0x1F, 0x01] => Ok(Self::V1),
+			[0x1F, 0x02] => Ok(Self::V2),
 			_ => Err(Error::Serialization),
 		}
 	}
@@ -105,6 +146,7 @@ impl Display for MetadataVersion {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match *self {
 			Self::V1 => write!(f, "V1"),
+			Self::V2 => write!(f, "V2"),
 		}
 	}
 }
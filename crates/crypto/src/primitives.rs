@@ -42,13 +42,13 @@ pub const APP_IDENTIFIER: &str = "Spacedrive";
 pub const SECRET_KEY_IDENTIFIER: &str = "Secret key";
 
 /// Defines the latest `FileHeaderVersion`
-pub const LATEST_FILE_HEADER: FileHeaderVersion = FileHeaderVersion::V1;
+pub const LATEST_FILE_HEADER: FileHeaderVersion = FileHeaderVersion::V2;
 
 /// Defines the latest `KeyslotVersion`
-pub const LATEST_KEYSLOT: KeyslotVersion = KeyslotVersion::V1;
+pub const LATEST_KEYSLOT: KeyslotVersion = KeyslotVersion::V2;
 
 /// Defines the latest `MetadataVersion`
-pub const LATEST_METADATA: MetadataVersion = MetadataVersion::V1;
+pub const LATEST_METADATA: MetadataVersion = MetadataVersion::V2;
 
 /// Defines the latest `PreviewMediaVersion`
 pub const LATEST_PREVIEW_MEDIA: PreviewMediaVersion = PreviewMediaVersion::V1;
@@ -26,7 +26,7 @@ pub enum Nonce {
 /// These parameters define the password-hashing level.
 ///
 /// The greater the parameter, the longer the password will take to hash.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[cfg_attr(
 	feature = "serde",
 	derive(serde::Serialize),
@@ -40,7 +40,7 @@ pub enum Params {
 }
 
 /// This defines all available password hashing algorithms.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[cfg_attr(
 	feature = "serde",
 	derive(serde::Serialize),
@@ -121,7 +121,7 @@ impl Deref for Nonce {
 }
 
 /// These are all possible algorithms that can be used for encryption and decryption
-#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(
 	feature = "serde",
 	derive(serde::Serialize),
@@ -161,7 +161,13 @@ impl Key {
 	#[must_use]
 	#[allow(clippy::needless_pass_by_value)]
 	pub fn derive(key: Self, salt: Salt, context: &str) -> Self {
-		let mut input = key.expose().to_vec();
+		Self::derive_from_secret(key.expose(), salt, context)
+	}
+
+	/// This is the same as `Key::derive()`, but it borrows the secret so it never has to be copied out of it's (zeroizing) container.
+	#[must_use]
+	pub fn derive_from_secret(secret: &[u8], salt: Salt, context: &str) -> Self {
+		let mut input = secret.to_vec();
 		input.extend_from_slice(&salt);
 		let key = blake3::derive_key(context, &input);
 
@@ -77,12 +77,13 @@ extension_category_enum! {
 		Webp = [0x52, 0x49, 0x46, 0x46, _, _, _, _, 0x57, 0x45, 0x42, 0x50],
 		Svg = [0x3C, 0x73, 0x76, 0x67],
 		Ico = [0x00, 0x00, 0x01, 0x00],
-		Heic = [0x00, 0x00, 0x00, 0x18, 0x66, 0x74, 0x79, 0x70, 0x68, 0x65, 0x69, 0x63],
-		Heics  = [0x00, 0x00, 0x00, 0x18, 0x66, 0x74, 0x79, 0x70, 0x68, 0x65, 0x69, 0x63],
-		Heif = [],
-		Heifs = [],
+		// HEIF based formats are ISO BMFF files, identified by the brand of their `ftyp` box
+		Heic = [0x66, 0x74, 0x79, 0x70, 0x68, 0x65, 0x69, 0x63] + 4 | [0x66, 0x74, 0x79, 0x70, 0x68, 0x65, 0x69, 0x78] + 4,
+		Heics = [0x66, 0x74, 0x79, 0x70, 0x68, 0x65, 0x76, 0x63] + 4 | [0x66, 0x74, 0x79, 0x70, 0x68, 0x65, 0x76, 0x78] + 4,
+		Heif = [0x66, 0x74, 0x79, 0x70, 0x6D, 0x69, 0x66, 0x31] + 4,
+		Heifs = [0x66, 0x74, 0x79, 0x70, 0x6D, 0x73, 0x66, 0x31] + 4,
 		Hif = [],
-		Avif = [],
+		Avif = [0x66, 0x74, 0x79, 0x70, 0x61, 0x76, 0x69, 0x66] + 4 | [0x66, 0x74, 0x79, 0x70, 0x61, 0x76, 0x69, 0x73] + 4,
 		Avci = [],
 		Avcs = [],
 		Raw = [],
@@ -209,6 +210,8 @@ extension_category_enum! {
 	EncryptedExtension _ALL_ENCRYPTED_EXTENSIONS {
 		// Spacedrive encrypted file
 		Bytes = [0x62, 0x61, 0x6C, 0x6C, 0x61, 0x70, 0x70],
+		// Spacedrive encrypted file, written by the file encryptor job
+		Sdenc = [0x62, 0x61, 0x6C, 0x6C, 0x61, 0x70, 0x70],
 		// Spacedrive container
 		Container = [0x73, 0x64, 0x62, 0x6F, 0x78],
 		// Spacedrive block storage,
@@ -385,6 +388,19 @@ mod test {
 				Extension::Code(CodeExtension::Ts)
 			]))
 		);
+		// phones usually use uppercase extensions for these
+		assert_eq!(
+			Extension::from_str("HEIC"),
+			Some(ExtensionPossibility::Known(Extension::Image(
+				ImageExtension::Heic
+			)))
+		);
+		assert_eq!(
+			Extension::from_str("avif"),
+			Some(ExtensionPossibility::Known(Extension::Image(
+				ImageExtension::Avif
+			)))
+		);
 		// invalid case
 		assert_eq!(Extension::from_str("jeff"), None);
 	}
@@ -31,7 +31,8 @@ static TEXT_CHARS: [u8; 256] = [
 ];
 
 fn looks_latin1(buf: &[u8]) -> bool {
-	buf.iter().all(|&byte| byte == T || byte == I)
+	buf.iter()
+		.all(|&byte| matches!(TEXT_CHARS[byte as usize], T | I))
 }
 
 const XX: u8 = 0xF1; // invalid: size 1
@@ -163,7 +164,7 @@ enum UCS16 {
 }
 
 fn looks_ucs16(buf: &[u8]) -> Option<UCS16> {
-	if buf.len() % 2 == 0 {
+	if buf.len() < 2 || buf.len() % 2 != 0 {
 		return None;
 	}
 
@@ -192,7 +193,7 @@ fn looks_ucs16(buf: &[u8]) -> Option<UCS16> {
 
 		if hi != 0 {
 			// UCS16_LOSURR
-			if (0xdc00..=0xdfff).contains(&uc) {
+			if !(0xdc00..=0xdfff).contains(&uc) {
 				return None;
 			}
 			uc = 0x10000 + 0x400 * (hi - 1) + (uc - 0xdc00);
@@ -227,7 +228,7 @@ enum UCS32 {
 }
 
 fn looks_ucs32(buf: &[u8]) -> Option<UCS32> {
-	if buf.len() % 4 == 0 {
+	if buf.len() < 4 || buf.len() % 4 != 0 {
 		return None;
 	}
 
@@ -295,3 +296,86 @@ pub fn is_text(data: &[u8], partial: bool) -> Option<&'static str> {
 		None
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn latin1() {
+		assert!(looks_latin1(b"caf\xe9\n"));
+		// NEL is the only text character between 0x80 and 0x9f
+		assert!(looks_latin1(b"line\x85"));
+		assert!(!looks_latin1(b"caf\xe9\x80"));
+		assert!(!looks_latin1(b"null\x00"));
+	}
+
+	#[test]
+	fn ucs16() {
+		assert!(matches!(
+			looks_ucs16(b"\xff\xfeh\x00i\x00"),
+			Some(UCS16::LittleEnd)
+		));
+		assert!(matches!(
+			looks_ucs16(b"\xfe\xff\x00h\x00i"),
+			Some(UCS16::BigEnd)
+		));
+		// without a byte order mark
+		assert!(looks_ucs16(b"h\x00i\x00").is_none());
+		// too short, or cut in the middle of a code unit
+		assert!(looks_ucs16(b"").is_none());
+		assert!(looks_ucs16(b"\xff").is_none());
+		assert!(looks_ucs16(b"\xff\xfeh\x00i").is_none());
+		// control characters
+		assert!(looks_ucs16(b"\xff\xfe\x01\x00").is_none());
+	}
+
+	#[test]
+	fn ucs16_surrogates() {
+		// U+1F600, as the surrogate pair D83D DE00
+		assert!(matches!(
+			looks_ucs16(b"\xff\xfe\x3d\xd8\x00\xde"),
+			Some(UCS16::LittleEnd)
+		));
+		assert!(matches!(
+			looks_ucs16(b"\xfe\xff\xd8\x3d\xde\x00"),
+			Some(UCS16::BigEnd)
+		));
+		// a high surrogate not followed by a low one
+		assert!(looks_ucs16(b"\xff\xfe\x3d\xd8h\x00").is_none());
+		// a low surrogate on its own
+		assert!(looks_ucs16(b"\xff\xfe\x00\xdeh\x00").is_none());
+	}
+
+	#[test]
+	fn ucs32() {
+		assert!(matches!(
+			looks_ucs32(b"\xff\xfe\x00\x00h\x00\x00\x00"),
+			Some(UCS32::LittleEnd)
+		));
+		assert!(matches!(
+			looks_ucs32(b"\x00\x00\xfe\xff\x00\x00\x00h"),
+			Some(UCS32::BigEnd)
+		));
+		// without a byte order mark
+		assert!(looks_ucs32(b"h\x00\x00\x00").is_none());
+		// too short, or cut in the middle of a code unit
+		assert!(looks_ucs32(b"").is_none());
+		assert!(looks_ucs32(b"\xff\xfe").is_none());
+		assert!(looks_ucs32(b"\xff\xfe\x00\x00h\x00").is_none());
+		// control characters
+		assert!(looks_ucs32(b"\xff\xfe\x00\x00\x01\x00\x00\x00").is_none());
+	}
+
+	#[test]
+	fn text_encodings() {
+		assert_eq!(is_text(b"hi\n", false), Some("utf-8"));
+		assert_eq!(is_text(b"\xff\xfeh\x00i\x00", false), Some("utf-16le"));
+		assert_eq!(
+			is_text(b"\x00\x00\xfe\xff\x00\x00\x00h", false),
+			Some("utf-32be")
+		);
+		assert_eq!(is_text(b"caf\xe9\n", false), Some("iso-8859-1"));
+		assert_eq!(is_text(b"\x00\x01\x02", false), None);
+	}
+}
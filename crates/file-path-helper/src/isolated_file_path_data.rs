@@ -10,6 +10,7 @@ use std::{
 
 use regex::RegexSet;
 use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
 
 use super::{
 	file_path_for_file_identifier, file_path_for_media_processor, file_path_for_object_validator,
@@ -167,6 +168,11 @@ impl<'a> IsolatedFilePathData<'a> {
 		}
 	}
 
+	/// The [`full_name`](Self::full_name) as stored in `file_path.name_normalized`, see [`normalize_name`]
+	pub fn normalized_name(&self, case_insensitive: bool) -> String {
+		normalize_name(&self.full_name(), case_insensitive)
+	}
+
 	pub fn materialized_path_for_children(&self) -> Option<String> {
 		if self.materialized_path == "/" && self.name.is_empty() && self.is_dir {
 			// We're at the root file_path
@@ -475,6 +481,7 @@ impl_from_db_without_location_id!(
 	file_path_to_full_path,
 	file_path_for_media_processor,
 	file_path_for_object_validator,
+	file_path_for_integrity_verifier,
 	file_path_to_handle_custom_uri,
 	file_path_to_handle_p2p_serve_file
 );
@@ -578,6 +585,17 @@ pub fn push_location_relative_path(
 	location_path
 }
 
+/// Normalizes a file name to compare it the way the filesystem does, as different names can point
+/// to the same file: they're composed into NFC, as macOS may hand them out decomposed (NFD), and
+/// are case folded if the filesystem is case-insensitive.
+pub fn normalize_name(name: &str, case_insensitive: bool) -> String {
+	if case_insensitive {
+		name.to_lowercase().nfc().collect()
+	} else {
+		name.nfc().collect()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -763,4 +781,26 @@ mod tests {
 			"a file inside a third level directory",
 		);
 	}
+
+	#[test]
+	fn normalize_names() {
+		let composed = "caf\u{e9}.txt";
+		let decomposed = "cafe\u{301}.txt";
+
+		assert_ne!(composed, decomposed);
+		assert_eq!(
+			normalize_name(composed, false),
+			normalize_name(decomposed, false)
+		);
+		assert_eq!(normalize_name(decomposed, false), composed);
+
+		assert_ne!(
+			normalize_name("CAF\u{c9}.TXT", false),
+			normalize_name(decomposed, false)
+		);
+		assert_eq!(
+			normalize_name("CAF\u{c9}.TXT", true),
+			normalize_name(decomposed, true)
+		);
+	}
 }
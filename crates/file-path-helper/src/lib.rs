@@ -17,7 +17,7 @@ use tracing::error;
 pub mod isolated_file_path_data;
 
 pub use isolated_file_path_data::{
-	join_location_relative_path, push_location_relative_path, IsolatedFilePathData,
+	join_location_relative_path, normalize_name, push_location_relative_path, IsolatedFilePathData,
 	IsolatedFilePathDataParts,
 };
 
@@ -45,6 +45,17 @@ file_path::select!(file_path_for_object_validator {
 	extension
 	integrity_checksum
 });
+file_path::select!(file_path_for_integrity_verifier {
+	id
+	materialized_path
+	is_dir
+	name
+	extension
+	cas_id
+	integrity_checksum
+	size_in_bytes_bytes
+	date_modified
+});
 file_path::select!(file_path_for_media_processor {
 	id
 	materialized_path
@@ -458,6 +469,46 @@ pub async fn get_inode_from_path(path: impl AsRef<Path>) -> Result<u64, FilePath
 	}
 }
 
+/// Identifies a file or directory on its filesystem. It's kept when the entry is renamed or moved
+/// within its volume, but not when it's copied.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilesystemId {
+	/// The device on unix and the volume serial number on windows.
+	pub device: u64,
+	/// The inode on unix and the file index on windows.
+	pub inode: u64,
+}
+
+pub async fn get_filesystem_id(path: impl AsRef<Path>) -> Result<FilesystemId, FilePathError> {
+	#[cfg(target_family = "unix")]
+	{
+		use std::os::unix::fs::MetadataExt;
+
+		let metadata = fs::metadata(path.as_ref())
+			.await
+			.map_err(|e| FileIOError::from((path, e)))?;
+
+		Ok(FilesystemId {
+			device: metadata.dev(),
+			inode: metadata.ino(),
+		})
+	}
+
+	#[cfg(target_family = "windows")]
+	{
+		use winapi_util::{file::information, Handle};
+
+		let info = Handle::from_path_any(path.as_ref())
+			.and_then(|ref handle| information(handle))
+			.map_err(|e| FileIOError::from((path, e)))?;
+
+		Ok(FilesystemId {
+			device: info.volume_serial_number(),
+			inode: info.file_index(),
+		})
+	}
+}
+
 pub trait MetadataExt {
 	fn created_or_now(&self) -> SystemTime;
 
@@ -38,6 +38,7 @@ pub use consts::{all_compatible_extensions, ConvertableExtension};
 pub use error::{Error, Result};
 pub use handler::{convert_image, format_image};
 pub use image::DynamicImage;
+pub use pdf::{format_pdf_page, pdf_page_count};
 
 pub trait ImageHandler {
 	#[inline]
@@ -81,22 +81,40 @@ pub struct PdfHandler {}
 
 impl ImageHandler for PdfHandler {
 	fn handle_image(&self, path: &Path) -> Result<DynamicImage> {
-		let pdfium = Pdfium::new(Pdfium::bind_to_library(PDFIUM_LIB.as_str()).or_else(|err| {
+		format_pdf_page(path, 0)
+	}
+}
+
+fn pdfium() -> Result<Pdfium> {
+	Ok(Pdfium::new(
+		Pdfium::bind_to_library(PDFIUM_LIB.as_str()).or_else(|err| {
 			error!("{err:#?}");
 			Pdfium::bind_to_system_library()
-		})?);
+		})?,
+	))
+}
 
-		let pdf = pdfium.load_pdf_from_file(path, None)?;
-		let first_page = pdf.pages().first()?;
+/// Renders a page of a PDF document, by its zero-based index, the same way its thumbnail is.
+pub fn format_pdf_page(path: impl AsRef<Path>, page_index: u16) -> Result<DynamicImage> {
+	let pdfium = pdfium()?;
 
-		let image = first_page
-			.render_with_config(if first_page.is_portrait() {
-				&PORTRAIT_CONFIG
-			} else {
-				&LANDSCAPE_CONFIG
-			})?
-			.as_image();
+	let pdf = pdfium.load_pdf_from_file(path.as_ref(), None)?;
+	let page = pdf.pages().get(page_index)?;
 
-		Ok(image)
-	}
+	let image = page
+		.render_with_config(if page.is_portrait() {
+			&PORTRAIT_CONFIG
+		} else {
+			&LANDSCAPE_CONFIG
+		})?
+		.as_image();
+
+	Ok(image)
+}
+
+pub fn pdf_page_count(path: impl AsRef<Path>) -> Result<u16> {
+	Ok(pdfium()?
+		.load_pdf_from_file(path.as_ref(), None)?
+		.pages()
+		.len())
 }
@@ -0,0 +1,6 @@
+#[derive(
+	Default, Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize, specta::Type,
+)]
+pub struct DocumentMetadata {
+	pub page_count: u32,
+}
@@ -2,7 +2,7 @@ use super::{
 	consts::{OFFSET_TAGS, TIME_TAGS},
 	ExifReader,
 };
-use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
 use serde::{
 	de::{self, Visitor},
 	Deserialize, Deserializer,
@@ -50,6 +50,44 @@ impl MediaDate {
 			.map(Clone::clone)
 	}
 
+	/// Finds the date in file names like `IMG_20230415_093012.jpg` or
+	/// `Screenshot 2023-04-15 at 09.30.12.png`, as cameras and phones name their files.
+	///
+	/// Years, months and days can be separated by `-`, `_` or `.`, or not at all. Without a time
+	/// after the date, it's midnight. Those dates are always naive, as file names have no timezone.
+	#[must_use]
+	pub fn from_file_name(name: &str) -> Option<Self> {
+		let bytes = name.as_bytes();
+
+		(0..bytes.len())
+			// A date doesn't start in the middle of a number
+			.filter(|&start| start == 0 || !bytes[start - 1].is_ascii_digit())
+			.find_map(|start| {
+				let mut cursor = start;
+
+				let year = take_digits(bytes, &mut cursor, 4)?;
+				if !(1900..=2099).contains(&year) {
+					return None;
+				}
+				skip_separator(bytes, &mut cursor, b"-_.");
+				let month = take_digits(bytes, &mut cursor, 2)?;
+				skip_separator(bytes, &mut cursor, b"-_.");
+				let day = take_digits(bytes, &mut cursor, 2)?;
+
+				let date = NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month, day)?;
+
+				if bytes[cursor..].starts_with(b" at ") {
+					cursor += 4;
+				} else {
+					skip_separator(bytes, &mut cursor, b" _T-");
+				}
+
+				Some(Self::Naive(date.and_time(
+					take_time(bytes, &mut cursor).unwrap_or(NaiveTime::MIN),
+				)))
+			})
+	}
+
 	/// Returns the amount of non-leap secods since the Unix Epoch (1970-01-01T00:00:00+00:00)
 	///
 	/// This is for search ordering/sorting
@@ -62,6 +100,40 @@ impl MediaDate {
 	}
 }
 
+fn take_digits(bytes: &[u8], cursor: &mut usize, count: usize) -> Option<u32> {
+	let digits = bytes.get(*cursor..*cursor + count)?;
+	if !digits.iter().all(u8::is_ascii_digit) {
+		return None;
+	}
+
+	*cursor += count;
+
+	Some(
+		digits
+			.iter()
+			.fold(0, |acc, digit| acc * 10 + u32::from(digit - b'0')),
+	)
+}
+
+fn take_time(bytes: &[u8], cursor: &mut usize) -> Option<NaiveTime> {
+	let hour = take_digits(bytes, cursor, 2)?;
+	skip_separator(bytes, cursor, b"-_.:");
+	let minute = take_digits(bytes, cursor, 2)?;
+	skip_separator(bytes, cursor, b"-_.:");
+	let second = take_digits(bytes, cursor, 2)?;
+
+	NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+fn skip_separator(bytes: &[u8], cursor: &mut usize, separators: &[u8]) {
+	if bytes
+		.get(*cursor)
+		.is_some_and(|byte| separators.contains(byte))
+	{
+		*cursor += 1;
+	}
+}
+
 impl serde::Serialize for MediaDate {
 	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
 	where
@@ -107,3 +179,43 @@ impl<'de> Deserialize<'de> for MediaDate {
 		deserializer.deserialize_str(MediaDateVisitor)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn naive(date: &str) -> Option<MediaDate> {
+		Some(MediaDate::Naive(
+			NaiveDateTime::parse_from_str(date, NAIVE_FORMAT_STR).unwrap(),
+		))
+	}
+
+	#[test]
+	fn test_from_file_name() {
+		assert_eq!(
+			MediaDate::from_file_name("IMG_20230415_093012.jpg"),
+			naive("2023-04-15 09:30:12")
+		);
+		assert_eq!(
+			MediaDate::from_file_name("PXL_20230415_093012345.jpg"),
+			naive("2023-04-15 09:30:12")
+		);
+		assert_eq!(
+			MediaDate::from_file_name("Screenshot 2023-04-15 at 09.30.12.png"),
+			naive("2023-04-15 09:30:12")
+		);
+		assert_eq!(
+			MediaDate::from_file_name("2023-04-15 holidays.heic"),
+			naive("2023-04-15 00:00:00")
+		);
+		assert_eq!(
+			MediaDate::from_file_name("VID-20230415-WA0001.mp4"),
+			naive("2023-04-15 00:00:00")
+		);
+
+		assert_eq!(MediaDate::from_file_name("IMG_0042.jpg"), None);
+		assert_eq!(MediaDate::from_file_name("IMG_20231345.jpg"), None);
+		assert_eq!(MediaDate::from_file_name("1202304150.jpg"), None);
+		assert_eq!(MediaDate::from_file_name("notes.txt"), None);
+	}
+}
@@ -22,11 +22,13 @@
 #![allow(clippy::missing_errors_doc, clippy::module_name_repetitions)]
 
 pub mod audio;
+pub mod document;
 mod error;
 pub mod image;
 pub mod video;
 
 pub use audio::AudioMetadata;
+pub use document::DocumentMetadata;
 pub use error::{Error, Result};
 pub use image::ImageMetadata;
 pub use video::VideoMetadata;
@@ -37,4 +39,5 @@ pub enum MediaMetadata {
 	Image(Box<ImageMetadata>),
 	Video(Box<VideoMetadata>),
 	Audio(Box<AudioMetadata>),
+	Document(Box<DocumentMetadata>),
 }
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use libp2p::{
+	dcutr, ping, relay,
+	swarm::{behaviour::toggle::Toggle, NetworkBehaviour},
+	PeerId,
+};
+
+use crate::{spacetime::SpaceTime, Manager};
+
+/// The [`NetworkBehaviour`] driving the `libp2p::Swarm`.
+///
+/// `SpaceTime` does all of the application level work. The other behaviours allow us to reach peers which are not on the local network.
+#[derive(NetworkBehaviour)]
+pub(crate) struct SwarmBehaviour {
+	pub(crate) spacetime: SpaceTime,
+	/// Allows connecting to peers through a relay server when a direct connection isn't possible.
+	pub(crate) relay: relay::client::Behaviour,
+	/// Attempts to upgrade relayed connections into direct ones. This is toggled by `ManagerConfig::enable_hole_punching` when the swarm is built.
+	pub(crate) dcutr: Toggle<dcutr::Behaviour>,
+	/// Used to measure the round trip time of each connection.
+	pub(crate) ping: ping::Behaviour,
+}
+
+impl SwarmBehaviour {
+	pub(crate) fn new(
+		manager: Arc<Manager>,
+		relay: relay::client::Behaviour,
+		enable_hole_punching: bool,
+	) -> Self {
+		let peer_id = manager.peer_id;
+		Self {
+			spacetime: SpaceTime::new(manager),
+			relay,
+			dcutr: hole_punching(peer_id, enable_hole_punching),
+			ping: ping::Behaviour::default(),
+		}
+	}
+}
+
+/// Construct the hole punching behaviour.
+/// This must live as long as the swarm, as `dcutr::Behaviour` panics when told about a connection closing which it never saw established.
+pub(crate) fn hole_punching(peer_id: PeerId, enabled: bool) -> Toggle<dcutr::Behaviour> {
+	Toggle::from(enabled.then(|| dcutr::Behaviour::new(peer_id)))
+}
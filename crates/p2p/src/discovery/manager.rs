@@ -40,7 +40,7 @@ impl DiscoveryManager {
 		service_shutdown_rx: mpsc::Receiver<String>,
 	) -> Result<Self, mdns_sd::Error> {
 		let mut mdns = None;
-		if config.enabled {
+		if config.mdns_enabled() {
 			mdns = Some(Mdns::new(application_name, identity, peer_id)?);
 		}
 
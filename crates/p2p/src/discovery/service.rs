@@ -81,7 +81,7 @@ impl<TMeta: Metadata> Service<TMeta> {
 			.get_mut(&self.name)
 		{
 			let meta = meta.to_hashmap();
-			let did_change = services_meta.as_ref().is_some_and(|v| *v == meta);
+			let did_change = services_meta.as_ref() != Some(&meta);
 			*services_meta = Some(meta);
 
 			if did_change {
@@ -1,6 +1,7 @@
 //! Rust Peer to Peer Networking Library
 #![warn(clippy::all, clippy::unwrap_used, clippy::panic)]
 
+mod behaviour;
 mod discovery;
 mod event;
 mod manager;
@@ -12,6 +13,7 @@ pub mod spacetime;
 pub mod spacetunnel;
 mod utils;
 
+pub(crate) use behaviour::*;
 pub use discovery::*;
 pub use event::*;
 pub use manager::*;
@@ -3,27 +3,32 @@ use std::{
 	convert::Infallible,
 	fmt,
 	net::SocketAddr,
+	str::FromStr,
 	sync::{
 		atomic::{AtomicBool, AtomicU64},
 		Arc, PoisonError, RwLock,
 	},
+	time::Duration,
 };
 
 use libp2p::{
 	core::{muxing::StreamMuxerBox, transport::ListenerId, ConnectedPoint},
-	PeerId, SwarmBuilder, Transport,
+	multiaddr::Protocol,
+	Multiaddr, PeerId, SwarmBuilder, Transport,
 };
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot};
 use tracing::{error, warn};
+use zeroize::Zeroizing;
 
 use crate::{
-	spacetime::{SpaceTime, UnicastStream, UnicastStreamError},
+	socketaddr_to_quic_multiaddr,
+	spacetime::{UnicastStream, UnicastStreamError},
 	spacetunnel::{Identity, RemoteIdentity},
 	DiscoveryManager, DiscoveryManagerState, Keypair, ManagerStream, ManagerStreamAction,
-	ManagerStreamAction2,
+	ManagerStreamAction2, SwarmBehaviour,
 };
 
 // State of the manager that may infrequently change
@@ -35,11 +40,15 @@ pub struct DynamicManagerState {
 	pub(crate) ipv4_port: Option<u16>,
 	pub(crate) ipv6_listener_id: Option<Result<ListenerId, String>>,
 	pub(crate) ipv6_port: Option<u16>,
+	// The relay servers we are currently holding a reservation on, keyed by the relay's `PeerId`.
+	pub(crate) relay_listener_ids: HashMap<libp2p::PeerId, Result<ListenerId, String>>,
 	// A map of connected clients.
 	// This includes both inbound and outbound connections!
 	pub(crate) connected: HashMap<libp2p::PeerId, RemoteIdentity>,
 	// TODO: Removing this would be nice. It's a hack to things working after removing the `PeerId` from public API.
 	pub(crate) connections: HashMap<libp2p::PeerId, (ConnectedPoint, usize)>,
+	// The last measured round trip time for each connected peer
+	pub(crate) rtt: HashMap<libp2p::PeerId, Duration>,
 }
 
 /// Is the core component of the P2P system that holds the state and delegates actions to the other components
@@ -78,6 +87,7 @@ impl Manager {
 		let (event_stream_tx2, event_stream_rx2) = mpsc::channel(128);
 
 		let config2 = config.clone();
+		let enable_hole_punching = config.enable_hole_punching;
 		let (discovery_state, service_shutdown_rx) = DiscoveryManagerState::new();
 		let this = Arc::new(Self {
 			application_name: format!("/{application_name}/spacetime/1.0.0"),
@@ -89,8 +99,10 @@ impl Manager {
 				ipv4_port: None,
 				ipv6_listener_id: None,
 				ipv6_port: None,
+				relay_listener_ids: Default::default(),
 				connected: Default::default(),
 				connections: Default::default(),
+				rtt: Default::default(),
 			}),
 			discovery_state,
 			peer_id,
@@ -107,13 +119,15 @@ impl Manager {
 				.map(|(p, c), _| (p, StreamMuxerBox::new(c)))
 				.boxed()
 			}))
-		.with_behaviour(|_| SpaceTime::new(this.clone())))
+		.with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)?
+		.with_behaviour(|_, relay| SwarmBehaviour::new(this.clone(), relay, enable_hole_punching)))
 		.build();
 
-		ManagerStream::refresh_listeners(
-			&mut swarm,
-			&mut this.state.write().unwrap_or_else(PoisonError::into_inner),
-		);
+		{
+			let mut state = this.state.write().unwrap_or_else(PoisonError::into_inner);
+			ManagerStream::refresh_listeners(&mut swarm, &mut state);
+			ManagerStream::refresh_relays(&mut swarm, &mut state);
+		}
 
 		Ok((
 			this.clone(),
@@ -152,10 +166,44 @@ impl Manager {
 		self.peer_id
 	}
 
+	/// The secret shared between this node and the remote peer. Refer to [`Identity::shared_secret`].
+	#[must_use]
+	pub fn shared_secret(&self, identity: &RemoteIdentity) -> Zeroizing<[u8; 32]> {
+		self.identity.shared_secret(identity)
+	}
+
 	pub async fn update_config(&self, config: ManagerConfig) {
 		self.emit(ManagerStreamAction::UpdateConfig(config)).await;
 	}
 
+	/// Attempt to connect to a peer through each of the configured relay servers.
+	/// This is used for peers which we know about but can't discover on the local network.
+	pub async fn connect_via_relay(&self, identity: RemoteIdentity) {
+		self.emit(ManagerStreamAction::DialRelayed(identity)).await;
+	}
+
+	/// Get information about the quality of each active connection
+	pub fn connections(&self) -> Vec<PeerConnection> {
+		let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+
+		state
+			.connected
+			.iter()
+			.map(|(peer_id, identity)| PeerConnection {
+				identity: *identity,
+				relayed: state
+					.connections
+					.get(peer_id)
+					.map(|(endpoint, _)| endpoint.is_relayed())
+					.unwrap_or_default(),
+				rtt_ms: state
+					.rtt
+					.get(peer_id)
+					.map(|rtt| rtt.as_millis().try_into().unwrap_or(u32::MAX)),
+			})
+			.collect()
+	}
+
 	pub async fn get_connected_peers(&self) -> Result<Vec<RemoteIdentity>, ()> {
 		let (tx, rx) = oneshot::channel();
 		self.emit(ManagerStreamAction::GetConnectedPeers(tx)).await;
@@ -311,6 +359,8 @@ pub enum ManagerError {
 	InvalidAppName,
 	#[error("error with mdns discovery: {0}")]
 	Mdns(#[from] mdns_sd::Error),
+	#[error("error setting up relay transport: {0}")]
+	Relay(#[from] libp2p::noise::Error),
 	// #[error("todo")]
 	// Manager(#[from] ManagerError),
 }
@@ -325,6 +375,15 @@ pub struct ManagerConfig {
 	// `None` will chose a random free port on startup
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	pub port: Option<u16>,
+	// Relay servers used to reach peers which can't be discovered on the local network
+	#[serde(default)]
+	pub relays: Vec<RelayConfig>,
+	// Attempt to upgrade relayed connections into direct connections, changes apply on the next startup
+	#[serde(default = "default_enable_hole_punching")]
+	pub enable_hole_punching: bool,
+	// How peers are found, so it can be kept to the local network
+	#[serde(default)]
+	pub discovery: DiscoveryMode,
 }
 
 impl Default for ManagerConfig {
@@ -332,10 +391,73 @@ impl Default for ManagerConfig {
 		Self {
 			enabled: true,
 			port: None,
+			relays: Vec::new(),
+			enable_hole_punching: default_enable_hole_punching(),
+			discovery: DiscoveryMode::default(),
 		}
 	}
 }
 
+impl ManagerConfig {
+	/// Whether peers are looked for, and this node advertised, on the local network with mDNS.
+	pub fn mdns_enabled(&self) -> bool {
+		self.enabled && self.discovery != DiscoveryMode::Disabled
+	}
+
+	/// Whether the relays are used, so peers on other networks can be reached.
+	pub fn relays_enabled(&self) -> bool {
+		self.enabled && self.discovery == DiscoveryMode::Full
+	}
+}
+
+/// How the P2P layer finds peers, and lets itself be found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Type)]
+pub enum DiscoveryMode {
+	/// Peers aren't looked for, nor is this node advertised.
+	Disabled,
+	/// Only peers on the local network are found, with mDNS.
+	LocalOnly,
+	/// Peers on the local network and, through the relays, on other networks are found.
+	#[default]
+	Full,
+}
+
+fn default_enable_hole_punching() -> bool {
+	true
+}
+
+/// A relay (or rendezvous) server which can be used to establish connections between peers on different networks.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+pub struct RelayConfig {
+	/// The libp2p `PeerId` of the relay server
+	pub peer_id: String,
+	/// The QUIC address the relay server is listening on
+	pub address: SocketAddr,
+}
+
+impl RelayConfig {
+	/// The address of the relay server itself
+	pub(crate) fn relay_multiaddr(&self) -> Result<(PeerId, Multiaddr), String> {
+		let peer_id = PeerId::from_str(&self.peer_id)
+			.map_err(|err| format!("invalid relay peer id '{}': {err}", self.peer_id))?;
+
+		Ok((
+			peer_id,
+			socketaddr_to_quic_multiaddr(&self.address).with(Protocol::P2p(peer_id)),
+		))
+	}
+}
+
+/// Information about an active connection to a peer
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct PeerConnection {
+	pub identity: RemoteIdentity,
+	/// Is the connection going through a relay server or is it a direct connection
+	pub relayed: bool,
+	/// The last measured round trip time. `None` if it hasn't been measured yet.
+	pub rtt_ms: Option<u32>,
+}
+
 #[derive(Serialize, Debug, Type)]
 pub struct P2PStatus {
 	ipv4: ListenerStatus,
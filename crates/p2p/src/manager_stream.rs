@@ -10,6 +10,7 @@ use std::{
 
 use libp2p::{
 	futures::StreamExt,
+	multiaddr::Protocol,
 	swarm::{
 		dial_opts::{DialOpts, PeerCondition},
 		NotifyHandler, SwarmEvent, ToSwarm,
@@ -21,9 +22,10 @@ use tracing::{debug, error, info, trace, warn};
 
 use crate::{
 	quic_multiaddr_to_socketaddr, socketaddr_to_quic_multiaddr,
-	spacetime::{OutboundRequest, SpaceTime, UnicastStreamBuilder},
+	spacetime::{OutboundRequest, UnicastStreamBuilder},
 	spacetunnel::RemoteIdentity,
-	DiscoveryManager, DynamicManagerState, Event, Manager, ManagerConfig, Mdns,
+	DiscoveryManager, DynamicManagerState, Event, Manager, ManagerConfig, Mdns, SwarmBehaviour,
+	SwarmBehaviourEvent,
 };
 
 /// TODO
@@ -37,6 +39,8 @@ pub enum ManagerStreamAction {
 		peer_id: PeerId,
 		addresses: Vec<SocketAddr>,
 	},
+	/// Tell the [`libp2p::Swarm`](libp2p::Swarm) to establish a connection to a peer through each of the configured relays.
+	DialRelayed(RemoteIdentity),
 	/// Update the config. This requires the `libp2p::Swarm`
 	UpdateConfig(ManagerConfig),
 	/// the node is shutting down. The `ManagerStream` should convert this into `Event::Shutdown`
@@ -79,7 +83,7 @@ pub struct ManagerStream {
 	pub(crate) manager: Arc<Manager>,
 	pub(crate) event_stream_rx: mpsc::Receiver<ManagerStreamAction>,
 	pub(crate) event_stream_rx2: mpsc::Receiver<ManagerStreamAction2>,
-	pub(crate) swarm: Swarm<SpaceTime>,
+	pub(crate) swarm: Swarm<SwarmBehaviour>,
 	pub(crate) discovery_manager: DiscoveryManager,
 	pub(crate) queued_events: VecDeque<Event>,
 	pub(crate) shutdown: AtomicBool,
@@ -89,7 +93,10 @@ pub struct ManagerStream {
 impl ManagerStream {
 	/// Setup the libp2p listeners based on the manager config.
 	/// This method will take care of removing old listeners if needed
-	pub(crate) fn refresh_listeners(swarm: &mut Swarm<SpaceTime>, state: &mut DynamicManagerState) {
+	pub(crate) fn refresh_listeners(
+		swarm: &mut Swarm<SwarmBehaviour>,
+		state: &mut DynamicManagerState,
+	) {
 		if state.config.enabled {
 			let port = state.config.port.unwrap_or(0);
 
@@ -140,6 +147,61 @@ impl ManagerStream {
 			}
 		}
 	}
+
+	/// Make a reservation with each of the configured relay servers so other peers can reach us through them.
+	/// This method will take care of removing reservations with relays which are no longer configured.
+	pub(crate) fn refresh_relays(
+		swarm: &mut Swarm<SwarmBehaviour>,
+		state: &mut DynamicManagerState,
+	) {
+		let relays = if state.config.relays_enabled() {
+			state
+				.config
+				.relays
+				.iter()
+				.filter_map(|relay| {
+					relay
+						.relay_multiaddr()
+						.map_err(|err| warn!("skipping relay {relay:?}: {err}"))
+						.ok()
+				})
+				.collect::<HashMap<_, _>>()
+		} else {
+			HashMap::new()
+		};
+
+		state.relay_listener_ids.retain(|peer_id, listener_id| {
+			if relays.contains_key(peer_id) && listener_id.is_ok() {
+				return true;
+			}
+
+			if let Ok(listener_id) = listener_id {
+				debug!("removing relay listener for '{peer_id}' with id '{listener_id:?}'");
+				swarm.remove_listener(*listener_id);
+			}
+			false
+		});
+
+		for (peer_id, addr) in relays {
+			if state.relay_listener_ids.contains_key(&peer_id) {
+				continue;
+			}
+
+			state.relay_listener_ids.insert(
+				peer_id,
+				swarm
+					.listen_on(addr.with(Protocol::P2pCircuit))
+					.map(|id| {
+						debug!("registered relay listener for '{peer_id}': {id:?}");
+						id
+					})
+					.map_err(|err| {
+						error!("failed to register relay listener for '{peer_id}': {err}");
+						err.to_string()
+					}),
+			);
+		}
+	}
 }
 
 enum EitherManagerStreamAction {
@@ -191,7 +253,7 @@ impl ManagerStream {
 				}
 				event = self.swarm.select_next_some() => {
 					match event {
-						SwarmEvent::Behaviour(event) => {
+						SwarmEvent::Behaviour(SwarmBehaviourEvent::Spacetime(event)) => {
 							if let Some(event) = self.handle_manager_stream_action(event.into()).await {
 								if let Event::Shutdown { .. } = event {
 									self.shutdown.store(true, Ordering::Relaxed);
@@ -200,11 +262,25 @@ impl ManagerStream {
 								return Some(event);
 							}
 						},
+						SwarmEvent::Behaviour(SwarmBehaviourEvent::Relay(event)) => debug!("relay event: {event:?}"),
+						SwarmEvent::Behaviour(SwarmBehaviourEvent::Dcutr(event)) => match event.result {
+							Ok(_) => debug!("hole punched direct connection to peer '{}'", event.remote_peer_id),
+							Err(err) => debug!("failed to hole punch connection to peer '{}': {err}", event.remote_peer_id),
+						},
+						SwarmEvent::Behaviour(SwarmBehaviourEvent::Ping(event)) => {
+							if let Ok(rtt) = event.result {
+								self.manager.state.write()
+									.unwrap_or_else(PoisonError::into_inner)
+									.rtt
+									.insert(event.peer, rtt);
+							}
+						},
 						SwarmEvent::ConnectionEstablished { peer_id, .. } => {
 							if let Some(streams) = self.on_establish_streams.remove(&peer_id) {
 								for event in streams {
 									self.swarm
 										.behaviour_mut()
+										.spacetime
 										.pending_events
 										.push_back(ToSwarm::NotifyHandler {
 											peer_id,
@@ -218,6 +294,7 @@ impl ManagerStream {
 							if num_established == 0 {
 							let mut state = self.manager.state.write()
 								.unwrap_or_else(PoisonError::into_inner);
+								state.rtt.remove(&peer_id);
 								if state
 									.connected
 									.remove(&peer_id).is_none() || state.connections.remove(&peer_id).is_none() {
@@ -281,6 +358,12 @@ impl ManagerStream {
 						}
 						SwarmEvent::ListenerClosed { listener_id, addresses, reason } => {
 							trace!("listener '{:?}' was closed due to: {:?}", listener_id, reason);
+							// If this was a relay reservation we forget it so it will be recreated on the next config refresh
+							self.manager.state.write()
+								.unwrap_or_else(PoisonError::into_inner)
+								.relay_listener_ids
+								.retain(|_, id| id.as_ref().ok() != Some(&listener_id));
+
 							for address in addresses {
 								match quic_multiaddr_to_socketaddr(address) {
 									Ok(addr) => {
@@ -355,6 +438,51 @@ impl ManagerStream {
 						),
 					}
 				}
+				ManagerStreamAction::DialRelayed(identity) => {
+					let peer_id = match identity.to_peer_id() {
+						Ok(peer_id) => peer_id,
+						Err(err) => {
+							warn!("error converting '{identity}' into a 'PeerId': {err}");
+							return None;
+						}
+					};
+
+					if self.swarm.is_connected(&peer_id) {
+						return None;
+					}
+
+					let addresses = {
+						let state = self
+							.manager
+							.state
+							.read()
+							.unwrap_or_else(PoisonError::into_inner);
+						if !state.config.relays_enabled() {
+							return None;
+						}
+
+						state
+							.config
+							.relays
+							.iter()
+							.filter_map(|relay| relay.relay_multiaddr().ok())
+							.map(|(_, addr)| addr.with(Protocol::P2pCircuit))
+							.collect::<Vec<_>>()
+					};
+					if addresses.is_empty() {
+						return None;
+					}
+
+					match self.swarm.dial(
+						DialOpts::peer_id(peer_id)
+							.condition(PeerCondition::DisconnectedAndNotDialing)
+							.addresses(addresses)
+							.build(),
+					) {
+						Ok(()) => debug!("dialing peer '{identity}' via relay"),
+						Err(err) => warn!("error dialing peer '{identity}' via relay: {err}"),
+					}
+				}
 				ManagerStreamAction::UpdateConfig(config) => {
 					let mut state = self
 						.manager
@@ -362,10 +490,23 @@ impl ManagerStream {
 						.write()
 						.unwrap_or_else(PoisonError::into_inner);
 
+					// The hole punching behaviour can't be swapped out under the swarm's open connections
+					if self.swarm.behaviour().dcutr.is_enabled() != config.enable_hole_punching {
+						info!(
+							"hole punching will be {} after a restart",
+							if config.enable_hole_punching {
+								"enabled"
+							} else {
+								"disabled"
+							}
+						);
+					}
+
 					state.config = config;
 					Self::refresh_listeners(&mut self.swarm, &mut state);
+					Self::refresh_relays(&mut self.swarm, &mut state);
 
-					if !state.config.enabled {
+					if !state.config.mdns_enabled() {
 						if let Some(mdns) = self.discovery_manager.mdns.take() {
 							drop(state);
 							mdns.shutdown();
@@ -452,13 +593,15 @@ impl ManagerStream {
 							.or_default()
 							.push(OutboundRequest::Unicast(tx));
 					} else {
-						self.swarm.behaviour_mut().pending_events.push_back(
-							ToSwarm::NotifyHandler {
+						self.swarm
+							.behaviour_mut()
+							.spacetime
+							.pending_events
+							.push_back(ToSwarm::NotifyHandler {
 								peer_id,
 								handler: NotifyHandler::Any,
 								event: OutboundRequest::Unicast(tx),
-							},
-						);
+							});
 					}
 				}
 			},
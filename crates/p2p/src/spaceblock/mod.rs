@@ -130,6 +130,7 @@ where
 					"File sending has stopped but it doesn't match the expected length!"
 				);
 
+				self.i += 1;
 				return Ok(());
 			}
 
@@ -156,7 +157,10 @@ where
 					return Ok(());
 				}
 				// Transfer complete
-				2 => return Ok(()),
+				2 => {
+					self.i += 1;
+					return Ok(());
+				}
 				_ => todo!(),
 			}
 		}
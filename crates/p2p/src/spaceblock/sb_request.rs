@@ -69,9 +69,27 @@ pub enum SpaceblockRequestsError {
 	BlockSize(std::io::Error),
 }
 
+/// The most requests [`SpaceblockRequests::to_bytes`] can encode, as it's what peers from before
+/// [`SpaceblockRequests::to_bytes_v2`] existed understand.
+pub const MAX_REQUESTS_V1: usize = u8::MAX as usize;
+
 impl SpaceblockRequests {
 	pub async fn from_stream(
 		stream: &mut (impl AsyncRead + Unpin),
+	) -> Result<Self, SpaceblockRequestsError> {
+		Self::from_stream_inner(stream, false).await
+	}
+
+	/// Read requests encoded with [`Self::to_bytes_v2`].
+	pub async fn from_stream_v2(
+		stream: &mut (impl AsyncRead + Unpin),
+	) -> Result<Self, SpaceblockRequestsError> {
+		Self::from_stream_inner(stream, true).await
+	}
+
+	async fn from_stream_inner(
+		stream: &mut (impl AsyncRead + Unpin),
+		v2: bool,
 	) -> Result<Self, SpaceblockRequestsError> {
 		let id = decode::uuid(stream)
 			.await
@@ -81,11 +99,13 @@ impl SpaceblockRequests {
 			.await
 			.map_err(SpaceblockRequestsError::BlockSize)?;
 
-		let size = stream
+		let size = if v2 {
+			stream.read_u32_le().await
+		} else {
 			// Max of 255 files in one request
-			.read_u8()
-			.await
-			.map_err(SpaceblockRequestsError::InvalidLen)?;
+			stream.read_u8().await.map(u32::from)
+		}
+		.map_err(SpaceblockRequestsError::InvalidLen)?;
 
 		let mut requests = Vec::new();
 		for i in 0..size {
@@ -101,21 +121,43 @@ impl SpaceblockRequests {
 
 	#[must_use]
 	pub fn to_bytes(&self) -> Vec<u8> {
+		#[allow(clippy::panic)] // TODO: Remove this panic
+		assert!(
+			self.requests.len() <= MAX_REQUESTS_V1,
+			"Can't Spacedrop more than 255 files at once!"
+		);
+
+		self.to_bytes_inner(false)
+	}
+
+	/// Like [`Self::to_bytes`] but with the number of requests as a `u32`, so more than 255 files
+	/// can be sent at once. Only peers which understand the header it's sent in can read it.
+	#[must_use]
+	pub fn to_bytes_v2(&self) -> Vec<u8> {
+		#[allow(clippy::panic)] // TODO: Remove this panic
+		assert!(
+			u32::try_from(self.requests.len()).is_ok(),
+			"Can't Spacedrop more than u32::MAX files at once!"
+		);
+
+		self.to_bytes_inner(true)
+	}
+
+	fn to_bytes_inner(&self, v2: bool) -> Vec<u8> {
 		let Self {
 			id,
 			block_size,
 			requests,
 		} = self;
-		#[allow(clippy::panic)] // TODO: Remove this panic
-		assert!(
-			requests.len() <= 255,
-			"Can't Spacedrop more than 255 files at once!"
-		);
 
 		let mut buf = vec![];
 		encode::uuid(&mut buf, id);
 		buf.append(&mut block_size.to_bytes().to_vec());
-		buf.push(requests.len() as u8);
+		if v2 {
+			buf.extend_from_slice(&(requests.len() as u32).to_le_bytes());
+		} else {
+			buf.push(requests.len() as u8);
+		}
 		for request in requests {
 			buf.extend_from_slice(&request.to_bytes());
 		}
@@ -267,4 +309,37 @@ mod tests {
 			.unwrap();
 		assert_eq!(req, req2);
 	}
+
+	#[tokio::test]
+	async fn test_spaceblock_requests_more_than_255() {
+		let req = SpaceblockRequests {
+			id: Uuid::new_v4(),
+			block_size: BlockSize::from_size(42069),
+			requests: (0..300)
+				.map(|i| SpaceblockRequest {
+					name: format!("Demo/{i}"),
+					size: i,
+					range: Range::Full,
+				})
+				.collect(),
+		};
+
+		let bytes = req.to_bytes_v2();
+		let req2 = SpaceblockRequests::from_stream_v2(&mut Cursor::new(bytes))
+			.await
+			.unwrap();
+		assert_eq!(req, req2);
+	}
+
+	#[tokio::test]
+	async fn test_spaceblock_requests_v1_len_is_one_byte() {
+		let req = SpaceblockRequests {
+			id: Uuid::new_v4(),
+			block_size: BlockSize::from_size(42069),
+			requests: vec![],
+		};
+
+		// Peers from before `to_bytes_v2` expect the number of requests in a single byte
+		assert_eq!(req.to_bytes().len() + 3, req.to_bytes_v2().len());
+	}
 }
@@ -4,12 +4,12 @@ use std::{
 };
 
 use base64::{engine::general_purpose, Engine};
-use ed25519_dalek::{VerifyingKey, SECRET_KEY_LENGTH};
+use ed25519_dalek::{Signer, VerifyingKey, SECRET_KEY_LENGTH, SIGNATURE_LENGTH};
 use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use thiserror::Error;
-use zeroize::ZeroizeOnDrop;
+use zeroize::{ZeroizeOnDrop, Zeroizing};
 
 pub const REMOTE_IDENTITY_LEN: usize = 32;
 
@@ -61,6 +61,21 @@ impl Identity {
 	pub fn to_remote_identity(&self) -> RemoteIdentity {
 		RemoteIdentity(self.0.verifying_key())
 	}
+
+	/// Signs `message`, so anyone knowing our [`RemoteIdentity`] can check it came from us.
+	#[must_use]
+	pub fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_LENGTH] {
+		self.0.sign(message).to_bytes()
+	}
+
+	/// A secret shared with the remote peer, from an X25519 key agreement using both ed25519 keys.
+	///
+	/// The remote peer gets the same secret by calling this with our identity, so it can be used to derive keys which only the two peers know.
+	/// It's the same on every call, so a unique salt should be used when deriving keys from it.
+	#[must_use]
+	pub fn shared_secret(&self, remote: &RemoteIdentity) -> Zeroizing<[u8; 32]> {
+		Zeroizing::new((self.0.to_scalar() * remote.0.to_montgomery()).to_bytes())
+	}
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Type)]
@@ -161,6 +176,22 @@ impl RemoteIdentity {
 	pub fn verifying_key(&self) -> VerifyingKey {
 		self.0
 	}
+
+	/// Whether `signature` is the [`Identity::sign`] of `message` by this identity.
+	#[must_use]
+	pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+		ed25519_dalek::Signature::from_slice(signature).map_or(false, |signature| {
+			self.0.verify_strict(message, &signature).is_ok()
+		})
+	}
+
+	// This depends on libp2p deriving the `PeerId` from the ed25519 public key, the same as `Keypair::peer_id`
+	pub(crate) fn to_peer_id(self) -> Result<libp2p::PeerId, libp2p::identity::DecodingError> {
+		let pk: libp2p::identity::PublicKey =
+			libp2p::identity::ed25519::PublicKey::try_from_bytes(self.0.as_bytes())?.into();
+
+		Ok(libp2p::PeerId::from_public_key(&pk))
+	}
 }
 
 impl From<ed25519_dalek::SigningKey> for Identity {
@@ -213,3 +244,31 @@ impl IdentityOrRemoteIdentity {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_shared_secret() {
+		let a = Identity::new();
+		let b = Identity::new();
+		let c = Identity::new();
+
+		let secret = a.shared_secret(&b.to_remote_identity());
+		assert_eq!(secret, b.shared_secret(&a.to_remote_identity()));
+		assert_ne!(secret, a.shared_secret(&c.to_remote_identity()));
+	}
+
+	#[test]
+	fn test_sign() {
+		let a = Identity::new();
+		let b = Identity::new();
+
+		let signature = a.sign(b"message");
+		assert!(a.to_remote_identity().verify(b"message", &signature));
+		assert!(!a.to_remote_identity().verify(b"other message", &signature));
+		assert!(!b.to_remote_identity().verify(b"message", &signature));
+		assert!(!a.to_remote_identity().verify(b"message", &signature[1..]));
+	}
+}
@@ -112,9 +112,11 @@ pub fn r#enum(models: Vec<ModelWithSyncType>) -> TokenStream {
 				};
 
 				quote! {
+					// Either of them may have never been synced, like the ones in locations
+					// excluded from sync
 					let (Some(item), Some(group)) =
 						db._batch((#(#db_batch_items),*)).await? else {
-							panic!("item and group not found!");
+							return Ok(());
 					};
 
 					let id = prisma::tag_on_object::#compound_id(item.id, group.id);
@@ -1,4 +1,6 @@
-use prisma_client_rust::{migrations::*, NewClientError};
+use std::path::{Path, PathBuf};
+
+use prisma_client_rust::{migrations::*, raw, NewClientError, QueryError};
 use sd_prisma::prisma::{self, PrismaClient};
 use thiserror::Error;
 
@@ -13,6 +15,8 @@ pub enum MigrationError {
 	#[cfg(not(debug_assertions))]
 	#[error("An error occurred during migration: {0}")]
 	MigrateFailed(#[from] MigrateDeployError),
+	#[error("An error occurred while enabling WAL mode: {0}")]
+	EnableWal(#[from] QueryError),
 }
 
 /// load_and_migrate will load the database from the given path and migrate it to the latest version of the schema.
@@ -54,9 +58,50 @@ pub async fn load_and_migrate(db_url: &str) -> Result<PrismaClient, MigrationErr
 	#[cfg(not(debug_assertions))]
 	client._migrate_deploy().await?;
 
+	enable_wal(&client).await?;
+
 	Ok(client)
 }
 
+/// Switch the database to WAL mode, which lets readers carry on while another connection writes.
+/// This is needed for connection limits above 1 to not constantly fail with "database is locked".
+///
+/// The journal mode is persisted in the database file, so this is a no-op after the first time.
+/// WAL keeps `-wal` and `-shm` files next to the database (see [`wal_files`]), which must be
+/// moved with it, and doesn't work on network filesystems.
+async fn enable_wal(client: &PrismaClient) -> Result<(), QueryError> {
+	let journal_mode = client
+		._query_raw::<serde_json::Value>(raw!("PRAGMA journal_mode"))
+		.exec()
+		.await?;
+
+	let is_wal = journal_mode
+		.first()
+		.and_then(|row| row.get("journal_mode"))
+		.and_then(serde_json::Value::as_str)
+		.map(|mode| mode.eq_ignore_ascii_case("wal"))
+		.unwrap_or(false);
+
+	if !is_wal {
+		client
+			._query_raw::<serde_json::Value>(raw!("PRAGMA journal_mode = WAL"))
+			.exec()
+			.await?;
+	}
+
+	Ok(())
+}
+
+/// The `-wal` and `-shm` files SQLite keeps next to the database at `db_path` in WAL mode.
+/// Left behind, they would be applied to whatever database is later put at that path.
+pub fn wal_files(db_path: impl AsRef<Path>) -> [PathBuf; 2] {
+	["-wal", "-shm"].map(|suffix| {
+		let mut path = db_path.as_ref().as_os_str().to_owned();
+		path.push(suffix);
+		PathBuf::from(path)
+	})
+}
+
 pub fn inode_from_db(db_inode: &[u8]) -> u64 {
 	u64::from_le_bytes(db_inode.try_into().expect("corrupted inode in database"))
 }
@@ -247,7 +247,10 @@ export const SingleItemMetadata = ({ item }: { item: ExplorerItem }) => {
 		}
 	);
 
-	const mediaData = filesMediaData ?? ephemeralLocationMediaData ?? null;
+	const mediaData =
+		filesMediaData.data?.status === 'Ready'
+			? filesMediaData.data.data
+			: ephemeralLocationMediaData.data ?? null;
 
 	const fullPath = queriedFullPath.data ?? ephemeralPathData?.path;
 
@@ -344,7 +347,7 @@ export const SingleItemMetadata = ({ item }: { item: ExplorerItem }) => {
 				</MetaContainer>
 			)}
 
-			{mediaData.data && <MediaData data={mediaData.data} />}
+			{mediaData && <MediaData data={mediaData} />}
 
 			<MetaContainer className="flex !flex-row flex-wrap gap-1 overflow-hidden">
 				<InfoPill>{isDir ? 'Folder' : kind}</InfoPill>
@@ -53,7 +53,7 @@ export default (props: PropsWithChildren) => {
 			console.error(e);
 		},
 		onSuccess: (folder) => {
-			toast.success({ title: `Created new folder "${folder}"` });
+			toast.success({ title: `Created new folder "${folder.name}"` });
 			rescan();
 		}
 	});
@@ -44,7 +44,7 @@ export const uniqueId = (item: ExplorerItem | { pub_id: number[] }) => {
 		case 'NonIndexedPath':
 			return item.item.path;
 		case 'SpacedropPeer':
-			return item.item.name;
+			return item.identity;
 		default:
 			return pubIdToString(item.item.pub_id);
 	}
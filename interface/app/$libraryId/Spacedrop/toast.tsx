@@ -37,7 +37,7 @@ export function useIncomingSpacedropToast() {
 			{
 				duration: 30 * 1000,
 				onClose: ({ event }) => {
-					event !== 'on-action' && acceptSpacedrop.mutate([data.id, null]);
+					event !== 'on-action' && acceptSpacedrop.mutate([data.id, null, null]);
 				},
 				action: {
 					label: 'Accept',
@@ -69,7 +69,7 @@ export function useIncomingSpacedropToast() {
 						}
 
 						if (destinationFilePath === '') return;
-						await acceptSpacedrop.mutateAsync([data.id, destinationFilePath]);
+						await acceptSpacedrop.mutateAsync([data.id, destinationFilePath, null]);
 					}
 				},
 				cancel: 'Reject'
@@ -1,20 +1,21 @@
-import { inferSubscriptionResult } from '@oscartbeaumont-sd/rspc-client';
-import { useMemo, useState } from 'react';
-import { Procedures, useLibraryMutation, useLibrarySubscription } from '@sd/client';
+import dayjs from 'dayjs';
+import { useState } from 'react';
+import { ActorEvent, ActorState, useLibraryMutation, useLibrarySubscription } from '@sd/client';
 import { Button } from '@sd/ui';
 import { useRouteTitle } from '~/hooks/useRouteTitle';
 
 export const Component = () => {
 	useRouteTitle('Actors');
 
-	const [data, setData] = useState<inferSubscriptionResult<Procedures, 'library.actors'>>({});
+	const [data, setData] = useState<ActorState[]>([]);
 
 	useLibrarySubscription(['library.actors'], { onData: setData });
 
-	const sortedData = useMemo(() => {
-		const sorted = Object.entries(data).sort(([a], [b]) => a.localeCompare(b));
-		return sorted;
-	}, [data]);
+	const [events, setEvents] = useState<ActorEvent[]>([]);
+
+	useLibrarySubscription(['actors.events'], {
+		onData: (event) => setEvents((events) => [event, ...events].slice(0, 50))
+	});
 
 	return (
 		<div className="h-full w-full">
@@ -22,19 +23,52 @@ export const Component = () => {
 				<tr>
 					<th>Name</th>
 					<th>Running</th>
+					<th>Uptime</th>
+					<th>Last Activity</th>
+					<th>Restarts</th>
+					<th>Crashes</th>
+					<th>Last Error</th>
 				</tr>
-				{sortedData.map(([name, running]) => (
-					<tr key={name}>
-						<td className="pl-2 pr-4 text-left">{name}</td>
-						<td className="pl-2 pr-4 text-left">
-							{running ? 'Running' : 'Not Running'}
-						</td>
-						<td className="py-1">
-							{running ? <StopButton name={name} /> : <StartButton name={name} />}
-						</td>
-					</tr>
-				))}
+				{data.map(
+					({
+						name,
+						running,
+						started_at,
+						last_activity,
+						restart_count,
+						crash_count,
+						last_error
+					}) => (
+						<tr key={name}>
+							<td className="pl-2 pr-4 text-left">{name}</td>
+							<td className="pl-2 pr-4 text-left">
+								{running ? 'Running' : 'Not Running'}
+							</td>
+							<td className="pl-2 pr-4 text-left">
+								{started_at ? dayjs(started_at).fromNow(true) : '-'}
+							</td>
+							<td className="pl-2 pr-4 text-left">
+								{last_activity ? dayjs(last_activity).fromNow() : '-'}
+							</td>
+							<td className="pl-2 pr-4 text-left">{restart_count}</td>
+							<td className="pl-2 pr-4 text-left">{crash_count}</td>
+							<td className="pl-2 pr-4 text-left">{last_error ?? '-'}</td>
+							<td className="py-1">
+								{running ? <StopButton name={name} /> : <StartButton name={name} />}
+							</td>
+						</tr>
+					)
+				)}
 			</table>
+			<h2 className="mt-4 pl-2 font-bold">Events</h2>
+			<ul className="pl-2">
+				{events.map((event, i) => (
+					<li key={i}>
+						{event.name}: {event.type}
+						{event.type === 'Crashed' && ` (${event.error})`}
+					</li>
+				))}
+			</ul>
 		</div>
 	);
 };
@@ -16,7 +16,7 @@ import { TopBarPortal } from './TopBar/Portal';
 export function Component() {
 	useRouteTitle('Labels');
 
-	const labels = useLibraryQuery(['labels.listWithThumbnails', '']);
+	const labels = useLibraryQuery(['labels.listWithThumbnails', { cursor: '' }]);
 
 	const explorerSettings = useExplorerSettings({
 		settings: useMemo(() => {
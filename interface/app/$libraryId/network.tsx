@@ -17,7 +17,7 @@ export const Component = () => {
 	const { t } = useLocale();
 
 	const discoveredPeers = useDiscoveredPeers();
-	const peers = useMemo(() => Array.from(discoveredPeers.values()), [discoveredPeers]);
+	const peers = useMemo(() => Array.from(discoveredPeers.entries()), [discoveredPeers]);
 
 	const explorerSettings = useExplorerSettings({
 		settings: useMemo(
@@ -34,8 +34,9 @@ export const Component = () => {
 	});
 
 	const explorer = useExplorer({
-		items: peers.map((peer) => ({
+		items: peers.map(([identity, peer]) => ({
 			type: 'SpacedropPeer' as const,
+			identity,
 			has_local_thumbnail: false,
 			thumbnail: null,
 			item: {
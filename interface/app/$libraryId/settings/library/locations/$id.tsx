@@ -34,6 +34,7 @@ const schema = z.object({
 	name: z.string().min(1).nullable(),
 	path: z.string().min(1).nullable(),
 	hidden: z.boolean().nullable(),
+	indexHidden: z.boolean().nullable(),
 	indexerRulesIds: z.array(z.number()),
 	locationType: z.string(),
 	syncPreviewMedia: z.boolean().nullable(),
@@ -52,6 +53,7 @@ const EditLocationForm = () => {
 	const { id: locationId } = useZodRouteParams(LocationIdParamsSchema);
 	const navigate = useNavigate();
 	const fullRescan = useLibraryMutation('locations.fullRescan');
+	const pruneHidden = useLibraryMutation('locations.pruneHidden');
 	const queryClient = useQueryClient();
 
 	const locationDataQuery = useLibraryQuery(['locations.getWithRules', locationId], {
@@ -68,8 +70,9 @@ const EditLocationForm = () => {
 			name: locationData?.name ?? '',
 			path: locationData?.path ?? '',
 			hidden: locationData?.hidden ?? false,
+			indexHidden: locationData?.index_hidden ?? true,
 			syncPreviewMedia: locationData?.sync_preview_media ?? false,
-			generatePreviewMedia: locationData?.generate_preview_media ?? false
+			generatePreviewMedia: locationData?.generate_preview_media ?? true
 		}
 	});
 
@@ -83,17 +86,37 @@ const EditLocationForm = () => {
 		}
 	});
 
-	const onSubmit = form.handleSubmit((data) =>
-		updateLocation.mutateAsync({
+	const onSubmit = form.handleSubmit(async (data) => {
+		const stoppedIndexingHidden = form.formState.defaultValues?.indexHidden && !data.indexHidden;
+
+		await updateLocation.mutateAsync({
 			id: locationId,
 			path: data.path,
 			name: data.name,
 			hidden: data.hidden,
+			index_hidden: data.indexHidden,
 			indexer_rules_ids: data.indexerRulesIds,
 			sync_preview_media: data.syncPreviewMedia,
 			generate_preview_media: data.generatePreviewMedia
-		})
-	);
+		});
+
+		// Hidden files which were already indexed are kept until they're pruned
+		if (stoppedIndexingHidden)
+			toast.info(
+				{
+					title: t('prune_hidden_files_title'),
+					body: t('prune_hidden_files_description')
+				},
+				{
+					duration: Infinity,
+					action: {
+						label: t('prune'),
+						onClick: () => pruneHidden.mutate(locationId)
+					},
+					cancel: t('keep')
+				}
+			);
+	});
 
 	const { t } = useLocale();
 
@@ -171,6 +194,15 @@ const EditLocationForm = () => {
 						</Label>
 						<SwitchField {...form.register('hidden')} size="sm" />
 					</ToggleSection>
+					<ToggleSection>
+						<Label className="grow">
+							{t('index_hidden_files')}{' '}
+							<Tooltip label={t('index_hidden_files_label')}>
+								<Info className="inline" />
+							</Tooltip>
+						</Label>
+						<SwitchField {...form.register('indexHidden')} size="sm" />
+					</ToggleSection>
 				</div>
 				<Divider />
 				<Controller
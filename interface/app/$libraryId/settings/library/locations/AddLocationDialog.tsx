@@ -3,6 +3,8 @@ import { Controller, get } from 'react-hook-form';
 import { useDebouncedCallback } from 'use-debounce';
 import {
 	extractInfoRSPCError,
+	extractLocationCreateError,
+	LocationCreateError,
 	UnionToTuple,
 	useCache,
 	useLibraryMutation,
@@ -44,6 +46,21 @@ type RemoteErrorFormMessage = keyof typeof REMOTE_ERROR_FORM_MESSAGE;
 const isRemoteErrorFormMessage = (message: unknown): message is RemoteErrorFormMessage =>
 	typeof message === 'string' && Object.hasOwnProperty.call(REMOTE_ERROR_FORM_MESSAGE, message);
 
+const describeLocationCreateError = (error: LocationCreateError): string => {
+	switch (error.code) {
+		case 'ADD_LIBRARY':
+			return 'ADD_LIBRARY';
+		case 'PATH_NOT_FOUND':
+			return 'This folder does not exist';
+		case 'OVERLAPPING':
+			return 'This folder is inside another location, or contains one';
+		case 'PERMISSION_DENIED':
+			return 'Spacedrive does not have permission to read this folder';
+		case 'READ_ONLY':
+			return 'This folder is read only, Spacedrive needs to write a .spacedrive file in it';
+	}
+};
+
 const schema = z.object({
 	path: z.string().min(1),
 	method: z.enum(Object.keys(REMOTE_ERROR_FORM_MESSAGE) as UnionToTuple<RemoteErrorFormMessage>),
@@ -99,16 +116,37 @@ export const AddLocationDialog = ({
 			let id = null;
 
 			switch (method) {
-				case 'CREATE':
-					id = await createLocation.mutateAsync({
+				case 'CREATE': {
+					const result = await createLocation.mutateAsync({
 						path,
 						dry_run: dryRun,
-						indexer_rules_ids: indexerRulesIds
+						indexer_rules_ids: indexerRulesIds,
+						interactive: true
 					});
 
+					if (result.type === 'DryRun') {
+						if (result.other_libraries.length > 0) {
+							form.setValue('method', 'ADD_LIBRARY');
+							form.setError(REMOTE_ERROR_FORM_FIELD, {
+								type: 'remote',
+								message: REMOTE_ERROR_FORM_MESSAGE.ADD_LIBRARY
+							});
+						} else if (result.overlapping_locations.length > 0) {
+							form.setError(REMOTE_ERROR_FORM_FIELD, {
+								type: 'remote',
+								message: 'Nested locations are currently not supported'
+							});
+						}
+
+						return;
+					}
+
+					id = result.id;
+
 					submitPlausibleEvent({ event: { type: 'locationCreate' } });
 
 					break;
+				}
 				case 'NEED_RELINK':
 					if (!dryRun) id = await relinkLocation.mutateAsync(path);
 					// TODO: Update relinked location with new indexer rules, don't have a way to get location id yet though
@@ -138,7 +176,7 @@ export const AddLocationDialog = ({
 
 			if (shouldRedirect) explorerStore.newLocationToRedirect = id;
 		},
-		[createLocation, relinkLocation, addLocationToLibrary, submitPlausibleEvent]
+		[form, createLocation, relinkLocation, addLocationToLibrary, submitPlausibleEvent]
 	);
 
 	const handleAddError = useCallback(
@@ -146,7 +184,8 @@ export const AddLocationDialog = ({
 			const rspcErrorInfo = extractInfoRSPCError(error);
 			if (!rspcErrorInfo || rspcErrorInfo.code === 500) return false;
 
-			let { message } = rspcErrorInfo;
+			const createError = extractLocationCreateError(error);
+			let message = createError ? describeLocationCreateError(createError) : rspcErrorInfo.message;
 			if (rspcErrorInfo.code == 409 && isRemoteErrorFormMessage(message)) {
 				/**
 				 * TODO: On NEED_RELINK, we should query the backend for
@@ -6,7 +6,7 @@ import { Controller, FormProvider, useFieldArray } from 'react-hook-form';
 import {
 	extractInfoRSPCError,
 	IndexerRuleCreateArgs,
-	RuleKind,
+	RuleParameters,
 	UnionToTuple,
 	useLibraryMutation,
 	useZodForm
@@ -17,11 +17,14 @@ import { useLocale } from '~/hooks';
 
 import { InputKinds, RuleInput, validateInput } from './RuleInput';
 
+type RuleKind = RuleParameters['kind'];
+
 const ruleKinds: UnionToTuple<RuleKind> = [
 	'AcceptFilesByGlob',
 	'RejectFilesByGlob',
 	'AcceptIfChildrenDirectoriesArePresent',
-	'RejectIfChildrenDirectoriesArePresent'
+	'RejectIfChildrenDirectoriesArePresent',
+	'AcceptIfAllOfRejectIfAnyOf'
 ];
 const ruleKindEnum = z.enum(ruleKinds);
 
@@ -103,12 +106,12 @@ const RulesForm = ({ onSubmitted }: Props) => {
 			rules: data.rules.map(({ type, value, kind }) => {
 				switch (type) {
 					case 'Name':
-						return [kind, [`**/${value}`]];
+						return { kind, globs: [`**/${value}`] };
 					case 'Extension':
 						// .tar should work for .tar.gz, .tar.bz2, etc.
-						return [kind, [`**/*${value}`, `**/*${value}.*`]];
+						return { kind, globs: [`**/*${value}`, `**/*${value}.*`] };
 					default:
-						return [kind, [value]];
+						return { kind, globs: [value] };
 				}
 			})
 		} as IndexerRuleCreateArgs;
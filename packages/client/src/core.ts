@@ -10,23 +10,36 @@ export type Procedures = {
         { key: "cloud.library.get", input: LibraryArgs<null>, result: { id: string; uuid: string; name: string; instances: CloudInstance[]; ownerId: string } | null } | 
         { key: "cloud.library.list", input: never, result: CloudLibrary[] } | 
         { key: "cloud.locations.list", input: never, result: CloudLocation[] } | 
+        { key: "cloud.pendingOperations", input: LibraryArgs<null>, result: PendingCloudOperation[] } | 
+        { key: "cloud.syncStatus", input: LibraryArgs<null>, result: CloudSyncStatus } | 
+        { key: "documents.pageThumbnail", input: LibraryArgs<DocumentPageThumbnailArgs>, result: string[] } | 
         { key: "ephemeralFiles.getMediaData", input: string, result: ({ type: "Image" } & ImageMetadata) | ({ type: "Video" } & VideoMetadata) | ({ type: "Audio" } & AudioMetadata) | null } | 
+        { key: "files.favorites", input: LibraryArgs<null>, result: NormalisedResults<ExplorerItem> } | 
         { key: "files.get", input: LibraryArgs<number>, result: { item: Reference<ObjectWithFilePaths2>; nodes: CacheNode[] } | null } | 
         { key: "files.getConvertableImageExtensions", input: never, result: string[] } | 
-        { key: "files.getMediaData", input: LibraryArgs<number>, result: MediaMetadata } | 
+        { key: "files.getMediaData", input: LibraryArgs<number>, result: MediaDataState } | 
+        { key: "files.getOpenWithApplications", input: LibraryArgs<string>, result: OpenWithApplication[] } | 
         { key: "files.getPath", input: LibraryArgs<number>, result: string | null } | 
+        { key: "files.groupedByDate", input: LibraryArgs<LocationMediaDateArgs>, result: MediaDateBucket[] } | 
+        { key: "files.inspect", input: FileInspectArgs, result: FileInspection } | 
+        { key: "files.integrityReport", input: LibraryArgs<number>, result: IntegrityReport } | 
+        { key: "files.recents", input: LibraryArgs<RecentsArgs>, result: NormalisedResults<ExplorerItem> } | 
+        { key: "files.textPreview", input: LibraryArgs<TextPreviewArgs>, result: TextPreview } | 
         { key: "invalidation.test-invalidate", input: never, result: number } | 
         { key: "jobs.isActive", input: LibraryArgs<null>, result: boolean } | 
         { key: "jobs.reports", input: LibraryArgs<null>, result: JobGroup[] } | 
         { key: "labels.count", input: LibraryArgs<null>, result: number } | 
         { key: "labels.get", input: LibraryArgs<number>, result: { id: number; pub_id: number[]; name: string; date_created: string; date_modified: string } | null } | 
         { key: "labels.getForObject", input: LibraryArgs<number>, result: Label[] } | 
-        { key: "labels.getWithObjects", input: LibraryArgs<number[]>, result: { [key in number]: { date_created: string; object: { id: number } }[] } } | 
+        { key: "labels.getWithObjects", input: LibraryArgs<number[]>, result: { [key in number]: { date_created: string; confidence: number | null; object: { id: number } }[] } } | 
         { key: "labels.list", input: LibraryArgs<null>, result: Label[] } | 
-        { key: "labels.listWithThumbnails", input: LibraryArgs<string>, result: ExplorerItem[] } | 
+        { key: "labels.listWithThumbnails", input: LibraryArgs<ListWithThumbnailsArgs>, result: ExplorerItem[] } | 
         { key: "library.kindStatistics", input: LibraryArgs<null>, result: KindStatistics } | 
+        { key: "library.kv.get", input: LibraryArgs<KeyValueArgs>, result: JsonValue | null } | 
+        { key: "library.kv.list", input: LibraryArgs<string>, result: KeyValueEntry[] } | 
         { key: "library.list", input: never, result: NormalisedResults<LibraryConfigWrapped> } | 
         { key: "library.statistics", input: LibraryArgs<null>, result: StatisticsResponse } | 
+        { key: "library.statisticsHistory", input: LibraryArgs<StatisticsHistoryArgs>, result: StatisticsHistory[] } | 
         { key: "locations.get", input: LibraryArgs<number>, result: { item: Reference<Location>; nodes: CacheNode[] } | null } | 
         { key: "locations.getWithRules", input: LibraryArgs<number>, result: { item: Reference<LocationWithIndexerRule>; nodes: CacheNode[] } | null } | 
         { key: "locations.indexer_rules.get", input: LibraryArgs<number>, result: NormalisedResult<IndexerRule> } | 
@@ -34,18 +47,31 @@ export type Procedures = {
         { key: "locations.indexer_rules.listForLocation", input: LibraryArgs<number>, result: NormalisedResults<IndexerRule> } | 
         { key: "locations.list", input: LibraryArgs<null>, result: NormalisedResults<Location> } | 
         { key: "locations.systemLocations", input: never, result: SystemLocations } | 
+        { key: "locations.volumeInfo", input: LibraryArgs<number>, result: LocationVolumeInfo } | 
         { key: "models.image_detection.list", input: never, result: string[] } | 
+        { key: "models.list", input: never, result: ImageLabelerModel[] } | 
         { key: "nodeState", input: never, result: NodeState } | 
+        { key: "nodes.cryptoDefaults", input: never, result: CryptoDefaults } | 
+        { key: "nodes.eventBusMetrics", input: never, result: EventBusMetrics } | 
         { key: "nodes.listLocations", input: LibraryArgs<string | null>, result: ExplorerItem[] } | 
+        { key: "nodes.networkUsage", input: NetworkUsageArgs, result: DailyNetworkUsage[] } | 
         { key: "notifications.dismiss", input: NotificationId, result: null } | 
         { key: "notifications.dismissAll", input: never, result: null } | 
         { key: "notifications.get", input: never, result: Notification[] } | 
+        { key: "notifications.list", input: NotificationsListArgs, result: NotificationsPage } | 
+        { key: "objects.favorites", input: LibraryArgs<null>, result: ExplorerItem[] } | 
+        { key: "p2p.connections", input: never, result: PeerConnection[] } | 
+        { key: "p2p.discoveryMode", input: never, result: DiscoveryMode } | 
         { key: "p2p.state", input: never, result: JsonValue } | 
         { key: "preferences.get", input: LibraryArgs<null>, result: LibraryPreferences } | 
+        { key: "search.explorerItemsByIds", input: LibraryArgs<ExplorerItemId[]>, result: ExplorerItemsByIds } | 
+        { key: "search.mediaByDate", input: LibraryArgs<MediaDateFilter>, result: MediaDateBucket[] } | 
+        { key: "search.mediaByDateBucket", input: LibraryArgs<MediaByDateBucketArgs>, result: MediaDateBucketItems } | 
         { key: "search.objects", input: LibraryArgs<ObjectSearchArgs>, result: SearchData<ExplorerItem> } | 
         { key: "search.objectsCount", input: LibraryArgs<{ filters?: SearchFilterArgs[] }>, result: number } | 
         { key: "search.paths", input: LibraryArgs<FilePathSearchArgs>, result: SearchData<ExplorerItem> } | 
         { key: "search.pathsCount", input: LibraryArgs<{ filters?: SearchFilterArgs[] }>, result: number } | 
+        { key: "search.pathsPage", input: LibraryArgs<FilePathPageArgs>, result: FilePathPage } | 
         { key: "search.saved.get", input: LibraryArgs<number>, result: { id: number; pub_id: number[]; search: string | null; filters: string | null; name: string | null; icon: string | null; description: string | null; date_created: string | null; date_modified: string | null } | null } | 
         { key: "search.saved.list", input: LibraryArgs<null>, result: SavedSearch[] } | 
         { key: "sync.messages", input: LibraryArgs<null>, result: CRDTOperation[] } | 
@@ -60,29 +86,43 @@ export type Procedures = {
         { key: "backups.backup", input: LibraryArgs<null>, result: string } | 
         { key: "backups.delete", input: string, result: null } | 
         { key: "backups.restore", input: string, result: null } | 
+        { key: "cloud.flushNow", input: LibraryArgs<null>, result: number } | 
         { key: "cloud.library.create", input: LibraryArgs<null>, result: null } | 
         { key: "cloud.library.join", input: string, result: LibraryConfigWrapped } | 
         { key: "cloud.library.sync", input: LibraryArgs<null>, result: null } | 
         { key: "cloud.locations.create", input: string, result: CloudLocation } | 
         { key: "cloud.locations.remove", input: string, result: CloudLocation } | 
         { key: "cloud.locations.testing", input: TestingParams, result: null } | 
+        { key: "cloud.resetSync", input: LibraryArgs<ResetSyncArgs>, result: null } | 
         { key: "cloud.setApiOrigin", input: string, result: null } | 
-        { key: "ephemeralFiles.copyFiles", input: LibraryArgs<EphemeralFileSystemOps>, result: null } | 
-        { key: "ephemeralFiles.createFolder", input: LibraryArgs<CreateEphemeralFolderArgs>, result: string } | 
-        { key: "ephemeralFiles.cutFiles", input: LibraryArgs<EphemeralFileSystemOps>, result: null } | 
+        { key: "ephemeralFiles.copyFiles", input: LibraryArgs<EphemeralFileSystemOps>, result: NonIndexedPathItem[] } | 
+        { key: "ephemeralFiles.createFolder", input: LibraryArgs<CreateEphemeralFolderArgs>, result: NonIndexedPathItem } | 
+        { key: "ephemeralFiles.cutFiles", input: LibraryArgs<EphemeralFileSystemOps>, result: NonIndexedPathItem[] } | 
         { key: "ephemeralFiles.deleteFiles", input: LibraryArgs<string[]>, result: null } | 
-        { key: "ephemeralFiles.renameFile", input: LibraryArgs<EphemeralRenameFileArgs>, result: null } | 
+        { key: "ephemeralFiles.moveToTrash", input: LibraryArgs<string[]>, result: null } | 
+        { key: "ephemeralFiles.renameFile", input: LibraryArgs<EphemeralRenameFileArgs>, result: NonIndexedPathItem[] } | 
+        { key: "files.clearCustomThumbnail", input: LibraryArgs<number>, result: null } | 
         { key: "files.convertImage", input: LibraryArgs<ConvertImageArgs>, result: null } | 
         { key: "files.copyFiles", input: LibraryArgs<FileCopierJobInit>, result: null } | 
         { key: "files.createFolder", input: LibraryArgs<CreateFolderArgs>, result: string } | 
         { key: "files.cutFiles", input: LibraryArgs<FileCutterJobInit>, result: null } | 
+        { key: "files.decrypt", input: LibraryArgs<FileDecryptorJobInit>, result: null } | 
         { key: "files.deleteFiles", input: LibraryArgs<FileDeleterJobInit>, result: null } | 
+        { key: "files.encrypt", input: LibraryArgs<FileEncryptorJobInit>, result: null } | 
         { key: "files.eraseFiles", input: LibraryArgs<FileEraserJobInit>, result: null } | 
+        { key: "files.openDefault", input: LibraryArgs<string>, result: null } | 
+        { key: "files.openInDefaultApp", input: LibraryArgs<OpenInDefaultAppArgs>, result: null } | 
+        { key: "files.openWith", input: LibraryArgs<OpenWithArgs>, result: null } | 
+        { key: "files.recordAccess", input: LibraryArgs<number[]>, result: null } | 
         { key: "files.removeAccessTime", input: LibraryArgs<number[]>, result: null } | 
+        { key: "files.rename", input: LibraryArgs<RenameArgs>, result: null } | 
         { key: "files.renameFile", input: LibraryArgs<RenameFileArgs>, result: null } | 
+        { key: "files.revealInFileManager", input: LibraryArgs<RevealInFileManagerArgs>, result: null } | 
+        { key: "files.setCustomThumbnail", input: LibraryArgs<SetCustomThumbnailArgs>, result: null } | 
         { key: "files.setFavorite", input: LibraryArgs<SetFavoriteArgs>, result: null } | 
         { key: "files.setNote", input: LibraryArgs<SetNoteArgs>, result: null } | 
         { key: "files.updateAccessTime", input: LibraryArgs<number[]>, result: null } | 
+        { key: "files.verifyIntegrity", input: LibraryArgs<VerifyIntegrityArgs>, result: null } | 
         { key: "invalidation.test-invalidate-mutation", input: LibraryArgs<null>, result: null } | 
         { key: "jobs.cancel", input: LibraryArgs<string>, result: null } | 
         { key: "jobs.clear", input: LibraryArgs<string>, result: null } | 
@@ -93,26 +133,57 @@ export type Procedures = {
         { key: "jobs.objectValidator", input: LibraryArgs<ObjectValidatorArgs>, result: null } | 
         { key: "jobs.pause", input: LibraryArgs<string>, result: null } | 
         { key: "jobs.resume", input: LibraryArgs<string>, result: null } | 
+        { key: "jobs.setPriority", input: LibraryArgs<SetJobPriorityArgs>, result: null } | 
         { key: "labels.delete", input: LibraryArgs<number>, result: null } | 
+        { key: "labels.removeFromObject", input: LibraryArgs<RemoveLabelFromObjectArgs>, result: null } | 
+        { key: "labels.reprocessLocation", input: LibraryArgs<ReprocessLocationLabelsArgs>, result: null } | 
+        { key: "labels.reprocessObject", input: LibraryArgs<number>, result: null } | 
+        { key: "library.cleanOrphans", input: LibraryArgs<null>, result: OrphansCleanup } | 
+        { key: "library.consolidateObjects", input: LibraryArgs<null>, result: null } | 
         { key: "library.create", input: CreateLibraryArgs, result: NormalisedResult<LibraryConfigWrapped> } | 
         { key: "library.delete", input: string, result: null } | 
         { key: "library.edit", input: EditLibraryArgs, result: null } | 
+        { key: "library.kv.delete", input: LibraryArgs<KeyValueArgs>, result: null } | 
+        { key: "library.kv.set", input: LibraryArgs<SetKeyValueArgs>, result: null } | 
         { key: "library.startActor", input: LibraryArgs<string>, result: null } | 
         { key: "library.stopActor", input: LibraryArgs<string>, result: null } | 
         { key: "locations.addLibrary", input: LibraryArgs<LocationCreateArgs>, result: number | null } | 
-        { key: "locations.create", input: LibraryArgs<LocationCreateArgs>, result: number | null } | 
+        { key: "locations.create", input: LibraryArgs<LocationCreateArgs>, result: LocationCreateResult } | 
+        { key: "locations.createMany", input: LibraryArgs<LocationCreateManyArgs>, result: LocationCreateManyItem[] } | 
         { key: "locations.delete", input: LibraryArgs<number>, result: null } | 
         { key: "locations.fullRescan", input: LibraryArgs<FullRescanArgs>, result: null } | 
-        { key: "locations.indexer_rules.create", input: LibraryArgs<IndexerRuleCreateArgs>, result: null } | 
+        { key: "locations.indexPath", input: LibraryArgs<IndexPathArgs>, result: number } | 
+        { key: "locations.indexer_rules.create", input: LibraryArgs<IndexerRuleCreateArgs>, result: SavedIndexerRule | null } | 
         { key: "locations.indexer_rules.delete", input: LibraryArgs<number>, result: null } | 
+        { key: "locations.indexer_rules.resetDefaults", input: LibraryArgs<null>, result: null } | 
+        { key: "locations.indexer_rules.update", input: LibraryArgs<IndexerRuleUpdateArgs>, result: SavedIndexerRule } | 
+        { key: "locations.pruneHidden", input: LibraryArgs<number>, result: null } | 
+        { key: "locations.rebase", input: LibraryArgs<LocationRebaseArgs>, result: null } | 
         { key: "locations.relink", input: LibraryArgs<string>, result: number } | 
+        { key: "locations.setFavorite", input: LibraryArgs<SetLocationFavoriteArgs>, result: null } | 
+        { key: "locations.setRescanSchedule", input: LibraryArgs<SetRescanScheduleArgs>, result: null } | 
+        { key: "locations.setSyncEnabled", input: LibraryArgs<SetLocationSyncEnabledArgs>, result: null } | 
         { key: "locations.subPathRescan", input: LibraryArgs<RescanArgs>, result: null } | 
         { key: "locations.update", input: LibraryArgs<LocationUpdateArgs>, result: null } | 
+        { key: "models.set", input: string, result: null } | 
         { key: "nodes.edit", input: ChangeNodeNameArgs, result: null } | 
+        { key: "nodes.setLogLevel", input: SetLogLevelArgs, result: string } | 
+        { key: "nodes.updateCryptoDefaults", input: CryptoDefaults, result: null } | 
+        { key: "nodes.updateIntegrityPreferences", input: UpdateIntegrityPreferences, result: null } | 
+        { key: "nodes.updateNetworkPreferences", input: UpdateNetworkPreferences, result: null } | 
+        { key: "nodes.updateStatisticsPreferences", input: UpdateStatisticsPreferences, result: null } | 
         { key: "nodes.updateThumbnailerPreferences", input: UpdateThumbnailerPreferences, result: null } | 
-        { key: "p2p.acceptSpacedrop", input: [string, string | null], result: null } | 
+        { key: "notifications.markAllRead", input: never, result: null } | 
+        { key: "notifications.markRead", input: NotificationsMarkReadArgs, result: null } | 
+        { key: "objects.setFavorite", input: LibraryArgs<SetObjectFavoriteArgs>, result: null } | 
+        { key: "objects.setNote", input: LibraryArgs<SetObjectNoteArgs>, result: null } | 
+        { key: "objects.update", input: LibraryArgs<ObjectUpdateArgs>, result: null } | 
+        { key: "p2p.acceptSpacedrop", input: [string, string | null, SpacedropLocationTarget | null], result: null } | 
         { key: "p2p.cancelSpacedrop", input: string, result: null } | 
+        { key: "p2p.requestFile", input: LibraryArgs<RequestFileArgs>, result: string } | 
+        { key: "p2p.setDiscoveryMode", input: DiscoveryMode, result: null } | 
         { key: "p2p.spacedrop", input: SpacedropArgs, result: string } | 
+        { key: "p2p.updateConfig", input: UpdateConfigArgs, result: null } | 
         { key: "preferences.update", input: LibraryArgs<LibraryPreferences>, result: null } | 
         { key: "search.saved.create", input: LibraryArgs<{ name: string; search?: string | null; filters?: string | null; description?: string | null; icon?: string | null }>, result: null } | 
         { key: "search.saved.delete", input: LibraryArgs<number>, result: null } | 
@@ -120,22 +191,49 @@ export type Procedures = {
         { key: "tags.assign", input: LibraryArgs<{ targets: Target[]; tag_id: number; unassign: boolean }>, result: null } | 
         { key: "tags.create", input: LibraryArgs<TagCreateArgs>, result: Tag } | 
         { key: "tags.delete", input: LibraryArgs<number>, result: null } | 
+        { key: "tags.reorder", input: LibraryArgs<TagReorderArgs>, result: null } | 
+        { key: "tags.resetDefaults", input: LibraryArgs<null>, result: null } | 
         { key: "tags.update", input: LibraryArgs<TagUpdateArgs>, result: null } | 
+        { key: "thumbnails.prewarm", input: LibraryArgs<ThumbnailsPrewarmArgs>, result: string } | 
+        { key: "thumbnails.regenerateAll", input: LibraryArgs<null>, result: number } | 
         { key: "toggleFeatureFlag", input: BackendFeature, result: null },
     subscriptions: 
+        { key: "actors.events", input: LibraryArgs<null>, result: ActorEvent } | 
         { key: "auth.loginSession", input: never, result: Response } | 
+        { key: "cloud.resetSyncProgress", input: LibraryArgs<null>, result: CloudSyncResetStage } | 
         { key: "invalidation.listen", input: never, result: InvalidateOperationEvent[] } | 
         { key: "jobs.newThumbnail", input: LibraryArgs<null>, result: string[] } | 
         { key: "jobs.progress", input: LibraryArgs<null>, result: JobProgressEvent } | 
-        { key: "library.actors", input: LibraryArgs<null>, result: { [key in string]: boolean } } | 
+        { key: "library.actors", input: LibraryArgs<null>, result: ActorState[] } | 
         { key: "locations.online", input: never, result: number[][] } | 
         { key: "locations.quickRescan", input: LibraryArgs<LightScanArgs>, result: null } | 
+        { key: "models.downloadProgress", input: never, result: ModelDownloadProgress } | 
         { key: "notifications.listen", input: never, result: Notification } | 
         { key: "p2p.events", input: never, result: P2PEvent } | 
+        { key: "nodes.readiness", input: never, result: Readiness } | 
+        { key: "preferences.watch", input: LibraryArgs<null>, result: LibraryPreferences } | 
         { key: "search.ephemeralPaths", input: LibraryArgs<EphemeralPathSearchArgs>, result: EphemeralPathsResultItem } | 
-        { key: "sync.newMessage", input: LibraryArgs<null>, result: null }
+        { key: "sync.newMessage", input: LibraryArgs<null>, result: null } | 
+        { key: "thumbnails.prewarmProgress", input: string, result: BatchProgress }
 };
 
+export type ActorEvent = { type: "Started"; name: string } | { type: "Stopped"; name: string } | { type: "Crashed"; name: string; error: string } | { type: "Restarted"; name: string }
+
+export type ActorState = { name: string; running: boolean; 
+/**
+ * When the current run started, `None` if it isn't running.
+ */
+started_at: string | null; 
+/**
+ * The last time the actor reported doing something, see [`ActorActivity`].
+ */
+last_activity: string | null; restart_count: number; crash_count: number; last_error: string | null }
+
+/**
+ * These are all possible algorithms that can be used for encryption and decryption
+ */
+export type Algorithm = "XChaCha20Poly1305" | "Aes256Gcm"
+
 export type Args = { search?: string | null; filters?: string | null; name?: string | null; icon?: string | null; description?: string | null }
 
 export type AudioMetadata = { duration: number | null; audio_codec: string | null }
@@ -149,6 +247,11 @@ export type BackendFeature = "syncEmitMessages" | "filesOverP2P" | "cloudSync"
 
 export type Backup = ({ id: string; timestamp: string; library_id: string; library_name: string }) & { path: string }
 
+/**
+ * Progress of a batch sent with [`Thumbnailer::new_indexed_thumbnails_prewarm_batch`].
+ */
+export type BatchProgress = { completed: number; total: number; percentage: number }
+
 export type BuildInfo = { version: string; commit: string }
 
 export type CRDTOperation = { instance: string; timestamp: number; id: string; model: string; record_id: JsonValue; data: CRDTOperationData }
@@ -159,7 +262,7 @@ export type CacheNode = { __type: string; __id: string; "#node": any }
 
 export type CameraData = { device_make: string | null; device_model: string | null; color_space: string | null; color_profile: ColorProfile | null; focal_length: number | null; shutter_speed: number | null; flash: Flash | null; orientation: Orientation; lens_make: string | null; lens_model: string | null; bit_depth: number | null; red_eye: boolean | null; zoom: number | null; iso: number | null; software: string | null; serial_number: string | null; lens_serial_number: string | null; contrast: number | null; saturation: number | null; sharpness: number | null; composite: Composite | null }
 
-export type ChangeNodeNameArgs = { name: string | null; p2p_port: MaybeUndefined<number>; p2p_enabled: boolean | null; image_labeler_version: string | null }
+export type ChangeNodeNameArgs = { name: string | null; p2p_port?: MaybeUndefined<number>; p2p_enabled: boolean | null; image_labeler_version: string | null }
 
 export type CloudInstance = { id: string; uuid: string; identity: RemoteIdentity; nodeId: string; nodeName: string; nodePlatform: number }
 
@@ -167,6 +270,21 @@ export type CloudLibrary = { id: string; uuid: string; name: string; instances:
 
 export type CloudLocation = { id: string; name: string }
 
+/**
+ * A change to the library on the cloud, which ends up the same however many times it's sent.
+ */
+export type CloudOperation = { type: "UpdateLibrary"; name: string } | { type: "UpdateInstance"; instance_uuid: string; node_id: string; node_name: string; node_platform: number }
+
+/**
+ * How far along [`reset`] is, sent as [`CoreEvent::CloudSyncReset`].
+ */
+export type CloudSyncResetStage = { stage: "StoppingActors" } | { stage: "ClearingState" } | { stage: "Pulling" } | { stage: "Done" } | { stage: "Failed"; error: string }
+
+/**
+ * The local operations which haven't been sent to the cloud yet.
+ */
+export type CloudSyncStatus = { queued_operations: number; queued_batches: number; oldest_queued_at: string | null }
+
 export type ColorProfile = "Normal" | "Custom" | "HDRNoOriginal" | "HDRWithOriginal" | "OriginalForHDR" | "Panorama" | "PortraitHDR" | "Portrait"
 
 export type Composite = 
@@ -197,15 +315,42 @@ export type CreateFolderArgs = { location_id: number; sub_path: string | null; n
 
 export type CreateLibraryArgs = { name: LibraryName; default_locations: DefaultLocations | null }
 
+export type CryptoDefaults = { algorithm: Algorithm; 
+/**
+ * Used for hashing the password of each file's keyslot, stronger params take longer to hash.
+ */
+hashing_algorithm: HashingAlgorithm }
+
 export type CursorOrderItem<T> = { order: SortOrder; data: T }
 
+export type DailyNetworkUsage = { 
+/**
+ * The UTC day, as `YYYY-MM-DD`.
+ */
+date: string; category: NetworkUsageCategory; sent: string; received: string }
+
+export type DateGranularity = "day" | "month" | "year"
+
 export type DefaultLocations = { desktop: boolean; documents: boolean; downloads: boolean; pictures: boolean; music: boolean; videos: boolean }
 
+/**
+ * How the P2P layer finds peers, and lets itself be found.
+ */
+export type DiscoveryMode = "Disabled" | "LocalOnly" | "Full"
+
 export type DiskType = "SSD" | "HDD" | "Removable"
 
+export type DocumentMetadata = { page_count: number }
+
+export type DocumentPageThumbnailArgs = { file_path_id: number; 
+/**
+ * Numbered from 1, like the pages of the document
+ */
+page: number }
+
 export type DoubleClickAction = "openFile" | "quickPreview"
 
-export type EditLibraryArgs = { id: string; name: LibraryName | null; description: MaybeUndefined<string> }
+export type EditLibraryArgs = { id: string; name: LibraryName | null; description?: MaybeUndefined<string> }
 
 export type EphemeralFileSystemOps = { sources: string[]; target_dir: string }
 
@@ -230,7 +375,25 @@ export type Error = { code: ErrorCode; message: string }
  */
 export type ErrorCode = "BadRequest" | "Unauthorized" | "Forbidden" | "NotFound" | "Timeout" | "Conflict" | "PreconditionFailed" | "PayloadTooLarge" | "MethodNotSupported" | "ClientClosedRequest" | "InternalServerError"
 
-export type ExplorerItem = { type: "Path"; thumbnail: string[] | null; item: FilePathWithObject } | { type: "Object"; thumbnail: string[] | null; item: ObjectWithFilePaths } | { type: "Location"; item: Location } | { type: "NonIndexedPath"; thumbnail: string[] | null; item: NonIndexedPathItem } | { type: "SpacedropPeer"; item: PeerMetadata } | { type: "Label"; thumbnails: string[][]; item: LabelWithObjects }
+export type EventBusMetrics = { capacity: number; 
+/**
+ * How many times a subscriber fell behind and lost events.
+ */
+lagged: number; 
+/**
+ * How many events were lost in total.
+ */
+dropped_events: number }
+
+export type ExplorerItem = { type: "Path"; thumbnail: string[] | null; thumbnail_status: ThumbnailStatus; item: FilePathWithObject } | { type: "Object"; thumbnail: string[] | null; thumbnail_status: ThumbnailStatus; item: ObjectWithFilePaths } | { type: "Location"; item: Location } | { type: "NonIndexedPath"; thumbnail: string[] | null; thumbnail_status: ThumbnailStatus; item: NonIndexedPathItem } | { type: "SpacedropPeer"; identity: RemoteIdentity; item: PeerMetadata } | { type: "Label"; thumbnails: string[][]; item: LabelWithObjects }
+
+export type ExplorerItemId = { FilePath: number } | { Object: number } | { NonIndexed: string }
+
+export type ExplorerItemsByIds = { 
+/**
+ * In the same order as the requested ids, `None` for the ones which weren't found
+ */
+items: (Reference<ExplorerItem> | null)[]; nodes: CacheNode[] }
 
 export type ExplorerLayout = "grid" | "list" | "media"
 
@@ -242,11 +405,43 @@ export type FileCopierJobInit = { source_location_id: number; target_location_id
 
 export type FileCutterJobInit = { source_location_id: number; target_location_id: number; sources_file_path_ids: number[]; target_location_relative_directory_path: string }
 
+export type FileDecryptorJobInit = { location_id: number; file_path_ids: number[]; 
+/**
+ * Never persisted with the job's state, so the job is canceled instead of resumed after a restart.
+ */
+password?: string | null; 
+/**
+ * Delete each encrypted file once it's decrypted.
+ */
+delete_original?: boolean }
+
 export type FileDeleterJobInit = { location_id: number; file_path_ids: number[] }
 
+export type FileEncryptorJobInit = { location_id: number; file_path_ids: number[]; 
+/**
+ * Defaults to the node's [`CryptoDefaults`](crate::node::config::CryptoDefaults).
+ */
+algorithm?: Algorithm | null; 
+/**
+ * Defaults to the node's [`CryptoDefaults`](crate::node::config::CryptoDefaults).
+ */
+hashing_algorithm?: HashingAlgorithm | null; 
+/**
+ * Never persisted with the job's state, so the job is canceled instead of resumed after a restart.
+ */
+password?: string | null; 
+/**
+ * Delete each original file once it's encrypted.
+ */
+delete_original?: boolean }
+
 export type FileEraserJobInit = { location_id: number; file_path_ids: number[]; passes: string }
 
-export type FilePath = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; inode: number[] | null; object_id: number | null; key_id: number | null; date_created: string | null; date_modified: string | null; date_indexed: string | null }
+export type FileInspectArgs = { path: string }
+
+export type FileInspection = { item: NonIndexedPathItem; cas_id: string | null; media_data: MediaMetadata | null }
+
+export type FilePath = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; name_normalized: string | null; symlink_target: string | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; inode: number[] | null; object_id: number | null; key_id: number | null; date_created: string | null; date_modified: string | null; date_indexed: string | null }
 
 export type FilePathCursor = { isDir: boolean; variant: FilePathCursorVariant }
 
@@ -258,9 +453,23 @@ export type FilePathObjectCursor = { dateAccessed: CursorOrderItem<string> } | {
 
 export type FilePathOrder = { field: "name"; value: SortOrder } | { field: "sizeInBytes"; value: SortOrder } | { field: "dateCreated"; value: SortOrder } | { field: "dateModified"; value: SortOrder } | { field: "dateIndexed"; value: SortOrder } | { field: "object"; value: ObjectOrder }
 
+export type FilePathPage = { items: Reference<ExplorerItem>[]; nodes: CacheNode[]; 
+/**
+ * `None` once the last page has been returned
+ */
+nextCursor: number | null }
+
+export type FilePathPageArgs = { take?: number | null; 
+/**
+ * The `id` of the last item of the previous page
+ */
+cursor?: number | null; order?: FilePathPageOrder; filters?: SearchFilterArgs[] }
+
+export type FilePathPageOrder = { field: "id"; value: SortOrder } | { field: "name"; value: SortOrder }
+
 export type FilePathSearchArgs = { take?: number | null; orderAndPagination?: OrderAndPagination<number, FilePathOrder, FilePathCursor> | null; filters?: SearchFilterArgs[]; groupDirectories?: boolean }
 
-export type FilePathWithObject = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; inode: number[] | null; object_id: number | null; key_id: number | null; date_created: string | null; date_modified: string | null; date_indexed: string | null; object: { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null } | null }
+export type FilePathWithObject = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; name_normalized: string | null; symlink_target: string | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; inode: number[] | null; object_id: number | null; key_id: number | null; date_created: string | null; date_modified: string | null; date_indexed: string | null; object: { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null } | null }
 
 export type Flash = { 
 /**
@@ -309,7 +518,12 @@ export type FlashMode =
 
 export type FromPattern = { pattern: string; replace_all: boolean }
 
-export type FullRescanArgs = { location_id: number; reidentify_objects: boolean }
+export type FullRescanArgs = { location_id: number; reidentify_objects: boolean; 
+/**
+ * Don't walk into directories which weren't modified since the last scan. It's quicker,
+ * but files added deeper inside them are missed until the next full scan.
+ */
+skip_unchanged?: boolean }
 
 export type GenerateLabelsForLocationArgs = { id: number; path: string; regenerate?: boolean }
 
@@ -319,38 +533,109 @@ export type GetAll = { backups: Backup[]; directory: string }
 
 export type HardwareModel = "Other" | "MacStudio" | "MacBookAir" | "MacBookPro" | "MacBook" | "MacMini" | "MacPro" | "IMac" | "IMacPro" | "IPad" | "IPhone"
 
+/**
+ * This defines all available password hashing algorithms.
+ */
+export type HashingAlgorithm = { name: "Argon2id"; params: Params } | { name: "BalloonBlake3"; params: Params }
+
 export type IdentifyUniqueFilesArgs = { id: number; path: string }
 
+/**
+ * An image labeler model which can be selected.
+ */
+export type ImageLabelerModel = { version: string; 
+/**
+ * Whether this is the model the image labeler is configured to use.
+ */
+active: boolean; downloaded: boolean; 
+/**
+ * Size in bytes of the model on disk, as a string because it may not fit in a JS number.
+ */
+size: string | null }
+
 export type ImageMetadata = { resolution: Resolution; date_taken: MediaDate | null; location: MediaLocation | null; camera_data: CameraData; artist: string | null; description: string | null; copyright: string | null; exif_version: string | null }
 
 export type InOrNotIn<T> = { in: T[] } | { notIn: T[] }
 
+export type IndexPathArgs = { location_id: number; 
+/**
+ * Absolute, or relative to the location.
+ */
+path: string }
+
 export type IndexerRule = { id: number; pub_id: number[]; name: string | null; default: boolean | null; rules_per_kind: number[] | null; date_created: string | null; date_modified: string | null }
 
 /**
  * `IndexerRuleCreateArgs` is the argument received from the client using rspc to create a new indexer rule.
- * Note that `rules` field is a vector of tuples of `RuleKind` and `parameters`.
- * 
- * In case of  `RuleKind::AcceptFilesByGlob` or `RuleKind::RejectFilesByGlob`, it will be a
- * vector of strings containing a glob patterns.
+ * Each of its `rules` is one of the kinds of rules with its parameters, see [`RuleParameters`].
+ */
+export type IndexerRuleCreateArgs = { name: string; dry_run: boolean; rules: RuleParameters[] }
+
+export type IndexerRuleImpact = { indexer_rule_id: number; name: string; 
+/**
+ * Sampled entries this rule would keep from being indexed on its own.
+ */
+rejected: number }
+
+/**
+ * `IndexerRuleUpdateArgs` is the argument received from the client using rspc to edit an indexer
+ * rule, the fields left out are kept as they are.
  * 
- * In case of `RuleKind::AcceptIfChildrenDirectoriesArePresent` or `RuleKind::RejectIfChildrenDirectoriesArePresent` the
- * `parameters` field must be a vector of strings containing the names of the directories.
+ * Default rules can't be edited, `locations.indexer_rules.resetDefaults` would undo it.
+ */
+export type IndexerRuleUpdateArgs = { id: number; name: string | null; 
+/**
+ * Replaces all of the rule's rules.
  */
-export type IndexerRuleCreateArgs = { name: string; dry_run: boolean; rules: ([RuleKind, string[]])[] }
+rules: RuleParameters[] | null }
+
+export type IntegrityMismatchWithFilePath = { file_path_id: number; location_id: number; expected_cas_id: string; actual_cas_id: string | null; date_detected: string; file_path: FilePath }
+
+export type IntegrityPreferences = { max_throughput_mb_per_sec: number }
+
+export type IntegrityReport = { 
+/**
+ * When all the files of the location were last verified, a verification of only some objects
+ * doesn't count.
+ */
+verified_at: string | null; mismatches: IntegrityMismatchWithFilePath[] }
 
 export type InvalidateOperationEvent = { type: "single"; data: SingleInvalidateOperationEvent } | { type: "all" }
 
 export type JobGroup = { id: string; action: string | null; status: JobStatus; created_at: string; jobs: JobReport[] }
 
-export type JobProgressEvent = { id: string; library_id: string; task_count: number; completed_task_count: number; phase: string; message: string; estimated_completion: string }
+/**
+ * A named stage of a job, like walking a location before saving what was found, with its own
+ * progress so each stage can be told apart from the others.
+ */
+export type JobPhase = { name: string; task_count: number; completed_task_count: number; started_at: string; completed_at: string | null }
+
+/**
+ * Queued jobs with a higher priority are run first, jobs with the same priority run in the
+ * order they were queued.
+ */
+export type JobPriority = "Low" | "Normal" | "High"
 
-export type JobReport = { id: string; name: string; action: string | null; data: number[] | null; metadata: { [key in string]: JsonValue } | null; errors_text: string[]; created_at: string | null; started_at: string | null; completed_at: string | null; parent_id: string | null; status: JobStatus; task_count: number; completed_task_count: number; phase: string; message: string; estimated_completion: string }
+export type JobProgressEvent = { id: string; library_id: string; task_count: number; completed_task_count: number; 
+/**
+ * Name of the phase the job is in, empty if it doesn't report phases.
+ */
+phase: string; message: string; estimated_completion: string }
+
+export type JobReport = { id: string; name: string; action: string | null; data: number[] | null; metadata: { [key in string]: JsonValue } | null; errors_text: string[]; created_at: string | null; started_at: string | null; completed_at: string | null; parent_id: string | null; status: JobStatus; priority: JobPriority; task_count: number; completed_task_count: number; 
+/**
+ * Every phase the job went through so far, in order, the last one being the current phase.
+ */
+phases: JobPhase[]; phase: string; message: string; estimated_completion: string }
 
 export type JobStatus = "Queued" | "Running" | "Completed" | "Canceled" | "Failed" | "Paused" | "CompletedWithErrors"
 
 export type JsonValue = null | boolean | number | string | JsonValue[] | { [key in string]: JsonValue }
 
+export type KeyValueArgs = { namespace: string; key: string }
+
+export type KeyValueEntry = { key: string; value: JsonValue }
+
 export type KindStatistic = { kind: number; name: string; count: number; total_bytes: string }
 
 export type KindStatistics = { statistics: KindStatistic[] }
@@ -392,20 +677,94 @@ export type LibraryConfigWrapped = { uuid: string; instance_id: string; instance
 
 export type LibraryName = string
 
-export type LibraryPreferences = { location?: { [key in string]: LocationSettings } }
+export type LibraryPreferences = { location?: { [key in string]: LocationSettings }; 
+/**
+ * The explorer settings used by views without their own, like locations that were never customized.
+ */
+explorer?: ExplorerSettings<FilePathOrder> | null; 
+/**
+ * Whether GPS coordinates extracted from media are returned to the frontend.
+ */
+exposeLocationMetadata?: boolean | null; 
+/**
+ * Whether identifying files also stores their full BLAKE3 checksum, which reads them whole
+ * instead of sampling them but tells apart big files the `cas_id` can't.
+ */
+fullHash?: boolean | null }
 
 export type LightScanArgs = { location_id: number; sub_path: string }
 
+export type ListWithThumbnailsArgs = { cursor: string; 
+/**
+ * Exclude labels which were only assigned with a lower confidence than this.
+ */
+min_confidence?: number | null }
+
 export type ListenerStatus = { status: "Disabled" } | { status: "Enabling" } | { status: "Listening"; port: number } | { status: "Error"; error: string }
 
-export type Location = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; date_created: string | null; instance_id: number | null }
+export type Location = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; favorite: boolean | null; date_created: string | null; scanned_at: string | null; rescan_interval: number | null; integrity_verified_at: string | null; case_insensitive: boolean | null; symlink_policy: number | null; index_hidden: boolean | null; sync_enabled: boolean | null; instance_id: number | null }
 
 /**
  * `LocationCreateArgs` is the argument received from the client using `rspc` to create a new location.
  * It has the actual path and a vector of indexer rules ids, to create many-to-many relationships
  * between the location and indexer rules.
+ * 
+ * When `interactive` is set, the top level of the location is scanned before returning, so it can
+ * be shown right away, and the full scan is queued ahead of other jobs.
+ * 
+ * A location inside another one of the library, or containing one, is rejected as its files would
+ * be indexed twice, unless `force` is set for overlaps that are meant to be.
+ */
+export type LocationCreateArgs = { path: string; dry_run: boolean; indexer_rules_ids: number[]; interactive?: boolean; force?: boolean }
+
+/**
+ * The failures creating a location commonly has, so the frontend can tell them apart.
+ */
+export type LocationCreateError = { code: "PATH_NOT_FOUND"; data: { path: string } } | { code: "OVERLAPPING"; data: { path: string; location_id: number } } | { code: "ADD_LIBRARY"; data: { path: string; other_library: OtherLibrary | null } } | { code: "PERMISSION_DENIED"; data: { path: string } } | { code: "READ_ONLY"; data: { path: string } }
+
+/**
+ * `LocationCreateManyArgs` adds several locations at once, each one as with [`LocationCreateArgs`].
+ */
+export type LocationCreateManyArgs = { args: LocationCreateArgs[] }
+
+export type LocationCreateManyItem = { path: string; result: LocationCreateManyResult }
+
+export type LocationCreateManyResult = { type: "Created"; id: number } | { type: "Failed"; error: string }
+
+/**
+ * What creating a location would do, returned by dry runs instead of creating it.
+ */
+export type LocationCreatePreview = { 
+/**
+ * Entries which would be indexed, among the sampled ones.
+ */
+files: number; directories: number; total_size: string; 
+/**
+ * Whether every entry was sampled, otherwise the counts only cover part of the location.
  */
-export type LocationCreateArgs = { path: string; dry_run: boolean; indexer_rules_ids: number[] }
+complete: boolean; 
+/**
+ * In the same order as the rules were given.
+ */
+rules: IndexerRuleImpact[]; 
+/**
+ * Locations of this library containing the path or inside it.
+ */
+overlapping_locations: number[]; 
+/**
+ * Libraries which already have the path as a location, from its `.spacedrive` file.
+ */
+other_libraries: OtherLibrary[] }
+
+export type LocationCreateResult = { type: "Created"; id: number; 
+/**
+ * Only for interactive creations
+ */
+shallow_scan: ShallowScanSummary | null } | ({ type: "DryRun" } & LocationCreatePreview)
+
+export type LocationMediaDateArgs = { locationId: number; granularity: DateGranularity }
+
+export type LocationRebaseArgs = { location_id: number; old_prefix: string; new_prefix: string }
 
 export type LocationSettings = { explorer: ExplorerSettings<FilePathOrder> }
 
@@ -417,25 +776,93 @@ export type LocationSettings = { explorer: ExplorerSettings<FilePathOrder> }
  * It is important to note that only the indexer rule ids in this vector will be used from now on.
  * Old rules that aren't in this vector will be purged.
  */
-export type LocationUpdateArgs = { id: number; name: string | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; indexer_rules_ids: number[]; path: string | null }
+export type LocationUpdateArgs = { id: number; name: string | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; indexer_rules_ids: number[]; path: string | null; symlink_policy?: SymlinkPolicy | null; index_hidden?: boolean | null }
+
+export type LocationVolumeInfo = ({ status: "Available" } & Volume) | { status: "Unavailable" }
 
-export type LocationWithIndexerRule = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; date_created: string | null; instance_id: number | null; indexer_rules: Reference<IndexerRule>[] }
+export type LocationWithIndexerRule = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; favorite: boolean | null; date_created: string | null; scanned_at: string | null; rescan_interval: number | null; integrity_verified_at: string | null; case_insensitive: boolean | null; symlink_policy: number | null; index_hidden: boolean | null; sync_enabled: boolean | null; instance_id: number | null; indexer_rules: Reference<IndexerRule>[] }
 
 export type MaybeUndefined<T> = null | T
 
+export type MediaByDateBucketArgs = { filter: MediaDateFilter; 
+/**
+ * The `date` of the bucket, as returned by `search.mediaByDate`.
+ */
+date: string; take: number; cursor?: MediaDateCursor | null }
+
 export type MediaDataOrder = { field: "epochTime"; value: SortOrder }
 
+export type MediaDataState = { status: "Ready"; data: MediaMetadata } | 
+/**
+ * Extraction has been queued and `files.getMediaData` will be invalidated once it's done.
+ */
+{ status: "Pending" } | 
+/**
+ * The object doesn't have any media data we can extract.
+ */
+{ status: "Unavailable" } | 
+/**
+ * Extraction failed, it's tried again when requested after a while.
+ */
+{ status: "Failed"; data: { error: string } }
+
 /**
  * This can be either naive with no TZ (`YYYY-MM-DD HH-MM-SS`) or UTC (`YYYY-MM-DD HH-MM-SS ±HHMM`),
  * where `±HHMM` is the timezone data. It may be negative if West of the Prime Meridian, or positive if East.
  */
 export type MediaDate = string
 
+export type MediaDateBucket = { 
+/**
+ * The bucket key, formatted as `YYYY-MM-DD`, `YYYY-MM` or `YYYY` depending on the granularity.
+ */
+date: string; count: number; sample_thumb_keys: string[][] }
+
+export type MediaDateBucketItems = { cursor: MediaDateCursor | null; items: Reference<ExplorerItem>[]; nodes: CacheNode[] }
+
+/**
+ * Position in a bucket to continue paginating from.
+ */
+export type MediaDateCursor = { capturedAt: string; id: number }
+
+export type MediaDateFilter = { 
+/**
+ * The `ObjectKind`s to include, defaults to images and videos.
+ */
+kinds?: number[]; granularity: DateGranularity; from?: string | null; to?: string | null }
+
 export type MediaLocation = { latitude: number; longitude: number; pluscode: PlusCode; altitude: number | null; direction: number | null }
 
-export type MediaMetadata = ({ type: "Image" } & ImageMetadata) | ({ type: "Video" } & VideoMetadata) | ({ type: "Audio" } & AudioMetadata)
+export type MediaMetadata = ({ type: "Image" } & ImageMetadata) | ({ type: "Video" } & VideoMetadata) | ({ type: "Audio" } & AudioMetadata) | ({ type: "Document" } & DocumentMetadata)
 
-export type NodePreferences = { thumbnailer: ThumbnailerPreferences }
+export type ModelDownloadProgress = { version: string; 
+/**
+ * `None` when the server didn't tell us the size of the model.
+ */
+percent: number | null }
+
+export type NetworkPreferences = { 
+/**
+ * Pauses sending to the cloud and files over P2P, Spacedrops started by the user still go.
+ */
+metered_connection: boolean }
+
+export type NetworkUsageArgs = { 
+/**
+ * The first UTC day included.
+ */
+from: string; 
+/**
+ * The last UTC day included.
+ */
+to: string }
+
+/**
+ * What the data moved over the network was for.
+ */
+export type NetworkUsageCategory = "CloudSync" | "P2PSync" | "Spacedrop" | "FilesOverP2P"
+
+export type NodePreferences = { thumbnailer: ThumbnailerPreferences; integrity?: IntegrityPreferences; statistics?: StatisticsPreferences; network?: NetworkPreferences }
 
 export type NodeState = ({ 
 /**
@@ -445,7 +872,7 @@ id: string;
 /**
  * name is the display name of the current node. This is set by the user and is shown in the UI. // TODO: Length validation so it can fit in DNS record
  */
-name: string; p2p_enabled: boolean; p2p_port: number | null; features: BackendFeature[]; preferences: NodePreferences; image_labeler_version: string | null }) & { data_path: string; p2p: P2PStatus; device_model: string | null }
+name: string; p2p_enabled: boolean; p2p_port: number | null; p2p_relays: RelayConfig[]; p2p_enable_hole_punching: boolean; p2p_discovery: DiscoveryMode; features: BackendFeature[]; preferences: NodePreferences; image_labeler_version: string | null }) & { data_path: string; p2p: P2PStatus; device_model: string | null }
 
 export type NonIndexedPathItem = { path: string; name: string; extension: string; kind: number; is_dir: boolean; date_created: string; date_modified: string; size_in_bytes_bytes: number[]; hidden: boolean }
 
@@ -466,7 +893,11 @@ export type NormalisedResults<T> = { items: Reference<T>[]; nodes: CacheNode[] }
 /**
  * Represents a single notification.
  */
-export type Notification = ({ type: "library"; id: [string, number] } | { type: "node"; id: number }) & { data: NotificationData; read: boolean; expires: string | null }
+export type Notification = ({ type: "library"; id: [string, number] } | { type: "node"; id: number }) & { data: NotificationData; read: boolean; expires: string | null; 
+/**
+ * Notifications stored before this was added are treated as the oldest ones.
+ */
+created_at?: string }
 
 /**
  * Represents the data of a single notification.
@@ -478,6 +909,25 @@ export type NotificationId = { type: "library"; id: [string, number] } | { type:
 
 export type NotificationKind = "info" | "success" | "error" | "warning"
 
+/**
+ * The last notification of a page, which is still valid after that notification is dismissed.
+ */
+export type NotificationsCursor = { created_at: string; id: NotificationId }
+
+export type NotificationsListArgs = { unread_only?: boolean; limit: number; 
+/**
+ * The `cursor` of the previous page.
+ */
+cursor?: NotificationsCursor | null }
+
+export type NotificationsMarkReadArgs = { ids: NotificationId[] }
+
+export type NotificationsPage = { items: Notification[]; 
+/**
+ * Pass it back to get the next page, `None` once there are no more notifications.
+ */
+cursor: NotificationsCursor | null }
+
 export type Object = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null }
 
 export type ObjectCursor = "none" | { dateAccessed: CursorOrderItem<string> } | { kind: CursorOrderItem<number> }
@@ -490,12 +940,31 @@ export type ObjectOrder = { field: "dateAccessed"; value: SortOrder } | { field:
 
 export type ObjectSearchArgs = { take: number; orderAndPagination?: OrderAndPagination<number, ObjectOrder, ObjectCursor> | null; filters?: SearchFilterArgs[] }
 
+export type ObjectUpdateArgs = { id: number; note?: MaybeUndefined<string>; favorite: boolean | null; hidden: boolean | null }
+
 export type ObjectValidatorArgs = { id: number; path: string }
 
 export type ObjectWithFilePaths = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; file_paths: FilePath[] }
 
 export type ObjectWithFilePaths2 = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; file_paths: Reference<FilePath>[] }
 
+export type OpenInDefaultAppArgs = { file_path_id: number }
+
+/**
+ * An application which can open a file.
+ */
+export type OpenWithApplication = { 
+/**
+ * Opaque id of the application, to be passed back to `files.openWith`.
+ */
+handler_id: string; name: string; 
+/**
+ * Platform specific hint for the frontend to find the application's icon, like the app bundle on macOS.
+ */
+icon_hint: string | null }
+
+export type OpenWithArgs = { path: string; handler_id: string }
+
 /**
  * Represents the operating system which the remote peer is running.
  * This is not used internally and predominantly is designed to be used for display purposes by the embedding application.
@@ -506,19 +975,78 @@ export type OrderAndPagination<TId, TOrder, TCursor> = { orderOnly: TOrder } | {
 
 export type Orientation = "Normal" | "CW90" | "CW180" | "CW270" | "MirroredVertical" | "MirroredHorizontal" | "MirroredHorizontalAnd90CW" | "MirroredHorizontalAnd270CW"
 
+/**
+ * How many orphaned objects a clean up found.
+ */
+export type OrphansCleanup = { removed: number; 
+/**
+ * Orphans kept because the user left something on them, a note or a favorite.
+ */
+preserved: number }
+
+export type OtherLibrary = { id: string; name: string }
+
 /**
  * TODO: P2P event for the frontend
  */
-export type P2PEvent = { type: "DiscoveredPeer"; identity: RemoteIdentity; metadata: PeerMetadata } | { type: "ExpiredPeer"; identity: RemoteIdentity } | { type: "ConnectedPeer"; identity: RemoteIdentity } | { type: "DisconnectedPeer"; identity: RemoteIdentity } | { type: "SpacedropRequest"; id: string; identity: RemoteIdentity; peer_name: string; files: string[] } | { type: "SpacedropProgress"; id: string; percent: number } | { type: "SpacedropTimedout"; id: string } | { type: "SpacedropRejected"; id: string }
+export type P2PEvent = { type: "DiscoveredPeer"; identity: RemoteIdentity; metadata: PeerMetadata } | { type: "ExpiredPeer"; identity: RemoteIdentity } | { type: "ConnectedPeer"; identity: RemoteIdentity } | { type: "DisconnectedPeer"; identity: RemoteIdentity } | { type: "SpacedropRequest"; id: string; identity: RemoteIdentity; peer_name: string; files: string[]; 
+/**
+ * Whether a directory is being sent, in which case `files` are paths relative to the accepted directory.
+ */
+is_directory: boolean; 
+/**
+ * Whether the files are end-to-end encrypted between the peers, on top of the connection's encryption.
+ */
+encrypted: boolean; total_size: string; file_count: number } | { type: "SpacedropProgress"; id: string; percent: number } | { type: "FilePullProgress"; id: string; percent: number } | { type: "SpacedropTimedout"; id: string } | { type: "SpacedropRejected"; id: string } | { type: "SpacedropIndexed"; id: string; library_id: string; location_id: number; file_path_ids: number[] } | { type: "SpacedropCompleted"; id: string; 
+/**
+ * Files which were skipped, such as symlinks.
+ */
+warnings: string[] } | { type: "DiscoveryModeChanged"; mode: DiscoveryMode }
 
 export type P2PStatus = { ipv4: ListenerStatus; ipv6: ListenerStatus }
 
-export type PeerMetadata = { name: string; operating_system: OperatingSystem | null; device_model: HardwareModel | null; version: string | null }
+/**
+ * These parameters define the password-hashing level.
+ *
+ * The greater the parameter, the longer the password will take to hash.
+ */
+export type Params = "Standard" | "Hardened" | "Paranoid"
+
+/**
+ * Information about an active connection to a peer
+ */
+export type PeerConnection = { identity: RemoteIdentity; 
+/**
+ * Is the connection going through a relay server or is it a direct connection
+ */
+relayed: boolean; 
+/**
+ * The last measured round trip time. `None` if it hasn't been measured yet.
+ */
+rtt_ms: number | null }
+
+export type PeerMetadata = { name: string; operating_system: OperatingSystem | null; device_model: HardwareModel | null; version: string | null; 
+/**
+ * Whether the peer can receive end-to-end encrypted Spacedrops. Older nodes don't advertise it.
+ */
+encrypted_spacedrop: boolean }
+
+/**
+ * An operation waiting to be sent to the cloud.
+ */
+export type PendingCloudOperation = { operation: CloudOperation; attempts: number; last_error: string | null; next_attempt_at: string; queued_at: string }
 
 export type PlusCode = string
 
 export type Range<T> = { from: T } | { to: T }
 
+/**
+ * Which parts of the core are up. A host app can use this to show a splash screen until the core is ready.
+ */
+export type Readiness = { config: SubsystemStatus; thumbnailer: SubsystemStatus; p2p: SubsystemStatus; libraries: SubsystemStatus }
+
+export type RecentsArgs = { limit?: number | null }
+
 /**
  * A reference to a `CacheNode`.
  * 
@@ -529,8 +1057,25 @@ export type Range<T> = { from: T } | { to: T }
  */
 export type Reference<T> = { __type: string; __id: string; "#type": T }
 
+/**
+ * A relay (or rendezvous) server which can be used to establish connections between peers on different networks.
+ */
+export type RelayConfig = { 
+/**
+ * The libp2p `PeerId` of the relay server
+ */
+peer_id: string; 
+/**
+ * The QUIC address the relay server is listening on
+ */
+address: string }
+
 export type RemoteIdentity = string
 
+export type RemoveLabelFromObjectArgs = { object_id: number; label_id: number }
+
+export type RenameArgs = { file_path_id: number; new_name: string }
+
 export type RenameFileArgs = { location_id: number; kind: RenameKind }
 
 export type RenameKind = { One: RenameOne } | { Many: RenameMany }
@@ -539,13 +1084,58 @@ export type RenameMany = { from_pattern: FromPattern; to_pattern: string; from_f
 
 export type RenameOne = { from_file_path_id: number; to: string }
 
+export type ReprocessLocationLabelsArgs = { location_id: number; 
+/**
+ * Labels which aren't detected again with at least this confidence are removed.
+ */
+min_confidence: number }
+
+export type RequestFileArgs = { identity: RemoteIdentity; 
+/**
+ * The `pub_id` of the `file_path` on the remote instance.
+ */
+file_path_id: string; 
+/**
+ * Where to save the file locally.
+ */
+path: string }
+
 export type RescanArgs = { location_id: number; sub_path: string }
 
+export type ResetSyncArgs = { 
+/**
+ * Must be set, as pulling everything again can mean a large download.
+ */
+confirm: boolean; 
+/**
+ * Start syncing again right away, pulling everything from the cloud.
+ */
+pull: boolean }
+
 export type Resolution = { width: number; height: number }
 
 export type Response = { Start: { user_code: string; verification_url: string; verification_url_complete: string } } | "Complete" | { Error: string }
 
-export type RuleKind = "AcceptFilesByGlob" | "RejectFilesByGlob" | "AcceptIfChildrenDirectoriesArePresent" | "RejectIfChildrenDirectoriesArePresent"
+export type RevealInFileManagerArgs = { file_path_id: number }
+
+/**
+ * The parameters of each kind of rule.
+ * In case of doubt about globs, consult <https://docs.rs/globset/latest/globset/#syntax>
+ */
+export type RuleParameters = { kind: "AcceptFilesByGlob"; globs: string[] } | { kind: "RejectFilesByGlob"; globs: string[] } | { kind: "AcceptIfChildrenDirectoriesArePresent"; children: string[] } | { kind: "RejectIfChildrenDirectoriesArePresent"; children: string[] } | { kind: "AcceptIfAllOfRejectIfAnyOf"; accept: string[]; reject: string[] }
+
+/**
+ * An indexer rule once it's been created or updated.
+ */
+export type SavedIndexerRule = { id: number; name: string; 
+/**
+ * In the order they're evaluated, see [`RuleKind::EVALUATION_ORDER`].
+ */
+rules: RuleParameters[]; 
+/**
+ * The locations using this rule which will be fully rescanned, as its rules changed.
+ */
+rescan_location_ids: number[] }
 
 export type SavedSearch = { id: number; pub_id: number[]; search: string | null; filters: string | null; name: string | null; icon: string | null; description: string | null; date_created: string | null; date_modified: string | null }
 
@@ -553,10 +1143,49 @@ export type SearchData<T> = { cursor: number[] | null; items: Reference<T>[]; no
 
 export type SearchFilterArgs = { filePath: FilePathFilterArgs } | { object: ObjectFilterArgs }
 
+export type SetCustomThumbnailArgs = { file_path_id: number; 
+/**
+ * The image to use as the thumbnail, in any format we can decode
+ */
+image_bytes: number[] }
+
 export type SetFavoriteArgs = { id: number; favorite: boolean }
 
+export type SetJobPriorityArgs = { id: string; priority: JobPriority }
+
+export type SetKeyValueArgs = { namespace: string; key: string; value: JsonValue }
+
+export type SetLocationFavoriteArgs = { location_id: number; favorite: boolean }
+
+export type SetLocationSyncEnabledArgs = { location_id: number; enabled: boolean }
+
+export type SetLogLevelArgs = { 
+/**
+ * Same syntax as `RUST_LOG`, like `info,sd_core=trace`.
+ */
+directive: string }
+
 export type SetNoteArgs = { id: number; note: string | null }
 
+export type SetObjectFavoriteArgs = { object_id: number; favorite: boolean }
+
+export type SetObjectNoteArgs = { object_id: number; note: string | null }
+
+export type SetRescanScheduleArgs = { location_id: number; 
+/**
+ * Seconds between automatic rescans, `null` disables them.
+ */
+interval_secs: number | null }
+
+/**
+ * What the shallow pass of [`interactive_scan_location`] found.
+ */
+export type ShallowScanSummary = { 
+/**
+ * Entries indexed at the top level of the location
+ */
+entries: number }
+
 export type SingleInvalidateOperationEvent = { 
 /**
  * This fields are intentionally private.
@@ -567,27 +1196,107 @@ export type SortOrder = "Asc" | "Desc"
 
 export type SpacedropArgs = { identity: RemoteIdentity; file_path: string[] }
 
+export type SpacedropLocationTarget = { library_id: string; location_id: number; sub_path: string | null }
+
 export type Statistics = { id: number; date_captured: string; total_object_count: number; library_db_size: string; total_bytes_used: string; total_bytes_capacity: string; total_unique_bytes: string; total_bytes_free: string; preview_media_bytes: string }
 
-export type StatisticsResponse = { statistics: Statistics | null }
+export type StatisticsHistory = { id: number; date_captured: string; total_bytes: string; file_count: number; object_count: number }
+
+export type StatisticsHistoryArgs = { 
+/**
+ * Only points captured after this date, or the whole history if `null`.
+ */
+since: string | null }
+
+export type StatisticsPreferences = { refresh_interval_mins: number }
+
+export type StatisticsResponse = { statistics: Statistics | null; 
+/**
+ * When the statistics were last refreshed, whether a client asked for it or not.
+ */
+last_refreshed_at: string | null }
+
+/**
+ * What the indexer and the watcher do with the symlinks in a location, see `location.symlink_policy`.
+ */
+/**
+ * The state of a single subsystem during startup.
+ */
+export type SubsystemStatus = { status: "Pending" } | { status: "Ready" } | { status: "Failed"; error: string }
+
+export type SymlinkPolicy = "Skip" | "FollowSafe" | "IndexAsLink"
 
 export type SystemLocations = { desktop: string | null; documents: string | null; downloads: string | null; pictures: string | null; music: string | null; videos: string | null }
 
-export type Tag = { id: number; pub_id: number[]; name: string | null; color: string | null; is_hidden: boolean | null; date_created: string | null; date_modified: string | null }
+export type Tag = { id: number; pub_id: number[]; name: string | null; color: string | null; sort_order: number | null; is_hidden: boolean | null; date_created: string | null; date_modified: string | null }
 
 export type TagCreateArgs = { name: string; color: string }
 
+export type TagReorderArgs = { ordered_ids: number[] }
+
 export type TagUpdateArgs = { id: number; name: string | null; color: string | null }
 
 export type Target = { Object: number } | { FilePath: number }
 
 export type TestingParams = { id: string; path: string }
 
+export type TextEncoding = "Utf8" | "Utf16Le" | "Utf16Be" | "Utf32Le" | "Utf32Be" | "Latin1"
+
 export type TextMatch = { contains: string } | { startsWith: string } | { endsWith: string } | { equals: string }
 
-export type ThumbnailerPreferences = { background_processing_percentage: number }
+export type TextPreview = { 
+/**
+ * `None` for binary files, as decoding them would only give garbage.
+ */
+text: string | null; encoding: TextEncoding | null; binary: boolean; 
+/**
+ * Whether the file goes on after the previewed text.
+ */
+truncated: boolean; 
+/**
+ * What to highlight the text as, from the file's extension.
+ */
+language: string | null }
+
+export type TextPreviewArgs = { file_path_id: number; 
+/**
+ * Capped by the server, so asking for more than it allows returns less.
+ */
+max_bytes: number }
+
+/**
+ * The image format new thumbnails are encoded in, the ones already generated stay in theirs.
+ */
+export type ThumbnailFormat = "WebP" | "Jpeg" | "Png"
+
+/**
+ * Whether the thumbnail of an explorer item is generated, waiting to be, or neither.
+ */
+export type ThumbnailStatus = "None" | "Queued" | "Ready"
+
+export type ThumbnailerPreferences = { background_processing_percentage: number; thumbnail_format?: ThumbnailFormat }
 
-export type UpdateThumbnailerPreferences = { background_processing_percentage: number }
+export type ThumbnailsPrewarmArgs = { location_id: number; sub_path: string }
+
+export type UpdateConfigArgs = { relays: RelayConfig[] | null; enable_hole_punching: boolean | null }
+
+export type UpdateIntegrityPreferences = { max_throughput_mb_per_sec: number }
+
+export type UpdateNetworkPreferences = { metered_connection: boolean }
+
+export type UpdateStatisticsPreferences = { refresh_interval_mins: number }
+
+export type UpdateThumbnailerPreferences = { background_processing_percentage: number; 
+/**
+ * Left as it is when not set, the thumbnails already generated keep their format.
+ */
+thumbnail_format?: ThumbnailFormat | null }
+
+export type VerifyIntegrityArgs = { location_id: number; 
+/**
+ * Only verify the files of these objects, instead of the whole location.
+ */
+object_ids?: number[] | null }
 
 export type VideoMetadata = { duration: number | null; video_codec: string | null; audio_codec: string | null }
 
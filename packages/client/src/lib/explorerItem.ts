@@ -51,7 +51,8 @@ export function getExplorerItemData(data?: ExplorerItem | null): ItemData {
 				itemData.thumbnailKeys = [data.thumbnail];
 			}
 
-			itemData.hasLocalThumbnail = !!data.thumbnail;
+			// queued thumbnails are picked up by the new thumbnail events once they're generated
+			itemData.hasLocalThumbnail = data.thumbnail_status === 'Ready';
 			// handle file path
 			const filePath = getItemFilePath(data);
 			if (filePath) {
@@ -5,7 +5,7 @@ import { QueryClient } from '@tanstack/react-query';
 import { createContext, PropsWithChildren, useContext } from 'react';
 import { match, P } from 'ts-pattern';
 
-import { LibraryArgs, Procedures } from './core';
+import { LibraryArgs, LocationCreateError, Procedures } from './core';
 import { currentLibraryCache } from './hooks';
 
 type NonLibraryProcedure<T extends keyof Procedures> =
@@ -127,3 +127,19 @@ export function extractInfoRSPCError(error: unknown) {
 	if (!(error instanceof AlphaRSPCError)) return null;
 	return error;
 }
+
+/**
+ * `locations.create` sends its common failures as the JSON of a `LocationCreateError` in the
+ * error's message, as rspc errors can't carry data.
+ */
+export function extractLocationCreateError(error: unknown): LocationCreateError | null {
+	const rspcError = extractInfoRSPCError(error);
+	if (!rspcError) return null;
+
+	try {
+		const parsed = JSON.parse(rspcError.message);
+		return typeof parsed?.code === 'string' ? (parsed as LocationCreateError) : null;
+	} catch {
+		return null;
+	}
+}